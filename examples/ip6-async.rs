@@ -0,0 +1,104 @@
+//! Async 6LowPAN Example Application
+//!
+//! Runs a pair of async 6LoWPAN stacks over linked mock MACs,
+//! exchanging fragmented datagrams between these
+//
+// https://github.com/rust-iot/rust-lpwan
+// Copyright 2021 Ryan Kurte
+
+use std::time::{Duration, Instant};
+
+use log::{info, LevelFilter};
+
+use lpwan::asynch::AsyncSixLo;
+use lpwan::prelude::*;
+use lpwan::sixlo::mock::MockMac;
+use lpwan::Ts;
+
+#[derive(Clone, Debug)]
+pub struct SystemTimer {
+    start: Instant,
+}
+
+impl SystemTimer {
+    fn new() -> Self {
+        Self {
+            start: Instant::now(),
+        }
+    }
+}
+
+impl MacTimer for SystemTimer {
+    fn ticks_ms(&self) -> u64 {
+        Instant::now().duration_since(self.start).as_millis() as u64
+    }
+
+    fn ticks_us(&self) -> u64 {
+        Instant::now().duration_since(self.start).as_micros() as u64
+    }
+}
+
+#[tokio::main(flavor = "current_thread")]
+async fn main() -> anyhow::Result<()> {
+    let _ = simplelog::SimpleLogger::init(LevelFilter::Info, simplelog::Config::default());
+
+    info!("Starting lpwan-async");
+
+    let addr_a = MacAddress::Short(PanId(1), ShortAddress(1));
+    let addr_b = MacAddress::Short(PanId(1), ShortAddress(2));
+
+    // Mock MACs share a medium, standing in for the radio
+    let mac_a = MockMac::new(addr_a);
+    let mac_b = mac_a.link(addr_b);
+
+    let timer = SystemTimer::new();
+    let sixlo_a = AsyncSixLo::new(
+        SixLo::<_, _, 127>::new(mac_a, addr_a, SixLoConfig::default()),
+        timer.clone(),
+    );
+    let sixlo_b = AsyncSixLo::new(
+        SixLo::<_, _, 127>::new(mac_b, addr_b, SixLoConfig::default()),
+        timer.clone(),
+    );
+
+    let delay = |ms: Ts| tokio::time::sleep(Duration::from_millis(ms));
+
+    let app = async {
+        let mut buff = [0u8; 512];
+
+        for i in 0..4u8 {
+            let data = [i; 200];
+
+            info!(
+                "TX {:02x} ({} bytes) at {} ms",
+                i,
+                data.len(),
+                timer.ticks_ms()
+            );
+            sixlo_a.transmit(addr_b, &data).await?;
+
+            let (n, info, _h) = sixlo_b.receive(&mut buff).await?;
+            info!(
+                "RX {:02x} ({} bytes, {} fragments) from {:?} (rssi: {}) at {} ms",
+                buff[0],
+                n,
+                info.fragments,
+                info.source,
+                info.rssi_avg,
+                timer.ticks_ms()
+            );
+
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+
+        Ok::<_, SixLoError<_>>(())
+    };
+
+    tokio::select! {
+        r = app => r.map_err(|e| anyhow::anyhow!("Stack error: {:?}", e))?,
+        r = sixlo_a.run(delay, None) => r.map_err(|e| anyhow::anyhow!("Run error: {:?}", e))?,
+        r = sixlo_b.run(delay, None) => r.map_err(|e| anyhow::anyhow!("Run error: {:?}", e))?,
+    }
+
+    Ok(())
+}
@@ -19,6 +19,13 @@ use radio_sx128x::prelude::*;
 use radio_sx128x::Config as Sx128xConfig;
 
 use lpwan::prelude::*;
+#[cfg(feature = "tun")]
+use lpwan::sixlo::tun::{self, TunBridge};
+use lpwan::sixlo::{frag::FragConfig, nd::NdConfig};
+
+/// Maximum sleep between ticks, the radio is polled as the ready (IRQ)
+/// pin is owned by the driver so radio events cannot be signalled
+const MAX_SLEEP_MS: u64 = 5;
 
 #[derive(Debug, StructOpt)]
 struct Options {
@@ -33,6 +40,20 @@ struct Options {
     /// Set PAN ID
     pub pan_id: u16,
 
+    #[cfg(feature = "tun")]
+    #[structopt(long)]
+    /// Bridge datagrams to the host via the named TUN interface,
+    /// this should be configured with an MTU of 1280 bytes
+    pub tun: Option<String>,
+
+    #[structopt(long)]
+    /// Capture radio frames to the provided pcap file
+    pub pcap: Option<String>,
+
+    #[structopt(long, default_value = "60")]
+    /// Interval for logging stack statistics in seconds, 0 to disable
+    pub stats_interval: u64,
+
     #[structopt(long, default_value = "info")]
     /// Configure radio log level
     pub log_level: simplelog::LevelFilter,
@@ -123,28 +144,58 @@ fn main() -> anyhow::Result<()> {
     let address = ExtendedAddress(rand::random::<u64>() % 1000);
     let mac_config = mac_802154::Config {
         pan_coordinator: opts.coordinator,
+        // Send fragments consecutively rather than one per superframe
+        cap_burst: 16,
         ..Default::default()
     };
 
     debug!("Initialising MAC");
 
     let timer = SystemTimer::new();
-    let mac = match mac_802154::Mac::new(address, mac_config, radio, timer.clone()) {
+    let mut mac = match mac_802154::Mac::new(address, mac_config, radio, timer.clone()) {
         Ok(m) => m,
         Err(e) => {
             return Err(anyhow::anyhow!("Error initalising MAC: {:?}", e));
         }
     };
 
+    // Capture frames where requested, writers live for the duration of the application
+    if let Some(path) = &opts.pcap {
+        info!("Capturing frames to {}", path);
+        let w = PcapWriter::create(path)?;
+        mac.set_capture(Some(Box::leak(Box::new(w))));
+    }
+
     debug!("Initialising 6lo");
     let sixlo_cfg = SixLoConfig {
+        // Coordinators act as 6LoWPAN (border) routers
+        nd: NdConfig {
+            router: opts.coordinator,
+            ..Default::default()
+        },
+        frag: FragConfig {
+            tx_window: 4,
+            ..Default::default()
+        },
         ..Default::default()
     };
-    let mut sixlo = SixLo::<_, 127>::new(mac, MacAddress::Extended(PanId(1), address), sixlo_cfg);
+    let mut sixlo =
+        SixLo::<_, _, 127>::new(mac, MacAddress::Extended(PanId(1), address), sixlo_cfg);
+
+    // Bridge datagrams to the host IPv6 stack where configured
+    #[cfg(feature = "tun")]
+    let mut bridge = match &opts.tun {
+        Some(name) => {
+            info!("Bridging to TUN interface {}", name);
+            Some(TunBridge::new(tun::open(name)?))
+        }
+        None => None,
+    };
 
     debug!("Starting loop");
 
     let mut last_tx = timer.ticks_ms();
+    let mut last_stats = timer.ticks_ms();
 
     while running.load(Ordering::SeqCst) {
         let now = timer.ticks_ms();
@@ -157,16 +208,32 @@ fn main() -> anyhow::Result<()> {
             }
         }
 
-        // Check for RX'd packets
-        let mut buff = [0u8; 256];
-        match sixlo.receive(now, &mut buff) {
-            Ok(Some((n, _a, _h))) => {
-                info!("Received data: {:02x?}", &buff[..n]);
+        // Forward datagrams to and from the host where bridging
+        #[cfg(feature = "tun")]
+        let bridged = match bridge.as_mut() {
+            Some(b) => {
+                if let Err(e) = b.poll(now, &mut sixlo) {
+                    error!("TUN bridge error: {:?}", e);
+                }
+                true
             }
-            Err(e) => {
-                error!("MAC RX error: {:?}", e)
+            None => false,
+        };
+        #[cfg(not(feature = "tun"))]
+        let bridged = false;
+
+        // Otherwise check for RX'd packets
+        if !bridged {
+            let mut buff = [0u8; 256];
+            match sixlo.receive(now, &mut buff) {
+                Ok(Some((n, _a, _h))) => {
+                    info!("Received data: {:02x?}", &buff[..n]);
+                }
+                Err(e) => {
+                    error!("MAC RX error: {:?}", e)
+                }
+                _ => (),
             }
-            _ => (),
         }
 
         // Periodic transmit
@@ -182,10 +249,30 @@ fn main() -> anyhow::Result<()> {
             last_tx = now;
         }
 
+        // Periodic statistics
+        if opts.stats_interval > 0 && now > last_stats + opts.stats_interval * 1000 {
+            info!("Stats: {:?}", sixlo.stats());
+            #[cfg(feature = "tun")]
+            {
+                if let Some(b) = bridge.as_ref() {
+                    info!("TUN stats: {:?}", b.stats());
+                }
+            }
+
+            last_stats = now;
+        }
+
         // TODO: rx / tx packets
 
-        // TODO: wait a wee while for the next tick
-        Delay {}.delay_ms(1).unwrap();
+        // Sleep until the next scheduled operation
+        let sleep = sixlo
+            .next_deadline(now)
+            .map(|d| d.saturating_sub(timer.ticks_ms()))
+            .unwrap_or(MAX_SLEEP_MS)
+            .min(MAX_SLEEP_MS);
+        if sleep > 0 {
+            Delay {}.delay_ms(sleep as u32).unwrap();
+        }
     }
 
     Ok(())
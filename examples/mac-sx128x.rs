@@ -20,6 +20,10 @@ use radio_sx128x::Config as Sx128xConfig;
 
 use lpwan::prelude::*;
 
+/// Maximum sleep between ticks, the radio is polled as the ready (IRQ)
+/// pin is owned by the driver so radio events cannot be signalled
+const MAX_SLEEP_MS: u64 = 5;
+
 #[derive(Debug, StructOpt)]
 struct Options {
     #[structopt(flatten)]
@@ -33,6 +37,14 @@ struct Options {
     /// Set PAN ID
     pub pan_id: u16,
 
+    #[structopt(long)]
+    /// Capture radio frames to the provided pcap file
+    pub pcap: Option<String>,
+
+    #[structopt(long, default_value = "60")]
+    /// Interval for logging stack statistics in seconds, 0 to disable
+    pub stats_interval: u64,
+
     #[structopt(long, default_value = "info")]
     /// Configure radio log level
     pub log_level: simplelog::LevelFilter,
@@ -136,9 +148,17 @@ fn main() -> anyhow::Result<()> {
         }
     };
 
+    // Capture frames where requested, writers live for the duration of the application
+    if let Some(path) = &opts.pcap {
+        info!("Capturing frames to {}", path);
+        let w = PcapWriter::create(path)?;
+        mac.set_capture(Some(Box::leak(Box::new(w))));
+    }
+
     debug!("Starting loop");
 
     let mut last_tx = timer.ticks_ms();
+    let mut last_stats = timer.ticks_ms();
 
     while running.load(Ordering::SeqCst) {
         let now = timer.ticks_ms();
@@ -176,10 +196,23 @@ fn main() -> anyhow::Result<()> {
             last_tx = now;
         }
 
+        // Periodic statistics
+        if opts.stats_interval > 0 && now > last_stats + opts.stats_interval * 1000 {
+            info!("Stats: {:?}", Mac::stats(&mac));
+            last_stats = now;
+        }
+
         // TODO: rx / tx packets
 
-        // TODO: wait a wee while for the next tick
-        Delay {}.delay_ms(1).unwrap();
+        // Sleep until the next scheduled operation
+        let sleep = mac
+            .next_deadline()
+            .map(|d| d.saturating_sub(timer.ticks_ms()))
+            .unwrap_or(MAX_SLEEP_MS)
+            .min(MAX_SLEEP_MS);
+        if sleep > 0 {
+            Delay {}.delay_ms(sleep as u32).unwrap();
+        }
     }
 
     Ok(())
@@ -0,0 +1,96 @@
+//! 6LowPAN UDP Example Application
+//!
+//! Runs a pair of 6LoWPAN stacks over linked mock MACs, exchanging
+//! UDP datagrams with compressed (IPHC + NHC) headers between these
+//
+// https://github.com/rust-iot/rust-lpwan
+// Copyright 2021 Ryan Kurte
+
+use log::{info, LevelFilter};
+
+use lpwan::prelude::*;
+use lpwan::sixlo::mock::MockMac;
+
+/// CoAP port, carried inline
+const SERVER_PORT: u16 = 5683;
+/// Client port, compressed to 4 bits
+const CLIENT_PORT: u16 = 0xf0b1;
+
+fn main() -> anyhow::Result<()> {
+    let _ = simplelog::SimpleLogger::init(LevelFilter::Info, simplelog::Config::default());
+
+    info!("Starting udp-mock");
+
+    let addr_a = MacAddress::Short(PanId(1), ShortAddress(1));
+    let addr_b = MacAddress::Short(PanId(1), ShortAddress(2));
+
+    // Mock MACs share a medium, standing in for the radio
+    let mac_a = MockMac::new(addr_a);
+    let mac_b = mac_a.link(addr_b);
+
+    let mut client = SixLo::<_, _, 127>::new(mac_a, addr_a, SixLoConfig::default());
+    let mut server = SixLo::<_, _, 127>::new(mac_b, addr_b, SixLoConfig::default());
+
+    let server_ip = server.link_local_addr();
+    let mut buff = [0u8; 256];
+
+    // Send a request from the client to the server
+    client
+        .transmit_udp(0, server_ip, CLIENT_PORT, SERVER_PORT, b"hello")
+        .map_err(|e| anyhow::anyhow!("Transmit error: {:?}", e))?;
+
+    for now in 0..20 {
+        client
+            .tick(now)
+            .map_err(|e| anyhow::anyhow!("Client error: {:?}", e))?;
+        server
+            .tick(now)
+            .map_err(|e| anyhow::anyhow!("Server error: {:?}", e))?;
+
+        // Echo requests received by the server
+        if let Some((n, _source, h)) = server
+            .receive(now, &mut buff)
+            .map_err(|e| anyhow::anyhow!("Server error: {:?}", e))?
+        {
+            let (ip, udp) = match (h.iphc, h.udp) {
+                (Some(ip), Some(udp)) if udp.dst_port == SERVER_PORT => (ip, udp),
+                _ => continue,
+            };
+
+            info!(
+                "Server RX {:?} ({} bytes) from port {} (checksum: {:04x?})",
+                core::str::from_utf8(&buff[..n]),
+                n,
+                udp.src_port,
+                udp.checksum
+            );
+
+            let resp = buff[..n].to_vec();
+            server
+                .transmit_udp(now, ip.src, SERVER_PORT, udp.src_port, &resp)
+                .map_err(|e| anyhow::anyhow!("Transmit error: {:?}", e))?;
+        }
+
+        // Wait for the response at the client
+        if let Some((n, _source, h)) = client
+            .receive(now, &mut buff)
+            .map_err(|e| anyhow::anyhow!("Client error: {:?}", e))?
+        {
+            if let Some(udp) = h.udp {
+                info!(
+                    "Client RX {:?} ({} bytes) from port {}",
+                    core::str::from_utf8(&buff[..n]),
+                    n,
+                    udp.src_port
+                );
+
+                info!("Client stats: {:?}", client.stats());
+                info!("Server stats: {:?}", server.stats());
+
+                return Ok(());
+            }
+        }
+    }
+
+    Err(anyhow::anyhow!("No response received"))
+}
@@ -0,0 +1,31 @@
+//! Fuzz 802.15.4 frame and 6LoWPAN header decoding with arbitrary radio input,
+//! run with `cargo fuzz run decode` from the repository root
+//
+// https://github.com/rust-iot/rust-lpwan
+// Copyright 2021 Ryan Kurte
+
+#![no_main]
+
+use ieee802154::mac::{Address, PanId, ShortAddress};
+use libfuzzer_sys::fuzz_target;
+
+use lpwan::mac_802154::Packet;
+use lpwan::sixlo::headers::Header;
+
+fuzz_target!(|data: &[u8]| {
+    // Raw frames, with and without the FCS
+    let _ = Packet::decode(data, true);
+    let packet = Packet::decode(data, false);
+
+    // 6LoWPAN headers from the frame payload where this decodes,
+    // otherwise directly from the input
+    let (src, dst, payload) = match &packet {
+        Ok(p) => (p.header.source, p.header.destination, p.payload()),
+        Err(_) => (
+            Address::Short(PanId(1), ShortAddress(1)),
+            Address::Short(PanId(1), ShortAddress(2)),
+            data,
+        ),
+    };
+    let _ = Header::decode(payload, &src, &dst);
+});
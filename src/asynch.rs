@@ -0,0 +1,596 @@
+//! Async Stack Wrappers
+//!
+//! [`AsyncMac`] and [`AsyncSixLo`] drive the existing poll-based [`Mac`] and [`SixLo`]
+//! state machines from a `run` future, which ticks the stack then sleeps until the
+//! next deadline or a radio event. Transmit and receive are exposed as futures that
+//! resolve once the stack is able to accept or has data available (along with
+//! transmit outcomes for [`AsyncMac`]), for use with async executors such as embassy.
+//!
+//! There are no executor dependencies, sleeping is provided by an [`AsyncDelay`]
+//! (implemented for closures returning futures, so `embassy_time::Timer::after` or
+//! `tokio::time::sleep` can be adapted), with radio events signalled via a
+//! [`RadioSignal`] from the radio interrupt handler.
+//
+// https://github.com/rust-iot/rust-lpwan
+// Copyright 2021 Ryan Kurte
+
+use core::cell::RefCell;
+use core::future::Future;
+use core::pin::Pin;
+use core::sync::atomic::{AtomicBool, Ordering};
+use core::task::{Context, Poll};
+
+use futures::future::{poll_fn, select, Either};
+use futures::pin_mut;
+use futures::task::AtomicWaker;
+use ieee802154::mac::Address as MacAddress;
+
+use crate::log::{warn, FmtError};
+use crate::sixlo::frag::{DEFAULT_RX_SLOTS, DEFAULT_TX_SLOTS};
+use crate::sixlo::{headers::Header, AddressOps, DatagramInfo, SixLo, SixLoError};
+use crate::timer::Timer;
+use crate::{Mac, MacError, RxInfo, Ts, TxHandle, TxStatus};
+
+/// Maximum sleep between ticks where radio events are not signalled,
+/// as the radio must then be polled
+pub const DEFAULT_POLL_MS: Ts = 1;
+
+/// Async delay, used by the run loop to sleep until the next deadline
+pub trait AsyncDelay {
+    type Delay: Future<Output = ()>;
+
+    /// Create a future resolving after the provided number of milliseconds
+    fn delay_ms(&mut self, ms: Ts) -> Self::Delay;
+}
+
+impl<F, D> AsyncDelay for F
+where
+    F: FnMut(Ts) -> D,
+    D: Future<Output = ()>,
+{
+    type Delay = D;
+
+    fn delay_ms(&mut self, ms: Ts) -> Self::Delay {
+        (self)(ms)
+    }
+}
+
+/// Radio event signal, raised from the radio interrupt handler to wake the run loop.
+/// This is `const` constructable so may be used as a `static`
+pub struct RadioSignal {
+    pending: AtomicBool,
+    waker: AtomicWaker,
+}
+
+impl Default for RadioSignal {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RadioSignal {
+    pub const fn new() -> Self {
+        Self {
+            pending: AtomicBool::new(false),
+            waker: AtomicWaker::new(),
+        }
+    }
+
+    /// Signal a radio event, safe to call from interrupt context
+    pub fn signal(&self) {
+        self.pending.store(true, Ordering::Release);
+        self.waker.wake();
+    }
+
+    /// Check for (and clear) a pending radio event
+    pub fn take(&self) -> bool {
+        let pending = self.pending.load(Ordering::Acquire);
+        if pending {
+            self.pending.store(false, Ordering::Release);
+        }
+        pending
+    }
+
+    /// Wait for a radio event
+    pub async fn wait(&self) {
+        poll_fn(|cx| {
+            self.waker.register(cx.waker());
+
+            match self.take() {
+                true => Poll::Ready(()),
+                false => Poll::Pending,
+            }
+        })
+        .await
+    }
+}
+
+/// Yield to the executor once, so other tasks may progress where
+/// deadlines are already due
+async fn yield_now() {
+    struct Yield(bool);
+
+    impl Future for Yield {
+        type Output = ();
+
+        fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+            if self.0 {
+                return Poll::Ready(());
+            }
+
+            self.0 = true;
+            cx.waker().wake_by_ref();
+            Poll::Pending
+        }
+    }
+
+    Yield(false).await
+}
+
+/// Sleep until the provided deadline or a radio event,
+/// returning true if woken by a radio event
+async fn sleep<D: AsyncDelay>(
+    delay: &mut D,
+    now_ms: Ts,
+    deadline: Option<Ts>,
+    signal: Option<&RadioSignal>,
+) -> bool {
+    yield_now().await;
+
+    let signal = match signal {
+        Some(s) => s,
+        None => {
+            // Without radio events the radio must be polled
+            let ms = deadline
+                .map(|d| d.saturating_sub(now_ms))
+                .unwrap_or(DEFAULT_POLL_MS)
+                .min(DEFAULT_POLL_MS);
+            delay.delay_ms(ms).await;
+            return false;
+        }
+    };
+
+    // Skip sleeping for events raised since the last wait
+    if signal.take() {
+        return true;
+    }
+
+    let wait = signal.wait();
+    pin_mut!(wait);
+
+    match deadline {
+        Some(d) => {
+            let d = delay.delay_ms(d.saturating_sub(now_ms));
+            pin_mut!(d);
+
+            matches!(select(wait, d).await, Either::Left(_))
+        }
+        None => {
+            wait.await;
+            true
+        }
+    }
+}
+
+/// Async wrapper for [`Mac`] implementations
+pub struct AsyncMac<M, T> {
+    mac: RefCell<M>,
+    timer: T,
+    rx_waker: AtomicWaker,
+    tx_waker: AtomicWaker,
+}
+
+impl<M, T> AsyncMac<M, T>
+where
+    M: Mac,
+    <M as Mac>::Error: FmtError,
+    T: Timer,
+{
+    /// Create a new async MAC wrapper, using the provided timer for the run loop
+    pub fn new(mac: M, timer: T) -> Self {
+        Self {
+            mac: RefCell::new(mac),
+            timer,
+            rx_waker: AtomicWaker::new(),
+            tx_waker: AtomicWaker::new(),
+        }
+    }
+
+    /// Access the underlying MAC
+    pub fn with<R>(&self, f: impl FnOnce(&mut M) -> R) -> R {
+        f(&mut self.mac.borrow_mut())
+    }
+
+    /// Return the underlying MAC
+    pub fn into_inner(self) -> M {
+        self.mac.into_inner()
+    }
+
+    /// Run the MAC, ticking on each deadline or radio event.
+    /// Radio events are signalled via the provided [`RadioSignal`], otherwise
+    /// the radio is polled every [`DEFAULT_POLL_MS`].
+    /// Tick errors are logged and ticking continues, this only returns on
+    /// [`fatal`](MacError::fatal) errors
+    pub async fn run<D: AsyncDelay>(
+        &self,
+        mut delay: D,
+        signal: Option<&RadioSignal>,
+    ) -> Result<(), <M as Mac>::Error> {
+        loop {
+            let deadline = {
+                let mut mac = self.mac.borrow_mut();
+                match mac.tick() {
+                    Err(e) if e.fatal() => return Err(e),
+                    Err(e) => warn!("MAC tick error: {:?}", e),
+                    Ok(_) => (),
+                }
+                mac.next_deadline()
+            };
+
+            // Wake pending operations to re-check the MAC
+            self.rx_waker.wake();
+            self.tx_waker.wake();
+
+            let now_ms = self.timer.ticks_ms();
+            if sleep(&mut delay, now_ms, deadline, signal).await {
+                self.mac.borrow_mut().radio_event();
+            }
+        }
+    }
+
+    /// Transmit a packet, waiting for transmit buffer space where required
+    pub async fn transmit(
+        &self,
+        dest: MacAddress,
+        data: &[u8],
+        ack: bool,
+    ) -> Result<TxHandle, <M as Mac>::Error> {
+        poll_fn(|cx| {
+            self.tx_waker.register(cx.waker());
+
+            let mut mac = self.mac.borrow_mut();
+            match mac.can_transmit() {
+                Ok(true) => (),
+                Ok(false) => return Poll::Pending,
+                Err(e) => return Poll::Ready(Err(e)),
+            }
+
+            match mac.transmit(dest, data, ack) {
+                Err(e) if e.queue_full() => Poll::Pending,
+                r => Poll::Ready(r),
+            }
+        })
+        .await
+    }
+
+    /// Fetch the next transmit outcome, waiting until one is available
+    pub async fn tx_status(&self) -> Result<(TxHandle, TxStatus), <M as Mac>::Error> {
+        poll_fn(|cx| {
+            self.tx_waker.register(cx.waker());
+
+            match self.mac.borrow_mut().tx_status() {
+                Ok(Some(r)) => Poll::Ready(Ok(r)),
+                Ok(None) => Poll::Pending,
+                Err(e) => Poll::Ready(Err(e)),
+            }
+        })
+        .await
+    }
+
+    /// Receive a packet, waiting until one is available
+    pub async fn receive(&self, data: &mut [u8]) -> Result<(usize, RxInfo), <M as Mac>::Error> {
+        poll_fn(|cx| {
+            self.rx_waker.register(cx.waker());
+
+            match self.mac.borrow_mut().receive(data) {
+                Ok(Some(r)) => Poll::Ready(Ok(r)),
+                Ok(None) => Poll::Pending,
+                Err(e) => Poll::Ready(Err(e)),
+            }
+        })
+        .await
+    }
+}
+
+/// Async wrapper for the [`SixLo`] stack
+pub struct AsyncSixLo<
+    M,
+    A,
+    T,
+    const MAX_PAYLOAD: usize,
+    const TX_SLOTS: usize = DEFAULT_TX_SLOTS,
+    const RX_SLOTS: usize = DEFAULT_RX_SLOTS,
+> {
+    sixlo: RefCell<SixLo<M, A, MAX_PAYLOAD, TX_SLOTS, RX_SLOTS>>,
+    timer: T,
+    rx_waker: AtomicWaker,
+    tx_waker: AtomicWaker,
+}
+
+impl<M, A, T, const MAX_PAYLOAD: usize, const TX_SLOTS: usize, const RX_SLOTS: usize>
+    AsyncSixLo<M, A, T, MAX_PAYLOAD, TX_SLOTS, RX_SLOTS>
+where
+    M: Mac<A>,
+    A: AddressOps,
+    <M as Mac<A>>::Error: FmtError,
+    T: Timer,
+{
+    /// Create a new async 6LoWPAN wrapper, using the provided timer for the run loop
+    pub fn new(sixlo: SixLo<M, A, MAX_PAYLOAD, TX_SLOTS, RX_SLOTS>, timer: T) -> Self {
+        Self {
+            sixlo: RefCell::new(sixlo),
+            timer,
+            rx_waker: AtomicWaker::new(),
+            tx_waker: AtomicWaker::new(),
+        }
+    }
+
+    /// Access the underlying stack
+    pub fn with<R>(
+        &self,
+        f: impl FnOnce(&mut SixLo<M, A, MAX_PAYLOAD, TX_SLOTS, RX_SLOTS>) -> R,
+    ) -> R {
+        f(&mut self.sixlo.borrow_mut())
+    }
+
+    /// Return the underlying stack
+    pub fn into_inner(self) -> SixLo<M, A, MAX_PAYLOAD, TX_SLOTS, RX_SLOTS> {
+        self.sixlo.into_inner()
+    }
+
+    /// Run the stack, ticking on each deadline or radio event.
+    /// Radio events are signalled via the provided [`RadioSignal`], otherwise
+    /// the radio is polled every [`DEFAULT_POLL_MS`].
+    /// Tick errors are logged and ticking continues, this only returns on
+    /// [`fatal`](MacError::fatal) errors
+    pub async fn run<D: AsyncDelay>(
+        &self,
+        mut delay: D,
+        signal: Option<&RadioSignal>,
+    ) -> Result<(), SixLoError<<M as Mac<A>>::Error>> {
+        loop {
+            let now_ms = self.timer.ticks_ms();
+
+            let deadline = {
+                let mut sixlo = self.sixlo.borrow_mut();
+                match sixlo.tick(now_ms) {
+                    Err(e) if e.fatal() => return Err(e),
+                    Err(e) => warn!("6LoWPAN tick error: {:?}", e),
+                    Ok(_) => (),
+                }
+                sixlo.next_deadline(now_ms)
+            };
+
+            // Wake pending operations to re-check the stack
+            self.rx_waker.wake();
+            self.tx_waker.wake();
+
+            let now_ms = self.timer.ticks_ms();
+            if sleep(&mut delay, now_ms, deadline, signal).await {
+                self.sixlo.borrow_mut().radio_event();
+            }
+        }
+    }
+
+    /// Transmit a datagram, waiting for fragmentation buffer or MAC queue space where required
+    pub async fn transmit(
+        &self,
+        dest: A,
+        data: &[u8],
+    ) -> Result<(), SixLoError<<M as Mac<A>>::Error>> {
+        poll_fn(|cx| {
+            self.tx_waker.register(cx.waker());
+
+            let now_ms = self.timer.ticks_ms();
+            match self.sixlo.borrow_mut().transmit(now_ms, dest.clone(), data) {
+                Err(SixLoError::NoTxSlots) => Poll::Pending,
+                Err(SixLoError::Mac(e)) if e.queue_full() => Poll::Pending,
+                r => Poll::Ready(r),
+            }
+        })
+        .await
+    }
+
+    /// Receive a datagram, waiting until one is available
+    pub async fn receive(
+        &self,
+        data: &mut [u8],
+    ) -> Result<(usize, DatagramInfo<A>, Header), SixLoError<<M as Mac<A>>::Error>> {
+        poll_fn(|cx| {
+            self.rx_waker.register(cx.waker());
+
+            let now_ms = self.timer.ticks_ms();
+            match self.sixlo.borrow_mut().receive(now_ms, data) {
+                Ok(Some(r)) => Poll::Ready(Ok(r)),
+                Ok(None) => Poll::Pending,
+                Err(e) => Poll::Ready(Err(e)),
+            }
+        })
+        .await
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use futures::executor::block_on;
+    use futures::future::{join, ready, select};
+    use futures::task::noop_waker;
+    use ieee802154::mac::{ExtendedAddress, PanId, ShortAddress};
+
+    use super::*;
+    use crate::mac_802154::{self, Config};
+    use crate::sim::VirtualChannel;
+    use crate::sixlo::{
+        mock::{MockError, MockMac},
+        SixLoConfig, SixLoError,
+    };
+    use crate::timer::mock::MockTimer;
+    use crate::MacState;
+
+    #[test]
+    fn radio_signal() {
+        let signal = RadioSignal::new();
+        let waker = noop_waker();
+        let mut cx = Context::from_waker(&waker);
+
+        let wait = signal.wait();
+        pin_mut!(wait);
+        assert_eq!(wait.as_mut().poll(&mut cx), Poll::Pending);
+
+        // Signals resolve pending waits, and are cleared on completion
+        signal.signal();
+        assert_eq!(wait.as_mut().poll(&mut cx), Poll::Ready(()));
+        assert!(!signal.take());
+    }
+
+    #[test]
+    fn async_sixlo() {
+        let addr_a = MacAddress::Short(PanId(1), ShortAddress(1));
+        let addr_b = MacAddress::Short(PanId(1), ShortAddress(2));
+
+        let mac_a = MockMac::new(addr_a).with_capacity(2);
+        let mac_b = mac_a.link(addr_b);
+
+        let timer = MockTimer::new();
+
+        let sixlo_a = AsyncSixLo::new(
+            SixLo::<_, _, 127>::new(mac_a, addr_a, SixLoConfig::default()),
+            timer.clone(),
+        );
+        let sixlo_b = AsyncSixLo::new(
+            SixLo::<_, _, 127>::new(mac_b, addr_b, SixLoConfig::default()),
+            timer.clone(),
+        );
+
+        // Delays advance the mock timer
+        let delay = |ms: Ts| {
+            let mut t = timer.clone();
+            for _ in 0..ms.max(1) {
+                t.inc();
+            }
+            ready(())
+        };
+
+        let tx = [0xab; 300];
+        let mut buff = [0u8; 512];
+
+        let (n, info, _h) = {
+            let app = async {
+                sixlo_a.transmit(addr_b, &tx).await.unwrap();
+                sixlo_b.receive(&mut buff).await.unwrap()
+            };
+            let run = join(sixlo_a.run(delay, None), sixlo_b.run(delay, None));
+
+            pin_mut!(app, run);
+            match block_on(select(app, run)) {
+                Either::Left((r, _)) => r,
+                Either::Right(((a, b), _)) => panic!("Run exited: {:?} {:?}", a, b),
+            }
+        };
+
+        assert_eq!(info.source, addr_a);
+        assert_eq!(&buff[..n], &tx[..]);
+    }
+
+    #[test]
+    fn async_sixlo_tick_errors() {
+        let addr = MacAddress::Short(PanId(1), ShortAddress(1));
+        let timer = MockTimer::new();
+
+        // Transient errors are logged and skipped, fatal errors end the run
+        let mut mac = MockMac::new(addr);
+        mac.fail_tick(MockError::Tick);
+        mac.fail_tick(MockError::Tick);
+        mac.fail_tick(MockError::Wedged);
+
+        let sixlo = AsyncSixLo::new(
+            SixLo::<_, _, 127>::new(mac, addr, SixLoConfig::default()),
+            timer.clone(),
+        );
+
+        let delay = |_ms: Ts| {
+            timer.clone().inc();
+            ready(())
+        };
+
+        let res = block_on(sixlo.run(delay, None));
+        assert_eq!(res, Err(SixLoError::Mac(MockError::Wedged)));
+    }
+
+    #[test]
+    fn async_mac_ack() {
+        let mut timer = MockTimer::new();
+        let channel = VirtualChannel::new(timer.clone());
+
+        let coord_cfg = Config {
+            pan_coordinator: true,
+            ..Default::default()
+        };
+        let mut coord = mac_802154::Mac::new(
+            ExtendedAddress(0xc0),
+            coord_cfg,
+            channel.attach(),
+            timer.clone(),
+        )
+        .unwrap();
+        let mut dev = mac_802154::Mac::new(
+            ExtendedAddress(0xa0),
+            Config::default(),
+            channel.attach(),
+            timer.clone(),
+        )
+        .unwrap();
+        let coord_addr = coord.addr();
+
+        // Associate prior to running asynchronously
+        while dev.state() != Ok(MacState::Associated(coord_addr)) {
+            assert!(timer.val() < 20_000, "Association timeout");
+
+            timer.inc();
+            coord.tick().unwrap();
+            dev.tick().unwrap();
+        }
+
+        let coord = AsyncMac::new(coord, timer.clone());
+        let dev = AsyncMac::new(dev, timer.clone());
+
+        // Delays advance the mock timer
+        let delay = |ms: Ts| {
+            let mut t = timer.clone();
+            for _ in 0..ms.max(1) {
+                t.inc();
+            }
+            assert!(t.val() < 40_000, "Transmit timeout");
+            ready(())
+        };
+
+        let tx = [0x11, 0x22, 0x33];
+        let mut buff = [0u8; 128];
+
+        // Data is received by the coordinator and the acknowledgement reported
+        let (n, info, status) = {
+            let app = async {
+                let handle = dev.transmit(coord_addr, &tx, true).await.unwrap();
+                let (n, info) = coord.receive(&mut buff).await.unwrap();
+
+                loop {
+                    match dev.tx_status().await.unwrap() {
+                        (h, s) if h == handle => break (n, info, s),
+                        _ => (),
+                    }
+                }
+            };
+            let run = join(dev.run(delay, None), coord.run(delay, None));
+
+            pin_mut!(app, run);
+            match block_on(select(app, run)) {
+                Either::Left((r, _)) => r,
+                Either::Right(((a, b), _)) => panic!("Run exited: {:?} {:?}", a, b),
+            }
+        };
+
+        assert_eq!(status, TxStatus::Acked);
+        assert_eq!(info.source, dev.with(|m| m.addr()));
+        assert_eq!(&buff[..n], &tx);
+    }
+}
@@ -1,4 +1,21 @@
 //! Radio base - manages radio states
+//!
+//! The radio is polled for receive and transmit completion on each [`Base::tick`] by
+//! default. Where radio interrupts are available the base may instead be set event
+//! driven via [`Base::set_event_driven`], with radio events signalled by
+//! [`Base::event`] so the radio is only polled following an event, or on expiry of
+//! an optional fallback timeout to recover from missed interrupts.
+//!
+//! Radio errors while polling are recovered by resetting the radio to idle and
+//! re-entering the previous mode, with [`CoreError::RadioWedged`] reported after
+//! the configured number of consecutive failures (see [`Base::set_max_failures`])
+//! so the radio may be power-cycled. Failures are observable via [`Base::status`].
+//!
+//! Frames, bytes, and time spent transmitting and receiving are accumulated from
+//! radio state transitions for collection via [`Base::stats`].
+//!
+//! Frames given to and received from the radio may be captured by setting a
+//! [`PcapSink`] via [`Base::set_capture`].
 //
 // https://github.com/rust-iot/rust-lpwan
 // Copyright 2021 Ryan Kurte
@@ -6,14 +23,85 @@ use core::{fmt::Debug, ops::Deref};
 
 use radio::{RadioState, Receive, ReceiveInfo, State};
 
-use crate::log::{debug, trace};
+#[cfg(feature = "trace-frames")]
+use crate::log::info;
+use crate::log::{debug, error, trace, warn, HexSlice};
 
+use crate::pcap::{Capture, Direction, PcapSink};
 use crate::{error::CoreError, Radio, RawPacket};
 
+/// Maximum number of bytes included in frame traces
+#[cfg(feature = "trace-frames")]
+pub const TRACE_FRAME_BYTES: usize = 32;
+
 #[derive(Debug, Clone, PartialEq)]
 pub struct Base<R> {
     radio: R,
     state: BaseState,
+
+    event_driven: bool,
+    poll_timeout: Option<u64>,
+    pending: bool,
+    last_poll: u64,
+
+    stats: BaseStats,
+    /// Time of the last radio state transition
+    state_since: u64,
+
+    status: BaseStatus,
+    max_failures: u32,
+    /// Mode to be resumed where a radio reset failed, retried on the next tick
+    resume: Option<BaseState>,
+
+    capture: Capture,
+}
+
+/// Default number of consecutive radio failures prior to reporting
+/// [`CoreError::RadioWedged`]
+pub const DEFAULT_MAX_FAILURES: u32 = 3;
+
+/// Radio operation, recorded on failure in [`BaseStatus`]
+#[derive(Debug, Copy, Clone, PartialEq, strum::Display)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum RadioOp {
+    SetState,
+    SetChannel,
+    StartTransmit,
+    CheckTransmit,
+    StartReceive,
+    CheckReceive,
+    GetReceived,
+    PollRssi,
+}
+
+/// Radio error recovery status, see [`Base::status`]
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct BaseStatus {
+    /// Consecutive radio failures, cleared on a successful poll
+    pub failures: u32,
+    /// Radio resets completed following failures
+    pub recoveries: u32,
+    /// Operation and time (ms) of the most recent radio error
+    pub last_error: Option<(RadioOp, u64)>,
+}
+
+/// Radio statistics, accumulated by the [`Base`]
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct BaseStats {
+    /// Frames transmitted, including acknowledgements and beacons
+    pub tx_frames: u32,
+    /// Bytes transmitted
+    pub tx_bytes: u64,
+    /// Frames received
+    pub rx_frames: u32,
+    /// Bytes received
+    pub rx_bytes: u64,
+    /// Time spent transmitting in ms
+    pub tx_ms: u64,
+    /// Time spent listening or receiving in ms
+    pub rx_ms: u64,
 }
 
 #[derive(Debug, Copy, Clone, PartialEq, strum::Display)]
@@ -26,6 +114,18 @@ pub enum BaseState {
     Sleeping,
 }
 
+/// Radio event hint, signalled via [`Base::event`] (typically from the radio IRQ)
+#[derive(Debug, Copy, Clone, PartialEq, strum::Display)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum RadioEvent {
+    /// Radio interrupt of unknown cause
+    Irq,
+    /// Transmission complete
+    TxDone,
+    /// Reception complete
+    RxDone,
+}
+
 impl<R> Base<R>
 where
     R: Radio,
@@ -38,6 +138,20 @@ where
         let s = Self {
             radio,
             state: BaseState::Idle,
+
+            event_driven: false,
+            poll_timeout: None,
+            pending: true,
+            last_poll: 0,
+
+            stats: BaseStats::default(),
+            state_since: 0,
+
+            status: BaseStatus::default(),
+            max_failures: DEFAULT_MAX_FAILURES,
+            resume: None,
+
+            capture: Capture::default(),
         };
 
         Ok(s)
@@ -48,6 +162,65 @@ where
         self.state
     }
 
+    /// Fetch radio statistics, including time spent in the current state
+    pub fn stats(&self, now: u64) -> BaseStats {
+        let mut stats = self.stats.clone();
+        Self::accumulate(&mut stats, self.state, now.saturating_sub(self.state_since));
+        stats
+    }
+
+    /// Reset radio statistics
+    pub fn reset_stats(&mut self, now: u64) {
+        self.stats = BaseStats::default();
+        self.state_since = now;
+    }
+
+    /// Fetch radio error recovery status
+    pub fn status(&self) -> &BaseStatus {
+        &self.status
+    }
+
+    /// Set (or clear) the sink for capture of transmitted and received frames
+    pub fn set_capture(&mut self, sink: Option<&'static mut dyn PcapSink>) {
+        self.capture.set(sink);
+    }
+
+    /// Set the number of consecutive radio failures after which
+    /// [`CoreError::RadioWedged`] is reported
+    pub fn set_max_failures(&mut self, max_failures: u32) {
+        self.max_failures = max_failures;
+    }
+
+    /// Set whether the radio is only polled following radio events signalled via
+    /// [`Base::event`], or on expiry of the (optional) poll timeout since the last poll.
+    /// Otherwise the radio is polled on each [`Base::tick`]
+    pub fn set_event_driven(&mut self, event_driven: bool, poll_timeout: Option<u64>) {
+        self.event_driven = event_driven;
+        self.poll_timeout = poll_timeout;
+    }
+
+    /// Signal a radio event, the radio is then polled on the next [`Base::tick`]
+    pub fn event(&mut self, now: u64, hint: RadioEvent) {
+        trace!("Radio event {} at {} ms, state: {}", hint, now, self.state);
+
+        self.pending = true;
+    }
+
+    /// Check whether a radio event is awaiting the next [`Base::tick`],
+    /// always false where the base is not event driven
+    pub fn is_pending(&self) -> bool {
+        self.event_driven && self.pending
+    }
+
+    /// Fetch the time at which the radio is next polled without a radio event,
+    /// where the base is event driven with a poll timeout
+    pub fn poll_deadline(&self) -> Option<u64> {
+        match (self.event_driven, self.poll_timeout) {
+            (true, Some(t)) => Some(self.last_poll + t),
+            _ => None,
+        }
+    }
+
     /// Check if the MAC radio is busy
     pub fn is_busy(&self) -> bool {
         use BaseState::*;
@@ -58,7 +231,7 @@ where
         }
     }
 
-    pub fn sleep(&mut self) -> Result<(), CoreError<<R as Radio>::Error>> {
+    pub fn sleep(&mut self, now: u64) -> Result<(), CoreError<<R as Radio>::Error>> {
         // Check we're not busy
         if self.is_busy() {
             return Err(CoreError::Busy);
@@ -68,8 +241,8 @@ where
 
         self.radio
             .set_state(<R as State>::State::sleep())
-            .map_err(CoreError::Radio)?;
-        self.state = BaseState::Sleeping;
+            .map_err(|e| self.fault(now, RadioOp::SetState, e))?;
+        self.set_state(now, BaseState::Sleeping);
 
         Ok(())
     }
@@ -86,16 +259,20 @@ where
         }
 
         debug!("Transmit {} bytes at {} ms", data.len(), now);
-        #[cfg(not(feature = "defmt"))]
-        trace!("{:02x?}", data);
-        #[cfg(feature = "defmt")]
-        trace!("{:?}", data);
+        trace!("{}", HexSlice(data));
+        #[cfg(feature = "trace-frames")]
+        trace_frame("TX", now, data, None);
+        self.capture.capture(Direction::Tx, now * 1000, data);
 
         // Start the transmission
-        self.radio.start_transmit(&data).map_err(CoreError::Radio)?;
+        self.radio
+            .start_transmit(&data)
+            .map_err(|e| self.fault(now, RadioOp::StartTransmit, e))?;
 
         // Update MAC state
-        self.state = BaseState::Transmitting;
+        self.set_state(now, BaseState::Transmitting);
+        self.stats.tx_frames = self.stats.tx_frames.saturating_add(1);
+        self.stats.tx_bytes = self.stats.tx_bytes.saturating_add(data.len() as u64);
 
         Ok(())
     }
@@ -108,52 +285,190 @@ where
         }
 
         debug!("Start receive at {} ms", now);
-        self.radio.start_receive().map_err(CoreError::Radio)?;
-        self.state = BaseState::Listening;
+        self.radio
+            .start_receive()
+            .map_err(|e| self.fault(now, RadioOp::StartReceive, e))?;
+        self.set_state(now, BaseState::Listening);
+
+        Ok(())
+    }
+
+    /// Set the radio channel, this will fail if the radio is busy.
+    /// Receive is restarted on the new channel where the radio is listening
+    #[cfg(feature = "channels")]
+    pub fn set_channel(
+        &mut self,
+        now: u64,
+        channel: &<R as Radio>::Channel,
+    ) -> Result<(), CoreError<<R as Radio>::Error>> {
+        // Check we're not busy
+        if self.is_busy() {
+            return Err(CoreError::Busy);
+        }
+
+        debug!("Set channel at {} ms", now);
+        self.radio
+            .set_channel(channel)
+            .map_err(|e| self.fault(now, RadioOp::SetChannel, e))?;
+
+        if self.state == BaseState::Listening {
+            self.radio
+                .start_receive()
+                .map_err(|e| self.fault(now, RadioOp::StartReceive, e))?;
+        }
+
+        Ok(())
+    }
+
+    /// Set the radio channel, without the `channels` feature the radio remains
+    /// on a fixed channel so this only checks the radio is not busy
+    #[cfg(not(feature = "channels"))]
+    pub fn set_channel(
+        &mut self,
+        now: u64,
+        _channel: &<R as Radio>::Channel,
+    ) -> Result<(), CoreError<<R as Radio>::Error>> {
+        // Check we're not busy
+        if self.is_busy() {
+            return Err(CoreError::Busy);
+        }
+
+        trace!("Fixed channel, ignoring channel change at {} ms", now);
 
         Ok(())
     }
 
     /// Fetch the channel RSSI
-    pub fn rssi(&mut self, _now: u64) -> Result<i16, CoreError<<R as Radio>::Error>> {
+    pub fn rssi(&mut self, now: u64) -> Result<i16, CoreError<<R as Radio>::Error>> {
         // Check we're not busy
         if self.is_busy() {
             return Err(CoreError::Busy);
         }
 
         // Read the RSSI
-        let rssi = self.radio.poll_rssi().map_err(CoreError::Radio)?;
+        let rssi = self
+            .radio
+            .poll_rssi()
+            .map_err(|e| self.fault(now, RadioOp::PollRssi, e))?;
 
         Ok(rssi)
     }
 
-    /// Tick to update the MAC radio device
+    /// Tick to update the MAC radio device. Where event driven the radio is only
+    /// polled following a radio event or on expiry of the poll timeout.
+    ///
+    /// Radio errors are returned following a reset of the radio, or as
+    /// [`CoreError::RadioWedged`] where the maximum consecutive failures is reached
     pub fn tick(&mut self, now: u64) -> Result<Option<RawPacket>, CoreError<<R as Radio>::Error>> {
         use BaseState::*;
 
+        let timeout = self.poll_deadline().map(|t| now >= t).unwrap_or(false);
+        if self.event_driven && !self.pending && !timeout {
+            return Ok(None);
+        }
+        self.pending = false;
+        self.last_poll = now;
+
         trace!("BASE tick at {} ms, state: {}", now, self.state);
 
-        match self.state {
+        // Retry resuming the previous mode where the last radio reset failed
+        let (mode, retry) = match self.resume.take() {
+            Some(mode) => (mode, true),
+            None => (self.state, false),
+        };
+
+        let res = match self.state {
+            _ if retry => self.reset(now, mode).map(|_| None),
             Idle => {
                 // TODO: Auto-start here or not?
+                Ok(None)
             }
             Listening | Receiving => {
                 // Check for received completion and return to caller
-                if let Some(rx) = self.check_receive(now)? {
-                    return Ok(Some(rx));
-                }
                 // TODO: periodic check we're okay in the RX state?
+                self.check_receive(now)
             }
             Transmitting => {
                 // Check for transmit completion
-                self.check_transmit(now)?;
+                self.check_transmit(now).map(|_| None)
             }
             Sleeping => {
                 // TODO: pre-emptive wake here on sleep timeout?
+                Ok(None)
+            }
+        };
+
+        match res {
+            Ok(rx) => {
+                self.status.failures = 0;
+                Ok(rx)
+            }
+            Err(e) => {
+                // Reset the radio, retrying on the next tick where this fails
+                if retry || self.reset(now, mode).is_err() {
+                    self.resume = Some(mode);
+                }
+
+                Err(self.failed(now, e))
             }
         }
+    }
+
+    /// Reset the radio to idle and re-enter the provided mode, in-progress
+    /// transmissions are abandoned in favour of receive mode
+    fn reset(&mut self, now: u64, mode: BaseState) -> Result<(), CoreError<<R as Radio>::Error>> {
+        use BaseState::*;
+
+        debug!("Resetting radio at {} ms to resume {}", now, mode);
+
+        self.radio
+            .set_state(<R as State>::State::idle())
+            .map_err(|e| self.fault(now, RadioOp::SetState, e))?;
+        self.set_state(now, Idle);
+
+        match mode {
+            Listening | Receiving | Transmitting => self.receive(now)?,
+            Sleeping => self.sleep(now)?,
+            Idle => (),
+        }
+
+        self.status.recoveries = self.status.recoveries.saturating_add(1);
+
+        Ok(())
+    }
+
+    /// Count a radio failure, returning the error to be reported
+    fn failed(
+        &mut self,
+        now: u64,
+        err: CoreError<<R as Radio>::Error>,
+    ) -> CoreError<<R as Radio>::Error> {
+        self.status.failures = self.status.failures.saturating_add(1);
+
+        if self.status.failures >= self.max_failures {
+            error!(
+                "Radio wedged after {} consecutive failures at {} ms",
+                self.status.failures, now
+            );
+            return CoreError::RadioWedged;
+        }
+
+        warn!("Radio failure {} at {} ms", self.status.failures, now);
 
-        Ok(None)
+        err
+    }
+
+    /// Record a failed radio operation, returning the wrapped error
+    fn fault(
+        &mut self,
+        now: u64,
+        op: RadioOp,
+        err: <R as Radio>::Error,
+    ) -> CoreError<<R as Radio>::Error> {
+        debug!("Radio error on {} at {} ms", op, now);
+
+        self.status.last_error = Some((op, now));
+        CoreError::Radio(err)
     }
 
     /// Internal function for receive state(s)
@@ -164,7 +479,11 @@ where
         // TODO: Check if we're currently receiving a packet and update state
 
         // Check for any received packets (and re-enter RX if required)
-        if !self.radio.check_receive(true).map_err(CoreError::Radio)? {
+        let received = self
+            .radio
+            .check_receive(true)
+            .map_err(|e| self.fault(now, RadioOp::CheckReceive, e))?;
+        if !received {
             return Ok(None);
         }
 
@@ -174,7 +493,7 @@ where
         let (len, info) = self
             .radio
             .get_received(&mut pkt.data)
-            .map_err(CoreError::Radio)?;
+            .map_err(|e| self.fault(now, RadioOp::GetReceived, e))?;
 
         pkt.len = len;
         pkt.rssi = info.rssi();
@@ -185,14 +504,19 @@ where
             info.rssi(),
             now
         );
-        #[cfg(not(feature = "defmt"))]
-        trace!("{:02x?}", pkt.data());
-        #[cfg(feature = "defmt")]
-        trace!("{:?}", pkt.data());
+        trace!("{}", HexSlice(pkt.data()));
+        #[cfg(feature = "trace-frames")]
+        trace_frame("RX", now, pkt.data(), Some(pkt.rssi));
+        self.capture.capture(Direction::Rx, now * 1000, pkt.data());
+
+        self.stats.rx_frames = self.stats.rx_frames.saturating_add(1);
+        self.stats.rx_bytes = self.stats.rx_bytes.saturating_add(pkt.len as u64);
 
         // Restart RX
-        self.radio.start_receive().map_err(CoreError::Radio)?;
-        self.state = BaseState::Listening;
+        self.radio
+            .start_receive()
+            .map_err(|e| self.fault(now, RadioOp::StartReceive, e))?;
+        self.set_state(now, BaseState::Listening);
 
         Ok(Some(pkt))
     }
@@ -200,18 +524,59 @@ where
     /// Internal function for transmit state(s)
     fn check_transmit(&mut self, now: u64) -> Result<(), CoreError<<R as Radio>::Error>> {
         // Check for tx completion
-        if !self.radio.check_transmit().map_err(CoreError::Radio)? {
+        let sent = self
+            .radio
+            .check_transmit()
+            .map_err(|e| self.fault(now, RadioOp::CheckTransmit, e))?;
+        if !sent {
             return Ok(());
         }
 
         debug!("Transmit complete at {} ms", now);
 
         // Re-enter receive mode and update state
-        self.radio.start_receive().map_err(CoreError::Radio)?;
-        self.state = BaseState::Listening;
+        self.radio
+            .start_receive()
+            .map_err(|e| self.fault(now, RadioOp::StartReceive, e))?;
+        self.set_state(now, BaseState::Listening);
 
         Ok(())
     }
+
+    /// Update the radio state, accumulating time spent in the previous state
+    fn set_state(&mut self, now: u64, state: BaseState) {
+        let elapsed = now.saturating_sub(self.state_since);
+        Self::accumulate(&mut self.stats, self.state, elapsed);
+
+        self.state = state;
+        self.state_since = now;
+    }
+
+    /// Add time spent in a radio state to the provided statistics
+    fn accumulate(stats: &mut BaseStats, state: BaseState, elapsed: u64) {
+        match state {
+            BaseState::Transmitting => stats.tx_ms = stats.tx_ms.saturating_add(elapsed),
+            BaseState::Listening | BaseState::Receiving => {
+                stats.rx_ms = stats.rx_ms.saturating_add(elapsed)
+            }
+            BaseState::Idle | BaseState::Sleeping => (),
+        }
+    }
+}
+
+/// Log a transmitted or received frame, truncated to [`TRACE_FRAME_BYTES`]
+#[cfg(feature = "trace-frames")]
+fn trace_frame(dir: &str, now: u64, data: &[u8], rssi: Option<i16>) {
+    let n = data.len().min(TRACE_FRAME_BYTES);
+
+    info!(
+        "Frame {} at {} ms, {} bytes, RSSI {:?}: {}",
+        dir,
+        now,
+        data.len(),
+        rssi,
+        HexSlice(&data[..n])
+    );
 }
 
 #[cfg(test)]
@@ -219,6 +584,15 @@ mod test {
     use super::*;
     use radio::{mock::*, BasicInfo};
 
+    #[test]
+    fn hex_slice() {
+        let data = [0x00, 0x0b, 0xa5, 0xff];
+
+        assert_eq!(std::format!("{}", HexSlice(&data)), "[00, 0b, a5, ff]");
+        assert_eq!(std::format!("{:?}", HexSlice(&data[..2])), "[00, 0b]");
+        assert_eq!(std::format!("{}", HexSlice(&[])), "[]");
+    }
+
     #[test]
     fn init() {
         let mut radio = MockRadio::new(&[]);
@@ -290,6 +664,167 @@ mod test {
         // Return to listening state
         assert_eq!(base.state(), BaseState::Listening);
 
+        // Transmit time and frames are recorded
+        let stats = base.stats(3);
+        assert_eq!(stats.tx_frames, 1);
+        assert_eq!(stats.tx_bytes, 3);
+        assert_eq!(stats.tx_ms, 2);
+        assert_eq!(stats.rx_ms, 1);
+
+        base.reset_stats(3);
+        assert_eq!(base.stats(3), BaseStats::default());
+
+        radio.done();
+    }
+
+    /// Sink recording captured frames
+    struct Recorder(std::sync::Arc<std::sync::Mutex<std::vec::Vec<(Direction, u64, usize)>>>);
+
+    impl PcapSink for Recorder {
+        fn capture(&mut self, dir: Direction, ts_us: u64, frame: &[u8]) {
+            self.0.lock().unwrap().push((dir, ts_us, frame.len()));
+        }
+    }
+
+    #[test]
+    fn capture() {
+        let mut radio = MockRadio::new(&[]);
+        let mut base = Base::new(radio.clone()).unwrap();
+
+        let frames = std::sync::Arc::new(std::sync::Mutex::new(std::vec::Vec::new()));
+        let sink = std::boxed::Box::leak(std::boxed::Box::new(Recorder(frames.clone())));
+        base.set_capture(Some(sink));
+
+        radio.expect(&[Transaction::start_transmit(std::vec![00, 11, 22], None)]);
+        base.transmit(1, &[00, 11, 22]).unwrap();
+
+        radio.expect(&[
+            Transaction::check_transmit(Ok(true)),
+            Transaction::start_receive(None),
+            Transaction::check_receive(true, Ok(true)),
+            Transaction::get_received(Ok((std::vec![00, 11], BasicInfo::default()))),
+            Transaction::start_receive(None),
+        ]);
+        base.tick(2).unwrap();
+        assert!(base.tick(3).unwrap().is_some());
+
+        assert_eq!(
+            frames.lock().unwrap().as_slice(),
+            &[(Direction::Tx, 1000, 3), (Direction::Rx, 3000, 2)]
+        );
+
+        // Clearing the sink stops capture
+        base.set_capture(None);
+        radio.expect(&[Transaction::start_transmit(std::vec![00], None)]);
+        base.transmit(4, &[00]).unwrap();
+        assert_eq!(frames.lock().unwrap().len(), 2);
+
+        radio.done();
+    }
+
+    #[test]
+    fn event_driven() {
+        let mut radio = MockRadio::new(&[]);
+
+        let mut base = Base::new(radio.clone()).unwrap();
+        base.set_event_driven(true, Some(100));
+
+        radio.expect(&[Transaction::start_receive(None)]);
+        base.receive(0).unwrap();
+
+        // The radio is polled on the first tick
+        assert!(base.is_pending());
+        radio.expect(&[Transaction::check_receive(true, Ok(false))]);
+        base.tick(1).unwrap();
+        assert!(!base.is_pending());
+
+        // Then not touched until an event is signalled
+        base.tick(2).unwrap();
+        base.event(3, RadioEvent::RxDone);
+        assert!(base.is_pending());
+
+        radio.expect(&[
+            Transaction::check_receive(true, Ok(true)),
+            Transaction::get_received(Ok((std::vec![00, 11], BasicInfo::default()))),
+            Transaction::start_receive(None),
+        ]);
+        assert!(base.tick(4).unwrap().is_some());
+
+        // Or the poll timeout expires
+        assert_eq!(base.poll_deadline(), Some(104));
+        base.tick(103).unwrap();
+
+        radio.expect(&[Transaction::check_receive(true, Ok(false))]);
+        base.tick(104).unwrap();
+        assert_eq!(base.poll_deadline(), Some(204));
+
+        radio.done();
+    }
+
+    #[test]
+    fn radio_recovery() {
+        let mut radio = MockRadio::new(&[]);
+
+        let mut base = Base::new(radio.clone()).unwrap();
+        base.set_max_failures(2);
+
+        radio.expect(&[Transaction::start_receive(None)]);
+        base.receive(0).unwrap();
+
+        // Radio errors reset the radio and resume receive
+        radio.expect(&[
+            Transaction::check_receive(true, Err(MockError::Timeout)),
+            Transaction::set_state(MockState::Idle, None),
+            Transaction::start_receive(None),
+        ]);
+        assert_eq!(
+            base.tick(1).err(),
+            Some(CoreError::Radio(MockError::Timeout))
+        );
+        assert_eq!(base.state(), BaseState::Listening);
+
+        let status = BaseStatus {
+            failures: 1,
+            recoveries: 1,
+            last_error: Some((RadioOp::CheckReceive, 1)),
+        };
+        assert_eq!(base.status(), &status);
+
+        // Failures are cleared once the radio is polled successfully
+        radio.expect(&[Transaction::check_receive(true, Ok(false))]);
+        base.tick(2).unwrap();
+        assert_eq!(base.status().failures, 0);
+
+        // Failed resets are retried on the next tick
+        radio.expect(&[
+            Transaction::check_receive(true, Err(MockError::Timeout)),
+            Transaction::set_state(MockState::Idle, None),
+            Transaction::start_receive(Some(MockError::Timeout)),
+        ]);
+        assert_eq!(
+            base.tick(3).err(),
+            Some(CoreError::Radio(MockError::Timeout))
+        );
+        assert_eq!(base.state(), BaseState::Idle);
+        assert_eq!(base.status().last_error, Some((RadioOp::StartReceive, 3)));
+
+        // Until the maximum consecutive failures is reached
+        radio.expect(&[
+            Transaction::set_state(MockState::Idle, None),
+            Transaction::start_receive(Some(MockError::Timeout)),
+        ]);
+        assert_eq!(base.tick(4).err(), Some(CoreError::RadioWedged));
+
+        // Reporting the radio as wedged until recovered
+        radio.expect(&[
+            Transaction::set_state(MockState::Idle, None),
+            Transaction::start_receive(None),
+        ]);
+        assert!(base.tick(5).unwrap().is_none());
+        assert_eq!(base.state(), BaseState::Listening);
+        assert_eq!(base.status().failures, 0);
+        assert_eq!(base.status().recoveries, 2);
+
         radio.done();
     }
 }
@@ -5,8 +5,20 @@
 
 use ieee802154::mac::DecodeError;
 
+use crate::mac_802154::security::SecurityError;
 use crate::MacError;
 
+/// Buffer errors, where data does not fit within a provided buffer
+#[derive(Debug, Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum BufferError {
+    /// Buffer too small for the data to be written
+    TooSmall,
+    /// Buffer too small for the data to be read, with the data retained
+    /// for retrieval with a buffer of at least `needed` bytes
+    Insufficient { needed: usize },
+}
+
 /// Basic MAC errors
 #[derive(Debug, Clone, PartialEq)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
@@ -23,9 +35,28 @@ pub enum CoreError<E> {
     /// Decoding error
     DecodeError(DecodeError),
 
+    /// Frame security error
+    SecurityError(SecurityError),
+
+    /// Encoding or receive buffer too small
+    BufferError(BufferError),
+
+    /// Invalid MAC configuration
+    InvalidConfig,
+
+    /// Operation requires association with a coordinator
+    NotAssociated,
+
+    /// Operation not supported
+    Unsupported,
+
     /// Wrapper for unhandled / underlying radio errors
     Radio(E),
 
+    /// Radio failed to recover following consecutive errors, and should be
+    /// reset or power-cycled by the application
+    RadioWedged,
+
     Timeout,
 
     Busy,
@@ -38,4 +69,24 @@ impl<E> MacError for CoreError<E> {
             _ => false,
         }
     }
+
+    fn buffer_length(&self) -> bool {
+        match self {
+            Self::BufferError(_) => true,
+            _ => false,
+        }
+    }
+
+    fn fatal(&self) -> bool {
+        match self {
+            Self::RadioWedged => true,
+            _ => false,
+        }
+    }
+}
+
+impl<E> From<BufferError> for CoreError<E> {
+    fn from(e: BufferError) -> Self {
+        Self::BufferError(e)
+    }
 }
@@ -13,18 +13,28 @@ use core::ops::{Deref, DerefMut};
 
 use radio::{Busy, RadioState, Receive, ReceiveInfo, Rssi, State, Transmit};
 
+use crate::error::BufferError;
+
 #[cfg(any(test, feature = "std"))]
 extern crate std;
 
 #[cfg(any(test, feature = "alloc"))]
 extern crate alloc;
 
+/// Async wrappers for use with async executors
+#[cfg(feature = "async")]
+pub mod asynch;
 /// Common radio control, shared between MACs
 pub mod base;
 /// Shared error types
 pub mod error;
 /// 802.15.4 MAC implementation
 pub mod mac_802154;
+/// Packet capture of radio frames
+pub mod pcap;
+/// Virtual radio network for multi-node testing
+#[cfg(any(test, feature = "mocks"))]
+pub mod sim;
 /// 6LowPAN adaptation layer over MAC abstraction
 pub mod sixlo;
 /// Timer abstraction for stack use
@@ -78,12 +88,12 @@ impl<const N: usize> DerefMut for RawPacket<N> {
 }
 
 impl<const N: usize> TryFrom<&[u8]> for RawPacket<N> {
-    type Error = ();
+    type Error = BufferError;
 
     fn try_from(value: &[u8]) -> Result<Self, Self::Error> {
         // Check length is okay
         if value.len() > N {
-            return Err(());
+            return Err(BufferError::TooSmall);
         }
 
         // Copy data
@@ -105,11 +115,64 @@ impl<const N: usize> TryFrom<&[u8]> for RawPacket<N> {
 pub struct RxInfo<Address = ieee802154::mac::Address> {
     /// Source address
     pub source: Address,
+    /// Destination address
+    pub destination: Address,
     /// Receive RSSI
     pub rssi: i16,
 }
 
-/// Radio interface combines base [`radio`] traits
+/// Transmit handle, returned by [`Mac::transmit`] to correlate [`TxStatus`] reports
+pub type TxHandle = u32;
+
+/// Transmit outcome, reported per packet via [`Mac::tx_status`]
+#[derive(Debug, Copy, Clone, PartialEq, strum::Display)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum TxStatus {
+    /// Packet sent (no acknowledgement requested)
+    Sent,
+    /// Packet sent and acknowledged by the peer
+    Acked,
+    /// Packet transmission failed
+    Failed(TxFailure),
+}
+
+/// Transmit failure reasons
+#[derive(Debug, Copy, Clone, PartialEq, strum::Display)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum TxFailure {
+    /// Channel access failed (CSMA backoffs exhausted)
+    ChannelBusy,
+    /// Maximum retries exceeded without acknowledgement
+    RetriesExceeded,
+    /// Pending (indirect) packet expired before being requested by the peer,
+    /// or queued packet not sent by its deadline
+    Expired,
+    /// Packet exceeded the radio buffer on encoding
+    Oversize,
+    /// Packet dropped undelivered on MAC shutdown or disassociation
+    Cancelled,
+}
+
+/// Radio interface combines base [`radio`] traits, including [`radio::Channel`]
+/// for channel hopping and scans with the `channels` feature
+#[cfg(feature = "channels")]
+pub trait Radio:
+    radio::State<State = <Self as Radio>::State, Error = <Self as Radio>::Error>
+    + radio::Busy<Error = <Self as Radio>::Error>
+    + radio::Transmit<Error = <Self as Radio>::Error>
+    + radio::Receive<Info = <Self as Radio>::Info, Error = <Self as Radio>::Error>
+    + radio::Rssi<Error = <Self as Radio>::Error>
+    + radio::Channel<Channel = <Self as Radio>::Channel, Error = <Self as Radio>::Error>
+{
+    type State: RadioState + Debug;
+    type Info: ReceiveInfo + Debug + Default;
+    type Channel: Debug + Clone + PartialEq;
+    type Error: Debug;
+}
+
+/// Radio interface combines base [`radio`] traits, without the `channels` feature
+/// radios remain on a fixed channel and channel switching is ignored
+#[cfg(not(feature = "channels"))]
 pub trait Radio:
     radio::State<State = <Self as Radio>::State, Error = <Self as Radio>::Error>
     + radio::Busy<Error = <Self as Radio>::Error>
@@ -119,10 +182,33 @@ pub trait Radio:
 {
     type State: RadioState + Debug;
     type Info: ReceiveInfo + Debug + Default;
+    type Channel: Debug + Clone + PartialEq;
     type Error: Debug;
 }
 
 /// Automatic Radio impl for radio devices meeting the trait constraint
+#[cfg(feature = "channels")]
+impl<T, E: Debug> Radio for T
+where
+    T: State<Error = E>
+        + Busy<Error = E>
+        + Transmit<Error = E>
+        + Receive<Error = E>
+        + Rssi<Error = E>
+        + radio::Channel<Error = E>,
+    <T as State>::State: RadioState + Debug,
+    <T as Receive>::Info: ReceiveInfo + Debug + Default,
+    <T as radio::Channel>::Channel: Debug + Clone + PartialEq,
+{
+    type Error = E;
+    type State = <T as State>::State;
+    type Info = <T as Receive>::Info;
+    type Channel = <T as radio::Channel>::Channel;
+}
+
+/// Automatic Radio impl for radio devices meeting the trait constraint,
+/// with a placeholder channel type for the fixed radio channel
+#[cfg(not(feature = "channels"))]
 impl<T, E: Debug> Radio for T
 where
     T: State<Error = E>
@@ -136,6 +222,32 @@ where
     type Error = E;
     type State = <T as State>::State;
     type Info = <T as Receive>::Info;
+    type Channel = ();
+}
+
+/// Common MAC layer statistics, reported via [`Mac::stats`]
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct StackStats {
+    /// Frames sent (and acknowledged where requested)
+    pub tx_ok: u32,
+    /// Frames failed on channel access or retries, or expired prior to transmission
+    pub tx_fail: u32,
+    /// Frames received and accepted
+    pub rx_ok: u32,
+    /// Received frames dropped on decode errors, filtering, security failures
+    /// or without receive buffer space
+    pub rx_dropped: u32,
+    /// Frames queued for transmission
+    pub tx_queued: usize,
+    /// Received frames awaiting collection via [`Mac::receive`]
+    pub rx_queued: usize,
+    /// Transmissions missing their scheduled time
+    pub deadline_miss_tx: u32,
+    /// Acknowledgements missing their scheduled time
+    pub deadline_miss_ack: u32,
+    /// Channel access failures
+    pub csma_cca_fail: u32,
 }
 
 /// Network interface abstraction
@@ -154,41 +266,154 @@ pub trait Mac<Address = ieee802154::mac::Address> {
     /// Check for transmit buffer capacity
     fn can_transmit(&self) -> Result<bool, Self::Error>;
 
-    /// Setup a packet for transmission, buffered by the implementer
-    fn transmit(&mut self, dest: Address, data: &[u8], ack: bool) -> Result<(), Self::Error>;
+    /// Setup a packet for transmission, buffered by the implementer.
+    /// Returns a handle used to correlate the outcome reported by [`Mac::tx_status`]
+    fn transmit(&mut self, dest: Address, data: &[u8], ack: bool) -> Result<TxHandle, Self::Error>;
+
+    /// Setup a packet for transmission as with [`Mac::transmit`], to be dropped
+    /// (reporting [`TxFailure::Expired`]) where not sent by `expires_at`.
+    /// Implementations without deadline support transmit the packet regardless
+    fn transmit_with_deadline(
+        &mut self,
+        dest: Address,
+        data: &[u8],
+        ack: bool,
+        expires_at: Ts,
+    ) -> Result<TxHandle, Self::Error> {
+        let _ = expires_at;
+        self.transmit(dest, data, ack)
+    }
+
+    /// Fetch the next transmit outcome, if any
+    fn tx_status(&mut self) -> Result<Option<(TxHandle, TxStatus)>, Self::Error>;
 
-    /// Check for received packets, buffered by the implementer
+    /// Fetch the next MAC event, if any
+    fn event(&mut self) -> Result<Option<MacEvent<Address>>, Self::Error>;
+
+    /// Check for received packets, buffered by the implementer.
+    /// Packets exceeding the provided buffer are retained, returning an error
+    /// for which [`MacError::buffer_length`] is true, and may be received with a
+    /// larger buffer or dropped via [`Mac::discard`]
     fn receive(&mut self, data: &mut [u8])
         -> Result<Option<(usize, RxInfo<Address>)>, Self::Error>;
+
+    /// Drop the next received packet, as where this exceeds the buffers available
+    /// for [`Mac::receive`], returning whether a packet was dropped.
+    /// Implementations dropping such packets on receipt have nothing to discard
+    fn discard(&mut self) -> Result<bool, Self::Error> {
+        Ok(false)
+    }
+
+    /// Fetch the time of the next scheduled operation, by which [`Mac::tick`] should
+    /// be called. Deadlines at or before the current time are due immediately
+    fn next_deadline(&self) -> Option<Ts>;
+
+    /// Signal a radio event (such as a radio interrupt), the radio is then serviced
+    /// on the next [`Mac::tick`] where the implementation is interrupt driven
+    fn radio_event(&mut self);
+
+    /// Resolve the next hop toward a destination not reached directly, such as
+    /// a device associated via a router. Implementations without routing
+    /// information assume destinations are direct neighbours
+    fn next_hop(&self, dest: &Address) -> Option<Address> {
+        let _ = dest;
+        None
+    }
+
+    /// Fetch MAC layer statistics, including current queue depths.
+    /// Implementations without statistics report zeroed counters
+    fn stats(&self) -> StackStats {
+        StackStats::default()
+    }
+
+    /// Reset MAC layer statistics
+    fn reset_stats(&mut self) {}
 }
 
 pub trait MacError {
     fn queue_full(&self) -> bool;
+
+    /// Check whether the error is due to a buffer too small for the data
+    fn buffer_length(&self) -> bool {
+        false
+    }
+
+    /// Check whether the error is fatal, requiring the stack (or radio) to be reset
+    /// by the application rather than being retried on the next tick
+    fn fatal(&self) -> bool {
+        false
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, strum::Display)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub enum MacState<Address = ieee802154::mac::Address> {
     Disconnected,
+    /// Scanning channels prior to operation
+    Scanning,
+    /// Radio sleeping between superframe active periods, or parked on shutdown
+    Sleep,
     Synced(Address),
     Associated(Address),
 }
 
+/// MAC events, reported via [`Mac::event`]
+#[derive(Debug, Clone, PartialEq, strum::Display)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum MacEvent<Address = ieee802154::mac::Address> {
+    /// MAC restarted following reconfiguration, dropping sync, association and
+    /// pending operations, with the provided (possibly changed) MAC address
+    Restarted(Address),
+    /// Another coordinator was heard advertising the PAN ID of this coordinator
+    PanConflict,
+    /// PAN ID changed following conflict resolution or coordinator realignment,
+    /// with the updated MAC address
+    Realigned(Address),
+    /// Association ended via disassociation notification, with the address of the
+    /// departed device (coordinators) or of the coordinator left (devices)
+    Disassociated(Address),
+}
+
 // Wrap log macros to support switching between defmt and standard logging
 
-#[cfg(feature = "defmt")]
 mod log {
+    #[cfg(feature = "defmt")]
     pub use defmt::{debug, error, info, trace, warn};
+    #[cfg(not(feature = "defmt"))]
+    pub use log::{debug, error, info, trace, warn};
 
+    #[cfg(feature = "defmt")]
     pub trait FmtError: core::fmt::Debug + defmt::Format {}
+    #[cfg(feature = "defmt")]
     impl<T: core::fmt::Debug + defmt::Format> FmtError for T {}
-}
-#[cfg(not(feature = "defmt"))]
-mod log {
-    pub use log::{debug, error, info, trace, warn};
 
+    #[cfg(not(feature = "defmt"))]
     pub trait FmtError: core::fmt::Debug {}
+    #[cfg(not(feature = "defmt"))]
     impl<T: core::fmt::Debug> FmtError for T {}
+
+    /// Hex dump wrapper for byte slices, formatted with `{}` under either log backend
+    #[derive(Clone, Copy, PartialEq)]
+    pub struct HexSlice<'a>(pub &'a [u8]);
+
+    impl core::fmt::Display for HexSlice<'_> {
+        fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+            write!(f, "{:02x?}", self.0)
+        }
+    }
+
+    impl core::fmt::Debug for HexSlice<'_> {
+        fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+            core::fmt::Display::fmt(self, f)
+        }
+    }
+
+    #[cfg(feature = "defmt")]
+    impl defmt::Format for HexSlice<'_> {
+        fn format(&self, f: defmt::Formatter) {
+            defmt::write!(f, "{=[u8]:x}", self.0)
+        }
+    }
 }
 
 pub trait Alloc {
@@ -0,0 +1,196 @@
+//! 802.15.4 Association Table
+//
+// https://github.com/rust-iot/rust-lpwan
+// Copyright 2021 Ryan Kurte
+
+use heapless::Vec;
+use ieee802154::mac::{Address, ExtendedAddress, ShortAddress};
+
+/// Default association table capacity
+pub const DEFAULT_MAX_ASSOCIATIONS: usize = 16;
+
+/// Default first short address issued by the allocator
+const SHORT_ADDR_FIRST: u16 = 0x0001;
+/// Last short address issued by the allocator (0xfffe / 0xffff are reserved)
+const SHORT_ADDR_LAST: u16 = 0xfffd;
+
+/// Associated device record
+#[derive(Debug, Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Association {
+    /// Device extended address
+    pub extended: ExtendedAddress,
+    /// Short address assigned to the device
+    pub short: ShortAddress,
+    /// Device keeps the receiver enabled while idle, otherwise frames
+    /// for the device are held for indirect transmission
+    pub rx_on_when_idle: bool,
+}
+
+impl Association {
+    /// Check whether an address refers to this device
+    pub fn matches(&self, addr: &Address) -> bool {
+        match addr {
+            Address::Short(_, s) => *s == self.short,
+            Address::Extended(_, e) => *e == self.extended,
+            _ => false,
+        }
+    }
+}
+
+/// Association table, used by coordinators to allocate and track
+/// short addresses for associated devices
+#[derive(Debug, Clone, PartialEq)]
+pub struct AssocTable<const N: usize = DEFAULT_MAX_ASSOCIATIONS> {
+    entries: Vec<Association, N>,
+    first: u16,
+    next: u16,
+}
+
+impl<const N: usize> Default for AssocTable<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize> AssocTable<N> {
+    /// Create a new (empty) association table
+    pub fn new() -> Self {
+        Self::with_base(ShortAddress(SHORT_ADDR_FIRST))
+    }
+
+    /// Create a new (empty) association table, allocating short addresses
+    /// upwards from the provided base
+    pub fn with_base(base: ShortAddress) -> Self {
+        let first = base.0.min(SHORT_ADDR_LAST);
+
+        Self {
+            entries: Vec::new(),
+            first,
+            next: first,
+        }
+    }
+
+    /// Allocate a short address for a device, re-issuing the existing address
+    /// if the device is already associated.
+    ///
+    /// Returns `None` if the table is full or short addresses are exhausted.
+    pub fn allocate(
+        &mut self,
+        extended: ExtendedAddress,
+        rx_on_when_idle: bool,
+    ) -> Option<ShortAddress> {
+        if let Some(a) = self.entries.iter_mut().find(|a| a.extended == extended) {
+            a.rx_on_when_idle = rx_on_when_idle;
+            return Some(a.short);
+        }
+
+        let available = (SHORT_ADDR_LAST - self.first) as usize + 1;
+        if self.entries.is_full() || self.entries.len() >= available {
+            return None;
+        }
+
+        // Find the next unused short address
+        let mut short = self.next;
+        while self.lookup_short(&ShortAddress(short)).is_some() {
+            short = self.increment(short);
+        }
+        self.next = self.increment(short);
+
+        let a = Association {
+            extended,
+            short: ShortAddress(short),
+            rx_on_when_idle,
+        };
+        let _ = self.entries.push(a);
+
+        Some(a.short)
+    }
+
+    /// Remove a device from the table
+    pub fn remove(&mut self, extended: &ExtendedAddress) -> Option<Association> {
+        let i = self.entries.iter().position(|a| &a.extended == extended)?;
+        Some(self.entries.swap_remove(i))
+    }
+
+    /// Lookup an association by extended address
+    pub fn lookup(&self, extended: &ExtendedAddress) -> Option<&Association> {
+        self.entries.iter().find(|a| &a.extended == extended)
+    }
+
+    /// Lookup an association by short address
+    pub fn lookup_short(&self, short: &ShortAddress) -> Option<&Association> {
+        self.entries.iter().find(|a| &a.short == short)
+    }
+
+    /// Lookup an association by short or extended address
+    pub fn lookup_address(&self, addr: &Address) -> Option<&Association> {
+        self.entries.iter().find(|a| a.matches(addr))
+    }
+
+    /// Iterate over associated devices
+    pub fn iter(&self) -> impl Iterator<Item = &Association> {
+        self.entries.iter()
+    }
+
+    /// Fetch the number of associated devices
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    fn increment(&self, v: u16) -> u16 {
+        if v >= SHORT_ADDR_LAST {
+            self.first
+        } else {
+            v + 1
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use ieee802154::mac::PanId;
+
+    #[test]
+    fn allocate() {
+        let mut t = AssocTable::<2>::new();
+
+        let a = t.allocate(ExtendedAddress(0x10), true).unwrap();
+        let b = t.allocate(ExtendedAddress(0x20), true).unwrap();
+        assert_ne!(a, b);
+
+        // Re-association returns the existing address
+        assert_eq!(t.allocate(ExtendedAddress(0x10), false), Some(a));
+        assert_eq!(
+            t.lookup_address(&Address::Short(PanId(1), a))
+                .map(|a| a.rx_on_when_idle),
+            Some(false)
+        );
+
+        // Table full
+        assert_eq!(t.allocate(ExtendedAddress(0x30), true), None);
+
+        // Removal frees space without re-issuing in-use addresses
+        t.remove(&ExtendedAddress(0x10)).unwrap();
+        let c = t.allocate(ExtendedAddress(0x30), true).unwrap();
+        assert_ne!(c, b);
+        assert_eq!(t.len(), 2);
+    }
+
+    #[test]
+    fn allocate_base() {
+        let mut t = AssocTable::<3>::with_base(ShortAddress(0xfffc));
+
+        // Addresses are allocated from the base, wrapping prior to reserved addresses
+        let a = t.allocate(ExtendedAddress(0x10), true).unwrap();
+        let b = t.allocate(ExtendedAddress(0x20), true).unwrap();
+        assert_eq!((a, b), (ShortAddress(0xfffc), ShortAddress(0xfffd)));
+
+        // Until exhausted
+        assert_eq!(t.allocate(ExtendedAddress(0x30), true), None);
+
+        t.remove(&ExtendedAddress(0x10)).unwrap();
+        assert_eq!(t.allocate(ExtendedAddress(0x30), true), Some(a));
+    }
+}
@@ -0,0 +1,255 @@
+//! 802.15.4 Beacon Payloads
+//!
+//! Coordinators may carry application data in beacon payloads for network
+//! advertisement (network names, join flags, versions etc.), alongside the
+//! hopping information where a hopping sequence is configured. Each is carried
+//! as an element with an identifier and length header (as for 802.15.4 payload
+//! IEs), so receivers decode these independent of local configuration. The most
+//! recent payload heard from each coordinator is retained for retrieval by applications.
+//
+// https://github.com/rust-iot/rust-lpwan
+// Copyright 2021 Ryan Kurte
+
+use heapless::Vec;
+use ieee802154::mac::Address;
+
+use super::channels::HoppingInfo;
+use crate::Ts;
+
+/// Maximum beacon payload length (aMaxBeaconPayloadLength)
+pub const MAX_BEACON_PAYLOAD_LEN: usize = 52;
+
+/// Beacon element identifier for [`HoppingInfo`]
+pub const ELEMENT_HOPPING: u8 = 0x01;
+
+/// Beacon element identifier for application payloads
+pub const ELEMENT_APP: u8 = 0x02;
+
+/// Default number of coordinators for which beacon payloads are retained
+pub const DEFAULT_MAX_BEACON_SOURCES: usize = 4;
+
+/// Beacon payload heard from a coordinator
+#[derive(Debug, Clone, PartialEq)]
+pub struct BeaconPayload {
+    /// Beacon source address
+    pub source: Address,
+    /// Time the beacon was received
+    pub received: Ts,
+    payload: Vec<u8, MAX_BEACON_PAYLOAD_LEN>,
+}
+
+impl BeaconPayload {
+    /// Fetch the beacon payload data
+    pub fn payload(&self) -> &[u8] {
+        &self.payload
+    }
+}
+
+/// Elements carried in beacon payloads, each encoded with a one byte
+/// identifier and one byte length followed by the element data
+#[derive(Debug, Clone, PartialEq)]
+pub struct BeaconContent<'a> {
+    /// Hopping sequence advertised by the coordinator
+    pub hopping: Option<HoppingInfo>,
+    /// Application payload, see [`Mac::set_beacon_payload`](super::Mac::set_beacon_payload)
+    pub app: &'a [u8],
+}
+
+impl<'a> BeaconContent<'a> {
+    /// Element header length in bytes
+    pub const HEADER_LEN: usize = 2;
+
+    /// Maximum encoded length in bytes
+    pub const MAX_LEN: usize = 2 * Self::HEADER_LEN + HoppingInfo::LEN + MAX_BEACON_PAYLOAD_LEN;
+
+    /// Decode beacon elements, ignoring unknown elements and stopping at
+    /// the first truncated element
+    pub fn decode(buff: &'a [u8]) -> Self {
+        let mut content = Self {
+            hopping: None,
+            app: &[],
+        };
+
+        let mut rest = buff;
+        while rest.len() >= Self::HEADER_LEN {
+            let (id, len) = (rest[0], rest[1] as usize);
+            if rest.len() < Self::HEADER_LEN + len {
+                break;
+            }
+            let data = &rest[Self::HEADER_LEN..][..len];
+
+            match id {
+                ELEMENT_HOPPING if len == HoppingInfo::LEN => {
+                    content.hopping = HoppingInfo::decode(data)
+                }
+                ELEMENT_APP => content.app = data,
+                _ => (),
+            }
+
+            rest = &rest[Self::HEADER_LEN + len..];
+        }
+
+        content
+    }
+
+    /// Encode beacon elements, omitting absent hopping information and empty
+    /// application payloads. The buffer must be at least [`Self::MAX_LEN`] bytes
+    pub fn encode(&self, buff: &mut [u8]) -> usize {
+        let mut n = 0;
+
+        if let Some(h) = &self.hopping {
+            buff[n] = ELEMENT_HOPPING;
+            buff[n + 1] = HoppingInfo::LEN as u8;
+            n += Self::HEADER_LEN;
+            n += h.encode(&mut buff[n..]);
+        }
+
+        if !self.app.is_empty() {
+            buff[n] = ELEMENT_APP;
+            buff[n + 1] = self.app.len() as u8;
+            n += Self::HEADER_LEN;
+            buff[n..][..self.app.len()].copy_from_slice(self.app);
+            n += self.app.len();
+        }
+
+        n
+    }
+}
+
+/// Table of beacon payloads, ordered from least to most recently heard
+#[derive(Debug, Clone, PartialEq)]
+pub struct BeaconTable<const N: usize = DEFAULT_MAX_BEACON_SOURCES> {
+    entries: Vec<BeaconPayload, N>,
+}
+
+impl<const N: usize> Default for BeaconTable<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize> BeaconTable<N> {
+    /// Create a new (empty) beacon table
+    pub fn new() -> Self {
+        Self {
+            entries: Vec::new(),
+        }
+    }
+
+    /// Record the payload of a received beacon, replacing the least recently
+    /// heard coordinator where the table is full.
+    /// Returns false where the payload exceeds [`MAX_BEACON_PAYLOAD_LEN`]
+    pub fn received(&mut self, source: Address, payload: &[u8], now: Ts) -> bool {
+        let payload = match Vec::from_slice(payload) {
+            Ok(p) => p,
+            Err(_) => return false,
+        };
+
+        if let Some(i) = self.entries.iter().position(|b| b.source == source) {
+            self.entries.remove(i);
+        } else if self.entries.is_full() {
+            self.entries.remove(0);
+        }
+
+        let _ = self.entries.push(BeaconPayload {
+            source,
+            received: now,
+            payload,
+        });
+
+        true
+    }
+
+    /// Fetch the most recent beacon payload from a coordinator
+    pub fn lookup(&self, source: &Address) -> Option<&BeaconPayload> {
+        self.entries.iter().find(|b| &b.source == source)
+    }
+
+    /// Iterate over beacon payloads, from least to most recently heard
+    pub fn iter(&self) -> impl Iterator<Item = &BeaconPayload> {
+        self.entries.iter()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use ieee802154::mac::{PanId, ShortAddress};
+
+    use super::*;
+
+    #[test]
+    fn beacon_table() {
+        let a = Address::Short(PanId(1), ShortAddress(1));
+        let b = Address::Short(PanId(1), ShortAddress(2));
+        let c = Address::Short(PanId(1), ShortAddress(3));
+
+        let mut t = BeaconTable::<2>::new();
+
+        assert!(t.received(a, &[0x11], 10));
+        assert!(t.received(b, &[0x22, 0x23], 20));
+        assert_eq!(t.lookup(&a).map(|b| b.payload()), Some(&[0x11][..]));
+
+        // Payloads are replaced on receipt of the next beacon
+        assert!(t.received(a, &[], 30));
+        let p = t.lookup(&a).unwrap();
+        assert!(p.payload().is_empty());
+        assert_eq!(p.received, 30);
+
+        // Full tables replace the least recently heard coordinator
+        assert!(t.received(c, &[0x33], 40));
+        assert!(t.lookup(&b).is_none());
+        assert_eq!(t.iter().count(), 2);
+
+        // Oversized payloads are not recorded
+        assert!(!t.received(b, &[0u8; MAX_BEACON_PAYLOAD_LEN + 1], 50));
+        assert!(t.lookup(&b).is_none());
+    }
+
+    #[test]
+    fn beacon_content() {
+        let hopping = HoppingInfo { id: 3, asn: 12 };
+        let mut buff = [0u8; BeaconContent::MAX_LEN];
+
+        // Hopping information and application payloads are decoded where present
+        let c = BeaconContent {
+            hopping: Some(hopping),
+            app: &[0x11, 0x22],
+        };
+        let n = c.encode(&mut buff);
+        assert_eq!(n, 2 * BeaconContent::HEADER_LEN + HoppingInfo::LEN + 2);
+        assert_eq!(BeaconContent::decode(&buff[..n]), c);
+
+        let c = BeaconContent {
+            hopping: None,
+            app: &[0x33; MAX_BEACON_PAYLOAD_LEN],
+        };
+        let n = c.encode(&mut buff);
+        assert_eq!(BeaconContent::decode(&buff[..n]), c);
+
+        let c = BeaconContent {
+            hopping: Some(hopping),
+            app: &[],
+        };
+        let n = c.encode(&mut buff);
+        assert_eq!(n, BeaconContent::HEADER_LEN + HoppingInfo::LEN);
+        assert_eq!(BeaconContent::decode(&buff[..n]), c);
+
+        // Application payloads resembling hopping information are not decoded as such
+        let mut app = [0u8; BeaconContent::HEADER_LEN + HoppingInfo::LEN];
+        app[0] = ELEMENT_HOPPING;
+        app[1] = HoppingInfo::LEN as u8;
+        let c = BeaconContent {
+            hopping: None,
+            app: &app,
+        };
+        let n = c.encode(&mut buff);
+        assert_eq!(BeaconContent::decode(&buff[..n]), c);
+
+        // Unknown elements are skipped, truncated elements ignored
+        let buff = [0x7f, 0x01, 0xaa, 0x02, 0x01, 0xbb, 0x02, 0x04, 0xcc];
+        let c = BeaconContent::decode(&buff);
+        assert_eq!(c.hopping, None);
+        assert_eq!(c.app, &[0xbb]);
+        assert!(BeaconContent::decode(&[]).app.is_empty());
+    }
+}
@@ -3,19 +3,21 @@
 // https://github.com/rust-iot/rust-lpwan
 // Copyright 2021 Ryan Kurte
 
+use byteorder::{ByteOrder, LittleEndian};
+
 /// 2.4GHz channel
 pub struct Ch2450(u16);
 
 impl Ch2450 {
     /// Fetch the channel frequency in MHz
     pub fn mhz(self) -> f32 {
-        2405f32 * 5f32 * (self.0 as f32 - 11f32)
+        2405f32 + 5f32 * (self.0 as f32 - 11f32)
     }
 
     /// Attempt to convert a channel frequency into a channel index
     pub fn from_mhz(freq_mhz: f32) -> Option<Ch2450> {
         let index = (freq_mhz - 2405.0) / 5.0;
-        if index > 0.0 && index < 16.0 {
+        if index >= 0.0 && index < 16.0 {
             Some(Ch2450(index as u16 + 11))
         } else {
             None
@@ -30,3 +32,53 @@ pub const CHANNEL_PAGES_2450: &'static [&'static [u16]] = &[
         11, 12, 13, 14, 15, 16, 17, 18, 19, 20, 21, 22, 23, 24, 25, 26,
     ],
 ];
+
+/// Hopping information carried in beacon payloads, allowing joining devices to
+/// follow the coordinator hopping sequence from the advertised ASN
+#[derive(Debug, Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct HoppingInfo {
+    /// Hopping sequence identifier
+    pub id: u16,
+    /// Absolute Slot Number (40-bit) of the beacon slot
+    pub asn: u64,
+}
+
+impl HoppingInfo {
+    /// Encoded length in bytes
+    pub const LEN: usize = 7;
+
+    pub fn decode(buff: &[u8]) -> Option<Self> {
+        if buff.len() < Self::LEN {
+            return None;
+        }
+
+        Some(Self {
+            id: LittleEndian::read_u16(&buff[0..]),
+            asn: LittleEndian::read_uint(&buff[2..], 5),
+        })
+    }
+
+    pub fn encode(&self, buff: &mut [u8]) -> usize {
+        LittleEndian::write_u16(&mut buff[0..], self.id);
+        LittleEndian::write_uint(&mut buff[2..], self.asn & 0xff_ffff_ffff, 5);
+        Self::LEN
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn ch2450_mhz() {
+        assert_eq!(Ch2450(11).mhz(), 2405.0);
+        assert_eq!(Ch2450(26).mhz(), 2480.0);
+
+        for ch in CHANNEL_PAGES_2450[0] {
+            assert_eq!(Ch2450::from_mhz(Ch2450(*ch).mhz()).map(|c| c.0), Some(*ch));
+        }
+        assert!(Ch2450::from_mhz(2400.0).is_none());
+        assert!(Ch2450::from_mhz(2485.0).is_none());
+    }
+}
@@ -3,14 +3,89 @@
 // https://github.com/rust-iot/rust-lpwan
 // Copyright 2021 Ryan Kurte
 
+use heapless::Vec;
 use ieee802154::mac::beacon::{BeaconOrder, SuperframeOrder, SuperframeSpecification};
-use ieee802154::mac::PanId;
+use ieee802154::mac::{PanId, ShortAddress};
+
+use super::dedup::DEFAULT_MAX_SOURCES;
+use super::drift::MAX_DRIFT_SAMPLES;
+use super::scan::{ScanKind, MAX_SCAN_CHANNELS};
+use super::security::SecurityConfig;
+use crate::base::DEFAULT_MAX_FAILURES;
+
+/// Maximum number of channels in a hopping sequence
+pub const MAX_HOPPING_CHANNELS: usize = 16;
+
+/// Channel hopping sequence, with the channel for each slot selected as `channels[ASN % len]`
+#[derive(Debug, Clone, PartialEq)]
+pub struct HoppingSequence<Ch> {
+    /// Sequence identifier, advertised in beacons so joining devices can follow the sequence
+    pub id: u16,
+    /// Channels in hopping order
+    pub channels: Vec<Ch, MAX_HOPPING_CHANNELS>,
+}
+
+impl<Ch> HoppingSequence<Ch> {
+    /// Fetch the channel for the provided ASN
+    pub fn channel(&self, asn: u64) -> Option<&Ch> {
+        match self.channels.len() {
+            0 => None,
+            n => self.channels.get((asn % n as u64) as usize),
+        }
+    }
+}
+
+/// Clear channel assessment (CCA) mode, applied prior to CSMA transmissions
+#[derive(Debug, Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum CcaMode {
+    /// Channel is busy where the RSSI exceeds the threshold for any of `samples`
+    /// samples, taken at least `sample_interval_ms` apart prior to the transmit slot
+    EnergyAboveThreshold {
+        threshold: i16,
+        samples: u8,
+        sample_interval_ms: u8,
+    },
+    /// Channel is always clear, transmitting without CCA
+    AlwaysClear,
+}
 
 #[derive(Debug, Clone, PartialEq)]
-pub struct Config {
+pub struct Config<Ch = u16> {
     pub pan_coordinator: bool,
     pub pan_id: PanId,
 
+    /// Frame security, data frames are secured and unsecured data frames rejected where set
+    pub security: Option<SecurityConfig>,
+
+    /// Radio channel used while not hopping, the radio channel is left unchanged if unset
+    pub channel: Option<Ch>,
+
+    /// Channel hopping sequence, followed by coordinators and by devices once
+    /// synchronised to a coordinator advertising the same sequence id
+    pub hopping_sequence: Option<HoppingSequence<Ch>>,
+
+    /// Select the quietest of `scan_channels` via energy detection scan
+    /// prior to the first beacon (coordinators only)
+    pub auto_channel: bool,
+
+    /// Scan for PANs prior to joining (devices only), beacons are then only adopted
+    /// from the coordinator selected via [`super::Mac::join`]
+    pub pan_scan: Option<ScanKind>,
+
+    /// Move to a new PAN ID on detection of a PAN ID conflict (coordinators only),
+    /// conflicts are otherwise only reported via [`crate::MacEvent::PanConflict`]
+    pub pan_conflict_resolve: bool,
+
+    /// Number of superframes over which a new PAN ID must be unused prior to adoption
+    pub pan_conflict_quiet: u32,
+
+    /// Channels surveyed for automatic channel selection and PAN discovery
+    pub scan_channels: Vec<Ch, MAX_SCAN_CHANNELS>,
+
+    /// Scan dwell time per channel in ms
+    pub scan_duration: u64,
+
     /// Base superframe duration in ms
     pub base_superframe_duration: u32,
 
@@ -32,12 +107,78 @@ pub struct Config {
     /// Base slot duration in ms
     pub base_slot_duration: u32,
 
+    /// Final slot of the Contention Access Period (CAP), slots following this
+    /// form the Contention Free Period (CFP) used for guaranteed time slots
+    pub final_cap_slot: u8,
+
+    /// Number of guaranteed time slots to request from the coordinator (0 to disable)
+    pub gts_request_slots: u8,
+
+    /// Timeout for guaranteed time slot requests
+    pub gts_timeout: u64,
+
+    /// Period without frames from a device after which coordinators deallocate
+    /// the guaranteed time slots of the device
+    pub gts_inactivity_timeout: u64,
+
     /// Number of missed beacons before desync
     pub max_beacon_misses: u32,
 
+    /// Number of recent parent beacons over which drift of the local clock is
+    /// estimated for compensation between beacons, up to [`MAX_DRIFT_SAMPLES`]
+    /// (fewer than 2 disables compensation)
+    pub drift_window: u8,
+
+    /// Period from the first beacon heard while unsynchronised over which beacons are
+    /// collected, with the neighbour of the strongest average RSSI adopted as sync parent
+    /// (0 adopts the first beacon heard)
+    pub sync_window: u64,
+
+    /// Period of silence after which neighbours are dropped from the neighbour table
+    pub neighbour_timeout: u64,
+
+    /// Number of sources for which the last received sequence number is tracked to
+    /// reject retransmitted frames, up to [`DEFAULT_MAX_SOURCES`] (0 disables rejection)
+    pub dedup_sources: u8,
+
+    /// Period for which the association is retained following desync while attempting
+    /// to re-sync with the coordinator (0 drops the association immediately)
+    pub rejoin_grace: u64,
+
+    /// Initial backoff between orphan notifications while re-syncing,
+    /// doubled on each attempt
+    pub rejoin_backoff: u64,
+
+    /// Maximum backoff between orphan notifications
+    pub rejoin_backoff_max: u64,
+
     /// Timeout for association requests
     pub assoc_timeout: u64,
 
+    /// First short address allocated by coordinators to associated devices
+    pub short_addr_base: ShortAddress,
+
+    /// Keep the receiver enabled while idle, devices without this
+    /// poll their coordinator for pending frames using data requests
+    pub rx_on_when_idle: bool,
+
+    /// Deliver received data frames regardless of the destination address,
+    /// as for passive monitoring of the network
+    pub promiscuous: bool,
+
+    /// Sleep the radio outside the superframe active period (devices only),
+    /// see [`Config::active_duration`]
+    pub duty_cycle: bool,
+
+    /// Time prior to the next expected beacon at which the radio is woken from sleep
+    pub wake_guard: u64,
+
+    /// Time for which coordinators hold frames pending indirect transmission
+    pub transaction_persistence_ms: u64,
+
+    /// Timeout awaiting a pending frame following a data request
+    pub data_request_timeout: u64,
+
     /// Battery life extension flag, allows 0 slot minimum CSMA backoff
     pub battery_life_extension: bool,
 
@@ -47,50 +188,141 @@ pub struct Config {
     /// Delay between packet RX and ACK
     pub ack_delay: u64,
 
+    /// Timeout awaiting an ACK following transmission, after which the packet is retried
+    pub ack_timeout: u64,
+
     /// Minimum backoff exponent
     pub min_be: u8,
     /// Maximum backoff exponent
     pub max_be: u8,
-    /// RSSI threshold for a channel to be determined to be clear
-    pub channel_clear_threshold: i16,
+    /// Clear channel assessment mode
+    pub cca_mode: CcaMode,
     /// Maximum number of backoffs
     pub csma_max_backoffs: u8,
+    /// Queued frames sent consecutively following a CSMA transmission within the CAP,
+    /// each once the previous (and any ACK) completes and CCA passes, held for
+    /// [`Config::ack_delay`] following ACKs. 0 limits CSMA to a single frame per superframe
+    pub cap_burst: u8,
 
     /// Deadline for MAC operations (maximum allowed schedule slip)
     pub mac_deadline: u32,
+
+    /// Only poll the radio following [`crate::Mac::radio_event`], for use where radio
+    /// interrupts are available, otherwise the radio is polled on each tick
+    pub radio_irq: bool,
+    /// Interval (ms) after which the radio is polled without a radio event where
+    /// [`Config::radio_irq`] is set, recovering from missed interrupts
+    pub radio_poll_timeout: Option<u32>,
+    /// Consecutive radio failures (each followed by a radio reset) after which
+    /// [`CoreError::RadioWedged`](crate::error::CoreError::RadioWedged) is reported
+    pub radio_max_failures: u32,
+}
+
+/// Network configuration adopted from the beacons of the sync parent, held apart
+/// from the static [`Config`] so this is reverted on loss of sync
+#[derive(Debug, Clone, PartialEq)]
+pub struct NetworkConfig {
+    pub pan_id: PanId,
+    pub beacon_order: BeaconOrder,
+    pub superframe_order: SuperframeOrder,
+    /// Coordinator is accepting association requests
+    pub association_permit: bool,
+}
+
+impl NetworkConfig {
+    /// Build the network configuration advertised in a beacon superframe specification,
+    /// beacon and superframe orders are only adopted from beacon-enabled PANs
+    pub fn from_beacon<Ch>(
+        config: &Config<Ch>,
+        pan_id: PanId,
+        spec: &SuperframeSpecification,
+    ) -> Self {
+        let (beacon_order, superframe_order) = match spec.beacon_order {
+            BeaconOrder::BeaconOrder(_) => (spec.beacon_order, spec.superframe_order),
+            _ => (config.mac_beacon_order, config.mac_superframe_order),
+        };
+
+        Self {
+            pan_id,
+            beacon_order,
+            superframe_order,
+            association_permit: spec.association_permit,
+        }
+    }
 }
 
-impl Default for Config {
+impl<Ch> Default for Config<Ch> {
     fn default() -> Self {
         Self {
             pan_coordinator: false,
             pan_id: PanId(0x0100),
+            security: None,
+            channel: None,
+            hopping_sequence: None,
+            auto_channel: false,
+            pan_scan: None,
+            pan_conflict_resolve: true,
+            pan_conflict_quiet: 3,
+            scan_channels: Vec::new(),
+            scan_duration: 100,
 
             base_superframe_duration: 1000,
             base_slot_duration: 100,
+            final_cap_slot: 9,
+            gts_request_slots: 0,
+            gts_timeout: 10 * 1000,
+            gts_inactivity_timeout: 60 * 1000,
 
             mac_beacon_order: BeaconOrder::BeaconOrder(1),
             mac_superframe_order: SuperframeOrder::SuperframeOrder(0),
             mac_deadline: 10,
+            radio_irq: false,
+            radio_poll_timeout: None,
+            radio_max_failures: DEFAULT_MAX_FAILURES,
 
             max_beacon_misses: 10,
+            sync_window: 0,
+            drift_window: 4,
+            neighbour_timeout: 60 * 1000,
+            dedup_sources: DEFAULT_MAX_SOURCES as u8,
+            rejoin_grace: 30 * 1000,
+            rejoin_backoff: 1000,
+            rejoin_backoff_max: 8 * 1000,
             assoc_timeout: 10 * 1000,
+            short_addr_base: ShortAddress(0x0001),
+            rx_on_when_idle: true,
+            promiscuous: false,
+            duty_cycle: false,
+            wake_guard: 10,
+            transaction_persistence_ms: 10 * 1000,
+            data_request_timeout: 500,
             battery_life_extension: true,
 
             max_retries: 5,
             ack_delay: 50,
+            ack_timeout: 200,
 
             min_be: 2,
             max_be: 5,
             csma_max_backoffs: 3,
-            channel_clear_threshold: -50,
+            cap_burst: 0,
+            cca_mode: CcaMode::EnergyAboveThreshold {
+                threshold: -50,
+                samples: 1,
+                sample_interval_ms: 0,
+            },
         }
     }
 }
 
-impl Config {
+impl<Ch> Config<Ch> {
     pub fn superframe_duration(&self) -> u32 {
-        match self.mac_beacon_order {
+        self.superframe_duration_for(self.mac_beacon_order)
+    }
+
+    /// Fetch the superframe duration in ms for the provided beacon order
+    pub fn superframe_duration_for(&self, beacon_order: BeaconOrder) -> u32 {
+        match beacon_order {
             BeaconOrder::BeaconOrder(o) => {
                 (self.base_superframe_duration * 2_u32.pow(o as u32)) as u32
             }
@@ -98,6 +330,77 @@ impl Config {
         }
     }
 
+    /// Fetch the duration of the superframe active period in ms,
+    /// the remainder of the superframe is inactive
+    pub fn active_duration(&self) -> u32 {
+        self.active_duration_for(self.mac_beacon_order, self.mac_superframe_order)
+    }
+
+    /// Fetch the active period duration in ms for the provided beacon and superframe orders
+    pub fn active_duration_for(
+        &self,
+        beacon_order: BeaconOrder,
+        superframe_order: SuperframeOrder,
+    ) -> u32 {
+        let active = match superframe_order {
+            SuperframeOrder::SuperframeOrder(o) => {
+                self.base_superframe_duration * 2_u32.pow(o as u32)
+            }
+            _ => 0,
+        };
+
+        active.min(self.superframe_duration_for(beacon_order))
+    }
+
+    /// Check the configuration is valid, with a consistent superframe structure
+    /// and CSMA backoff exponents in range
+    pub fn is_valid(&self) -> bool {
+        let slots = self.base_slot_duration > 0
+            && self.base_superframe_duration >= self.base_slot_duration
+            && self.base_superframe_duration % self.base_slot_duration == 0
+            && (self.final_cap_slot as u64) < self.slots_per_slotframe();
+
+        let orders = match (self.mac_beacon_order, self.mac_superframe_order) {
+            (BeaconOrder::BeaconOrder(b), SuperframeOrder::SuperframeOrder(s)) => s <= b,
+            _ => true,
+        };
+
+        let drift = self.drift_window as usize <= MAX_DRIFT_SAMPLES;
+        let dedup = self.dedup_sources as usize <= DEFAULT_MAX_SOURCES;
+
+        let csma = self.min_be > 0 && self.min_be <= self.max_be && self.max_be < 32;
+
+        let cca = match self.cca_mode {
+            CcaMode::EnergyAboveThreshold { samples, .. } => samples > 0,
+            CcaMode::AlwaysClear => true,
+        };
+
+        let hopping = match &self.hopping_sequence {
+            Some(h) => !h.channels.is_empty(),
+            None => true,
+        };
+
+        slots && orders && drift && dedup && csma && cca && hopping
+    }
+
+    /// Check whether applying the provided configuration requires a MAC restart,
+    /// being changes to the PAN, coordinator role, or superframe structure.
+    /// Other changes may be applied to a running MAC
+    pub fn requires_restart(&self, other: &Self) -> bool
+    where
+        Ch: PartialEq,
+    {
+        self.pan_coordinator != other.pan_coordinator
+            || self.pan_id != other.pan_id
+            || self.mac_beacon_order != other.mac_beacon_order
+            || self.mac_superframe_order != other.mac_superframe_order
+            || self.base_superframe_duration != other.base_superframe_duration
+            || self.base_slot_duration != other.base_slot_duration
+            || self.final_cap_slot != other.final_cap_slot
+            || self.hopping_sequence != other.hopping_sequence
+            || self.rx_on_when_idle != other.rx_on_when_idle
+    }
+
     pub fn superframe_spec(&self) -> SuperframeSpecification {
         SuperframeSpecification {
             beacon_order: self.mac_beacon_order,
@@ -106,7 +409,7 @@ impl Config {
             // TODO: these values are placeholders and need to be correctly set
             battery_life_extension: false,
             association_permit: true,
-            final_cap_slot: 0,
+            final_cap_slot: self.final_cap_slot,
         }
     }
 
@@ -114,6 +417,14 @@ impl Config {
         (self.base_superframe_duration / self.base_slot_duration) as u64
     }
 
+    /// Fetch the first and last slots of the CFP
+    pub fn cfp_slots(&self) -> (u8, u8) {
+        (
+            self.final_cap_slot + 1,
+            self.slots_per_slotframe().saturating_sub(1) as u8,
+        )
+    }
+
     pub fn calculate_sfn(&self, now: u64, offset: u64) -> u64 {
         (now + offset) / self.superframe_duration() as u64
     }
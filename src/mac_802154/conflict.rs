@@ -0,0 +1,162 @@
+//! 802.15.4 PAN ID Conflict Resolution
+//!
+//! PAN coordinators detect conflicts on receipt of beacons from other coordinators
+//! advertising the same PAN ID, reported via [`MacEvent::PanConflict`]. Where
+//! [`Config::pan_conflict_resolve`](super::Config::pan_conflict_resolve) is set, the
+//! coordinator then selects a random PAN ID, listens to verify this is unused for
+//! [`Config::pan_conflict_quiet`](super::Config::pan_conflict_quiet) superframes, and
+//! moves to the new PAN ID, announcing this to associated devices via a broadcast
+//! coordinator realignment. Devices update their PAN ID (and addresses) on receipt
+//! of a realignment from their parent, reported via [`MacEvent::Realigned`].
+//
+// https://github.com/rust-iot/rust-lpwan
+// Copyright 2021 Ryan Kurte
+
+use ieee802154::mac::command::{Command, CoordinatorRealignmentData};
+use ieee802154::mac::{Address, PanId, ShortAddress};
+use rand_core::{OsRng, RngCore};
+
+use crate::log::{debug, error, info, warn};
+use crate::{timer::Timer, MacEvent, Radio};
+
+use super::{Aead, AssocState, Mac, Packet, SyncState};
+
+/// Short address indicating a device does not have a short address allocated
+const NO_SHORT_ADDR: ShortAddress = ShortAddress(0xfffe);
+
+/// Replace the PAN ID of an address
+pub(crate) fn with_pan_id(addr: Address, pan_id: PanId) -> Address {
+    match addr {
+        Address::Short(_, s) => Address::Short(pan_id, s),
+        Address::Extended(_, e) => Address::Extended(pan_id, e),
+        Address::None => Address::None,
+    }
+}
+
+impl<R, T, S, const TXQ: usize, const RXQ: usize> Mac<R, T, S, TXQ, RXQ>
+where
+    R: Radio,
+    T: Timer,
+    S: Aead,
+{
+    /// Check a beacon received by a PAN coordinator for PAN ID conflicts,
+    /// prior to PAN ID filtering
+    pub(crate) fn check_pan_conflict(&mut self, now_ms: u64, pan_id: PanId, source: &Address) {
+        // Restart the quiet period where the candidate PAN ID is also in use
+        if let Some((candidate, _)) = self.pan_resolve {
+            if pan_id == candidate {
+                let candidate = self.pan_id_candidate();
+                debug!(
+                    "Candidate PAN ID in use by {:?}, selected {:?}",
+                    source, candidate
+                );
+                self.pan_resolve = Some((candidate, now_ms + self.pan_conflict_quiet()));
+            }
+            return;
+        }
+
+        if pan_id != self.config.pan_id {
+            return;
+        }
+
+        warn!("PAN ID {:?} conflict with {:?}", pan_id, source);
+        self.stats.pan_conflicts = self.stats.pan_conflicts.saturating_add(1);
+        self.push_event(MacEvent::PanConflict);
+
+        if self.config.pan_conflict_resolve {
+            let candidate = self.pan_id_candidate();
+            info!(
+                "Resolving PAN ID conflict, listening for use of {:?}",
+                candidate
+            );
+            self.pan_resolve = Some((candidate, now_ms + self.pan_conflict_quiet()));
+        }
+    }
+
+    /// Move to the candidate PAN ID once verified unused, announcing this to
+    /// associated devices via coordinator realignment
+    pub(crate) fn tick_pan_conflict(&mut self, now_ms: u64) {
+        let pan_id = match self.pan_resolve {
+            Some((pan_id, until)) if now_ms >= until => pan_id,
+            _ => return,
+        };
+        self.pan_resolve = None;
+
+        info!(
+            "Moving from PAN ID {:?} to {:?} at {} ms",
+            self.config.pan_id, pan_id, now_ms
+        );
+
+        // Realignment is sent under the previous PAN ID so devices accept this
+        let realign = Command::CoordinatorRealignment(CoordinatorRealignmentData {
+            pan_id,
+            coordinator_address: self.short_addr.unwrap_or(NO_SHORT_ADDR),
+            channel: 0,
+            device_address: ShortAddress::broadcast(),
+            channel_page: None,
+        });
+        let mut p = Packet::command(
+            Address::Short(self.config.pan_id, ShortAddress::broadcast()),
+            self.addr(),
+            self.seq(),
+            realign,
+        );
+        p.header.ack_request = false;
+
+        if self.enqueue_tx(p).is_err() {
+            error!("Error adding coordinator realignment to tx buffer");
+        }
+
+        self.config.pan_id = pan_id;
+        self.assoc_state = AssocState::Associated(pan_id);
+        self.stats.realignments = self.stats.realignments.saturating_add(1);
+
+        self.push_event(MacEvent::Realigned(self.addr()));
+    }
+
+    /// Apply a coordinator realignment received from the sync parent,
+    /// updating the PAN ID and short address
+    pub(crate) fn realign(&mut self, parent: Address, r: &CoordinatorRealignmentData) {
+        info!(
+            "Coordinator realignment from {:?} (PAN: {:?})",
+            parent, r.pan_id
+        );
+
+        self.config.pan_id = r.pan_id;
+        if let Some(n) = self.network.as_mut() {
+            n.pan_id = r.pan_id;
+        }
+        if let AssocState::Associated(_) = self.assoc_state {
+            self.assoc_state = AssocState::Associated(r.pan_id);
+        }
+        if r.device_address != NO_SHORT_ADDR && r.device_address != ShortAddress::broadcast() {
+            self.short_addr = Some(r.device_address);
+        }
+
+        // Parent addresses include the PAN ID so must follow the new PAN
+        let parent = with_pan_id(parent, r.pan_id);
+        self.sync_state = SyncState::Synced(parent);
+        if self.join_target.is_some() {
+            self.join_target = Some(parent);
+        }
+
+        self.stats.realignments = self.stats.realignments.saturating_add(1);
+
+        self.push_event(MacEvent::Realigned(self.addr()));
+    }
+
+    /// Select a random PAN ID for conflict resolution
+    fn pan_id_candidate(&self) -> PanId {
+        loop {
+            let pan_id = PanId(OsRng {}.next_u32() as u16);
+            if pan_id != self.config.pan_id && pan_id != PanId::broadcast() {
+                return pan_id;
+            }
+        }
+    }
+
+    /// Fetch the period over which candidate PAN IDs must be unused
+    fn pan_conflict_quiet(&self) -> u64 {
+        self.config.pan_conflict_quiet as u64 * self.superframe_duration() as u64
+    }
+}
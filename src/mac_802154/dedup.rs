@@ -0,0 +1,113 @@
+//! 802.15.4 Duplicate Frame Rejection
+//!
+//! Where an ACK is lost the sender retransmits the same frame, so the last
+//! sequence number seen from each source is tracked to suppress re-delivery
+//! of these while still acknowledging them so the sender stops retrying.
+//
+// https://github.com/rust-iot/rust-lpwan
+// Copyright 2021 Ryan Kurte
+
+use heapless::Vec;
+use ieee802154::mac::Address;
+
+/// Default number of tracked sources
+pub const DEFAULT_MAX_SOURCES: usize = 8;
+
+/// Table of the last sequence number received from each source,
+/// ordered from least to most recently used
+#[derive(Debug, Clone, PartialEq)]
+pub struct DuplicateTable<const N: usize = DEFAULT_MAX_SOURCES> {
+    entries: Vec<(Address, u8), N>,
+}
+
+impl<const N: usize> Default for DuplicateTable<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize> DuplicateTable<N> {
+    /// Create a new (empty) duplicate table
+    pub fn new() -> Self {
+        Self {
+            entries: Vec::new(),
+        }
+    }
+
+    /// Check whether the provided sequence number matches the last seen from a source
+    pub fn is_duplicate(&self, source: &Address, seq: u8) -> bool {
+        self.entries.iter().any(|(a, s)| a == source && *s == seq)
+    }
+
+    /// Record the sequence number of a received frame,
+    /// replacing the least recently used source where the table is full
+    pub fn record(&mut self, source: Address, seq: u8) {
+        self.record_within(source, seq, N)
+    }
+
+    /// Record the sequence number of a received frame, tracking at most `limit`
+    /// sources and replacing the least recently used beyond this
+    pub fn record_within(&mut self, source: Address, seq: u8, limit: usize) {
+        let limit = limit.min(N);
+
+        if let Some(i) = self.entries.iter().position(|(a, _)| a == &source) {
+            self.entries.remove(i);
+        }
+        while !self.entries.is_empty() && self.entries.len() >= limit {
+            self.entries.remove(0);
+        }
+
+        if limit > 0 {
+            let _ = self.entries.push((source, seq));
+        }
+    }
+
+    /// Fetch the number of tracked sources
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use ieee802154::mac::{ExtendedAddress, PanId, ShortAddress};
+
+    use super::*;
+
+    #[test]
+    fn duplicate_table() {
+        let a = Address::Short(PanId(1), ShortAddress(1));
+        let b = Address::Extended(PanId(1), ExtendedAddress(2));
+        let c = Address::Short(PanId(1), ShortAddress(3));
+
+        let mut t = DuplicateTable::<2>::new();
+        assert!(!t.is_duplicate(&a, 1));
+
+        t.record(a, 1);
+        t.record(b, 1);
+        assert!(t.is_duplicate(&a, 1));
+        assert!(t.is_duplicate(&b, 1));
+        assert!(!t.is_duplicate(&a, 2));
+
+        // Only the last sequence number is retained per source
+        t.record(a, 2);
+        assert!(!t.is_duplicate(&a, 1));
+        assert!(t.is_duplicate(&a, 2));
+
+        // Full tables replace the least recently used source
+        t.record(c, 1);
+        assert_eq!(t.len(), 2);
+        assert!(!t.is_duplicate(&b, 1));
+        assert!(t.is_duplicate(&a, 2));
+        assert!(t.is_duplicate(&c, 1));
+
+        // Tracking may be limited below the table capacity
+        t.record_within(b, 1, 1);
+        assert_eq!(t.len(), 1);
+        assert!(t.is_duplicate(&b, 1));
+        assert!(!t.is_duplicate(&c, 1));
+
+        t.record_within(a, 3, 0);
+        assert_eq!(t.len(), 0);
+    }
+}
@@ -0,0 +1,127 @@
+//! 802.15.4 Clock Drift Estimation
+//!
+//! Crystal tolerances cause the local clock to drift relative to that of the sync
+//! parent, which with long beacon intervals may exceed the guard time applied when
+//! waking for beacons. The arrival times of recent beacons are tracked to fit the
+//! rate of this drift (in ppm), allowing the local clock to be compensated between
+//! beacons rather than only stepped on receipt.
+//
+// https://github.com/rust-iot/rust-lpwan
+// Copyright 2021 Ryan Kurte
+
+use heapless::Vec;
+
+/// Maximum number of beacon arrivals used for drift estimation
+pub const MAX_DRIFT_SAMPLES: usize = 8;
+
+/// Beacon arrival times for estimating the drift of the local clock
+#[derive(Debug, Clone, PartialEq)]
+pub struct DriftEstimator<const N: usize = MAX_DRIFT_SAMPLES> {
+    samples: Vec<u64, N>,
+}
+
+impl<const N: usize> Default for DriftEstimator<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize> DriftEstimator<N> {
+    /// Create a new (empty) drift estimator
+    pub fn new() -> Self {
+        Self {
+            samples: Vec::new(),
+        }
+    }
+
+    /// Record the local arrival time of a beacon,
+    /// retaining at most `window` of the most recent arrivals
+    pub fn record(&mut self, rx_time: u64, window: usize) {
+        let window = window.min(N);
+        if window == 0 {
+            self.samples.clear();
+            return;
+        }
+
+        while self.samples.len() >= window {
+            self.samples.remove(0);
+        }
+
+        let _ = self.samples.push(rx_time);
+    }
+
+    /// Fit the drift of the local clock in ppm from recorded arrivals and the nominal
+    /// beacon period, positive where the local clock runs fast. Arrivals are assigned
+    /// to the nearest beacon period, so missed beacons do not disrupt the estimate
+    pub fn ppm(&self, period: u64) -> Option<i32> {
+        let t0 = *self.samples.first()?;
+        if self.samples.len() < 2 || period == 0 {
+            return None;
+        }
+
+        // Least squares fit of arrival time against beacon index
+        let period = period as i128;
+        let (mut sx, mut sy, mut sxx, mut sxy) = (0i128, 0i128, 0i128, 0i128);
+        for t in self.samples.iter() {
+            let y = (t - t0) as i128;
+            let x = (y + period / 2) / period;
+
+            sx += x;
+            sy += y;
+            sxx += x * x;
+            sxy += x * y;
+        }
+
+        let n = self.samples.len() as i128;
+        let den = n * sxx - sx * sx;
+        if den == 0 {
+            return None;
+        }
+        let num = n * sxy - sx * sy;
+
+        // Deviation of the fitted period from nominal
+        let ppm = (num * 1_000_000 - period * 1_000_000 * den) / (den * period);
+
+        Some(ppm.max(i32::MIN as i128).min(i32::MAX as i128) as i32)
+    }
+
+    /// Clear recorded arrivals, such as on loss of sync
+    pub fn clear(&mut self) {
+        self.samples.clear();
+    }
+
+    /// Fetch the number of recorded arrivals
+    pub fn len(&self) -> usize {
+        self.samples.len()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn drift_estimate() {
+        let mut d = DriftEstimator::<4>::new();
+        assert_eq!(d.ppm(1000), None);
+
+        // Arrivals 1 ms late each second, with the local clock 1000 ppm fast
+        d.record(0, 4);
+        assert_eq!(d.ppm(1000), None);
+        d.record(1001, 4);
+        assert_eq!(d.ppm(1000), Some(1000));
+
+        // Missed beacons are assigned to the appropriate period
+        d.record(3003, 4);
+        assert_eq!(d.ppm(1000), Some(1000));
+
+        // Only the most recent arrivals are retained
+        d.record(4002, 4);
+        d.record(4998, 4);
+        assert_eq!(d.len(), 4);
+        assert!(d.ppm(1000).unwrap() < 0);
+
+        d.clear();
+        assert_eq!(d.ppm(1000), None);
+    }
+}
@@ -0,0 +1,289 @@
+//! 802.15.4 Guaranteed Time Slots
+//!
+//! Coordinators allocate slots in the contention free period (CFP) to
+//! devices on request, advertising these via the beacon GTS descriptor list.
+//! Devices then transmit during their granted slots without CSMA, with
+//! allocations released where no frames are received from the device for
+//! [`Config::gts_inactivity_timeout`](super::Config::gts_inactivity_timeout).
+//
+// https://github.com/rust-iot/rust-lpwan
+// Copyright 2021 Ryan Kurte
+
+use heapless::Vec;
+use ieee802154::mac::beacon::GuaranteedTimeSlotInformation;
+use ieee802154::mac::ShortAddress;
+
+/// Maximum number of GTS descriptors in a beacon
+pub const MAX_GTS_DESCRIPTORS: usize = 7;
+
+/// Encoded GTS field length (spec byte + directions byte + descriptors)
+const GTS_INFO_LEN: usize = 2 + MAX_GTS_DESCRIPTORS * 3;
+
+/// Guaranteed time slot direction, relative to the requesting device
+#[derive(Debug, Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum GtsDirection {
+    /// Slots for transmission from the device to the coordinator
+    Transmit,
+    /// Slots for receipt by the device from the coordinator
+    Receive,
+}
+
+/// Guaranteed time slot allocation, slots are relative to the start of the superframe
+#[derive(Debug, Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct GtsSlot {
+    /// Device short address
+    pub short: ShortAddress,
+    /// First allocated slot
+    pub start: u8,
+    /// Number of allocated slots
+    pub length: u8,
+}
+
+impl GtsSlot {
+    /// Check whether a slot falls within this allocation
+    pub fn contains(&self, slot: u8) -> bool {
+        slot >= self.start && slot < self.start + self.length
+    }
+
+    /// Check whether two allocations overlap
+    pub fn overlaps(&self, other: &GtsSlot) -> bool {
+        self.start < other.start + other.length && other.start < self.start + self.length
+    }
+}
+
+/// GTS allocation table, used by coordinators to manage the CFP
+#[derive(Debug, Clone, PartialEq)]
+pub struct GtsTable {
+    /// Allocations with the time of the most recent activity from each device
+    slots: Vec<(GtsSlot, u64), MAX_GTS_DESCRIPTORS>,
+}
+
+impl Default for GtsTable {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl GtsTable {
+    /// Create a new (empty) GTS table
+    pub fn new() -> Self {
+        Self { slots: Vec::new() }
+    }
+
+    /// Allocate contiguous slots between `first` and `last` (inclusive) for a device,
+    /// returning the existing allocation if the device already holds one.
+    ///
+    /// Returns `None` if no suitable slots are available.
+    pub fn allocate(
+        &mut self,
+        short: ShortAddress,
+        length: u8,
+        first: u8,
+        last: u8,
+        now_ms: u64,
+    ) -> Option<GtsSlot> {
+        if let Some(s) = self.lookup(&short).copied() {
+            self.active(&short, now_ms);
+            return Some(s);
+        }
+
+        if length == 0 || self.slots.is_full() {
+            return None;
+        }
+
+        // First-fit search for free slots
+        let mut start = first;
+        while start as u16 + length as u16 <= last as u16 + 1 {
+            let candidate = GtsSlot {
+                short,
+                start,
+                length,
+            };
+
+            let overlap = self.iter().find(|s| s.overlaps(&candidate)).copied();
+            match overlap {
+                Some(s) => start = s.start + s.length,
+                None => {
+                    let _ = self.slots.push((candidate, now_ms));
+                    return Some(candidate);
+                }
+            }
+        }
+
+        None
+    }
+
+    /// Remove a device allocation
+    pub fn remove(&mut self, short: &ShortAddress) -> Option<GtsSlot> {
+        let i = self.slots.iter().position(|(s, _)| &s.short == short)?;
+        Some(self.slots.swap_remove(i).0)
+    }
+
+    /// Record activity from a device, maintaining any allocation
+    pub fn active(&mut self, short: &ShortAddress, now_ms: u64) {
+        if let Some((_s, t)) = self.slots.iter_mut().find(|(s, _)| &s.short == short) {
+            *t = now_ms;
+        }
+    }
+
+    /// Remove an allocation without activity since the provided time, if any
+    pub fn expire(&mut self, before: u64) -> Option<GtsSlot> {
+        let i = self.slots.iter().position(|(_s, t)| *t < before)?;
+        Some(self.slots.swap_remove(i).0)
+    }
+
+    /// Fetch the time of the least recent activity over allocations
+    pub fn oldest_activity(&self) -> Option<u64> {
+        self.slots.iter().map(|(_s, t)| *t).min()
+    }
+
+    /// Lookup a device allocation
+    pub fn lookup(&self, short: &ShortAddress) -> Option<&GtsSlot> {
+        self.iter().find(|s| &s.short == short)
+    }
+
+    /// Iterate over allocations
+    pub fn iter(&self) -> impl Iterator<Item = &GtsSlot> {
+        self.slots.iter().map(|(s, _t)| s)
+    }
+
+    /// Build a beacon GTS field for current allocations
+    pub fn info(&self, permit: bool) -> GuaranteedTimeSlotInformation {
+        gts_info(permit, self.iter())
+    }
+}
+
+/// Build a beacon GTS field from a list of allocations.
+///
+/// As with pending addresses this is constructed via the wire format
+/// (spec byte, direction mask, then short address and start / length per descriptor),
+/// with descriptors beyond the field capacity omitted.
+pub fn gts_info<'a>(
+    permit: bool,
+    slots: impl Iterator<Item = &'a GtsSlot>,
+) -> GuaranteedTimeSlotInformation {
+    let mut buff = [0u8; GTS_INFO_LEN];
+
+    // Descriptors follow the (transmit only) direction mask
+    let mut n = 2;
+    let mut count = 0;
+    for s in slots.take(MAX_GTS_DESCRIPTORS) {
+        n += s.short.encode(&mut buff[n..]);
+        buff[n] = (s.start & 0x0f) | (s.length & 0x0f) << 4;
+        n += 1;
+        count += 1;
+    }
+
+    buff[0] = count as u8 | (permit as u8) << 7;
+
+    // Direction mask is omitted where there are no descriptors
+    let buff = match count {
+        0 => &buff[..1],
+        _ => &buff[..n],
+    };
+
+    match GuaranteedTimeSlotInformation::decode(buff) {
+        Ok((g, _)) => g,
+        Err(_) => GuaranteedTimeSlotInformation::new(),
+    }
+}
+
+/// Fetch the allocation for a device from a beacon GTS field
+pub fn gts_slot(info: &GuaranteedTimeSlotInformation, short: ShortAddress) -> Option<GtsSlot> {
+    let mut buff = [0u8; GTS_INFO_LEN];
+    let n = info.encode(&mut buff);
+    if n == 0 {
+        return None;
+    }
+
+    let count = (buff[0] & 0b0111) as usize;
+
+    let mut offset = 2;
+    for _ in 0..count {
+        let (s, len) = ShortAddress::decode(&buff[offset..n]).ok()?;
+        offset += len;
+
+        let b = *buff.get(offset)?;
+        offset += 1;
+
+        if s == short && (b >> 4) > 0 {
+            return Some(GtsSlot {
+                short: s,
+                start: b & 0x0f,
+                length: b >> 4,
+            });
+        }
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn allocate() {
+        let mut t = GtsTable::new();
+
+        // CFP of slots 6..=9
+        let a = t.allocate(ShortAddress(1), 2, 6, 9, 0).unwrap();
+        assert_eq!(a.start, 6);
+        let b = t.allocate(ShortAddress(2), 1, 6, 9, 0).unwrap();
+        assert_eq!(b.start, 8);
+
+        // Existing allocation returned
+        assert_eq!(t.allocate(ShortAddress(1), 1, 6, 9, 0), Some(a));
+
+        // Insufficient space
+        assert_eq!(t.allocate(ShortAddress(3), 2, 6, 9, 0), None);
+
+        // Removal frees slots
+        t.remove(&ShortAddress(1)).unwrap();
+        let c = t.allocate(ShortAddress(3), 2, 6, 9, 0).unwrap();
+        assert_eq!(c.start, 6);
+    }
+
+    #[test]
+    fn expire() {
+        let mut t = GtsTable::new();
+
+        t.allocate(ShortAddress(1), 1, 6, 9, 10).unwrap();
+        let b = t.allocate(ShortAddress(2), 1, 6, 9, 20).unwrap();
+        assert_eq!(t.oldest_activity(), Some(10));
+
+        // Activity maintains allocations
+        t.active(&ShortAddress(1), 30);
+        assert_eq!(t.oldest_activity(), Some(20));
+        assert_eq!(t.expire(20), None);
+
+        // Inactive allocations are removed
+        assert_eq!(t.expire(25), Some(b));
+        assert_eq!(t.expire(25), None);
+        assert!(t.lookup(&ShortAddress(1)).is_some());
+    }
+
+    #[test]
+    fn info_round_trip() {
+        let slots = [
+            GtsSlot {
+                short: ShortAddress(1),
+                start: 6,
+                length: 2,
+            },
+            GtsSlot {
+                short: ShortAddress(2),
+                start: 8,
+                length: 1,
+            },
+        ];
+
+        let info = gts_info(true, slots.iter());
+
+        assert_eq!(gts_slot(&info, ShortAddress(1)), Some(slots[0]));
+        assert_eq!(gts_slot(&info, ShortAddress(2)), Some(slots[1]));
+        assert_eq!(gts_slot(&info, ShortAddress(3)), None);
+    }
+}
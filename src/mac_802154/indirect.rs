@@ -0,0 +1,214 @@
+//! 802.15.4 Indirect Transmission
+//!
+//! Coordinators hold frames for devices that do not enable their receiver
+//! while idle, advertising these via the beacon pending address field until
+//! the device polls for them with a data request command.
+//
+// https://github.com/rust-iot/rust-lpwan
+// Copyright 2021 Ryan Kurte
+
+use heapless::Vec;
+use ieee802154::mac::beacon::PendingAddress;
+use ieee802154::mac::{Address, ExtendedAddress, ShortAddress};
+
+use super::{Packet, TxState};
+use crate::Ts;
+
+/// Maximum number of short (or extended) addresses in a pending address field
+pub const MAX_PENDING_ADDRESSES: usize = 7;
+
+/// Encoded pending address field length (spec byte + 7 short + 7 extended addresses)
+const PENDING_ADDRESS_LEN: usize = 1 + MAX_PENDING_ADDRESSES * (2 + 8);
+
+/// Queue of frames held for indirect transmission
+#[derive(Debug, Clone, PartialEq)]
+pub struct IndirectQueue<const N: usize = 4> {
+    frames: Vec<(Ts, TxState, Packet), N>,
+}
+
+impl<const N: usize> Default for IndirectQueue<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize> IndirectQueue<N> {
+    /// Create a new (empty) indirect queue
+    pub fn new() -> Self {
+        Self { frames: Vec::new() }
+    }
+
+    /// Add a frame to the queue, to be held until the provided expiry time
+    pub fn push(
+        &mut self,
+        expires: Ts,
+        state: TxState,
+        packet: Packet,
+    ) -> Result<(), (Ts, TxState, Packet)> {
+        self.frames.push((expires, state, packet))
+    }
+
+    /// Take the oldest frame with a destination matching the provided filter
+    pub fn take(&mut self, matches: impl Fn(&Address) -> bool) -> Option<(Ts, TxState, Packet)> {
+        let i = self
+            .frames
+            .iter()
+            .enumerate()
+            .filter(|(_, f)| matches(&f.2.header.destination))
+            .min_by_key(|(_, f)| f.0)
+            .map(|(i, _)| i)?;
+
+        Some(self.frames.swap_remove(i))
+    }
+
+    /// Check whether a frame is held with a destination matching the provided filter
+    pub fn contains(&self, matches: impl Fn(&Address) -> bool) -> bool {
+        self.frames.iter().any(|f| matches(&f.2.header.destination))
+    }
+
+    /// Remove a frame with an expiry prior to the provided time, if any
+    pub fn expire(&mut self, now: Ts) -> Option<(TxState, Packet)> {
+        let i = self.frames.iter().position(|f| f.0 < now)?;
+
+        let (_, state, packet) = self.frames.swap_remove(i);
+        Some((state, packet))
+    }
+
+    /// Fetch the earliest expiry of queued frames
+    pub fn next_expiry(&self) -> Option<Ts> {
+        self.frames.iter().map(|f| f.0).min()
+    }
+
+    /// Build a beacon pending address field for queued frames
+    pub fn pending_address(&self) -> PendingAddress {
+        pending_address(self.frames.iter().map(|f| &f.2.header.destination))
+    }
+
+    /// Fetch the number of queued frames
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    /// Check whether the queue is full
+    pub fn is_full(&self) -> bool {
+        self.frames.is_full()
+    }
+}
+
+/// Build a pending address field from a list of destination addresses.
+///
+/// `PendingAddress` does not expose mutators, so this is constructed via the
+/// wire format (spec byte with short / extended counts, followed by short
+/// then extended addresses), with addresses beyond the field capacity omitted.
+pub fn pending_address<'a>(addresses: impl Iterator<Item = &'a Address>) -> PendingAddress {
+    let mut short: Vec<ShortAddress, MAX_PENDING_ADDRESSES> = Vec::new();
+    let mut extended: Vec<ExtendedAddress, MAX_PENDING_ADDRESSES> = Vec::new();
+
+    for a in addresses {
+        match a {
+            Address::Short(_, s) if !short.contains(s) => {
+                let _ = short.push(*s);
+            }
+            Address::Extended(_, e) if !extended.contains(e) => {
+                let _ = extended.push(*e);
+            }
+            _ => (),
+        }
+    }
+
+    let mut buff = [0u8; PENDING_ADDRESS_LEN];
+    buff[0] = short.len() as u8 | (extended.len() as u8) << 4;
+
+    let mut n = 1;
+    for s in short.iter() {
+        n += s.encode(&mut buff[n..]);
+    }
+    for e in extended.iter() {
+        n += e.encode(&mut buff[n..]);
+    }
+
+    match PendingAddress::decode(&buff[..n]) {
+        Ok((p, _)) => p,
+        Err(_) => PendingAddress::new(),
+    }
+}
+
+/// Check whether a pending address field lists the provided device addresses
+pub fn is_pending(
+    pending: &PendingAddress,
+    short: Option<ShortAddress>,
+    extended: ExtendedAddress,
+) -> bool {
+    let mut buff = [0u8; PENDING_ADDRESS_LEN];
+    let n = pending.encode(&mut buff);
+    if n == 0 {
+        return false;
+    }
+
+    let short_count = (buff[0] & 0b0111) as usize;
+    let extended_count = ((buff[0] >> 4) & 0b0111) as usize;
+
+    let mut offset = 1;
+    for _ in 0..short_count {
+        let s = match ShortAddress::decode(&buff[offset..n]) {
+            Ok((s, len)) => {
+                offset += len;
+                s
+            }
+            Err(_) => return false,
+        };
+        if Some(s) == short {
+            return true;
+        }
+    }
+
+    for _ in 0..extended_count {
+        let e = match ExtendedAddress::decode(&buff[offset..n]) {
+            Ok((e, len)) => {
+                offset += len;
+                e
+            }
+            Err(_) => return false,
+        };
+        if e == extended {
+            return true;
+        }
+    }
+
+    false
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use ieee802154::mac::PanId;
+
+    #[test]
+    fn pending_address_round_trip() {
+        let pan_id = PanId(1);
+        let addrs = [
+            Address::Short(pan_id, ShortAddress(0x0001)),
+            Address::Extended(pan_id, ExtendedAddress(0x1122)),
+            Address::Short(pan_id, ShortAddress(0x0001)),
+        ];
+
+        let p = pending_address(addrs.iter());
+
+        assert!(is_pending(
+            &p,
+            Some(ShortAddress(0x0001)),
+            ExtendedAddress(0x10)
+        ));
+        assert!(is_pending(&p, None, ExtendedAddress(0x1122)));
+        assert!(!is_pending(
+            &p,
+            Some(ShortAddress(0x0002)),
+            ExtendedAddress(0x10)
+        ));
+        assert!(!is_pending(
+            &PendingAddress::new(),
+            None,
+            ExtendedAddress(0x1122)
+        ));
+    }
+}
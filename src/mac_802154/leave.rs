@@ -0,0 +1,265 @@
+//! 802.15.4 Disassociation and Shutdown
+//!
+//! Devices leave the PAN via [`Mac::disassociate`], sending a disassociation
+//! notification to their coordinator then, once this is acknowledged (or fails),
+//! dropping sync and association and parking the radio. Coordinators remove devices
+//! from their association, route and neighbour tables on receipt of a notification,
+//! and may evict devices via [`Mac::evict`], with evicted devices leaving as if
+//! disassociating themselves. Both are reported via [`MacEvent::Disassociated`].
+//!
+//! [`Mac::shutdown`] drops pending frames and parks the radio ahead of deep sleep.
+//! Parked MACs are idle until [`Mac::resume`], which restarts operation.
+//
+// https://github.com/rust-iot/rust-lpwan
+// Copyright 2021 Ryan Kurte
+
+use ieee802154::mac::command::{Command, DisassociationReason};
+use ieee802154::mac::Address;
+
+use crate::error::CoreError;
+use crate::log::{debug, info};
+use crate::{timer::Timer, MacEvent, Radio, TxFailure, TxHandle, TxStatus};
+
+use super::{
+    AckState, AckWait, Aead, AssocState, Association, CsmaState, GtsState, Mac, Packet, SleepState,
+    SyncState,
+};
+
+/// Disassociation state, see [`Mac::disassociate`]
+#[derive(Debug, Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum LeaveState {
+    None,
+    /// Awaiting the outcome of the notification sent to the provided coordinator
+    Notifying(Address, TxHandle),
+    /// Evicted by the provided coordinator, leaving once the notification is acknowledged
+    Evicted(Address),
+}
+
+impl<R, T, S, const TXQ: usize, const RXQ: usize> Mac<R, T, S, TXQ, RXQ>
+where
+    R: Radio,
+    T: Timer,
+    S: Aead,
+{
+    /// Leave the PAN, sending a disassociation notification with the provided reason
+    /// to the coordinator. Once this is acknowledged (or fails, as reported for the
+    /// returned handle) sync and association are dropped, queued frames are dropped
+    /// and the radio is parked until [`Mac::resume`].
+    ///
+    /// Returns [`CoreError::NotAssociated`] where not associated, or
+    /// [`CoreError::InvalidConfig`] for PAN coordinators (see [`Mac::evict`])
+    pub fn disassociate(
+        &mut self,
+        reason: DisassociationReason,
+    ) -> Result<TxHandle, CoreError<<R as Radio>::Error>> {
+        if self.config.pan_coordinator {
+            return Err(CoreError::InvalidConfig);
+        }
+
+        let parent = match (self.sync_state, self.assoc_state) {
+            (SyncState::Synced(parent), AssocState::Associated(_)) => parent,
+            _ => return Err(CoreError::NotAssociated),
+        };
+
+        // Only one notification may be outstanding
+        if self.leave != LeaveState::None {
+            return Err(CoreError::Busy);
+        }
+
+        let now = self.timer.ticks_ms();
+        let cmd = Command::DisassociationNotification(reason);
+        let packet = Packet::command(parent, self.addr(), self.seq(), cmd);
+
+        // Leave regardless where the notification is not sent in time
+        let expires = now + self.config.assoc_timeout;
+        let handle = self.enqueue_tx_until(packet, Some(expires), None)?;
+
+        info!("Disassociating from {:?} at {} ms", parent, now);
+        self.leave = LeaveState::Notifying(parent, handle);
+
+        Ok(handle)
+    }
+
+    /// Evict a device associated with this coordinator, sending a disassociation
+    /// notification (held for indirect transmission where the device polls for data)
+    /// and removing the device from the association, route and neighbour tables.
+    ///
+    /// Returns [`CoreError::InvalidConfig`] where this is not a coordinator, or
+    /// [`CoreError::NotAssociated`] where the device is not associated
+    pub fn evict(&mut self, device: &Address) -> Result<TxHandle, CoreError<<R as Radio>::Error>> {
+        if !self.config.pan_coordinator {
+            return Err(CoreError::InvalidConfig);
+        }
+
+        let assoc = match self.assoc_table.lookup_address(device) {
+            Some(a) => *a,
+            None => return Err(CoreError::NotAssociated),
+        };
+
+        // Sent to the short address, as used by the device when polling for data,
+        // so this is released once the association is removed
+        let dest = Address::Short(self.pan_id(), assoc.short);
+        let cmd = Command::DisassociationNotification(DisassociationReason::CoordinatorLeave);
+        let packet = Packet::command(dest, self.addr(), self.seq(), cmd);
+
+        let handle = match assoc.rx_on_when_idle {
+            true => self.enqueue_tx(packet)?,
+            false => self.enqueue_indirect(packet, None, None)?,
+        };
+
+        info!("Evicting device {:?}", device);
+        self.remove_device(&assoc);
+
+        Ok(handle)
+    }
+
+    /// Stop operation ahead of deep sleep, dropping queued and held frames (reported
+    /// as [`TxFailure::Cancelled`]) and parking the radio until [`Mac::resume`].
+    ///
+    /// Returns the number of frames dropped undelivered, or [`CoreError::Busy`]
+    /// where a radio operation is in progress, in which case nothing is dropped and
+    /// shutdown may be retried following [`MacIf::tick`](crate::Mac::tick)
+    pub fn shutdown(&mut self) -> Result<usize, CoreError<<R as Radio>::Error>> {
+        if self.base.is_busy() {
+            return Err(CoreError::Busy);
+        }
+
+        let now = self.timer.ticks_ms();
+        info!("Shutting down MAC at {} ms", now);
+
+        self.leave = LeaveState::None;
+        self.park(now)
+    }
+
+    /// Resume operation of a MAC parked by [`Mac::shutdown`] or [`Mac::disassociate`],
+    /// restarting as for [`Mac::reconfigure`] (reported via [`MacEvent::Restarted`])
+    pub fn resume(&mut self) -> Result<(), CoreError<<R as Radio>::Error>> {
+        if !self.parked {
+            return Ok(());
+        }
+
+        let now = self.timer.ticks_ms();
+        info!("Resuming MAC at {} ms", now);
+
+        self.reset();
+        if !self.config.pan_coordinator {
+            self.short_addr = None;
+        }
+        self.start(now)?;
+
+        self.push_event(MacEvent::Restarted(self.addr()));
+
+        Ok(())
+    }
+
+    /// Check whether the MAC is parked, see [`Mac::shutdown`]
+    pub fn is_parked(&self) -> bool {
+        self.parked
+    }
+
+    /// Handle a disassociation notification, from devices leaving this coordinator
+    /// or from our parent where evicted
+    pub(crate) fn handle_disassociation(&mut self, source: Address) {
+        // Devices leaving this coordinator, with frames held for these dropped
+        if let Some(assoc) = self.assoc_table.lookup_address(&source).cloned() {
+            info!("Device {:?} disassociated", source);
+            self.remove_device(&assoc);
+
+            while let Some((_t, s, _p)) = self.indirect.take(|a| assoc.matches(a)) {
+                self.tx_done(s.handle, TxStatus::Failed(TxFailure::Cancelled));
+            }
+
+            self.push_event(MacEvent::Disassociated(source));
+            return;
+        }
+
+        match (self.sync_state, self.assoc_state) {
+            (SyncState::Synced(parent), AssocState::Associated(_))
+                if parent == source && !self.config.pan_coordinator =>
+            {
+                info!("Evicted by coordinator {:?}", parent);
+                self.leave = LeaveState::Evicted(parent);
+            }
+            _ => debug!("Ignoring disassociation notification from {:?}", source),
+        }
+    }
+
+    /// Complete disassociation once the notification is sent (or fails), and any
+    /// acknowledgement of an eviction is sent
+    pub(crate) fn tick_leave(&mut self, now_ms: u64) -> Result<(), CoreError<<R as Radio>::Error>> {
+        let parent = match self.leave {
+            LeaveState::None => return Ok(()),
+            LeaveState::Notifying(_, h) if self.tx_buff.iter().any(|(s, _)| s.handle == h) => {
+                return Ok(())
+            }
+            LeaveState::Notifying(parent, _) | LeaveState::Evicted(parent) => parent,
+        };
+
+        if self.base.is_busy() || self.ack_state != AckState::None {
+            return Ok(());
+        }
+
+        info!("Disassociated from {:?} at {} ms", parent, now_ms);
+
+        self.sync_state = SyncState::Unsynced;
+        self.network = None;
+        self.assoc_state = AssocState::Unassociated;
+        self.short_addr = None;
+        self.gts_state = GtsState::None;
+        self.hopping = None;
+        self.join_target = None;
+
+        self.park(now_ms)?;
+        self.leave = LeaveState::None;
+
+        self.push_event(MacEvent::Disassociated(parent));
+
+        Ok(())
+    }
+
+    /// Remove a departed device from the association, route, GTS and neighbour tables,
+    /// along with routes to devices reached via this
+    fn remove_device(&mut self, assoc: &Association) {
+        let _ = self.assoc_table.remove(&assoc.extended);
+        let _ = self.routes.remove(&assoc.extended);
+        let _ = self.gts_table.remove(&assoc.short);
+
+        let pan_id = self.pan_id();
+        self.routes.remove_via(&Address::Short(pan_id, assoc.short));
+        self.routes
+            .remove_via(&Address::Extended(pan_id, assoc.extended));
+
+        self.neighbours.remove(|a| assoc.matches(a));
+    }
+
+    /// Put the radio to sleep and drop queued and held frames, returning the number
+    /// of frames dropped. The MAC is then idle until [`Mac::resume`]
+    fn park(&mut self, now_ms: u64) -> Result<usize, CoreError<<R as Radio>::Error>> {
+        self.base.sleep(now_ms)?;
+        self.parked = true;
+
+        let mut dropped = 0;
+        while let Some((s, _p)) = self.tx_buff.dequeue() {
+            self.tx_done(s.handle, TxStatus::Failed(TxFailure::Cancelled));
+            dropped += 1;
+        }
+        while let Some((_t, s, _p)) = self.indirect.take(|_| true) {
+            self.tx_done(s.handle, TxStatus::Failed(TxFailure::Cancelled));
+            dropped += 1;
+        }
+
+        self.csma_state = CsmaState::None;
+        self.burst = 0;
+        self.ack_state = AckState::None;
+        self.ack_wait = AckWait::None;
+        self.sleep_state = SleepState::Awake;
+        self.data_request = None;
+
+        if dropped > 0 {
+            debug!("Dropped {} pending frames", dropped);
+        }
+
+        Ok(dropped)
+    }
+}
@@ -6,32 +6,89 @@
 use core::fmt::Debug;
 use core::ops::Deref;
 
-use ieee802154::mac::beacon::{Beacon, BeaconOrder, GuaranteedTimeSlotInformation, PendingAddress};
-use ieee802154::mac::command::{AssociationStatus, CapabilityInformation, Command};
-use ieee802154::mac::{Address, ExtendedAddress, FrameContent, PanId, ShortAddress, WriteFooter};
+use ieee802154::mac::beacon::{Beacon, BeaconOrder, SuperframeSpecification};
+use ieee802154::mac::command::{
+    AssociationStatus, CapabilityInformation, Command, CoordinatorRealignmentData,
+    GuaranteedTimeSlotCharacteristics,
+};
+use ieee802154::mac::{
+    Address, AddressMode, ExtendedAddress, FrameContent, PanId, ShortAddress, WriteFooter,
+};
 use radio::{RadioState, Receive, ReceiveInfo, State};
 
 use crate::log::{debug, error, info, trace, warn};
 use heapless::spsc::Queue;
+use heapless::Vec;
 
 use rand_core::{OsRng, RngCore};
 
-use crate::base::{Base, BaseState};
-use crate::{error::CoreError, timer::Timer, Mac as MacIf, MacState, Radio, RawPacket, RxInfo};
+use crate::base::{Base, BaseState, BaseStats, RadioEvent};
+use crate::pcap::PcapSink;
+use crate::{
+    error::{BufferError, CoreError},
+    timer::Timer,
+    Mac as MacIf, MacEvent, MacState, Radio, RawPacket, RxInfo, StackStats, Ts, TxFailure,
+    TxHandle, TxStatus,
+};
 
 pub mod config;
-pub use config::Config;
+pub use config::{CcaMode, Config, HoppingSequence, NetworkConfig};
 
 pub mod packet;
 pub use packet::Packet;
 
 pub mod channels;
+use channels::HoppingInfo;
+
+pub mod scan;
+pub use scan::{PanDescriptor, Scan, ScanKind, ScanResult};
+
+pub mod assoc;
+pub use assoc::{AssocTable, Association};
+
+pub mod routes;
+pub use routes::{Route, RouteTable};
+
+pub mod indirect;
+pub use indirect::IndirectQueue;
+
+pub mod txq;
+pub use txq::{TxPriority, TxQueue, DEFAULT_MAX_CONTROL, DEFAULT_MAX_DATA};
+
+pub mod gts;
+pub use gts::{GtsDirection, GtsSlot, GtsTable};
+
+pub mod dedup;
+pub use dedup::DuplicateTable;
+
+pub mod neighbours;
+pub use neighbours::{Neighbour, NeighbourTable, Relation};
+
+pub mod beacons;
+pub use beacons::{BeaconContent, BeaconPayload, BeaconTable, MAX_BEACON_PAYLOAD_LEN};
+
+pub mod persist;
+pub use persist::{Persist, Snapshot};
+
+pub mod conflict;
+
+pub mod leave;
+use leave::LeaveState;
+
+pub mod drift;
+pub use drift::{DriftEstimator, MAX_DRIFT_SAMPLES};
+
+pub mod security;
+use security::SecurityState;
+pub use security::{Aead, NoSecurity, SecurityConfig, SecurityError};
 
 #[derive(Debug, Copy, Clone, PartialEq, strum::Display)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub enum SyncState {
     Unsynced,
     Synced(Address),
+    /// Sync lost with the parent, retaining the association until the provided expiry
+    Lost(Address, u64),
 }
 
 impl SyncState {
@@ -41,6 +98,14 @@ impl SyncState {
             _ => false,
         }
     }
+
+    /// Fetch the sync parent, including while attempting to re-sync
+    pub fn parent(&self) -> Option<&Address> {
+        match self {
+            SyncState::Synced(p) | SyncState::Lost(p, _) => Some(p),
+            SyncState::Unsynced => None,
+        }
+    }
 }
 
 #[derive(Debug, Copy, Clone, PartialEq, strum::Display)]
@@ -60,18 +125,73 @@ impl AssocState {
     }
 }
 
+/// Radio sleep state, for devices duty cycling between superframe active periods
+#[derive(Debug, Copy, Clone, PartialEq, strum::Display)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum SleepState {
+    Awake,
+    /// Asleep from the first time until the scheduled wake time
+    Asleep(u64, u64),
+    /// Held awake until the provided time, following an early wake
+    Held(u64),
+}
+
+/// Superframe period of a slot, see [`MacTiming`]
+#[derive(Debug, Copy, Clone, PartialEq, strum::Display)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum SlotPeriod {
+    /// Contention access period
+    Cap,
+    /// Contention free period, in which guaranteed time slots are allocated
+    Gts,
+    /// Inactive period following the superframe active period
+    Inactive,
+}
+
+/// Superframe timing, see [`Mac::timing`]
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct MacTiming {
+    /// Absolute slot number
+    pub asn: u64,
+    /// Relative slot number within the slotframe
+    pub rsn: u64,
+    /// Superframe number
+    pub sfn: u64,
+    /// Time until the next beacon (transmitted or expected) in ms, where scheduled
+    pub next_beacon_ms: Option<u64>,
+    /// Period of the current slot
+    pub period: SlotPeriod,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, strum::Display)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum GtsState {
+    None,
+    Pending(u64),
+    Granted(GtsSlot),
+}
+
 #[derive(Debug, Clone, PartialEq)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub struct TxState {
+    pub handle: TxHandle,
     pub pending: bool,
     pub retries: u8,
+    /// Deadline after which the frame is dropped rather than transmitted
+    pub expires: Option<Ts>,
+    /// Transmit priority, otherwise selected by frame type (see [`TxPriority::of`])
+    pub priority: Option<TxPriority>,
 }
 
 impl Default for TxState {
     fn default() -> Self {
         Self {
+            handle: 0,
             pending: true,
             retries: 0,
+            expires: None,
+            priority: None,
         }
     }
 }
@@ -82,18 +202,49 @@ pub enum CsmaState {
         packet: Packet,
         tx_slot: u64,
         retries: u64,
+        cca: CcaState,
     },
 }
 
+/// Clear channel assessment state for a pending CSMA transmission
+#[derive(Debug, Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum CcaState {
+    /// Sampling the channel, with the number of clear samples and time of the last sample
+    Sampling { samples: u8, last_ms: Option<u64> },
+    /// Channel determined to be clear
+    Clear,
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum AckState {
     None,
     Pending { packet: Packet, tx_time: u64 },
 }
 
-#[derive(Debug, Clone, PartialEq)]
+/// ACK wait state, for transmitted packets requesting acknowledgement
+#[derive(Debug, Copy, Clone, PartialEq)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
-pub enum MacEvent {}
+pub enum AckWait {
+    None,
+    Pending { seq: u8, tx_time: u64 },
+}
+
+/// Maximum number of beacon sources tracked during the sync window
+const MAX_SYNC_CANDIDATES: usize = 4;
+
+/// Radio buffer length for encoding frames, frames exceeding this are dropped
+const TX_BUFF_LEN: usize = 255;
+
+/// Beacon source heard during the sync window, see [`Config::sync_window`]
+#[derive(Debug, Clone, PartialEq)]
+struct SyncCandidate {
+    source: Address,
+    /// Time of the most recent beacon from the source
+    rx_time: u64,
+    network: NetworkConfig,
+    hopping: Option<HoppingInfo>,
+}
 
 #[derive(Debug, Clone, PartialEq)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
@@ -103,6 +254,58 @@ pub struct MacStats {
     pub csma_cca_fail: u32,
     pub tx_fail: u32,
     pub sync_fail: u32,
+    pub indirect_expired: u32,
+    /// Received frames failing security processing
+    pub security_fail: u32,
+    /// Sync recovered with the parent following sync loss
+    pub resyncs: u32,
+    /// Orphan notifications issued while attempting to re-sync
+    pub rejoin_attempts: u32,
+    /// Retransmitted frames suppressed on receipt
+    pub rx_duplicates: u32,
+    /// Radio sleeps between superframe active periods
+    pub sleeps: u32,
+    /// Time spent with the radio asleep in ms
+    pub sleep_ms: u64,
+    /// PAN ID conflicts detected with other coordinators
+    pub pan_conflicts: u32,
+    /// Coordinator realignments processed, including PAN ID changes
+    /// following conflict resolution
+    pub realignments: u32,
+    /// Queued frames dropped on passing their transmit deadline
+    pub tx_expired: u32,
+    /// Association requests received from devices already associated
+    pub duplicate_joins: u32,
+    /// Frames sent, and acknowledged where requested
+    pub tx_ok: u32,
+    /// Frames received and accepted
+    pub rx_ok: u32,
+    /// Received frames dropped on decode errors
+    pub rx_drop_decode: u32,
+    /// Received frames dropped on PAN ID mismatch
+    pub rx_drop_pan: u32,
+    /// Received frames dropped as not addressed to this device
+    pub rx_drop_addr: u32,
+    /// Received data frames dropped without receive buffer space
+    pub rx_queue_full: u32,
+    /// Minimum RSSI sampled during clear channel assessment
+    pub cca_rssi_min: Option<i16>,
+    /// Maximum RSSI sampled during clear channel assessment
+    pub cca_rssi_max: Option<i16>,
+    /// Acknowledgements sent
+    pub tx_acks: u32,
+    /// Acknowledgements dropped on radio errors
+    pub tx_ack_fail: u32,
+    /// Frames dropped on failing to encode within the radio buffer
+    pub tx_drop_encode: u32,
+    /// Acknowledgements received for pending transmissions
+    pub rx_acks: u32,
+    /// Beacons sent
+    pub tx_beacons: u32,
+    /// Beacons received
+    pub rx_beacons: u32,
+    /// Radio frame, byte and timing statistics
+    pub radio: BaseStats,
 }
 
 impl MacStats {
@@ -113,36 +316,137 @@ impl MacStats {
             csma_cca_fail: 0,
             tx_fail: 0,
             sync_fail: 0,
+            indirect_expired: 0,
+            security_fail: 0,
+            resyncs: 0,
+            rejoin_attempts: 0,
+            rx_duplicates: 0,
+            sleeps: 0,
+            sleep_ms: 0,
+            pan_conflicts: 0,
+            realignments: 0,
+            tx_expired: 0,
+            duplicate_joins: 0,
+            tx_ok: 0,
+            rx_ok: 0,
+            rx_drop_decode: 0,
+            rx_drop_pan: 0,
+            rx_drop_addr: 0,
+            rx_queue_full: 0,
+            cca_rssi_min: None,
+            cca_rssi_max: None,
+            tx_acks: 0,
+            tx_ack_fail: 0,
+            tx_drop_encode: 0,
+            rx_acks: 0,
+            tx_beacons: 0,
+            rx_beacons: 0,
+            radio: BaseStats::default(),
         }
     }
+
+    /// Reset all counters
+    pub fn reset(&mut self) {
+        *self = Self::new();
+    }
 }
 
+/// Default number of received frames queued for [`MacIf::receive`]
+pub const DEFAULT_MAX_RX: usize = 4;
+
+/// 802.15.4 MAC, queueing up to `TXQ` data frames for transmission (in addition
+/// to [`DEFAULT_MAX_CONTROL`] control frames) and `RXQ` received frames
 #[derive(Debug, Clone, PartialEq)]
-pub struct Mac<R, T, const N: usize = 4> {
+pub struct Mac<
+    R: Radio,
+    T,
+    S = NoSecurity,
+    const TXQ: usize = DEFAULT_MAX_DATA,
+    const RXQ: usize = DEFAULT_MAX_RX,
+> {
     pub address: ExtendedAddress,
     pub short_addr: Option<ShortAddress>,
 
-    config: Config,
+    config: Config<<R as Radio>::Channel>,
+    /// Network configuration adopted from the sync parent, overriding `config` while synced
+    network: Option<NetworkConfig>,
     base: Base<R>,
     timer: T,
+    aead: S,
+
+    /// Current radio channel, if set
+    channel: Option<<R as Radio>::Channel>,
+    /// Offset from local to coordinator ASN while following the hopping sequence
+    hopping: Option<u64>,
+    /// Energy detection scan, retained on completion for collection of results
+    scan: Option<Scan<<R as Radio>::Channel>>,
+    /// Coordinator selected via [`Mac::join`], beacons from others are not adopted
+    join_target: Option<Address>,
+    /// End of the sync window, with beacon sources heard during the window
+    sync_window: Option<u64>,
+    sync_candidates: Vec<SyncCandidate, MAX_SYNC_CANDIDATES>,
 
     seq: u8,
     sync_offset: u64,
     sync_correction: i64,
+    /// Arrivals of parent beacons for estimating clock drift
+    drift: DriftEstimator,
+    /// Time to which drift compensation has been applied, with the remainder (ns)
+    drift_at: u64,
+    drift_rem: i64,
     last_asn: u64,
 
     next_beacon: u64,
     beacon_miss_count: u32,
+    /// Time of the next orphan notification while sync is lost
+    rejoin_at: u64,
+    /// Backoff following the next orphan notification
+    rejoin_backoff: u64,
+    /// Candidate PAN ID while resolving a PAN ID conflict, with the end of the quiet period
+    pan_resolve: Option<(PanId, u64)>,
 
     sync_state: SyncState,
     assoc_state: AssocState,
     csma_state: CsmaState,
+    /// ASN of the last CAP start, with CCA sampled in the remainder of the slot
+    cap_asn: u64,
+    /// Frames remaining in the current CAP burst, see [`Config::cap_burst`]
+    burst: u8,
+    /// Time before which burst frames are held following an ACK, allowing
+    /// the receiver to complete the ACK and return to receive
+    burst_hold: u64,
     ack_state: AckState,
+    ack_wait: AckWait,
+    gts_state: GtsState,
+    last_gts_asn: u64,
+    sleep_state: SleepState,
+    /// Disassociation in progress, see [`Mac::disassociate`]
+    leave: LeaveState,
+    /// Radio parked until [`Mac::resume`], see [`Mac::shutdown`]
+    parked: bool,
 
     stats: MacStats,
-
-    rx_buff: Queue<(RxInfo, Packet), 4>,
-    tx_buff: Queue<(TxState, Packet), 4>,
+    security: SecurityState,
+
+    assoc_table: AssocTable,
+    /// Downstream routes to devices beneath this coordinator
+    routes: RouteTable,
+    indirect: IndirectQueue,
+    data_request: Option<u64>,
+    gts_table: GtsTable,
+    rx_seen: DuplicateTable,
+    neighbours: NeighbourTable,
+    /// Application payload carried in beacons, see [`Mac::set_beacon_payload`]
+    beacon_payload: Vec<u8, MAX_BEACON_PAYLOAD_LEN>,
+    /// Beacon payloads heard from coordinators
+    beacons: BeaconTable,
+
+    next_handle: TxHandle,
+    tx_results: Queue<(TxHandle, TxStatus), 8>,
+    events: Queue<MacEvent<Address>, 4>,
+
+    rx_buff: Vec<(RxInfo, Packet), RXQ>,
+    tx_buff: TxQueue<DEFAULT_MAX_CONTROL, TXQ>,
 }
 
 impl<R, T> Mac<R, T>
@@ -156,54 +460,266 @@ where
     /// This takes control of the provided radio, and uses the provided timer for operation timekeeping
     pub fn new(
         address: ExtendedAddress,
-        config: Config,
+        config: Config<<R as Radio>::Channel>,
+        radio: R,
+        timer: T,
+    ) -> Result<Self, CoreError<<R as Radio>::Error>> {
+        Self::with_aead(address, config, radio, timer, NoSecurity)
+    }
+}
+
+impl<R, T, S> Mac<R, T, S>
+where
+    R: Radio,
+    <R as State>::State: RadioState + Debug,
+    <R as Receive>::Info: ReceiveInfo + Debug + Default,
+    T: Timer,
+    S: Aead,
+{
+    /// Setup the MAC with the provided [`Aead`] implementation for frame security,
+    /// returning [`CoreError::InvalidConfig`] where the configuration is not valid
+    /// (see [`Config::is_valid`])
+    pub fn with_aead(
+        address: ExtendedAddress,
+        config: Config<<R as Radio>::Channel>,
         radio: R,
         timer: T,
+        aead: S,
     ) -> Result<Self, CoreError<<R as Radio>::Error>> {
+        Self::with_queues(address, config, radio, timer, aead)
+    }
+}
+
+impl<R, T, S, const TXQ: usize, const RXQ: usize> Mac<R, T, S, TXQ, RXQ>
+where
+    R: Radio,
+    <R as State>::State: RadioState + Debug,
+    <R as Receive>::Info: ReceiveInfo + Debug + Default,
+    T: Timer,
+    S: Aead,
+{
+    /// Setup the MAC with the provided [`Aead`] implementation and queue depths,
+    /// as for [`Mac::with_aead`]. Depths are selected via the type, for example
+    /// `Mac::<_, _, NoSecurity, 4, 2>::with_queues(..)`
+    pub fn with_queues(
+        address: ExtendedAddress,
+        config: Config<<R as Radio>::Channel>,
+        radio: R,
+        timer: T,
+        aead: S,
+    ) -> Result<Self, CoreError<<R as Radio>::Error>> {
+        if !config.is_valid() {
+            return Err(CoreError::InvalidConfig);
+        }
+
+        let short_addr_base = config.short_addr_base;
+
         let mut s = Self {
             address,
             short_addr: None,
             config,
+            network: None,
 
             base: Base::new(radio)?,
             timer,
+            aead,
+
+            channel: None,
+            hopping: None,
+            scan: None,
+            join_target: None,
+            sync_window: None,
+            sync_candidates: Vec::new(),
 
             seq: 0,
             sync_offset: 0,
             sync_correction: 0,
+            drift: DriftEstimator::new(),
+            drift_at: 0,
+            drift_rem: 0,
             last_asn: 0,
             next_beacon: 0,
             beacon_miss_count: 0,
+            rejoin_at: 0,
+            rejoin_backoff: 0,
+            pan_resolve: None,
 
             sync_state: SyncState::Unsynced,
             assoc_state: AssocState::Unassociated,
             csma_state: CsmaState::None,
+            cap_asn: 0,
+            burst: 0,
+            burst_hold: 0,
             ack_state: AckState::None,
+            ack_wait: AckWait::None,
+            gts_state: GtsState::None,
+            last_gts_asn: 0,
+            sleep_state: SleepState::Awake,
+            leave: LeaveState::None,
+            parked: false,
 
             stats: MacStats::new(),
-
-            rx_buff: Queue::new(),
-            tx_buff: Queue::new(),
+            security: SecurityState::new(),
+
+            assoc_table: AssocTable::with_base(short_addr_base),
+            routes: RouteTable::new(),
+            indirect: IndirectQueue::new(),
+            data_request: None,
+            gts_table: GtsTable::new(),
+            rx_seen: DuplicateTable::new(),
+            neighbours: NeighbourTable::new(),
+            beacon_payload: Vec::new(),
+            beacons: BeaconTable::new(),
+
+            next_handle: 0,
+            tx_results: Queue::new(),
+            events: Queue::new(),
+
+            rx_buff: Vec::new(),
+            tx_buff: TxQueue::new(),
         };
 
         let now = s.timer.ticks_ms();
         s.sync_offset = now;
+        s.configure_base();
 
         debug!("Setup MAC with address {:?} at {} ms", s.address, now);
 
-        if s.config.pan_coordinator && s.config.mac_beacon_order != BeaconOrder::OnDemand {
-            s.next_beacon = now + s.config.superframe_duration() as u64;
-            debug!("Setup next beacon for {} ms", s.next_beacon);
+        s.start(now)?;
+
+        Ok(s)
+    }
+
+    /// Apply a new configuration to the running MAC.
+    ///
+    /// CSMA, retry, threshold and deadline changes apply immediately, while changes
+    /// requiring a restart (see [`Config::requires_restart`]) drop sync, association,
+    /// and pending CSMA and ACK operations prior to resuming under the new configuration,
+    /// reported via [`MacEvent::Restarted`]
+    pub fn reconfigure(
+        &mut self,
+        config: Config<<R as Radio>::Channel>,
+    ) -> Result<(), CoreError<<R as Radio>::Error>> {
+        if !config.is_valid() {
+            return Err(CoreError::InvalidConfig);
+        }
+
+        let restart = self.config.requires_restart(&config);
+
+        // Defer restarts until in-progress radio operations complete
+        if restart && self.base.is_busy() {
+            return Err(CoreError::Busy);
+        }
+
+        // Short addresses of devices are allocated on association, so released on restart
+        let allocated = !self.config.pan_coordinator;
+
+        self.config = config;
+        self.configure_base();
+
+        if !restart {
+            debug!("Applied configuration changes");
+            return Ok(());
+        }
+
+        let now = self.timer.ticks_ms();
+        info!("Restarting MAC at {} ms to apply configuration", now);
+
+        self.reset();
+        if allocated {
+            self.short_addr = None;
+        }
+        self.start(now)?;
+
+        self.push_event(MacEvent::Restarted(self.addr()));
+
+        Ok(())
+    }
+
+    /// Apply radio interrupt and recovery configuration to the base,
+    /// see [`Config::radio_irq`] and [`Config::radio_max_failures`]
+    fn configure_base(&mut self) {
+        let timeout = self.config.radio_poll_timeout.map(|t| t as u64);
+        self.base.set_event_driven(self.config.radio_irq, timeout);
+        self.base.set_max_failures(self.config.radio_max_failures);
+    }
+
+    /// Reset sync, association and pending operations prior to restart
+    fn reset(&mut self) {
+        self.sync_state = SyncState::Unsynced;
+        self.network = None;
+        self.assoc_state = AssocState::Unassociated;
+        self.join_target = None;
+        self.sync_window = None;
+        self.sync_candidates.clear();
+
+        self.next_beacon = 0;
+        self.beacon_miss_count = 0;
+        self.sync_correction = 0;
+        self.drift.clear();
+        self.drift_rem = 0;
+        self.hopping = None;
+        self.scan = None;
+        self.pan_resolve = None;
+
+        self.csma_state = CsmaState::None;
+        self.burst = 0;
+        self.ack_state = AckState::None;
+        self.ack_wait = AckWait::None;
+        self.gts_state = GtsState::None;
+        self.sleep_state = SleepState::Awake;
+        self.data_request = None;
+        self.leave = LeaveState::None;
+        self.parked = false;
+
+        // Associations with and frames pending for children of the previous
+        // configuration no longer apply
+        self.assoc_table = AssocTable::with_base(self.config.short_addr_base);
+        self.routes.clear();
+        self.gts_table = GtsTable::new();
+        while let Some((_t, s, _p)) = self.indirect.take(|_| true) {
+            self.tx_done(s.handle, TxStatus::Failed(TxFailure::Expired));
+        }
+    }
+
+    /// Start operation under the current configuration
+    fn start(&mut self, now: u64) -> Result<(), CoreError<<R as Radio>::Error>> {
+        if self.config.pan_coordinator && self.config.mac_beacon_order != BeaconOrder::OnDemand {
+            self.next_beacon = now + self.config.superframe_duration() as u64;
+            debug!("Setup next beacon for {} ms", self.next_beacon);
+        }
+
+        if self.config.pan_coordinator {
+            self.assoc_state = AssocState::Associated(self.config.pan_id);
+        }
+
+        // Coordinators define the hopping sequence so follow this from the start
+        if self.config.pan_coordinator && self.config.hopping_sequence.is_some() {
+            self.hopping = Some(0);
         }
 
-        if s.config.pan_coordinator {
-            s.assoc_state = AssocState::Associated(s.config.pan_id);
+        // Coordinators select a channel by scanning prior to the first beacon,
+        // devices may scan for PANs prior to joining
+        let scan = match (self.config.pan_coordinator, self.config.pan_scan) {
+            (true, _) if self.config.auto_channel => Some(ScanKind::Energy),
+            (false, Some(kind)) => Some(kind),
+            _ => None,
+        };
+        if let Some(kind) = scan {
+            let mut scan =
+                Scan::new(kind, &self.config.scan_channels, self.config.scan_duration).unwrap();
+            scan.select = kind == ScanKind::Energy;
+
+            match scan.is_done() {
+                true => warn!("No scan channels configured for {:?} scan", kind),
+                false => self.scan = Some(scan),
+            }
         }
 
         debug!("Set radio to receive mode");
-        s.base.receive(now)?;
+        self.base.receive(now)?;
 
-        Ok(s)
+        Ok(())
     }
 
     pub fn ticks_ms(&self) -> u64 {
@@ -211,29 +727,51 @@ where
     }
 }
 
-impl<R, T> MacIf<Address> for Mac<R, T>
+impl<R, T, S, const TXQ: usize, const RXQ: usize> MacIf<Address> for Mac<R, T, S, TXQ, RXQ>
 where
     R: Radio,
     T: Timer,
+    S: Aead,
 {
     type Error = CoreError<<R as Radio>::Error>;
 
     fn state(&self) -> Result<MacState<Address>, Self::Error> {
+        if self.scanning() {
+            return Ok(MacState::Scanning);
+        }
+
+        if let SleepState::Asleep(..) = self.sleep_state {
+            return Ok(MacState::Sleep);
+        }
+        if self.parked {
+            return Ok(MacState::Sleep);
+        }
+
         match (self.sync_state, self.assoc_state) {
+            // Started coordinators define the PAN rather than syncing to a parent
+            (_, AssocState::Associated(_)) if self.config.pan_coordinator => {
+                Ok(MacState::Associated(self.addr()))
+            }
             (SyncState::Synced(addr), AssocState::Associated(_)) => Ok(MacState::Associated(addr)),
             (SyncState::Synced(addr), _) => Ok(MacState::Synced(addr)),
-            (SyncState::Unsynced, _) => Ok(MacState::Disconnected),
+            (SyncState::Unsynced, _) | (SyncState::Lost(..), _) => Ok(MacState::Disconnected),
         }
     }
 
     fn tick(&mut self) -> Result<(), Self::Error> {
         let now_ms = self.timer.ticks_ms();
 
+        // Parked MACs are idle until resumed
+        if self.parked {
+            return Ok(());
+        }
+
         let last_sync_state = self.sync_state.clone();
 
-        let sfn = self.config.calculate_sfn(now_ms, self.sync_offset);
-        let asn = self.config.calculate_asn(now_ms, self.sync_offset);
-        let rsn = self.config.calculate_rsn(now_ms, self.sync_offset);
+        // Compensate for drift of the local clock relative to the sync parent
+        self.tick_drift(now_ms);
+
+        let MacTiming { asn, rsn, sfn, .. } = self.timing(now_ms);
 
         trace!(
             "Tick at {} ms with ASN: {} (SFN: {} RSN: {})",
@@ -243,14 +781,29 @@ where
             rsn
         );
 
-        // Update base radio interface
-        // TODO: come up with a mechanism for propagating radio state changes
-        // so we don't have to always poll on the radio?
+        // Wake from sleep ahead of the next beacon, or early for pending transmissions
+        self.tick_wake(now_ms)?;
+
+        // Update base radio interface, only following radio events where interrupt driven
         if let Some(rx) = self.base.tick(now_ms)? {
             // Handle received packets
             self.handle_received(now_ms, rx)?;
         }
 
+        // Energy detection scans take priority over normal operation
+        if self.scanning() {
+            return self.tick_scan(now_ms);
+        }
+
+        // Adopt the strongest sync parent once the sync window closes
+        self.tick_sync_window(now_ms);
+
+        // Move to a new PAN ID once verified unused following a conflict
+        self.tick_pan_conflict(now_ms);
+
+        // Select the channel for this slot
+        self.tick_channel(now_ms)?;
+
         // Compute state based on slot
         // TODO: refactor this out so that the slot selector can be unit tested
 
@@ -267,9 +820,10 @@ where
             self.tick_beacon(now_ms, asn)?;
         }
 
-        // Transmit ACKs if scheduled
+        // Transmit ACKs if scheduled, deferring while the radio is busy
+        // (such as with an in-progress beacon transmission)
         match self.ack_state.clone() {
-            AckState::Pending { packet, tx_time } if tx_time < now_ms => {
+            AckState::Pending { packet, tx_time } if tx_time < now_ms && !self.base.is_busy() => {
                 if now_ms > (tx_time + self.config.mac_deadline as u64) {
                     warn!("ACK TX deadline exceeded by {} ms", now_ms - tx_time);
                     self.stats.deadline_miss_ack = self.stats.deadline_miss_ack.saturating_add(1);
@@ -280,12 +834,89 @@ where
                     packet.header.seq, packet.header.destination, now_ms
                 );
 
-                let mut buff = [0u8; 256];
-                let n = packet.encode(&mut buff, WriteFooter::No);
+                self.ack_state = AckState::None;
 
-                self.base.transmit(now_ms, &buff[..n])?;
+                let mut buff = [0u8; TX_BUFF_LEN];
+                if let Some(n) = self.encode_tx(&packet, &mut buff) {
+                    match self.base.transmit(now_ms, &buff[..n]) {
+                        Ok(()) => {
+                            self.stats.tx_acks = self.stats.tx_acks.saturating_add(1);
+                        }
+                        Err(_) => {
+                            warn!("ACK TX failed for packet {}, dropped", packet.header.seq);
+                            self.stats.tx_ack_fail = self.stats.tx_ack_fail.saturating_add(1);
+                        }
+                    }
+                }
+            }
+            _ => (),
+        }
 
-                self.ack_state = AckState::None;
+        // Retry packets not acknowledged in time
+        match self.ack_wait {
+            AckWait::Pending { seq, tx_time } if now_ms > tx_time + self.config.ack_timeout => {
+                debug!("ACK timeout for packet {} at {} ms", seq, now_ms);
+                self.ack_wait = AckWait::None;
+
+                if let Some((_, p)) = self.tx_buff.peek() {
+                    self.neighbours.ack_missed(&p.header.destination);
+                }
+
+                // Packets for our GTS are retried in the next granted slot
+                if let Some(tx) = self
+                    .tx_buff
+                    .peek()
+                    .filter(|(_, p)| !self.gts_dest(p))
+                    .cloned()
+                {
+                    self.csma_schedule(asn, &tx);
+                }
+            }
+            _ => (),
+        }
+
+        // Age out neighbours not heard from recently
+        let (sync_state, assoc_table) = (&self.sync_state, &self.assoc_table);
+        self.neighbours
+            .update_relations(|a| Self::relation(sync_state, assoc_table, a));
+        let expired = self
+            .neighbours
+            .expire(now_ms.saturating_sub(self.config.neighbour_timeout));
+        if expired > 0 {
+            debug!("Expired {} neighbours at {} ms", expired, now_ms);
+        }
+
+        // Drop pending indirect frames not requested in time
+        while let Some((s, p)) = self.indirect.expire(now_ms) {
+            warn!(
+                "Pending packet {} for {:?} expired",
+                p.header.seq, p.header.destination
+            );
+            self.stats.indirect_expired = self.stats.indirect_expired.saturating_add(1);
+            self.tx_done(s.handle, TxStatus::Failed(TxFailure::Expired));
+        }
+
+        // Deallocate guaranteed time slots of inactive devices
+        let gts_before = now_ms.saturating_sub(self.config.gts_inactivity_timeout);
+        while let Some(s) = self.gts_table.expire(gts_before) {
+            info!("Deallocated GTS slots for inactive device {:?}", s.short);
+        }
+
+        // Drop queued frames not sent by their deadline
+        while let Some((s, p)) = self.tx_buff.expire(now_ms) {
+            warn!(
+                "Queued packet {} for {:?} expired",
+                p.header.seq, p.header.destination
+            );
+            self.stats.tx_expired = self.stats.tx_expired.saturating_add(1);
+            self.tx_done(s.handle, TxStatus::Failed(TxFailure::Expired));
+        }
+
+        // Timeout outstanding data requests
+        match self.data_request {
+            Some(expiry) if now_ms > expiry => {
+                debug!("Data request timeout at {} ms", now_ms);
+                self.data_request = None;
             }
             _ => (),
         }
@@ -293,10 +924,19 @@ where
         // TODO: CSMA operations take place during Contention Access Period (CAP), starting from the beacon frame
         self.tick_cap(now_ms, asn)?;
 
-        // TODO: Collision free operations occupy the rest of the slot
+        // Collision free operations occupy the rest of the slot
+        self.tick_cfp(now_ms, asn)?;
+
+        // Complete disassociation, parking the radio
+        self.tick_leave(now_ms)?;
+        if self.parked {
+            return Ok(());
+        }
 
         // TODO: Handle state changes
         match (self.sync_state.clone(), self.assoc_state.clone()) {
+            // Associations are refused locally where not permitted by the coordinator
+            (SyncState::Synced(_parent), AssocState::Unassociated) if !self.assoc_permitted() => (),
             // On sync, attempt association
             (SyncState::Synced(parent), AssocState::Unassociated) => {
                 let assoc_cmd = Command::AssociationRequest(CapabilityInformation {
@@ -305,20 +945,27 @@ where
                     frame_protection: false,
                     full_function_device: true,
                     mains_power: false,
-                    idle_receive: false,
+                    idle_receive: self.config.rx_on_when_idle,
                 });
 
                 let assoc = Packet::command(parent, self.addr(), self.seq(), assoc_cmd);
 
-                // TODO: handle error
-                if let Err(_) = self.tx_buff.enqueue((TxState::default(), assoc)) {
-                    error!("Error adding associate request to tx buffer");
-                }
-
-                info!("Received network sync, issuing association request");
+                match self.enqueue_tx(assoc) {
+                    Ok(_) => {
+                        info!("Received network sync, issuing association request");
 
-                self.assoc_state =
-                    AssocState::Pending(parent.clone(), now_ms + self.config.assoc_timeout);
+                        self.assoc_state =
+                            AssocState::Pending(parent.clone(), now_ms + self.config.assoc_timeout);
+                    }
+                    // Remain unassociated to retry once queued frames are sent
+                    Err(CoreError::BufferFull) => {
+                        debug!("TX buffer full, deferring association request");
+                    }
+                    Err(e) => {
+                        error!("Error adding associate request to tx buffer");
+                        return Err(e);
+                    }
+                }
             }
             // Timeout pending associations
             (SyncState::Synced(_parent), AssocState::Pending(_assoc_parent, expiry)) => {
@@ -328,19 +975,48 @@ where
                     self.assoc_state = AssocState::Unassociated;
                 }
             }
-            // IDK
-            (SyncState::Synced(_parent), AssocState::Associated(_pan_id)) => {}
-            // Drop association on de-sync?
-            // TODO: do we want to do this or, attempt to re-sync first?
+            // Request guaranteed time slots once associated
+            (SyncState::Synced(parent), AssocState::Associated(_pan_id)) => {
+                self.tick_gts_request(now_ms, parent);
+            }
+            // Attempt to re-sync with the parent while the association is retained
+            (SyncState::Lost(parent, expiry), AssocState::Associated(_pan_id))
+                if now_ms <= expiry =>
+            {
+                self.tick_rejoin(now_ms, parent)?;
+            }
+            // Otherwise fall back to re-association once the grace period expires
+            (SyncState::Lost(_parent, _expiry), _) => {
+                warn!("Re-sync failed, dropping association at {} ms", now_ms);
+                self.sync_state = SyncState::Unsynced;
+                self.network = None;
+                self.drop_association();
+
+                // Re-scan prior to joining where configured
+                if let Some(kind) = self.config.pan_scan {
+                    let channels = self.config.scan_channels.clone();
+                    self.join_target = None;
+
+                    if self
+                        .scan(kind, &channels, self.config.scan_duration)
+                        .is_err()
+                    {
+                        error!("Error starting PAN re-scan");
+                    }
+                }
+            }
+            // Drop association on de-sync
             (SyncState::Unsynced, AssocState::Associated(_pan_id))
                 if last_sync_state != SyncState::Unsynced =>
             {
-                self.stats.sync_fail = self.stats.sync_fail.saturating_add(1);
-                self.assoc_state = AssocState::Unassociated;
+                self.drop_association();
             }
             _ => (),
         }
 
+        // Sleep the radio once the active period is complete
+        self.tick_sleep(now_ms)?;
+
         Ok(())
     }
 
@@ -348,567 +1024,6202 @@ where
     fn busy(&mut self) -> Result<bool, Self::Error> {
         let b = self.csma_state != CsmaState::None
             || self.ack_state != AckState::None
-            || !self.assoc_state.is_associated();
+            || self.ack_wait != AckWait::None
+            || !self.assoc_state.is_associated()
+            || matches!(self.sync_state, SyncState::Lost(..))
+            || self.tx_buff.is_pending(TxPriority::Control);
 
         Ok(b)
     }
 
-    /// Check whether we have space in the transmit buffer
+    /// Check whether we have space in the transmit buffer for data frames
     fn can_transmit(&self) -> Result<bool, Self::Error> {
-        Ok(!self.tx_buff.is_full())
+        Ok(!self.tx_buff.is_full(TxPriority::Data))
     }
 
     /// Enqueue a packet for TX
-    fn transmit(&mut self, dest: Address, data: &[u8], ack: bool) -> Result<(), Self::Error> {
-        // Setup packet for sending
-        let packet = Packet::data(dest, self.addr(), self.seq(), data, ack);
+    fn transmit(&mut self, dest: Address, data: &[u8], ack: bool) -> Result<TxHandle, Self::Error> {
+        self.transmit_data(dest, data, ack, None, None)
+    }
 
-        // Enqueue in TX buffer
-        if let Err(_e) = self.tx_buff.enqueue((TxState::default(), packet)) {
-            error!("Error enqueuing packet to send");
-            return Err(CoreError::BufferFull);
-        }
+    /// Enqueue a packet for TX, dropping this if not sent by the provided deadline
+    fn transmit_with_deadline(
+        &mut self,
+        dest: Address,
+        data: &[u8],
+        ack: bool,
+        expires_at: Ts,
+    ) -> Result<TxHandle, Self::Error> {
+        self.transmit_data(dest, data, ack, Some(expires_at), None)
+    }
 
-        Ok(())
+    /// Fetch the next transmit outcome
+    fn tx_status(&mut self) -> Result<Option<(TxHandle, TxStatus)>, Self::Error> {
+        Ok(self.tx_results.dequeue())
+    }
+
+    /// Fetch the next MAC event
+    fn event(&mut self) -> Result<Option<MacEvent<Address>>, Self::Error> {
+        Ok(self.events.dequeue())
     }
 
     /// Check for received packets
     fn receive(&mut self, data: &mut [u8]) -> Result<Option<(usize, RxInfo)>, Self::Error> {
         // Fetch from RX buffer
-        let rx = match self.rx_buff.dequeue() {
-            Some(rx) => rx,
+        let needed = match self.rx_buff.first() {
+            Some(rx) => rx.1.payload().len(),
             None => return Ok(None),
         };
 
-        // Decode data
+        // Retain packets exceeding the provided buffer for retry
+        if needed > data.len() {
+            debug!(
+                "Received {} byte payload exceeds {} byte buffer",
+                needed,
+                data.len()
+            );
+            return Err(CoreError::BufferError(BufferError::Insufficient { needed }));
+        }
+
+        let rx = self.rx_buff.remove(0);
         let payload = rx.1.payload();
         data[..payload.len()].copy_from_slice(&payload);
 
         // Return payload length
         Ok(Some((payload.len(), rx.0)))
     }
-}
 
-impl<R, T> Mac<R, T>
-where
-    R: Radio,
-    T: Timer,
-{
-    /// Fetch configured MAC address
-    pub fn addr(&self) -> Address {
-        // TODO: use broadcast(?) pan_id if unjoined
-        match self.short_addr {
-            Some(s) => Address::Short(self.config.pan_id, s),
-            None => Address::Extended(self.config.pan_id, self.address),
+    /// Drop the next received packet
+    fn discard(&mut self) -> Result<bool, Self::Error> {
+        if self.rx_buff.is_empty() {
+            return Ok(false);
         }
-    }
+        let rx = self.rx_buff.remove(0);
 
-    /// Fetch and increment TX sequence number
-    fn seq(&mut self) -> u8 {
-        let s = self.seq;
-        self.seq = self.seq.wrapping_add(1);
-        s
-    }
+        warn!(
+            "Dropped {} byte payload from {:?}",
+            rx.1.payload().len(),
+            rx.0.source
+        );
 
-    /// Fetch MAC layer statistics
-    pub fn stats(&self) -> MacStats {
-        self.stats.clone()
+        Ok(true)
     }
 
-    fn tick_beacon(&mut self, now_ms: u64, asn: u64) -> Result<(), CoreError<<R as Radio>::Error>> {
-        // No ASN change / nothing we need to do for beaconing
-        if self.last_asn == asn {
-            return Ok(());
-        }
+    /// Fetch the time of the next scheduled operation
+    fn next_deadline(&self) -> Option<Ts> {
+        let now_ms = self.timer.ticks_ms();
+        let asn = self.config.calculate_asn(now_ms, self.sync_offset);
 
-        // No pending beacon or not yet expected beacon time
-        if self.next_beacon == 0 || self.next_beacon > now_ms {
-            return Ok(());
-        }
+        // Start of the next slot, for operations evaluated on each slot
+        let next_slot =
+            ((asn + 1) * self.config.base_slot_duration as u64).saturating_sub(self.sync_offset);
 
-        // Check for schedule misses
-        // (self.next_beacon updated on receipt of viable beacon)
-        if (self.next_beacon + self.config.mac_deadline as u64) < now_ms {
-            // Desync after configured number of beacon misses
-            if let SyncState::Synced(_) = self.sync_state {
-                self.beacon_miss_count += 1;
+        let mut deadline: Option<Ts> = None;
+        let mut at = |t: Ts| deadline = Some(deadline.map_or(t, |d| d.min(t)));
 
-                if self.beacon_miss_count > self.config.max_beacon_misses {
-                    warn!("Exceeded maximum beacon misses, synchronization lost");
-                    self.sync_state = SyncState::Unsynced;
-                    self.next_beacon = 0;
+        // Outstanding radio events and results awaiting collection
+        if self.base.is_pending() || !self.rx_buff.is_empty() || !self.tx_results.is_empty() {
+            at(now_ms);
+        }
+        if self.parked {
+            return deadline;
+        }
+        if let Some(t) = self.base.poll_deadline() {
+            at(t);
+        }
 
-                    return Ok(());
-                }
-            } else {
-                // TODO: Count coordinator schedule misses here
+        // Energy detection samples on each tick, other scans wait out the dwell
+        if let Some(s) = self.scan.as_ref().filter(|s| !s.is_done()) {
+            match (s.kind(), s.dwell_end()) {
+                (ScanKind::Energy, _) | (_, None) => at(now_ms),
+                (_, Some(t)) => at(t),
             }
+            return deadline;
         }
 
-        // PAN coordinator broadcasts beacons
-        // TODO: as do other coordinators in their respective slots? need to tx and rx for these
-        // TODO: is there still a randomness to this to avoid collisions if neighbors > slotframe count?
-        if self.config.pan_coordinator {
-            debug!("Broadcasting beacon in ASN: {} at {} ms", asn, now_ms);
+        // Beacon TX (coordinators) or RX (devices)
+        if self.next_beacon != 0 {
+            at(self.next_beacon);
+        }
 
-            // TODO: beacon type varies with TSCH/non-tsch?
-            let beacon = Beacon {
-                superframe_spec: self.config.superframe_spec(),
-                // TODO: replace placeholders with actual configuration
-                guaranteed_time_slot_info: GuaranteedTimeSlotInformation::new(),
-                pending_address: PendingAddress::new(),
-            };
+        // Sync parent selection
+        if let Some(t) = self.sync_window {
+            at(t);
+        }
 
-            let packet = Packet::beacon(self.addr(), self.seq(), beacon);
+        // ACK transmission and timeouts
+        if let AckState::Pending { tx_time, .. } = &self.ack_state {
+            at(tx_time + 1);
+        }
+        if let AckWait::Pending { tx_time, .. } = &self.ack_wait {
+            at(tx_time + self.config.ack_timeout + 1);
+        }
 
-            let mut buff = [0u8; 256];
-            let n = packet.encode(&mut buff, WriteFooter::No);
+        // Spaced CCA samples, within the slot
+        if let (
+            CsmaState::Pending {
+                cca: CcaState::Sampling {
+                    last_ms: Some(t), ..
+                },
+                ..
+            },
+            CcaMode::EnergyAboveThreshold {
+                sample_interval_ms, ..
+            },
+        ) = (&self.csma_state, self.config.cca_mode)
+        {
+            at(t + sample_interval_ms as u64);
+        }
 
-            self.base.transmit(now_ms, &buff[..n])?;
+        // CAP bursts continue once the previous frame completes
+        if self.burst > 0
+            && !self.tx_buff.is_empty()
+            && !self.base.is_busy()
+            && self.ack_wait == AckWait::None
+        {
+            at(now_ms.max(self.burst_hold));
+        }
 
-            // Re-arm beacon for next slot
-            self.next_beacon += self.config.superframe_duration() as u64;
+        // CSMA, channel hopping, and GTS transmissions operate per-slot
+        if self.csma_state != CsmaState::None || !self.tx_buff.is_empty() || self.hopping.is_some()
+        {
+            at(next_slot);
+        }
 
-            debug!("Armed next beacon TX for {} ms", self.next_beacon);
-        } else {
-            debug!("Set beacon RX for ASN: {} at {} ms", asn, now_ms);
+        // Indirect frame expiry and data request timeouts
+        if let Some(t) = self.indirect.next_expiry() {
+            at(t + 1);
+        }
+        if let Some(t) = self.data_request {
+            at(t + 1);
+        }
 
-            if self.base.state() != BaseState::Listening {
-                self.base.receive(now_ms)?;
+        // Association, re-sync, and GTS requests
+        match (self.sync_state, self.assoc_state) {
+            (SyncState::Synced(_), AssocState::Unassociated) if self.assoc_permitted() => {
+                at(now_ms)
             }
+            (SyncState::Synced(_), AssocState::Pending(_, expiry)) => at(expiry + 1),
+            (SyncState::Synced(_), AssocState::Associated(_))
+                if self.gts_state == GtsState::None
+                    && self.config.gts_request_slots > 0
+                    && self.short_addr.is_some() =>
+            {
+                at(now_ms)
+            }
+            (SyncState::Lost(_, expiry), _) => {
+                at(self.rejoin_at);
+                at(expiry + 1);
+            }
+            _ => (),
+        }
+        if let GtsState::Pending(expiry) = self.gts_state {
+            at(expiry + 1);
+        }
+        if let Some(t) = self.gts_table.oldest_activity() {
+            at(t + self.config.gts_inactivity_timeout);
+        }
 
-            // TODO: re-arm beacon or keep listening depending on join state?
-            // This has to happen _after_ rx I guess
-            // so we need a timeout on operations? or maybe on slots?
-
-            self.next_beacon += self.config.superframe_duration() as u64;
-            debug!("Arm next beacon RX for {} ms", self.next_beacon);
+        // Sleep following the active period, waking ahead of the next beacon
+        // or early for pending transmissions
+        match self.sleep_state {
+            SleepState::Asleep(_, wake_at) if self.tx_buff.is_empty() => at(wake_at),
+            SleepState::Asleep(..) => at(now_ms),
+            _ => {
+                if let Some(t) = self.active_end().filter(|t| *t > now_ms) {
+                    at(t);
+                }
+            }
         }
 
-        Ok(())
+        deadline
     }
 
-    fn tick_cap(&mut self, now_ms: u64, asn: u64) -> Result<(), CoreError<<R as Radio>::Error>> {
-        let rsn = self.config.calculate_rsn(now_ms, self.sync_offset);
+    /// Signal a radio event, used where [`Config::radio_irq`] is set
+    fn radio_event(&mut self) {
+        self.base.event(self.timer.ticks_ms(), RadioEvent::Irq);
+    }
 
-        if asn != self.last_asn && rsn == 0 {
-            // If we're already attempting CSMA, restart if possible
-            if let CsmaState::Pending {
-                packet,
-                tx_slot,
-                retries,
-            } = &self.csma_state
-            {
-                // Limit CSMA backoff retries
-                if *retries >= self.config.csma_max_backoffs as u64 {
-                    warn!("CSMA TX failed for packet {}", packet.header.seq);
-                    self.stats.csma_cca_fail = self.stats.csma_cca_fail.saturating_add(1);
+    /// Resolve the router via which a device beneath this coordinator is reached,
+    /// see [`Mac::add_route`]
+    fn next_hop(&self, dest: &Address) -> Option<Address> {
+        self.routes.next_hop(dest)
+    }
 
-                    // TODO: should _mac_ ACK/Retry cause CSMA re-attempts?
+    /// Fetch common MAC statistics, see [`Mac::stats`] for all counters
+    fn stats(&self) -> StackStats {
+        let s = &self.stats;
+
+        StackStats {
+            tx_ok: s.tx_ok,
+            tx_fail: s
+                .tx_fail
+                .saturating_add(s.csma_cca_fail)
+                .saturating_add(s.tx_expired)
+                .saturating_add(s.indirect_expired),
+            rx_ok: s.rx_ok,
+            rx_dropped: s
+                .rx_drop_decode
+                .saturating_add(s.rx_drop_pan)
+                .saturating_add(s.rx_drop_addr)
+                .saturating_add(s.rx_queue_full)
+                .saturating_add(s.security_fail),
+            tx_queued: self.tx_buff.len(),
+            rx_queued: self.rx_buff.len(),
+            deadline_miss_tx: s.deadline_miss_tx,
+            deadline_miss_ack: s.deadline_miss_ack,
+            csma_cca_fail: s.csma_cca_fail,
+        }
+    }
 
-                    // TODO: notify higher level of failure?
-                    self.csma_state = CsmaState::None;
-                    let _ = self.tx_buff.dequeue();
-                } else if *tx_slot == 0 {
-                    // Re-schedule CSMA attempt
-                    let be = (self.config.min_be as u32 + *retries as u32)
-                        .min(self.config.max_be as u32);
+    /// Reset MAC statistics
+    fn reset_stats(&mut self) {
+        self.stats.reset();
+        self.base.reset_stats(self.timer.ticks_ms());
+    }
+}
 
-                    let backoff = (OsRng {}.next_u32() % (2u32.pow(be as u32) - 1)) as u64 + 1;
+impl<R, T, S, const TXQ: usize, const RXQ: usize> Mac<R, T, S, TXQ, RXQ>
+where
+    R: Radio,
+    T: Timer,
+    S: Aead,
+{
+    /// Fetch configured MAC address
+    pub fn addr(&self) -> Address {
+        // TODO: use broadcast(?) pan_id if unjoined
+        match self.short_addr {
+            Some(s) => Address::Short(self.pan_id(), s),
+            None => Address::Extended(self.pan_id(), self.address),
+        }
+    }
 
-                    debug!(
-                        "Scheduling CSMA TX retry for ASN {} ({} slots)",
-                        asn + backoff,
-                        backoff
-                    );
+    /// Fetch the active configuration, see [`Mac::reconfigure`]
+    pub fn config(&self) -> &Config<<R as Radio>::Channel> {
+        &self.config
+    }
 
-                    self.csma_state = CsmaState::Pending {
-                        packet: packet.clone(),
-                        tx_slot: asn + backoff,
-                        retries: *retries + 1,
-                    };
-                }
+    /// Fetch the PAN ID, adopted from the sync parent where synchronised
+    pub fn pan_id(&self) -> PanId {
+        match &self.network {
+            Some(n) => n.pan_id,
+            None => self.config.pan_id,
+        }
+    }
 
-            // Otherwise if we have something to TX, get started
-            } else if let Some(tx) = self.tx_buff.peek().map(|v| v.clone()) {
-                debug!(
-                    "Found pending packet {} to: {:?}",
-                    tx.1.header.seq, tx.1.header.destination
-                );
+    /// Fetch superframe timing at the provided time, for scheduling application
+    /// activity (such as sensor sampling) around radio operation
+    pub fn timing(&self, now_ms: Ts) -> MacTiming {
+        let asn = self.config.calculate_asn(now_ms, self.sync_offset);
+        let rsn = self.config.calculate_rsn(now_ms, self.sync_offset);
 
-                // Check TX retries and increase counter
-                if tx.0.retries > self.config.max_retries {
-                    debug!("Packet {} TX failed exceeded max retries", tx.1.header.seq);
-                    self.stats.tx_fail = self.stats.tx_fail.saturating_add(1);
+        let superframe = (self.superframe_duration() as u64).max(1);
+        let sfn = (now_ms + self.sync_offset) / superframe;
 
-                    let _ = self.tx_buff.dequeue();
-                    return Ok(());
-                }
-                self.tx_buff
-                    .iter_mut()
-                    .find(|(_, p)| p.header.seq == tx.1.header.seq)
-                    .map(|(i, _)| i.retries += 1);
-
-                // Calcuate backoff periods for TX
-                let be = match self.config.battery_life_extension {
-                    true => 2.min(self.config.min_be),
-                    false => self.config.min_be,
-                };
+        // Superframes are inactive following the active period, where shorter
+        let active = self.active_duration() as u64;
+        let offset = (now_ms + self.sync_offset) % superframe;
 
-                let backoff = (OsRng {}.next_u32() % (2u32.pow(be as u32) - 1)) as u64 + 1;
+        let period = if active < superframe && offset >= active {
+            SlotPeriod::Inactive
+        } else if rsn > self.config.final_cap_slot as u64 {
+            SlotPeriod::Gts
+        } else {
+            SlotPeriod::Cap
+        };
 
-                debug!(
-                    "Scheduling CSMA TX for ASN {} ({} slots)",
-                    asn + backoff,
-                    backoff
-                );
+        let next_beacon_ms = match self.next_beacon {
+            0 => None,
+            t => Some(t.saturating_sub(now_ms)),
+        };
 
-                self.csma_state = CsmaState::Pending {
-                    packet: tx.1.clone(),
-                    tx_slot: asn + backoff,
-                    retries: 0,
-                };
-            }
+        MacTiming {
+            asn,
+            rsn,
+            sfn,
+            next_beacon_ms,
+            period,
+        }
+    }
 
-        // In other slots _if_ we have a pending TX, run CSMA
-        } else if let CsmaState::Pending {
-            packet,
-            tx_slot,
-            retries,
-        } = self.csma_state.clone()
-        {
-            if asn < tx_slot {
-                // Check for clear slots
-                // TODO: this needs to be called multiple times in a slot (or offset into the slot to see the RX) rather than once per ASN as is currently guarded in `tick`
-                let rssi = self.base.rssi(now_ms)?;
-                if rssi > self.config.channel_clear_threshold {
-                    // If we're not clear, try again
-                    debug!("CCA fail at ASN: {} (rssi: {})", asn, rssi);
+    /// Fetch the earliest time the MAC needs to be ticked (no earlier than the
+    /// current time), per [`MacIf::next_deadline`], so the MCU may sleep between
+    /// ticks. Where nothing is scheduled this is `Ts::MAX`, with only radio events
+    /// (see [`MacIf::radio_event`]) requiring a tick
+    pub fn next_wake(&self) -> Ts {
+        match self.next_deadline() {
+            Some(t) => t.max(self.timer.ticks_ms()),
+            None => Ts::MAX,
+        }
+    }
 
-                    self.csma_state = CsmaState::Pending {
-                        packet: packet.clone(),
-                        tx_slot: 0,
-                        retries: retries + 1,
-                    };
-                }
-            } else if asn == tx_slot {
-                // Prepare packet and transmit
-                let mut buff = [0u8; 255];
-                let n = packet.encode(&mut buff, WriteFooter::No);
+    /// Fetch neighbours heard from, from least to most recently heard
+    pub fn neighbours(&self) -> impl Iterator<Item = &Neighbour> {
+        self.neighbours.iter()
+    }
 
-                self.base.transmit(now_ms, &buff[..n])?;
+    /// Fetch the link quality record for a neighbour
+    pub fn neighbour(&self, address: &Address) -> Option<&Neighbour> {
+        self.neighbours.lookup(address)
+    }
 
-                debug!("CSMA TX at {} ms", now_ms);
+    /// Fetch the relationship of a neighbour to this device
+    fn relation(sync_state: &SyncState, assoc_table: &AssocTable, address: &Address) -> Relation {
+        if sync_state.parent() == Some(address) {
+            Relation::Parent
+        } else if assoc_table.lookup_address(address).is_some() {
+            Relation::Child
+        } else {
+            Relation::Peer
+        }
+    }
 
-                // Update CSMA state and packet buffer
-                self.csma_state = CsmaState::None;
+    /// Set (or clear) the sink for capture of frames transmitted and received
+    /// by the radio, see [`Base::set_capture`]
+    pub fn set_capture(&mut self, sink: Option<&'static mut dyn PcapSink>) {
+        self.base.set_capture(sink);
+    }
 
-                if !packet.header.ack_request {
-                    let _ = self.tx_buff.dequeue();
-                } else {
-                    // TODO: arm ACK RX?
-                }
-            } else if tx_slot != 0 && asn > tx_slot {
-                warn!("CSMA TX slot miss");
-                self.stats.deadline_miss_tx = self.stats.deadline_miss_tx.saturating_add(1);
+    /// Set the application payload carried in beacons, up to [`MAX_BEACON_PAYLOAD_LEN`] bytes.
+    ///
+    /// This must fit within the beacon frame alongside the current GTS and pending
+    /// address fields, beacons are sent without the payload where these later grow
+    /// to exceed the available frame space
+    pub fn set_beacon_payload(
+        &mut self,
+        data: &[u8],
+    ) -> Result<(), CoreError<<R as Radio>::Error>> {
+        let payload = Vec::from_slice(data).map_err(|_| BufferError::TooSmall)?;
+        let prev = core::mem::replace(&mut self.beacon_payload, payload);
 
-                self.csma_state = CsmaState::Pending {
-                    packet: packet.clone(),
-                    tx_slot: 0,
-                    retries: retries + 1,
-                };
-            }
+        // Hopping information is fixed length, so the ASN does not matter here
+        if !beacon_fits(&self.beacon_frame(self.seq, 0, true)) {
+            self.beacon_payload = prev;
+            return Err(CoreError::BufferError(BufferError::TooSmall));
         }
 
         Ok(())
     }
 
-    fn handle_received(
-        &mut self,
-        now: u64,
-        rx: RawPacket,
-    ) -> Result<(), CoreError<<R as Radio>::Error>> {
-        // Decode packet
-        let p = match Packet::decode(rx.data(), false) {
-            Ok(p) => p,
-            Err(e) => {
-                error!("Error decoding received packet: {:?}", e);
-                return Err(CoreError::DecodeError(e));
-            }
-        };
+    /// Fetch the application payload carried in beacons
+    pub fn beacon_payload(&self) -> &[u8] {
+        &self.beacon_payload
+    }
 
-        trace!(
-            "Received {} byte {:?} packet",
-            p.payload().len(),
-            p.header.frame_type
-        );
+    /// Fetch the time and application payload of the most recent beacon heard
+    /// from a coordinator
+    pub fn last_beacon(&self, coord_addr: &Address) -> Option<(Ts, &[u8])> {
+        self.beacons
+            .lookup(coord_addr)
+            .map(|b| (b.received, b.payload()))
+    }
 
-        // Filter by PAN ID
-        let pan_id = p.pan_id();
-        if pan_id != PanId::broadcast() {
-            match &self.assoc_state {
-                AssocState::Associated(id) if pan_id != *id => {
-                    debug!(
-                        "Pan ID mismatch, dropped packet {} for {:?}",
-                        p.header.seq, pan_id
-                    );
-                    return Ok(());
-                }
-                _ => (),
-            }
+    /// Fetch the network configuration adopted from the sync parent, if synchronised
+    pub fn network(&self) -> Option<&NetworkConfig> {
+        self.network.as_ref()
+    }
+
+    /// Fetch the superframe duration in ms, using the beacon order of the sync parent
+    /// where synchronised
+    fn superframe_duration(&self) -> u32 {
+        match &self.network {
+            Some(n) => self.config.superframe_duration_for(n.beacon_order),
+            None => self.config.superframe_duration(),
         }
+    }
 
-        // Filter by address
-        match (p.header.destination, self.short_addr) {
-            // Accept messages to broadcast short address
-            (Address::Short(_, short), _) if short == ShortAddress::broadcast() => (),
-            // Accept messages to our short address
-            (Address::Short(_, short), Some(addr)) if short == addr => (),
-            // Accept messages to our extended address
-            (Address::Extended(_, ext), _) if ext == self.address => (),
-            _ => {
-                debug!(
-                    "Address mismatch, dropped packet {} for {:?}",
-                    p.header.seq, p.header.destination
-                );
-                return Ok(());
-            }
+    /// Fetch the estimated drift of the local clock relative to the sync parent in ppm,
+    /// positive where the local clock runs fast. See [`Config::drift_window`]
+    pub fn drift_ppm(&self) -> Option<i32> {
+        self.drift.ppm(self.superframe_duration() as u64)
+    }
+
+    /// Fetch the expected interval between beacons from the sync parent in local time,
+    /// compensated for the estimated clock drift
+    fn beacon_interval(&self) -> i64 {
+        let superframe = self.superframe_duration() as i64;
+        let drift = self.drift_ppm().unwrap_or(0) as i64;
+
+        superframe + superframe * drift / 1_000_000
+    }
+
+    /// Apply the estimated clock drift to the sync offset while synchronised,
+    /// accumulating sub-millisecond corrections between ticks
+    fn tick_drift(&mut self, now_ms: u64) {
+        let elapsed = now_ms.saturating_sub(self.drift_at);
+        self.drift_at = now_ms;
+
+        let ppm = match (&self.sync_state, self.drift_ppm()) {
+            (SyncState::Synced(_), Some(ppm)) if !self.config.pan_coordinator => ppm,
+            _ => return,
         };
 
-        // Arm ACK response if required
-        if p.header.ack_request {
-            // Build ACK payload
+        // Accumulate drift in ns (ms * ppm), applying whole ms to the offset so slot
+        // timing advances where the local clock runs slow and retards where fast
+        self.drift_rem += elapsed as i64 * ppm as i64;
+        let ms = self.drift_rem / 1_000_000;
+        self.drift_rem -= ms * 1_000_000;
 
-            let ack = Packet::ack(&p);
-            self.ack_state = AckState::Pending {
-                tx_time: now + self.config.ack_delay,
-                packet: ack,
-            };
+        self.sync_offset = (self.sync_offset as i64 - ms).max(0) as u64;
+    }
 
-            debug!(
-                "Scheduled ACK for packet {} from {:?} for {} ms",
-                p.header.seq,
-                p.header.source,
-                now + self.config.ack_delay
-            );
+    /// Fetch the superframe active period duration in ms, using the beacon and superframe
+    /// orders of the sync parent where synchronised
+    fn active_duration(&self) -> u32 {
+        match &self.network {
+            Some(n) => self
+                .config
+                .active_duration_for(n.beacon_order, n.superframe_order),
+            None => self.config.active_duration(),
         }
+    }
 
-        // Handle received packets
-        match p.content {
-            FrameContent::Beacon(_b) => {
-                debug!("Received beacon from {:?} at {} ms", p.header.source, now);
+    /// Check whether association is permitted by the coordinator
+    fn assoc_permitted(&self) -> bool {
+        self.network
+            .as_ref()
+            .map(|n| n.association_permit)
+            .unwrap_or(true)
+    }
 
-                // If we're the pan coordinator we're not going to _sync_ on this
-                // (but it might be useful to look at for drift?)
-                if self.config.pan_coordinator {
+    /// Fetch and increment TX sequence number
+    fn seq(&mut self) -> u8 {
+        let s = self.seq;
+        self.seq = self.seq.wrapping_add(1);
+        s
+    }
 
-                    // If we're unsynced parse this and decide whether to adopt as the
-                    // authorative time source
-                } else if self.sync_state == SyncState::Unsynced {
-                    debug!("Adopting sync parent {:?}", p.header.source);
+    /// Enqueue a data packet for TX with the provided priority, for time-critical
+    /// messages to be sent ahead of queued data frames
+    pub fn transmit_with_priority(
+        &mut self,
+        dest: Address,
+        data: &[u8],
+        ack: bool,
+        priority: TxPriority,
+    ) -> Result<TxHandle, CoreError<<R as Radio>::Error>> {
+        self.transmit_data(dest, data, ack, None, Some(priority))
+    }
 
-                    // TODO: apply received configuration
+    /// Fetch the remaining transmit buffer capacity for a priority class
+    pub fn tx_capacity(&self, priority: TxPriority) -> usize {
+        self.tx_buff.capacity(priority)
+    }
 
-                    // Set sync state and compute next beacon time
-                    // TODO: apply shift to compensate for time to tx/rx beacon
-                    self.sync_state = SyncState::Synced(p.header.source);
-                    // TODO: in TSCH impls sync offset set based on ASN
-                    self.sync_offset = now;
-                    self.sync_correction = 0;
+    /// Fetch MAC layer statistics
+    pub fn stats(&self) -> MacStats {
+        MacStats {
+            radio: self.base.stats(self.timer.ticks_ms()),
+            ..self.stats.clone()
+        }
+    }
 
-                    self.next_beacon = now + self.config.superframe_duration() as u64;
-                    self.beacon_miss_count = 0;
+    /// Fetch MAC layer statistics and reset all counters,
+    /// for computing rates over the interval between calls
+    pub fn stats_take(&mut self) -> MacStats {
+        let now = self.timer.ticks_ms();
+        let stats = MacStats {
+            radio: self.base.stats(now),
+            ..self.stats.clone()
+        };
 
-                    debug!(
-                        "Received beacon at {} ms (set offset to {} ms)",
-                        now, self.sync_offset
-                    );
+        self.stats.reset();
+        self.base.reset_stats(now);
 
-                // If we're synced use this to evaluate drift and correct _if_ it's from
-                //our parent
-                } else if let SyncState::Synced(parent) = self.sync_state {
-                    if p.header.source != parent {
-                        debug!("Disgarding sync from non-parent: {:?}", p.header.source);
+        stats
+    }
 
-                        // TODO: should this contribute to desyncing? often occurs because
-                        // the root has restarted so the time system is off
-                    } else {
-                        // Compute offset from expected time
-                        // This is improved by TSCH EBs / ASNs huh?
-                        // TODO: what happens if we're > one slot out of sync
+    /// Add an event for collection via [`MacIf::event`],
+    /// dropping the oldest event if these are not being collected
+    fn push_event(&mut self, event: MacEvent<Address>) {
+        if self.events.is_full() {
+            let _ = self.events.dequeue();
+        }
+        let _ = self.events.enqueue(event);
+    }
 
-                        // Compute the error in frame sync
-                        let delta = (now as i64 - self.next_beacon as i64) as i64
-                            % self.config.superframe_duration() as i64;
+    /// Request guaranteed time slots from the sync parent, requiring association
+    /// and an allocated short address. Grants are applied on receipt of the parent
+    /// beacon, with requests not granted within [`Config::gts_timeout`] dropped
+    pub fn request_gts(
+        &mut self,
+        slots: u8,
+        direction: GtsDirection,
+    ) -> Result<(), CoreError<<R as Radio>::Error>> {
+        let parent = match (self.sync_state, self.assoc_state, self.short_addr) {
+            (SyncState::Synced(parent), AssocState::Associated(_), Some(_)) => parent,
+            _ => return Err(CoreError::NotAssociated),
+        };
+        if slots == 0 || matches!(self.gts_state, GtsState::Pending(_)) {
+            return Err(CoreError::Busy);
+        }
 
-                        // Compute the difference between our expectation and the actual rx time
-                        // normalised within the frame time.
-                        let shift = calculate_offset(
-                            now as i64,
-                            self.next_beacon as i64,
-                            self.config.superframe_duration() as i64,
-                        );
+        let now_ms = self.timer.ticks_ms();
+        self.send_gts_request(now_ms, parent, slots, direction)
+    }
 
-                        // TODO: update sync offset to match ASN (when beacons include this)
-                        // (or, split sync offset and ASN concepts)
+    /// Allocate guaranteed time slots to a device without a request from the device
+    /// (PAN coordinators only), for fixed schedules. Allocations are advertised in
+    /// following beacons and released as for requested slots, with devices addressed
+    /// by short address or associated with this coordinator.
+    ///
+    /// Only transmit (device to coordinator) slots are currently supported, and
+    /// [`CoreError::BufferFull`] is returned where the CFP has no space for the slots
+    pub fn allocate_gts(
+        &mut self,
+        addr: Address,
+        slots: u8,
+        direction: GtsDirection,
+    ) -> Result<GtsSlot, CoreError<<R as Radio>::Error>> {
+        if !self.config.pan_coordinator {
+            return Err(CoreError::InvalidConfig);
+        }
+        if direction == GtsDirection::Receive {
+            return Err(CoreError::Unsupported);
+        }
+        let short = self.device_short(&addr).ok_or(CoreError::NotAssociated)?;
 
-                        // Update beacon sync correction offset (eg. clock / timing compensation)
-                        self.sync_correction = self.sync_correction + shift / 2;
+        let now_ms = self.timer.ticks_ms();
+        let (first, last) = self.config.cfp_slots();
+        let s = self
+            .gts_table
+            .allocate(short, slots, first, last, now_ms)
+            .ok_or(CoreError::BufferFull)?;
+
+        info!(
+            "Allocated GTS slots {} to {} for {:?}",
+            s.start,
+            s.start + s.length - 1,
+            short
+        );
 
-                        debug!(
-                            "Received new beacon at {} ms (error: {} ms, new correction of {} ms)",
-                            now,
-                            delta,
-                            self.sync_correction
-                        );
+        Ok(s)
+    }
 
-                        // Set new beacon expected time
-                        // TODO: really this should happen in tick rather than here?
-                        self.next_beacon = (now as i64 + self.config.superframe_duration() as i64 + self.sync_correction) as u64;
-                        self.beacon_miss_count = 0;
-                        debug!("Arm next beacon RX at {} ms", self.next_beacon);
-                    }
-                }
+    /// Release guaranteed time slots allocated to a device (PAN coordinators only)
+    pub fn deallocate_gts(&mut self, addr: &Address) -> Option<GtsSlot> {
+        let short = self.device_short(addr)?;
+        self.gts_table.remove(&short)
+    }
 
-                // TODO: apply beacon info to config?
-                // How to do this in a transient way? maybe hold separately and merge?
-            }
-            FrameContent::Command(c) => {
-                match c {
-                    Command::AssociationRequest(req) => {
-                        debug!(
-                            "Association request from: {:?} (cap: {:?}",
-                            p.header.source, req
-                        );
+    /// Wake the radio where asleep between superframe active periods,
+    /// holding this awake until the next beacon
+    pub fn wake(&mut self) -> Result<(), CoreError<<R as Radio>::Error>> {
+        let now_ms = self.timer.ticks_ms();
 
-                        // TODO: check whether to allow association?
+        if let SleepState::Asleep(slept_at, _) = self.sleep_state {
+            debug!("Early wake at {} ms", now_ms);
+            self.wake_radio(now_ms, slept_at)?;
+        }
 
-                        // TODO: how do we _reasonably_ assign short addresses here?
-                        // For global uniqueness we either need to know all of em or
-                        // go back to the pan_coordinator for assignment?
-                        // For now, use no-assign short addr
-                        let assoc_addr = ShortAddress(0xfffe);
-                        let assoc_status = AssociationStatus::Successful;
+        self.sleep_state = SleepState::Held(self.next_beacon);
 
-                        // Build response
-                        let assoc_cmd = Command::AssociationResponse(assoc_addr, assoc_status);
-                        let assoc_resp =
-                            Packet::command(p.header.source, self.addr(), self.seq(), assoc_cmd);
+        Ok(())
+    }
 
-                        if let Err(_) = self.tx_buff.enqueue((TxState::default(), assoc_resp)) {
-                            error!("Error adding associate request to tx buffer");
-                        }
-                    }
-                    Command::AssociationResponse(_assoc_addr, assoc_state) => {
-                        // Only handle expected associations
-                        match self.assoc_state {
-                            AssocState::Unassociated | AssocState::Associated(_) => return Ok(()),
-                            AssocState::Pending(addr, _) if addr != p.header.source => {
-                                warn!(
-                                    "Associate response from unexpected peer {:?}",
-                                    p.header.source
-                                );
-                                return Ok(());
-                            }
-                            _ => (),
-                        }
+    /// Start a scan over the provided channels, dwelling for `duration_ms` on each.
+    /// This runs via [`MacIf::tick`], with progress and results available from
+    /// [`Mac::scan_state`] and discovered PANs from [`Mac::scan_results`]
+    pub fn scan(
+        &mut self,
+        kind: ScanKind,
+        channels: &[<R as Radio>::Channel],
+        duration_ms: u64,
+    ) -> Result<(), CoreError<<R as Radio>::Error>> {
+        if self.scanning() {
+            return Err(CoreError::Busy);
+        }
 
-                        if assoc_state == AssociationStatus::Successful {
-                            let pan_id = p.header.source.pan_id().unwrap();
-                            info!("Associated with PAN: {}!", pan_id.0);
+        let scan = Scan::new(kind, channels, duration_ms).ok_or(CoreError::BufferFull)?;
 
-                            // TODO: apply short address if received
+        debug!(
+            "Starting {:?} scan of {} channels ({} ms dwell)",
+            kind,
+            channels.len(),
+            duration_ms
+        );
 
-                            // TODO: extract pan ID to support compression?
-                            self.assoc_state = AssocState::Associated(pan_id);
-                        } else {
-                            warn!("Association failed with status: {:?}", assoc_state);
+        self.scan = Some(scan);
 
-                            // TODO: add back-off or reset sync on failure?
-                            self.assoc_state = AssocState::Unassociated;
-                        }
-                    }
-                    _ => {
-                        info!("RX unhandled command: {:?}", c);
-                    }
-                }
-            }
-            FrameContent::Acknowledgement => {
-                match self.tx_buff.peek() {
-                    Some((_s, t)) if p.is_ack_for(t) => {
-                        debug!("ACK rx for packet: {}!", p.header.seq);
+        Ok(())
+    }
 
-                        // Remove from TX buffer
-                        // TODO: signal success to higher level?
-                        let _ = self.tx_buff.dequeue();
-                    }
-                    Some((_s, _t)) => {
-                        warn!("ACK sequence mismatch");
-                    }
-                    None => {
-                        warn!("ACK with no pending operation");
-                    }
-                }
+    /// Fetch the current (or most recent) scan
+    pub fn scan_state(&self) -> Option<&Scan<<R as Radio>::Channel>> {
+        self.scan.as_ref()
+    }
+
+    /// Fetch PANs discovered by the most recent passive or active scan
+    pub fn scan_results(&self) -> &[PanDescriptor<<R as Radio>::Channel>] {
+        match &self.scan {
+            Some(s) => s.pans(),
+            None => &[],
+        }
+    }
+
+    /// Join the PAN described by a scan result, synchronising to (and then associating with)
+    /// the described coordinator on receipt of the next beacon
+    pub fn join(
+        &mut self,
+        pan: &PanDescriptor<<R as Radio>::Channel>,
+    ) -> Result<(), CoreError<<R as Radio>::Error>> {
+        if self.scanning() {
+            return Err(CoreError::Busy);
+        }
+
+        info!("Joining PAN {:?} via {:?}", pan.pan_id, pan.coord_addr);
+
+        self.config.pan_id = pan.pan_id;
+        self.config.channel = Some(pan.channel.clone());
+        self.join_target = Some(pan.coord_addr);
+        self.sync_window = None;
+        self.sync_candidates.clear();
+
+        // Drop any existing sync to resync with the selected coordinator
+        self.sync_state = SyncState::Unsynced;
+        self.network = None;
+        self.assoc_state = AssocState::Unassociated;
+        self.next_beacon = 0;
+        self.hopping = None;
+
+        Ok(())
+    }
+
+    /// Check whether beacons from the provided coordinator may be adopted for sync
+    fn join_permitted(&self, coord_addr: &Address) -> bool {
+        match &self.join_target {
+            Some(a) => a == coord_addr,
+            None => self.config.pan_scan.is_none(),
+        }
+    }
+
+    /// Check whether a scan is in progress
+    fn scanning(&self) -> bool {
+        self.scan.as_ref().map(|s| !s.is_done()).unwrap_or(false)
+    }
+
+    /// Fetch devices associated with this coordinator
+    pub fn associations(&self) -> impl Iterator<Item = &Association> {
+        self.assoc_table.iter()
+    }
+
+    /// Fetch downstream routes to devices beneath this coordinator,
+    /// associated directly or via routers
+    pub fn routes(&self) -> impl Iterator<Item = &Route> {
+        self.routes.iter()
+    }
+
+    /// Add a route to a device associated via a router beneath this coordinator,
+    /// superseding any existing route or direct association of the device.
+    /// Returns [`CoreError::InvalidConfig`] where this is not a coordinator
+    /// or [`CoreError::BufferFull`] where the route table is full
+    pub fn add_route(
+        &mut self,
+        short: ShortAddress,
+        extended: ExtendedAddress,
+        via: Address,
+    ) -> Result<(), CoreError<<R as Radio>::Error>> {
+        if !self.config.pan_coordinator {
+            return Err(CoreError::InvalidConfig);
+        }
+
+        // The device has re-associated elsewhere
+        if self.assoc_table.remove(&extended).is_some() {
+            debug!("Device {:?} re-associated via {:?}", extended, via);
+        }
+
+        let route = Route {
+            short,
+            extended,
+            via: Some(via),
+            updated: self.timer.ticks_ms(),
+        };
+        self.routes.update(route).map_err(|_| CoreError::BufferFull)
+    }
+
+    /// Remove the downstream route to a device
+    pub fn remove_route(&mut self, extended: &ExtendedAddress) -> Option<Route> {
+        self.routes.remove(extended)
+    }
+
+    /// Setup a data packet for transmission, with an optional deadline after which
+    /// this is dropped rather than transmitted
+    fn transmit_data(
+        &mut self,
+        dest: Address,
+        data: &[u8],
+        ack: bool,
+        expires: Option<Ts>,
+        priority: Option<TxPriority>,
+    ) -> Result<TxHandle, CoreError<<R as Radio>::Error>> {
+        let packet = self.data_packet(dest, data, ack)?;
+
+        // Hold packets for devices that poll for data
+        let indirect = self
+            .assoc_table
+            .lookup_address(&dest)
+            .map(|a| !a.rx_on_when_idle)
+            .unwrap_or(false);
+        if indirect {
+            return self.enqueue_indirect(packet, expires, priority);
+        }
+
+        // Enqueue in TX buffer
+        match self.enqueue_tx_until(packet, expires, priority) {
+            Ok(handle) => Ok(handle),
+            Err(e) => {
+                error!("Error enqueuing packet to send");
+                Err(e)
             }
-            FrameContent::Data => {
-                debug!(
-                    "Received {} bytes of data from {:?}",
-                    p.payload().len(),
-                    p.header.source
+        }
+    }
+
+    /// Hold a data packet for indirect transmission to a device that polls for data,
+    /// irrespective of the receive-on-idle capability of the device. The packet is
+    /// released on receipt of a data request from the destination, or dropped on
+    /// expiry of the [`Config::transaction_persistence_ms`] (reporting
+    /// [`TxFailure::Expired`])
+    pub fn transmit_indirect(
+        &mut self,
+        dest: Address,
+        data: &[u8],
+        ack: bool,
+    ) -> Result<TxHandle, CoreError<<R as Radio>::Error>> {
+        if !self.config.pan_coordinator {
+            return Err(CoreError::InvalidConfig);
+        }
+
+        let packet = self.data_packet(dest, data, ack)?;
+
+        self.enqueue_indirect(packet, None, None)
+    }
+
+    /// Build a data packet for transmission, applying frame security where configured
+    fn data_packet(
+        &mut self,
+        dest: Address,
+        data: &[u8],
+        ack: bool,
+    ) -> Result<Packet, CoreError<<R as Radio>::Error>> {
+        // Setup packet for sending
+        let mut packet = Packet::data(dest, self.addr(), self.seq(), data, ack)
+            .map_err(CoreError::BufferError)?;
+
+        // Apply frame security where configured
+        if self.config.security.is_some() {
+            self.secure_packet(&mut packet)
+                .map_err(CoreError::SecurityError)?;
+        }
+
+        // Reject frames exceeding the radio buffer here rather than on transmission
+        let mut buff = [0u8; TX_BUFF_LEN];
+        packet.encode(&mut buff, WriteFooter::No)?;
+
+        Ok(packet)
+    }
+
+    /// Add a packet to the TX buffer, returning a handle for outcome tracking
+    fn enqueue_tx(&mut self, packet: Packet) -> Result<TxHandle, CoreError<<R as Radio>::Error>> {
+        self.enqueue_tx_until(packet, None, None)
+    }
+
+    /// Add a packet to the TX buffer with an optional deadline and priority,
+    /// returning a handle for outcome tracking
+    fn enqueue_tx_until(
+        &mut self,
+        packet: Packet,
+        expires: Option<Ts>,
+        priority: Option<TxPriority>,
+    ) -> Result<TxHandle, CoreError<<R as Radio>::Error>> {
+        let handle = self.next_handle;
+
+        let state = TxState {
+            handle,
+            expires,
+            priority,
+            ..Default::default()
+        };
+
+        if let Err(_e) = self.tx_buff.enqueue((state, packet)) {
+            return Err(CoreError::BufferFull);
+        }
+
+        self.next_handle = self.next_handle.wrapping_add(1);
+
+        Ok(handle)
+    }
+
+    /// Hold a packet for indirect transmission, returning a handle for outcome tracking.
+    /// Frames with a deadline are held until the earlier of this and the transaction persistence time
+    fn enqueue_indirect(
+        &mut self,
+        packet: Packet,
+        deadline: Option<Ts>,
+        priority: Option<TxPriority>,
+    ) -> Result<TxHandle, CoreError<<R as Radio>::Error>> {
+        let handle = self.next_handle;
+
+        let state = TxState {
+            handle,
+            expires: deadline,
+            priority,
+            ..Default::default()
+        };
+
+        // Hold frames until the transaction persistence time or deadline
+        let persist = self.timer.ticks_ms() + self.config.transaction_persistence_ms;
+        let expires = deadline.map_or(persist, |d| d.min(persist));
+
+        debug!(
+            "Holding packet {} for {:?} until {} ms",
+            packet.header.seq, packet.header.destination, expires
+        );
+
+        if let Err(_e) = self.indirect.push(expires, state, packet) {
+            error!("Error adding packet to indirect buffer");
+            return Err(CoreError::BufferFull);
+        }
+
+        self.next_handle = self.next_handle.wrapping_add(1);
+
+        Ok(handle)
+    }
+
+    /// Poll the sync parent for frames held for indirect transmission, for devices
+    /// without receive-on-idle. Received frames are collected via [`MacIf::receive`],
+    /// with the receiver enabled until these are received or the
+    /// [`Config::data_request_timeout`] elapses
+    pub fn poll_parent(&mut self) -> Result<(), CoreError<<R as Radio>::Error>> {
+        let parent = match self.sync_state {
+            SyncState::Synced(parent) if self.assoc_state.is_associated() => parent,
+            _ => return Err(CoreError::NotAssociated),
+        };
+
+        // Only one data request may be outstanding
+        if self.data_request.is_some() {
+            return Err(CoreError::Busy);
+        }
+
+        self.request_data(self.timer.ticks_ms(), parent)
+    }
+
+    /// Request pending data from our parent
+    fn request_data(
+        &mut self,
+        now: u64,
+        parent: Address,
+    ) -> Result<(), CoreError<<R as Radio>::Error>> {
+        let req = Packet::command(parent, self.addr(), self.seq(), Command::DataRequest);
+
+        if let Err(e) = self.enqueue_tx(req) {
+            error!("Error adding data request to tx buffer");
+            return Err(e);
+        }
+
+        debug!("Issued data request to {:?}", parent);
+
+        self.data_request = Some(now + self.config.data_request_timeout);
+
+        Ok(())
+    }
+
+    /// Check whether frames are held for indirect transmission to the provided device
+    fn indirect_pending(&self, addr: &Address) -> bool {
+        let assoc = self.assoc_table.lookup_address(addr);
+
+        self.indirect
+            .contains(|a| a == addr || assoc.map(|x| x.matches(a)).unwrap_or(false))
+    }
+
+    /// Record the outcome of a transmission for collection via `tx_status`
+    fn tx_done(&mut self, handle: TxHandle, status: TxStatus) {
+        debug!("TX {} complete: {}", handle, status);
+
+        if let TxStatus::Sent | TxStatus::Acked = status {
+            self.stats.tx_ok = self.stats.tx_ok.saturating_add(1);
+        }
+
+        // Drop the oldest outcome if these are not being collected
+        if self.tx_results.is_full() {
+            let _ = self.tx_results.dequeue();
+        }
+
+        let _ = self.tx_results.enqueue((handle, status));
+    }
+
+    /// Encode a packet for transmission, returning `None` and counting the
+    /// frame as dropped where this does not fit the radio buffer
+    fn encode_tx(&mut self, packet: &Packet, buff: &mut [u8]) -> Option<usize> {
+        match packet.encode(buff, WriteFooter::No) {
+            Ok(n) => Some(n),
+            Err(e) => {
+                error!(
+                    "Dropped packet {} failing to encode: {:?}",
+                    packet.header.seq, e
                 );
+                self.stats.tx_drop_encode = self.stats.tx_drop_encode.saturating_add(1);
+                None
+            }
+        }
+    }
 
-                let i = RxInfo {
-                    source: p.header.source,
-                    rssi: rx.rssi,
-                };
+    /// Drop the packet at the head of the TX queue on failing to encode
+    fn tx_drop_head(&mut self) {
+        if let Some((s, _p)) = self.tx_buff.dequeue() {
+            self.tx_done(s.handle, TxStatus::Failed(TxFailure::Oversize));
+        }
+    }
 
-                // Enqueue in RX buffer
-                if let Err(_e) = self.rx_buff.enqueue((i, p)) {
-                    error!("Error adding packet to RX queue");
-                }
+    /// Check whether the CFP has space for guaranteed time slots
+    fn gts_permit(&self) -> bool {
+        let (first, last) = self.config.cfp_slots();
+        first <= last
+    }
+
+    /// Check whether a packet is to be sent in our guaranteed time slots
+    fn gts_dest(&self, packet: &Packet) -> bool {
+        match (self.gts_state, self.sync_state) {
+            (GtsState::Granted(_), SyncState::Synced(parent)) => {
+                packet.header.destination == parent
             }
+            _ => false,
+        }
+    }
+
+    /// Fetch the end of the current superframe active period, where duty cycling
+    fn active_end(&self) -> Option<u64> {
+        let superframe = self.superframe_duration() as u64;
+        let active = self.active_duration() as u64;
+
+        if !self.config.duty_cycle
+            || self.config.pan_coordinator
+            || !self.sync_state.is_synced()
+            || self.next_beacon == 0
+            || active >= superframe
+        {
+            return None;
+        }
+
+        // Superframes start at the expected beacon time
+        Some(self.next_beacon.saturating_sub(superframe) + active)
+    }
+
+    /// Check for pending operations requiring the radio to remain awake
+    fn sleep_blocked(&self) -> bool {
+        !self.tx_buff.is_empty()
+            || self.csma_state != CsmaState::None
+            || self.ack_state != AckState::None
+            || self.ack_wait != AckWait::None
+            || self.data_request.is_some()
+            || matches!(self.assoc_state, AssocState::Pending(..))
+            || self.base.state() != BaseState::Listening
+    }
+
+    /// Sleep the radio between the end of the active period and
+    /// the wake guard prior to the next beacon, where nothing is pending
+    fn tick_sleep(&mut self, now_ms: u64) -> Result<(), CoreError<<R as Radio>::Error>> {
+        let active_end = match self.active_end() {
+            Some(t) => t,
+            None => return Ok(()),
+        };
+        let wake_at = self.next_beacon.saturating_sub(self.config.wake_guard);
+
+        match self.sleep_state {
+            SleepState::Awake => (),
+            SleepState::Held(until) if now_ms >= until => (),
+            _ => return Ok(()),
+        }
+
+        if now_ms < active_end || now_ms >= wake_at || self.sleep_blocked() {
+            return Ok(());
+        }
+
+        debug!(
+            "Sleeping at {} ms until {} ms (next beacon at {} ms)",
+            now_ms, wake_at, self.next_beacon
+        );
+
+        self.base.sleep(now_ms)?;
+
+        self.sleep_state = SleepState::Asleep(now_ms, wake_at);
+        self.stats.sleeps = self.stats.sleeps.saturating_add(1);
+
+        Ok(())
+    }
+
+    /// Wake the radio ahead of the next beacon, or early where transmissions
+    /// are pending or sync has been dropped
+    fn tick_wake(&mut self, now_ms: u64) -> Result<(), CoreError<<R as Radio>::Error>> {
+        let (slept_at, wake_at) = match self.sleep_state {
+            SleepState::Asleep(slept_at, wake_at) => (slept_at, wake_at),
+            _ => return Ok(()),
+        };
+
+        // Radio already woken by another operation
+        if self.base.state() != BaseState::Sleeping {
+            self.sleep_state = SleepState::Awake;
+            self.stats.sleep_ms = self
+                .stats
+                .sleep_ms
+                .saturating_add(now_ms.saturating_sub(slept_at));
+            return Ok(());
         }
 
+        if now_ms >= wake_at {
+            debug!("Waking for beacon at {} ms", now_ms);
+        } else if !self.tx_buff.is_empty() || !self.sync_state.is_synced() {
+            debug!("Early wake for pending operations at {} ms", now_ms);
+        } else {
+            return Ok(());
+        }
+
+        self.wake_radio(now_ms, slept_at)
+    }
+
+    /// Return the radio to receive mode following sleep
+    fn wake_radio(
+        &mut self,
+        now_ms: u64,
+        slept_at: u64,
+    ) -> Result<(), CoreError<<R as Radio>::Error>> {
+        self.base.receive(now_ms)?;
+
+        self.sleep_state = SleepState::Awake;
+        self.stats.sleep_ms = self
+            .stats
+            .sleep_ms
+            .saturating_add(now_ms.saturating_sub(slept_at));
+
         Ok(())
     }
-}
 
-fn calculate_offset(now: i64, expected: i64, frame: i64) -> i64 {
-    // Compute the difference between our expectation and the actual rx time
-    let mut delta = (now - expected) % frame;
+    /// Request guaranteed time slots from our parent if configured
+    /// Drop the current association
+    fn drop_association(&mut self) {
+        self.stats.sync_fail = self.stats.sync_fail.saturating_add(1);
+        self.assoc_state = AssocState::Unassociated;
+        self.short_addr = None;
+        self.gts_state = GtsState::None;
+    }
+
+    /// Issue orphan notifications with backoff while sync is lost,
+    /// prompting the coordinator to respond with a realignment
+    fn tick_rejoin(
+        &mut self,
+        now_ms: u64,
+        parent: Address,
+    ) -> Result<(), CoreError<<R as Radio>::Error>> {
+        if now_ms < self.rejoin_at || self.base.is_busy() {
+            return Ok(());
+        }
+
+        info!(
+            "Issuing orphan notification for {:?} at {} ms",
+            parent, now_ms
+        );
+
+        // Orphan notifications are always sent from the extended address
+        let mut req = Packet::command(
+            Address::broadcast(&AddressMode::Short),
+            Address::Extended(self.pan_id(), self.address),
+            self.seq(),
+            Command::OrphanNotification,
+        );
+        req.header.ack_request = false;
+
+        let mut buff = [0u8; TX_BUFF_LEN];
+        if let Some(n) = self.encode_tx(&req, &mut buff) {
+            self.base.transmit(now_ms, &buff[..n])?;
+        }
+
+        self.stats.rejoin_attempts = self.stats.rejoin_attempts.saturating_add(1);
+        self.rejoin_at = now_ms + self.rejoin_backoff;
+        self.rejoin_backoff = (self.rejoin_backoff * 2).min(self.config.rejoin_backoff_max);
+
+        Ok(())
+    }
+
+    fn tick_gts_request(&mut self, now_ms: u64, parent: Address) {
+        match self.gts_state {
+            GtsState::None if self.config.gts_request_slots > 0 && self.short_addr.is_some() => {
+                let slots = self.config.gts_request_slots;
+                if let Err(_) = self.send_gts_request(now_ms, parent, slots, GtsDirection::Transmit)
+                {
+                    error!("Error adding GTS request to tx buffer");
+                }
+            }
+            GtsState::Pending(expiry) if now_ms > expiry => {
+                warn!("GTS request expired at {} ms", now_ms);
+                self.gts_state = GtsState::None;
+            }
+            _ => (),
+        }
+    }
+
+    /// Send a guaranteed time slot request to the parent, pending a grant via
+    /// the parent beacon
+    fn send_gts_request(
+        &mut self,
+        now_ms: u64,
+        parent: Address,
+        slots: u8,
+        direction: GtsDirection,
+    ) -> Result<(), CoreError<<R as Radio>::Error>> {
+        let req = Command::GuaranteedTimeSlotRequest(GuaranteedTimeSlotCharacteristics {
+            count: slots,
+            receive_only: direction == GtsDirection::Receive,
+            allocation: true,
+        });
+
+        let packet = Packet::command(parent, self.addr(), self.seq(), req);
+        self.enqueue_tx(packet)?;
+
+        info!(
+            "Requesting {} guaranteed time slots ({:?})",
+            slots, direction
+        );
+
+        self.gts_state = GtsState::Pending(now_ms + self.config.gts_timeout);
+
+        Ok(())
+    }
+
+    /// Resolve the short address of a device, for devices using extended
+    /// addresses this is found via the association table
+    fn device_short(&self, addr: &Address) -> Option<ShortAddress> {
+        match addr {
+            Address::Short(_, s) => Some(*s),
+            a => self.assoc_table.lookup_address(a).map(|a| a.short),
+        }
+    }
+
+    /// Update an energy detection scan, tuning to each channel in turn
+    /// and sampling the RSSI for the dwell time
+    fn tick_scan(&mut self, now_ms: u64) -> Result<(), CoreError<<R as Radio>::Error>> {
+        // Wait for any current operation to complete
+        if self.base.is_busy() {
+            return Ok(());
+        }
+
+        let (kind, dwell_end, channel) = match &self.scan {
+            Some(s) => (s.kind(), s.dwell_end(), s.current().cloned()),
+            None => return Ok(()),
+        };
+
+        match (dwell_end, channel) {
+            // Tune to the next channel
+            (None, Some(ch)) => {
+                self.base.set_channel(now_ms, &ch)?;
+                self.channel = Some(ch);
+
+                if let Some(s) = &mut self.scan {
+                    s.start(now_ms);
+                    debug!("Scanning channel {} at {} ms", s.progress().0, now_ms);
+                }
+
+                // Active scans request beacons from coordinators on the channel
+                if kind == ScanKind::Active {
+                    let mut req = Packet::command(
+                        Address::broadcast(&AddressMode::Short),
+                        self.addr(),
+                        self.seq(),
+                        Command::BeaconRequest,
+                    );
+                    req.header.ack_request = false;
+
+                    let mut buff = [0u8; TX_BUFF_LEN];
+                    if let Some(n) = self.encode_tx(&req, &mut buff) {
+                        self.base.transmit(now_ms, &buff[..n])?;
+                    }
+                }
+            }
+            // Complete the current channel and move to the next
+            (Some(end), _) if now_ms >= end => {
+                if let Some(s) = &mut self.scan {
+                    s.finish();
+                }
+
+                return match self.scanning() {
+                    true => self.tick_scan(now_ms),
+                    false => {
+                        self.scan_done(now_ms);
+                        Ok(())
+                    }
+                };
+            }
+            (Some(_), _) => (),
+            (None, None) => return Ok(()),
+        }
+
+        // Sample the channel RSSI for energy detection
+        if kind != ScanKind::Energy {
+            return Ok(());
+        }
+
+        let rssi = self.base.rssi(now_ms)?;
+        if let Some(s) = &mut self.scan {
+            s.sample(rssi);
+        }
+
+        Ok(())
+    }
+
+    /// Apply the outcome of a completed energy detection scan
+    fn scan_done(&mut self, now_ms: u64) {
+        let scan = match &self.scan {
+            Some(s) => s,
+            None => return,
+        };
+
+        info!(
+            "Completed scan of {} channels at {} ms",
+            scan.progress().1,
+            now_ms
+        );
+
+        // Select the quietest channel, returned to via `tick_channel`
+        if let Some(r) = scan.quietest().filter(|_| scan.select) {
+            info!(
+                "Selected channel with max RSSI {} (mean {})",
+                r.max_rssi, r.mean_rssi
+            );
+            self.config.channel = Some(r.channel.clone());
+
+            // Beacon from the end of the scan
+            if self.config.pan_coordinator && self.config.mac_beacon_order != BeaconOrder::OnDemand
+            {
+                self.next_beacon = now_ms + self.config.superframe_duration() as u64;
+            }
+        }
+    }
+
+    /// Select the channel for the current slot, following the hopping sequence
+    /// where enabled and otherwise using the configured channel
+    fn tick_channel(&mut self, now_ms: u64) -> Result<(), CoreError<<R as Radio>::Error>> {
+        // ASN is recomputed as the sync offset may be updated on beacon receipt
+        let asn = self.config.calculate_asn(now_ms, self.sync_offset);
+
+        let channel = match (&self.config.hopping_sequence, self.hopping) {
+            (Some(h), Some(offset)) => h.channel(asn.wrapping_add(offset)),
+            _ => self.config.channel.as_ref(),
+        };
+
+        // Skip where unchanged, or defer until the radio is no longer busy
+        let channel = match channel {
+            Some(c) if self.channel.as_ref() != Some(c) && !self.base.is_busy() => c.clone(),
+            _ => return Ok(()),
+        };
+
+        trace!("Switching channel for ASN: {} at {} ms", asn, now_ms);
+
+        self.base.set_channel(now_ms, &channel)?;
+        self.channel = Some(channel);
+
+        Ok(())
+    }
+
+    /// Build a beacon for the provided ASN
+    fn beacon(&mut self, asn: u64) -> Packet {
+        let seq = self.seq();
+        let packet = self.beacon_frame(seq, asn, true);
+
+        // GTS and pending address fields vary, so the application payload may
+        // not always fit alongside these
+        if !beacon_fits(&packet) {
+            warn!("Beacon payload exceeds available frame space, omitting");
+            return self.beacon_frame(seq, asn, false);
+        }
+
+        packet
+    }
+
+    /// Build a beacon frame, including the application payload where `app_payload` is set
+    fn beacon_frame(&self, seq: u8, asn: u64, app_payload: bool) -> Packet {
+        // TODO: beacon type varies with TSCH/non-tsch?
+        let beacon = Beacon {
+            superframe_spec: self.config.superframe_spec(),
+            // TODO: replace placeholders with actual configuration
+            guaranteed_time_slot_info: self.gts_table.info(self.gts_permit()),
+            pending_address: self.indirect.pending_address(),
+        };
+
+        let mut packet = Packet::beacon(self.addr(), seq, beacon);
+
+        // Advertise the hopping sequence and beacon ASN, with the application payload
+        let content = BeaconContent {
+            hopping: self
+                .config
+                .hopping_sequence
+                .as_ref()
+                .map(|h| HoppingInfo { id: h.id, asn }),
+            app: match app_payload {
+                true => &self.beacon_payload,
+                false => &[],
+            },
+        };
+
+        let mut payload = [0u8; BeaconContent::MAX_LEN];
+        let n = content.encode(&mut payload);
+
+        let _ = packet.set_payload(&payload[..n]);
+
+        packet
+    }
+
+    fn tick_beacon(&mut self, now_ms: u64, asn: u64) -> Result<(), CoreError<<R as Radio>::Error>> {
+        // No ASN change / nothing we need to do for beaconing
+        if self.last_asn == asn {
+            return Ok(());
+        }
+
+        // No pending beacon or not yet expected beacon time
+        if self.next_beacon == 0 || self.next_beacon > now_ms {
+            return Ok(());
+        }
+
+        // Check for schedule misses
+        // (self.next_beacon updated on receipt of viable beacon)
+        if (self.next_beacon + self.config.mac_deadline as u64) < now_ms {
+            // Desync after configured number of beacon misses
+            if let SyncState::Synced(parent) = self.sync_state {
+                self.beacon_miss_count += 1;
+
+                if self.beacon_miss_count > self.config.max_beacon_misses {
+                    warn!("Exceeded maximum beacon misses, synchronization lost");
+                    self.next_beacon = 0;
+                    self.hopping = None;
+
+                    // Retain the association while attempting to re-sync
+                    self.sync_state = match self.assoc_state {
+                        AssocState::Associated(_) if self.config.rejoin_grace > 0 => {
+                            self.rejoin_at = now_ms;
+                            self.rejoin_backoff = self.config.rejoin_backoff;
+                            SyncState::Lost(parent, now_ms + self.config.rejoin_grace)
+                        }
+                        _ => {
+                            self.network = None;
+                            SyncState::Unsynced
+                        }
+                    };
+
+                    return Ok(());
+                }
+            } else {
+                // TODO: Count coordinator schedule misses here
+            }
+        }
+
+        // PAN coordinator broadcasts beacons
+        // TODO: as do other coordinators in their respective slots? need to tx and rx for these
+        // TODO: is there still a randomness to this to avoid collisions if neighbors > slotframe count?
+        if self.config.pan_coordinator {
+            debug!("Broadcasting beacon in ASN: {} at {} ms", asn, now_ms);
+
+            let packet = self.beacon(asn);
+
+            let mut buff = [0u8; TX_BUFF_LEN];
+            if let Some(n) = self.encode_tx(&packet, &mut buff) {
+                self.base.transmit(now_ms, &buff[..n])?;
+                self.stats.tx_beacons = self.stats.tx_beacons.saturating_add(1);
+            }
+
+            // Re-arm beacon for next slot
+            self.next_beacon += self.superframe_duration() as u64;
+
+            debug!("Armed next beacon TX for {} ms", self.next_beacon);
+        } else {
+            debug!("Set beacon RX for ASN: {} at {} ms", asn, now_ms);
+
+            if self.base.state() != BaseState::Listening {
+                self.base.receive(now_ms)?;
+            }
+
+            // TODO: re-arm beacon or keep listening depending on join state?
+            // This has to happen _after_ rx I guess
+            // so we need a timeout on operations? or maybe on slots?
+
+            self.next_beacon += self.superframe_duration() as u64;
+            debug!("Arm next beacon RX for {} ms", self.next_beacon);
+        }
+
+        Ok(())
+    }
+
+    fn tick_cap(&mut self, now_ms: u64, asn: u64) -> Result<(), CoreError<<R as Radio>::Error>> {
+        let rsn = self.config.calculate_rsn(now_ms, self.sync_offset);
+
+        if asn != self.cap_asn && rsn == 0 {
+            self.cap_asn = asn;
+
+            // Bursts end with the superframe
+            self.burst = 0;
+
+            // If we're already attempting CSMA, restart if possible
+            if let CsmaState::Pending {
+                packet,
+                tx_slot,
+                retries,
+                ..
+            } = &self.csma_state
+            {
+                // Limit CSMA backoff retries
+                if *retries >= self.config.csma_max_backoffs as u64 {
+                    warn!("CSMA TX failed for packet {}", packet.header.seq);
+                    self.stats.csma_cca_fail = self.stats.csma_cca_fail.saturating_add(1);
+
+                    // TODO: should _mac_ ACK/Retry cause CSMA re-attempts?
+
+                    self.csma_state = CsmaState::None;
+                    if let Some((s, _p)) = self.tx_buff.dequeue() {
+                        self.tx_done(s.handle, TxStatus::Failed(TxFailure::ChannelBusy));
+                    }
+                } else if *tx_slot == 0 {
+                    // Re-schedule CSMA attempt
+                    let be = (self.config.min_be as u32 + *retries as u32)
+                        .min(self.config.max_be as u32);
+
+                    let backoff = (OsRng {}.next_u32() % (2u32.pow(be as u32) - 1)) as u64 + 1;
+
+                    debug!(
+                        "Scheduling CSMA TX retry for ASN {} ({} slots)",
+                        asn + backoff,
+                        backoff
+                    );
+
+                    self.csma_state = CsmaState::Pending {
+                        packet: packet.clone(),
+                        tx_slot: asn + backoff,
+                        retries: *retries + 1,
+                        cca: self.cca_start(),
+                    };
+                }
+
+            // Otherwise if we have something to TX (outside our GTS) and are not
+            // awaiting an ACK, get started
+            } else if let Some(tx) = self
+                .tx_buff
+                .peek()
+                .filter(|(_, p)| self.ack_wait == AckWait::None && !self.gts_dest(p))
+                .map(|v| v.clone())
+            {
+                debug!(
+                    "Found pending packet {} to: {:?}",
+                    tx.1.header.seq, tx.1.header.destination
+                );
+
+                self.csma_schedule(asn, &tx);
+            }
+
+        // Following a CSMA transmission, send queued frames in the remainder of the CAP
+        } else if self.burst > 0 {
+            self.tick_burst(now_ms, asn, rsn)?;
+
+        // In other slots _if_ we have a pending TX, run CSMA
+        } else if let CsmaState::Pending {
+            packet,
+            tx_slot,
+            retries,
+            cca,
+        } = self.csma_state.clone()
+        {
+            if rsn > self.config.final_cap_slot as u64 {
+                // No CSMA in the CFP, defer to the next CAP if the slot has been reached
+                if tx_slot != 0 && asn >= tx_slot {
+                    debug!("CSMA TX slot in CFP, deferring");
+
+                    self.csma_state = CsmaState::Pending {
+                        packet: packet.clone(),
+                        tx_slot: 0,
+                        retries,
+                        cca: self.cca_start(),
+                    };
+                }
+            } else if asn < tx_slot {
+                // Assess the channel prior to the TX slot, if we're not clear try again
+                self.csma_state = match self.tick_cca(now_ms, cca)? {
+                    Some(cca) => CsmaState::Pending {
+                        packet,
+                        tx_slot,
+                        retries,
+                        cca,
+                    },
+                    None => {
+                        debug!("CCA fail at ASN: {}", asn);
+                        CsmaState::Pending {
+                            packet,
+                            tx_slot: 0,
+                            retries: retries + 1,
+                            cca: self.cca_start(),
+                        }
+                    }
+                };
+            } else if asn == tx_slot && cca != CcaState::Clear {
+                // Channel not assessed in time, try again
+                debug!("CCA incomplete at ASN: {}", asn);
+
+                self.csma_state = CsmaState::Pending {
+                    packet,
+                    tx_slot: 0,
+                    retries: retries + 1,
+                    cca: self.cca_start(),
+                };
+            } else if asn == tx_slot {
+                self.csma_transmit(now_ms, &packet)?;
+            } else if tx_slot != 0 && asn > tx_slot {
+                warn!("CSMA TX slot miss");
+                self.stats.deadline_miss_tx = self.stats.deadline_miss_tx.saturating_add(1);
+
+                self.csma_state = CsmaState::Pending {
+                    packet: packet.clone(),
+                    tx_slot: 0,
+                    retries: retries + 1,
+                    cca: self.cca_start(),
+                };
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Send queued frames consecutively following a CSMA transmission within the CAP,
+    /// each once the previous frame (and any ACK) completes and CCA passes, ending the
+    /// burst where the channel is busy, see [`Config::cap_burst`]
+    fn tick_burst(
+        &mut self,
+        now_ms: u64,
+        asn: u64,
+        rsn: u64,
+    ) -> Result<(), CoreError<<R as Radio>::Error>> {
+        // Bursts end with the CAP, with pending frames deferred to CSMA in the next
+        if rsn > self.config.final_cap_slot as u64 {
+            debug!("CAP burst ended at {} ms", now_ms);
+            self.burst = 0;
+            self.csma_defer();
+            return Ok(());
+        }
+
+        // Await completion of the previous frame, and of any pending ACKs
+        if self.base.is_busy()
+            || self.ack_wait != AckWait::None
+            || self.ack_state != AckState::None
+            || now_ms < self.burst_hold
+        {
+            return Ok(());
+        }
+
+        // Start the next frame, failing this where retries have been exhausted
+        if self.csma_state == CsmaState::None {
+            match self
+                .tx_buff
+                .peek()
+                .filter(|(_, p)| !self.gts_dest(p))
+                .cloned()
+            {
+                Some(tx) => self.csma_schedule(asn, &tx),
+                None => {
+                    self.burst = 0;
+                    return Ok(());
+                }
+            }
+        }
+        let (packet, retries, cca) = match self.csma_state.clone() {
+            CsmaState::Pending {
+                packet,
+                retries,
+                cca,
+                ..
+            } => (packet, retries, cca),
+            CsmaState::None => return Ok(()),
+        };
+
+        match self.tick_cca(now_ms, cca)? {
+            Some(CcaState::Clear) => self.csma_transmit(now_ms, &packet)?,
+            Some(cca) => {
+                self.csma_state = CsmaState::Pending {
+                    packet,
+                    tx_slot: 0,
+                    retries,
+                    cca,
+                }
+            }
+            None => {
+                debug!("CCA fail in CAP burst at ASN: {}", asn);
+                self.burst = 0;
+                self.csma_state = CsmaState::Pending {
+                    packet,
+                    tx_slot: 0,
+                    retries: retries + 1,
+                    cca: self.cca_start(),
+                };
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Defer a pending CSMA transmission to the next superframe
+    fn csma_defer(&mut self) {
+        let cca = self.cca_start();
+        if let CsmaState::Pending {
+            tx_slot, cca: c, ..
+        } = &mut self.csma_state
+        {
+            *tx_slot = 0;
+            *c = cca;
+        }
+    }
+
+    /// Transmit a packet following CSMA, holding this until acknowledged where
+    /// requested and arming any following CAP burst
+    fn csma_transmit(
+        &mut self,
+        now_ms: u64,
+        packet: &Packet,
+    ) -> Result<(), CoreError<<R as Radio>::Error>> {
+        // Prepare packet and transmit, dropping packets that cannot be encoded
+        let mut buff = [0u8; TX_BUFF_LEN];
+        let n = match self.encode_tx(packet, &mut buff) {
+            Some(n) => n,
+            None => {
+                self.csma_state = CsmaState::None;
+                self.tx_drop_head();
+                return Ok(());
+            }
+        };
+
+        self.base.transmit(now_ms, &buff[..n])?;
+
+        debug!("CSMA TX at {} ms", now_ms);
+
+        // Update CSMA state and packet buffer
+        self.csma_state = CsmaState::None;
+        self.burst = match self.burst {
+            0 => self.config.cap_burst,
+            n => n - 1,
+        };
+
+        if !packet.header.ack_request {
+            if let Some((s, _p)) = self.tx_buff.dequeue() {
+                self.tx_done(s.handle, TxStatus::Sent);
+            }
+        } else {
+            // Hold the packet until acknowledged (or timed out)
+            self.ack_wait = AckWait::Pending {
+                seq: packet.header.seq,
+                tx_time: now_ms,
+            };
+        }
+
+        Ok(())
+    }
+
+    /// Sample the channel for clear channel assessment per [`Config::cca_mode`],
+    /// returning the updated CCA state or `None` where the channel is busy
+    fn tick_cca(
+        &mut self,
+        now_ms: u64,
+        cca: CcaState,
+    ) -> Result<Option<CcaState>, CoreError<<R as Radio>::Error>> {
+        let (threshold, samples, interval) = match self.config.cca_mode {
+            CcaMode::EnergyAboveThreshold {
+                threshold,
+                samples,
+                sample_interval_ms,
+            } => (threshold, samples, sample_interval_ms as u64),
+            CcaMode::AlwaysClear => return Ok(Some(CcaState::Clear)),
+        };
+
+        let (taken, last_ms) = match cca {
+            CcaState::Sampling { samples, last_ms } => (samples, last_ms),
+            CcaState::Clear => return Ok(Some(cca)),
+        };
+        if last_ms.map(|t| now_ms < t + interval).unwrap_or(false) {
+            return Ok(Some(cca));
+        }
+
+        let rssi = self.base.rssi(now_ms)?;
+
+        let s = &mut self.stats;
+        s.cca_rssi_min = Some(s.cca_rssi_min.map_or(rssi, |r| r.min(rssi)));
+        s.cca_rssi_max = Some(s.cca_rssi_max.map_or(rssi, |r| r.max(rssi)));
+
+        if rssi > threshold {
+            debug!("CCA busy at {} ms (rssi: {})", now_ms, rssi);
+            return Ok(None);
+        }
+
+        if taken + 1 >= samples {
+            debug!("CCA clear at {} ms ({} samples)", now_ms, taken + 1);
+            return Ok(Some(CcaState::Clear));
+        }
+
+        Ok(Some(CcaState::Sampling {
+            samples: taken + 1,
+            last_ms: Some(now_ms),
+        }))
+    }
+
+    /// Fetch the initial CCA state for newly scheduled CSMA transmissions
+    fn cca_start(&self) -> CcaState {
+        match self.config.cca_mode {
+            CcaMode::AlwaysClear => CcaState::Clear,
+            _ => CcaState::Sampling {
+                samples: 0,
+                last_ms: None,
+            },
+        }
+    }
+
+    fn tick_cfp(&mut self, now_ms: u64, asn: u64) -> Result<(), CoreError<<R as Radio>::Error>> {
+        let rsn = self.config.calculate_rsn(now_ms, self.sync_offset);
+
+        // Only applicable within our granted slots
+        let slot = match self.gts_state {
+            GtsState::Granted(s) => s,
+            _ => return Ok(()),
+        };
+        if rsn <= self.config.final_cap_slot as u64 || !slot.contains(rsn as u8) {
+            return Ok(());
+        }
+
+        // Transmit at most once per slot, and not while awaiting an ACK
+        if asn == self.last_gts_asn || self.base.is_busy() || self.ack_wait != AckWait::None {
+            return Ok(());
+        }
+
+        let tx = match self.tx_buff.peek() {
+            Some(tx) if self.gts_dest(&tx.1) => tx.clone(),
+            _ => return Ok(()),
+        };
+
+        // Check TX retries and increase counter
+        if tx.0.retries > self.config.max_retries {
+            debug!("Packet {} TX failed exceeded max retries", tx.1.header.seq);
+            self.stats.tx_fail = self.stats.tx_fail.saturating_add(1);
+
+            let _ = self.tx_buff.dequeue();
+            self.tx_done(tx.0.handle, TxStatus::Failed(TxFailure::RetriesExceeded));
+
+            return Ok(());
+        }
+        if let Some((s, _)) = self.tx_buff.peek_mut() {
+            s.retries += 1;
+        }
+
+        // Transmit without CSMA, dropping packets that cannot be encoded
+        let mut buff = [0u8; TX_BUFF_LEN];
+        let n = match self.encode_tx(&tx.1, &mut buff) {
+            Some(n) => n,
+            None => {
+                self.tx_drop_head();
+                return Ok(());
+            }
+        };
+
+        self.base.transmit(now_ms, &buff[..n])?;
+        self.last_gts_asn = asn;
+
+        debug!("GTS TX in slot {} at {} ms", rsn, now_ms);
+
+        if !tx.1.header.ack_request {
+            if let Some((s, _p)) = self.tx_buff.dequeue() {
+                self.tx_done(s.handle, TxStatus::Sent);
+            }
+        } else {
+            self.ack_wait = AckWait::Pending {
+                seq: tx.1.header.seq,
+                tx_time: now_ms,
+            };
+        }
+
+        Ok(())
+    }
+
+    /// Schedule a CSMA transmission for the packet at the head of the TX buffer,
+    /// failing the packet where retries have been exhausted
+    fn csma_schedule(&mut self, asn: u64, tx: &(TxState, Packet)) {
+        // Check TX retries and increase counter
+        if tx.0.retries > self.config.max_retries {
+            debug!("Packet {} TX failed exceeded max retries", tx.1.header.seq);
+            self.stats.tx_fail = self.stats.tx_fail.saturating_add(1);
+
+            let _ = self.tx_buff.dequeue();
+            self.tx_done(tx.0.handle, TxStatus::Failed(TxFailure::RetriesExceeded));
+
+            return;
+        }
+        if let Some((s, _)) = self.tx_buff.peek_mut() {
+            s.retries += 1;
+        }
+
+        // Calcuate backoff periods for TX
+        let be = match self.config.battery_life_extension {
+            true => 2.min(self.config.min_be),
+            false => self.config.min_be,
+        };
+
+        let backoff = (OsRng {}.next_u32() % (2u32.pow(be as u32) - 1)) as u64 + 1;
+
+        debug!(
+            "Scheduling CSMA TX for ASN {} ({} slots)",
+            asn + backoff,
+            backoff
+        );
+
+        self.csma_state = CsmaState::Pending {
+            packet: tx.1.clone(),
+            tx_slot: asn + backoff,
+            retries: 0,
+            cca: self.cca_start(),
+        };
+    }
+
+    /// Record a beacon heard during the sync window, opening the window on the first
+    fn add_sync_candidate(&mut self, candidate: SyncCandidate) {
+        if self.sync_window.is_none() {
+            debug!("Opened sync window at {} ms", candidate.rx_time);
+            self.sync_window = Some(candidate.rx_time + self.config.sync_window);
+        }
+
+        let source = candidate.source;
+        match self.sync_candidates.iter_mut().find(|c| c.source == source) {
+            Some(c) => *c = candidate,
+            None => {
+                if self.sync_candidates.push(candidate).is_err() {
+                    debug!("Sync candidates full, ignoring beacon from {:?}", source);
+                }
+            }
+        }
+    }
+
+    /// Adopt the sync candidate with the strongest average RSSI once the sync window closes
+    fn tick_sync_window(&mut self, now_ms: u64) {
+        match self.sync_window {
+            Some(end) if now_ms >= end => self.sync_window = None,
+            _ => return,
+        }
+
+        let candidates = core::mem::take(&mut self.sync_candidates);
+        if self.sync_state != SyncState::Unsynced {
+            return;
+        }
+
+        // Ties are resolved in favour of the first heard
+        let neighbours = &self.neighbours;
+        let best = candidates.iter().rev().max_by_key(|c| {
+            neighbours
+                .lookup(&c.source)
+                .map(|n| n.rssi())
+                .unwrap_or(i16::MIN)
+        });
+        let c = match best {
+            Some(c) => c.clone(),
+            None => return,
+        };
+
+        info!(
+            "Selected sync parent {:?} from {} candidates",
+            c.source,
+            candidates.len()
+        );
+
+        self.adopt_parent(c.rx_time, c.source, c.network, c.hopping);
+
+        // Skip beacons passed while the window was open
+        let superframe = self.superframe_duration() as u64;
+        while superframe > 0 && self.next_beacon <= now_ms {
+            self.next_beacon += superframe;
+        }
+    }
+
+    /// Fetch the network configuration advertised in a beacon
+    fn beacon_network(&self, source: &Address, spec: &SuperframeSpecification) -> NetworkConfig {
+        let pan_id = match source {
+            Address::Short(pan_id, _) | Address::Extended(pan_id, _) => *pan_id,
+            Address::None => self.config.pan_id,
+        };
+
+        NetworkConfig::from_beacon(&self.config, pan_id, spec)
+    }
+
+    /// Adopt a sync parent and the advertised network configuration,
+    /// from the beacon received at the provided time
+    fn adopt_parent(
+        &mut self,
+        now: u64,
+        source: Address,
+        network: NetworkConfig,
+        hopping: Option<HoppingInfo>,
+    ) {
+        debug!("Adopting sync parent {:?}", source);
+
+        if !network.association_permit {
+            info!("Association not permitted by {:?}", source);
+        }
+        self.network = Some(network);
+
+        // Set sync state and compute next beacon time
+        // TODO: apply shift to compensate for time to tx/rx beacon
+        self.sync_state = SyncState::Synced(source);
+        // TODO: in TSCH impls sync offset set based on ASN
+        self.sync_offset = now;
+        self.sync_correction = 0;
+        self.drift.clear();
+        self.drift.record(now, self.config.drift_window as usize);
+        self.drift_rem = 0;
+
+        self.next_beacon = now + self.superframe_duration() as u64;
+        self.beacon_miss_count = 0;
+
+        debug!(
+            "Received beacon at {} ms (set offset to {} ms)",
+            now, self.sync_offset
+        );
+
+        // Follow the coordinator hopping sequence where this matches our own
+        match (&self.config.hopping_sequence, hopping) {
+            (Some(h), Some(i)) if h.id == i.id => {
+                let asn = self.config.calculate_asn(now, self.sync_offset);
+                debug!("Following hopping sequence {} from ASN: {}", i.id, i.asn);
+                self.hopping = Some(i.asn.wrapping_sub(asn));
+            }
+            (_, Some(i)) => {
+                warn!("Unsupported hopping sequence {} from coordinator", i.id);
+            }
+            _ => (),
+        }
+    }
+
+    fn handle_received(
+        &mut self,
+        now: u64,
+        rx: RawPacket,
+    ) -> Result<(), CoreError<<R as Radio>::Error>> {
+        // Decode packet
+        let mut p = match Packet::decode(rx.data(), false) {
+            Ok(p) => p,
+            // Malformed or foreign frames are dropped without failing the tick
+            Err(e) => {
+                warn!("Dropped undecodable packet: {:?}", e);
+                self.stats.rx_drop_decode = self.stats.rx_drop_decode.saturating_add(1);
+                return Ok(());
+            }
+        };
+
+        trace!(
+            "Received {} byte {:?} packet",
+            p.payload().len(),
+            p.header.frame_type
+        );
+
+        // Check beacons for PAN ID conflicts prior to filtering, using the source PAN ID
+        // as beacons are sent to the broadcast PAN
+        if self.config.pan_coordinator && matches!(p.content, FrameContent::Beacon(_)) {
+            match p.header.source {
+                Address::Short(pan_id, _) | Address::Extended(pan_id, _) => {
+                    self.check_pan_conflict(now, pan_id, &p.header.source)
+                }
+                Address::None => (),
+            }
+        }
+
+        // Filter by PAN ID
+        let pan_id = p.pan_id();
+        if pan_id != PanId::broadcast() {
+            match &self.assoc_state {
+                AssocState::Associated(id) if pan_id != *id => {
+                    debug!(
+                        "Pan ID mismatch, dropped packet {} for {:?}",
+                        p.header.seq, pan_id
+                    );
+                    self.stats.rx_drop_pan = self.stats.rx_drop_pan.saturating_add(1);
+                    return Ok(());
+                }
+                _ => (),
+            }
+        }
+
+        // Track link quality for neighbours, including frames not addressed to us
+        if p.header.source != Address::None {
+            let relation = Self::relation(&self.sync_state, &self.assoc_table, &p.header.source);
+            let beacon = matches!(p.content, FrameContent::Beacon(_));
+            self.neighbours
+                .received(p.header.source, rx.rssi, now, relation, beacon);
+        }
+
+        // Filter by address
+        match (p.header.destination, self.short_addr) {
+            // Accept messages to broadcast short address
+            (Address::Short(_, short), _) if short == ShortAddress::broadcast() => (),
+            // Accept messages to our short address
+            (Address::Short(_, short), Some(addr)) if short == addr => (),
+            // Accept messages to our extended address
+            (Address::Extended(_, ext), _) if ext == self.address => (),
+            // Deliver data frames for other devices where promiscuous, without
+            // acknowledgement, duplicate suppression or security processing
+            _ if self.config.promiscuous && matches!(p.content, FrameContent::Data) => {
+                self.stats.rx_ok = self.stats.rx_ok.saturating_add(1);
+                self.rx_enqueue(rx.rssi, p);
+                return Ok(());
+            }
+            _ => {
+                debug!(
+                    "Address mismatch, dropped packet {} for {:?}",
+                    p.header.seq, p.header.destination
+                );
+                self.stats.rx_drop_addr = self.stats.rx_drop_addr.saturating_add(1);
+                return Ok(());
+            }
+        };
+
+        // Arm ACK response if required, retransmissions received prior to sending
+        // the ACK are acknowledged by that already scheduled
+        match &self.ack_state {
+            AckState::Pending { packet, .. }
+                if p.header.ack_request
+                    && packet.header.destination == p.header.source
+                    && packet.header.seq == p.header.seq =>
+            {
+                debug!(
+                    "ACK already scheduled for packet {} from {:?}",
+                    p.header.seq, p.header.source
+                );
+            }
+            _ if p.header.ack_request => {
+                // Build ACK payload, signalling frames held for the device
+                let mut ack = Packet::ack(&p);
+                ack.header.frame_pending = self.indirect_pending(&p.header.source);
+
+                self.ack_state = AckState::Pending {
+                    tx_time: now + self.config.ack_delay,
+                    packet: ack,
+                };
+
+                debug!(
+                    "Scheduled ACK for packet {} from {:?} for {} ms",
+                    p.header.seq,
+                    p.header.source,
+                    now + self.config.ack_delay
+                );
+            }
+            _ => (),
+        }
+
+        // Drop retransmissions of data and command frames, these are still
+        // acknowledged above so the sender stops retrying
+        let dedup = matches!(p.content, FrameContent::Data | FrameContent::Command(_))
+            && self.config.dedup_sources > 0;
+        if dedup && self.rx_seen.is_duplicate(&p.header.source, p.header.seq) {
+            debug!(
+                "Duplicate packet {} from {:?}, dropped",
+                p.header.seq, p.header.source
+            );
+            self.stats.rx_duplicates = self.stats.rx_duplicates.saturating_add(1);
+            return Ok(());
+        }
+
+        // Verify and decrypt secured frames, dropping (and counting) failures
+        // so unauthenticated senders cannot fail the tick
+        if let Err(e) = self.unsecure_packet(rx.data(), &mut p) {
+            warn!(
+                "Security failure for packet {} from {:?}: {:?}",
+                p.header.seq, p.header.source, e
+            );
+            self.stats.security_fail = self.stats.security_fail.saturating_add(1);
+            return Ok(());
+        }
+
+        // Sequence numbers are only recorded for authentic frames
+        if dedup {
+            let limit = self.config.dedup_sources as usize;
+            self.rx_seen
+                .record_within(p.header.source, p.header.seq, limit);
+        }
+        self.stats.rx_ok = self.stats.rx_ok.saturating_add(1);
+
+        // Frames from devices maintain any guaranteed time slot allocation
+        if let Some(short) = self.device_short(&p.header.source) {
+            self.gts_table.active(&short, now);
+        }
+
+        // Handle received packets
+        match p.content {
+            FrameContent::Beacon(b) => {
+                debug!("Received beacon from {:?} at {} ms", p.header.source, now);
+                self.stats.rx_beacons = self.stats.rx_beacons.saturating_add(1);
+
+                // Hopping information is decoded where advertised, regardless
+                // of whether a hopping sequence is configured locally
+                let content = BeaconContent::decode(p.payload());
+                let hopping = content.hopping;
+                if !self.beacons.received(p.header.source, content.app, now) {
+                    debug!(
+                        "Dropped oversized beacon payload from {:?} ({} bytes)",
+                        p.header.source,
+                        content.app.len()
+                    );
+                }
+
+                // Record discovered PANs while scanning, rather than syncing
+                if let Some(s) = self
+                    .scan
+                    .as_mut()
+                    .filter(|s| !s.is_done() && s.kind() != ScanKind::Energy)
+                {
+                    let (pan_id, channel) = match (p.header.source, s.current()) {
+                        (Address::Short(pan_id, _), Some(c))
+                        | (Address::Extended(pan_id, _), Some(c)) => (pan_id, c.clone()),
+                        _ => return Ok(()),
+                    };
+
+                    debug!("Discovered PAN {:?} via {:?}", pan_id, p.header.source);
+
+                    s.add_pan(PanDescriptor {
+                        pan_id,
+                        coord_addr: p.header.source,
+                        channel,
+                        rssi: rx.rssi,
+                        beacon_order: b.superframe_spec.beacon_order,
+                        superframe_order: b.superframe_spec.superframe_order,
+                        association_permit: b.superframe_spec.association_permit,
+                    });
+
+                    return Ok(());
+                }
+
+                // If we're the pan coordinator we're not going to _sync_ on this
+                // (but it might be useful to look at for drift?)
+                if self.config.pan_coordinator {
+
+                    // After a PAN scan only beacons from the selected coordinator are adopted
+                } else if self.sync_state == SyncState::Unsynced
+                    && !self.join_permitted(&p.header.source)
+                {
+                    debug!(
+                        "Ignoring beacon from {:?}, not selected for join",
+                        p.header.source
+                    );
+
+                // If we're unsynced with a sync window configured, collect beacons
+                // to select the strongest parent once the window closes
+                } else if self.sync_state == SyncState::Unsynced && self.config.sync_window > 0 {
+                    self.add_sync_candidate(SyncCandidate {
+                        source: p.header.source,
+                        rx_time: now,
+                        network: self.beacon_network(&p.header.source, &b.superframe_spec),
+                        hopping,
+                    });
+
+                // If we're unsynced (or have lost sync with this parent) parse this
+                // and decide whether to adopt as the authorative time source
+                } else if self.sync_state == SyncState::Unsynced
+                    || matches!(
+                        self.sync_state,
+                        SyncState::Lost(parent, _) if parent == p.header.source
+                    )
+                {
+                    if let SyncState::Lost(..) = self.sync_state {
+                        info!("Re-synchronised with parent {:?}", p.header.source);
+                        self.stats.resyncs = self.stats.resyncs.saturating_add(1);
+                    }
+
+                    let network = self.beacon_network(&p.header.source, &b.superframe_spec);
+                    self.adopt_parent(now, p.header.source, network, hopping);
+
+                // If we're synced use this to evaluate drift and correct _if_ it's from
+                //our parent
+                } else if let SyncState::Synced(parent) = self.sync_state {
+                    if p.header.source != parent {
+                        debug!("Disgarding sync from non-parent: {:?}", p.header.source);
+
+                        // TODO: should this contribute to desyncing? often occurs because
+                        // the root has restarted so the time system is off
+                    } else {
+                        // Compute offset from expected time
+                        // This is improved by TSCH EBs / ASNs huh?
+                        // TODO: what happens if we're > one slot out of sync
+
+                        // Compute the error in frame sync
+                        let delta = (now as i64 - self.next_beacon as i64) as i64
+                            % self.superframe_duration() as i64;
+
+                        // Compute the difference between our expectation and the actual rx time
+                        // normalised within the frame time.
+                        let shift = calculate_offset(
+                            now as i64,
+                            self.next_beacon as i64,
+                            self.superframe_duration() as i64,
+                        );
+
+                        // TODO: update sync offset to match ASN (when beacons include this)
+                        // (or, split sync offset and ASN concepts)
+
+                        // Update beacon sync correction offset (eg. clock / timing compensation),
+                        // superseded by drift compensation once the drift rate is estimated
+                        self.drift.record(now, self.config.drift_window as usize);
+                        self.sync_correction = match self.drift_ppm() {
+                            Some(_) => 0,
+                            None => self.sync_correction + shift / 2,
+                        };
+
+                        debug!(
+                            "Received new beacon at {} ms (error: {} ms, new correction of {} ms)",
+                            now,
+                            delta,
+                            self.sync_correction
+                        );
+
+                        // Set new beacon expected time
+                        // TODO: really this should happen in tick rather than here?
+                        self.next_beacon =
+                            (now as i64 + self.beacon_interval() + self.sync_correction) as u64;
+                        self.beacon_miss_count = 0;
+                        debug!("Arm next beacon RX at {} ms", self.next_beacon);
+
+                        // Follow changes to the coordinator association permit
+                        if let Some(n) = self.network.as_mut() {
+                            n.association_permit = b.superframe_spec.association_permit;
+                        }
+                    }
+                }
+
+                // Apply parent beacon information
+                match self.sync_state {
+                    SyncState::Synced(parent)
+                        if parent == p.header.source
+                            && !self.config.pan_coordinator
+                            && self.assoc_state.is_associated() =>
+                    {
+                        // Poll for pending frames if we're not always listening
+                        if !self.config.rx_on_when_idle
+                            && indirect::is_pending(
+                                &b.pending_address,
+                                self.short_addr,
+                                self.address,
+                            )
+                        {
+                            let _ = self.request_data(now, parent);
+                        }
+
+                        // Update guaranteed time slot allocation
+                        let slot = self
+                            .short_addr
+                            .and_then(|s| gts::gts_slot(&b.guaranteed_time_slot_info, s));
+                        match (slot, self.gts_state) {
+                            (Some(s), state) if state != GtsState::Granted(s) => {
+                                info!(
+                                    "Granted GTS slots {} to {}",
+                                    s.start,
+                                    s.start + s.length - 1
+                                );
+                                self.gts_state = GtsState::Granted(s);
+                            }
+                            (None, GtsState::Granted(_)) => {
+                                warn!("GTS allocation removed by coordinator");
+                                self.gts_state = GtsState::None;
+                            }
+                            _ => (),
+                        }
+                    }
+                    _ => (),
+                }
+
+                // TODO: apply beacon info to config?
+                // How to do this in a transient way? maybe hold separately and merge?
+            }
+            FrameContent::Command(c) => {
+                match c {
+                    Command::AssociationRequest(req) => {
+                        debug!(
+                            "Association request from: {:?} (cap: {:?}",
+                            p.header.source, req
+                        );
+
+                        // TODO: check whether to allow association?
+
+                        // Devices already associated are re-issued their existing
+                        // address, as where the response was lost or the device restarted
+                        let rejoin = self
+                            .neighbours
+                            .lookup(&p.header.source)
+                            .map(|n| n.relation == Relation::Child)
+                            .unwrap_or(false);
+                        if rejoin {
+                            info!("Duplicate association request from {:?}", p.header.source);
+                            self.stats.duplicate_joins =
+                                self.stats.duplicate_joins.saturating_add(1);
+                        }
+
+                        // Allocate a short address, re-issuing the existing address
+                        // where the device has previously associated
+                        // TODO: for global uniqueness non-PAN coordinators should
+                        // go back to the PAN coordinator for assignment
+                        let allocated = match p.header.source {
+                            Address::Extended(_, ext) => Some(
+                                self.assoc_table
+                                    .allocate(ext, req.idle_receive)
+                                    .map(|a| (ext, a)),
+                            ),
+                            _ => None,
+                        };
+
+                        let (assoc_addr, assoc_status) = match allocated {
+                            Some(Some((extended, a))) => {
+                                debug!("Assigned short address {:?} to {:?}", a, p.header.source);
+
+                                // Record the direct route, superseding any via a router
+                                let route = Route {
+                                    short: a,
+                                    extended,
+                                    via: None,
+                                    updated: now,
+                                };
+                                if self.routes.update(route).is_err() {
+                                    warn!("Route table full, no route for {:?}", p.header.source);
+                                }
+                                (a, AssociationStatus::Successful)
+                            }
+                            Some(None) => {
+                                warn!("Association table full, denying {:?}", p.header.source);
+                                (
+                                    ShortAddress::broadcast(),
+                                    AssociationStatus::NetworkAtCapacity,
+                                )
+                            }
+                            None => {
+                                warn!(
+                                    "Association request without extended address, denying {:?}",
+                                    p.header.source
+                                );
+                                (ShortAddress::broadcast(), AssociationStatus::AccessDenied)
+                            }
+                        };
+
+                        // Build response
+                        let assoc_cmd = Command::AssociationResponse(assoc_addr, assoc_status);
+                        let assoc_resp =
+                            Packet::command(p.header.source, self.addr(), self.seq(), assoc_cmd);
+
+                        // Devices re-issue requests where no response is received,
+                        // being re-issued the allocated address
+                        if let Err(e) = self.enqueue_tx(assoc_resp) {
+                            error!("Error adding associate response to tx buffer");
+                            return Err(e);
+                        }
+                    }
+                    Command::AssociationResponse(assoc_addr, assoc_state) => {
+                        // Only handle expected associations
+                        match self.assoc_state {
+                            AssocState::Unassociated | AssocState::Associated(_) => return Ok(()),
+                            AssocState::Pending(addr, _) if addr != p.header.source => {
+                                warn!(
+                                    "Associate response from unexpected peer {:?}",
+                                    p.header.source
+                                );
+                                return Ok(());
+                            }
+                            _ => (),
+                        }
+
+                        if assoc_state == AssociationStatus::Successful {
+                            let pan_id = p.header.source.pan_id().unwrap();
+                            info!("Associated with PAN: {}!", pan_id.0);
+
+                            // Apply short address if assigned
+                            // (0xfffe indicates association without a short address)
+                            if assoc_addr != ShortAddress(0xfffe)
+                                && assoc_addr != ShortAddress::broadcast()
+                            {
+                                debug!("Using assigned short address {:?}", assoc_addr);
+                                self.short_addr = Some(assoc_addr);
+                            }
+
+                            // TODO: extract pan ID to support compression?
+                            self.assoc_state = AssocState::Associated(pan_id);
+                        } else {
+                            warn!("Association failed with status: {:?}", assoc_state);
+
+                            // TODO: add back-off or reset sync on failure?
+                            self.assoc_state = AssocState::Unassociated;
+                        }
+                    }
+                    Command::GuaranteedTimeSlotRequest(c) => {
+                        // GTS descriptors identify devices by short address
+                        let short = match self.device_short(&p.header.source) {
+                            Some(s) => s,
+                            None => {
+                                warn!("GTS request from unknown device {:?}", p.header.source);
+                                return Ok(());
+                            }
+                        };
+
+                        // Only transmit (device to coordinator) slots are served in the CFP
+                        if c.allocation && c.receive_only {
+                            warn!("Receive GTS unsupported, ignoring request from {:?}", short);
+                        } else if c.allocation {
+                            let (first, last) = self.config.cfp_slots();
+                            match self.gts_table.allocate(short, c.count, first, last, now) {
+                                Some(s) => {
+                                    info!(
+                                        "Allocated GTS slots {} to {} for {:?}",
+                                        s.start,
+                                        s.start + s.length - 1,
+                                        short
+                                    );
+                                }
+                                None => {
+                                    warn!(
+                                        "Unable to allocate {} GTS slots for {:?}",
+                                        c.count, short
+                                    );
+                                }
+                            }
+                        } else if let Some(_s) = self.gts_table.remove(&short) {
+                            info!("Deallocated GTS slots for {:?}", short);
+                        }
+                    }
+                    Command::OrphanNotification => {
+                        // Realign orphaned devices that are associated with us
+                        let assoc = match p.header.source {
+                            Address::Extended(_, ext) => self.assoc_table.lookup(&ext).cloned(),
+                            _ => None,
+                        };
+                        let assoc = match assoc {
+                            Some(a) => a,
+                            None => {
+                                debug!("Ignoring orphan notification from {:?}", p.header.source);
+                                return Ok(());
+                            }
+                        };
+
+                        debug!("Realigning orphaned device {:?}", p.header.source);
+
+                        // Radio channels are not 802.15.4 channel numbers so the channel
+                        // is not advertised, devices remain on the current channel
+                        let realign = Command::CoordinatorRealignment(CoordinatorRealignmentData {
+                            pan_id: self.config.pan_id,
+                            coordinator_address: self.short_addr.unwrap_or(ShortAddress(0xfffe)),
+                            channel: 0,
+                            device_address: assoc.short,
+                            channel_page: None,
+                        });
+                        let resp =
+                            Packet::command(p.header.source, self.addr(), self.seq(), realign);
+
+                        if let Err(e) = self.enqueue_tx(resp) {
+                            error!("Error adding coordinator realignment to tx buffer");
+                            return Err(e);
+                        }
+                    }
+                    Command::CoordinatorRealignment(r) => {
+                        // Handle realignment while attempting to re-sync, or where
+                        // the sync parent has changed PAN ID
+                        let expiry = match self.sync_state {
+                            SyncState::Lost(_, expiry) => expiry,
+                            SyncState::Synced(parent)
+                                if parent == p.header.source && !self.config.pan_coordinator =>
+                            {
+                                self.realign(parent, &r);
+                                return Ok(());
+                            }
+                            _ => return Ok(()),
+                        };
+
+                        info!(
+                            "Coordinator realignment from {:?} (PAN: {})",
+                            p.header.source, r.pan_id.0
+                        );
+
+                        self.config.pan_id = r.pan_id;
+                        if let Some(n) = self.network.as_mut() {
+                            n.pan_id = r.pan_id;
+                        }
+                        self.assoc_state = AssocState::Associated(r.pan_id);
+                        if r.device_address != ShortAddress(0xfffe)
+                            && r.device_address != ShortAddress::broadcast()
+                        {
+                            self.short_addr = Some(r.device_address);
+                        }
+
+                        self.stats.realignments = self.stats.realignments.saturating_add(1);
+
+                        // Resume with the realigned coordinator on the next beacon,
+                        // without further orphan notifications
+                        self.sync_state = SyncState::Lost(p.header.source, expiry);
+                        self.rejoin_at = expiry;
+                    }
+                    Command::BeaconRequest => {
+                        // Coordinators of non-beacon PANs respond to beacon requests,
+                        // otherwise beacons are periodic
+                        if self.config.pan_coordinator
+                            && self.config.mac_beacon_order == BeaconOrder::OnDemand
+                            && !self.base.is_busy()
+                        {
+                            debug!("Beacon request from {:?}", p.header.source);
+
+                            let asn = self.config.calculate_asn(now, self.sync_offset);
+                            let packet = self.beacon(asn);
+
+                            let mut buff = [0u8; TX_BUFF_LEN];
+                            if let Some(n) = self.encode_tx(&packet, &mut buff) {
+                                self.base.transmit(now, &buff[..n])?;
+                                self.stats.tx_beacons = self.stats.tx_beacons.saturating_add(1);
+                            }
+                        }
+                    }
+                    Command::DisassociationNotification(_reason) => {
+                        self.handle_disassociation(p.header.source);
+                    }
+                    Command::DataRequest => {
+                        let source = p.header.source;
+                        let assoc = self.assoc_table.lookup_address(&source).cloned();
+
+                        // Release the oldest frame pending for the requesting device
+                        let pending = self
+                            .indirect
+                            .take(|a| *a == source || assoc.map(|x| x.matches(a)).unwrap_or(false));
+
+                        match pending {
+                            Some((expires, state, packet)) => {
+                                debug!(
+                                    "Data request from {:?}, releasing packet {}",
+                                    source, packet.header.seq
+                                );
+
+                                if let Err((state, packet)) = self.tx_buff.enqueue((state, packet))
+                                {
+                                    // Hold for the next request if we can't send this now
+                                    error!("Error adding pending packet to tx buffer");
+                                    let _ = self.indirect.push(expires, state, packet);
+                                }
+                            }
+                            None => {
+                                debug!("Data request from {:?}, no pending packets", source);
+                            }
+                        }
+                    }
+                    _ => {
+                        info!("RX unhandled command: {:?}", c);
+                    }
+                }
+            }
+            FrameContent::Acknowledgement => {
+                match (self.ack_wait, self.tx_buff.peek()) {
+                    (AckWait::Pending { .. }, Some((_s, t))) if p.is_ack_for(t) => {
+                        debug!("ACK rx for packet: {}!", p.header.seq);
+                        self.stats.rx_acks = self.stats.rx_acks.saturating_add(1);
+
+                        // Data requests without pending frames are complete on ACK
+                        let request =
+                            matches!(t.content, FrameContent::Command(Command::DataRequest));
+                        if request && !p.header.frame_pending {
+                            debug!("No frames pending from {:?}", p.header.source);
+                            self.data_request = None;
+                        }
+
+                        // Cancel ACK wait, remove from TX buffer and signal success
+                        self.ack_wait = AckWait::None;
+                        self.burst_hold = now + self.config.ack_delay;
+                        if let Some((s, _p)) = self.tx_buff.dequeue() {
+                            self.tx_done(s.handle, TxStatus::Acked);
+                        }
+                    }
+                    (AckWait::Pending { seq, .. }, _) => {
+                        warn!("ACK sequence mismatch (expected {})", seq);
+                    }
+                    (AckWait::None, _) => {
+                        warn!("ACK with no pending operation");
+                    }
+                }
+            }
+            FrameContent::Data => {
+                debug!(
+                    "Received {} bytes of data from {:?}",
+                    p.payload().len(),
+                    p.header.source
+                );
+
+                // Pending data received from our parent
+                match (self.data_request, self.sync_state) {
+                    (Some(_), SyncState::Synced(parent)) if parent == p.header.source => {
+                        self.data_request = None;
+                    }
+                    _ => (),
+                }
+
+                self.rx_enqueue(rx.rssi, p);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Enqueue a received data frame for collection via [`MacIf::receive`]
+    fn rx_enqueue(&mut self, rssi: i16, p: Packet) {
+        let i = RxInfo {
+            source: p.header.source,
+            destination: p.header.destination,
+            rssi,
+        };
+
+        if let Err(_e) = self.rx_buff.push((i, p)) {
+            error!("Error adding packet to RX queue");
+            self.stats.rx_queue_full = self.stats.rx_queue_full.saturating_add(1);
+        }
+    }
+}
+
+fn calculate_offset(now: i64, expected: i64, frame: i64) -> i64 {
+    // Compute the difference between our expectation and the actual rx time
+    let mut delta = (now - expected) % frame;
+
+    // Renormalize correction in case of missed slots
+    if delta > frame / 2 {
+        delta -= frame;
+    } else if delta < -frame / 2 {
+        delta += frame;
+    }
+
+    return delta;
+}
+
+/// Check whether a beacon fits within the maximum frame length
+fn beacon_fits(p: &Packet) -> bool {
+    let mut buff = [0u8; 256];
+    match p.encode(&mut buff, WriteFooter::No) {
+        Ok(n) => n + packet::FCS_LEN <= packet::MAX_FRAME_LEN,
+        Err(_) => false,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use ieee802154::mac::beacon::{GuaranteedTimeSlotInformation, PendingAddress, SuperframeOrder};
+    use ieee802154::mac::*;
+    use radio::{mock::*, BasicInfo};
+
+    use super::*;
+    use crate::timer::mock::MockTimer;
+
+    #[test]
+    fn beacon_tx() {
+        let _ =
+            simplelog::SimpleLogger::init(log::LevelFilter::Trace, simplelog::Config::default());
+
+        let mut radio = MockRadio::new(&[]);
+        let mut timer = MockTimer::new();
+        let mut seq = 0;
+
+        let mac_addr = ExtendedAddress(0xabcd);
+        let mac_cfg = Config {
+            pan_coordinator: true,
+            ..Default::default()
+        };
+
+        // Initialise MAC
+        radio.expect(&[Transaction::start_receive(None)]);
+        let mut mac = Mac::new(
+            mac_addr.clone(),
+            mac_cfg.clone(),
+            radio.clone(),
+            timer.clone(),
+        )
+        .unwrap();
+
+        // Chilling in rx mode
+        radio.expect(&[Transaction::check_receive(true, Ok(false))]);
+        mac.tick().unwrap();
+
+        for n in 0..2 {
+            // Advance in time
+            for i in 0..mac_cfg.superframe_duration() / 100 - 1 {
+                timer.set_ms(n * mac_cfg.superframe_duration() + i * 100);
+
+                radio.expect(&[Transaction::check_receive(true, Ok(false))]);
+
+                mac.tick().unwrap();
+            }
+
+            // Beacon at beacon interval
+            timer.set_ms((n + 1) * mac_cfg.superframe_duration());
+
+            let beacon_info = Beacon {
+                superframe_spec: mac_cfg.superframe_spec(),
+                // TODO: replace placeholders with actual configuration
+                guaranteed_time_slot_info: GuaranteedTimeSlotInformation::new(),
+                pending_address: PendingAddress::new(),
+            };
+            let beacon = Packet::beacon(
+                Address::Extended(mac_cfg.pan_id, mac_addr),
+                seq,
+                beacon_info,
+            );
+            seq += 1;
+
+            // Start beacon TX
+            radio.expect(&[
+                Transaction::check_receive(true, Ok(false)),
+                Transaction::start_transmit(beacon.into(), None),
+            ]);
+            mac.tick().unwrap();
+
+            // Complete beacon TX
+            radio.expect(&[
+                Transaction::check_transmit(Ok(true)),
+                Transaction::start_receive(None),
+            ]);
+            mac.tick().unwrap();
+        }
+
+        let stats = mac.stats();
+        assert_eq!(stats.tx_beacons, 2);
+        assert_eq!(stats.radio.tx_frames, 2);
+    }
+
+    #[test]
+    fn timing() {
+        let mut radio = MockRadio::new(&[]);
+        let mut timer = MockTimer::new();
+
+        // 100 ms slots with a CFP from slot 6, and 1 s active in each 2 s superframe
+        let mac_cfg = Config {
+            final_cap_slot: 5,
+            mac_beacon_order: BeaconOrder::BeaconOrder(1),
+            mac_superframe_order: SuperframeOrder::SuperframeOrder(0),
+            ..Default::default()
+        };
+
+        radio.expect(&[Transaction::start_receive(None)]);
+        let mut mac = Mac::new(
+            ExtendedAddress(0xabcd),
+            mac_cfg,
+            radio.clone(),
+            timer.clone(),
+        )
+        .unwrap();
+        mac.sync_offset = 150;
+        mac.next_beacon = 1850;
+
+        let timing = |asn, rsn, sfn, next_beacon_ms, period| MacTiming {
+            asn,
+            rsn,
+            sfn,
+            next_beacon_ms,
+            period,
+        };
+
+        // 150 ms into the first superframe, CAP slot 1
+        assert_eq!(mac.timing(0), timing(1, 1, 0, Some(1850), SlotPeriod::Cap));
+        // 650 ms, CFP slot 6
+        assert_eq!(
+            mac.timing(500),
+            timing(6, 6, 0, Some(1350), SlotPeriod::Gts)
+        );
+        // 1150 ms, following the active period
+        assert_eq!(
+            mac.timing(1000),
+            timing(11, 1, 0, Some(850), SlotPeriod::Inactive)
+        );
+        // 2050 ms, slot 0 of the second superframe
+        assert_eq!(mac.timing(1900), timing(20, 0, 1, Some(0), SlotPeriod::Cap));
+
+        // Ticks are required at the next beacon where otherwise idle
+        timer.set_ms(1000);
+        assert_eq!(mac.next_wake(), 1850);
+
+        // Deadlines past are due immediately
+        timer.set_ms(2000);
+        assert_eq!(mac.next_wake(), 2000);
+
+        radio.done();
+    }
+
+    #[test]
+    fn beacon_payload_too_large() {
+        let mut radio = MockRadio::new(&[]);
+        let timer = MockTimer::new();
+
+        let mac_cfg = Config {
+            pan_coordinator: true,
+            ..Default::default()
+        };
+
+        radio.expect(&[Transaction::start_receive(None)]);
+        let mut mac = Mac::new(ExtendedAddress(0xabcd), mac_cfg, radio.clone(), timer).unwrap();
+
+        let payload = [0xaa; MAX_BEACON_PAYLOAD_LEN];
+        assert_eq!(mac.set_beacon_payload(&payload[..16]), Ok(()));
+
+        // Oversized payloads are rejected, retaining the existing payload
+        assert_eq!(
+            mac.set_beacon_payload(&[0xbb; MAX_BEACON_PAYLOAD_LEN + 1]),
+            Err(CoreError::BufferError(BufferError::TooSmall))
+        );
+        assert_eq!(mac.beacon_payload(), &payload[..16]);
+
+        // Maximum length payloads fit alongside the beacon fields
+        assert_eq!(mac.set_beacon_payload(&payload), Ok(()));
+        assert!(beacon_fits(&mac.beacon(0)));
+
+        radio.done();
+    }
+
+    #[test]
+    fn beacon_rx_sync() {
+        let _ =
+            simplelog::SimpleLogger::init(log::LevelFilter::Debug, simplelog::Config::default());
+
+        let mut radio = MockRadio::new(&[]);
+        let mut timer = MockTimer::new();
+        let seq = 0;
+
+        let mac_addr = ExtendedAddress(0xabcd);
+        let mac_cfg = Config {
+            pan_coordinator: false,
+            ..Default::default()
+        };
+        let coord_addr = Address::Extended(mac_cfg.pan_id, ExtendedAddress(0x1122));
+
+        // Initialise MAC
+        radio.expect(&[Transaction::start_receive(None)]);
+        let mut mac = Mac::new(
+            mac_addr.clone(),
+            mac_cfg.clone(),
+            radio.clone(),
+            timer.clone(),
+        )
+        .unwrap();
+
+        // Chilling in rx mode
+        radio.expect(&[Transaction::check_receive(true, Ok(false))]);
+        mac.tick().unwrap();
+
+        timer.set_ms(100);
+
+        debug!("RX beacon");
+
+        // Receive beacon
+        let beacon_info = Beacon {
+            superframe_spec: mac_cfg.superframe_spec(),
+            // TODO: replace placeholders with actual configuration
+            guaranteed_time_slot_info: GuaranteedTimeSlotInformation::new(),
+            pending_address: PendingAddress::new(),
+        };
+        let beacon = Packet::beacon(coord_addr, seq, beacon_info);
+
+        radio.expect(&[
+            Transaction::check_receive(true, Ok(true)),
+            Transaction::get_received(Ok((beacon.into(), BasicInfo::default()))),
+            Transaction::start_receive(None),
+        ]);
+        mac.tick().unwrap();
+
+        // Check sync offset / next beacon time applied
+        assert_eq!(mac.sync_state, SyncState::Synced(coord_addr));
+        assert_eq!(
+            mac.next_beacon,
+            mac_cfg.superframe_duration() as u64 + timer.ticks_ms()
+        );
+        assert_eq!(mac.stats.rx_beacons, 1);
+    }
+
+    #[test]
+    fn beacon_rx_payload_elements() {
+        let _ =
+            simplelog::SimpleLogger::init(log::LevelFilter::Debug, simplelog::Config::default());
+
+        let mut radio = MockRadio::new(&[]);
+        let mut timer = MockTimer::new();
+
+        // Without a hopping sequence configured
+        let mac_cfg = Config::default();
+        let coord_addr = Address::Extended(mac_cfg.pan_id, ExtendedAddress(0x1122));
+
+        radio.expect(&[Transaction::start_receive(None)]);
+        let mut mac = Mac::new(
+            ExtendedAddress(0xabcd),
+            mac_cfg.clone(),
+            radio.clone(),
+            timer.clone(),
+        )
+        .unwrap();
+
+        // Receive a beacon advertising a hopping sequence with an application payload
+        timer.set_ms(100);
+        let beacon_info = Beacon {
+            superframe_spec: mac_cfg.superframe_spec(),
+            guaranteed_time_slot_info: GuaranteedTimeSlotInformation::new(),
+            pending_address: PendingAddress::new(),
+        };
+        let mut beacon = Packet::beacon(coord_addr, 0, beacon_info);
+
+        let app = [0x11, 0x22, 0x33];
+        let content = BeaconContent {
+            hopping: Some(HoppingInfo { id: 1, asn: 12 }),
+            app: &app,
+        };
+        let mut payload = [0u8; BeaconContent::MAX_LEN];
+        let n = content.encode(&mut payload);
+        beacon.set_payload(&payload[..n]).unwrap();
+
+        radio.expect(&[
+            Transaction::check_receive(true, Ok(true)),
+            Transaction::get_received(Ok((beacon.into(), BasicInfo::default()))),
+            Transaction::start_receive(None),
+        ]);
+        mac.tick().unwrap();
+
+        // Hopping information is separated from the application payload,
+        // with the unsupported sequence not followed
+        assert_eq!(mac.sync_state, SyncState::Synced(coord_addr));
+        assert_eq!(mac.hopping, None);
+        assert_eq!(mac.last_beacon(&coord_addr), Some((100, &app[..])));
+
+        radio.done();
+    }
+
+    #[test]
+    fn beacon_rx_next() {
+        let _ =
+            simplelog::SimpleLogger::init(log::LevelFilter::Debug, simplelog::Config::default());
+
+        let mut radio = MockRadio::new(&[]);
+        let mut timer = MockTimer::new();
+        let mut seq = 0;
+
+        let mac_addr = ExtendedAddress(0xabcd);
+        let mac_cfg = Config {
+            pan_coordinator: false,
+            ..Default::default()
+        };
+        let coord_addr = Address::Extended(mac_cfg.pan_id, ExtendedAddress(0x1122));
+
+        // Initialise MAC
+        radio.expect(&[Transaction::start_receive(None)]);
+        let mut mac = Mac::new(
+            mac_addr.clone(),
+            mac_cfg.clone(),
+            radio.clone(),
+            timer.clone(),
+        )
+        .unwrap();
+
+        // Chilling in rx mode, force to sleep so we see wake -> RX transition
+        radio.expect(&[
+            Transaction::check_receive(true, Ok(false)),
+            Transaction::set_state(MockState::Sleep, None),
+        ]);
+        mac.tick().unwrap();
+        mac.base.sleep(timer.ticks_ms()).unwrap();
+
+        // Set sync'd state so we're expecting a beacon
+        mac.sync_state = SyncState::Synced(coord_addr.clone());
+        mac.next_beacon = mac_cfg.superframe_duration() as u64;
+
+        // Arm RX for next expected beacon
+        timer.set_ms(mac.next_beacon as u32 + 3);
+        radio.expect(&[Transaction::start_receive(None)]);
+        mac.tick().unwrap();
+
+        debug!("RX beacon");
+
+        // Receive beacon
+        let beacon_info = Beacon {
+            superframe_spec: mac_cfg.superframe_spec(),
+            // TODO: replace placeholders with actual configuration
+            guaranteed_time_slot_info: GuaranteedTimeSlotInformation::new(),
+            pending_address: PendingAddress::new(),
+        };
+        let beacon = Packet::beacon(coord_addr, seq, beacon_info);
+
+        radio.expect(&[
+            Transaction::check_receive(true, Ok(true)),
+            Transaction::get_received(Ok((beacon.into(), BasicInfo::default()))),
+            Transaction::start_receive(None),
+        ]);
+        mac.tick().unwrap();
+
+        // Check sync offset / next beacon time applied
+        assert_eq!(mac.sync_state, SyncState::Synced(coord_addr));
+        assert_eq!(
+            mac.next_beacon,
+            mac_cfg.superframe_duration() as u64 + timer.ticks_ms()
+        );
+    }
+
+    #[test]
+    fn beacon_rx_adopt_config() {
+        let _ =
+            simplelog::SimpleLogger::init(log::LevelFilter::Debug, simplelog::Config::default());
+
+        let mut radio = MockRadio::new(&[]);
+        let mut timer = MockTimer::new();
+
+        let mac_addr = ExtendedAddress(0xabcd);
+        let mac_cfg = Config::default();
+
+        // Coordinator runs a longer beacon interval than the device default
+        let coord_cfg = Config::<u16> {
+            pan_coordinator: true,
+            pan_id: PanId(0x0200),
+            mac_beacon_order: BeaconOrder::BeaconOrder(3),
+            ..Default::default()
+        };
+        let coord_addr = Address::Extended(coord_cfg.pan_id, ExtendedAddress(0x1122));
+        let superframe = coord_cfg.superframe_duration() as u64;
+        assert_ne!(superframe, mac_cfg.superframe_duration() as u64);
+
+        let mut superframe_spec = coord_cfg.superframe_spec();
+        superframe_spec.association_permit = false;
+        let beacon_info = Beacon {
+            superframe_spec,
+            guaranteed_time_slot_info: GuaranteedTimeSlotInformation::new(),
+            pending_address: PendingAddress::new(),
+        };
+
+        // Initialise MAC
+        radio.expect(&[Transaction::start_receive(None)]);
+        let mut mac = Mac::new(
+            mac_addr.clone(),
+            mac_cfg.clone(),
+            radio.clone(),
+            timer.clone(),
+        )
+        .unwrap();
+
+        // Sync on the first beacon
+        timer.set_ms(100);
+        let beacon = Packet::beacon(coord_addr, 0, beacon_info.clone());
+        radio.expect(&[
+            Transaction::check_receive(true, Ok(true)),
+            Transaction::get_received(Ok((beacon.into(), BasicInfo::default()))),
+            Transaction::start_receive(None),
+        ]);
+        mac.tick().unwrap();
+
+        // Beacon configuration is adopted
+        assert_eq!(mac.sync_state, SyncState::Synced(coord_addr));
+        assert_eq!(mac.next_beacon, 100 + superframe);
+        assert_eq!(
+            mac.network(),
+            Some(&NetworkConfig {
+                pan_id: PanId(0x0200),
+                beacon_order: BeaconOrder::BeaconOrder(3),
+                superframe_order: SuperframeOrder::SuperframeOrder(0),
+                association_permit: false,
+            })
+        );
+        assert_eq!(mac.addr(), Address::Extended(PanId(0x0200), mac_addr));
+
+        // No beacon is missed where expected by the default configuration,
+        // and association is not attempted
+        timer.set_ms(mac_cfg.superframe_duration() + 900);
+        radio.expect(&[Transaction::check_receive(true, Ok(false))]);
+        mac.tick().unwrap();
+
+        assert_eq!(mac.beacon_miss_count, 0);
+        assert_eq!(mac.assoc_state, AssocState::Unassociated);
+        assert!(mac.tx_buff.is_empty());
+
+        // The next beacon continues to be tracked
+        let now = 100 + superframe + 5;
+        timer.set_ms(now as u32);
+        let beacon = Packet::beacon(coord_addr, 1, beacon_info);
+        radio.expect(&[
+            Transaction::check_receive(true, Ok(true)),
+            Transaction::get_received(Ok((beacon.into(), BasicInfo::default()))),
+            Transaction::start_receive(None),
+        ]);
+        mac.tick().unwrap();
+
+        assert_eq!(mac.sync_state, SyncState::Synced(coord_addr));
+        assert_eq!(mac.beacon_miss_count, 0);
+        assert_eq!(
+            mac.next_beacon,
+            (now as i64 + mac.beacon_interval() + mac.sync_correction) as u64
+        );
+
+        radio.done();
+    }
+
+    #[test]
+    fn beacon_drift_compensation() {
+        let _ =
+            simplelog::SimpleLogger::init(log::LevelFilter::Debug, simplelog::Config::default());
+
+        let mut radio = MockRadio::new(&[]);
+        let mut timer = MockTimer::new();
+
+        let mac_addr = ExtendedAddress(0xabcd);
+        let mac_cfg = Config::default();
+
+        // Coordinator with a long beacon interval
+        let coord_cfg = Config::<u16> {
+            pan_coordinator: true,
+            mac_beacon_order: BeaconOrder::BeaconOrder(6),
+            ..Default::default()
+        };
+        let coord_addr = Address::Extended(coord_cfg.pan_id, ExtendedAddress(0x1122));
+        let superframe = coord_cfg.superframe_duration() as f64;
+
+        let mut superframe_spec = coord_cfg.superframe_spec();
+        superframe_spec.association_permit = false;
+        let beacon_info = Beacon {
+            superframe_spec,
+            guaranteed_time_slot_info: GuaranteedTimeSlotInformation::new(),
+            pending_address: PendingAddress::new(),
+        };
+
+        // Initialise MAC
+        radio.expect(&[Transaction::start_receive(None)]);
+        let mut mac = Mac::new(
+            mac_addr.clone(),
+            mac_cfg.clone(),
+            radio.clone(),
+            timer.clone(),
+        )
+        .unwrap();
+
+        // Beacons arrive with the local clock running 100 ppm slow
+        let arrival = |k: u32| 100 + (k as f64 * superframe * (1.0 - 100e-6)).round() as u64;
+
+        for k in 0..20 {
+            let now = arrival(k);
+            timer.set_ms(now as u32);
+
+            // Once the drift is estimated beacons arrive when expected
+            if k >= 2 {
+                let error = now as i64 - mac.next_beacon as i64;
+                assert!(error.abs() <= 2, "beacon {} error {} ms", k, error);
+            }
+
+            let beacon = Packet::beacon(coord_addr, k as u8, beacon_info.clone());
+            radio.expect(&[
+                Transaction::check_receive(true, Ok(true)),
+                Transaction::get_received(Ok((beacon.into(), BasicInfo::default()))),
+                Transaction::start_receive(None),
+            ]);
+            mac.tick().unwrap();
+
+            assert_eq!(mac.sync_state, SyncState::Synced(coord_addr));
+            assert_eq!(mac.beacon_miss_count, 0);
+        }
+
+        // Drift is estimated and applied to the sync offset between beacons
+        let ppm = mac.drift_ppm().unwrap();
+        assert!((-105..=-95).contains(&ppm), "drift {} ppm", ppm);
+        assert_eq!(mac.sync_correction, 0);
+
+        let offset = mac.sync_offset - arrival(0);
+        assert!((105..=125).contains(&offset), "offset {} ms", offset);
+
+        radio.done();
+    }
+
+    #[test]
+    fn reconfigure() {
+        let _ =
+            simplelog::SimpleLogger::init(log::LevelFilter::Debug, simplelog::Config::default());
+
+        let mut radio = MockRadio::new(&[]);
+        let mut timer = MockTimer::new();
+
+        let mac_addr = ExtendedAddress(0xabcd);
+        let mac_cfg = Config::default();
+
+        // Invalid configurations are rejected on setup
+        let invalid = Config {
+            mac_superframe_order: SuperframeOrder::SuperframeOrder(2),
+            ..mac_cfg.clone()
+        };
+        let res = Mac::new(mac_addr.clone(), invalid, radio.clone(), timer.clone());
+        assert_eq!(res.err(), Some(CoreError::InvalidConfig));
+
+        // Initialise MAC
+        radio.expect(&[Transaction::start_receive(None)]);
+        let mut mac = Mac::new(
+            mac_addr.clone(),
+            mac_cfg.clone(),
+            radio.clone(),
+            timer.clone(),
+        )
+        .unwrap();
+
+        // Parameter changes apply without restarting
+        let cfg = Config {
+            min_be: 3,
+            max_retries: 2,
+            cca_mode: CcaMode::EnergyAboveThreshold {
+                threshold: -60,
+                samples: 2,
+                sample_interval_ms: 10,
+            },
+            ..mac_cfg.clone()
+        };
+        mac.reconfigure(cfg.clone()).unwrap();
+        assert_eq!(mac.config(), &cfg);
+        assert_eq!(mac.event(), Ok(None));
+
+        // Invalid configurations are rejected
+        let invalid = Config {
+            min_be: 6,
+            ..mac_cfg.clone()
+        };
+        assert_eq!(mac.reconfigure(invalid), Err(CoreError::InvalidConfig));
+        assert_eq!(mac.config(), &cfg);
+
+        // Switch to coordinator with a longer beacon interval
+        let coord_cfg = Config {
+            pan_coordinator: true,
+            mac_beacon_order: BeaconOrder::BeaconOrder(2),
+            ..cfg.clone()
+        };
+        timer.set_ms(1000);
+        radio.expect(&[Transaction::start_receive(None)]);
+        mac.reconfigure(coord_cfg.clone()).unwrap();
+
+        let coord_addr = Address::Extended(coord_cfg.pan_id, mac_addr);
+        assert_eq!(mac.event(), Ok(Some(MacEvent::Restarted(coord_addr))));
+        assert_eq!(mac.assoc_state, AssocState::Associated(coord_cfg.pan_id));
+        assert_eq!(
+            mac.next_beacon,
+            1000 + coord_cfg.superframe_duration() as u64
+        );
+
+        // Beacons are sent at the new interval
+        timer.set_ms(mac.next_beacon as u32);
+        let beacon = Packet::beacon(
+            coord_addr,
+            0,
+            Beacon {
+                superframe_spec: coord_cfg.superframe_spec(),
+                guaranteed_time_slot_info: GuaranteedTimeSlotInformation::new(),
+                pending_address: PendingAddress::new(),
+            },
+        );
+        radio.expect(&[
+            Transaction::check_receive(true, Ok(false)),
+            Transaction::start_transmit(beacon.into(), None),
+        ]);
+        mac.tick().unwrap();
+
+        timer.set_ms(timer.ticks_ms() as u32 + 10);
+        radio.expect(&[
+            Transaction::check_transmit(Ok(true)),
+            Transaction::start_receive(None),
+        ]);
+        mac.tick().unwrap();
+
+        // And back to a device
+        radio.expect(&[Transaction::start_receive(None)]);
+        mac.reconfigure(cfg.clone()).unwrap();
+
+        assert_eq!(mac.event(), Ok(Some(MacEvent::Restarted(coord_addr))));
+        assert_eq!(mac.sync_state, SyncState::Unsynced);
+        assert_eq!(mac.assoc_state, AssocState::Unassociated);
+        assert_eq!(mac.next_beacon, 0);
+
+        // Which then syncs to a coordinator
+        let parent = Address::Extended(cfg.pan_id, ExtendedAddress(0x1122));
+        let beacon = Packet::beacon(
+            parent,
+            0,
+            Beacon {
+                superframe_spec: cfg.superframe_spec(),
+                guaranteed_time_slot_info: GuaranteedTimeSlotInformation::new(),
+                pending_address: PendingAddress::new(),
+            },
+        );
+
+        timer.set_ms(timer.ticks_ms() as u32 + 100);
+        radio.expect(&[
+            Transaction::check_receive(true, Ok(true)),
+            Transaction::get_received(Ok((beacon.into(), BasicInfo::default()))),
+            Transaction::start_receive(None),
+        ]);
+        mac.tick().unwrap();
+
+        assert_eq!(mac.sync_state, SyncState::Synced(parent));
+        assert_eq!(
+            mac.next_beacon,
+            timer.ticks_ms() + cfg.superframe_duration() as u64
+        );
+
+        radio.done();
+    }
+
+    #[test]
+    fn sync_parent_selection() {
+        let _ =
+            simplelog::SimpleLogger::init(log::LevelFilter::Debug, simplelog::Config::default());
+
+        let mut radio = MockRadio::new(&[]);
+        let mut timer = MockTimer::new();
+
+        let mac_addr = ExtendedAddress(0xabcd);
+        let mac_cfg = Config {
+            sync_window: 500,
+            neighbour_timeout: 1000,
+            ..Default::default()
+        };
+        let weak = Address::Extended(mac_cfg.pan_id, ExtendedAddress(0x1122));
+        let strong = Address::Extended(mac_cfg.pan_id, ExtendedAddress(0x3344));
+
+        // Initialise MAC
+        radio.expect(&[Transaction::start_receive(None)]);
+        let mut mac = Mac::new(
+            mac_addr.clone(),
+            mac_cfg.clone(),
+            radio.clone(),
+            timer.clone(),
+        )
+        .unwrap();
+
+        // Hear beacons from both coordinators, the weaker first
+        for (t, source, rssi) in [(100, weak, -80), (150, strong, -50)].iter() {
+            let beacon = Packet::beacon(
+                *source,
+                0,
+                Beacon {
+                    superframe_spec: mac_cfg.superframe_spec(),
+                    guaranteed_time_slot_info: GuaranteedTimeSlotInformation::new(),
+                    pending_address: PendingAddress::new(),
+                },
+            );
+
+            timer.set_ms(*t);
+            radio.expect(&[
+                Transaction::check_receive(true, Ok(true)),
+                Transaction::get_received(Ok((beacon.into(), BasicInfo::new(*rssi, 0)))),
+                Transaction::start_receive(None),
+            ]);
+            mac.tick().unwrap();
+
+            // Neither is adopted until the sync window closes
+            assert_eq!(mac.sync_state, SyncState::Unsynced);
+        }
+        assert_eq!(mac.next_deadline(), Some(100 + mac_cfg.sync_window));
+
+        // The stronger coordinator is adopted at the end of the window
+        timer.set_ms(600);
+        radio.expect(&[Transaction::check_receive(true, Ok(false))]);
+        mac.tick().unwrap();
+
+        assert_eq!(mac.sync_state, SyncState::Synced(strong));
+        assert_eq!(mac.sync_offset, 150);
+        assert_eq!(mac.next_beacon, 150 + mac_cfg.superframe_duration() as u64);
+
+        // With both tracked as neighbours
+        assert_eq!(mac.neighbours().count(), 2);
+        let n = mac.neighbour(&weak).unwrap();
+        assert_eq!((n.rssi(), n.rx_count, n.last_heard), (-80, 1, 100));
+        let n = mac.neighbour(&strong).unwrap();
+        assert_eq!((n.rssi(), n.rx_count, n.last_heard), (-50, 1, 150));
+        assert!(mac.neighbours().all(|n| n.coordinator));
+
+        // Neighbours are dropped once silent for the timeout
+        timer.set_ms(1120);
+        radio.expect(&[Transaction::check_receive(true, Ok(false))]);
+        mac.tick().unwrap();
+
+        assert_eq!(mac.neighbours().count(), 1);
+        assert!(mac.neighbour(&weak).is_none());
+        assert_eq!(
+            mac.neighbour(&strong).map(|n| n.relation),
+            Some(Relation::Parent)
+        );
+
+        radio.done();
+    }
+
+    #[test]
+    fn channel_hopping() {
+        let _ =
+            simplelog::SimpleLogger::init(log::LevelFilter::Debug, simplelog::Config::default());
+
+        let mut radio = MockRadio::new(&[]);
+        let mut timer = MockTimer::new();
+
+        let channels = [11, 15, 20];
+        let mac_cfg = Config {
+            pan_coordinator: true,
+            hopping_sequence: Some(HoppingSequence {
+                id: 1,
+                channels: heapless::Vec::from_slice(&channels).unwrap(),
+            }),
+            ..Default::default()
+        };
+
+        // Initialise MAC
+        radio.expect(&[Transaction::start_receive(None)]);
+        let mut mac = Mac::new(
+            ExtendedAddress(0xabcd),
+            mac_cfg.clone(),
+            radio.clone(),
+            timer.clone(),
+        )
+        .unwrap();
+
+        // Channel follows the hopping sequence in each slot, restarting RX
+        for asn in 0..6 {
+            timer.set_ms(asn * 100 + 50);
+
+            radio.expect(&[
+                Transaction::check_receive(true, Ok(false)),
+                Transaction::set_channel(channels[asn as usize % channels.len()].clone(), None),
+                Transaction::start_receive(None),
+            ]);
+            mac.tick().unwrap();
+        }
+
+        // No change within a slot
+        timer.set_ms(580);
+        radio.expect(&[Transaction::check_receive(true, Ok(false))]);
+        mac.tick().unwrap();
+
+        radio.done();
+    }
+
+    #[test]
+    fn channel_hopping_follow() {
+        let _ =
+            simplelog::SimpleLogger::init(log::LevelFilter::Debug, simplelog::Config::default());
+
+        let mut radio = MockRadio::new(&[]);
+        let mut timer = MockTimer::new();
+
+        let channels = [11, 15, 20];
+        let mac_cfg = Config {
+            pan_coordinator: false,
+            hopping_sequence: Some(HoppingSequence {
+                id: 1,
+                channels: heapless::Vec::from_slice(&channels).unwrap(),
+            }),
+            ..Default::default()
+        };
+        let coord_addr = Address::Extended(mac_cfg.pan_id, ExtendedAddress(0x1122));
+
+        // Initialise MAC
+        radio.expect(&[Transaction::start_receive(None)]);
+        let mut mac = Mac::new(
+            ExtendedAddress(0xabcd),
+            mac_cfg.clone(),
+            radio.clone(),
+            timer.clone(),
+        )
+        .unwrap();
+
+        // No hopping prior to sync
+        radio.expect(&[Transaction::check_receive(true, Ok(false))]);
+        mac.tick().unwrap();
+
+        // Receive beacon advertising the hopping sequence in ASN 12
+        timer.set_ms(100);
+
+        let beacon_info = Beacon {
+            superframe_spec: mac_cfg.superframe_spec(),
+            guaranteed_time_slot_info: GuaranteedTimeSlotInformation::new(),
+            pending_address: PendingAddress::new(),
+        };
+        let mut beacon = Packet::beacon(coord_addr, 0, beacon_info);
+
+        let content = BeaconContent {
+            hopping: Some(HoppingInfo { id: 1, asn: 12 }),
+            app: &[],
+        };
+        let mut payload = [0u8; BeaconContent::MAX_LEN];
+        let n = content.encode(&mut payload);
+        beacon.set_payload(&payload[..n]).unwrap();
+
+        radio.expect(&[
+            Transaction::check_receive(true, Ok(true)),
+            Transaction::get_received(Ok((beacon.into(), BasicInfo::default()))),
+            Transaction::start_receive(None),
+            Transaction::set_channel(channels[12 % 3].clone(), None),
+            Transaction::start_receive(None),
+        ]);
+        mac.tick().unwrap();
+
+        assert_eq!(mac.sync_state, SyncState::Synced(coord_addr));
+
+        // Following slot uses the next channel in the sequence
+        timer.set_ms(200);
+
+        radio.expect(&[
+            Transaction::check_receive(true, Ok(false)),
+            Transaction::set_channel(channels[13 % 3].clone(), None),
+            Transaction::start_receive(None),
+        ]);
+        mac.tick().unwrap();
+
+        radio.done();
+    }
+
+    #[test]
+    fn auto_channel_scan() {
+        let _ =
+            simplelog::SimpleLogger::init(log::LevelFilter::Debug, simplelog::Config::default());
+
+        let mut radio = MockRadio::new(&[]);
+        let mut timer = MockTimer::new();
+
+        let channels = [11, 15, 20];
+        let mac_cfg = Config {
+            pan_coordinator: true,
+            auto_channel: true,
+            scan_channels: heapless::Vec::from_slice(&channels).unwrap(),
+            scan_duration: 100,
+            ..Default::default()
+        };
+
+        // Initialise MAC
+        radio.expect(&[Transaction::start_receive(None)]);
+        let mut mac = Mac::new(
+            ExtendedAddress(0xabcd),
+            mac_cfg.clone(),
+            radio.clone(),
+            timer.clone(),
+        )
+        .unwrap();
+
+        assert_eq!(mac.state(), Ok(MacState::Scanning));
+
+        // Sample two RSSI values per channel
+        let rssi = [[-60, -70], [-90, -80], [-50, -55]];
+        for (i, ch) in channels.iter().enumerate() {
+            timer.set_ms(i as u32 * 100);
+
+            // Completing the previous channel tunes to the next
+            radio.expect(&[
+                Transaction::check_receive(true, Ok(false)),
+                Transaction::set_channel(ch.clone(), None),
+                Transaction::start_receive(None),
+                Transaction::poll_rssi(Ok(rssi[i][0])),
+            ]);
+            mac.tick().unwrap();
+
+            timer.set_ms(i as u32 * 100 + 50);
+
+            radio.expect(&[
+                Transaction::check_receive(true, Ok(false)),
+                Transaction::poll_rssi(Ok(rssi[i][1])),
+            ]);
+            mac.tick().unwrap();
+
+            assert_eq!(mac.scan_state().unwrap().progress(), (i, channels.len()));
+        }
+
+        // Complete scan
+        timer.set_ms(300);
+        radio.expect(&[Transaction::check_receive(true, Ok(false))]);
+        mac.tick().unwrap();
+
+        let scan = mac.scan_state().unwrap();
+        assert!(scan.is_done());
+        assert_eq!(
+            scan.results()[1],
+            ScanResult {
+                channel: channels[1].clone(),
+                max_rssi: -80,
+                mean_rssi: -85,
+            }
+        );
+        assert_eq!(scan.results()[2].mean_rssi, -52);
+
+        // Quietest channel is selected, with the first beacon following the scan
+        assert_eq!(mac.config.channel, Some(channels[1].clone()));
+        assert_eq!(mac.next_beacon, 300 + mac_cfg.superframe_duration() as u64);
+        assert_ne!(mac.state(), Ok(MacState::Scanning));
+
+        // Radio returns to the selected channel
+        timer.set_ms(350);
+        radio.expect(&[
+            Transaction::check_receive(true, Ok(false)),
+            Transaction::set_channel(channels[1].clone(), None),
+            Transaction::start_receive(None),
+        ]);
+        mac.tick().unwrap();
+
+        radio.done();
+    }
+
+    #[test]
+    fn pan_scan_join() {
+        let _ =
+            simplelog::SimpleLogger::init(log::LevelFilter::Debug, simplelog::Config::default());
+
+        let mut radio = MockRadio::new(&[]);
+        let mut timer = MockTimer::new();
+
+        let mac_cfg = Config {
+            pan_coordinator: false,
+            pan_scan: Some(ScanKind::Passive),
+            scan_channels: heapless::Vec::from_slice(&[11]).unwrap(),
+            scan_duration: 500,
+            ..Default::default()
+        };
+
+        // Initialise MAC
+        radio.expect(&[Transaction::start_receive(None)]);
+        let mut mac = Mac::new(
+            ExtendedAddress(0xabcd),
+            mac_cfg.clone(),
+            radio.clone(),
+            timer.clone(),
+        )
+        .unwrap();
+
+        assert_eq!(mac.state(), Ok(MacState::Scanning));
+
+        radio.expect(&[
+            Transaction::check_receive(true, Ok(false)),
+            Transaction::set_channel(11, None),
+            Transaction::start_receive(None),
+        ]);
+        mac.tick().unwrap();
+
+        // Receive beacons from two PANs
+        let coord_a = Address::Short(PanId(0x0100), ShortAddress(0x1111));
+        let coord_b = Address::Short(PanId(0x0200), ShortAddress(0x2222));
+
+        let beacon = |coord_addr| {
+            let beacon_info = Beacon {
+                superframe_spec: mac_cfg.superframe_spec(),
+                guaranteed_time_slot_info: GuaranteedTimeSlotInformation::new(),
+                pending_address: PendingAddress::new(),
+            };
+            Packet::beacon(coord_addr, 0, beacon_info)
+        };
+
+        for (t, coord_addr) in [(100, coord_a), (200, coord_b)].iter() {
+            timer.set_ms(*t);
+            radio.expect(&[
+                Transaction::check_receive(true, Ok(true)),
+                Transaction::get_received(Ok((beacon(*coord_addr).into(), BasicInfo::default()))),
+                Transaction::start_receive(None),
+            ]);
+            mac.tick().unwrap();
+
+            // Beacons are recorded rather than adopted while scanning
+            assert_eq!(mac.sync_state, SyncState::Unsynced);
+        }
+
+        // Complete scan
+        timer.set_ms(500);
+        radio.expect(&[Transaction::check_receive(true, Ok(false))]);
+        mac.tick().unwrap();
+
+        let pans = mac.scan_results().to_vec();
+        assert_eq!(pans.len(), 2);
+        assert_eq!(pans[1].pan_id, PanId(0x0200));
+        assert_eq!(pans[1].coord_addr, coord_b);
+        assert_eq!(pans[1].channel, 11);
+        assert_eq!(
+            pans[1].association_permit,
+            mac_cfg.superframe_spec().association_permit
+        );
+
+        // Join the second PAN
+        mac.join(&pans[1]).unwrap();
+        assert_eq!(mac.config.pan_id, PanId(0x0200));
+
+        // Beacons from other coordinators are ignored
+        timer.set_ms(700);
+        radio.expect(&[
+            Transaction::check_receive(true, Ok(true)),
+            Transaction::get_received(Ok((beacon(coord_a).into(), BasicInfo::default()))),
+            Transaction::start_receive(None),
+        ]);
+        mac.tick().unwrap();
+
+        assert_eq!(mac.sync_state, SyncState::Unsynced);
+
+        // With sync adopted from the selected coordinator
+        timer.set_ms(800);
+        radio.expect(&[
+            Transaction::check_receive(true, Ok(true)),
+            Transaction::get_received(Ok((beacon(coord_b).into(), BasicInfo::default()))),
+            Transaction::start_receive(None),
+        ]);
+        mac.tick().unwrap();
+
+        assert_eq!(mac.sync_state, SyncState::Synced(coord_b));
+
+        radio.done();
+    }
+
+    #[test]
+    fn rejoin_resync() {
+        let _ =
+            simplelog::SimpleLogger::init(log::LevelFilter::Debug, simplelog::Config::default());
+
+        let mut radio = MockRadio::new(&[]);
+        let mut timer = MockTimer::new();
+
+        let mac_addr = ExtendedAddress(0xabcd);
+        let mac_cfg = Config {
+            max_beacon_misses: 1,
+            ..Default::default()
+        };
+        let coord_addr = Address::Extended(mac_cfg.pan_id, ExtendedAddress(0x1122));
+
+        // Initialise MAC
+        radio.expect(&[Transaction::start_receive(None)]);
+        let mut mac = Mac::new(
+            mac_addr.clone(),
+            mac_cfg.clone(),
+            radio.clone(),
+            timer.clone(),
+        )
+        .unwrap();
+
+        // Synced and associated
+        mac.sync_state = SyncState::Synced(coord_addr);
+        mac.assoc_state = AssocState::Associated(mac_cfg.pan_id);
+        mac.short_addr = Some(ShortAddress(0x0005));
+        mac.next_beacon = 2000;
+
+        // Coordinator goes silent
+        for t in [2000, 4020].iter() {
+            timer.set_ms(*t);
+            radio.expect(&[Transaction::check_receive(true, Ok(false))]);
+            mac.tick().unwrap();
+        }
+
+        // Orphan notifications are issued with backoff once sync is lost
+        for (i, t) in [6020, 7020].iter().enumerate() {
+            let mut orphan = Packet::command(
+                Address::broadcast(&AddressMode::Short),
+                Address::Extended(mac_cfg.pan_id, mac_addr),
+                i as u8,
+                Command::OrphanNotification,
+            );
+            orphan.header.ack_request = false;
+
+            timer.set_ms(*t);
+            radio.expect(&[
+                Transaction::check_receive(true, Ok(false)),
+                Transaction::start_transmit(orphan.into(), None),
+            ]);
+            mac.tick().unwrap();
+
+            // Association is retained while attempting to re-sync
+            assert_eq!(mac.state(), Ok(MacState::Disconnected));
+            assert_eq!(mac.assoc_state, AssocState::Associated(mac_cfg.pan_id));
+            assert_eq!(mac.stats.rejoin_attempts, i as u32 + 1);
+
+            timer.set_ms(*t + 80);
+            radio.expect(&[
+                Transaction::check_transmit(Ok(true)),
+                Transaction::start_receive(None),
+            ]);
+            mac.tick().unwrap();
+        }
+
+        // Coordinator responds with a realignment
+        let mut realign = Packet::command(
+            Address::Extended(mac_cfg.pan_id, mac_addr),
+            coord_addr,
+            0,
+            Command::CoordinatorRealignment(CoordinatorRealignmentData {
+                pan_id: mac_cfg.pan_id,
+                coordinator_address: ShortAddress(0xfffe),
+                channel: 0,
+                device_address: ShortAddress(0x0007),
+                channel_page: None,
+            }),
+        );
+        realign.header.ack_request = false;
+
+        timer.set_ms(7500);
+        radio.expect(&[
+            Transaction::check_receive(true, Ok(true)),
+            Transaction::get_received(Ok((realign.into(), BasicInfo::default()))),
+            Transaction::start_receive(None),
+        ]);
+        mac.tick().unwrap();
+
+        assert_eq!(mac.short_addr, Some(ShortAddress(0x0007)));
+
+        // Beacons resume, with sync recovered without re-association
+        let beacon_info = Beacon {
+            superframe_spec: mac_cfg.superframe_spec(),
+            guaranteed_time_slot_info: GuaranteedTimeSlotInformation::new(),
+            pending_address: PendingAddress::new(),
+        };
+        let beacon = Packet::beacon(coord_addr, 0, beacon_info);
+
+        timer.set_ms(8000);
+        radio.expect(&[
+            Transaction::check_receive(true, Ok(true)),
+            Transaction::get_received(Ok((beacon.into(), BasicInfo::default()))),
+            Transaction::start_receive(None),
+        ]);
+        mac.tick().unwrap();
+
+        assert_eq!(mac.state(), Ok(MacState::Associated(coord_addr)));
+        assert_eq!(mac.stats.resyncs, 1);
+        assert_eq!(mac.stats.sync_fail, 0);
+        assert!(mac.tx_buff.is_empty());
+
+        radio.done();
+    }
+
+    #[test]
+    fn rejoin_grace_expiry() {
+        let _ =
+            simplelog::SimpleLogger::init(log::LevelFilter::Debug, simplelog::Config::default());
+
+        let mut radio = MockRadio::new(&[]);
+        let mut timer = MockTimer::new();
+
+        let mac_cfg = Config::default();
+        let coord_addr = Address::Extended(mac_cfg.pan_id, ExtendedAddress(0x1122));
+
+        // Initialise MAC
+        radio.expect(&[Transaction::start_receive(None)]);
+        let mut mac = Mac::new(
+            ExtendedAddress(0xabcd),
+            mac_cfg.clone(),
+            radio.clone(),
+            timer.clone(),
+        )
+        .unwrap();
+
+        // Sync lost with the orphan notification backoff pending
+        mac.sync_state = SyncState::Lost(coord_addr, 1000);
+        mac.assoc_state = AssocState::Associated(mac_cfg.pan_id);
+        mac.short_addr = Some(ShortAddress(0x0005));
+        mac.rejoin_at = 2000;
+
+        // Association is dropped once the grace period expires
+        timer.set_ms(1010);
+        radio.expect(&[Transaction::check_receive(true, Ok(false))]);
+        mac.tick().unwrap();
+
+        assert_eq!(mac.sync_state, SyncState::Unsynced);
+        assert_eq!(mac.assoc_state, AssocState::Unassociated);
+        assert_eq!(mac.short_addr, None);
+        assert_eq!(mac.stats.sync_fail, 1);
+
+        radio.done();
+    }
+
+    #[test]
+    fn orphan_realignment() {
+        let _ =
+            simplelog::SimpleLogger::init(log::LevelFilter::Debug, simplelog::Config::default());
+
+        let mut radio = MockRadio::new(&[]);
+        let mut timer = MockTimer::new();
+
+        let mac_cfg = Config {
+            pan_coordinator: true,
+            ..Default::default()
+        };
+        let device_addr = Address::Extended(mac_cfg.pan_id, ExtendedAddress(0x1122));
+        let unknown_addr = Address::Extended(mac_cfg.pan_id, ExtendedAddress(0x3344));
+
+        // Initialise MAC
+        radio.expect(&[Transaction::start_receive(None)]);
+        let mut mac = Mac::new(
+            ExtendedAddress(0xabcd),
+            mac_cfg.clone(),
+            radio.clone(),
+            timer.clone(),
+        )
+        .unwrap();
+
+        mac.assoc_table.allocate(ExtendedAddress(0x1122), true);
+
+        // Receive orphan notifications from an associated and an unknown device
+        for (i, source) in [device_addr, unknown_addr].iter().enumerate() {
+            let mut orphan = Packet::command(
+                Address::broadcast(&AddressMode::Short),
+                *source,
+                0,
+                Command::OrphanNotification,
+            );
+            orphan.header.ack_request = false;
+
+            timer.set_ms(100 + i as u32 * 10);
+            radio.expect(&[
+                Transaction::check_receive(true, Ok(true)),
+                Transaction::get_received(Ok((orphan.into(), BasicInfo::default()))),
+                Transaction::start_receive(None),
+            ]);
+            mac.tick().unwrap();
+        }
+
+        // Only the associated device is realigned
+        let realigned: std::vec::Vec<_> = mac.tx_buff.iter().map(|(_s, p)| p.clone()).collect();
+        assert_eq!(realigned.len(), 1);
+        assert_eq!(realigned[0].header.destination, device_addr);
+        assert_eq!(
+            realigned[0].content,
+            FrameContent::Command(Command::CoordinatorRealignment(
+                CoordinatorRealignmentData {
+                    pan_id: mac_cfg.pan_id,
+                    coordinator_address: ShortAddress(0xfffe),
+                    channel: 0,
+                    device_address: ShortAddress(0x0001),
+                    channel_page: None,
+                }
+            ))
+        );
+
+        radio.done();
+    }
+
+    #[test]
+    fn secured_data() {
+        use super::security::{secure, AuxSecurityHeader, CcmStar, KeyId, SecurityLevel, SoftAes};
+
+        let _ =
+            simplelog::SimpleLogger::init(log::LevelFilter::Debug, simplelog::Config::default());
+
+        let mut radio = MockRadio::new(&[]);
+        let mut timer = MockTimer::new();
+
+        let mac_addr = ExtendedAddress(0xabcd);
+        let security = SecurityConfig {
+            key: [0x11; 16],
+            key_id: KeyId::Index(1),
+            level: SecurityLevel::EncMic32,
+        };
+        let mac_cfg = Config {
+            security: Some(security.clone()),
+            ..Default::default()
+        };
+        let coord_ext = ExtendedAddress(0x1122);
+        let coord_addr = Address::Extended(mac_cfg.pan_id, coord_ext);
+
+        // Initialise MAC
+        radio.expect(&[Transaction::start_receive(None)]);
+        let mut mac = Mac::with_aead(
+            mac_addr.clone(),
+            mac_cfg.clone(),
+            radio.clone(),
+            timer.clone(),
+            CcmStar(SoftAes),
+        )
+        .unwrap();
+
+        // Receive a secured data frame
+        let mut data = Packet::data(mac.addr(), coord_addr, 0, &[0x11, 0x22, 0x33], false).unwrap();
+        let aux = AuxSecurityHeader {
+            level: security.level,
+            key_id: security.key_id,
+            frame_counter: 7,
+        };
+        secure(
+            &mut CcmStar(SoftAes),
+            &security.key,
+            coord_ext,
+            aux,
+            &mut data,
+        )
+        .unwrap();
+
+        timer.set_ms(10);
+        radio.expect(&[
+            Transaction::check_receive(true, Ok(true)),
+            Transaction::get_received(Ok((data.into(), BasicInfo::default()))),
+            Transaction::start_receive(None),
+        ]);
+        mac.tick().unwrap();
+
+        let mut buff = [0u8; 16];
+        let (n, info) = mac.receive(&mut buff).unwrap().unwrap();
+        assert_eq!(&buff[..n], &[0x11, 0x22, 0x33]);
+        assert_eq!(info.source, coord_addr);
+
+        // Replayed frame counters are rejected
+        let mut replay =
+            Packet::data(mac.addr(), coord_addr, 1, &[0x11, 0x22, 0x33], false).unwrap();
+        secure(
+            &mut CcmStar(SoftAes),
+            &security.key,
+            coord_ext,
+            aux,
+            &mut replay,
+        )
+        .unwrap();
+
+        timer.set_ms(20);
+        radio.expect(&[
+            Transaction::check_receive(true, Ok(true)),
+            Transaction::get_received(Ok((replay.into(), BasicInfo::default()))),
+            Transaction::start_receive(None),
+        ]);
+        mac.tick().unwrap();
+
+        // As are unsecured data frames
+        let data = Packet::data(mac.addr(), coord_addr, 1, &[0x11, 0x22, 0x33], false).unwrap();
+
+        timer.set_ms(30);
+        radio.expect(&[
+            Transaction::check_receive(true, Ok(true)),
+            Transaction::get_received(Ok((data.into(), BasicInfo::default()))),
+            Transaction::start_receive(None),
+        ]);
+        mac.tick().unwrap();
+
+        // Both are dropped and counted without failing the tick
+        assert_eq!(mac.stats.security_fail, 2);
+        assert!(mac.receive(&mut buff).unwrap().is_none());
+
+        // Outgoing data frames are secured with incrementing frame counters
+        mac.transmit(coord_addr, &[0xaa, 0xbb], false).unwrap();
+
+        let (_s, p) = mac.tx_buff.peek().unwrap();
+        assert_eq!(p.aux_security.map(|a| a.frame_counter), Some(0));
+        assert_eq!(p.payload().len(), 2 + 4);
+        assert_eq!(mac.frame_counter(), 1);
+
+        radio.done();
+    }
+
+    #[test]
+    fn buffer_length() {
+        let _ =
+            simplelog::SimpleLogger::init(log::LevelFilter::Debug, simplelog::Config::default());
+
+        let mut radio = MockRadio::new(&[]);
+        let mut timer = MockTimer::new();
+
+        let mac_addr = ExtendedAddress(0xabcd);
+        let mac_cfg = Config::default();
+        let source = Address::Short(mac_cfg.pan_id, ShortAddress(0x0010));
+
+        // Initialise MAC
+        radio.expect(&[Transaction::start_receive(None)]);
+        let mut mac = Mac::new(
+            mac_addr.clone(),
+            mac_cfg.clone(),
+            radio.clone(),
+            timer.clone(),
+        )
+        .unwrap();
+
+        // Frames exceeding the radio buffer are rejected on transmit
+        assert_eq!(
+            mac.transmit(source, &[0xaa; 300], false).err(),
+            Some(CoreError::BufferError(BufferError::TooSmall))
+        );
+        assert_eq!(
+            mac.transmit(source, &[0xaa; 250], false).err(),
+            Some(CoreError::BufferError(BufferError::TooSmall))
+        );
+        assert!(mac.tx_buff.is_empty());
+
+        // Receive a frame
+        timer.set_ms(150);
+        let data = Packet::data(mac.addr(), source, 7, &[0x11, 0x22, 0x33], false).unwrap();
+        radio.expect(&[
+            Transaction::check_receive(true, Ok(true)),
+            Transaction::get_received(Ok((data.into(), BasicInfo::default()))),
+            Transaction::start_receive(None),
+        ]);
+        mac.tick().unwrap();
+
+        // Payloads exceeding the receive buffer are retained
+        let mut buff = [0u8; 2];
+        assert_eq!(
+            mac.receive(&mut buff),
+            Err(CoreError::BufferError(BufferError::Insufficient {
+                needed: 3
+            }))
+        );
+        assert_eq!(mac.rx_buff.len(), 1);
+
+        // Then received on retry with a sufficient buffer
+        let mut buff = [0u8; 3];
+        let (n, info) = mac.receive(&mut buff).unwrap().unwrap();
+        assert_eq!(&buff[..n], &[0x11, 0x22, 0x33]);
+        assert_eq!(info.source, source);
+        assert!(mac.receive(&mut buff).unwrap().is_none());
+
+        // Or discarded where no larger buffer is available
+        timer.set_ms(160);
+        let data = Packet::data(mac.addr(), source, 8, &[0x44; 8], false).unwrap();
+        radio.expect(&[
+            Transaction::check_receive(true, Ok(true)),
+            Transaction::get_received(Ok((data.into(), BasicInfo::default()))),
+            Transaction::start_receive(None),
+        ]);
+        mac.tick().unwrap();
+
+        let e = mac.receive(&mut buff).unwrap_err();
+        assert!(crate::MacError::buffer_length(&e));
+        assert_eq!(mac.discard(), Ok(true));
+        assert_eq!(mac.discard(), Ok(false));
+        assert!(mac.receive(&mut buff).unwrap().is_none());
+
+        // Encoding checks the output buffer
+        let p = Packet::data(source, mac.addr(), 0, &[0x11, 0x22, 0x33], false).unwrap();
+        let mut buff = [0u8; 256];
+        let n = p.encode(&mut buff, WriteFooter::No).unwrap();
+        assert_eq!(
+            p.encode(&mut buff[..n - 1], WriteFooter::No),
+            Err(BufferError::TooSmall)
+        );
+
+        // Oversized payloads are rejected on construction, retaining any existing payload
+        let oversized = [0xaa; packet::MAX_PAYLOAD_LEN + 1];
+        assert_eq!(
+            Packet::data(source, mac.addr(), 0, &oversized, false).err(),
+            Some(BufferError::TooSmall)
+        );
+        let mut p = p;
+        assert_eq!(p.set_payload(&oversized), Err(BufferError::TooSmall));
+        assert_eq!(p.payload(), &[0x11, 0x22, 0x33]);
+
+        // Transmissions exceeding the TX queue are rejected as queue full
+        for _ in 0..mac.tx_capacity(TxPriority::Data) {
+            mac.transmit(source, &[0xaa], false).unwrap();
+        }
+        let e = mac.transmit(source, &[0xaa], false).unwrap_err();
+        assert_eq!(e, CoreError::BufferFull);
+        assert!(crate::MacError::queue_full(&e));
+
+        radio.done();
+    }
+
+    #[test]
+    fn queue_depths() {
+        let _ =
+            simplelog::SimpleLogger::init(log::LevelFilter::Debug, simplelog::Config::default());
+
+        let mut radio = MockRadio::new(&[]);
+        let mut timer = MockTimer::new();
+
+        let mac_addr = ExtendedAddress(0xabcd);
+        let mac_cfg = Config::default();
+        let source = Address::Short(mac_cfg.pan_id, ShortAddress(0x0010));
+
+        // Initialise MAC with shallow queues
+        radio.expect(&[Transaction::start_receive(None)]);
+        let mut mac = Mac::<_, _, NoSecurity, 2, 1>::with_queues(
+            mac_addr.clone(),
+            mac_cfg.clone(),
+            radio.clone(),
+            timer.clone(),
+            NoSecurity,
+        )
+        .unwrap();
+
+        // Transmit capacity follows the configured depth
+        assert_eq!(mac.tx_capacity(TxPriority::Data), 2);
+        for _ in 0..2 {
+            assert_eq!(mac.can_transmit(), Ok(true));
+            mac.transmit(source, &[0xaa], false).unwrap();
+        }
+        assert_eq!(mac.can_transmit(), Ok(false));
+        assert_eq!(
+            mac.transmit(source, &[0xaa], false).err(),
+            Some(CoreError::BufferFull)
+        );
+
+        // Received frames exceeding the configured depth are dropped
+        for seq in 0..2 {
+            timer.set_ms(150 + seq as u32);
+            let data = Packet::data(mac.addr(), source, seq, &[0x11, seq], false).unwrap();
+            radio.expect(&[
+                Transaction::check_receive(true, Ok(true)),
+                Transaction::get_received(Ok((data.into(), BasicInfo::default()))),
+                Transaction::start_receive(None),
+            ]);
+            mac.tick().unwrap();
+        }
+        assert_eq!(mac.stats.rx_queue_full, 1);
+
+        let mut buff = [0u8; 16];
+        let (n, _info) = mac.receive(&mut buff).unwrap().unwrap();
+        assert_eq!(&buff[..n], &[0x11, 0x00]);
+        assert!(mac.receive(&mut buff).unwrap().is_none());
+
+        radio.done();
+    }
+
+    #[test]
+    fn duplicate_rx() {
+        let _ =
+            simplelog::SimpleLogger::init(log::LevelFilter::Debug, simplelog::Config::default());
+
+        let mut radio = MockRadio::new(&[]);
+        let mut timer = MockTimer::new();
+
+        let mac_addr = ExtendedAddress(0xabcd);
+        let mac_cfg = Config::default();
+        let source = Address::Short(mac_cfg.pan_id, ShortAddress(0x0010));
+
+        // Initialise MAC
+        radio.expect(&[Transaction::start_receive(None)]);
+        let mut mac = Mac::new(
+            mac_addr.clone(),
+            mac_cfg.clone(),
+            radio.clone(),
+            timer.clone(),
+        )
+        .unwrap();
+
+        let data = Packet::data(mac.addr(), source, 7, &[0x11, 0x22, 0x33], true).unwrap();
+        let ack = Packet::ack(&data);
+
+        // Deliver the same frame twice, as if the first ACK was lost
+        for t in [150, 250].iter() {
+            timer.set_ms(*t);
+            radio.expect(&[
+                Transaction::check_receive(true, Ok(true)),
+                Transaction::get_received(Ok((data.clone().into(), BasicInfo::default()))),
+                Transaction::start_receive(None),
+            ]);
+            mac.tick().unwrap();
+
+            // Both copies are acknowledged
+            timer.set_ms(*t + mac_cfg.ack_delay as u32 + 10);
+            radio.expect(&[
+                Transaction::check_receive(true, Ok(false)),
+                Transaction::start_transmit(ack.clone().into(), None),
+            ]);
+            mac.tick().unwrap();
+
+            timer.set_ms(*t + mac_cfg.ack_delay as u32 + 20);
+            radio.expect(&[
+                Transaction::check_transmit(Ok(true)),
+                Transaction::start_receive(None),
+            ]);
+            mac.tick().unwrap();
+        }
+
+        // But only delivered once
+        let mut buff = [0u8; 16];
+        let (n, info) = mac.receive(&mut buff).unwrap().unwrap();
+        assert_eq!(&buff[..n], &[0x11, 0x22, 0x33]);
+        assert_eq!(info.source, source);
+        assert!(mac.receive(&mut buff).unwrap().is_none());
+
+        assert_eq!(mac.stats.rx_duplicates, 1);
+
+        // Rejection may be disabled, delivering retransmissions
+        mac.config.dedup_sources = 0;
+        let data = Packet::data(mac.addr(), source, 8, &[0x44], false).unwrap();
+        for t in [400, 500].iter() {
+            timer.set_ms(*t);
+            radio.expect(&[
+                Transaction::check_receive(true, Ok(true)),
+                Transaction::get_received(Ok((data.clone().into(), BasicInfo::default()))),
+                Transaction::start_receive(None),
+            ]);
+            mac.tick().unwrap();
+        }
+
+        for _ in 0..2 {
+            let (n, _info) = mac.receive(&mut buff).unwrap().unwrap();
+            assert_eq!(&buff[..n], &[0x44]);
+        }
+        assert_eq!(mac.stats.rx_duplicates, 1);
+
+        radio.done();
+    }
+
+    #[test]
+    fn rx_stats() {
+        let _ =
+            simplelog::SimpleLogger::init(log::LevelFilter::Debug, simplelog::Config::default());
+
+        let mut radio = MockRadio::new(&[]);
+        let mut timer = MockTimer::new();
+
+        let mac_addr = ExtendedAddress(0xabcd);
+        let mac_cfg = Config::default();
+        let source = Address::Short(mac_cfg.pan_id, ShortAddress(0x0010));
+        let other = Address::Short(mac_cfg.pan_id, ShortAddress(0x0020));
+
+        // Initialise MAC
+        radio.expect(&[Transaction::start_receive(None)]);
+        let mut mac = Mac::new(
+            mac_addr.clone(),
+            mac_cfg.clone(),
+            radio.clone(),
+            timer.clone(),
+        )
+        .unwrap();
+
+        // Malformed frames are dropped
+        timer.set_ms(150);
+        radio.expect(&[
+            Transaction::check_receive(true, Ok(true)),
+            Transaction::get_received(Ok((std::vec![0x41], BasicInfo::default()))),
+            Transaction::start_receive(None),
+        ]);
+        mac.tick().unwrap();
+
+        // As are frames for other addresses
+        let data = Packet::data(other, source, 1, &[0x11, 0x22], false).unwrap();
+
+        timer.set_ms(160);
+        radio.expect(&[
+            Transaction::check_receive(true, Ok(true)),
+            Transaction::get_received(Ok((data.into(), BasicInfo::default()))),
+            Transaction::start_receive(None),
+        ]);
+        mac.tick().unwrap();
+
+        assert_eq!(mac.stats().rx_drop_decode, 1);
+        assert_eq!(mac.stats().rx_drop_addr, 1);
+        assert_eq!(mac.stats().rx_ok, 0);
+
+        // Frames for us are accepted and queued for collection
+        let data = Packet::data(mac.addr(), source, 2, &[0x11, 0x22], false).unwrap();
+
+        timer.set_ms(170);
+        radio.expect(&[
+            Transaction::check_receive(true, Ok(true)),
+            Transaction::get_received(Ok((data.into(), BasicInfo::default()))),
+            Transaction::start_receive(None),
+        ]);
+        mac.tick().unwrap();
+
+        let stats = MacIf::stats(&mac);
+        assert_eq!(stats.rx_ok, 1);
+        assert_eq!(stats.rx_dropped, 2);
+        assert_eq!(stats.rx_queued, 1);
+
+        // Taking statistics resets counters for the next interval
+        let stats = mac.stats_take();
+        assert_eq!(stats.rx_ok, 1);
+        assert_eq!(stats.radio.rx_frames, 3);
+        assert_eq!(stats.radio.rx_ms, 170);
+
+        timer.set_ms(200);
+        let stats = mac.stats();
+        assert_eq!(stats.rx_ok, 0);
+        assert_eq!(stats.radio.rx_frames, 0);
+        assert_eq!(stats.radio.rx_ms, 30);
+
+        // Resetting clears counters but not queue depths
+        mac.reset_stats();
+        assert_eq!(mac.stats(), MacStats::new());
+        assert_eq!(MacIf::stats(&mac).rx_queued, 1);
+
+        radio.done();
+    }
+
+    #[test]
+    fn promiscuous_rx() {
+        let _ =
+            simplelog::SimpleLogger::init(log::LevelFilter::Debug, simplelog::Config::default());
+
+        let mut radio = MockRadio::new(&[]);
+        let mut timer = MockTimer::new();
+
+        let mac_addr = ExtendedAddress(0xabcd);
+        let mac_cfg = Config {
+            promiscuous: true,
+            ..Default::default()
+        };
+        let source = Address::Short(mac_cfg.pan_id, ShortAddress(0x0010));
+        let other = Address::Short(mac_cfg.pan_id, ShortAddress(0x0020));
+
+        // Initialise MAC
+        radio.expect(&[Transaction::start_receive(None)]);
+        let mut mac = Mac::new(
+            mac_addr.clone(),
+            mac_cfg.clone(),
+            radio.clone(),
+            timer.clone(),
+        )
+        .unwrap();
+
+        // Data frames for other addresses are delivered, without acknowledgement
+        let data = Packet::data(other, source, 1, &[0x11, 0x22], true).unwrap();
+
+        timer.set_ms(150);
+        radio.expect(&[
+            Transaction::check_receive(true, Ok(true)),
+            Transaction::get_received(Ok((data.into(), BasicInfo::new(-60, 0)))),
+            Transaction::start_receive(None),
+        ]);
+        mac.tick().unwrap();
+
+        assert_eq!(mac.ack_state, AckState::None);
+
+        let mut buff = [0u8; 16];
+        let (n, info) = mac.receive(&mut buff).unwrap().unwrap();
+        assert_eq!(&buff[..n], &[0x11, 0x22]);
+        assert_eq!(
+            info,
+            RxInfo {
+                source,
+                destination: other,
+                rssi: -60,
+            }
+        );
+
+        // While other frames for other addresses are still dropped
+        let cmd = Packet::command(other, source, 2, Command::DataRequest);
+
+        timer.set_ms(160);
+        radio.expect(&[
+            Transaction::check_receive(true, Ok(true)),
+            Transaction::get_received(Ok((cmd.into(), BasicInfo::default()))),
+            Transaction::start_receive(None),
+        ]);
+        mac.tick().unwrap();
+
+        assert_eq!(mac.stats().rx_ok, 1);
+        assert_eq!(mac.stats().rx_drop_addr, 1);
+
+        radio.done();
+    }
+
+    #[test]
+    fn rx_malformed() {
+        let _ =
+            simplelog::SimpleLogger::init(log::LevelFilter::Debug, simplelog::Config::default());
+
+        let mut radio = MockRadio::new(&[]);
+        let mut timer = MockTimer::new();
+
+        let mac_addr = ExtendedAddress(0xabcd);
+        let mac_cfg = Config::default();
+        let source = Address::Short(mac_cfg.pan_id, ShortAddress(0x0010));
+
+        // Initialise MAC
+        radio.expect(&[Transaction::start_receive(None)]);
+        let mut mac = Mac::new(
+            mac_addr.clone(),
+            mac_cfg.clone(),
+            radio.clone(),
+            timer.clone(),
+        )
+        .unwrap();
+
+        // Truncated frames, and frames with a reserved frame version, are dropped
+        // without failing the tick
+        let mut version: std::vec::Vec<u8> =
+            Packet::data(mac.addr(), source, 1, &[0x11, 0x22], false)
+                .unwrap()
+                .into();
+        version[1] |= 0b0011_0000;
+
+        let frames = [std::vec![0x41], std::vec![0x41, 0x88, 0x01], version];
+        for (i, f) in frames.iter().enumerate() {
+            timer.set_ms(150 + i as u32 * 10);
+            radio.expect(&[
+                Transaction::check_receive(true, Ok(true)),
+                Transaction::get_received(Ok((f.clone(), BasicInfo::default()))),
+                Transaction::start_receive(None),
+            ]);
+            mac.tick().unwrap();
+        }
+        assert_eq!(mac.stats().rx_drop_decode, 3);
+
+        // Following frames are still received
+        let data = Packet::data(mac.addr(), source, 2, &[0x11, 0x22], false).unwrap();
+
+        timer.set_ms(200);
+        radio.expect(&[
+            Transaction::check_receive(true, Ok(true)),
+            Transaction::get_received(Ok((data.into(), BasicInfo::default()))),
+            Transaction::start_receive(None),
+        ]);
+        mac.tick().unwrap();
+
+        let mut buff = [0u8; 16];
+        let (n, _info) = mac.receive(&mut buff).unwrap().unwrap();
+        assert_eq!(&buff[..n], &[0x11, 0x22]);
+
+        radio.done();
+    }
+
+    #[test]
+    fn deadlines() {
+        let _ =
+            simplelog::SimpleLogger::init(log::LevelFilter::Debug, simplelog::Config::default());
+
+        let mut radio = MockRadio::new(&[]);
+        let mut timer = MockTimer::new();
+
+        let mac_addr = ExtendedAddress(0xabcd);
+        let mac_cfg = Config {
+            pan_coordinator: true,
+            ..Default::default()
+        };
+        let device_addr = Address::Extended(mac_cfg.pan_id, ExtendedAddress(0x1122));
+
+        // Initialise MAC
+        radio.expect(&[Transaction::start_receive(None)]);
+        let mut mac = Mac::new(
+            mac_addr.clone(),
+            mac_cfg.clone(),
+            radio.clone(),
+            timer.clone(),
+        )
+        .unwrap();
+
+        // Coordinators are next due at the first beacon
+        let beacon_time = mac_cfg.superframe_duration() as u64;
+        assert_eq!(mac.next_deadline(), Some(beacon_time));
+
+        // Receive a packet requesting an ACK
+        let data = Packet::data(mac.addr(), device_addr, 0, &[0x11, 0x22], true).unwrap();
+
+        timer.set_ms(150);
+        radio.expect(&[
+            Transaction::check_receive(true, Ok(true)),
+            Transaction::get_received(Ok((data.clone().into(), BasicInfo::default()))),
+            Transaction::start_receive(None),
+        ]);
+        mac.tick().unwrap();
+
+        // Received packets are due for collection immediately
+        assert_eq!(mac.next_deadline(), Some(150));
+
+        let mut buff = [0u8; 16];
+        assert!(mac.receive(&mut buff).unwrap().is_some());
+
+        // Then the ACK
+        assert_eq!(mac.next_deadline(), Some(150 + mac_cfg.ack_delay + 1));
+
+        timer.set_ms(210);
+        radio.expect(&[
+            Transaction::check_receive(true, Ok(false)),
+            Transaction::start_transmit(Packet::ack(&data).into(), None),
+        ]);
+        mac.tick().unwrap();
+
+        assert_eq!(mac.next_deadline(), Some(beacon_time));
+
+        // Pending transmissions are evaluated each slot
+        mac.transmit(device_addr, &[0x33], false).unwrap();
+        assert_eq!(mac.next_deadline(), Some(300));
+
+        radio.done();
+    }
+
+    #[test]
+    fn radio_irq() {
+        let mut radio = MockRadio::new(&[]);
+        let mut timer = MockTimer::new();
+
+        let mac_addr = ExtendedAddress(0xabcd);
+        let mac_cfg = Config {
+            radio_irq: true,
+            ..Default::default()
+        };
+
+        // Initialise MAC
+        radio.expect(&[Transaction::start_receive(None)]);
+        let mut mac = Mac::new(
+            mac_addr.clone(),
+            mac_cfg.clone(),
+            radio.clone(),
+            timer.clone(),
+        )
+        .unwrap();
+
+        // The radio is serviced on the first tick
+        radio.expect(&[Transaction::check_receive(true, Ok(false))]);
+        mac.tick().unwrap();
+
+        // Then only following radio events
+        timer.set_ms(10);
+        mac.tick().unwrap();
+        assert_eq!(mac.next_deadline(), None);
+
+        mac.radio_event();
+        assert_eq!(mac.next_deadline(), Some(10));
+
+        timer.set_ms(20);
+        radio.expect(&[Transaction::check_receive(true, Ok(false))]);
+        mac.tick().unwrap();
+
+        // Or on expiry of the poll timeout where configured
+        let mac_cfg = Config {
+            radio_poll_timeout: Some(100),
+            ..mac_cfg
+        };
+        mac.reconfigure(mac_cfg).unwrap();
+        assert_eq!(mac.next_deadline(), Some(120));
+
+        timer.set_ms(119);
+        mac.tick().unwrap();
+
+        timer.set_ms(120);
+        radio.expect(&[Transaction::check_receive(true, Ok(false))]);
+        mac.tick().unwrap();
+        assert_eq!(mac.next_deadline(), Some(220));
+
+        radio.done();
+    }
+
+    #[test]
+    fn data_tx_status() {
+        let _ =
+            simplelog::SimpleLogger::init(log::LevelFilter::Debug, simplelog::Config::default());
+
+        let mut radio = MockRadio::new(&[]);
+        let mut timer = MockTimer::new();
+
+        let mac_addr = ExtendedAddress(0xabcd);
+        let mac_cfg = Config::default();
+        let dest = Address::Extended(mac_cfg.pan_id, ExtendedAddress(0x1122));
+
+        // Initialise MAC
+        radio.expect(&[Transaction::start_receive(None)]);
+        let mut mac = Mac::new(
+            mac_addr.clone(),
+            mac_cfg.clone(),
+            radio.clone(),
+            timer.clone(),
+        )
+        .unwrap();
+
+        // Enqueue packet without ACK
+        let data = [0x11, 0x22, 0x33];
+        let handle = mac.transmit(dest, &data, false).unwrap();
+        let packet = Packet::data(dest, mac.addr(), 0, &data, false).unwrap();
+
+        // Schedule CSMA TX in the current (non-beacon) slot
+        timer.set_ms(150);
+        mac.csma_state = CsmaState::Pending {
+            packet: packet.clone(),
+            tx_slot: mac_cfg.calculate_asn(150, mac.sync_offset),
+            retries: 0,
+            cca: CcaState::Clear,
+        };
+
+        radio.expect(&[
+            Transaction::check_receive(true, Ok(false)),
+            Transaction::start_transmit(packet.into(), None),
+        ]);
+        mac.tick().unwrap();
+
+        // Outcome reported against the returned handle
+        assert_eq!(mac.tx_status().unwrap(), Some((handle, TxStatus::Sent)));
+        assert_eq!(mac.tx_status().unwrap(), None);
+
+        assert_eq!(mac.stats().tx_ok, 1);
+        assert_eq!(MacIf::stats(&mac).tx_queued, 0);
+
+        radio.done();
+    }
+
+    #[test]
+    fn csma_fail_tx_status() {
+        let _ =
+            simplelog::SimpleLogger::init(log::LevelFilter::Debug, simplelog::Config::default());
+
+        let mut radio = MockRadio::new(&[]);
+        let mut timer = MockTimer::new();
+
+        let mac_addr = ExtendedAddress(0xabcd);
+        let mac_cfg = Config::default();
+        let dest = Address::Extended(mac_cfg.pan_id, ExtendedAddress(0x1122));
+
+        // Initialise MAC
+        radio.expect(&[Transaction::start_receive(None)]);
+        let mut mac = Mac::new(
+            mac_addr.clone(),
+            mac_cfg.clone(),
+            radio.clone(),
+            timer.clone(),
+        )
+        .unwrap();
+
+        let data = [0x11, 0x22, 0x33];
+        let handle = mac.transmit(dest, &data, false).unwrap();
+        let packet = Packet::data(dest, mac.addr(), 0, &data, false).unwrap();
+
+        // Exhaust CSMA backoffs, failure is reported at the next slotframe start
+        timer.set_ms(mac_cfg.base_superframe_duration);
+        mac.csma_state = CsmaState::Pending {
+            packet,
+            tx_slot: 0,
+            retries: mac_cfg.csma_max_backoffs as u64,
+            cca: CcaState::Clear,
+        };
+
+        radio.expect(&[Transaction::check_receive(true, Ok(false))]);
+        mac.tick().unwrap();
+
+        assert_eq!(
+            mac.tx_status().unwrap(),
+            Some((handle, TxStatus::Failed(TxFailure::ChannelBusy)))
+        );
+        assert_eq!(mac.can_transmit().unwrap(), true);
+
+        radio.done();
+    }
+
+    #[test]
+    fn csma_cca_samples() {
+        let _ =
+            simplelog::SimpleLogger::init(log::LevelFilter::Debug, simplelog::Config::default());
+
+        let mut radio = MockRadio::new(&[]);
+        let mut timer = MockTimer::new();
+
+        let mac_addr = ExtendedAddress(0xabcd);
+        let mac_cfg = Config {
+            cca_mode: CcaMode::EnergyAboveThreshold {
+                threshold: -60,
+                samples: 3,
+                sample_interval_ms: 10,
+            },
+            ..Default::default()
+        };
+        let dest = Address::Extended(mac_cfg.pan_id, ExtendedAddress(0x1122));
+
+        // Initialise MAC
+        radio.expect(&[Transaction::start_receive(None)]);
+        let mut mac = Mac::new(
+            mac_addr.clone(),
+            mac_cfg.clone(),
+            radio.clone(),
+            timer.clone(),
+        )
+        .unwrap();
+
+        let data = [0x11, 0x22, 0x33];
+        let handle = mac.transmit(dest, &data, false).unwrap();
+        let packet = Packet::data(dest, mac.addr(), 0, &data, false).unwrap();
+
+        // Schedule CSMA TX in the following slot
+        let tx_slot = mac_cfg.calculate_asn(150, mac.sync_offset) + 1;
+        let pending = CsmaState::Pending {
+            packet: packet.clone(),
+            tx_slot,
+            retries: 0,
+            cca: mac.cca_start(),
+        };
+
+        // Any sample above the threshold finds the channel busy
+        timer.set_ms(150);
+        mac.csma_state = pending.clone();
+        radio.expect(&[
+            Transaction::check_receive(true, Ok(false)),
+            Transaction::poll_rssi(Ok(-40)),
+        ]);
+        mac.tick().unwrap();
+
+        match &mac.csma_state {
+            CsmaState::Pending {
+                tx_slot: 0,
+                retries: 1,
+                ..
+            } => (),
+            s => panic!("Unexpected CSMA state: {:?}", s),
+        }
+
+        // Otherwise samples are taken across the slot, spaced by the sample interval
+        let samples = [
+            (150, Some(-90)),
+            (155, None),
+            (160, Some(-70)),
+            (170, Some(-80)),
+        ];
+        mac.csma_state = pending;
+        for (t, rssi) in samples.iter() {
+            let mut expected = std::vec![Transaction::check_receive(true, Ok(false))];
+            if let Some(r) = rssi {
+                expected.push(Transaction::poll_rssi(Ok(*r)));
+            }
+
+            timer.set_ms(*t);
+            radio.expect(&expected);
+            mac.tick().unwrap();
+        }
+
+        match &mac.csma_state {
+            CsmaState::Pending {
+                cca: CcaState::Clear,
+                ..
+            } => (),
+            s => panic!("Unexpected CSMA state: {:?}", s),
+        }
+        assert_eq!(mac.stats().cca_rssi_min, Some(-90));
+        assert_eq!(mac.stats().cca_rssi_max, Some(-40));
+
+        // With the channel clear, the packet is sent in the TX slot
+        timer.set_ms((tx_slot * mac_cfg.base_slot_duration as u64) as u32);
+        radio.expect(&[
+            Transaction::check_receive(true, Ok(false)),
+            Transaction::start_transmit(packet.into(), None),
+        ]);
+        mac.tick().unwrap();
+
+        assert_eq!(mac.tx_status().unwrap(), Some((handle, TxStatus::Sent)));
+
+        radio.done();
+    }
+
+    /// Setup a MAC with a transmitted (CSMA) packet awaiting an ACK
+    fn ack_wait_setup(
+        mac_cfg: &Config<<MockRadio as crate::Radio>::Channel>,
+        radio: &mut MockRadio,
+        timer: &mut MockTimer,
+    ) -> (Mac<MockRadio, MockTimer>, TxHandle, Packet) {
+        let mac_addr = ExtendedAddress(0xabcd);
+        let dest = Address::Extended(mac_cfg.pan_id, ExtendedAddress(0x1122));
+
+        // Initialise MAC
+        radio.expect(&[Transaction::start_receive(None)]);
+        let mut mac = Mac::new(
+            mac_addr.clone(),
+            mac_cfg.clone(),
+            radio.clone(),
+            timer.clone(),
+        )
+        .unwrap();
+
+        // Enqueue packet with ACK
+        let data = [0x11, 0x22, 0x33];
+        let handle = mac.transmit(dest, &data, true).unwrap();
+        let packet = Packet::data(dest, mac.addr(), 0, &data, true).unwrap();
+
+        // Schedule CSMA TX in the current (non-beacon) slot
+        timer.set_ms(150);
+        mac.csma_state = CsmaState::Pending {
+            packet: packet.clone(),
+            tx_slot: mac_cfg.calculate_asn(150, mac.sync_offset),
+            retries: 0,
+            cca: CcaState::Clear,
+        };
+
+        radio.expect(&[
+            Transaction::check_receive(true, Ok(false)),
+            Transaction::start_transmit(packet.clone().into(), None),
+        ]);
+        mac.tick().unwrap();
+
+        // Packet is held pending ACK
+        assert_eq!(
+            mac.ack_wait,
+            AckWait::Pending {
+                seq: 0,
+                tx_time: 150
+            }
+        );
+        assert_eq!(mac.tx_status().unwrap(), None);
+
+        // Complete TX and return to RX
+        timer.set_ms(160);
+        radio.expect(&[
+            Transaction::check_transmit(Ok(true)),
+            Transaction::start_receive(None),
+        ]);
+        mac.tick().unwrap();
+
+        (mac, handle, packet)
+    }
+
+    #[test]
+    fn ack_rx() {
+        let _ =
+            simplelog::SimpleLogger::init(log::LevelFilter::Debug, simplelog::Config::default());
+
+        let mut radio = MockRadio::new(&[]);
+        let mut timer = MockTimer::new();
+        let mac_cfg = Config::default();
+
+        let (mut mac, handle, packet) = ack_wait_setup(&mac_cfg, &mut radio, &mut timer);
+
+        // ACK received within the timeout
+        timer.set_ms(200);
+        radio.expect(&[
+            Transaction::check_receive(true, Ok(true)),
+            Transaction::get_received(Ok((Packet::ack(&packet).into(), BasicInfo::default()))),
+            Transaction::start_receive(None),
+        ]);
+        mac.tick().unwrap();
+
+        assert_eq!(mac.tx_status().unwrap(), Some((handle, TxStatus::Acked)));
+        assert_eq!(mac.ack_wait, AckWait::None);
+        assert!(mac.tx_buff.is_empty());
+
+        // Frames and time in each radio state are counted
+        let frame: std::vec::Vec<u8> = packet.into();
+        let stats = mac.stats();
+        assert_eq!(stats.rx_acks, 1);
+        assert_eq!(stats.radio.tx_frames, 1);
+        assert_eq!(stats.radio.tx_bytes, frame.len() as u64);
+        assert_eq!(stats.radio.rx_frames, 1);
+        assert_eq!((stats.radio.tx_ms, stats.radio.rx_ms), (10, 190));
+
+        radio.done();
+    }
+
+    #[test]
+    fn ack_tx_deferred() {
+        let _ =
+            simplelog::SimpleLogger::init(log::LevelFilter::Debug, simplelog::Config::default());
+
+        let mut radio = MockRadio::new(&[]);
+        let mut timer = MockTimer::new();
+
+        let mac_addr = ExtendedAddress(0xabcd);
+        let mac_cfg = Config {
+            pan_coordinator: true,
+            ack_delay: 5,
+            ..Default::default()
+        };
+        let source = Address::Short(mac_cfg.pan_id, ShortAddress(0x0010));
+
+        // Initialise MAC
+        radio.expect(&[Transaction::start_receive(None)]);
+        let mut mac = Mac::new(
+            mac_addr.clone(),
+            mac_cfg.clone(),
+            radio.clone(),
+            timer.clone(),
+        )
+        .unwrap();
+
+        // Receive a frame requesting an ACK shortly before the beacon
+        let data = Packet::data(mac.addr(), source, 7, &[0x11, 0x22], true).unwrap();
+        let ack = Packet::ack(&data);
+        let beacon_at = mac_cfg.superframe_duration();
+
+        timer.set_ms(beacon_at - 4);
+        radio.expect(&[
+            Transaction::check_receive(true, Ok(true)),
+            Transaction::get_received(Ok((data.clone().into(), BasicInfo::default()))),
+            Transaction::start_receive(None),
+        ]);
+        mac.tick().unwrap();
+
+        let pending = AckState::Pending {
+            packet: ack.clone(),
+            tx_time: beacon_at as u64 + 1,
+        };
+        assert_eq!(mac.ack_state, pending);
+
+        // Retransmissions do not schedule a further ACK
+        timer.set_ms(beacon_at - 2);
+        radio.expect(&[
+            Transaction::check_receive(true, Ok(true)),
+            Transaction::get_received(Ok((data.into(), BasicInfo::default()))),
+            Transaction::start_receive(None),
+        ]);
+        mac.tick().unwrap();
+        assert_eq!(mac.ack_state, pending);
+
+        // ACK is deferred while the beacon is transmitted
+        let beacon_info = Beacon {
+            superframe_spec: mac_cfg.superframe_spec(),
+            guaranteed_time_slot_info: GuaranteedTimeSlotInformation::new(),
+            pending_address: PendingAddress::new(),
+        };
+        let beacon = Packet::beacon(Address::Extended(mac_cfg.pan_id, mac_addr), 0, beacon_info);
+
+        timer.set_ms(beacon_at + 2);
+        radio.expect(&[
+            Transaction::check_receive(true, Ok(false)),
+            Transaction::start_transmit(beacon.into(), None),
+        ]);
+        mac.tick().unwrap();
+        assert_eq!(mac.ack_state, pending);
+
+        // Then sent on completion
+        timer.set_ms(beacon_at + 3);
+        radio.expect(&[
+            Transaction::check_transmit(Ok(true)),
+            Transaction::start_receive(None),
+            Transaction::start_transmit(ack.into(), None),
+        ]);
+        mac.tick().unwrap();
+
+        assert_eq!(mac.ack_state, AckState::None);
+        assert_eq!(mac.stats.tx_acks, 1);
+        assert_eq!(mac.stats.deadline_miss_ack, 0);
+
+        radio.done();
+    }
+
+    #[test]
+    fn ack_timeout_retry() {
+        let _ =
+            simplelog::SimpleLogger::init(log::LevelFilter::Debug, simplelog::Config::default());
+
+        let mut radio = MockRadio::new(&[]);
+        let mut timer = MockTimer::new();
+        let mac_cfg = Config::default();
+
+        let (mut mac, handle, packet) = ack_wait_setup(&mac_cfg, &mut radio, &mut timer);
+
+        // No ACK within the timeout schedules a retry, with CCA prior to the TX slot
+        let now = 150 + mac_cfg.ack_timeout as u32 + 1;
+        timer.set_ms(now);
+        radio.expect(&[
+            Transaction::check_receive(true, Ok(false)),
+            Transaction::poll_rssi(Ok(-90)),
+        ]);
+        mac.tick().unwrap();
+
+        let asn = mac_cfg.calculate_asn(now as u64, mac.sync_offset);
+        let tx_slot = match mac.csma_state {
+            CsmaState::Pending { tx_slot, .. } if tx_slot > asn => tx_slot,
+            _ => panic!("Expected CSMA retry"),
+        };
+        assert_eq!(mac.ack_wait, AckWait::None);
+        assert_eq!(mac.tx_buff.peek().map(|(s, _)| s.retries), Some(1));
+
+        // Late ACK is ignored, with the channel already determined clear
+        radio.expect(&[
+            Transaction::check_receive(true, Ok(true)),
+            Transaction::get_received(Ok((Packet::ack(&packet).into(), BasicInfo::default()))),
+            Transaction::start_receive(None),
+        ]);
+        mac.tick().unwrap();
+        assert_eq!(mac.tx_status().unwrap(), None);
+
+        // Retry sent in the scheduled slot
+        let now = (tx_slot * mac_cfg.base_slot_duration as u64) as u32 + 10;
+        timer.set_ms(now);
+        radio.expect(&[
+            Transaction::check_receive(true, Ok(false)),
+            Transaction::start_transmit(packet.clone().into(), None),
+        ]);
+        mac.tick().unwrap();
+
+        assert_eq!(
+            mac.ack_wait,
+            AckWait::Pending {
+                seq: 0,
+                tx_time: now as u64
+            }
+        );
+
+        // Retry acknowledged
+        timer.set_ms(now + 50);
+        radio.expect(&[
+            Transaction::check_transmit(Ok(true)),
+            Transaction::start_receive(None),
+        ]);
+        mac.tick().unwrap();
+
+        radio.expect(&[
+            Transaction::check_receive(true, Ok(true)),
+            Transaction::get_received(Ok((Packet::ack(&packet).into(), BasicInfo::default()))),
+            Transaction::start_receive(None),
+        ]);
+        mac.tick().unwrap();
+
+        assert_eq!(mac.tx_status().unwrap(), Some((handle, TxStatus::Acked)));
+
+        radio.done();
+    }
+
+    #[test]
+    fn ack_retries_exceeded() {
+        let _ =
+            simplelog::SimpleLogger::init(log::LevelFilter::Debug, simplelog::Config::default());
+
+        let mut radio = MockRadio::new(&[]);
+        let mut timer = MockTimer::new();
+        let mac_cfg = Config::default();
+
+        let (mut mac, handle, _packet) = ack_wait_setup(&mac_cfg, &mut radio, &mut timer);
+
+        // Final attempt
+        for (s, _p) in mac.tx_buff.iter_mut() {
+            s.retries = mac_cfg.max_retries + 1;
+        }
+
+        // No ACK within the timeout fails the packet
+        timer.set_ms(150 + mac_cfg.ack_timeout as u32 + 1);
+        radio.expect(&[Transaction::check_receive(true, Ok(false))]);
+        mac.tick().unwrap();
+
+        assert_eq!(
+            mac.tx_status().unwrap(),
+            Some((handle, TxStatus::Failed(TxFailure::RetriesExceeded)))
+        );
+        assert_eq!(mac.stats().tx_fail, 1);
+        assert_eq!(mac.csma_state, CsmaState::None);
+        assert!(mac.tx_buff.is_empty());
+
+        radio.done();
+    }
+
+    #[test]
+    fn assoc_short_addr_allocate() {
+        let _ =
+            simplelog::SimpleLogger::init(log::LevelFilter::Debug, simplelog::Config::default());
+
+        let mut radio = MockRadio::new(&[]);
+        let mut timer = MockTimer::new();
+
+        let mac_addr = ExtendedAddress(0xabcd);
+        let mac_cfg = Config {
+            pan_coordinator: true,
+            ..Default::default()
+        };
+        let device_addr = Address::Extended(mac_cfg.pan_id, ExtendedAddress(0x1122));
+
+        // Initialise MAC
+        radio.expect(&[Transaction::start_receive(None)]);
+        let mut mac = Mac::new(
+            mac_addr.clone(),
+            mac_cfg.clone(),
+            radio.clone(),
+            timer.clone(),
+        )
+        .unwrap();
+
+        let assoc_req = Command::AssociationRequest(CapabilityInformation {
+            allocate_address: true,
+            frame_protection: false,
+            full_function_device: true,
+            mains_power: false,
+            idle_receive: false,
+        });
+
+        // Receive two association requests from the same device
+        for i in 0..2 {
+            timer.set_ms(100 + i * 10);
+
+            let req = Packet::command(mac.addr(), device_addr, i as u8, assoc_req.clone());
+            radio.expect(&[
+                Transaction::check_receive(true, Ok(true)),
+                Transaction::get_received(Ok((req.into(), BasicInfo::default()))),
+                Transaction::start_receive(None),
+            ]);
+            mac.tick().unwrap();
+        }
+
+        // With the repeated request detected as a duplicate join
+        assert_eq!(mac.stats.duplicate_joins, 1);
+        assert_eq!(
+            mac.neighbour(&device_addr).map(|n| n.relation),
+            Some(Relation::Child)
+        );
+
+        // While other devices heard from are tracked as peers
+        let peer_addr = Address::Extended(mac_cfg.pan_id, ExtendedAddress(0x3344));
+        timer.set_ms(130);
+        let data = Packet::data(mac.addr(), peer_addr, 0, &[0xaa], false).unwrap();
+        radio.expect(&[
+            Transaction::check_receive(true, Ok(true)),
+            Transaction::get_received(Ok((data.into(), BasicInfo::new(-70, 0)))),
+            Transaction::start_receive(None),
+        ]);
+        mac.tick().unwrap();
+
+        let n = mac.neighbour(&peer_addr).unwrap();
+        assert_eq!((n.relation, n.last_rssi), (Relation::Peer, -70));
+        assert!(!n.coordinator);
+        assert_eq!(mac.neighbours().count(), 2);
+
+        // Both responses carry the same allocated short address
+        let mut responses = 0;
+        for (_s, p) in mac.tx_buff.iter() {
+            assert_eq!(p.header.destination, device_addr);
+            assert_eq!(
+                p.content,
+                FrameContent::Command(Command::AssociationResponse(
+                    ShortAddress(0x0001),
+                    AssociationStatus::Successful
+                ))
+            );
+            responses += 1;
+        }
+        assert_eq!(responses, 2);
+
+        let associations: std::vec::Vec<_> = mac.associations().cloned().collect();
+        assert_eq!(
+            associations,
+            &[Association {
+                extended: ExtendedAddress(0x1122),
+                short: ShortAddress(0x0001),
+                rx_on_when_idle: false,
+            }]
+        );
+
+        radio.done();
+    }
+
+    #[test]
+    fn assoc_downstream_routes() {
+        let _ =
+            simplelog::SimpleLogger::init(log::LevelFilter::Debug, simplelog::Config::default());
+
+        let mut radio = MockRadio::new(&[]);
+        let mut timer = MockTimer::new();
+
+        let mac_addr = ExtendedAddress(0xabcd);
+        let mac_cfg = Config {
+            pan_coordinator: true,
+            ..Default::default()
+        };
+        let pan_id = mac_cfg.pan_id;
+
+        // Initialise MAC
+        radio.expect(&[Transaction::start_receive(None)]);
+        let mut mac = Mac::new(
+            mac_addr.clone(),
+            mac_cfg.clone(),
+            radio.clone(),
+            timer.clone(),
+        )
+        .unwrap();
+
+        let assoc_req = Command::AssociationRequest(CapabilityInformation {
+            allocate_address: true,
+            frame_protection: false,
+            full_function_device: true,
+            mains_power: true,
+            idle_receive: true,
+        });
+
+        // A router and a device associate directly
+        for (i, ext) in [0x1122, 0x3344].iter().enumerate() {
+            timer.set_ms(100 + i as u32 * 10);
+
+            let source = Address::Extended(pan_id, ExtendedAddress(*ext));
+            let req = Packet::command(mac.addr(), source, i as u8, assoc_req.clone());
+            radio.expect(&[
+                Transaction::check_receive(true, Ok(true)),
+                Transaction::get_received(Ok((req.into(), BasicInfo::default()))),
+                Transaction::start_receive(None),
+            ]);
+            mac.tick().unwrap();
+        }
+
+        let router = Address::Short(pan_id, ShortAddress(0x0001));
+        let device = Address::Short(pan_id, ShortAddress(0x0002));
+        assert_eq!(
+            mac.routes().next(),
+            Some(&Route {
+                short: ShortAddress(0x0001),
+                extended: ExtendedAddress(0x1122),
+                via: None,
+                updated: 100,
+            })
+        );
+        assert_eq!(mac.routes().count(), 2);
+        assert_eq!(mac.next_hop(&router), None);
+
+        // Devices beneath the router are reached via the router
+        let child = Address::Short(pan_id, ShortAddress(0x0100));
+        mac.add_route(ShortAddress(0x0100), ExtendedAddress(0x5566), router)
+            .unwrap();
+        assert_eq!(mac.next_hop(&child), Some(router));
+        assert_eq!(mac.next_hop(&device), None);
+
+        // Devices re-associating elsewhere supersede the direct association
+        mac.add_route(ShortAddress(0x0101), ExtendedAddress(0x3344), router)
+            .unwrap();
+        assert_eq!(mac.next_hop(&device), None);
+        assert_eq!(
+            mac.next_hop(&Address::Short(pan_id, ShortAddress(0x0101))),
+            Some(router)
+        );
+        assert_eq!(mac.associations().count(), 1);
+
+        // And routed devices associating directly supersede the route
+        timer.set_ms(120);
+        let source = Address::Extended(pan_id, ExtendedAddress(0x5566));
+        let req = Packet::command(mac.addr(), source, 2, assoc_req.clone());
+        radio.expect(&[
+            Transaction::check_receive(true, Ok(true)),
+            Transaction::get_received(Ok((req.into(), BasicInfo::default()))),
+            Transaction::start_receive(None),
+        ]);
+        mac.tick().unwrap();
+
+        assert_eq!(mac.next_hop(&child), None);
+        assert_eq!(mac.next_hop(&source), None);
+        assert_eq!(mac.routes().count(), 3);
+
+        radio.done();
+    }
+
+    #[test]
+    fn assoc_short_addr_capacity() {
+        let _ =
+            simplelog::SimpleLogger::init(log::LevelFilter::Debug, simplelog::Config::default());
+
+        let mut radio = MockRadio::new(&[]);
+        let mut timer = MockTimer::new();
+
+        // Allocate from the last available short address
+        let mac_addr = ExtendedAddress(0xabcd);
+        let mac_cfg = Config {
+            pan_coordinator: true,
+            short_addr_base: ShortAddress(0xfffd),
+            ..Default::default()
+        };
+
+        // Initialise MAC
+        radio.expect(&[Transaction::start_receive(None)]);
+        let mut mac = Mac::new(
+            mac_addr.clone(),
+            mac_cfg.clone(),
+            radio.clone(),
+            timer.clone(),
+        )
+        .unwrap();
+
+        let assoc_req = Command::AssociationRequest(CapabilityInformation {
+            allocate_address: true,
+            frame_protection: false,
+            full_function_device: true,
+            mains_power: false,
+            idle_receive: false,
+        });
+
+        // Receive association requests from two devices
+        for i in 0..2 {
+            timer.set_ms(100 + i * 10);
+
+            let device_addr = Address::Extended(mac_cfg.pan_id, ExtendedAddress(0x1122 + i as u64));
+            let req = Packet::command(mac.addr(), device_addr, i as u8, assoc_req.clone());
+            radio.expect(&[
+                Transaction::check_receive(true, Ok(true)),
+                Transaction::get_received(Ok((req.into(), BasicInfo::default()))),
+                Transaction::start_receive(None),
+            ]);
+            mac.tick().unwrap();
+        }
+
+        // The first is assigned the base address, with the second rejected
+        let responses: std::vec::Vec<_> = mac.tx_buff.iter().map(|(_s, p)| &p.content).collect();
+        assert_eq!(
+            responses,
+            &[
+                &FrameContent::Command(Command::AssociationResponse(
+                    ShortAddress(0xfffd),
+                    AssociationStatus::Successful
+                )),
+                &FrameContent::Command(Command::AssociationResponse(
+                    ShortAddress::broadcast(),
+                    AssociationStatus::NetworkAtCapacity
+                )),
+            ]
+        );
+        assert_eq!(mac.associations().count(), 1);
+
+        radio.done();
+    }
+
+    #[test]
+    fn assoc_short_addr_apply() {
+        let _ =
+            simplelog::SimpleLogger::init(log::LevelFilter::Debug, simplelog::Config::default());
+
+        let mut radio = MockRadio::new(&[]);
+        let mut timer = MockTimer::new();
+
+        let mac_addr = ExtendedAddress(0xabcd);
+        let mac_cfg = Config::default();
+        let coord_addr = Address::Extended(mac_cfg.pan_id, ExtendedAddress(0x1122));
+
+        // Initialise MAC
+        radio.expect(&[Transaction::start_receive(None)]);
+        let mut mac = Mac::new(
+            mac_addr.clone(),
+            mac_cfg.clone(),
+            radio.clone(),
+            timer.clone(),
+        )
+        .unwrap();
+
+        // Synced with pending association
+        mac.sync_state = SyncState::Synced(coord_addr);
+        mac.assoc_state = AssocState::Pending(coord_addr, 10_000);
+
+        // Receive association response with a short address
+        timer.set_ms(100);
+        let resp = Packet::command(
+            mac.addr(),
+            coord_addr,
+            0,
+            Command::AssociationResponse(ShortAddress(0x0005), AssociationStatus::Successful),
+        );
+        radio.expect(&[
+            Transaction::check_receive(true, Ok(true)),
+            Transaction::get_received(Ok((resp.into(), BasicInfo::default()))),
+            Transaction::start_receive(None),
+        ]);
+        mac.tick().unwrap();
+
+        assert_eq!(mac.assoc_state, AssocState::Associated(mac_cfg.pan_id));
+        assert_eq!(mac.short_addr, Some(ShortAddress(0x0005)));
+        assert_eq!(
+            mac.addr(),
+            Address::Short(mac_cfg.pan_id, ShortAddress(0x0005))
+        );
+
+        // Frames to the assigned short address are now accepted
+        timer.set_ms(120);
+        let data = Packet::data(mac.addr(), coord_addr, 1, &[0xaa, 0xbb], false).unwrap();
+        radio.expect(&[
+            Transaction::check_receive(true, Ok(true)),
+            Transaction::get_received(Ok((data.into(), BasicInfo::default()))),
+            Transaction::start_receive(None),
+        ]);
+        mac.tick().unwrap();
+
+        let mut buff = [0u8; 16];
+        let (n, info) = mac.receive(&mut buff).unwrap().unwrap();
+        assert_eq!(&buff[..n], &[0xaa, 0xbb]);
+        assert_eq!(info.source, coord_addr);
+
+        radio.done();
+    }
+
+    #[test]
+    fn join_state() {
+        let _ =
+            simplelog::SimpleLogger::init(log::LevelFilter::Debug, simplelog::Config::default());
+
+        let mut radio = MockRadio::new(&[]);
+        let mut timer = MockTimer::new();
+
+        let mac_addr = ExtendedAddress(0xabcd);
+        let mac_cfg = Config::default();
+        let coord_addr = Address::Extended(mac_cfg.pan_id, ExtendedAddress(0x1122));
+
+        // Initialise MAC
+        radio.expect(&[Transaction::start_receive(None)]);
+        let mut mac = Mac::new(
+            mac_addr.clone(),
+            mac_cfg.clone(),
+            radio.clone(),
+            timer.clone(),
+        )
+        .unwrap();
+
+        assert_eq!(mac.state(), Ok(MacState::Disconnected));
+        assert_eq!(mac.can_transmit(), Ok(true));
+
+        // Sync on receipt of a beacon
+        timer.set_ms(100);
+        let beacon = Packet::beacon(
+            coord_addr,
+            0,
+            Beacon {
+                superframe_spec: mac_cfg.superframe_spec(),
+                guaranteed_time_slot_info: GuaranteedTimeSlotInformation::new(),
+                pending_address: PendingAddress::new(),
+            },
+        );
+        radio.expect(&[
+            Transaction::check_receive(true, Ok(true)),
+            Transaction::get_received(Ok((beacon.into(), BasicInfo::default()))),
+            Transaction::start_receive(None),
+        ]);
+        mac.tick().unwrap();
+
+        assert_eq!(mac.state(), Ok(MacState::Synced(coord_addr)));
+
+        // Then request association with the coordinator
+        timer.set_ms(120);
+        radio.expect(&[Transaction::check_receive(true, Ok(false))]);
+        mac.tick().unwrap();
+
+        assert!(matches!(mac.assoc_state, AssocState::Pending(a, _) if a == coord_addr));
+        let queued = mac.tx_buff.peek().map(|(_, p)| p.content.clone());
+        assert!(matches!(
+            queued,
+            Some(FrameContent::Command(Command::AssociationRequest(_)))
+        ));
+        assert_eq!(mac.state(), Ok(MacState::Synced(coord_addr)));
+
+        // Associating on receipt of the response
+        timer.set_ms(150);
+        let resp = Packet::command(
+            mac.addr(),
+            coord_addr,
+            0,
+            Command::AssociationResponse(ShortAddress(0x0005), AssociationStatus::Successful),
+        );
+        radio.expect(&[
+            Transaction::check_receive(true, Ok(true)),
+            Transaction::get_received(Ok((resp.into(), BasicInfo::default()))),
+            Transaction::start_receive(None),
+        ]);
+        mac.tick().unwrap();
+
+        assert_eq!(mac.state(), Ok(MacState::Associated(coord_addr)));
+
+        radio.done();
+
+        // PAN coordinators are associated with themselves once started
+        let coord_cfg = Config {
+            pan_coordinator: true,
+            ..Default::default()
+        };
+        radio.expect(&[Transaction::start_receive(None)]);
+        let coord = Mac::new(
+            ExtendedAddress(0x1122),
+            coord_cfg,
+            radio.clone(),
+            timer.clone(),
+        )
+        .unwrap();
+
+        assert_eq!(coord.state(), Ok(MacState::Associated(coord.addr())));
+
+        radio.done();
+    }
+
+    #[test]
+    fn join_tx_buffer_full() {
+        let _ =
+            simplelog::SimpleLogger::init(log::LevelFilter::Debug, simplelog::Config::default());
+
+        let mut radio = MockRadio::new(&[]);
+        let mut timer = MockTimer::new();
+
+        let mac_addr = ExtendedAddress(0xabcd);
+        let mac_cfg = Config::default();
+        let coord_addr = Address::Extended(mac_cfg.pan_id, ExtendedAddress(0x1122));
+
+        // Initialise MAC
+        radio.expect(&[Transaction::start_receive(None)]);
+        let mut mac = Mac::new(
+            mac_addr.clone(),
+            mac_cfg.clone(),
+            radio.clone(),
+            timer.clone(),
+        )
+        .unwrap();
+
+        // Fill the control queue
+        for i in 0..txq::DEFAULT_MAX_CONTROL {
+            let req = Packet::command(coord_addr, mac.addr(), i as u8, Command::DataRequest);
+            mac.enqueue_tx(req).unwrap();
+        }
+        assert_eq!(mac.tx_capacity(TxPriority::Control), 0);
+
+        // Sync on receipt of a beacon, with the association request deferred
+        // rather than lost
+        timer.set_ms(100);
+        let beacon = Packet::beacon(
+            coord_addr,
+            0,
+            Beacon {
+                superframe_spec: mac_cfg.superframe_spec(),
+                guaranteed_time_slot_info: GuaranteedTimeSlotInformation::new(),
+                pending_address: PendingAddress::new(),
+            },
+        );
+        radio.expect(&[
+            Transaction::check_receive(true, Ok(true)),
+            Transaction::get_received(Ok((beacon.into(), BasicInfo::default()))),
+            Transaction::start_receive(None),
+        ]);
+        mac.tick().unwrap();
+
+        assert_eq!(mac.state(), Ok(MacState::Synced(coord_addr)));
+        assert_eq!(mac.assoc_state, AssocState::Unassociated);
+        assert!(mac.tx_buff.iter().all(|(_, p)| !matches!(
+            p.content,
+            FrameContent::Command(Command::AssociationRequest(_))
+        )));
+
+        // Then issued on the next tick once the queue drains
+        while mac.tx_buff.dequeue().is_some() {}
+        mac.csma_state = CsmaState::None;
+
+        timer.set_ms(130);
+        radio.expect(&[Transaction::check_receive(true, Ok(false))]);
+        mac.tick().unwrap();
+
+        assert!(matches!(mac.assoc_state, AssocState::Pending(a, _) if a == coord_addr));
+        let queued = mac.tx_buff.peek().map(|(_, p)| p.content.clone());
+        assert!(matches!(
+            queued,
+            Some(FrameContent::Command(Command::AssociationRequest(_)))
+        ));
+
+        // And the association completes on receipt of the response
+        timer.set_ms(150);
+        let resp = Packet::command(
+            mac.addr(),
+            coord_addr,
+            0,
+            Command::AssociationResponse(ShortAddress(0x0005), AssociationStatus::Successful),
+        );
+        radio.expect(&[
+            Transaction::check_receive(true, Ok(true)),
+            Transaction::get_received(Ok((resp.into(), BasicInfo::default()))),
+            Transaction::start_receive(None),
+        ]);
+        mac.tick().unwrap();
+
+        assert_eq!(mac.state(), Ok(MacState::Associated(coord_addr)));
+
+        radio.done();
+    }
+
+    #[test]
+    fn indirect_tx_data_request() {
+        let _ =
+            simplelog::SimpleLogger::init(log::LevelFilter::Debug, simplelog::Config::default());
+
+        let mut radio = MockRadio::new(&[]);
+        let mut timer = MockTimer::new();
+
+        let mac_addr = ExtendedAddress(0xabcd);
+        let mac_cfg = Config {
+            pan_coordinator: true,
+            ..Default::default()
+        };
+
+        // Initialise MAC
+        radio.expect(&[Transaction::start_receive(None)]);
+        let mut mac = Mac::new(
+            mac_addr.clone(),
+            mac_cfg.clone(),
+            radio.clone(),
+            timer.clone(),
+        )
+        .unwrap();
+
+        // Associated device without receive-on-idle
+        let short = mac
+            .assoc_table
+            .allocate(ExtendedAddress(0x1122), false)
+            .unwrap();
+        let device_addr = Address::Short(mac_cfg.pan_id, short);
+
+        // Packets for the device are held rather than sent
+        let data = [0x11, 0x22, 0x33];
+        let _handle = mac.transmit(device_addr, &data, true).unwrap();
+
+        assert!(mac.tx_buff.is_empty());
+        assert_eq!(mac.indirect.len(), 1);
+        assert!(indirect::is_pending(
+            &mac.indirect.pending_address(),
+            Some(short),
+            ExtendedAddress(0x1122)
+        ));
+
+        // Data request from the device releases the packet
+        timer.set_ms(100);
+        let req = Packet::command(mac.addr(), device_addr, 0, Command::DataRequest);
+        radio.expect(&[
+            Transaction::check_receive(true, Ok(true)),
+            Transaction::get_received(Ok((req.into(), BasicInfo::default()))),
+            Transaction::start_receive(None),
+        ]);
+        mac.tick().unwrap();
+
+        assert_eq!(mac.indirect.len(), 0);
+        let (_s, p) = mac.tx_buff.peek().unwrap();
+        assert_eq!(p.header.destination, device_addr);
+        assert_eq!(p.payload(), &data);
+
+        radio.done();
+    }
+
+    #[test]
+    fn indirect_tx_expiry() {
+        let _ =
+            simplelog::SimpleLogger::init(log::LevelFilter::Debug, simplelog::Config::default());
+
+        let mut radio = MockRadio::new(&[]);
+        let mut timer = MockTimer::new();
+
+        let mac_addr = ExtendedAddress(0xabcd);
+        let mac_cfg = Config {
+            pan_coordinator: true,
+            ..Default::default()
+        };
+
+        // Initialise MAC
+        radio.expect(&[Transaction::start_receive(None)]);
+        let mut mac = Mac::new(
+            mac_addr.clone(),
+            mac_cfg.clone(),
+            radio.clone(),
+            timer.clone(),
+        )
+        .unwrap();
+
+        let device_ext = ExtendedAddress(0x1122);
+        mac.assoc_table.allocate(device_ext, false).unwrap();
+
+        let handle = mac
+            .transmit(
+                Address::Extended(mac_cfg.pan_id, device_ext),
+                &[0xaa],
+                false,
+            )
+            .unwrap();
+        assert_eq!(mac.indirect.len(), 1);
+
+        // Not requested within the persistence time
+        timer.set_ms(mac_cfg.transaction_persistence_ms as u32 + 150);
+        radio.expect(&[Transaction::check_receive(true, Ok(false))]);
+        mac.tick().unwrap();
+
+        assert_eq!(mac.indirect.len(), 0);
+        assert_eq!(mac.stats().indirect_expired, 1);
+        assert_eq!(
+            mac.tx_status().unwrap(),
+            Some((handle, TxStatus::Failed(TxFailure::Expired)))
+        );
+
+        radio.done();
+    }
+
+    #[test]
+    fn indirect_tx_frame_pending() {
+        let _ =
+            simplelog::SimpleLogger::init(log::LevelFilter::Debug, simplelog::Config::default());
+
+        let mut radio = MockRadio::new(&[]);
+        let mut timer = MockTimer::new();
+
+        let mac_addr = ExtendedAddress(0xabcd);
+        let mac_cfg = Config {
+            pan_coordinator: true,
+            ..Default::default()
+        };
+        let device_addr = Address::Short(mac_cfg.pan_id, ShortAddress(0x0005));
+
+        // Initialise MAC
+        radio.expect(&[Transaction::start_receive(None)]);
+        let mut mac = Mac::new(
+            mac_addr.clone(),
+            mac_cfg.clone(),
+            radio.clone(),
+            timer.clone(),
+        )
+        .unwrap();
+
+        // Packets may be held for devices irrespective of association
+        let data = [0x11, 0x22, 0x33];
+        let _handle = mac.transmit_indirect(device_addr, &data, true).unwrap();
+
+        assert!(mac.tx_buff.is_empty());
+        assert_eq!(mac.indirect.len(), 1);
+
+        // ACKs to data requests signal pending frames
+        for (seq, pending) in [(0, true), (1, false)].iter() {
+            timer.set_ms(100 + *seq as u32 * 10);
+            let req = Packet::command(mac.addr(), device_addr, *seq, Command::DataRequest);
+            radio.expect(&[
+                Transaction::check_receive(true, Ok(true)),
+                Transaction::get_received(Ok((req.clone().into(), BasicInfo::default()))),
+                Transaction::start_receive(None),
+            ]);
+            mac.tick().unwrap();
+
+            let mut ack = Packet::ack(&req);
+            ack.header.frame_pending = *pending;
+            let ack_state = AckState::Pending {
+                packet: ack,
+                tx_time: timer.ticks_ms() + mac_cfg.ack_delay,
+            };
+            assert_eq!(mac.ack_state, ack_state);
+        }
+
+        // With the held packet released on request
+        assert_eq!(mac.indirect.len(), 0);
+        let (_s, p) = mac.tx_buff.peek().unwrap();
+        assert_eq!(p.header.destination, device_addr);
+        assert_eq!(p.payload(), &data);
+
+        radio.done();
+    }
+
+    #[test]
+    fn tx_priority_control() {
+        let _ =
+            simplelog::SimpleLogger::init(log::LevelFilter::Debug, simplelog::Config::default());
+
+        let mut radio = MockRadio::new(&[]);
+        let mut timer = MockTimer::new();
+
+        let mac_addr = ExtendedAddress(0xabcd);
+        let mac_cfg = Config {
+            pan_coordinator: true,
+            ..Default::default()
+        };
+        let device_addr = Address::Extended(mac_cfg.pan_id, ExtendedAddress(0x1122));
+        let bulk_addr = Address::Extended(mac_cfg.pan_id, ExtendedAddress(0x3344));
+
+        // Initialise MAC
+        radio.expect(&[Transaction::start_receive(None)]);
+        let mut mac = Mac::new(
+            mac_addr.clone(),
+            mac_cfg.clone(),
+            radio.clone(),
+            timer.clone(),
+        )
+        .unwrap();
+
+        // Queue bulk data frames
+        for i in 0..10 {
+            mac.transmit(bulk_addr, &[i], false).unwrap();
+        }
+        assert_eq!(
+            mac.tx_capacity(TxPriority::Data),
+            txq::DEFAULT_MAX_DATA - 10
+        );
+
+        // Followed by an association request, queueing a response
+        let assoc_req = Command::AssociationRequest(CapabilityInformation {
+            allocate_address: true,
+            frame_protection: false,
+            full_function_device: true,
+            mains_power: false,
+            idle_receive: true,
+        });
+        let req = Packet::command(mac.addr(), device_addr, 0, assoc_req);
+
+        timer.set_ms(100);
+        radio.expect(&[
+            Transaction::check_receive(true, Ok(true)),
+            Transaction::get_received(Ok((req.into(), BasicInfo::default()))),
+            Transaction::start_receive(None),
+        ]);
+        mac.tick().unwrap();
+
+        assert_eq!(
+            mac.tx_capacity(TxPriority::Control),
+            txq::DEFAULT_MAX_CONTROL - 1
+        );
+        assert!(mac.busy().unwrap());
+
+        // The association response is the next frame scheduled for TX
+        let now = mac_cfg.base_superframe_duration as u64;
+        let asn = mac_cfg.calculate_asn(now, mac.sync_offset);
+        mac.tick_cap(now, asn).unwrap();
+
+        let response = FrameContent::Command(Command::AssociationResponse(
+            ShortAddress(0x0001),
+            AssociationStatus::Successful,
+        ));
+        match &mac.csma_state {
+            CsmaState::Pending { packet, .. } => {
+                assert_eq!(packet.header.destination, device_addr);
+                assert_eq!(packet.content, response);
+            }
+            s => panic!("Unexpected CSMA state: {:?}", s),
+        }
+
+        // With data frames following in order
+        assert_eq!(
+            mac.tx_buff.dequeue().map(|(_s, p)| p.content),
+            Some(response)
+        );
+        for i in 0..10 {
+            let (_s, p) = mac.tx_buff.dequeue().unwrap();
+            assert_eq!(p.header.destination, bulk_addr);
+            assert_eq!(p.payload(), &[i]);
+        }
+
+        radio.done();
+    }
+
+    #[test]
+    fn tx_deadline_expiry() {
+        let _ =
+            simplelog::SimpleLogger::init(log::LevelFilter::Debug, simplelog::Config::default());
+
+        let mut radio = MockRadio::new(&[]);
+        let mut timer = MockTimer::new();
+
+        let mac_addr = ExtendedAddress(0xabcd);
+        let mac_cfg = Config {
+            pan_coordinator: true,
+            ..Default::default()
+        };
+        let dest = Address::Extended(mac_cfg.pan_id, ExtendedAddress(0x1122));
+
+        // Initialise MAC
+        radio.expect(&[Transaction::start_receive(None)]);
+        let mut mac = Mac::new(
+            mac_addr.clone(),
+            mac_cfg.clone(),
+            radio.clone(),
+            timer.clone(),
+        )
+        .unwrap();
+
+        timer.set_ms(100);
+        let expired = mac
+            .transmit_with_deadline(dest, &[0xaa], false, 50)
+            .unwrap();
+        let pending = mac
+            .transmit_with_deadline(dest, &[0xbb], false, 500)
+            .unwrap();
+        mac.transmit(dest, &[0xcc], false).unwrap();
+
+        // Frames past their deadline are dropped rather than sent
+        timer.set_ms(150);
+        radio.expect(&[Transaction::check_receive(true, Ok(false))]);
+        mac.tick().unwrap();
+
+        assert_eq!(
+            mac.tx_status().unwrap(),
+            Some((expired, TxStatus::Failed(TxFailure::Expired)))
+        );
+        assert_eq!(mac.stats().tx_expired, 1);
+        assert_eq!(mac.tx_buff.len(), 2);
+        assert_eq!(mac.tx_buff.peek().map(|(s, _p)| s.handle), Some(pending));
+
+        radio.done();
+    }
+
+    #[test]
+    fn indirect_rx_data_request() {
+        let _ =
+            simplelog::SimpleLogger::init(log::LevelFilter::Debug, simplelog::Config::default());
+
+        let mut radio = MockRadio::new(&[]);
+        let mut timer = MockTimer::new();
+
+        let mac_addr = ExtendedAddress(0xabcd);
+        let mac_cfg = Config {
+            rx_on_when_idle: false,
+            ..Default::default()
+        };
+        let coord_addr = Address::Extended(mac_cfg.pan_id, ExtendedAddress(0x1122));
+
+        // Initialise MAC
+        radio.expect(&[Transaction::start_receive(None)]);
+        let mut mac = Mac::new(
+            mac_addr.clone(),
+            mac_cfg.clone(),
+            radio.clone(),
+            timer.clone(),
+        )
+        .unwrap();
+
+        // Synced and associated with a short address
+        mac.sync_state = SyncState::Synced(coord_addr);
+        mac.assoc_state = AssocState::Associated(mac_cfg.pan_id);
+        mac.short_addr = Some(ShortAddress(0x0005));
+
+        // Receive beacon listing our address as pending
+        timer.set_ms(100);
+        let pending = [Address::Short(mac_cfg.pan_id, ShortAddress(0x0005))];
+        let beacon_info = Beacon {
+            superframe_spec: mac_cfg.superframe_spec(),
+            guaranteed_time_slot_info: GuaranteedTimeSlotInformation::new(),
+            pending_address: indirect::pending_address(pending.iter()),
+        };
+        let beacon = Packet::beacon(coord_addr, 0, beacon_info);
+
+        radio.expect(&[
+            Transaction::check_receive(true, Ok(true)),
+            Transaction::get_received(Ok((beacon.into(), BasicInfo::default()))),
+            Transaction::start_receive(None),
+        ]);
+        mac.tick().unwrap();
+
+        // Data request issued to our parent
+        let (_s, p) = mac.tx_buff.peek().unwrap();
+        assert_eq!(p.header.destination, coord_addr);
+        assert_eq!(p.content, FrameContent::Command(Command::DataRequest));
+        assert!(mac.data_request.is_some());
+
+        // Pending data received from our parent
+        timer.set_ms(120);
+        let data = Packet::data(mac.addr(), coord_addr, 1, &[0xaa], false).unwrap();
+        radio.expect(&[
+            Transaction::check_receive(true, Ok(true)),
+            Transaction::get_received(Ok((data.into(), BasicInfo::default()))),
+            Transaction::start_receive(None),
+        ]);
+        mac.tick().unwrap();
+
+        assert_eq!(mac.data_request, None);
+
+        radio.done();
+    }
+
+    #[test]
+    fn poll_parent() {
+        let _ =
+            simplelog::SimpleLogger::init(log::LevelFilter::Debug, simplelog::Config::default());
+
+        let mut radio = MockRadio::new(&[]);
+        let mut timer = MockTimer::new();
+
+        let mac_addr = ExtendedAddress(0xabcd);
+        let mac_cfg = Config {
+            rx_on_when_idle: false,
+            ..Default::default()
+        };
+        let coord_addr = Address::Extended(mac_cfg.pan_id, ExtendedAddress(0x1122));
+
+        // Initialise MAC
+        radio.expect(&[Transaction::start_receive(None)]);
+        let mut mac = Mac::new(
+            mac_addr.clone(),
+            mac_cfg.clone(),
+            radio.clone(),
+            timer.clone(),
+        )
+        .unwrap();
+
+        // Polling requires a parent
+        assert_eq!(mac.poll_parent(), Err(CoreError::NotAssociated));
+
+        mac.sync_state = SyncState::Synced(coord_addr);
+        mac.assoc_state = AssocState::Associated(mac_cfg.pan_id);
+        mac.short_addr = Some(ShortAddress(0x0005));
+
+        for (t, pending) in [(100, true), (130, false)].iter() {
+            // Data request issued to our parent
+            timer.set_ms(*t);
+            mac.poll_parent().unwrap();
+            assert_eq!(mac.poll_parent(), Err(CoreError::Busy));
+
+            let (_s, req) = mac.tx_buff.peek().cloned().unwrap();
+            assert_eq!(req.header.destination, coord_addr);
+            assert_eq!(req.content, FrameContent::Command(Command::DataRequest));
+
+            // Request sent and acknowledged by the parent
+            mac.ack_wait = AckWait::Pending {
+                seq: req.header.seq,
+                tx_time: *t as u64,
+            };
+
+            timer.set_ms(t + 10);
+            let mut ack = Packet::ack(&req);
+            ack.header.frame_pending = *pending;
+            radio.expect(&[
+                Transaction::check_receive(true, Ok(true)),
+                Transaction::get_received(Ok((ack.into(), BasicInfo::default()))),
+                Transaction::start_receive(None),
+            ]);
+            mac.tick().unwrap();
+
+            // Receiver remains enabled only where frames are pending
+            assert_eq!(mac.data_request.is_some(), *pending);
+            if !*pending {
+                break;
+            }
+
+            // Pending data received from our parent
+            timer.set_ms(t + 20);
+            let data = Packet::data(mac.addr(), coord_addr, 1, &[0xaa], false).unwrap();
+            radio.expect(&[
+                Transaction::check_receive(true, Ok(true)),
+                Transaction::get_received(Ok((data.into(), BasicInfo::default()))),
+                Transaction::start_receive(None),
+            ]);
+            mac.tick().unwrap();
+
+            assert_eq!(mac.data_request, None);
+            let mut buff = [0u8; 16];
+            assert!(MacIf::receive(&mut mac, &mut buff).unwrap().is_some());
+        }
+
+        radio.done();
+    }
+
+    #[test]
+    fn gts_request_allocate() {
+        let _ =
+            simplelog::SimpleLogger::init(log::LevelFilter::Debug, simplelog::Config::default());
+
+        let mut radio = MockRadio::new(&[]);
+        let mut timer = MockTimer::new();
+
+        let mac_addr = ExtendedAddress(0xabcd);
+        let mac_cfg = Config {
+            pan_coordinator: true,
+            final_cap_slot: 5,
+            ..Default::default()
+        };
+        let device_addr = Address::Short(mac_cfg.pan_id, ShortAddress(0x0005));
+
+        // Initialise MAC
+        radio.expect(&[Transaction::start_receive(None)]);
+        let mut mac = Mac::new(
+            mac_addr.clone(),
+            mac_cfg.clone(),
+            radio.clone(),
+            timer.clone(),
+        )
+        .unwrap();
+
+        // Receive GTS request
+        timer.set_ms(100);
+        let req = Packet::command(
+            mac.addr(),
+            device_addr,
+            0,
+            Command::GuaranteedTimeSlotRequest(GuaranteedTimeSlotCharacteristics {
+                count: 2,
+                receive_only: false,
+                allocation: true,
+            }),
+        );
+        radio.expect(&[
+            Transaction::check_receive(true, Ok(true)),
+            Transaction::get_received(Ok((req.into(), BasicInfo::default()))),
+            Transaction::start_receive(None),
+        ]);
+        mac.tick().unwrap();
 
-    // Renormalize correction in case of missed slots
-    if delta > frame / 2 {
-        delta -= frame;
-    } else if delta < -frame / 2 {
-        delta += frame;
-    }
+        // Slots allocated from the start of the CFP and advertised in beacons
+        let slot = GtsSlot {
+            short: ShortAddress(0x0005),
+            start: 6,
+            length: 2,
+        };
+        assert_eq!(mac.gts_table.lookup(&ShortAddress(0x0005)), Some(&slot));
+        assert_eq!(
+            gts::gts_slot(&mac.gts_table.info(true), ShortAddress(0x0005)),
+            Some(slot)
+        );
 
-    return delta;
-}
+        // Allocations are held until the device is inactive
+        assert_eq!(mac.gts_table.oldest_activity(), Some(100));
+        assert!(mac.next_deadline().unwrap() <= 100 + mac_cfg.gts_inactivity_timeout);
+
+        // Receive-only slots are not allocated
+        timer.set_ms(110);
+        let req = Packet::command(
+            mac.addr(),
+            Address::Short(mac_cfg.pan_id, ShortAddress(0x0006)),
+            0,
+            Command::GuaranteedTimeSlotRequest(GuaranteedTimeSlotCharacteristics {
+                count: 1,
+                receive_only: true,
+                allocation: true,
+            }),
+        );
+        radio.expect(&[
+            Transaction::check_receive(true, Ok(true)),
+            Transaction::get_received(Ok((req.into(), BasicInfo::default()))),
+            Transaction::start_receive(None),
+        ]);
+        mac.tick().unwrap();
 
-#[cfg(test)]
-mod test {
-    use ieee802154::mac::*;
-    use radio::{mock::*, BasicInfo};
+        assert!(mac.gts_table.lookup(&ShortAddress(0x0006)).is_none());
 
-    use super::*;
-    use crate::timer::mock::MockTimer;
+        radio.done();
+    }
 
     #[test]
-    fn beacon_tx() {
-        let _ =
-            simplelog::SimpleLogger::init(log::LevelFilter::Trace, simplelog::Config::default());
-
+    fn gts_allocate() {
         let mut radio = MockRadio::new(&[]);
-        let mut timer = MockTimer::new();
-        let mut seq = 0;
+        let timer = MockTimer::new();
 
         let mac_addr = ExtendedAddress(0xabcd);
         let mac_cfg = Config {
             pan_coordinator: true,
+            final_cap_slot: 5,
             ..Default::default()
         };
+        let device_ext = ExtendedAddress(0x1122);
+        let device_addr = Address::Extended(mac_cfg.pan_id, device_ext);
 
         // Initialise MAC
         radio.expect(&[Transaction::start_receive(None)]);
@@ -920,64 +7231,114 @@ mod test {
         )
         .unwrap();
 
-        // Chilling in rx mode
-        radio.expect(&[Transaction::check_receive(true, Ok(false))]);
-        mac.tick().unwrap();
+        // Devices must be associated
+        assert_eq!(
+            mac.allocate_gts(device_addr, 2, GtsDirection::Transmit),
+            Err(CoreError::NotAssociated)
+        );
+        let short = mac.assoc_table.allocate(device_ext, true).unwrap();
 
-        for n in 0..2 {
-            // Advance in time
-            for i in 0..mac_cfg.superframe_duration() / 100 - 1 {
-                timer.set_ms(n * mac_cfg.superframe_duration() + i * 100);
+        // Receive slots are not supported
+        assert_eq!(
+            mac.allocate_gts(device_addr, 2, GtsDirection::Receive),
+            Err(CoreError::Unsupported)
+        );
 
-                radio.expect(&[Transaction::check_receive(true, Ok(false))]);
+        // Slots are allocated from the start of the CFP and advertised in beacons
+        let slot = GtsSlot {
+            short,
+            start: 6,
+            length: 2,
+        };
+        assert_eq!(
+            mac.allocate_gts(device_addr, 2, GtsDirection::Transmit),
+            Ok(slot)
+        );
+        assert_eq!(gts::gts_slot(&mac.gts_table.info(true), short), Some(slot));
 
-                mac.tick().unwrap();
-            }
+        // Until the CFP is full
+        assert_eq!(
+            mac.allocate_gts(
+                Address::Short(mac_cfg.pan_id, ShortAddress(0x0010)),
+                15,
+                GtsDirection::Transmit
+            ),
+            Err(CoreError::BufferFull)
+        );
 
-            // Beacon at beacon interval
-            timer.set_ms((n + 1) * mac_cfg.superframe_duration());
+        // Allocations may be released
+        assert_eq!(mac.deallocate_gts(&device_addr), Some(slot));
+        assert!(mac.gts_table.lookup(&short).is_none());
 
-            let beacon_info = Beacon {
-                superframe_spec: mac_cfg.superframe_spec(),
-                // TODO: replace placeholders with actual configuration
-                guaranteed_time_slot_info: GuaranteedTimeSlotInformation::new(),
-                pending_address: PendingAddress::new(),
-            };
-            let beacon = Packet::beacon(
-                Address::Extended(mac_cfg.pan_id, mac_addr),
-                seq,
-                beacon_info,
-            );
-            seq += 1;
+        radio.done();
+    }
 
-            // Start beacon TX
-            radio.expect(&[
-                Transaction::check_receive(true, Ok(false)),
-                Transaction::start_transmit(beacon.into(), None),
-            ]);
-            mac.tick().unwrap();
+    #[test]
+    fn gts_request() {
+        let mut radio = MockRadio::new(&[]);
+        let timer = MockTimer::new();
 
-            // Complete beacon TX
-            radio.expect(&[
-                Transaction::check_transmit(Ok(true)),
-                Transaction::start_receive(None),
-            ]);
-            mac.tick().unwrap();
-        }
+        let mac_addr = ExtendedAddress(0xabcd);
+        let mac_cfg = Config::default();
+        let coord_addr = Address::Extended(mac_cfg.pan_id, ExtendedAddress(0x1122));
+
+        // Initialise MAC
+        radio.expect(&[Transaction::start_receive(None)]);
+        let mut mac = Mac::new(
+            mac_addr.clone(),
+            mac_cfg.clone(),
+            radio.clone(),
+            timer.clone(),
+        )
+        .unwrap();
+
+        // Requests require association
+        assert_eq!(
+            mac.request_gts(2, GtsDirection::Transmit),
+            Err(CoreError::NotAssociated)
+        );
+
+        mac.sync_state = SyncState::Synced(coord_addr);
+        mac.assoc_state = AssocState::Associated(mac_cfg.pan_id);
+        mac.short_addr = Some(ShortAddress(0x0005));
+
+        // Request sent to the parent, pending a grant
+        mac.request_gts(2, GtsDirection::Transmit).unwrap();
+
+        let (_s, p) = mac.tx_buff.peek().unwrap();
+        assert_eq!(p.header.destination, coord_addr);
+        assert_eq!(
+            p.content,
+            FrameContent::Command(Command::GuaranteedTimeSlotRequest(
+                GuaranteedTimeSlotCharacteristics {
+                    count: 2,
+                    receive_only: false,
+                    allocation: true,
+                }
+            ))
+        );
+        assert_eq!(mac.gts_state, GtsState::Pending(mac_cfg.gts_timeout));
+
+        // With further requests rejected while pending
+        assert_eq!(
+            mac.request_gts(1, GtsDirection::Transmit),
+            Err(CoreError::Busy)
+        );
+
+        radio.done();
     }
 
     #[test]
-    fn beacon_rx_sync() {
+    fn gts_tx_in_slot() {
         let _ =
             simplelog::SimpleLogger::init(log::LevelFilter::Debug, simplelog::Config::default());
 
         let mut radio = MockRadio::new(&[]);
         let mut timer = MockTimer::new();
-        let seq = 0;
 
         let mac_addr = ExtendedAddress(0xabcd);
         let mac_cfg = Config {
-            pan_coordinator: false,
+            final_cap_slot: 5,
             ..Default::default()
         };
         let coord_addr = Address::Extended(mac_cfg.pan_id, ExtendedAddress(0x1122));
@@ -992,22 +7353,92 @@ mod test {
         )
         .unwrap();
 
-        // Chilling in rx mode
+        // Synced and associated with a short address
+        mac.sync_state = SyncState::Synced(coord_addr);
+        mac.assoc_state = AssocState::Associated(mac_cfg.pan_id);
+        mac.short_addr = Some(ShortAddress(0x0005));
+
+        // Receive beacon granting slot 7, with slot 6 held by another device
+        let slots = [
+            GtsSlot {
+                short: ShortAddress(0x0004),
+                start: 6,
+                length: 1,
+            },
+            GtsSlot {
+                short: ShortAddress(0x0005),
+                start: 7,
+                length: 1,
+            },
+        ];
+        let beacon_info = Beacon {
+            superframe_spec: mac_cfg.superframe_spec(),
+            guaranteed_time_slot_info: gts::gts_info(true, slots.iter()),
+            pending_address: PendingAddress::new(),
+        };
+        let beacon = Packet::beacon(coord_addr, 0, beacon_info);
+
+        timer.set_ms(100);
+        radio.expect(&[
+            Transaction::check_receive(true, Ok(true)),
+            Transaction::get_received(Ok((beacon.into(), BasicInfo::default()))),
+            Transaction::start_receive(None),
+        ]);
+        mac.tick().unwrap();
+
+        assert_eq!(mac.gts_state, GtsState::Granted(slots[1]));
+
+        // Enqueue packet for our parent
+        let data = [0x11, 0x22, 0x33];
+        let handle = mac.transmit(coord_addr, &data, false).unwrap();
+        let packet = Packet::data(coord_addr, mac.addr(), 0, &data, false).unwrap();
+
+        // No TX in another device's slot
+        timer.set_ms(650);
         radio.expect(&[Transaction::check_receive(true, Ok(false))]);
         mac.tick().unwrap();
 
-        timer.set_ms(100);
+        // TX without CSMA in our slot
+        timer.set_ms(750);
+        radio.expect(&[
+            Transaction::check_receive(true, Ok(false)),
+            Transaction::start_transmit(packet.into(), None),
+        ]);
+        mac.tick().unwrap();
 
-        debug!("RX beacon");
+        assert_eq!(mac.tx_status().unwrap(), Some((handle, TxStatus::Sent)));
 
-        // Receive beacon
+        radio.done();
+    }
+
+    /// Setup an associated duty cycling MAC, sleeping following the active period
+    fn duty_cycle_setup(
+        mac_cfg: &Config<<MockRadio as crate::Radio>::Channel>,
+        radio: &mut MockRadio,
+        timer: &mut MockTimer,
+    ) -> (Mac<MockRadio, MockTimer>, Address) {
+        let mac_addr = ExtendedAddress(0xabcd);
+        let coord_addr = Address::Extended(mac_cfg.pan_id, ExtendedAddress(0x1122));
+
+        // Initialise MAC
+        radio.expect(&[Transaction::start_receive(None)]);
+        let mut mac = Mac::new(
+            mac_addr.clone(),
+            mac_cfg.clone(),
+            radio.clone(),
+            timer.clone(),
+        )
+        .unwrap();
+        mac.assoc_state = AssocState::Associated(mac_cfg.pan_id);
+
+        // Receive beacon to synchronise
+        timer.set_ms(100);
         let beacon_info = Beacon {
             superframe_spec: mac_cfg.superframe_spec(),
-            // TODO: replace placeholders with actual configuration
             guaranteed_time_slot_info: GuaranteedTimeSlotInformation::new(),
             pending_address: PendingAddress::new(),
         };
-        let beacon = Packet::beacon(coord_addr, seq, beacon_info);
+        let beacon = Packet::beacon(coord_addr, 0, beacon_info);
 
         radio.expect(&[
             Transaction::check_receive(true, Ok(true)),
@@ -1015,68 +7446,72 @@ mod test {
             Transaction::start_receive(None),
         ]);
         mac.tick().unwrap();
+        assert_eq!(mac.next_beacon, 2100);
 
-        // Check sync offset / next beacon time applied
-        assert_eq!(mac.sync_state, SyncState::Synced(coord_addr));
+        // Remain awake for the active period
+        timer.set_ms(1099);
+        radio.expect(&[Transaction::check_receive(true, Ok(false))]);
+        mac.tick().unwrap();
+        assert_eq!(mac.next_deadline(), Some(1100));
+
+        // Sleep at the end of the active period
+        timer.set_ms(1100);
+        radio.expect(&[
+            Transaction::check_receive(true, Ok(false)),
+            Transaction::set_state(MockState::Sleep, None),
+        ]);
+        mac.tick().unwrap();
+
+        assert_eq!(mac.state().unwrap(), MacState::Sleep);
+        assert_eq!(mac.stats().sleeps, 1);
+
+        // Wake scheduled ahead of the next beacon
         assert_eq!(
-            mac.next_beacon,
-            mac_cfg.superframe_duration() as u64 + timer.ticks_ms()
+            mac.next_deadline(),
+            Some(mac.next_beacon - mac_cfg.wake_guard)
         );
+
+        (mac, coord_addr)
     }
 
     #[test]
-    fn beacon_rx_next() {
+    fn duty_cycle_sleep_wake() {
         let _ =
             simplelog::SimpleLogger::init(log::LevelFilter::Debug, simplelog::Config::default());
 
         let mut radio = MockRadio::new(&[]);
         let mut timer = MockTimer::new();
-        let mut seq = 0;
-
-        let mac_addr = ExtendedAddress(0xabcd);
         let mac_cfg = Config {
             pan_coordinator: false,
+            duty_cycle: true,
             ..Default::default()
         };
-        let coord_addr = Address::Extended(mac_cfg.pan_id, ExtendedAddress(0x1122));
+        let (mut mac, coord_addr) = duty_cycle_setup(&mac_cfg, &mut radio, &mut timer);
 
-        // Initialise MAC
-        radio.expect(&[Transaction::start_receive(None)]);
-        let mut mac = Mac::new(
-            mac_addr.clone(),
-            mac_cfg.clone(),
-            radio.clone(),
-            timer.clone(),
-        )
-        .unwrap();
+        // Remain asleep until the wake time
+        timer.set_ms(2000);
+        mac.tick().unwrap();
+        assert_eq!(mac.state().unwrap(), MacState::Sleep);
 
-        // Chilling in rx mode, force to sleep so we see wake -> RX transition
+        // Wake ahead of the next beacon
+        timer.set_ms(2090);
         radio.expect(&[
+            Transaction::start_receive(None),
             Transaction::check_receive(true, Ok(false)),
-            Transaction::set_state(MockState::Sleep, None),
         ]);
         mac.tick().unwrap();
-        mac.base.sleep().unwrap();
-
-        // Set sync'd state so we're expecting a beacon
-        mac.sync_state = SyncState::Synced(coord_addr.clone());
-        mac.next_beacon = mac_cfg.superframe_duration() as u64;
-
-        // Arm RX for next expected beacon
-        timer.set_ms(mac.next_beacon as u32 + 3);
-        radio.expect(&[Transaction::start_receive(None)]);
-        mac.tick().unwrap();
 
-        debug!("RX beacon");
+        assert_eq!(mac.state().unwrap(), MacState::Associated(coord_addr));
+        assert_eq!(mac.stats().sleep_ms, 990);
 
-        // Receive beacon
+        // Receive beacon and sleep following the next active period
+        timer.set_ms(2100);
         let beacon_info = Beacon {
             superframe_spec: mac_cfg.superframe_spec(),
-            // TODO: replace placeholders with actual configuration
             guaranteed_time_slot_info: GuaranteedTimeSlotInformation::new(),
             pending_address: PendingAddress::new(),
         };
-        let beacon = Packet::beacon(coord_addr, seq, beacon_info);
+        let beacon = Packet::beacon(coord_addr, 1, beacon_info);
 
         radio.expect(&[
             Transaction::check_receive(true, Ok(true)),
@@ -1085,12 +7520,82 @@ mod test {
         ]);
         mac.tick().unwrap();
 
-        // Check sync offset / next beacon time applied
-        assert_eq!(mac.sync_state, SyncState::Synced(coord_addr));
-        assert_eq!(
-            mac.next_beacon,
-            mac_cfg.superframe_duration() as u64 + timer.ticks_ms()
-        );
+        timer.set_ms(3100);
+        radio.expect(&[
+            Transaction::check_receive(true, Ok(false)),
+            Transaction::set_state(MockState::Sleep, None),
+        ]);
+        mac.tick().unwrap();
+
+        assert_eq!(mac.state().unwrap(), MacState::Sleep);
+        assert_eq!(mac.stats().sleeps, 2);
+
+        radio.done();
+    }
+
+    #[test]
+    fn duty_cycle_app_wake() {
+        let _ =
+            simplelog::SimpleLogger::init(log::LevelFilter::Debug, simplelog::Config::default());
+
+        let mut radio = MockRadio::new(&[]);
+        let mut timer = MockTimer::new();
+        let mac_cfg = Config {
+            pan_coordinator: false,
+            duty_cycle: true,
+            ..Default::default()
+        };
+        let (mut mac, coord_addr) = duty_cycle_setup(&mac_cfg, &mut radio, &mut timer);
+
+        // Application wakes the radio early
+        timer.set_ms(1500);
+        radio.expect(&[Transaction::start_receive(None)]);
+        mac.wake().unwrap();
+
+        assert_eq!(mac.state().unwrap(), MacState::Associated(coord_addr));
+        assert_eq!(mac.stats().sleep_ms, 400);
+
+        // Radio is held awake until the next beacon
+        timer.set_ms(1600);
+        radio.expect(&[Transaction::check_receive(true, Ok(false))]);
+        mac.tick().unwrap();
+
+        assert_eq!(mac.state().unwrap(), MacState::Associated(coord_addr));
+        assert_eq!(mac.stats().sleeps, 1);
+
+        radio.done();
+    }
+
+    #[test]
+    fn duty_cycle_tx_wake() {
+        let _ =
+            simplelog::SimpleLogger::init(log::LevelFilter::Debug, simplelog::Config::default());
+
+        let mut radio = MockRadio::new(&[]);
+        let mut timer = MockTimer::new();
+        let mac_cfg = Config {
+            pan_coordinator: false,
+            duty_cycle: true,
+            ..Default::default()
+        };
+        let (mut mac, coord_addr) = duty_cycle_setup(&mac_cfg, &mut radio, &mut timer);
+
+        // Enqueue packet while asleep
+        timer.set_ms(1500);
+        mac.transmit(coord_addr, &[0x11, 0x22, 0x33], true).unwrap();
+        assert_eq!(mac.next_deadline(), Some(1500));
+
+        // Radio is woken early for the pending transmission
+        radio.expect(&[
+            Transaction::start_receive(None),
+            Transaction::check_receive(true, Ok(false)),
+        ]);
+        mac.tick().unwrap();
+
+        assert_eq!(mac.state().unwrap(), MacState::Associated(coord_addr));
+        assert_eq!(mac.stats().sleeps, 1);
+
+        radio.done();
     }
 
     #[test]
@@ -0,0 +1,264 @@
+//! 802.15.4 Neighbour Table
+//!
+//! Link quality is tracked for each neighbour heard from, as a moving average of
+//! received frame RSSI along with receive and missed ACK counts, for use in sync
+//! parent selection and for reporting to higher layers. Neighbours are marked as
+//! the sync parent or as associated children where applicable, and as coordinators
+//! once heard transmitting beacons. Those not heard from within the configured
+//! timeout are aged out.
+//
+// https://github.com/rust-iot/rust-lpwan
+// Copyright 2021 Ryan Kurte
+
+use heapless::Vec;
+use ieee802154::mac::Address;
+
+use crate::Ts;
+
+/// Default neighbour table capacity
+pub const DEFAULT_MAX_NEIGHBOURS: usize = 8;
+
+/// Weight of each new sample in the RSSI average (1 / 2^RSSI_WEIGHT_SHIFT)
+const RSSI_WEIGHT_SHIFT: u32 = 3;
+
+/// Fractional bits of the fixed point RSSI average
+const RSSI_FRAC_BITS: u32 = 4;
+
+/// Relationship of a neighbour to this device
+#[derive(Debug, Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Relation {
+    /// Neighbour heard from without a parent or child relationship
+    Peer,
+    /// Sync parent of this device
+    Parent,
+    /// Device associated with this coordinator
+    Child,
+}
+
+/// Neighbour link quality record
+#[derive(Debug, Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Neighbour {
+    /// Neighbour address, as the source of received frames
+    pub address: Address,
+    /// Number of frames received from the neighbour
+    pub rx_count: u32,
+    /// Number of frames sent to the neighbour without acknowledgement
+    pub ack_misses: u32,
+    /// Time the neighbour was last heard from
+    pub last_heard: Ts,
+    /// RSSI of the last frame received from the neighbour
+    pub last_rssi: i16,
+    /// Relationship of the neighbour to this device
+    pub relation: Relation,
+    /// Whether the neighbour is a coordinator, having been heard transmitting beacons
+    pub coordinator: bool,
+    /// Fixed point RSSI average
+    rssi_avg: i32,
+}
+
+impl Neighbour {
+    fn new(address: Address, rssi: i16, now: Ts, relation: Relation, beacon: bool) -> Self {
+        Self {
+            address,
+            rx_count: 1,
+            ack_misses: 0,
+            last_heard: now,
+            last_rssi: rssi,
+            relation,
+            coordinator: beacon,
+            rssi_avg: (rssi as i32) << RSSI_FRAC_BITS,
+        }
+    }
+
+    /// Fetch the exponentially weighted moving average of received frame RSSI
+    pub fn rssi(&self) -> i16 {
+        ((self.rssi_avg + (1 << (RSSI_FRAC_BITS - 1))) >> RSSI_FRAC_BITS) as i16
+    }
+
+    fn update(&mut self, rssi: i16, now: Ts, relation: Relation, beacon: bool) {
+        let sample = (rssi as i32) << RSSI_FRAC_BITS;
+        self.rssi_avg += (sample - self.rssi_avg) >> RSSI_WEIGHT_SHIFT;
+
+        self.rx_count = self.rx_count.saturating_add(1);
+        self.last_heard = now;
+        self.last_rssi = rssi;
+        self.relation = relation;
+        self.coordinator |= beacon;
+    }
+}
+
+/// Neighbour table, ordered from least to most recently heard
+#[derive(Debug, Clone, PartialEq)]
+pub struct NeighbourTable<const N: usize = DEFAULT_MAX_NEIGHBOURS> {
+    entries: Vec<Neighbour, N>,
+}
+
+impl<const N: usize> Default for NeighbourTable<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize> NeighbourTable<N> {
+    /// Create a new (empty) neighbour table
+    pub fn new() -> Self {
+        Self {
+            entries: Vec::new(),
+        }
+    }
+
+    /// Update a neighbour on receipt of a frame (or beacon),
+    /// replacing the least recently heard neighbour where the table is full
+    pub fn received(
+        &mut self,
+        address: Address,
+        rssi: i16,
+        now: Ts,
+        relation: Relation,
+        beacon: bool,
+    ) {
+        let n = match self.entries.iter().position(|n| n.address == address) {
+            Some(i) => {
+                let mut n = self.entries.remove(i);
+                n.update(rssi, now, relation, beacon);
+                n
+            }
+            None => {
+                if self.entries.is_full() {
+                    self.entries.remove(0);
+                }
+                Neighbour::new(address, rssi, now, relation, beacon)
+            }
+        };
+
+        let _ = self.entries.push(n);
+    }
+
+    /// Record a frame sent to a neighbour without acknowledgement
+    pub fn ack_missed(&mut self, address: &Address) {
+        if let Some(n) = self.entries.iter_mut().find(|n| &n.address == address) {
+            n.ack_misses = n.ack_misses.saturating_add(1);
+        }
+    }
+
+    /// Update the relationship of each neighbour, following changes in sync
+    /// or association state
+    pub fn update_relations(&mut self, relation: impl Fn(&Address) -> Relation) {
+        for n in self.entries.iter_mut() {
+            n.relation = relation(&n.address);
+        }
+    }
+
+    /// Drop neighbours not heard from since the provided time,
+    /// returning the number of neighbours removed
+    pub fn expire(&mut self, before: Ts) -> usize {
+        let mut removed = 0;
+        let mut i = 0;
+        while i < self.entries.len() {
+            if self.entries[i].last_heard < before {
+                self.entries.remove(i);
+                removed += 1;
+            } else {
+                i += 1;
+            }
+        }
+        removed
+    }
+
+    /// Remove neighbours with addresses matching the provided filter,
+    /// returning the number of neighbours removed
+    pub fn remove(&mut self, matches: impl Fn(&Address) -> bool) -> usize {
+        let n = self.entries.len();
+        self.entries.retain(|e| !matches(&e.address));
+        n - self.entries.len()
+    }
+
+    /// Fetch the record for a neighbour
+    pub fn lookup(&self, address: &Address) -> Option<&Neighbour> {
+        self.entries.iter().find(|n| &n.address == address)
+    }
+
+    /// Iterate over neighbours, from least to most recently heard
+    pub fn iter(&self) -> impl Iterator<Item = &Neighbour> {
+        self.entries.iter()
+    }
+
+    /// Iterate over neighbours heard transmitting beacons, for parent selection
+    pub fn coordinators(&self) -> impl Iterator<Item = &Neighbour> {
+        self.entries.iter().filter(|n| n.coordinator)
+    }
+
+    /// Fetch the number of neighbours
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use ieee802154::mac::{ExtendedAddress, PanId, ShortAddress};
+
+    use super::*;
+
+    #[test]
+    fn neighbour_table() {
+        let a = Address::Short(PanId(1), ShortAddress(1));
+        let b = Address::Extended(PanId(1), ExtendedAddress(2));
+        let c = Address::Short(PanId(1), ShortAddress(3));
+
+        let mut t = NeighbourTable::<2>::new();
+
+        t.received(a, -60, 10, Relation::Parent, true);
+        t.received(b, -80, 20, Relation::Peer, false);
+        assert_eq!(t.lookup(&a).map(|n| n.rssi()), Some(-60));
+        assert_eq!(t.lookup(&b).map(|n| n.rssi()), Some(-80));
+        assert_eq!(t.lookup(&a).map(|n| n.relation), Some(Relation::Parent));
+
+        // RSSI is averaged over received frames
+        for i in 0..32 {
+            t.received(a, -40, 30 + i, Relation::Parent, false);
+        }
+        // Coordinators are identified by beacons, retained on receipt of other frames
+        let coordinators: std::vec::Vec<_> = t.coordinators().map(|n| n.address).collect();
+        assert_eq!(coordinators, &[a]);
+        assert_eq!(t.lookup(&b).map(|n| n.coordinator), Some(false));
+
+        let n = t.lookup(&a).unwrap();
+        assert_eq!(n.rssi(), -40);
+        assert_eq!(n.rx_count, 33);
+        assert_eq!(n.last_heard, 61);
+
+        t.received(a, -80, 70, Relation::Parent, false);
+        assert_eq!(t.lookup(&a).map(|n| n.rssi()), Some(-45));
+        assert_eq!(t.lookup(&a).map(|n| n.last_rssi), Some(-80));
+
+        // Missed ACKs are counted for known neighbours
+        t.ack_missed(&b);
+        t.ack_missed(&c);
+        assert_eq!(t.lookup(&b).map(|n| n.ack_misses), Some(1));
+        assert!(t.lookup(&c).is_none());
+
+        // Full tables replace the least recently heard neighbour
+        t.received(c, -70, 80, Relation::Child, false);
+        assert_eq!(t.len(), 2);
+        assert!(t.lookup(&b).is_none());
+
+        // Relationships follow changes in sync and association state
+        t.update_relations(|n| {
+            if n == &c {
+                Relation::Child
+            } else {
+                Relation::Peer
+            }
+        });
+        assert_eq!(t.lookup(&a).map(|n| n.relation), Some(Relation::Peer));
+        assert_eq!(t.lookup(&c).map(|n| n.relation), Some(Relation::Child));
+
+        // Silent neighbours are aged out
+        assert_eq!(t.expire(75), 1);
+        assert!(t.lookup(&a).is_none());
+        assert!(t.lookup(&c).is_some());
+    }
+}
@@ -8,24 +8,39 @@ use ieee802154::mac::{beacon::Beacon, command::Command};
 
 use heapless::Vec;
 
-// TODO: fix or remove this?
-pub const MAX_PAYLOAD_LEN: usize = 256;
+use super::security::AuxSecurityHeader;
+use crate::error::BufferError;
+#[cfg(feature = "defmt")]
+use crate::log::HexSlice;
+
+/// Maximum PHY frame length including the FCS (aMaxPHYPacketSize)
+pub const MAX_FRAME_LEN: usize = 127;
+
+/// Default packet payload storage, bounded by the maximum frame length
+pub const MAX_PAYLOAD_LEN: usize = MAX_FRAME_LEN;
+
+/// Frame check sequence length
+pub const FCS_LEN: usize = 2;
+
+/// Security enabled flag in the first byte of the frame control field
+const SECURITY_ENABLED: u8 = 1 << 3;
 
 /// Packet object represents an IEEE 802.15.4 object with owned storage.
 ///
 /// Based on https://docs.rs/ieee802154/0.3.0/ieee802154/mac/frame/struct.Frame.html
 /// altered for static / owned storage via heapless
+///
+/// `defmt::Format` is implemented manually as heapless::Vec does not support defmt,
+/// see: https://github.com/japaric/heapless/issues/171
 #[derive(Clone, Debug)]
-// TODO: disabled on heapless::Vec support in defmt
-// See: https://github.com/japaric/heapless/issues/171
-//#[cfg_attr(feature = "defmt", derive(defmt::Format))]
-
-pub struct Packet<const N: usize = 256> {
+pub struct Packet<const N: usize = MAX_PAYLOAD_LEN> {
     pub header: Header,
 
+    /// Auxiliary security header, present for secured frames
+    pub aux_security: Option<AuxSecurityHeader>,
+
     pub content: FrameContent,
 
-    // TODO: replace with const generic version when available in heapless
     payload: Vec<u8, N>,
 
     pub footer: [u8; 2],
@@ -34,12 +49,28 @@ pub struct Packet<const N: usize = 256> {
 impl PartialEq for Packet {
     fn eq(&self, o: &Self) -> bool {
         self.header == o.header
+            && self.aux_security == o.aux_security
             && self.content == o.content
             && self.payload() == o.payload()
             && self.footer == o.footer
     }
 }
 
+#[cfg(feature = "defmt")]
+impl defmt::Format for Packet {
+    fn format(&self, f: defmt::Formatter) {
+        defmt::write!(
+            f,
+            "Packet {{ header: {}, aux_security: {}, content: {}, payload: {}, footer: {} }}",
+            self.header,
+            self.aux_security,
+            self.content,
+            HexSlice(self.payload()),
+            HexSlice(&self.footer)
+        )
+    }
+}
+
 impl Packet {
     pub fn beacon(source: Address, seq: u8, beacon: Beacon) -> Packet {
         Packet {
@@ -56,6 +87,7 @@ impl Packet {
                 seq_no_suppress: false,
                 ie_present: false,
             },
+            aux_security: None,
             content: FrameContent::Beacon(beacon),
             payload: Vec::new(),
             footer: [0u8; 2],
@@ -77,16 +109,25 @@ impl Packet {
                 seq_no_suppress: false,
                 ie_present: false,
             },
+            aux_security: None,
             content: FrameContent::Command(command),
             payload: Vec::new(),
             footer: [0u8; 2],
         }
     }
 
-    pub fn data(dest: Address, source: Address, seq: u8, data: &[u8], ack: bool) -> Packet {
-        let payload = Vec::from_slice(data).unwrap();
+    /// Create a data packet, returning [`BufferError::TooSmall`] where the
+    /// payload exceeds [`MAX_PAYLOAD_LEN`]
+    pub fn data(
+        dest: Address,
+        source: Address,
+        seq: u8,
+        data: &[u8],
+        ack: bool,
+    ) -> Result<Packet, BufferError> {
+        let payload = Vec::from_slice(data).map_err(|_| BufferError::TooSmall)?;
 
-        Packet {
+        Ok(Packet {
             header: Header {
                 frame_type: FrameType::Data,
                 frame_pending: false,
@@ -100,10 +141,11 @@ impl Packet {
                 seq_no_suppress: false,
                 ie_present: false,
             },
+            aux_security: None,
             content: FrameContent::Data,
             payload,
             footer: [0u8; 2],
-        }
+        })
     }
 
     // Generate an ACK for the provided packet
@@ -122,6 +164,7 @@ impl Packet {
                 seq_no_suppress: false,
                 ie_present: false,
             },
+            aux_security: None,
             content: FrameContent::Acknowledgement,
             payload: Vec::new(),
             footer: [0u8; 2],
@@ -153,14 +196,32 @@ impl Packet {
     }
 
     // Based on https://docs.rs/ieee802154/0.3.0/ieee802154/mac/frame/struct.Frame.html#method.encode
-    pub fn encode(&self, buf: &mut [u8], write_footer: WriteFooter) -> usize {
+    pub fn encode(&self, buf: &mut [u8], write_footer: WriteFooter) -> Result<usize, BufferError> {
+        // Header and content encoders do not check lengths, so these are written
+        // to a scratch buffer of the maximum frame size prior to copying out
+        let mut head = [0u8; MAX_PAYLOAD_LEN];
         let mut len = 0;
 
-        // Write header
-        len += self.header.encode(&mut buf[len..]);
+        // Write header, with the security enabled flag set by the auxiliary security header
+        let mut header = self.header.clone();
+        header.security = Security::None;
+        len += header.encode(&mut head[len..]);
+
+        // Write auxiliary security header
+        if let Some(aux) = &self.aux_security {
+            head[0] |= SECURITY_ENABLED;
+            len += aux.encode(&mut head[len..]);
+        }
 
         // Write content
-        len += self.content.encode(&mut buf[len..]);
+        len += self.content.encode(&mut head[len..]);
+
+        // Check the frame fits the output buffer
+        if len + self.payload.len() > buf.len() {
+            return Err(BufferError::TooSmall);
+        }
+
+        buf[..len].copy_from_slice(&head[..len]);
 
         // Write payload
         buf[len..len + self.payload.len()].copy_from_slice(&self.payload);
@@ -171,15 +232,45 @@ impl Packet {
         match write_footer {
             WriteFooter::No => (),
         }
-        len
+        Ok(len)
     }
 
     // Based on https://docs.rs/ieee802154/0.3.0/ieee802154/mac/frame/struct.Frame.html#method.decode
     pub fn decode(buf: &[u8], contains_footer: bool) -> Result<Self, DecodeError> {
+        // Frame control and sequence number, the latter is not length checked
+        // by the header decoder
+        if buf.len() < 3 {
+            return Err(DecodeError::NotEnoughBytes);
+        }
+
         let mut remaining = buf.len();
 
-        // First decode header
-        let (header, header_len) = Header::decode(buf)?;
+        // First decode header, masking the security enabled flag as secured frames
+        // are not supported by the header decoder
+        let secured = buf
+            .first()
+            .map(|b| b & SECURITY_ENABLED != 0)
+            .unwrap_or(false);
+        let (mut header, mut header_len) = match secured {
+            true => {
+                let mut h = [0u8; MAX_PAYLOAD_LEN];
+                let n = buf.len().min(h.len());
+                h[..n].copy_from_slice(&buf[..n]);
+                h[0] &= !SECURITY_ENABLED;
+
+                Header::decode(&h[..n])?
+            }
+            false => Header::decode(buf)?,
+        };
+
+        // Then the auxiliary security header, where present
+        let mut aux_security = None;
+        if secured {
+            let (aux, n) = AuxSecurityHeader::decode(&buf[header_len..])?;
+            header.security = Security::Enabled;
+            aux_security = Some(aux);
+            header_len += n;
+        }
         remaining -= header_len;
 
         // If there's a footer, decode this
@@ -200,15 +291,14 @@ impl Packet {
 
         // Decode the FrameContent
         let (content, used) = FrameContent::decode(body, &header)?;
-        remaining -= used;
-
-        let _ = remaining;
+        let body = body.get(used..).ok_or(DecodeError::NotEnoughBytes)?;
 
         // Copy out the payload
-        let payload = Vec::from_slice(&body[used..]).map_err(|_e| DecodeError::NotEnoughBytes)?;
+        let payload = Vec::from_slice(body).map_err(|_e| DecodeError::NotEnoughBytes)?;
 
         Ok(Packet {
             header,
+            aux_security,
             content,
             payload,
             footer,
@@ -219,8 +309,10 @@ impl Packet {
         &self.payload
     }
 
-    pub fn set_payload(&mut self, body: &[u8]) -> Result<(), ()> {
-        self.payload = Vec::from_slice(body)?;
+    /// Replace the packet payload, returning [`BufferError::TooSmall`] where
+    /// this exceeds the payload buffer
+    pub fn set_payload(&mut self, body: &[u8]) -> Result<(), BufferError> {
+        self.payload = Vec::from_slice(body).map_err(|_| BufferError::TooSmall)?;
 
         Ok(())
     }
@@ -230,7 +322,91 @@ impl Packet {
 impl Into<std::vec::Vec<u8>> for Packet {
     fn into(self) -> std::vec::Vec<u8> {
         let mut buff = [0u8; 256];
-        let n = self.encode(&mut buff, WriteFooter::No);
+        let n = self.encode(&mut buff, WriteFooter::No).unwrap();
         buff[..n].to_vec()
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn decode_malformed() {
+        let dest = Address::Short(PanId(1), ShortAddress(2));
+        let src = Address::Short(PanId(1), ShortAddress(1));
+
+        let mut buff = [0u8; 32];
+        let header_len = Packet::data(dest, src, 1, &[], false)
+            .unwrap()
+            .encode(&mut buff, WriteFooter::No)
+            .unwrap();
+
+        let p = Packet::data(dest, src, 1, &[0xaa, 0xbb], false).unwrap();
+        let n = p.encode(&mut buff, WriteFooter::No).unwrap();
+        let enc = &buff[..n];
+        assert_eq!(Packet::decode(enc, false), Ok(p));
+
+        // Frames truncated within the header are rejected
+        for i in 0..header_len {
+            assert!(Packet::decode(&enc[..i], false).is_err(), "length {}", i);
+        }
+
+        // As are frames too short for the footer
+        assert!(Packet::decode(&enc[..header_len], true).is_err());
+        assert!(Packet::decode(&enc[..header_len + 1], true).is_err());
+
+        // Secured frames with truncated auxiliary security headers
+        let mut secured = [0u8; 32];
+        secured[..n].copy_from_slice(enc);
+        secured[0] |= SECURITY_ENABLED;
+        for i in 0..=n {
+            assert!(
+                Packet::decode(&secured[..i], false).is_err(),
+                "length {}",
+                i
+            );
+        }
+
+        // And reserved frame types
+        let mut reserved = [0u8; 32];
+        reserved[..n].copy_from_slice(enc);
+        reserved[0] |= 0b111;
+        assert!(Packet::decode(&reserved[..n], false).is_err());
+    }
+
+    #[test]
+    fn encode_boundary() {
+        let dest = Address::Short(PanId(1), ShortAddress(2));
+        let src = Address::Short(PanId(1), ShortAddress(1));
+
+        let mut buff = [0u8; 256];
+        let header_len = Packet::data(dest, src, 1, &[], false)
+            .unwrap()
+            .encode(&mut buff, WriteFooter::No)
+            .unwrap();
+
+        // Payloads of the maximum length are accepted
+        let max = [0xaa; MAX_PAYLOAD_LEN];
+        let mut p = Packet::data(dest, src, 1, &max, false).unwrap();
+
+        // And encode into buffers of exactly the frame length, but no shorter
+        let n = header_len + MAX_PAYLOAD_LEN;
+        assert_eq!(p.encode(&mut buff[..n], WriteFooter::No), Ok(n));
+        assert_eq!(
+            p.encode(&mut buff[..n - 1], WriteFooter::No),
+            Err(BufferError::TooSmall)
+        );
+        assert_eq!(Packet::decode(&buff[..n], false), Ok(p.clone()));
+
+        // Payloads exceeding the maximum length are rejected
+        let oversized = [0xaa; MAX_PAYLOAD_LEN + 1];
+        assert_eq!(
+            Packet::data(dest, src, 1, &oversized, false).err(),
+            Some(BufferError::TooSmall)
+        );
+        assert_eq!(p.set_payload(&oversized), Err(BufferError::TooSmall));
+        assert_eq!(p.set_payload(&max[1..]), Ok(()));
+        assert_eq!(p.payload().len(), MAX_PAYLOAD_LEN - 1);
+    }
+}
@@ -0,0 +1,367 @@
+//! 802.15.4 MAC State Persistence
+//!
+//! Association and security state is captured in a [`Snapshot`] for storage in
+//! non-volatile memory via the [`Persist`] trait, allowing devices to resume operation
+//! following a reboot without repeating the association handshake or re-using frame
+//! counters. Snapshots are encoded in a compact, versioned and checksummed format.
+//!
+//! Frame counters are rounded up to the next [`FRAME_COUNTER_RESERVE`] boundary when
+//! snapshotted, so snapshots need only be re-stored when this boundary is crossed
+//! (i.e. when [`Mac::snapshot`] differs from the stored snapshot) rather than on
+//! every secured transmission.
+//
+// https://github.com/rust-iot/rust-lpwan
+// Copyright 2021 Ryan Kurte
+
+use core::fmt::Debug;
+
+use byteorder::{ByteOrder, LittleEndian};
+use ieee802154::mac::{Address, DecodeError, ExtendedAddress, PanId, ShortAddress};
+use rand_core::{OsRng, RngCore};
+
+use crate::log::{debug, info};
+use crate::{error::CoreError, timer::Timer, Radio};
+
+use super::{Aead, AssocState, Mac, SyncState};
+
+/// Snapshot encoding version
+pub const SNAPSHOT_VERSION: u8 = 1;
+
+/// Encoded snapshot length
+pub const SNAPSHOT_LEN: usize = 20;
+
+/// Frame counter reservation, snapshotted frame counters are rounded up to
+/// the next multiple of this value (which must be a power of two)
+pub const FRAME_COUNTER_RESERVE: u32 = 1024;
+
+/// Snapshot flag indicating a short address is allocated
+const FLAG_SHORT_ADDR: u8 = 1 << 0;
+/// Snapshot flag indicating an associated parent
+const FLAG_PARENT: u8 = 1 << 1;
+/// Snapshot flag indicating the parent address is extended
+const FLAG_PARENT_EXTENDED: u8 = 1 << 2;
+
+/// Non-volatile storage for MAC snapshots, implemented over flash, EEPROM, etc.
+pub trait Persist {
+    type Error: Debug;
+
+    /// Write an encoded snapshot to storage
+    fn write(&mut self, data: &[u8; SNAPSHOT_LEN]) -> Result<(), Self::Error>;
+
+    /// Read an encoded snapshot from storage, returning false where none has been written
+    fn read(&mut self, buff: &mut [u8; SNAPSHOT_LEN]) -> Result<bool, Self::Error>;
+
+    /// Store a snapshot
+    fn store(&mut self, snapshot: &Snapshot) -> Result<(), Self::Error> {
+        self.write(&snapshot.encode())
+    }
+
+    /// Load a stored snapshot, returning `None` where no valid snapshot is available
+    fn load(&mut self) -> Result<Option<Snapshot>, Self::Error> {
+        let mut buff = [0u8; SNAPSHOT_LEN];
+        if !self.read(&mut buff)? {
+            return Ok(None);
+        }
+
+        match Snapshot::decode(&buff) {
+            Ok(s) => Ok(Some(s)),
+            Err(e) => {
+                debug!("Discarding invalid snapshot: {:?}", e);
+                Ok(None)
+            }
+        }
+    }
+}
+
+/// MAC association and security state, see [`Mac::snapshot`] and [`Mac::restore`]
+#[derive(Debug, Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Snapshot {
+    /// PAN ID of the associated network
+    pub pan_id: PanId,
+    /// Short address allocated on association
+    pub short_addr: Option<ShortAddress>,
+    /// Parent with which the device is associated
+    pub parent: Option<Address>,
+    /// Frame counter reserved for outgoing secured frames
+    pub frame_counter: u32,
+}
+
+impl Snapshot {
+    /// Encode a snapshot
+    pub fn encode(&self) -> [u8; SNAPSHOT_LEN] {
+        let mut buff = [0u8; SNAPSHOT_LEN];
+
+        let mut flags = 0;
+        if let Some(s) = self.short_addr {
+            flags |= FLAG_SHORT_ADDR;
+            LittleEndian::write_u16(&mut buff[4..6], s.0);
+        }
+        match self.parent {
+            Some(Address::Short(_, s)) => {
+                flags |= FLAG_PARENT;
+                LittleEndian::write_u64(&mut buff[10..18], s.0 as u64);
+            }
+            Some(Address::Extended(_, e)) => {
+                flags |= FLAG_PARENT | FLAG_PARENT_EXTENDED;
+                LittleEndian::write_u64(&mut buff[10..18], e.0);
+            }
+            Some(Address::None) | None => (),
+        }
+
+        buff[0] = SNAPSHOT_VERSION;
+        buff[1] = flags;
+        LittleEndian::write_u16(&mut buff[2..4], self.pan_id.0);
+        LittleEndian::write_u32(&mut buff[6..10], self.frame_counter);
+
+        let crc = crc16(&buff[..SNAPSHOT_LEN - 2]);
+        LittleEndian::write_u16(&mut buff[SNAPSHOT_LEN - 2..], crc);
+
+        buff
+    }
+
+    /// Decode a snapshot, rejecting unsupported versions and corrupt data
+    pub fn decode(buff: &[u8]) -> Result<Self, DecodeError> {
+        if buff.len() < SNAPSHOT_LEN {
+            return Err(DecodeError::NotEnoughBytes);
+        }
+
+        let crc = LittleEndian::read_u16(&buff[SNAPSHOT_LEN - 2..]);
+        if buff[0] != SNAPSHOT_VERSION || crc != crc16(&buff[..SNAPSHOT_LEN - 2]) {
+            return Err(DecodeError::InvalidValue);
+        }
+
+        let flags = buff[1];
+        let pan_id = PanId(LittleEndian::read_u16(&buff[2..4]));
+
+        let short_addr = match flags & FLAG_SHORT_ADDR != 0 {
+            true => Some(ShortAddress(LittleEndian::read_u16(&buff[4..6]))),
+            false => None,
+        };
+
+        let parent = LittleEndian::read_u64(&buff[10..18]);
+        let parent = match (flags & FLAG_PARENT != 0, flags & FLAG_PARENT_EXTENDED != 0) {
+            (true, true) => Some(Address::Extended(pan_id, ExtendedAddress(parent))),
+            (true, false) => Some(Address::Short(pan_id, ShortAddress(parent as u16))),
+            _ => None,
+        };
+
+        Ok(Self {
+            pan_id,
+            short_addr,
+            parent,
+            frame_counter: LittleEndian::read_u32(&buff[6..10]),
+        })
+    }
+}
+
+/// Round a frame counter up to the next reservation boundary
+fn reserve_frame_counter(frame_counter: u32) -> u32 {
+    frame_counter
+        .checked_add(FRAME_COUNTER_RESERVE)
+        .map(|c| c & !(FRAME_COUNTER_RESERVE - 1))
+        .unwrap_or(u32::MAX)
+}
+
+/// CRC-16/KERMIT, as used for the 802.15.4 FCS
+fn crc16(data: &[u8]) -> u16 {
+    let mut crc = 0u16;
+    for b in data {
+        crc ^= *b as u16;
+        for _ in 0..8 {
+            crc = match crc & 1 != 0 {
+                true => (crc >> 1) ^ 0x8408,
+                false => crc >> 1,
+            };
+        }
+    }
+    crc
+}
+
+impl<R, T, S, const TXQ: usize, const RXQ: usize> Mac<R, T, S, TXQ, RXQ>
+where
+    R: Radio,
+    T: Timer,
+    S: Aead,
+{
+    /// Capture association and security state for persistence across reboot.
+    ///
+    /// The frame counter is reserved ahead of the current value, so this must be
+    /// re-stored prior to further secured transmissions once the current frame
+    /// counter reaches the stored value.
+    pub fn snapshot(&self) -> Snapshot {
+        let parent = match (&self.assoc_state, &self.sync_state) {
+            (AssocState::Associated(_), SyncState::Synced(p))
+            | (AssocState::Associated(_), SyncState::Lost(p, _))
+                if !self.config.pan_coordinator =>
+            {
+                Some(*p)
+            }
+            _ => None,
+        };
+
+        Snapshot {
+            pan_id: self.pan_id(),
+            short_addr: self.short_addr,
+            parent,
+            frame_counter: reserve_frame_counter(self.security.frame_counter),
+        }
+    }
+
+    /// Restore state from a [`Snapshot`] following a reboot.
+    ///
+    /// The frame counter is always restored, while devices restore the association
+    /// with the snapshotted parent and re-synchronise via orphan notification
+    /// (and coordinator realignment) without repeating the association handshake,
+    /// falling back to re-association if the parent is not heard from within
+    /// [`Config::rejoin_grace`](super::Config::rejoin_grace).
+    pub fn restore(&mut self, snapshot: &Snapshot) -> Result<(), CoreError<<R as Radio>::Error>> {
+        if self.scanning() {
+            return Err(CoreError::Busy);
+        }
+        // PAN coordinators are not associated so may only restore frame counters
+        if self.config.pan_coordinator && snapshot.parent.is_some() {
+            return Err(CoreError::InvalidConfig);
+        }
+
+        self.security.frame_counter = self.security.frame_counter.max(snapshot.frame_counter);
+
+        let parent = match snapshot.parent {
+            Some(p) => p,
+            None => return Ok(()),
+        };
+
+        let now_ms = self.timer.ticks_ms();
+        info!(
+            "Restoring association with {:?} (PAN {:?}) at {} ms",
+            parent, snapshot.pan_id, now_ms
+        );
+
+        self.config.pan_id = snapshot.pan_id;
+        self.short_addr = snapshot.short_addr;
+        self.join_target = Some(parent);
+        self.network = None;
+        self.next_beacon = 0;
+        self.hopping = None;
+
+        // Sequence numbers are not persisted, so start from a random value to avoid
+        // frames being dropped by the parent as duplicates of those sent prior to reboot
+        self.seq = OsRng {}.next_u32() as u8;
+
+        self.assoc_state = AssocState::Associated(snapshot.pan_id);
+        self.sync_state = match self.config.rejoin_grace {
+            0 => SyncState::Unsynced,
+            grace => {
+                self.rejoin_at = now_ms;
+                self.rejoin_backoff = self.config.rejoin_backoff;
+                SyncState::Lost(parent, now_ms + grace)
+            }
+        };
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    struct Flash(Option<[u8; SNAPSHOT_LEN]>);
+
+    impl Persist for Flash {
+        type Error = ();
+
+        fn write(&mut self, data: &[u8; SNAPSHOT_LEN]) -> Result<(), ()> {
+            self.0 = Some(*data);
+            Ok(())
+        }
+
+        fn read(&mut self, buff: &mut [u8; SNAPSHOT_LEN]) -> Result<bool, ()> {
+            match &self.0 {
+                Some(d) => {
+                    buff.copy_from_slice(d);
+                    Ok(true)
+                }
+                None => Ok(false),
+            }
+        }
+    }
+
+    #[test]
+    fn snapshot_encode_decode() {
+        let tests = [
+            Snapshot {
+                pan_id: PanId(0x1234),
+                short_addr: Some(ShortAddress(0x0102)),
+                parent: Some(Address::Short(PanId(0x1234), ShortAddress(0x0001))),
+                frame_counter: 2048,
+            },
+            Snapshot {
+                pan_id: PanId(1),
+                short_addr: None,
+                parent: Some(Address::Extended(
+                    PanId(1),
+                    ExtendedAddress(0x0011_2233_4455_6677),
+                )),
+                frame_counter: 0,
+            },
+            Snapshot {
+                pan_id: PanId(1),
+                short_addr: None,
+                parent: None,
+                frame_counter: u32::MAX,
+            },
+        ];
+
+        for s in &tests {
+            let buff = s.encode();
+            assert_eq!(buff[0], SNAPSHOT_VERSION);
+            assert_eq!(Snapshot::decode(&buff), Ok(*s));
+        }
+
+        let mut buff = tests[0].encode();
+        assert_eq!(
+            Snapshot::decode(&buff[..SNAPSHOT_LEN - 1]),
+            Err(DecodeError::NotEnoughBytes)
+        );
+
+        // Corrupt snapshots are rejected
+        buff[6] ^= 0x01;
+        assert_eq!(Snapshot::decode(&buff), Err(DecodeError::InvalidValue));
+
+        // As are unsupported versions
+        let mut buff = tests[0].encode();
+        buff[0] = SNAPSHOT_VERSION + 1;
+        assert_eq!(Snapshot::decode(&buff), Err(DecodeError::InvalidValue));
+    }
+
+    #[test]
+    fn snapshot_persist() {
+        let mut flash = Flash(None);
+        assert_eq!(flash.load(), Ok(None));
+
+        let s = Snapshot {
+            pan_id: PanId(1),
+            short_addr: Some(ShortAddress(2)),
+            parent: Some(Address::Short(PanId(1), ShortAddress(0))),
+            frame_counter: 1024,
+        };
+        flash.store(&s).unwrap();
+        assert_eq!(flash.load(), Ok(Some(s)));
+
+        // Invalid stored data is discarded
+        flash.0.as_mut().unwrap()[SNAPSHOT_LEN - 1] ^= 0xff;
+        assert_eq!(flash.load(), Ok(None));
+    }
+
+    #[test]
+    fn frame_counter_reserve() {
+        assert_eq!(reserve_frame_counter(0), 1024);
+        assert_eq!(reserve_frame_counter(1), 1024);
+        assert_eq!(reserve_frame_counter(1023), 1024);
+        assert_eq!(reserve_frame_counter(1024), 2048);
+        assert_eq!(reserve_frame_counter(u32::MAX - 1024), 0xffff_fc00);
+        assert_eq!(reserve_frame_counter(0xffff_fc00), u32::MAX);
+        assert_eq!(reserve_frame_counter(u32::MAX), u32::MAX);
+    }
+}
@@ -0,0 +1,172 @@
+//! 802.15.4 Downstream Route Table
+//!
+//! Coordinators track devices beneath them by short address, either associated
+//! directly or reached via an intermediate router, so frames for devices that are
+//! not direct neighbours may be forwarded via the router (for example by the
+//! 6LoWPAN layer with a mesh header carrying the final destination). Routes are
+//! replaced where a device re-associates elsewhere, or where a short address
+//! is re-issued to another device.
+//
+// https://github.com/rust-iot/rust-lpwan
+// Copyright 2021 Ryan Kurte
+
+use heapless::Vec;
+use ieee802154::mac::{Address, ExtendedAddress, ShortAddress};
+
+use crate::Ts;
+
+/// Default downstream route table capacity
+pub const DEFAULT_MAX_ROUTES: usize = 16;
+
+/// Downstream route to a device
+#[derive(Debug, Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Route {
+    /// Short address of the device
+    pub short: ShortAddress,
+    /// Extended address of the device
+    pub extended: ExtendedAddress,
+    /// Router via which the device is reached, `None` where associated directly
+    pub via: Option<Address>,
+    /// Time the route was last updated
+    pub updated: Ts,
+}
+
+impl Route {
+    /// Check whether an address refers to the device
+    pub fn matches(&self, addr: &Address) -> bool {
+        match addr {
+            Address::Short(_, s) => *s == self.short,
+            Address::Extended(_, e) => *e == self.extended,
+            _ => false,
+        }
+    }
+}
+
+/// Downstream route table, mapping devices to next hops
+#[derive(Debug, Clone, PartialEq)]
+pub struct RouteTable<const N: usize = DEFAULT_MAX_ROUTES> {
+    entries: Vec<Route, N>,
+}
+
+impl<const N: usize> Default for RouteTable<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize> RouteTable<N> {
+    /// Create a new (empty) route table
+    pub fn new() -> Self {
+        Self {
+            entries: Vec::new(),
+        }
+    }
+
+    /// Add or replace the route to a device, superseding any route with the
+    /// same extended or short address. Returns the route if the table is full
+    pub fn update(&mut self, route: Route) -> Result<(), Route> {
+        self.entries
+            .retain(|r| r.extended != route.extended && r.short != route.short);
+
+        self.entries.push(route)
+    }
+
+    /// Remove the route to a device
+    pub fn remove(&mut self, extended: &ExtendedAddress) -> Option<Route> {
+        let i = self.entries.iter().position(|r| &r.extended == extended)?;
+        Some(self.entries.swap_remove(i))
+    }
+
+    /// Remove routes via the provided router, returning the number removed
+    pub fn remove_via(&mut self, via: &Address) -> usize {
+        let n = self.entries.len();
+        self.entries.retain(|r| r.via.as_ref() != Some(via));
+        n - self.entries.len()
+    }
+
+    /// Lookup the route to a device by short or extended address
+    pub fn lookup(&self, addr: &Address) -> Option<&Route> {
+        self.entries.iter().find(|r| r.matches(addr))
+    }
+
+    /// Resolve the router via which a device is reached,
+    /// `None` where the device is associated directly or unknown
+    pub fn next_hop(&self, addr: &Address) -> Option<Address> {
+        self.lookup(addr).and_then(|r| r.via)
+    }
+
+    /// Iterate over routes
+    pub fn iter(&self) -> impl Iterator<Item = &Route> {
+        self.entries.iter()
+    }
+
+    /// Fetch the number of routes
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Remove all routes
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use ieee802154::mac::PanId;
+
+    fn route(short: u16, extended: u64, via: Option<Address>) -> Route {
+        Route {
+            short: ShortAddress(short),
+            extended: ExtendedAddress(extended),
+            via,
+            updated: 0,
+        }
+    }
+
+    #[test]
+    fn downstream_routes() {
+        let router = Address::Short(PanId(1), ShortAddress(0x0001));
+        let other = Address::Short(PanId(1), ShortAddress(0x0002));
+        let dest = Address::Short(PanId(1), ShortAddress(0x0010));
+
+        let mut t = RouteTable::<2>::new();
+        t.update(route(0x0001, 0x01, None)).unwrap();
+        t.update(route(0x0010, 0x10, Some(router))).unwrap();
+
+        // Direct devices have no next hop
+        assert_eq!(t.next_hop(&router), None);
+        assert_eq!(t.next_hop(&dest), Some(router));
+        assert_eq!(
+            t.next_hop(&Address::Extended(PanId(1), ExtendedAddress(0x10))),
+            Some(router)
+        );
+
+        // Table full
+        assert!(t.update(route(0x0020, 0x20, None)).is_err());
+
+        // Re-association elsewhere replaces the existing route
+        t.update(route(0x0010, 0x10, Some(other))).unwrap();
+        assert_eq!(t.next_hop(&dest), Some(other));
+        assert_eq!(t.len(), 2);
+
+        // As does re-issue of the short address to another device
+        t.update(route(0x0010, 0x11, None)).unwrap();
+        assert_eq!(t.next_hop(&dest), None);
+        assert_eq!(
+            t.lookup(&dest).map(|r| r.extended),
+            Some(ExtendedAddress(0x11))
+        );
+
+        // Routes are dropped with the router
+        t.update(route(0x0010, 0x10, Some(router))).unwrap();
+        assert_eq!(t.remove_via(&router), 1);
+        assert_eq!(
+            t.remove(&ExtendedAddress(0x01)).map(|r| r.short),
+            Some(ShortAddress(1))
+        );
+        assert_eq!(t.len(), 0);
+    }
+}
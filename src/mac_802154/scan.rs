@@ -0,0 +1,187 @@
+//! 802.15.4 Channel Scans
+//!
+//! Channels are surveyed sequentially with a dwell time on each, either sampling
+//! the channel RSSI to estimate channel occupancy (energy detection), or collecting
+//! beacons to discover nearby PANs (passive and active scans). Scans are driven by
+//! the MAC tick so the stack remains responsive while these are in progress.
+//
+// https://github.com/rust-iot/rust-lpwan
+// Copyright 2021 Ryan Kurte
+
+use heapless::Vec;
+use ieee802154::mac::beacon::{BeaconOrder, SuperframeOrder};
+use ieee802154::mac::{Address, PanId};
+
+use crate::Ts;
+
+/// Maximum number of channels in a scan
+pub const MAX_SCAN_CHANNELS: usize = 16;
+
+/// Maximum number of PAN descriptors collected by a scan
+pub const MAX_PAN_DESCRIPTORS: usize = 8;
+
+/// Scan type
+#[derive(Debug, Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ScanKind {
+    /// Energy detection, sampling the channel RSSI
+    Energy,
+    /// Passive PAN discovery, listening for beacons
+    Passive,
+    /// Active PAN discovery, issuing a beacon request on each channel then listening for beacons
+    Active,
+}
+
+/// PAN descriptor, describing a coordinator discovered by a passive or active scan
+#[derive(Debug, Clone, PartialEq)]
+pub struct PanDescriptor<Ch> {
+    pub pan_id: PanId,
+    pub coord_addr: Address,
+    pub channel: Ch,
+    /// RSSI of the most recent beacon
+    pub rssi: i16,
+    pub beacon_order: BeaconOrder,
+    pub superframe_order: SuperframeOrder,
+    pub association_permit: bool,
+}
+
+/// Energy detection result for a scanned channel,
+/// RSSI values are `i16::MIN` where no samples were taken
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScanResult<Ch> {
+    pub channel: Ch,
+    /// Maximum sampled RSSI
+    pub max_rssi: i16,
+    /// Mean sampled RSSI
+    pub mean_rssi: i16,
+}
+
+/// Energy detection scan state
+#[derive(Debug, Clone, PartialEq)]
+pub struct Scan<Ch> {
+    kind: ScanKind,
+    channels: Vec<Ch, MAX_SCAN_CHANNELS>,
+    duration_ms: Ts,
+    /// End of the dwell on the current channel, unset prior to tuning
+    dwell_end: Option<Ts>,
+    /// Select the quietest channel on completion
+    pub(crate) select: bool,
+
+    max: i16,
+    sum: i32,
+    samples: u32,
+
+    results: Vec<ScanResult<Ch>, MAX_SCAN_CHANNELS>,
+    pans: Vec<PanDescriptor<Ch>, MAX_PAN_DESCRIPTORS>,
+}
+
+impl<Ch: Clone + PartialEq> Scan<Ch> {
+    /// Create a new scan over the provided channels,
+    /// returning None if this exceeds [`MAX_SCAN_CHANNELS`]
+    pub fn new(kind: ScanKind, channels: &[Ch], duration_ms: Ts) -> Option<Self> {
+        Some(Self {
+            kind,
+            channels: Vec::from_slice(channels).ok()?,
+            duration_ms,
+            dwell_end: None,
+            select: false,
+            max: i16::MIN,
+            sum: 0,
+            samples: 0,
+            results: Vec::new(),
+            pans: Vec::new(),
+        })
+    }
+
+    /// Fetch the scan type
+    pub fn kind(&self) -> ScanKind {
+        self.kind
+    }
+
+    /// Check whether all channels have been scanned
+    pub fn is_done(&self) -> bool {
+        self.results.len() == self.channels.len()
+    }
+
+    /// Fetch scan progress as (channels scanned, total channels)
+    pub fn progress(&self) -> (usize, usize) {
+        (self.results.len(), self.channels.len())
+    }
+
+    /// Fetch results for scanned channels
+    pub fn results(&self) -> &[ScanResult<Ch>] {
+        &self.results
+    }
+
+    /// Fetch PANs discovered by passive or active scans
+    pub fn pans(&self) -> &[PanDescriptor<Ch>] {
+        &self.pans
+    }
+
+    /// Fetch the quietest scanned channel, by maximum then mean RSSI
+    pub fn quietest(&self) -> Option<&ScanResult<Ch>> {
+        self.results
+            .iter()
+            .min_by_key(|r| (r.max_rssi, r.mean_rssi))
+    }
+
+    /// Fetch the channel currently (or next) being scanned
+    pub(crate) fn current(&self) -> Option<&Ch> {
+        self.channels.get(self.results.len())
+    }
+
+    /// Fetch the end of the dwell on the current channel
+    pub(crate) fn dwell_end(&self) -> Option<Ts> {
+        self.dwell_end
+    }
+
+    /// Start the dwell on the current channel
+    pub(crate) fn start(&mut self, now: Ts) {
+        self.dwell_end = Some(now + self.duration_ms);
+        self.max = i16::MIN;
+        self.sum = 0;
+        self.samples = 0;
+    }
+
+    /// Add or update a discovered PAN, dropped where the descriptor list is full
+    pub(crate) fn add_pan(&mut self, pan: PanDescriptor<Ch>) {
+        match self
+            .pans
+            .iter_mut()
+            .find(|p| p.coord_addr == pan.coord_addr && p.channel == pan.channel)
+        {
+            Some(p) => *p = pan,
+            None => {
+                let _ = self.pans.push(pan);
+            }
+        }
+    }
+
+    /// Add an RSSI sample for the current channel
+    pub(crate) fn sample(&mut self, rssi: i16) {
+        self.max = self.max.max(rssi);
+        self.sum += rssi as i32;
+        self.samples += 1;
+    }
+
+    /// Complete the dwell on the current channel, recording the result
+    pub(crate) fn finish(&mut self) {
+        let channel = match self.current() {
+            Some(c) => c.clone(),
+            None => return,
+        };
+
+        let mean_rssi = match self.samples {
+            0 => i16::MIN,
+            n => (self.sum / n as i32) as i16,
+        };
+
+        let _ = self.results.push(ScanResult {
+            channel,
+            max_rssi: self.max,
+            mean_rssi,
+        });
+
+        self.dwell_end = None;
+    }
+}
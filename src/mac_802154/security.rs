@@ -0,0 +1,877 @@
+//! 802.15.4 MAC Security
+//!
+//! Frame protection per IEEE 802.15.4-2006 Section 7.6, using CCM* for encryption
+//! and authentication with an auxiliary security header following the MAC header.
+//! Encryption is provided via the [`Aead`] trait so hardware accelerators may be used,
+//! with [`CcmStar`] implementing CCM* over any AES-128 [`BlockCipher`] and [`SoftAes`]
+//! providing a software block cipher (with the `soft-aes` feature).
+//!
+//! Data frames are secured where a [`SecurityConfig`] is provided, beacons and MAC
+//! commands are sent in the clear to support joining devices. Received frames failing
+//! verification are dropped and counted in [`MacStats::security_fail`](super::MacStats).
+//
+// https://github.com/rust-iot/rust-lpwan
+// Copyright 2021 Ryan Kurte
+
+use byteorder::{ByteOrder, LittleEndian};
+use heapless::Vec;
+use ieee802154::mac::{
+    Address, DecodeError, ExtendedAddress, FrameType, FrameVersion, WriteFooter,
+};
+
+use crate::{timer::Timer, Radio};
+
+use super::packet::{Packet, MAX_PAYLOAD_LEN};
+use super::Mac;
+
+/// Security key length
+pub const KEY_LEN: usize = 16;
+
+/// CCM* nonce length
+pub const NONCE_LEN: usize = 13;
+
+/// Block cipher block length
+pub const BLOCK_LEN: usize = 16;
+
+/// Maximum MIC length
+pub const MAX_MIC_LEN: usize = 16;
+
+/// Default number of neighbours for which frame counters are tracked
+pub const DEFAULT_MAX_NEIGHBOURS: usize = 16;
+
+/// CCM* length field size, fixed at 2 for 802.15.4 with a 13 byte nonce
+const CCM_L: u8 = 2;
+
+/// Security level, per 802.15.4-2006 Table 95
+#[derive(Debug, Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum SecurityLevel {
+    None = 0,
+    Mic32 = 1,
+    Mic64 = 2,
+    Mic128 = 3,
+    Enc = 4,
+    EncMic32 = 5,
+    EncMic64 = 6,
+    EncMic128 = 7,
+}
+
+impl SecurityLevel {
+    /// Decode a security level from the low bits of the security control field
+    pub fn from_bits(v: u8) -> Self {
+        use SecurityLevel::*;
+
+        match v & 0b111 {
+            0 => None,
+            1 => Mic32,
+            2 => Mic64,
+            3 => Mic128,
+            4 => Enc,
+            5 => EncMic32,
+            6 => EncMic64,
+            _ => EncMic128,
+        }
+    }
+
+    /// Fetch the MIC length for the security level
+    pub fn mic_len(&self) -> usize {
+        match *self as u8 & 0b11 {
+            0 => 0,
+            1 => 4,
+            2 => 8,
+            _ => 16,
+        }
+    }
+
+    /// Check whether the security level provides confidentiality
+    pub fn encrypted(&self) -> bool {
+        *self as u8 & 0b100 != 0
+    }
+}
+
+/// Key identifier, per 802.15.4-2006 Section 7.6.2.4
+#[derive(Debug, Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum KeyId {
+    /// Key determined implicitly from the originator and recipient
+    Implicit,
+    /// Key determined from the key index and default key source
+    Index(u8),
+    /// Key determined from a 4 byte key source and key index
+    Source4([u8; 4], u8),
+    /// Key determined from an 8 byte key source and key index
+    Source8([u8; 8], u8),
+}
+
+impl KeyId {
+    fn mode(&self) -> u8 {
+        match self {
+            KeyId::Implicit => 0,
+            KeyId::Index(_) => 1,
+            KeyId::Source4(..) => 2,
+            KeyId::Source8(..) => 3,
+        }
+    }
+}
+
+/// Auxiliary security header
+#[derive(Debug, Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct AuxSecurityHeader {
+    pub level: SecurityLevel,
+    pub key_id: KeyId,
+    pub frame_counter: u32,
+}
+
+impl AuxSecurityHeader {
+    /// Decode an auxiliary security header, returning the header and decoded length
+    pub fn decode(buff: &[u8]) -> Result<(Self, usize), DecodeError> {
+        if buff.len() < 5 {
+            return Err(DecodeError::NotEnoughBytes);
+        }
+
+        let level = SecurityLevel::from_bits(buff[0]);
+        let frame_counter = LittleEndian::read_u32(&buff[1..5]);
+
+        let (key_id, n) = match (buff[0] >> 3) & 0b11 {
+            0 => (KeyId::Implicit, 5),
+            1 if buff.len() >= 6 => (KeyId::Index(buff[5]), 6),
+            2 if buff.len() >= 10 => {
+                let mut s = [0u8; 4];
+                s.copy_from_slice(&buff[5..9]);
+                (KeyId::Source4(s, buff[9]), 10)
+            }
+            3 if buff.len() >= 14 => {
+                let mut s = [0u8; 8];
+                s.copy_from_slice(&buff[5..13]);
+                (KeyId::Source8(s, buff[13]), 14)
+            }
+            _ => return Err(DecodeError::NotEnoughBytes),
+        };
+
+        let h = Self {
+            level,
+            key_id,
+            frame_counter,
+        };
+
+        Ok((h, n))
+    }
+
+    /// Encode an auxiliary security header, returning the encoded length
+    pub fn encode(&self, buff: &mut [u8]) -> usize {
+        buff[0] = self.level as u8 | self.key_id.mode() << 3;
+        LittleEndian::write_u32(&mut buff[1..5], self.frame_counter);
+
+        match &self.key_id {
+            KeyId::Implicit => 5,
+            KeyId::Index(i) => {
+                buff[5] = *i;
+                6
+            }
+            KeyId::Source4(s, i) => {
+                buff[5..9].copy_from_slice(s);
+                buff[9] = *i;
+                10
+            }
+            KeyId::Source8(s, i) => {
+                buff[5..13].copy_from_slice(s);
+                buff[13] = *i;
+                14
+            }
+        }
+    }
+}
+
+/// Security configuration
+#[derive(Debug, Clone, PartialEq)]
+pub struct SecurityConfig {
+    /// Network key
+    pub key: [u8; KEY_LEN],
+    /// Key identifier, advertised in outgoing frames and required on receipt
+    pub key_id: KeyId,
+    /// Security level for outgoing frames and required on receipt
+    pub level: SecurityLevel,
+}
+
+/// Security errors
+#[derive(Debug, Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum SecurityError {
+    /// Unsecured frame received where security is required
+    Unsecured,
+    /// Security level or frame type not supported
+    Unsupported,
+    /// No matching key for the frame
+    UnknownKey,
+    /// Originator extended address unknown (required for the nonce)
+    UnknownDevice,
+    /// Frame counter not greater than the last received from the originator
+    Replay,
+    /// Message integrity check failed
+    Mic,
+    /// Outgoing frame counter exhausted
+    CounterExhausted,
+    /// Secured frame exceeds buffer length
+    BufferFull,
+}
+
+/// Authenticated encryption for frame protection,
+/// implemented over a [`BlockCipher`] by [`CcmStar`] or by hardware accelerators
+pub trait Aead {
+    /// Encrypt `data` in place, writing the MIC over `aad` and `data` to `mic`
+    /// (where `mic` is empty no MIC is computed)
+    fn encrypt(
+        &mut self,
+        key: &[u8; KEY_LEN],
+        nonce: &[u8; NONCE_LEN],
+        aad: &[u8],
+        data: &mut [u8],
+        mic: &mut [u8],
+    ) -> Result<(), SecurityError>;
+
+    /// Decrypt `data` in place, verifying the MIC over `aad` and `data`
+    fn decrypt(
+        &mut self,
+        key: &[u8; KEY_LEN],
+        nonce: &[u8; NONCE_LEN],
+        aad: &[u8],
+        data: &mut [u8],
+        mic: &[u8],
+    ) -> Result<(), SecurityError>;
+}
+
+/// AES-128 block cipher (encryption only, as required by CCM*)
+pub trait BlockCipher {
+    fn encrypt_block(&mut self, key: &[u8; KEY_LEN], block: &mut [u8; BLOCK_LEN]);
+}
+
+/// Placeholder for MACs without security support, secured frames are rejected
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct NoSecurity;
+
+impl Aead for NoSecurity {
+    fn encrypt(
+        &mut self,
+        _key: &[u8; KEY_LEN],
+        _nonce: &[u8; NONCE_LEN],
+        _aad: &[u8],
+        _data: &mut [u8],
+        _mic: &mut [u8],
+    ) -> Result<(), SecurityError> {
+        Err(SecurityError::Unsupported)
+    }
+
+    fn decrypt(
+        &mut self,
+        _key: &[u8; KEY_LEN],
+        _nonce: &[u8; NONCE_LEN],
+        _aad: &[u8],
+        _data: &mut [u8],
+        _mic: &[u8],
+    ) -> Result<(), SecurityError> {
+        Err(SecurityError::Unsupported)
+    }
+}
+
+/// CCM* authenticated encryption over a block cipher, per 802.15.4-2006 Annex B
+#[derive(Debug, Clone, PartialEq)]
+pub struct CcmStar<B>(pub B);
+
+impl<B: BlockCipher> CcmStar<B> {
+    /// Compute the (unencrypted) authentication tag over `aad` and plaintext `data`
+    fn auth(
+        &mut self,
+        key: &[u8; KEY_LEN],
+        nonce: &[u8; NONCE_LEN],
+        aad: &[u8],
+        data: &[u8],
+        mic_len: usize,
+    ) -> [u8; BLOCK_LEN] {
+        let mut x = [0u8; BLOCK_LEN];
+        if mic_len == 0 {
+            return x;
+        }
+
+        // B0 carries flags, the nonce, and the message length
+        let adata = if aad.is_empty() { 0 } else { 1 << 6 };
+        x[0] = adata | (((mic_len - 2) / 2) as u8) << 3 | (CCM_L - 1);
+        x[1..14].copy_from_slice(nonce);
+        x[14..16].copy_from_slice(&(data.len() as u16).to_be_bytes());
+        self.0.encrypt_block(key, &mut x);
+
+        // Additional data is prefixed by its length, with both fields zero-padded to blocks
+        if !aad.is_empty() {
+            let len = (aad.len() as u16).to_be_bytes();
+            self.cbc_mac(key, &mut x, len.iter().chain(aad.iter()));
+        }
+        self.cbc_mac(key, &mut x, data.iter());
+
+        x
+    }
+
+    /// Update the CBC-MAC state with data, zero-padded to the block length
+    fn cbc_mac<'a>(
+        &mut self,
+        key: &[u8; KEY_LEN],
+        x: &mut [u8; BLOCK_LEN],
+        data: impl Iterator<Item = &'a u8>,
+    ) {
+        let mut i = 0;
+        for b in data {
+            x[i] ^= *b;
+            i += 1;
+
+            if i == BLOCK_LEN {
+                self.0.encrypt_block(key, x);
+                i = 0;
+            }
+        }
+
+        if i != 0 {
+            self.0.encrypt_block(key, x);
+        }
+    }
+
+    /// Generate the key stream block for counter `i`
+    fn key_stream(
+        &mut self,
+        key: &[u8; KEY_LEN],
+        nonce: &[u8; NONCE_LEN],
+        i: u16,
+    ) -> [u8; BLOCK_LEN] {
+        let mut a = [0u8; BLOCK_LEN];
+        a[0] = CCM_L - 1;
+        a[1..14].copy_from_slice(nonce);
+        a[14..16].copy_from_slice(&i.to_be_bytes());
+        self.0.encrypt_block(key, &mut a);
+        a
+    }
+
+    /// Apply the counter mode key stream to data (from counter 1)
+    fn ctr(&mut self, key: &[u8; KEY_LEN], nonce: &[u8; NONCE_LEN], data: &mut [u8]) {
+        for (i, chunk) in data.chunks_mut(BLOCK_LEN).enumerate() {
+            let s = self.key_stream(key, nonce, i as u16 + 1);
+            chunk.iter_mut().zip(s.iter()).for_each(|(d, s)| *d ^= s);
+        }
+    }
+
+    fn check_mic_len(mic_len: usize) -> Result<(), SecurityError> {
+        match mic_len {
+            0 | 4 | 6 | 8 | 10 | 12 | 14 | 16 => Ok(()),
+            _ => Err(SecurityError::Unsupported),
+        }
+    }
+}
+
+impl<B: BlockCipher> Aead for CcmStar<B> {
+    fn encrypt(
+        &mut self,
+        key: &[u8; KEY_LEN],
+        nonce: &[u8; NONCE_LEN],
+        aad: &[u8],
+        data: &mut [u8],
+        mic: &mut [u8],
+    ) -> Result<(), SecurityError> {
+        Self::check_mic_len(mic.len())?;
+
+        let t = self.auth(key, nonce, aad, data, mic.len());
+        self.ctr(key, nonce, data);
+
+        // MIC is the tag encrypted with the first key stream block
+        let s0 = self.key_stream(key, nonce, 0);
+        for (i, m) in mic.iter_mut().enumerate() {
+            *m = t[i] ^ s0[i];
+        }
+
+        Ok(())
+    }
+
+    fn decrypt(
+        &mut self,
+        key: &[u8; KEY_LEN],
+        nonce: &[u8; NONCE_LEN],
+        aad: &[u8],
+        data: &mut [u8],
+        mic: &[u8],
+    ) -> Result<(), SecurityError> {
+        Self::check_mic_len(mic.len())?;
+
+        self.ctr(key, nonce, data);
+        let t = self.auth(key, nonce, aad, data, mic.len());
+
+        // Compare without early exit
+        let s0 = self.key_stream(key, nonce, 0);
+        let diff = mic
+            .iter()
+            .enumerate()
+            .fold(0, |d, (i, m)| d | (t[i] ^ s0[i] ^ m));
+
+        match diff {
+            0 => Ok(()),
+            _ => Err(SecurityError::Mic),
+        }
+    }
+}
+
+/// Software AES-128 block cipher
+#[cfg(any(test, feature = "soft-aes"))]
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct SoftAes;
+
+#[cfg(any(test, feature = "soft-aes"))]
+impl BlockCipher for SoftAes {
+    fn encrypt_block(&mut self, key: &[u8; KEY_LEN], block: &mut [u8; BLOCK_LEN]) {
+        use aes::{BlockEncrypt, NewBlockCipher};
+
+        let cipher = aes::Aes128::new(key.into());
+        cipher.encrypt_block(block.into());
+    }
+}
+
+/// Software CCM* implementation
+#[cfg(any(test, feature = "soft-aes"))]
+pub type SoftCcm = CcmStar<SoftAes>;
+
+/// Build the CCM* nonce for a frame from the originator extended address
+pub fn nonce(source: ExtendedAddress, frame_counter: u32, level: SecurityLevel) -> [u8; NONCE_LEN] {
+    let mut n = [0u8; NONCE_LEN];
+    n[..8].copy_from_slice(&source.0.to_be_bytes());
+    n[8..12].copy_from_slice(&frame_counter.to_be_bytes());
+    n[12] = level as u8;
+    n
+}
+
+/// Apply security to a packet, encrypting the payload and appending the MIC
+pub fn secure<A: Aead>(
+    aead: &mut A,
+    key: &[u8; KEY_LEN],
+    source: ExtendedAddress,
+    aux: AuxSecurityHeader,
+    p: &mut Packet,
+) -> Result<(), SecurityError> {
+    // Non-data frames would require the open and private fields to be split
+    if p.header.frame_type != FrameType::Data {
+        return Err(SecurityError::Unsupported);
+    }
+
+    p.header.version = FrameVersion::Ieee802154_2006;
+    p.aux_security = Some(aux);
+
+    // Encode the open fields (and payload) to form the additional data
+    let mut buff = [0u8; MAX_PAYLOAD_LEN];
+    let n = p
+        .encode(&mut buff, WriteFooter::No)
+        .map_err(|_| SecurityError::BufferFull)?;
+    let open_len = n - p.payload().len();
+
+    let mut data: Vec<u8, MAX_PAYLOAD_LEN> = Vec::from_slice(p.payload()).unwrap();
+    let mut mic = [0u8; MAX_MIC_LEN];
+    let mic = &mut mic[..aux.level.mic_len()];
+
+    let nonce = nonce(source, aux.frame_counter, aux.level);
+    match aux.level.encrypted() {
+        true => aead.encrypt(key, &nonce, &buff[..open_len], &mut data, mic)?,
+        false => aead.encrypt(key, &nonce, &buff[..n], &mut [], mic)?,
+    }
+
+    data.extend_from_slice(mic)
+        .map_err(|_| SecurityError::BufferFull)?;
+    p.set_payload(&data)
+        .map_err(|_| SecurityError::BufferFull)?;
+
+    Ok(())
+}
+
+/// Verify and remove security from a received packet, decrypting the payload
+/// and stripping the MIC. `raw` contains the received frame the packet was decoded from
+pub fn unsecure<A: Aead>(
+    aead: &mut A,
+    key: &[u8; KEY_LEN],
+    source: ExtendedAddress,
+    raw: &[u8],
+    p: &mut Packet,
+) -> Result<AuxSecurityHeader, SecurityError> {
+    let aux = p.aux_security.ok_or(SecurityError::Unsecured)?;
+
+    if p.header.frame_type != FrameType::Data {
+        return Err(SecurityError::Unsupported);
+    }
+
+    let mic_len = aux.level.mic_len();
+    let payload_len = p.payload().len();
+    if payload_len < mic_len || raw.len() < payload_len {
+        return Err(SecurityError::Mic);
+    }
+
+    // Payload follows the open fields, with the MIC at the end of the frame
+    let open_len = raw.len() - payload_len;
+    let (data, mic) = p.payload().split_at(payload_len - mic_len);
+    let mut data: Vec<u8, MAX_PAYLOAD_LEN> = Vec::from_slice(data).unwrap();
+
+    let nonce = nonce(source, aux.frame_counter, aux.level);
+    match aux.level.encrypted() {
+        true => aead.decrypt(key, &nonce, &raw[..open_len], &mut data, mic)?,
+        false => aead.decrypt(key, &nonce, &raw[..raw.len() - mic_len], &mut [], mic)?,
+    }
+
+    p.set_payload(&data)
+        .map_err(|_| SecurityError::BufferFull)?;
+
+    Ok(aux)
+}
+
+/// Received frame counters for replay protection
+#[derive(Debug, Clone, PartialEq)]
+pub struct FrameCounters<const N: usize = DEFAULT_MAX_NEIGHBOURS> {
+    entries: Vec<(ExtendedAddress, u32), N>,
+}
+
+impl<const N: usize> Default for FrameCounters<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize> FrameCounters<N> {
+    /// Create a new (empty) frame counter table
+    pub fn new() -> Self {
+        Self {
+            entries: Vec::new(),
+        }
+    }
+
+    /// Check and update the frame counter for an originator, rejecting frames with
+    /// counters not greater than the last received. Where the table is full the
+    /// first entry is evicted
+    pub fn check(&mut self, source: ExtendedAddress, counter: u32) -> Result<(), SecurityError> {
+        if let Some(e) = self.entries.iter_mut().find(|(a, _)| *a == source) {
+            if counter <= e.1 {
+                return Err(SecurityError::Replay);
+            }
+
+            e.1 = counter;
+            return Ok(());
+        }
+
+        if self.entries.is_full() {
+            self.entries.swap_remove(0);
+        }
+        let _ = self.entries.push((source, counter));
+
+        Ok(())
+    }
+
+    /// Fetch the last received frame counter for an originator
+    pub fn get(&self, source: &ExtendedAddress) -> Option<u32> {
+        self.entries
+            .iter()
+            .find(|(a, _)| a == source)
+            .map(|(_, c)| *c)
+    }
+}
+
+/// MAC security state
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct SecurityState {
+    /// Frame counter for the next outgoing secured frame
+    pub frame_counter: u32,
+    pub counters: FrameCounters,
+}
+
+impl SecurityState {
+    pub(crate) fn new() -> Self {
+        Self {
+            frame_counter: 0,
+            counters: FrameCounters::new(),
+        }
+    }
+}
+
+impl<R, T, S, const TXQ: usize, const RXQ: usize> Mac<R, T, S, TXQ, RXQ>
+where
+    R: Radio,
+    T: Timer,
+    S: Aead,
+{
+    /// Fetch the frame counter for the next outgoing secured frame
+    pub fn frame_counter(&self) -> u32 {
+        self.security.frame_counter
+    }
+
+    /// Secure an outgoing packet using the next frame counter
+    pub(crate) fn secure_packet(&mut self, p: &mut Packet) -> Result<(), SecurityError> {
+        let cfg = match &self.config.security {
+            Some(c) if c.level != SecurityLevel::None => c,
+            _ => return Ok(()),
+        };
+
+        let frame_counter = self.security.frame_counter;
+        if frame_counter == u32::MAX {
+            return Err(SecurityError::CounterExhausted);
+        }
+
+        let aux = AuxSecurityHeader {
+            level: cfg.level,
+            key_id: cfg.key_id,
+            frame_counter,
+        };
+        secure(&mut self.aead, &cfg.key, self.address, aux, p)?;
+
+        self.security.frame_counter += 1;
+
+        Ok(())
+    }
+
+    /// Verify and decrypt a received packet, rejecting unsecured data frames
+    /// where security is configured
+    pub(crate) fn unsecure_packet(
+        &mut self,
+        raw: &[u8],
+        p: &mut Packet,
+    ) -> Result<(), SecurityError> {
+        let cfg = match &self.config.security {
+            Some(c) => c,
+            None if p.aux_security.is_none() => return Ok(()),
+            None => return Err(SecurityError::UnknownKey),
+        };
+
+        let aux = match p.aux_security {
+            Some(a) => a,
+            // Beacons and MAC commands are accepted in the clear
+            None if p.header.frame_type != FrameType::Data => return Ok(()),
+            None => return Err(SecurityError::Unsecured),
+        };
+
+        if aux.key_id != cfg.key_id {
+            return Err(SecurityError::UnknownKey);
+        }
+        if aux.level != cfg.level {
+            return Err(SecurityError::Unsupported);
+        }
+
+        // The originator extended address is required for the nonce
+        let source = match p.header.source {
+            Address::Extended(_, e) => e,
+            Address::Short(_, s) => self
+                .assoc_table
+                .lookup_short(&s)
+                .map(|a| a.extended)
+                .ok_or(SecurityError::UnknownDevice)?,
+            _ => return Err(SecurityError::UnknownDevice),
+        };
+
+        unsecure(&mut self.aead, &cfg.key, source, raw, p)?;
+
+        // Replay protection, only updated for authentic frames
+        self.security.counters.check(source, aux.frame_counter)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use ieee802154::mac::PanId;
+
+    use super::*;
+
+    const KEY: [u8; KEY_LEN] = [
+        0xc0, 0xc1, 0xc2, 0xc3, 0xc4, 0xc5, 0xc6, 0xc7, 0xc8, 0xc9, 0xca, 0xcb, 0xcc, 0xcd, 0xce,
+        0xcf,
+    ];
+
+    const SOURCE: ExtendedAddress = ExtendedAddress(0xacde480000000001);
+    const DEST: ExtendedAddress = ExtendedAddress(0xacde480000000002);
+
+    #[test]
+    fn aux_security_header() {
+        let tests = [
+            (
+                AuxSecurityHeader {
+                    level: SecurityLevel::EncMic64,
+                    key_id: KeyId::Implicit,
+                    frame_counter: 5,
+                },
+                &[0x06, 0x05, 0x00, 0x00, 0x00][..],
+            ),
+            (
+                AuxSecurityHeader {
+                    level: SecurityLevel::EncMic32,
+                    key_id: KeyId::Index(1),
+                    frame_counter: 0x01020304,
+                },
+                &[0x0d, 0x04, 0x03, 0x02, 0x01, 0x01][..],
+            ),
+            (
+                AuxSecurityHeader {
+                    level: SecurityLevel::Mic128,
+                    key_id: KeyId::Source4([0xaa, 0xbb, 0xcc, 0xdd], 2),
+                    frame_counter: 1,
+                },
+                &[0x13, 0x01, 0x00, 0x00, 0x00, 0xaa, 0xbb, 0xcc, 0xdd, 0x02][..],
+            ),
+        ];
+
+        for (h, b) in &tests {
+            let mut buff = [0u8; 16];
+            let n = h.encode(&mut buff);
+            assert_eq!(&buff[..n], *b);
+
+            assert_eq!(AuxSecurityHeader::decode(b).unwrap(), (*h, b.len()));
+        }
+    }
+
+    /// RFC3610 Packet Vector #1 (CCM with M = 8, L = 2)
+    #[test]
+    fn ccm_rfc3610() {
+        let nonce = [
+            0x00, 0x00, 0x00, 0x03, 0x02, 0x01, 0x00, 0xa0, 0xa1, 0xa2, 0xa3, 0xa4, 0xa5,
+        ];
+        let aad = [0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07];
+        let mut data: std::vec::Vec<u8> = (0x08..0x1f).collect();
+        let mut mic = [0u8; 8];
+
+        let mut ccm = CcmStar(SoftAes);
+        ccm.encrypt(&KEY, &nonce, &aad, &mut data, &mut mic)
+            .unwrap();
+
+        assert_eq!(
+            &data[..],
+            &[
+                0x58, 0x8c, 0x97, 0x9a, 0x61, 0xc6, 0x63, 0xd2, 0xf0, 0x66, 0xd0, 0xc2, 0xc0, 0xf9,
+                0x89, 0x80, 0x6d, 0x5f, 0x6b, 0x61, 0xda, 0xc3, 0x84
+            ]
+        );
+        assert_eq!(mic, [0x17, 0xe8, 0xd1, 0x2c, 0xfd, 0xf9, 0x26, 0xe0]);
+
+        ccm.decrypt(&KEY, &nonce, &aad, &mut data, &mic).unwrap();
+        assert_eq!(data, (0x08..0x1f).collect::<std::vec::Vec<u8>>());
+
+        // Tampered MICs are rejected
+        mic[0] ^= 0x01;
+        assert_eq!(
+            ccm.decrypt(&KEY, &nonce, &aad, &mut data, &mic),
+            Err(SecurityError::Mic)
+        );
+    }
+
+    /// 802.15.4-2006 Annex C.2.2, data frame with ENC security
+    #[test]
+    fn kat_data_frame() {
+        let frame = [
+            0x69, 0xdc, 0x84, 0x21, 0x43, 0x02, 0x00, 0x00, 0x00, 0x00, 0x48, 0xde, 0xac, 0x01,
+            0x00, 0x00, 0x00, 0x00, 0x48, 0xde, 0xac, 0x04, 0x05, 0x00, 0x00, 0x00, 0xd4, 0x3e,
+            0x02, 0x2b,
+        ];
+
+        let mut p = Packet::data(
+            Address::Extended(PanId(0x4321), DEST),
+            Address::Extended(PanId(0x4321), SOURCE),
+            0x84,
+            &[0x61, 0x62, 0x63, 0x64],
+            true,
+        )
+        .unwrap();
+        p.header.pan_id_compress = true;
+
+        let aux = AuxSecurityHeader {
+            level: SecurityLevel::Enc,
+            key_id: KeyId::Implicit,
+            frame_counter: 5,
+        };
+
+        let mut ccm = CcmStar(SoftAes);
+        secure(&mut ccm, &KEY, SOURCE, aux, &mut p).unwrap();
+
+        let mut buff = [0u8; 64];
+        let n = p.encode(&mut buff, WriteFooter::No).unwrap();
+        assert_eq!(&buff[..n], &frame);
+
+        // Decode and decrypt the secured frame
+        let mut p = Packet::decode(&frame, false).unwrap();
+        assert_eq!(p.aux_security, Some(aux));
+
+        unsecure(&mut ccm, &KEY, SOURCE, &frame, &mut p).unwrap();
+        assert_eq!(p.payload(), &[0x61, 0x62, 0x63, 0x64]);
+    }
+
+    /// 802.15.4-2006 Annex C.2.3, MAC command frame with ENC-MIC-64 security
+    #[test]
+    fn kat_command_frame() {
+        // Header, auxiliary security header, and command frame identifier
+        let aad = [
+            0x2b, 0xdc, 0x84, 0x21, 0x43, 0x02, 0x00, 0x00, 0x00, 0x00, 0x48, 0xde, 0xac, 0xff,
+            0xff, 0x01, 0x00, 0x00, 0x00, 0x00, 0x48, 0xde, 0xac, 0x06, 0x05, 0x00, 0x00, 0x00,
+            0x01,
+        ];
+        let mut data = [0xce];
+        let mut mic = [0u8; 8];
+
+        let nonce = nonce(SOURCE, 5, SecurityLevel::EncMic64);
+
+        let mut ccm = CcmStar(SoftAes);
+        ccm.encrypt(&KEY, &nonce, &aad, &mut data, &mut mic)
+            .unwrap();
+
+        assert_eq!(data, [0xd8]);
+        assert_eq!(mic, [0x4f, 0xde, 0x52, 0x90, 0x61, 0xf9, 0xc6, 0xf1]);
+    }
+
+    #[test]
+    fn secure_mic_only() {
+        let mut p = Packet::data(
+            Address::Extended(PanId(0x4321), DEST),
+            Address::Extended(PanId(0x4321), SOURCE),
+            1,
+            &[0x11, 0x22, 0x33],
+            false,
+        )
+        .unwrap();
+
+        let aux = AuxSecurityHeader {
+            level: SecurityLevel::Mic32,
+            key_id: KeyId::Index(1),
+            frame_counter: 10,
+        };
+
+        let mut ccm = CcmStar(SoftAes);
+        secure(&mut ccm, &KEY, SOURCE, aux, &mut p).unwrap();
+
+        // Payload is sent in the clear with the MIC appended
+        assert_eq!(&p.payload()[..3], &[0x11, 0x22, 0x33]);
+        assert_eq!(p.payload().len(), 3 + 4);
+
+        let mut buff = [0u8; 64];
+        let n = p.encode(&mut buff, WriteFooter::No).unwrap();
+
+        // Modified frames are rejected
+        let mut tampered = buff;
+        tampered[n - 5] ^= 0x01;
+        let mut t = Packet::decode(&tampered[..n], false).unwrap();
+        assert_eq!(
+            unsecure(&mut ccm, &KEY, SOURCE, &tampered[..n], &mut t),
+            Err(SecurityError::Mic)
+        );
+
+        let mut p = Packet::decode(&buff[..n], false).unwrap();
+        assert_eq!(
+            unsecure(&mut ccm, &KEY, SOURCE, &buff[..n], &mut p),
+            Ok(aux)
+        );
+        assert_eq!(p.payload(), &[0x11, 0x22, 0x33]);
+    }
+
+    #[test]
+    fn frame_counters() {
+        let mut c = FrameCounters::<1>::new();
+
+        c.check(SOURCE, 1).unwrap();
+        c.check(SOURCE, 3).unwrap();
+        assert_eq!(c.check(SOURCE, 3), Err(SecurityError::Replay));
+        assert_eq!(c.check(SOURCE, 2), Err(SecurityError::Replay));
+        assert_eq!(c.get(&SOURCE), Some(3));
+
+        // Full tables evict existing entries
+        c.check(DEST, 1).unwrap();
+        assert_eq!(c.get(&SOURCE), None);
+    }
+}
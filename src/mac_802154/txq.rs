@@ -0,0 +1,277 @@
+//! 802.15.4 Transmit Queue
+//!
+//! Frames are queued for transmission by priority class, with control (MAC command)
+//! frames served ahead of data frames so these are not held behind bulk traffic,
+//! and FIFO ordering retained within each class. Frames may carry a deadline after
+//! which they are dropped rather than transmitted stale.
+//
+// https://github.com/rust-iot/rust-lpwan
+// Copyright 2021 Ryan Kurte
+
+use heapless::Vec;
+use ieee802154::mac::FrameContent;
+
+use super::{Packet, TxState};
+use crate::Ts;
+
+/// Default number of queued control frames
+pub const DEFAULT_MAX_CONTROL: usize = 4;
+
+/// Default number of queued data frames
+pub const DEFAULT_MAX_DATA: usize = 16;
+
+/// Transmit priority class
+#[derive(Debug, Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum TxPriority {
+    /// MAC command frames (association, data requests, realignment etc.)
+    Control,
+    /// Data frames
+    Data,
+}
+
+impl TxPriority {
+    /// Fetch the priority class for a packet
+    pub fn of(packet: &Packet) -> Self {
+        match packet.content {
+            FrameContent::Command(_) => TxPriority::Control,
+            _ => TxPriority::Data,
+        }
+    }
+}
+
+/// Queue of frames pending transmission.
+///
+/// The head of the queue is the frame currently being sent, where a data frame has
+/// been started (scheduled for CSMA or GTS transmission) this is retained at the head
+/// until complete, otherwise the oldest control frame is served before data frames.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TxQueue<const C: usize = DEFAULT_MAX_CONTROL, const D: usize = DEFAULT_MAX_DATA> {
+    control: Vec<(TxState, Packet), C>,
+    data: Vec<(TxState, Packet), D>,
+}
+
+impl<const C: usize, const D: usize> Default for TxQueue<C, D> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const C: usize, const D: usize> TxQueue<C, D> {
+    /// Create a new (empty) transmit queue
+    pub fn new() -> Self {
+        Self {
+            control: Vec::new(),
+            data: Vec::new(),
+        }
+    }
+
+    /// Add a frame to the queue for its priority class,
+    /// per [`TxState::priority`] where set or otherwise the frame type
+    pub fn enqueue(&mut self, tx: (TxState, Packet)) -> Result<(), (TxState, Packet)> {
+        match tx.0.priority.unwrap_or_else(|| TxPriority::of(&tx.1)) {
+            TxPriority::Control => self.control.push(tx),
+            TxPriority::Data => self.data.push(tx),
+        }
+    }
+
+    /// Fetch the frame at the head of the queue
+    pub fn peek(&self) -> Option<&(TxState, Packet)> {
+        match self.head()? {
+            TxPriority::Control => self.control.first(),
+            TxPriority::Data => self.data.first(),
+        }
+    }
+
+    /// Fetch a mutable reference to the frame at the head of the queue
+    pub fn peek_mut(&mut self) -> Option<&mut (TxState, Packet)> {
+        match self.head()? {
+            TxPriority::Control => self.control.first_mut(),
+            TxPriority::Data => self.data.first_mut(),
+        }
+    }
+
+    /// Remove the frame at the head of the queue
+    pub fn dequeue(&mut self) -> Option<(TxState, Packet)> {
+        match self.head()? {
+            TxPriority::Control => Some(self.control.remove(0)),
+            TxPriority::Data => Some(self.data.remove(0)),
+        }
+    }
+
+    /// Remove a frame not yet started with a deadline prior to the provided time, if any.
+    /// Frames already started are completed via the usual retry handling
+    pub fn expire(&mut self, now: Ts) -> Option<(TxState, Packet)> {
+        let expired = |(s, _): &(TxState, Packet)| {
+            s.retries == 0 && s.expires.map(|t| t < now).unwrap_or(false)
+        };
+
+        if let Some(i) = self.control.iter().position(expired) {
+            return Some(self.control.remove(i));
+        }
+        if let Some(i) = self.data.iter().position(expired) {
+            return Some(self.data.remove(i));
+        }
+
+        None
+    }
+
+    /// Fetch the earliest deadline of queued frames
+    pub fn next_expiry(&self) -> Option<Ts> {
+        self.iter().filter_map(|(s, _)| s.expires).min()
+    }
+
+    /// Iterate over queued frames, control prior to data frames
+    pub fn iter(&self) -> impl Iterator<Item = &(TxState, Packet)> {
+        self.control.iter().chain(self.data.iter())
+    }
+
+    /// Iterate mutably over queued frames, control prior to data frames
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut (TxState, Packet)> {
+        self.control.iter_mut().chain(self.data.iter_mut())
+    }
+
+    /// Fetch the remaining capacity for a priority class
+    pub fn capacity(&self, priority: TxPriority) -> usize {
+        match priority {
+            TxPriority::Control => C - self.control.len(),
+            TxPriority::Data => D - self.data.len(),
+        }
+    }
+
+    /// Check whether the queue for a priority class is full
+    pub fn is_full(&self, priority: TxPriority) -> bool {
+        self.capacity(priority) == 0
+    }
+
+    /// Check whether frames are queued for a priority class
+    pub fn is_pending(&self, priority: TxPriority) -> bool {
+        match priority {
+            TxPriority::Control => !self.control.is_empty(),
+            TxPriority::Data => !self.data.is_empty(),
+        }
+    }
+
+    /// Fetch the number of queued frames
+    pub fn len(&self) -> usize {
+        self.control.len() + self.data.len()
+    }
+
+    /// Check whether the queue is empty
+    pub fn is_empty(&self) -> bool {
+        self.control.is_empty() && self.data.is_empty()
+    }
+
+    /// Select the priority class at the head of the queue
+    fn head(&self) -> Option<TxPriority> {
+        let data_started = self.data.first().map(|(s, _)| s.retries > 0);
+
+        match (data_started, self.control.is_empty()) {
+            (Some(true), _) => Some(TxPriority::Data),
+            (_, false) => Some(TxPriority::Control),
+            (Some(false), true) => Some(TxPriority::Data),
+            (None, true) => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use ieee802154::mac::command::Command;
+    use ieee802154::mac::{Address, PanId, ShortAddress};
+
+    use super::*;
+
+    fn tx(handle: u32, packet: Packet) -> (TxState, Packet) {
+        let state = TxState {
+            handle,
+            ..Default::default()
+        };
+        (state, packet)
+    }
+
+    #[test]
+    fn tx_priority() {
+        let dest = Address::Short(PanId(1), ShortAddress(2));
+        let src = Address::Short(PanId(1), ShortAddress(1));
+
+        let mut q = TxQueue::<2, 4>::new();
+
+        for i in 0..3 {
+            let p = Packet::data(dest, src, i as u8, &[0xaa], true).unwrap();
+            q.enqueue(tx(i, p)).unwrap();
+        }
+        q.enqueue(tx(3, Packet::command(dest, src, 3, Command::DataRequest)))
+            .unwrap();
+
+        assert_eq!(q.capacity(TxPriority::Control), 1);
+        assert_eq!(q.capacity(TxPriority::Data), 1);
+
+        // Control frames are served ahead of data frames
+        assert_eq!(q.peek().map(|(s, _)| s.handle), Some(3));
+        assert_eq!(q.dequeue().map(|(s, _)| s.handle), Some(3));
+        assert_eq!(q.peek().map(|(s, _)| s.handle), Some(0));
+
+        // Started data frames are retained at the head until complete
+        q.peek_mut().unwrap().0.retries += 1;
+        q.enqueue(tx(4, Packet::command(dest, src, 4, Command::DataRequest)))
+            .unwrap();
+        assert_eq!(q.dequeue().map(|(s, _)| s.handle), Some(0));
+
+        // Otherwise ordering is FIFO within each class
+        let order: std::vec::Vec<_> = core::iter::from_fn(|| q.dequeue())
+            .map(|(s, _)| s.handle)
+            .collect();
+        assert_eq!(order, &[4, 1, 2]);
+        assert!(q.is_empty());
+    }
+
+    #[test]
+    fn tx_priority_override() {
+        let dest = Address::Short(PanId(1), ShortAddress(2));
+        let src = Address::Short(PanId(1), ShortAddress(1));
+
+        let mut q = TxQueue::<2, 4>::new();
+
+        q.enqueue(tx(0, Packet::data(dest, src, 0, &[0xaa], true).unwrap()))
+            .unwrap();
+
+        // Data frames may be sent with control priority
+        let mut urgent = tx(1, Packet::data(dest, src, 1, &[0xbb], true).unwrap());
+        urgent.0.priority = Some(TxPriority::Control);
+        q.enqueue(urgent).unwrap();
+
+        assert_eq!(q.capacity(TxPriority::Control), 1);
+        assert_eq!(q.dequeue().map(|(s, _)| s.handle), Some(1));
+        assert_eq!(q.dequeue().map(|(s, _)| s.handle), Some(0));
+    }
+
+    #[test]
+    fn tx_expiry() {
+        let dest = Address::Short(PanId(1), ShortAddress(2));
+        let src = Address::Short(PanId(1), ShortAddress(1));
+
+        let mut q = TxQueue::<2, 4>::new();
+
+        let mut a = tx(0, Packet::data(dest, src, 0, &[0xaa], true).unwrap());
+        a.0.expires = Some(100);
+        let mut b = tx(1, Packet::data(dest, src, 1, &[0xbb], true).unwrap());
+        b.0.expires = Some(50);
+        q.enqueue(a).unwrap();
+        q.enqueue(b).unwrap();
+        q.enqueue(tx(2, Packet::data(dest, src, 2, &[0xcc], true).unwrap()))
+            .unwrap();
+
+        assert_eq!(q.next_expiry(), Some(50));
+        assert!(q.expire(50).is_none());
+
+        // Frames past their deadline are removed from any position
+        assert_eq!(q.expire(51).map(|(s, _)| s.handle), Some(1));
+        assert!(q.expire(51).is_none());
+
+        // Started frames are not expired
+        q.peek_mut().unwrap().0.retries += 1;
+        assert!(q.expire(200).is_none());
+        assert_eq!(q.len(), 2);
+    }
+}
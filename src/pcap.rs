@@ -0,0 +1,183 @@
+//! Packet capture
+//!
+//! A [`PcapSink`] may be set on the radio [`Base`](crate::base::Base) (or via
+//! [`Mac::set_capture`](crate::mac_802154::Mac::set_capture)) to observe exactly the
+//! bytes given to and received from the radio, with [`PcapWriter`] (requiring `std`)
+//! writing these to pcap files using `LINKTYPE_IEEE802_15_4_NOFCS` so captures may
+//! be opened directly in Wireshark, including dissection of 6LoWPAN payloads.
+//! No capture is performed where a sink is not set.
+//
+// https://github.com/rust-iot/rust-lpwan
+// Copyright 2021 Ryan Kurte
+
+use core::fmt;
+
+/// Direction of a captured frame
+#[derive(Debug, Copy, Clone, PartialEq, strum::Display)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Direction {
+    Tx,
+    Rx,
+}
+
+/// Sink for captured radio frames
+pub trait PcapSink {
+    /// Capture a frame transmitted or received at `ts_us` microseconds
+    fn capture(&mut self, dir: Direction, ts_us: u64, frame: &[u8]);
+}
+
+/// Optional capture sink held by the radio base
+#[derive(Default)]
+pub(crate) struct Capture(Option<&'static mut dyn PcapSink>);
+
+impl Capture {
+    /// Set or clear the capture sink
+    pub(crate) fn set(&mut self, sink: Option<&'static mut dyn PcapSink>) {
+        self.0 = sink;
+    }
+
+    /// Pass a frame to the sink where set
+    #[inline]
+    pub(crate) fn capture(&mut self, dir: Direction, ts_us: u64, frame: &[u8]) {
+        if let Some(s) = self.0.as_mut() {
+            s.capture(dir, ts_us, frame);
+        }
+    }
+}
+
+impl fmt::Debug for Capture {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Capture({})", self.0.is_some())
+    }
+}
+
+/// Sinks are exclusively borrowed and so are not carried over to clones
+impl Clone for Capture {
+    fn clone(&self) -> Self {
+        Self(None)
+    }
+}
+
+/// Capture sinks are not considered in comparisons
+impl PartialEq for Capture {
+    fn eq(&self, _other: &Self) -> bool {
+        true
+    }
+}
+
+/// pcap link type for 802.15.4 frames without FCS
+pub const LINKTYPE_IEEE802_15_4_NOFCS: u32 = 230;
+
+/// pcap snapshot length
+pub const PCAP_SNAPLEN: u32 = 65535;
+
+#[cfg(feature = "std")]
+pub use writer::PcapWriter;
+
+#[cfg(feature = "std")]
+mod writer {
+    use std::io::{self, Write};
+
+    use super::{Direction, PcapSink, LINKTYPE_IEEE802_15_4_NOFCS, PCAP_SNAPLEN};
+
+    /// pcap file writer, recording captured frames with microsecond timestamps
+    pub struct PcapWriter<W: Write> {
+        w: W,
+        error: Option<io::Error>,
+    }
+
+    impl PcapWriter<io::BufWriter<std::fs::File>> {
+        /// Create a pcap file at the provided path
+        pub fn create<P: AsRef<std::path::Path>>(path: P) -> io::Result<Self> {
+            let f = std::fs::File::create(path)?;
+            Self::new(io::BufWriter::new(f))
+        }
+    }
+
+    impl<W: Write> PcapWriter<W> {
+        /// Create a new pcap writer, writing the file header
+        pub fn new(mut w: W) -> io::Result<Self> {
+            let mut h = [0u8; 24];
+            h[0..4].copy_from_slice(&0xa1b2c3d4u32.to_le_bytes());
+            h[4..6].copy_from_slice(&2u16.to_le_bytes());
+            h[6..8].copy_from_slice(&4u16.to_le_bytes());
+            // Timezone offset and timestamp accuracy are zero
+            h[16..20].copy_from_slice(&PCAP_SNAPLEN.to_le_bytes());
+            h[20..24].copy_from_slice(&LINKTYPE_IEEE802_15_4_NOFCS.to_le_bytes());
+
+            w.write_all(&h)?;
+
+            Ok(Self { w, error: None })
+        }
+
+        /// Write a frame record
+        pub fn write(&mut self, ts_us: u64, frame: &[u8]) -> io::Result<()> {
+            let len = frame.len() as u32;
+
+            let mut h = [0u8; 16];
+            h[0..4].copy_from_slice(&((ts_us / 1_000_000) as u32).to_le_bytes());
+            h[4..8].copy_from_slice(&((ts_us % 1_000_000) as u32).to_le_bytes());
+            h[8..12].copy_from_slice(&len.min(PCAP_SNAPLEN).to_le_bytes());
+            h[12..16].copy_from_slice(&len.to_le_bytes());
+
+            self.w.write_all(&h)?;
+            self.w
+                .write_all(&frame[..frame.len().min(PCAP_SNAPLEN as usize)])?;
+            self.w.flush()
+        }
+
+        /// Take the first error encountered while capturing, if any
+        pub fn take_error(&mut self) -> Option<io::Error> {
+            self.error.take()
+        }
+
+        /// Return the underlying writer
+        pub fn into_inner(self) -> W {
+            self.w
+        }
+    }
+
+    /// Frames are recorded in both directions, with write errors retained
+    /// for [`PcapWriter::take_error`]
+    impl<W: Write> PcapSink for PcapWriter<W> {
+        fn capture(&mut self, _dir: Direction, ts_us: u64, frame: &[u8]) {
+            if let Err(e) = self.write(ts_us, frame) {
+                self.error.get_or_insert(e);
+            }
+        }
+    }
+
+    #[cfg(test)]
+    mod test {
+        use std::vec::Vec;
+
+        use super::*;
+
+        #[test]
+        fn pcap_writer() {
+            let mut w = PcapWriter::new(Vec::new()).unwrap();
+            w.capture(Direction::Tx, 1_500_000, &[0x41, 0xc8, 0x01]);
+            w.capture(Direction::Rx, 2_000_001, &[0x02]);
+            assert!(w.take_error().is_none());
+
+            let b = w.into_inner();
+            assert_eq!(b.len(), 24 + 16 + 3 + 16 + 1);
+
+            // File header
+            assert_eq!(&b[0..4], &[0xd4, 0xc3, 0xb2, 0xa1]);
+            assert_eq!(&b[4..8], &[2, 0, 4, 0]);
+            assert_eq!(&b[20..24], &[230, 0, 0, 0]);
+
+            // Records
+            assert_eq!(&b[24..28], &1u32.to_le_bytes());
+            assert_eq!(&b[28..32], &500_000u32.to_le_bytes());
+            assert_eq!(&b[32..36], &3u32.to_le_bytes());
+            assert_eq!(&b[36..40], &3u32.to_le_bytes());
+            assert_eq!(&b[40..43], &[0x41, 0xc8, 0x01]);
+
+            assert_eq!(&b[43..47], &2u32.to_le_bytes());
+            assert_eq!(&b[47..51], &1u32.to_le_bytes());
+            assert_eq!(&b[59..], &[0x02]);
+        }
+    }
+}
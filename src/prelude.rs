@@ -5,16 +5,20 @@
 
 pub use crate::{Radio, RawPacket};
 
-pub use crate::{Mac, MacState};
+pub use crate::{Mac, MacEvent, MacState, StackStats};
 
 pub use crate::error::CoreError;
 pub use crate::timer::Timer as MacTimer;
 
-pub use crate::base::{Base as MacBase, BaseState as MacBaseState};
+pub use crate::base::{Base as MacBase, BaseState as MacBaseState, RadioEvent};
 
-pub use crate::mac_802154::{self, Mac as Mac802145};
+#[cfg(feature = "std")]
+pub use crate::pcap::PcapWriter;
+pub use crate::pcap::{Direction as CaptureDirection, PcapSink};
 
-pub use crate::sixlo::{SixLo, SixLoConfig, SixLoError};
+pub use crate::mac_802154::{self, Mac as Mac802145, DEFAULT_MAX_DATA, DEFAULT_MAX_RX};
+
+pub use crate::sixlo::{DatagramInfo, SixLo, SixLoConfig, SixLoError, SixLoMac, SixLoStats};
 
 pub use ieee802154::mac::{
     Address as MacAddress, AddressMode, ExtendedAddress, PanId, ShortAddress,
@@ -0,0 +1,1104 @@
+//! Virtual Radio Network
+//!
+//! Simulated shared-medium radios to assist with multi-node testing.
+//! [`VirtualRadio`] endpoints attached to a [`VirtualChannel`] implement the [`radio`]
+//! traits (and thus [`Radio`](crate::Radio)), with each transmitted frame delivered to
+//! all other endpoints subject to the configured propagation delay, loss probability,
+//! and RSSI of the [`Link`] between these.
+//!
+//! Frames are only received by endpoints in the receive state and tuned to the
+//! transmitting channel on arrival, and occupy the channel (as seen by
+//! [`Rssi::poll_rssi`]) for the propagation delay.
+//
+// https://github.com/rust-iot/rust-lpwan
+// Copyright 2021 Ryan Kurte
+
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::vec::Vec;
+
+use radio::{Busy, Channel, RadioState, Receive, ReceiveInfo, Rssi, State, Transmit};
+
+use crate::timer::Timer;
+
+/// RSSI reported for channels with no frames in flight
+pub const NOISE_FLOOR: i16 = -100;
+
+/// Default seed for frame loss
+const DEFAULT_SEED: u64 = 0x2545_f491_4f6c_dd1d;
+
+/// Virtual radio state
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum VirtualState {
+    Idle,
+    Sleep,
+    Receive,
+    Transmit,
+}
+
+impl RadioState for VirtualState {
+    fn idle() -> Self {
+        VirtualState::Idle
+    }
+
+    fn sleep() -> Self {
+        VirtualState::Sleep
+    }
+}
+
+/// Virtual radio error
+#[derive(Debug, Clone, PartialEq)]
+pub enum VirtualError {
+    /// No received frame available
+    NoFrame,
+    /// Received frame exceeds the provided buffer
+    BufferLength,
+}
+
+/// Virtual radio receive information
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct VirtualInfo {
+    /// Transmitting endpoint
+    pub source: usize,
+    /// Link RSSI
+    pub rssi: i16,
+}
+
+impl ReceiveInfo for VirtualInfo {
+    fn rssi(&self) -> i16 {
+        self.rssi
+    }
+}
+
+/// Link characteristics from one endpoint to another
+#[derive(Debug, Clone, PartialEq)]
+pub struct Link {
+    /// Propagation delay in ms
+    pub delay_ms: u64,
+    /// Probability of frame loss, from 0.0 (no loss) to 1.0 (all frames lost)
+    pub loss: f32,
+    /// Received signal strength in dBm
+    pub rssi: i16,
+}
+
+impl Default for Link {
+    fn default() -> Self {
+        Self {
+            delay_ms: 0,
+            loss: 0.0,
+            rssi: -60,
+        }
+    }
+}
+
+/// Frame in flight to an endpoint
+#[derive(Debug, Clone)]
+struct Frame {
+    source: usize,
+    channel: u16,
+    data: Vec<u8>,
+    start: u64,
+    arrival: u64,
+    rssi: i16,
+    /// Lost frames occupy the channel but are not received
+    lost: bool,
+}
+
+/// Endpoint state, held by the medium
+#[derive(Debug)]
+struct Endpoint {
+    state: VirtualState,
+    channel: u16,
+    /// Completion time of the current transmission
+    tx_end: u64,
+    in_flight: Vec<Frame>,
+    received: VecDeque<Frame>,
+}
+
+/// Shared medium state
+#[derive(Debug)]
+struct Medium {
+    endpoints: Vec<Endpoint>,
+    default_link: Link,
+    /// Per-link overrides, as (from, to, link)
+    links: Vec<(usize, usize, Link)>,
+    rng: u64,
+}
+
+impl Medium {
+    /// Fetch the link from one endpoint to another
+    fn link(&self, from: usize, to: usize) -> &Link {
+        self.links
+            .iter()
+            .find(|(f, t, _)| *f == from && *t == to)
+            .map(|(_, _, l)| l)
+            .unwrap_or(&self.default_link)
+    }
+
+    /// Generate a uniform random value in [0, 1) (xorshift64)
+    fn random(&mut self) -> f32 {
+        let mut x = self.rng;
+        x ^= x << 13;
+        x ^= x >> 7;
+        x ^= x << 17;
+        self.rng = x;
+
+        (x >> 40) as f32 / (1u64 << 24) as f32
+    }
+
+    /// Resolve frames arriving at an endpoint up to the provided time, these are
+    /// received only where the endpoint is receiving on the frame channel.
+    /// Endpoint states only change via radio operations, so resolving frames prior
+    /// to each operation applies the state at the time of arrival
+    fn settle(&mut self, id: usize, now: u64) {
+        let e = &mut self.endpoints[id];
+
+        let mut i = 0;
+        while i < e.in_flight.len() {
+            if e.in_flight[i].arrival > now {
+                i += 1;
+                continue;
+            }
+
+            let f = e.in_flight.remove(i);
+            if !f.lost && e.state == VirtualState::Receive && e.channel == f.channel {
+                e.received.push_back(f);
+            }
+        }
+    }
+
+    /// Start a transmission from an endpoint, placing the frame in flight to all others
+    fn transmit(&mut self, source: usize, now: u64, data: &[u8]) {
+        let channel = self.endpoints[source].channel;
+        let mut tx_end = now;
+
+        for to in 0..self.endpoints.len() {
+            if to == source {
+                continue;
+            }
+
+            let link = self.link(source, to).clone();
+            let lost = link.loss > 0.0 && self.random() < link.loss;
+            tx_end = tx_end.max(now + link.delay_ms);
+
+            self.endpoints[to].in_flight.push(Frame {
+                source,
+                channel,
+                data: data.to_vec(),
+                start: now,
+                arrival: now + link.delay_ms,
+                rssi: link.rssi,
+                lost,
+            });
+        }
+
+        let e = &mut self.endpoints[source];
+        e.state = VirtualState::Transmit;
+        e.tx_end = tx_end;
+    }
+
+    /// Fetch the strongest RSSI of frames on air at an endpoint
+    fn rssi(&self, id: usize, now: u64) -> i16 {
+        let e = &self.endpoints[id];
+
+        e.in_flight
+            .iter()
+            .filter(|f| f.channel == e.channel && f.start <= now && now < f.arrival)
+            .map(|f| f.rssi)
+            .max()
+            .unwrap_or(NOISE_FLOOR)
+    }
+}
+
+/// Virtual channel, a shared medium delivering frames between attached [`VirtualRadio`]s
+#[derive(Clone, Debug)]
+pub struct VirtualChannel<T> {
+    medium: Arc<Mutex<Medium>>,
+    timer: T,
+}
+
+impl<T: Timer + Clone> VirtualChannel<T> {
+    /// Create a new channel, using the provided timer for frame timing
+    pub fn new(timer: T) -> Self {
+        let medium = Medium {
+            endpoints: Vec::new(),
+            default_link: Link::default(),
+            links: Vec::new(),
+            rng: DEFAULT_SEED,
+        };
+
+        Self {
+            medium: Arc::new(Mutex::new(medium)),
+            timer,
+        }
+    }
+
+    /// Set the default link characteristics between endpoints
+    pub fn with_link(self, link: Link) -> Self {
+        self.medium.lock().unwrap().default_link = link;
+        self
+    }
+
+    /// Seed the random number generator used to determine frame loss
+    pub fn with_seed(self, seed: u64) -> Self {
+        self.medium.lock().unwrap().rng = seed.max(1);
+        self
+    }
+
+    /// Override the link characteristics from one endpoint to another
+    pub fn set_link(&self, from: usize, to: usize, link: Link) {
+        let mut m = self.medium.lock().unwrap();
+
+        m.links.retain(|(f, t, _)| !(*f == from && *t == to));
+        m.links.push((from, to, link));
+    }
+
+    /// Attach a new radio to the channel, initially idle on channel 0
+    pub fn attach(&self) -> VirtualRadio<T> {
+        let mut m = self.medium.lock().unwrap();
+
+        let id = m.endpoints.len();
+        m.endpoints.push(Endpoint {
+            state: VirtualState::Idle,
+            channel: 0,
+            tx_end: 0,
+            in_flight: Vec::new(),
+            received: VecDeque::new(),
+        });
+
+        VirtualRadio {
+            id,
+            medium: self.medium.clone(),
+            timer: self.timer.clone(),
+        }
+    }
+}
+
+/// Virtual radio endpoint, attached to a [`VirtualChannel`]
+#[derive(Clone, Debug)]
+pub struct VirtualRadio<T> {
+    id: usize,
+    medium: Arc<Mutex<Medium>>,
+    timer: T,
+}
+
+impl<T: Timer> VirtualRadio<T> {
+    /// Fetch the endpoint identifier, used to configure links and reported
+    /// as the source of received frames
+    pub fn id(&self) -> usize {
+        self.id
+    }
+
+    /// Apply an operation to this endpoint, following resolution of arrived frames
+    fn with<R>(&self, f: impl FnOnce(&mut Medium, u64) -> R) -> R {
+        let now = self.timer.ticks_ms();
+
+        let mut m = self.medium.lock().unwrap();
+        m.settle(self.id, now);
+
+        f(&mut m, now)
+    }
+}
+
+impl<T: Timer> State for VirtualRadio<T> {
+    type State = VirtualState;
+    type Error = VirtualError;
+
+    fn set_state(&mut self, state: Self::State) -> Result<(), Self::Error> {
+        let id = self.id;
+        self.with(|m, _| m.endpoints[id].state = state);
+        Ok(())
+    }
+
+    fn get_state(&mut self) -> Result<Self::State, Self::Error> {
+        let id = self.id;
+        Ok(self.with(|m, _| m.endpoints[id].state))
+    }
+}
+
+impl<T: Timer> Busy for VirtualRadio<T> {
+    type Error = VirtualError;
+
+    fn is_busy(&mut self) -> Result<bool, Self::Error> {
+        let id = self.id;
+        Ok(self.with(|m, _| m.endpoints[id].state == VirtualState::Transmit))
+    }
+}
+
+impl<T: Timer> Transmit for VirtualRadio<T> {
+    type Error = VirtualError;
+
+    fn start_transmit(&mut self, data: &[u8]) -> Result<(), Self::Error> {
+        let id = self.id;
+        self.with(|m, now| m.transmit(id, now, data));
+        Ok(())
+    }
+
+    /// Transmissions complete once the frame has arrived at all other endpoints
+    fn check_transmit(&mut self) -> Result<bool, Self::Error> {
+        let id = self.id;
+        let done = self.with(|m, now| {
+            let e = &mut m.endpoints[id];
+            match e.state {
+                VirtualState::Transmit if now >= e.tx_end => {
+                    e.state = VirtualState::Idle;
+                    true
+                }
+                _ => false,
+            }
+        });
+
+        Ok(done)
+    }
+}
+
+impl<T: Timer> Receive for VirtualRadio<T> {
+    type Info = VirtualInfo;
+    type Error = VirtualError;
+
+    fn start_receive(&mut self) -> Result<(), Self::Error> {
+        let id = self.id;
+        self.with(|m, _| m.endpoints[id].state = VirtualState::Receive);
+        Ok(())
+    }
+
+    /// Received frames are buffered until collected via [`Receive::get_received`]
+    fn check_receive(&mut self, _restart: bool) -> Result<bool, Self::Error> {
+        let id = self.id;
+        Ok(self.with(|m, _| !m.endpoints[id].received.is_empty()))
+    }
+
+    fn get_received(&mut self, buff: &mut [u8]) -> Result<(usize, Self::Info), Self::Error> {
+        let id = self.id;
+        let f = self
+            .with(|m, _| m.endpoints[id].received.pop_front())
+            .ok_or(VirtualError::NoFrame)?;
+
+        if f.data.len() > buff.len() {
+            return Err(VirtualError::BufferLength);
+        }
+        buff[..f.data.len()].copy_from_slice(&f.data);
+
+        let info = VirtualInfo {
+            source: f.source,
+            rssi: f.rssi,
+        };
+
+        Ok((f.data.len(), info))
+    }
+}
+
+impl<T: Timer> Rssi for VirtualRadio<T> {
+    type Error = VirtualError;
+
+    fn poll_rssi(&mut self) -> Result<i16, Self::Error> {
+        let id = self.id;
+        Ok(self.with(|m, now| m.rssi(id, now)))
+    }
+}
+
+impl<T: Timer> Channel for VirtualRadio<T> {
+    type Channel = u16;
+    type Error = VirtualError;
+
+    fn set_channel(&mut self, channel: &Self::Channel) -> Result<(), Self::Error> {
+        let id = self.id;
+        self.with(|m, _| m.endpoints[id].channel = *channel);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use ieee802154::mac::command::DisassociationReason;
+    use ieee802154::mac::ExtendedAddress;
+
+    use super::*;
+    use crate::error::CoreError;
+    use crate::log::debug;
+    use crate::mac_802154::{Config, Mac, Snapshot};
+    use crate::sixlo::{frag::FragConfig, nd::NdConfig, SixLo, SixLoConfig};
+    use crate::timer::mock::MockTimer;
+    use crate::{Mac as MacIf, MacEvent, MacState, TxFailure, TxStatus};
+
+    type VirtualMac = Mac<VirtualRadio<MockTimer>, MockTimer>;
+
+    /// Collect the next received frame, if any
+    fn rx(r: &mut VirtualRadio<MockTimer>) -> Option<(Vec<u8>, VirtualInfo)> {
+        let mut buff = [0u8; 256];
+
+        match r.check_receive(true).unwrap() {
+            true => {
+                let (n, info) = r.get_received(&mut buff).unwrap();
+                Some((buff[..n].to_vec(), info))
+            }
+            false => None,
+        }
+    }
+
+    #[test]
+    fn virtual_channel() {
+        let mut timer = MockTimer::new();
+        let channel = VirtualChannel::new(timer.clone());
+
+        let mut a = channel.attach();
+        let mut b = channel.attach();
+        let mut c = channel.attach();
+
+        let info = VirtualInfo {
+            source: a.id(),
+            rssi: Link::default().rssi,
+        };
+
+        // Frames are delivered to all other receiving endpoints
+        b.start_receive().unwrap();
+        c.start_receive().unwrap();
+        a.start_transmit(&[0x11, 0x22]).unwrap();
+        assert_eq!(a.check_transmit(), Ok(true));
+
+        assert_eq!(rx(&mut a), None);
+        assert_eq!(rx(&mut b), Some((std::vec![0x11, 0x22], info.clone())));
+        assert_eq!(rx(&mut c), Some((std::vec![0x11, 0x22], info.clone())));
+
+        // Frames are only received while in RX and tuned to the same channel
+        b.set_channel(&12).unwrap();
+        c.set_state(VirtualState::Sleep).unwrap();
+        a.start_transmit(&[0x33]).unwrap();
+        assert_eq!(a.check_transmit(), Ok(true));
+
+        c.start_receive().unwrap();
+        assert_eq!(rx(&mut b), None);
+        assert_eq!(rx(&mut c), None);
+
+        b.set_channel(&0).unwrap();
+
+        // Frames occupy the channel for the link delay, with the link RSSI
+        let link = Link {
+            delay_ms: 10,
+            rssi: -40,
+            ..Default::default()
+        };
+        channel.set_link(a.id(), b.id(), link.clone());
+        a.start_transmit(&[0x44]).unwrap();
+
+        assert_eq!(a.check_transmit(), Ok(false));
+        assert_eq!(b.poll_rssi(), Ok(-40));
+        assert_eq!(rx(&mut b), None);
+        assert_eq!(c.poll_rssi(), Ok(NOISE_FLOOR));
+        assert_eq!(rx(&mut c).map(|(d, _)| d), Some(std::vec![0x44]));
+
+        timer.set_ms(10);
+        assert_eq!(a.check_transmit(), Ok(true));
+        assert_eq!(b.poll_rssi(), Ok(NOISE_FLOOR));
+        assert_eq!(
+            rx(&mut b),
+            Some((
+                std::vec![0x44],
+                VirtualInfo {
+                    source: a.id(),
+                    rssi: -40
+                }
+            ))
+        );
+
+        // Lost frames are not received
+        channel.set_link(
+            a.id(),
+            b.id(),
+            Link {
+                loss: 1.0,
+                ..Default::default()
+            },
+        );
+        a.start_transmit(&[0x55]).unwrap();
+        assert_eq!(a.check_transmit(), Ok(true));
+
+        assert_eq!(rx(&mut b), None);
+        assert_eq!(rx(&mut c).map(|(d, _)| d), Some(std::vec![0x55]));
+    }
+
+    /// Setup a coordinator and two devices attached to a virtual channel
+    fn network(timer: &MockTimer) -> (VirtualMac, VirtualMac, VirtualMac) {
+        network_with(timer, Config::default())
+    }
+
+    /// Setup a coordinator and two devices using the provided configuration
+    fn network_with(timer: &MockTimer, cfg: Config) -> (VirtualMac, VirtualMac, VirtualMac) {
+        let channel = VirtualChannel::new(timer.clone());
+
+        let coord_cfg = Config {
+            pan_coordinator: true,
+            ..cfg.clone()
+        };
+        let coord = Mac::new(
+            ExtendedAddress(0xc0),
+            coord_cfg,
+            channel.attach(),
+            timer.clone(),
+        )
+        .unwrap();
+
+        let dev_a = Mac::new(
+            ExtendedAddress(0xa0),
+            cfg.clone(),
+            channel.attach(),
+            timer.clone(),
+        )
+        .unwrap();
+        let dev_b = Mac::new(ExtendedAddress(0xb0), cfg, channel.attach(), timer.clone()).unwrap();
+
+        (coord, dev_a, dev_b)
+    }
+
+    /// Advance time by 1 ms, ticking each MAC in turn
+    fn step(timer: &mut MockTimer, macs: &mut [&mut VirtualMac]) {
+        timer.inc();
+
+        for m in macs.iter_mut() {
+            m.tick().unwrap();
+        }
+    }
+
+    /// Run the network until both devices have associated with the coordinator,
+    /// allowing for requests lost to contention to expire and be retried
+    fn associate(
+        timer: &mut MockTimer,
+        coord: &mut VirtualMac,
+        dev_a: &mut VirtualMac,
+        dev_b: &mut VirtualMac,
+    ) {
+        let associated = MacState::Associated(coord.addr());
+
+        for _ in 0..30_000 {
+            step(timer, &mut [&mut *coord, &mut *dev_a, &mut *dev_b]);
+
+            if dev_a.state() == Ok(associated.clone()) && dev_b.state() == Ok(associated.clone()) {
+                return;
+            }
+        }
+
+        panic!("Association timeout at {} ms", timer.val());
+    }
+
+    /// Devices synchronise, associate, and exchange acknowledged data with a coordinator
+    #[test]
+    fn mac_network() {
+        let _ = simplelog::SimpleLogger::init(log::LevelFilter::Info, simplelog::Config::default());
+
+        let mut timer = MockTimer::new();
+        let (mut coord, mut dev_a, mut dev_b) = network(&timer);
+        let coord_addr = coord.addr();
+
+        // Devices sync on the first beacon
+        while timer.val() < Config::<u16>::default().superframe_duration() {
+            step(&mut timer, &mut [&mut coord, &mut dev_a, &mut dev_b]);
+        }
+        assert_eq!(dev_a.state(), Ok(MacState::Synced(coord_addr)));
+        assert_eq!(dev_b.state(), Ok(MacState::Synced(coord_addr)));
+
+        // Then associate, with distinct short addresses allocated by the coordinator
+        associate(&mut timer, &mut coord, &mut dev_a, &mut dev_b);
+
+        let (short_a, short_b) = (dev_a.short_addr.unwrap(), dev_b.short_addr.unwrap());
+        assert_ne!(short_a, short_b);
+
+        let mut allocated: Vec<_> = coord.associations().map(|a| a.short).collect();
+        allocated.sort_by_key(|s| s.0);
+        let mut expected = std::vec![short_a, short_b];
+        expected.sort_by_key(|s| s.0);
+        assert_eq!(allocated, expected);
+
+        // Data is delivered to the coordinator and acknowledged
+        let data = [0x11, 0x22, 0x33, 0x44];
+        let handle = dev_a.transmit(coord_addr, &data, true).unwrap();
+
+        let mut buff = [0u8; 256];
+        let (mut status, mut received) = (None, None);
+        for _ in 0..5_000 {
+            step(&mut timer, &mut [&mut coord, &mut dev_a, &mut dev_b]);
+
+            while let Some((h, s)) = dev_a.tx_status().unwrap() {
+                if h == handle {
+                    status = Some(s);
+                }
+            }
+            if let Some((n, info)) = coord.receive(&mut buff).unwrap() {
+                received = Some((buff[..n].to_vec(), info.source));
+            }
+
+            if status.is_some() && received.is_some() {
+                break;
+            }
+        }
+
+        assert_eq!(status, Some(TxStatus::Acked));
+        assert_eq!(received, Some((data.to_vec(), dev_a.addr())));
+    }
+
+    /// Beacon payloads set by the coordinator are available to devices following sync
+    #[test]
+    fn mac_beacon_payload() {
+        let mut timer = MockTimer::new();
+        let (mut coord, mut dev_a, mut dev_b) = network(&timer);
+        let coord_addr = coord.addr();
+
+        let payload = [
+            0x6c, 0x70, 0x77, 0x61, 0x6e, 0x2d, 0x6e, 0x65, 0x74, 0x00, 0x00, 0x00, 0x01, 0x02,
+            0x03, 0x04,
+        ];
+        coord.set_beacon_payload(&payload).unwrap();
+        assert_eq!(dev_a.last_beacon(&coord_addr), None);
+
+        while timer.val() < Config::<u16>::default().superframe_duration() {
+            step(&mut timer, &mut [&mut coord, &mut dev_a, &mut dev_b]);
+        }
+        assert_eq!(dev_a.state(), Ok(MacState::Synced(coord_addr)));
+
+        let (received, data) = dev_a.last_beacon(&coord_addr).unwrap();
+        assert!(received <= timer.val() as u64);
+        assert_eq!(data, &payload);
+        assert_eq!(
+            dev_b.last_beacon(&coord_addr).map(|(_, d)| d),
+            Some(&payload[..])
+        );
+    }
+
+    /// Devices restored from a snapshot resume the association without re-associating
+    #[test]
+    fn mac_resume() {
+        let _ = simplelog::SimpleLogger::init(log::LevelFilter::Info, simplelog::Config::default());
+
+        let mut timer = MockTimer::new();
+        let channel = VirtualChannel::new(timer.clone());
+
+        let coord_cfg = Config {
+            pan_coordinator: true,
+            ..Default::default()
+        };
+        let mut coord = Mac::new(
+            ExtendedAddress(0xc0),
+            coord_cfg,
+            channel.attach(),
+            timer.clone(),
+        )
+        .unwrap();
+        let coord_addr = coord.addr();
+
+        let mut dev = Mac::new(
+            ExtendedAddress(0xa0),
+            Config::default(),
+            channel.attach(),
+            timer.clone(),
+        )
+        .unwrap();
+
+        let associated = MacState::Associated(coord_addr);
+        for _ in 0..10_000 {
+            step(&mut timer, &mut [&mut coord, &mut dev]);
+            if dev.state() == Ok(associated.clone()) {
+                break;
+            }
+        }
+        assert_eq!(dev.state(), Ok(associated.clone()));
+
+        // Snapshot the device state, via the encoded form as would be persisted
+        let snapshot = Snapshot::decode(&dev.snapshot().encode()).unwrap();
+        assert_eq!(snapshot.parent, Some(coord_addr));
+        assert_eq!(snapshot.short_addr, dev.short_addr);
+
+        // Then reboot the device and restore from the snapshot
+        let short_addr = dev.short_addr;
+        drop(dev);
+
+        let mut dev = Mac::new(
+            ExtendedAddress(0xa0),
+            Config::default(),
+            channel.attach(),
+            timer.clone(),
+        )
+        .unwrap();
+        dev.restore(&snapshot).unwrap();
+        assert_eq!(dev.short_addr, short_addr);
+        assert_eq!(dev.frame_counter(), snapshot.frame_counter);
+
+        // The device re-synchronises with the parent and retains the association
+        for _ in 0..10_000 {
+            step(&mut timer, &mut [&mut coord, &mut dev]);
+            if dev.state() == Ok(associated.clone()) {
+                break;
+            }
+        }
+        assert_eq!(dev.state(), Ok(associated));
+        assert_eq!(dev.short_addr, short_addr);
+        assert_eq!(dev.stats().resyncs, 1);
+        assert_eq!(coord.associations().count(), 1);
+
+        // And exchanges acknowledged data with the coordinator
+        let data = [0x11, 0x22, 0x33, 0x44];
+        let handle = dev.transmit(coord_addr, &data, true).unwrap();
+
+        let mut buff = [0u8; 256];
+        let (mut status, mut received) = (None, None);
+        for _ in 0..5_000 {
+            step(&mut timer, &mut [&mut coord, &mut dev]);
+
+            while let Some((h, s)) = dev.tx_status().unwrap() {
+                if h == handle {
+                    status = Some(s);
+                }
+            }
+            if let Some((n, info)) = coord.receive(&mut buff).unwrap() {
+                received = Some((buff[..n].to_vec(), info.source));
+            }
+
+            if status.is_some() && received.is_some() {
+                break;
+            }
+        }
+
+        assert_eq!(status, Some(TxStatus::Acked));
+        assert_eq!(received, Some((data.to_vec(), dev.addr())));
+    }
+
+    /// Coordinators sharing a PAN ID move onto distinct PAN IDs, with associated devices
+    /// following their coordinator via coordinator realignment
+    #[test]
+    fn pan_conflict() {
+        let _ = simplelog::SimpleLogger::init(log::LevelFilter::Info, simplelog::Config::default());
+
+        let mut timer = MockTimer::new();
+        let channel = VirtualChannel::new(timer.clone());
+
+        let coord_cfg = Config {
+            pan_coordinator: true,
+            ..Default::default()
+        };
+        let mut coord_a = Mac::new(
+            ExtendedAddress(0xc0),
+            coord_cfg.clone(),
+            channel.attach(),
+            timer.clone(),
+        )
+        .unwrap();
+        let mut dev = Mac::new(
+            ExtendedAddress(0xa0),
+            Config::default(),
+            channel.attach(),
+            timer.clone(),
+        )
+        .unwrap();
+
+        // The device associates prior to the second coordinator starting
+        for _ in 0..10_000 {
+            step(&mut timer, &mut [&mut coord_a, &mut dev]);
+            if dev.state() == Ok(MacState::Associated(coord_a.addr())) {
+                break;
+            }
+        }
+        assert_eq!(dev.state(), Ok(MacState::Associated(coord_a.addr())));
+
+        // Offset the second coordinator so beacons do not collide
+        for _ in 0..500 {
+            step(&mut timer, &mut [&mut coord_a, &mut dev]);
+        }
+        let mut coord_b = Mac::new(
+            ExtendedAddress(0xc1),
+            coord_cfg,
+            channel.attach(),
+            timer.clone(),
+        )
+        .unwrap();
+        let pan_id = coord_a.pan_id();
+        assert_eq!(coord_b.pan_id(), pan_id);
+
+        // Coordinators detect the conflict and move onto distinct PAN IDs,
+        // with the device following its coordinator
+        let diverged = |a: &VirtualMac, b: &VirtualMac, d: &VirtualMac| {
+            a.pan_id() != pan_id
+                && a.pan_id() != b.pan_id()
+                && d.state() == Ok(MacState::Associated(a.addr()))
+        };
+        for _ in 0..60_000 {
+            step(&mut timer, &mut [&mut coord_a, &mut coord_b, &mut dev]);
+            if diverged(&coord_a, &coord_b, &dev) {
+                break;
+            }
+        }
+        assert!(diverged(&coord_a, &coord_b, &dev));
+
+        assert!(coord_a.stats().pan_conflicts >= 1);
+        assert!(coord_a.stats().realignments >= 1);
+        assert_eq!(dev.pan_id(), coord_a.pan_id());
+        assert!(dev.stats().realignments >= 1);
+
+        // Conflicts and resulting PAN ID changes are reported to the application
+        let mut events = Vec::new();
+        while let Some(e) = coord_a.event().unwrap() {
+            events.push(e);
+        }
+        assert!(events.contains(&MacEvent::PanConflict));
+        assert!(events.contains(&MacEvent::Realigned(coord_a.addr())));
+
+        // The PANs remain distinct
+        let (pan_a, pan_b) = (coord_a.pan_id(), coord_b.pan_id());
+        for _ in 0..10_000 {
+            step(&mut timer, &mut [&mut coord_a, &mut coord_b, &mut dev]);
+        }
+        assert_eq!(coord_a.pan_id(), pan_a);
+        assert_eq!(coord_b.pan_id(), pan_b);
+        assert_eq!(dev.state(), Ok(MacState::Associated(coord_a.addr())));
+    }
+
+    /// Devices leave via disassociation notifications or are evicted by the coordinator,
+    /// being removed from the coordinator association and neighbour tables
+    #[test]
+    fn mac_disassociate() {
+        let mut timer = MockTimer::new();
+        let (mut coord, mut dev_a, mut dev_b) = network(&timer);
+        let coord_addr = coord.addr();
+
+        associate(&mut timer, &mut coord, &mut dev_a, &mut dev_b);
+        let (addr_a, addr_b) = (dev_a.addr(), dev_b.addr());
+        assert_eq!(coord.associations().count(), 2);
+
+        // Devices leave once the notification is acknowledged
+        let handle = dev_a
+            .disassociate(DisassociationReason::DeviceLeave)
+            .unwrap();
+        let mut status = None;
+        for _ in 0..5_000 {
+            step(&mut timer, &mut [&mut coord, &mut dev_a, &mut dev_b]);
+
+            while let Some((h, s)) = dev_a.tx_status().unwrap() {
+                if h == handle {
+                    status = Some(s);
+                }
+            }
+            if dev_a.is_parked() {
+                break;
+            }
+        }
+
+        assert_eq!(status, Some(TxStatus::Acked));
+        assert_eq!(dev_a.state(), Ok(MacState::Sleep));
+        assert_eq!(dev_a.short_addr, None);
+        assert_eq!(dev_a.event(), Ok(Some(MacEvent::Disassociated(coord_addr))));
+
+        assert_eq!(coord.event(), Ok(Some(MacEvent::Disassociated(addr_a))));
+        assert_eq!(coord.associations().count(), 1);
+        assert!(coord.neighbours().all(|n| n.address != addr_a));
+
+        // Parked devices remain idle
+        for _ in 0..1_000 {
+            step(&mut timer, &mut [&mut coord, &mut dev_a, &mut dev_b]);
+        }
+        assert_eq!(dev_a.state(), Ok(MacState::Sleep));
+
+        // Evicted devices leave on receipt of the notification
+        let handle = coord.evict(&addr_b).unwrap();
+        assert_eq!(coord.associations().count(), 0);
+        assert_eq!(coord.evict(&addr_b).err(), Some(CoreError::NotAssociated));
+
+        let mut status = None;
+        for _ in 0..5_000 {
+            step(&mut timer, &mut [&mut coord, &mut dev_a, &mut dev_b]);
+
+            while let Some((h, s)) = coord.tx_status().unwrap() {
+                if h == handle {
+                    status = Some(s);
+                }
+            }
+            if dev_b.is_parked() && status.is_some() {
+                break;
+            }
+        }
+
+        assert_eq!(status, Some(TxStatus::Acked));
+        assert_eq!(dev_b.state(), Ok(MacState::Sleep));
+        assert_eq!(dev_b.event(), Ok(Some(MacEvent::Disassociated(coord_addr))));
+        assert_eq!(
+            dev_b.disassociate(DisassociationReason::DeviceLeave).err(),
+            Some(CoreError::NotAssociated)
+        );
+    }
+
+    /// Shutdown drops pending frames and parks the radio until resumed
+    #[test]
+    fn mac_shutdown() {
+        let mut timer = MockTimer::new();
+        let (mut coord, mut dev_a, mut dev_b) = network(&timer);
+        let coord_addr = coord.addr();
+
+        associate(&mut timer, &mut coord, &mut dev_a, &mut dev_b);
+        while dev_a.busy().unwrap() {
+            step(&mut timer, &mut [&mut coord, &mut dev_a, &mut dev_b]);
+        }
+        for _ in 0..10 {
+            step(&mut timer, &mut [&mut coord, &mut dev_a, &mut dev_b]);
+        }
+        while dev_a.tx_status().unwrap().is_some() {}
+
+        // Queued frames are dropped undelivered
+        let handles: Vec<_> = (0..3)
+            .map(|i| dev_a.transmit(coord_addr, &[i], true).unwrap())
+            .collect();
+        let tx_ok = dev_a.stats().tx_ok;
+        assert_eq!(dev_a.shutdown(), Ok(3));
+
+        let mut outcomes = Vec::new();
+        while let Some(o) = dev_a.tx_status().unwrap() {
+            outcomes.push(o);
+        }
+        let cancelled: Vec<_> = handles
+            .iter()
+            .map(|h| (*h, TxStatus::Failed(TxFailure::Cancelled)))
+            .collect();
+        assert_eq!(outcomes, cancelled);
+
+        // Parked MACs are idle, with nothing scheduled
+        assert_eq!(dev_a.state(), Ok(MacState::Sleep));
+        assert_eq!(dev_a.next_deadline(), None);
+        for _ in 0..1_000 {
+            step(&mut timer, &mut [&mut coord, &mut dev_a, &mut dev_b]);
+        }
+        assert_eq!(dev_a.state(), Ok(MacState::Sleep));
+        assert_eq!(dev_a.stats().tx_ok, tx_ok);
+
+        // Until resumed, restarting and re-associating
+        dev_a.resume().unwrap();
+        assert!(!dev_a.is_parked());
+        assert_eq!(dev_a.event(), Ok(Some(MacEvent::Restarted(dev_a.addr()))));
+
+        associate(&mut timer, &mut coord, &mut dev_a, &mut dev_b);
+        assert_eq!(coord.associations().count(), 2);
+    }
+
+    /// Fragmented datagrams are delivered end to end via 6LoWPAN over the MAC
+    #[test]
+    fn sixlo_network() {
+        let _ = simplelog::SimpleLogger::init(log::LevelFilter::Info, simplelog::Config::default());
+
+        let mut timer = MockTimer::new();
+        let (mut coord, mut dev_a, mut dev_b) = network(&timer);
+
+        associate(&mut timer, &mut coord, &mut dev_a, &mut dev_b);
+
+        // Routers, so no neighbour discovery traffic is generated
+        let cfg = SixLoConfig {
+            nd: NdConfig {
+                router: true,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let (coord_addr, dev_addr) = (coord.addr(), dev_a.addr());
+        let mut sixlo_coord = SixLo::<_, _, 127>::new(coord, coord_addr, cfg.clone());
+        let mut sixlo_dev = SixLo::<_, _, 127>::new(dev_a, dev_addr, cfg.clone());
+
+        let tx: Vec<u8> = (0..200).map(|v| v as u8).collect();
+        sixlo_dev
+            .transmit(timer.ticks_ms(), coord_addr, &tx)
+            .unwrap();
+
+        let mut buff = [0u8; 512];
+        let mut received = None;
+        for _ in 0..15_000 {
+            timer.inc();
+            let now = timer.ticks_ms();
+
+            sixlo_coord.tick(now).unwrap();
+            sixlo_dev.tick(now).unwrap();
+            dev_b.tick().unwrap();
+
+            if let Some((n, info, _h)) = sixlo_coord.receive(now, &mut buff).unwrap() {
+                received = Some((n, info.source));
+                break;
+            }
+        }
+
+        assert_eq!(received, Some((tx.len(), dev_addr)));
+        assert_eq!(&buff[..tx.len()], &tx[..]);
+    }
+
+    /// Time taken to deliver a 1280 byte datagram from a device to the coordinator
+    fn datagram_time(mac_cfg: Config, sixlo_cfg: SixLoConfig) -> u64 {
+        let mut timer = MockTimer::new();
+        let (mut coord, mut dev_a, mut dev_b) = network_with(&timer, mac_cfg);
+
+        associate(&mut timer, &mut coord, &mut dev_a, &mut dev_b);
+
+        let (coord_addr, dev_addr) = (coord.addr(), dev_a.addr());
+        let mut sixlo_coord = SixLo::<_, _, 127>::new(coord, coord_addr, sixlo_cfg.clone());
+        let mut sixlo_dev = SixLo::<_, _, 127>::new(dev_a, dev_addr, sixlo_cfg);
+
+        let tx: Vec<u8> = (0..1280).map(|v| v as u8).collect();
+        let start = timer.ticks_ms();
+        sixlo_dev.transmit(start, coord_addr, &tx).unwrap();
+
+        let mut buff = [0u8; 1280];
+        for _ in 0..120_000 {
+            timer.inc();
+            let now = timer.ticks_ms();
+
+            sixlo_coord.tick(now).unwrap();
+            sixlo_dev.tick(now).unwrap();
+            dev_b.tick().unwrap();
+
+            if let Some((n, _info, _h)) = sixlo_coord.receive(now, &mut buff).unwrap() {
+                assert_eq!(&buff[..n], &tx[..]);
+                return now - start;
+            }
+        }
+
+        panic!("Datagram timeout at {} ms", timer.val());
+    }
+
+    /// Datagrams complete faster where multiple fragments are handed to the MAC
+    /// per tick and sent consecutively within the CAP
+    #[test]
+    fn sixlo_throughput() {
+        let _ = simplelog::SimpleLogger::init(log::LevelFilter::Info, simplelog::Config::default());
+
+        // Datagrams take tens of seconds at one fragment per superframe
+        let frag = FragConfig {
+            frag_tx_timeout_ms: 120_000,
+            frag_rx_timeout_ms: 120_000,
+            ..Default::default()
+        };
+        let nd = NdConfig {
+            router: true,
+            ..Default::default()
+        };
+
+        let single = datagram_time(
+            Config::default(),
+            SixLoConfig {
+                frag: frag.clone(),
+                nd: nd.clone(),
+                tx_budget: 1,
+                ..Default::default()
+            },
+        );
+        let burst = datagram_time(
+            Config {
+                cap_burst: 16,
+                ..Default::default()
+            },
+            SixLoConfig {
+                frag: FragConfig {
+                    tx_window: 4,
+                    ..frag
+                },
+                nd,
+                tx_budget: 4,
+                ..Default::default()
+            },
+        );
+
+        debug!(
+            "1280 byte datagram delivered in {} ms, {} ms with CAP bursts",
+            single, burst
+        );
+        assert!(burst * 4 < single);
+    }
+}
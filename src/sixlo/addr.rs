@@ -0,0 +1,117 @@
+//! 6LoWPAN Link-Layer Addressing
+//!
+//! The 6LoWPAN layer is generic over the link-layer address type of the underlying
+//! [`Mac`](crate::Mac), with addresses converted to interface identifiers ([`Eui64`])
+//! for IPv6 address derivation and compression, and for carriage in mesh headers
+//! and neighbour discovery options.
+//
+// https://github.com/rust-iot/rust-lpwan
+// Copyright 2021 Ryan Kurte
+
+use ieee802154::mac::{Address, AddressMode, ExtendedAddress, PanId, ShortAddress};
+
+use crate::log::FmtError;
+
+use super::headers::{Eui64, V6Addr};
+
+/// Link-layer address operations required by the 6LoWPAN layer
+pub trait AddressOps: Clone + PartialEq + FmtError + Into<Eui64> {
+    /// Fetch the broadcast address
+    fn broadcast() -> Self;
+
+    /// Check whether this is a broadcast address
+    fn is_broadcast(&self) -> bool;
+
+    /// Recover a link-layer address from an interface identifier, using the
+    /// local address for context not carried in the identifier (such as PAN IDs)
+    fn from_iid(iid: Eui64, local: &Self) -> Self;
+}
+
+/// Interface identifiers for 802.15.4 addresses per
+/// [RFC6282 Section 3.2.2](https://tools.ietf.org/html/rfc6282#section-3.2.2)
+impl From<Address> for Eui64 {
+    fn from(a: Address) -> Self {
+        match a {
+            Address::Short(_, s) => Eui64::short(s.0),
+            Address::Extended(_, e) => Eui64::extended(e.0),
+            Address::None => Eui64(0),
+        }
+    }
+}
+
+/// Link-local IPv6 addresses for 802.15.4 addresses, see [`V6Addr::link_local`]
+impl From<Address> for V6Addr {
+    fn from(a: Address) -> Self {
+        V6Addr::link_local(&a)
+    }
+}
+
+impl AddressOps for Address {
+    fn broadcast() -> Self {
+        Address::broadcast(&AddressMode::Short)
+    }
+
+    fn is_broadcast(&self) -> bool {
+        match self {
+            Address::Short(_, s) => *s == ShortAddress::BROADCAST,
+            Address::Extended(_, e) => *e == ExtendedAddress::BROADCAST,
+            Address::None => false,
+        }
+    }
+
+    /// PAN IDs are not carried in interface identifiers, so the local PAN ID is applied
+    fn from_iid(iid: Eui64, local: &Self) -> Self {
+        let pan_id = match local {
+            Address::Short(p, _) | Address::Extended(p, _) => *p,
+            Address::None => PanId(0),
+        };
+
+        match iid.to_short() {
+            Some(s) => Address::Short(pan_id, ShortAddress(s)),
+            None => Address::Extended(pan_id, ExtendedAddress(iid.to_extended())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::string::ToString;
+
+    use super::*;
+
+    #[test]
+    fn ieee802154_address_ops() {
+        let local = Address::Short(PanId(1), ShortAddress(1));
+
+        let short = Address::Short(PanId(1), ShortAddress(0x1234));
+        let extended = Address::Extended(PanId(1), ExtendedAddress(0x0011_2233_4455_6677));
+
+        // Addresses are recovered from interface identifiers
+        assert_eq!(Eui64::from(short), Eui64(0x0000_00ff_fe00_1234));
+        assert_eq!(Address::from_iid(short.into(), &local), short);
+        assert_eq!(Eui64::from(extended), Eui64(0x0211_2233_4455_6677));
+        assert_eq!(Address::from_iid(extended.into(), &local), extended);
+
+        let bcast = <Address as AddressOps>::broadcast();
+        assert!(bcast.is_broadcast());
+        assert!(Address::Short(PanId(1), ShortAddress::BROADCAST).is_broadcast());
+        assert!(!short.is_broadcast());
+    }
+
+    #[test]
+    fn ieee802154_link_local() {
+        let short = Address::Short(PanId(1), ShortAddress(0x0010));
+        let extended = Address::Extended(PanId(1), ExtendedAddress(0x0011_2233_4455_6677));
+
+        let addr = V6Addr::from(extended);
+        assert_eq!(addr.to_string(), "fe80::211:2233:4455:6677");
+        assert!(addr.is_link_local());
+        assert_eq!(addr.iid(), Eui64(0x0211_2233_4455_6677));
+        assert_eq!(addr.link_layer(&short), extended);
+
+        let addr = V6Addr::from(short);
+        assert_eq!(addr.to_string(), "fe80::ff:fe00:10");
+        assert_eq!(addr.link_layer(&extended), short);
+        assert!(!V6Addr::UNSPECIFIED.is_link_local());
+    }
+}
@@ -0,0 +1,276 @@
+//! 6LoWPAN Broadcast Duplicate Suppression
+//!
+//! Broadcast frames carry a LOWPAN_BC0 header with a per-originator sequence number
+//! per [RFC4944 Section 11.1](https://tools.ietf.org/html/rfc4944#section-11.1).
+//! Recently seen sequence numbers are cached so re-broadcast or forwarded copies
+//! of a frame are only delivered once, with entries aged out on [`SixLo::tick`].
+//
+// https://github.com/rust-iot/rust-lpwan
+// Copyright 2021 Ryan Kurte
+
+use heapless::Vec;
+use ieee802154::mac::Address as MacAddress;
+
+use crate::log::{debug, FmtError};
+use crate::{Mac, Ts};
+
+use super::headers::{BroadcastHeader, Header};
+use super::{AddressOps, SixLo};
+
+/// Default duplicate cache size
+pub const DEFAULT_MAX_SEEN: usize = 16;
+
+/// Broadcast configuration
+#[derive(Clone, PartialEq, Debug)]
+pub struct BroadcastConfig {
+    /// Time for which received broadcasts are remembered for duplicate suppression
+    pub seen_timeout_ms: Ts,
+}
+
+impl Default for BroadcastConfig {
+    fn default() -> Self {
+        Self {
+            seen_timeout_ms: 10_000,
+        }
+    }
+}
+
+/// Broadcast statistics
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct BroadcastStats {
+    /// Duplicate broadcast frames suppressed on receipt
+    pub rx_duplicates: u32,
+}
+
+impl Default for BroadcastStats {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl BroadcastStats {
+    pub fn new() -> Self {
+        Self { rx_duplicates: 0 }
+    }
+}
+
+/// Received broadcast, identified by originator, sequence number,
+/// and fragment offset (as all fragments of a datagram share a sequence number)
+#[derive(Debug, Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Seen<A = MacAddress> {
+    pub origin: A,
+    pub seq: u8,
+    pub offset: Option<u8>,
+    pub expires: Ts,
+}
+
+/// Cache of recently received broadcasts
+#[derive(Debug, Clone, PartialEq)]
+pub struct SeenCache<A = MacAddress, const N: usize = DEFAULT_MAX_SEEN> {
+    entries: Vec<Seen<A>, N>,
+}
+
+impl<A: PartialEq, const N: usize> Default for SeenCache<A, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<A: PartialEq, const N: usize> SeenCache<A, N> {
+    /// Create a new (empty) duplicate cache
+    pub fn new() -> Self {
+        Self {
+            entries: Vec::new(),
+        }
+    }
+
+    /// Record a received broadcast, returning true if this has already been seen.
+    /// Where the cache is full the entry closest to expiry is replaced
+    pub fn check(&mut self, s: Seen<A>) -> bool {
+        let key = |e: &Seen<A>| e.origin == s.origin && e.seq == s.seq && e.offset == s.offset;
+        if self.entries.iter().any(key) {
+            return true;
+        }
+
+        if let Err(s) = self.entries.push(s) {
+            if let Some(e) = self.entries.iter_mut().min_by_key(|e| e.expires) {
+                *e = s;
+            }
+        }
+
+        false
+    }
+
+    /// Remove an entry with an expiry prior to the provided time, if any
+    pub fn expire(&mut self, now: Ts) -> Option<Seen<A>> {
+        let i = self.entries.iter().position(|e| e.expires < now)?;
+        Some(self.entries.swap_remove(i))
+    }
+
+    /// Fetch the earliest expiry of cached entries
+    pub fn next_expiry(&self) -> Option<Ts> {
+        self.entries.iter().map(|e| e.expires).min()
+    }
+
+    /// Fetch the number of cached entries
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+}
+
+/// Broadcast state
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct BroadcastState<A> {
+    /// Sequence number for the next originated broadcast
+    seq: u8,
+    seen: SeenCache<A>,
+    stats: BroadcastStats,
+}
+
+impl<A: PartialEq> BroadcastState<A> {
+    pub(crate) fn new() -> Self {
+        Self {
+            seq: 0,
+            seen: SeenCache::new(),
+            stats: BroadcastStats::new(),
+        }
+    }
+}
+
+impl<M, A, const MAX_PAYLOAD: usize, const TX_SLOTS: usize, const RX_SLOTS: usize>
+    SixLo<M, A, MAX_PAYLOAD, TX_SLOTS, RX_SLOTS>
+where
+    M: Mac<A>,
+    A: AddressOps,
+    <M as Mac<A>>::Error: FmtError,
+{
+    /// Fetch broadcast statistics
+    pub fn bcast_stats(&self) -> BroadcastStats {
+        self.bcast.stats.clone()
+    }
+
+    /// Fetch a broadcast header for an originated broadcast
+    pub(crate) fn bcast_header(&mut self) -> BroadcastHeader {
+        let seq = self.bcast.seq;
+        self.bcast.seq = self.bcast.seq.wrapping_add(1);
+        BroadcastHeader { seq }
+    }
+
+    /// Check whether a received broadcast is a duplicate, updating the cache
+    pub(crate) fn bcast_duplicate(&mut self, now_ms: Ts, origin: &A, h: &Header) -> bool {
+        let seq = match &h.bcast {
+            Some(b) => b.seq,
+            None => return false,
+        };
+
+        let s = Seen {
+            origin: origin.clone(),
+            seq,
+            offset: h.frag.as_ref().and_then(|f| f.datagram_offset),
+            expires: now_ms + self.cfg.bcast.seen_timeout_ms,
+        };
+
+        if !self.bcast.seen.check(s) {
+            return false;
+        }
+
+        debug!("Suppressed duplicate broadcast {} from {:?}", seq, origin);
+        self.bcast.stats.rx_duplicates = self.bcast.stats.rx_duplicates.saturating_add(1);
+
+        true
+    }
+
+    /// Age out cached broadcasts
+    pub(crate) fn tick_bcast(&mut self, now_ms: Ts) {
+        while self.bcast.seen.expire(now_ms).is_some() {}
+    }
+
+    /// Fetch the time at which the next cached broadcast is aged out
+    pub(crate) fn bcast_deadline(&self) -> Option<Ts> {
+        self.bcast.seen.next_expiry().map(|t| t + 1)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use ieee802154::mac::{PanId, ShortAddress};
+
+    use super::*;
+    use crate::sixlo::{mock::MockMac, SixLoConfig};
+
+    #[test]
+    fn seen_cache() {
+        let a = MacAddress::Short(PanId(1), ShortAddress(1));
+        let seen = |seq, expires| Seen {
+            origin: a,
+            seq,
+            offset: None,
+            expires,
+        };
+
+        let mut c = SeenCache::<_, 2>::new();
+        assert!(!c.check(seen(1, 10)));
+        assert!(c.check(seen(1, 20)));
+        assert!(!c.check(seen(2, 20)));
+
+        // Full caches replace the entry closest to expiry
+        assert!(!c.check(seen(3, 30)));
+        assert!(!c.check(seen(1, 30)));
+        assert_eq!(c.len(), 2);
+
+        // Entries are aged out
+        assert_eq!(c.expire(25), None);
+        assert!(c.expire(31).is_some());
+        assert!(c.expire(31).is_some());
+        assert_eq!(c.len(), 0);
+    }
+
+    /// Repeated broadcast from A is only received once at B
+    #[test]
+    fn bcast_duplicate() {
+        let addr_a = MacAddress::Short(PanId(1), ShortAddress(1));
+        let addr_b = MacAddress::Short(PanId(1), ShortAddress(2));
+        let bcast = MacAddress::Short(PanId(1), ShortAddress::BROADCAST);
+
+        let mac_a = MockMac::new(addr_a);
+        let mac_b = mac_a.link(addr_b);
+
+        let mut sixlo_a = SixLo::<_, _, 127>::new(mac_a, addr_a, SixLoConfig::default());
+        let mut sixlo_b = SixLo::<_, _, 127>::new(mac_b, addr_b, SixLoConfig::default());
+
+        // Broadcast headers are attached automatically
+        sixlo_a.transmit(0, bcast, &[0xaa, 0xbb]).unwrap();
+
+        let header = Header {
+            bcast: Some(BroadcastHeader { seq: 0 }),
+            ..Default::default()
+        };
+        sixlo_a
+            .transmit_header(0, bcast, header, &[0xaa, 0xbb])
+            .unwrap();
+
+        let mut buff = [0u8; 128];
+        let mut received = 0;
+        for t in 0..4 {
+            sixlo_a.tick(t).unwrap();
+            sixlo_b.tick(t).unwrap();
+
+            if let Some((n, info, h)) = sixlo_b.receive(t, &mut buff).unwrap() {
+                assert_eq!(&buff[..n], &[0xaa, 0xbb]);
+                assert_eq!(info.source, addr_a);
+                assert_eq!(h.bcast, Some(BroadcastHeader { seq: 0 }));
+                received += 1;
+            }
+        }
+
+        assert_eq!(received, 1);
+        assert_eq!(sixlo_b.bcast_stats().rx_duplicates, 1);
+
+        // Once aged out the sequence number may be re-used
+        let timeout = SixLoConfig::default().bcast.seen_timeout_ms;
+        sixlo_b.tick(timeout + 10).unwrap();
+        assert_eq!(sixlo_b.bcast.seen.len(), 0);
+    }
+}
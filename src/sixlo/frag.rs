@@ -1,4 +1,11 @@
 //! 6LoWPAN/IPv6 Fragmentation Layer
+//!
+//! Up to [`FragConfig::tx_window`] fragments of each datagram are handed to the MAC
+//! at a time, with each held until the MAC reports the transmit outcome (see
+//! [`Frag::sent`] and [`Frag::tx_status`]) so failed fragments are retried rather
+//! than lost, resending from the failed fragment up to [`FragConfig::frag_tx_retries`]
+//! attempts before the datagram is aborted. The outcome of each fragmented datagram,
+//! including those dropped on timeout, is reported via [`Frag::take_event`].
 //
 // https://github.com/rust-iot/rust-lpwan
 // Copyright 2021 Ryan Kurte
@@ -6,15 +13,18 @@
 // TODO: is it important to be able to receive more than one fragmented packet at once?
 // seems... probable, in which case more buffers / a pooled approach might be better.
 
-// Maybe useful to be able to support Minimal Fragment Forwarding / other improved approaches?
+// Datagrams being forwarded are switched per-fragment via a virtual reassembly buffer
+// per Minimal Fragment Forwarding, rather than reassembled at each hop
 // https://tools.ietf.org/html/draft-ietf-6lo-minimal-fragment-01
 
+use heapless::spsc::Queue;
+use heapless::Vec;
 use ieee802154::mac::Address as MacAddress;
 
 use crate::log::{debug, warn};
-use crate::Ts;
+use crate::{RxInfo, Ts, TxHandle, TxStatus};
 
-use super::{headers::FragHeader, Header, SixLoError, IPV6_MTU};
+use super::{headers::FragHeader, AddressOps, Header, SixLoError, DEFAULT_FRAG_SIZE, IPV6_MTU};
 
 /// Fragmentation buffer state
 #[derive(Clone, PartialEq, Debug)]
@@ -25,23 +35,233 @@ pub enum FragState {
     Done,
 }
 
+/// Fragment reassembly errors
+#[derive(Copy, Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum FragError {
+    /// Fragment header missing or does not match the datagram
+    Mismatch,
+    /// Fragment exceeds the datagram size (or buffer capacity),
+    /// or is not a multiple of 8 bytes where not the final fragment
+    Length,
+    /// Fragment partially overlaps previously received data
+    Overlap,
+}
+
+/// Fragment offsets and (non-final) lengths are in units of 8 bytes
+const FRAG_BLOCK: usize = 8;
+
+/// Maximum datagram size representable in a fragment header (11 bits)
+const MAX_DATAGRAM_SIZE: usize = 2048;
+
+/// Number of completed datagrams buffered for [`Frag::pop`]
+pub const RX_QUEUE_DEPTH: usize = 4;
+
+/// Number of datagrams that may be forwarded concurrently
+pub const FWD_TABLE_SIZE: usize = 4;
+
+/// Default number of datagrams that may be transmitted concurrently
+pub const DEFAULT_TX_SLOTS: usize = 4;
+
+/// Default number of datagrams that may be reassembled concurrently
+pub const DEFAULT_RX_SLOTS: usize = 4;
+
+/// Number of datagram outcomes buffered for [`Frag::take_event`]
+pub const EVENT_QUEUE_DEPTH: usize = 8;
+
+/// Number of fragments (over all datagrams) tracked while awaiting transmit outcomes,
+/// fragments handed to the MAC beyond this are assumed to be sent
+pub const MAX_IN_FLIGHT: usize = 8;
+
+/// Outcome of a fragmented datagram
+#[derive(Copy, Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum FragOutcome {
+    /// All fragments of the datagram were transmitted
+    TxComplete,
+    /// Datagram aborted once fragment retries were exhausted
+    TxFailed,
+    /// Datagram dropped before all fragments were transmitted
+    TxTimeout,
+    /// Datagram reassembled and queued for collection
+    RxComplete,
+    /// Incomplete datagram dropped on reassembly timeout
+    RxTimeout,
+}
+
+/// Fragmented datagram event, reported via [`Frag::take_event`]
+#[derive(Clone, PartialEq, Debug)]
+pub struct FragEvent<A = MacAddress> {
+    /// Datagram tag
+    pub tag: u16,
+    /// Datagram destination for transmission, or source for reception
+    pub peer: A,
+    pub outcome: FragOutcome,
+}
+
+/// Fragmentation statistics
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct FragStats {
+    /// Completed datagrams dropped due to a full receive queue, or new datagrams
+    /// dropped without a free reassembly slot where eviction is disabled
+    pub rx_dropped: u32,
+    /// Completed datagrams destined for this node
+    pub rx_datagrams: u32,
+    /// Fragmented datagrams forwarded toward another node
+    pub fwd_datagrams: u32,
+    /// Fragments dropped for want of a forwarding entry
+    pub fwd_dropped: u32,
+    /// Fragmented datagrams transmitted
+    pub tx_datagrams: u32,
+    /// Fragment retransmissions following a failed transmission
+    pub tx_retries: u32,
+    /// Fragmented datagrams aborted once fragment retries were exhausted
+    pub tx_failed: u32,
+    /// Fragmented datagrams dropped on transmit timeout
+    pub tx_timeouts: u32,
+    /// Incomplete datagrams evicted to make room for new datagrams
+    pub rx_evicted: u32,
+    /// Incomplete datagrams dropped on reassembly timeout
+    pub rx_timeouts: u32,
+    /// Fragments rejected as exceeding the datagram size or buffer capacity,
+    /// or overlapping previously received data
+    pub rx_invalid: u32,
+    /// Fragments transmitted
+    pub tx_fragments: u32,
+    /// Fragments received for reassembly
+    pub rx_fragments: u32,
+    /// Maximum number of reassembly slots in use
+    pub rx_slots_max: u32,
+}
+
+impl Default for FragStats {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FragStats {
+    pub fn new() -> Self {
+        Self {
+            rx_dropped: 0,
+            rx_datagrams: 0,
+            fwd_datagrams: 0,
+            fwd_dropped: 0,
+            tx_datagrams: 0,
+            tx_retries: 0,
+            tx_failed: 0,
+            tx_timeouts: 0,
+            rx_evicted: 0,
+            rx_timeouts: 0,
+            rx_invalid: 0,
+            tx_fragments: 0,
+            rx_fragments: 0,
+            rx_slots_max: 0,
+        }
+    }
+
+    /// Reset all counters
+    pub fn reset(&mut self) {
+        *self = Self::new();
+    }
+}
+
+/// Forwarding entry (virtual reassembly buffer), mapping fragments of a datagram
+/// from the originator to the next hop without buffering the datagram
+#[derive(Clone, PartialEq, Debug)]
+pub struct FwdEntry<A = MacAddress> {
+    /// Datagram originator and tag
+    pub origin: A,
+    pub tag: u16,
+    /// Next hop and datagram tag for forwarded fragments
+    pub next_hop: A,
+    pub out_tag: u16,
+    pub timeout: Ts,
+}
+
+/// Fragment handed to the MAC and awaiting a transmit outcome
+#[derive(Clone, PartialEq, Debug)]
+struct InFlight<A> {
+    handle: TxHandle,
+    dest: A,
+    tag: u16,
+    offset: usize,
+    len: usize,
+}
+
+/// Receive information for a datagram, aggregated over its fragments
+#[derive(Clone, PartialEq, Debug)]
+pub struct DatagramInfo<A = MacAddress> {
+    /// Datagram source, the mesh originator where present
+    pub source: A,
+    /// Minimum RSSI of received fragments
+    pub rssi_min: i16,
+    /// Mean RSSI of received fragments
+    pub rssi_avg: i16,
+    /// Number of fragments received, excluding duplicates
+    pub fragments: u16,
+}
+
 /// Fragmentation manager, handles transmission and receipt of IPv6 datagrams
-/// as fragments via 6LoWPAN.
+/// as fragments via 6LoWPAN, and the switching of fragments for datagrams
+/// being forwarded (see [`Frag::forward`]).
 ///
-/// TODO: support fragment forwarding (only runs point-to-point atm)
-pub struct Frag<const MAX_FRAG_SIZE: usize> {
+/// Transmission and reassembly use separate slots (`TX_SLOTS` and `RX_SLOTS`), so
+/// slow senders cannot prevent transmission, and where all reassembly slots are in
+/// use the oldest incomplete datagram is evicted in favour of new datagrams
+/// (see [`FragConfig::evict_oldest`]).
+pub struct Frag<
+    A = MacAddress,
+    const TX_SLOTS: usize = DEFAULT_TX_SLOTS,
+    const RX_SLOTS: usize = DEFAULT_RX_SLOTS,
+> {
     config: FragConfig,
+    frag_size: usize,
     tag: u16,
     // TODO: it would be nice to use a queue to preserve ordering...
     // unfortunately heapless::Queue doesn't have arbitrary remove
     // and heapless::Vec can only remove_swap so we can't use those anyway
-    buffs: [FragBuffer<[u8; IPV6_MTU], MAX_FRAG_SIZE>; 4],
+    tx_buffs: [FragBuffer<[u8; IPV6_MTU], A>; TX_SLOTS],
+    // Index of the TX buffer following that last serviced, polled first so
+    // concurrent datagrams are interleaved
+    tx_next: usize,
+    rx_buffs: [FragBuffer<[u8; IPV6_MTU], A>; RX_SLOTS],
+    // Completed datagrams awaiting collection, separate from in-progress buffers
+    // so that unfragmented datagrams do not consume reassembly slots
+    rx_done: Queue<FragBuffer<[u8; IPV6_MTU], A>, { RX_QUEUE_DEPTH + 1 }>,
+    // Datagram most recently returned by `pop`
+    rx_current: FragBuffer<[u8; IPV6_MTU], A>,
+    // Fragments handed to the MAC and awaiting a transmit outcome
+    in_flight: Vec<InFlight<A>, MAX_IN_FLIGHT>,
+    // Datagrams being forwarded
+    fwd: Vec<FwdEntry<A>, FWD_TABLE_SIZE>,
+    // Datagram outcomes awaiting collection
+    events: Queue<FragEvent<A>, { EVENT_QUEUE_DEPTH + 1 }>,
+    stats: FragStats,
 }
 
 #[derive(Clone, PartialEq, Debug)]
 pub struct FragConfig {
     pub frag_rx_timeout_ms: Ts,
     pub frag_tx_timeout_ms: Ts,
+    /// Retries of each failed fragment transmission prior to aborting the datagram
+    pub frag_tx_retries: u8,
+    /// Maximum fragment (payload) size, must be a multiple of 8 bytes.
+    /// Fragments are further limited to the MAC payload remaining following the
+    /// headers of each fragment (see [`Frag::poll_budget`]), where unset this is
+    /// set to the MAC payload size by [`super::SixLo::new`]
+    pub frag_size: Option<usize>,
+    /// Evict the incomplete datagram with the oldest timeout where all reassembly
+    /// slots are in use, otherwise fragments of new datagrams are dropped
+    pub evict_oldest: bool,
+    /// Fragments of each datagram handed to the MAC ahead of transmit outcomes, with
+    /// 1 holding each fragment until the outcome of the last is reported. Following
+    /// a failure fragments are resent from the failed fragment
+    pub tx_window: u8,
+    /// Minimum interval between fragments of each datagram handed to the MAC,
+    /// pacing transmission to avoid overrunning the receiver or MAC queue
+    pub frag_interval_ms: Ts,
 }
 
 impl Default for FragConfig {
@@ -49,42 +269,103 @@ impl Default for FragConfig {
         Self {
             frag_rx_timeout_ms: 10_000,
             frag_tx_timeout_ms: 10_000,
+            frag_tx_retries: 3,
+            frag_size: None,
+            evict_oldest: true,
+            tx_window: 1,
+            frag_interval_ms: 0,
         }
     }
 }
 
-impl<const MAX_FRAG_SIZE: usize> Frag<MAX_FRAG_SIZE> {
+impl<A: AddressOps, const TX_SLOTS: usize, const RX_SLOTS: usize> Frag<A, TX_SLOTS, RX_SLOTS> {
     /// Create a new fragmentation manager
     pub fn new(config: FragConfig) -> Self {
+        let frag_size = config.frag_size.unwrap_or(DEFAULT_FRAG_SIZE);
+
+        // Fragment offsets are in 8-byte units (RFC4944 Section 5.3)
+        assert!(
+            frag_size >= FRAG_BLOCK && frag_size % FRAG_BLOCK == 0,
+            "fragment size must be a non-zero multiple of 8 bytes"
+        );
+
         Self {
             config,
+            frag_size,
             tag: 0,
-            buffs: Default::default(),
+            tx_buffs: [(); TX_SLOTS].map(|_| FragBuffer::default()),
+            tx_next: 0,
+            rx_buffs: [(); RX_SLOTS].map(|_| FragBuffer::default()),
+            rx_done: Queue::new(),
+            rx_current: Default::default(),
+            in_flight: Vec::new(),
+            fwd: Vec::new(),
+            events: Queue::new(),
+            stats: FragStats::new(),
+        }
+    }
+
+    /// Fetch the configured (maximum) fragment size
+    pub fn frag_size(&self) -> usize {
+        self.frag_size
+    }
+
+    /// Check for a free slot for datagram transmission
+    pub fn can_transmit(&self) -> bool {
+        self.tx_buffs.iter().any(|b| b.state == FragState::None)
+    }
+
+    /// Fetch fragmentation statistics
+    pub fn stats(&self) -> FragStats {
+        self.stats.clone()
+    }
+
+    /// Reset fragmentation statistics
+    pub fn reset_stats(&mut self) {
+        self.stats.reset();
+    }
+
+    /// Fetch the next fragmented datagram outcome, if any
+    pub fn take_event(&mut self) -> Option<FragEvent<A>> {
+        self.events.dequeue()
+    }
+
+    /// Add a datagram outcome for collection via [`Frag::take_event`],
+    /// dropping the oldest event if these are not being collected
+    fn push_event(&mut self, tag: u16, peer: A, outcome: FragOutcome) {
+        if self.events.is_full() {
+            let _ = self.events.dequeue();
         }
+        let _ = self.events.enqueue(FragEvent { tag, peer, outcome });
     }
 
     /// Set-up a datagram for transmission
     pub fn transmit<E>(
         &mut self,
         now_ms: Ts,
-        dest: MacAddress,
+        dest: A,
         hdr: Header,
         d: &[u8],
     ) -> Result<(), SixLoError<E>> {
         // Locate a free slot in the fragment buffer
         let slot = match self
-            .buffs
+            .tx_buffs
             .iter_mut()
             .find(|buff| buff.state == FragState::None)
         {
             Some(s) => s,
             None => {
-                return Err(SixLoError::NoTxFragSlots);
+                return Err(SixLoError::NoTxSlots);
             }
         };
 
+        // Sizes include the uncompressed headers, which must fit the datagram buffer
+        if hdr.uncompressed_len() + d.len() > slot.buff.len() {
+            return Err(SixLoError::Frag(FragError::Length));
+        }
+
         // Initialise slot for transmission
-        *slot = FragBuffer::init_tx(dest, hdr, self.tag, d);
+        *slot = FragBuffer::init_tx(dest, hdr, self.tag, self.frag_size, d);
         slot.timeout = now_ms + self.config.frag_tx_timeout_ms;
 
         // Increment fragment tag counter
@@ -93,60 +374,361 @@ impl<const MAX_FRAG_SIZE: usize> Frag<MAX_FRAG_SIZE> {
         Ok(())
     }
 
-    /// Add a buffer to tracking
-    fn push<E>(
-        &mut self,
-        fb: FragBuffer<[u8; IPV6_MTU], MAX_FRAG_SIZE>,
-    ) -> Result<usize, SixLoError<E>> {
-        // Find empty slot
-        let slot = self
-            .buffs
-            .iter_mut()
-            .enumerate()
-            .find(|(_idx, buff)| buff.state == FragState::None);
+    /// Track a fragment handed to the MAC for transmission, holding the datagram
+    /// until the transmit outcome is reported via [`Frag::tx_status`]. Following
+    /// fragments are returned by [`Frag::poll`] within [`FragConfig::tx_window`]
+    pub fn sent(&mut self, handle: TxHandle, dest: A, tag: u16) {
+        let i = match self.tx_buffs.iter().position(|b| {
+            b.state == FragState::Tx && b.addr == dest && b.tag == tag && b.frag_len != 0
+        }) {
+            Some(i) => i,
+            None => return,
+        };
 
-        if let Some((idx, slot)) = slot {
-            *slot = fb;
-            Ok(idx)
-        } else {
-            Err(SixLoError::NoTxFragSlots)
+        // Later polls start from the following buffer
+        self.tx_next = (i + 1) % TX_SLOTS;
+
+        let b = &mut self.tx_buffs[i];
+        let (offset, len) = (b.offset, b.frag_len);
+        b.offset += len;
+        b.frag_len = 0;
+        b.next_tx = b.polled + self.config.frag_interval_ms;
+
+        // Without tracking the outcome will not be seen, so assume this was sent
+        let f = InFlight {
+            handle,
+            dest,
+            tag,
+            offset,
+            len,
+        };
+        if let Err(f) = self.in_flight.push(f) {
+            warn!("Unable to track TX status for datagram {}", tag);
+            self.stats.tx_fragments = self.stats.tx_fragments.saturating_add(1);
+            if b.confirm(f.offset, f.len) {
+                self.stats.tx_datagrams = self.stats.tx_datagrams.saturating_add(1);
+                self.push_event(tag, f.dest, FragOutcome::TxComplete);
+            }
+            return;
         }
+
+        b.in_flight += 1;
+    }
+
+    /// Fetch the time of the next fragmentation operation, being when the next
+    /// pending fragment may be sent (per [`FragConfig::frag_interval_ms`]),
+    /// otherwise the earliest datagram timeout
+    pub fn next_deadline(&self, now_ms: Ts, can_tx: bool) -> Option<Ts> {
+        let tx = self
+            .tx_buffs
+            .iter()
+            .filter(|b| can_tx && b.sendable(self.config.tx_window))
+            .map(|b| b.next_tx.max(now_ms));
+
+        self.tx_buffs
+            .iter()
+            .chain(self.rx_buffs.iter())
+            .filter(|b| b.state != FragState::None && b.timeout != 0)
+            .map(|b| b.timeout)
+            .chain(self.fwd.iter().map(|f| f.timeout))
+            .map(|t| t + 1)
+            .chain(tx)
+            .min()
     }
 
-    /// Remove a completed buffer
-    pub fn pop<'a>(&'a mut self) -> Option<(&'a MacAddress, &'a Header, &'a [u8])> {
-        // Find completed slot
-        let slot = self
-            .buffs
+    /// Apply a MAC transmit outcome, advancing to the next fragment on success
+    /// or resending from the failed fragment on failure, aborting the datagram
+    /// once retries are exhausted
+    pub fn tx_status(&mut self, handle: TxHandle, status: TxStatus) {
+        // Skip outcomes for packets not originating from the fragmentation layer
+        let f = match self.in_flight.iter().position(|f| f.handle == handle) {
+            Some(i) => self.in_flight.swap_remove(i),
+            None => return,
+        };
+        let (dest, tag) = (f.dest, f.tag);
+
+        let b = match self
+            .tx_buffs
             .iter_mut()
-            .find(|buff| buff.state == FragState::Done);
+            .find(|b| b.state == FragState::Tx && b.addr == dest && b.tag == tag)
+        {
+            Some(b) => b,
+            None => return,
+        };
+        b.in_flight = b.in_flight.saturating_sub(1);
+
+        match status {
+            TxStatus::Sent | TxStatus::Acked => {
+                self.stats.tx_fragments = self.stats.tx_fragments.saturating_add(1);
+                if b.confirm(f.offset, f.len) {
+                    debug!("TX datagram {} complete", tag);
+                    self.stats.tx_datagrams = self.stats.tx_datagrams.saturating_add(1);
+                    self.push_event(tag, dest, FragOutcome::TxComplete);
+                }
+            }
+            TxStatus::Failed(reason) if b.retries < self.config.frag_tx_retries => {
+                b.retries += 1;
+                b.rewind(f.offset);
+                self.stats.tx_retries = self.stats.tx_retries.saturating_add(1);
+
+                debug!(
+                    "Fragment TX failed for datagram {} offset {} ({}), retry {} of {}",
+                    tag, f.offset, reason, b.retries, self.config.frag_tx_retries
+                );
 
-        let slot = match slot {
-            Some(s) => s,
-            None => return None,
+                // Later fragments are resent following the failed fragment,
+                // so outcomes of those already handed to the MAC are ignored
+                let (n, offset) = (self.in_flight.len(), f.offset);
+                self.in_flight
+                    .retain(|i| !(i.dest == dest && i.tag == tag && i.offset > offset));
+                b.in_flight = b.in_flight.saturating_sub((n - self.in_flight.len()) as u8);
+            }
+            TxStatus::Failed(reason) => {
+                warn!(
+                    "Fragment TX failed for datagram {} via {:?} ({}), aborting",
+                    tag, dest, reason
+                );
+
+                // Abort the datagram, there's no point sending the remaining fragments
+                b.state = FragState::None;
+                self.in_flight.retain(|i| !(i.dest == dest && i.tag == tag));
+                self.stats.tx_failed = self.stats.tx_failed.saturating_add(1);
+                self.push_event(tag, dest, FragOutcome::TxFailed);
+            }
+        }
+    }
+
+    /// Drop in-progress datagrams and forwarding entries, such as where the MAC has
+    /// restarted, completed datagrams remain available via [`Frag::pop`]
+    pub fn flush(&mut self) {
+        let active = self
+            .tx_buffs
+            .iter()
+            .chain(self.rx_buffs.iter())
+            .filter(|b| b.state != FragState::None)
+            .count();
+        if active > 0 || !self.fwd.is_empty() {
+            debug!(
+                "Flushed {} datagrams and {} forwarding entries",
+                active,
+                self.fwd.len()
+            );
+        }
+
+        for b in self.tx_buffs.iter_mut().chain(self.rx_buffs.iter_mut()) {
+            *b = FragBuffer::default();
+        }
+        self.in_flight.clear();
+        self.fwd.clear();
+    }
+
+    /// Add a buffer to reassembly tracking, evicting the oldest incomplete
+    /// datagram (by timeout) where all slots are in use and
+    /// [`FragConfig::evict_oldest`] is set
+    fn push<E>(&mut self, fb: FragBuffer<[u8; IPV6_MTU], A>) -> Result<usize, SixLoError<E>> {
+        // Find empty slot, otherwise the oldest in-progress datagram
+        let idx = match self
+            .rx_buffs
+            .iter()
+            .position(|buff| buff.state == FragState::None)
+        {
+            Some(idx) => idx,
+            None if !self.config.evict_oldest => {
+                warn!(
+                    "RX slots full, dropping datagram {} from {:?}",
+                    fb.tag, fb.addr
+                );
+                self.stats.rx_dropped = self.stats.rx_dropped.saturating_add(1);
+                return Err(SixLoError::NoRxSlots);
+            }
+            None => {
+                let (idx, old) = self
+                    .rx_buffs
+                    .iter()
+                    .enumerate()
+                    .min_by_key(|(_idx, buff)| buff.timeout)
+                    .ok_or(SixLoError::NoRxSlots)?;
+
+                warn!(
+                    "RX slots full, evicting datagram {} from {:?}",
+                    old.tag, old.addr
+                );
+                self.stats.rx_evicted = self.stats.rx_evicted.saturating_add(1);
+
+                idx
+            }
+        };
+
+        self.rx_buffs[idx] = fb;
+
+        let used = self
+            .rx_buffs
+            .iter()
+            .filter(|b| b.state != FragState::None)
+            .count() as u32;
+        self.stats.rx_slots_max = self.stats.rx_slots_max.max(used);
+
+        Ok(idx)
+    }
+
+    /// Add a completed datagram to the receive queue
+    fn complete<E>(&mut self, fb: FragBuffer<[u8; IPV6_MTU], A>) -> Result<(), SixLoError<E>> {
+        if let Err(fb) = self.rx_done.enqueue(fb) {
+            warn!(
+                "RX queue full, dropping datagram {} from {:?}",
+                fb.tag, fb.addr
+            );
+            self.stats.rx_dropped = self.stats.rx_dropped.saturating_add(1);
+            return Err(SixLoError::NoRxSlots);
+        }
+
+        self.stats.rx_datagrams = self.stats.rx_datagrams.saturating_add(1);
+
+        Ok(())
+    }
+
+    /// Switch a received fragment of a datagram addressed to another node,
+    /// returning the next hop and fragment header for transmission, or `None`
+    /// where the fragment is to be dropped.
+    ///
+    /// A forwarding entry is created on receipt of the first fragment using
+    /// the provided next hop, with following fragments sent to the same next hop
+    /// (rather than buffered) until the final fragment or the entry times out.
+    /// Datagram tags are rewritten where already in use toward the next hop.
+    pub fn forward(
+        &mut self,
+        now_ms: Ts,
+        origin: &A,
+        fh: &FragHeader,
+        len: usize,
+        next_hop: A,
+    ) -> Option<(A, FragHeader)> {
+        let existing = self
+            .fwd
+            .iter()
+            .position(|f| &f.origin == origin && f.tag == fh.datagram_tag);
+
+        let i = match (fh.datagram_offset, existing) {
+            (_, Some(i)) => i,
+            // Create a forwarding entry from the first fragment
+            (None, None) => {
+                let out_tag = self.fwd_tag(&next_hop, fh.datagram_tag);
+                let entry = FwdEntry {
+                    origin: origin.clone(),
+                    tag: fh.datagram_tag,
+                    next_hop,
+                    out_tag,
+                    timeout: now_ms + self.config.frag_rx_timeout_ms,
+                };
+
+                if let Err(e) = self.fwd.push(entry) {
+                    warn!(
+                        "Forwarding table full, dropping datagram {} from {:?}",
+                        e.tag, e.origin
+                    );
+                    self.stats.fwd_dropped = self.stats.fwd_dropped.saturating_add(1);
+                    return None;
+                }
+
+                debug!(
+                    "Forwarding datagram {} from {:?} via {:?} (tag {})",
+                    fh.datagram_tag,
+                    origin,
+                    self.fwd[self.fwd.len() - 1].next_hop,
+                    out_tag
+                );
+                self.stats.fwd_datagrams = self.stats.fwd_datagrams.saturating_add(1);
+
+                self.fwd.len() - 1
+            }
+            // Following fragments cannot be routed without an entry
+            (Some(_), None) => {
+                debug!(
+                    "No forwarding entry for datagram {} from {:?}, dropping fragment",
+                    fh.datagram_tag, origin
+                );
+                self.stats.fwd_dropped = self.stats.fwd_dropped.saturating_add(1);
+                return None;
+            }
+        };
+
+        let out = FragHeader {
+            datagram_tag: self.fwd[i].out_tag,
+            ..fh.clone()
+        };
+        let next_hop = self.fwd[i].next_hop.clone();
+
+        // Release the entry once the final fragment is forwarded
+        let end = fh.datagram_offset.unwrap_or(0) as usize * FRAG_BLOCK + len;
+        if fh.datagram_offset.is_some() && end >= fh.datagram_size as usize {
+            debug!("Forwarding datagram {} complete", fh.datagram_tag);
+            self.fwd.swap_remove(i);
+        }
+
+        Some((next_hop, out))
+    }
+
+    /// Select a datagram tag for forwarding toward the next hop,
+    /// retaining the original tag unless this is already in use
+    fn fwd_tag(&mut self, next_hop: &A, tag: u16) -> u16 {
+        let in_use = |fwd: &[FwdEntry<A>], t: u16| {
+            fwd.iter()
+                .any(|f| &f.next_hop == next_hop && f.out_tag == t)
         };
 
-        // Clear slot state
-        slot.state = FragState::None;
+        if !in_use(&self.fwd, tag) {
+            return tag;
+        }
+
+        // Allocate from our own tag counter, the table size bounds the search
+        loop {
+            let t = self.tag;
+            self.tag = self.tag.wrapping_add(1);
+
+            if !in_use(&self.fwd, t) {
+                return t;
+            }
+        }
+    }
+
+    /// Fetch the next completed datagram without removing it
+    pub fn peek<'a>(&'a self) -> Option<(DatagramInfo<A>, &'a Header, &'a [u8])> {
+        let s = self.rx_done.peek()?;
+        Some((s.rx_info(), &s.header, s.data()))
+    }
 
-        // Return slot header / data
-        Some((&slot.addr, &slot.header, slot.data()))
+    /// Remove a completed datagram
+    pub fn pop<'a>(&'a mut self) -> Option<(DatagramInfo<A>, &'a Header, &'a [u8])> {
+        // Fetch the next completed datagram
+        self.rx_current = self.rx_done.dequeue()?;
+
+        // Return datagram info / header / data
+        let s = &self.rx_current;
+        Some((s.rx_info(), &s.header, s.data()))
     }
 
-    /// Handle received fragments
+    /// Handle received fragments, with the source and RSSI of each provided via `info`
     pub fn receive<E>(
         &mut self,
         now_ms: Ts,
-        src: MacAddress,
+        info: RxInfo<A>,
         hdr: &Header,
         d: &[u8],
     ) -> Result<(), SixLoError<E>> {
+        let RxInfo {
+            source: src, rssi, ..
+        } = info;
+
+        if hdr.frag.is_some() {
+            self.stats.rx_fragments = self.stats.rx_fragments.saturating_add(1);
+        }
+
         // Find a matching fragment buffer
         let slot_idx = hdr
             .frag
             .as_ref()
             .map(|fh| {
-                self.buffs
+                self.rx_buffs
                     .iter()
                     .enumerate()
                     .find(|(_i, buff)| {
@@ -162,62 +744,136 @@ impl<const MAX_FRAG_SIZE: usize> Frag<MAX_FRAG_SIZE> {
             // Create a new buffer if no match exists
             (Some(_fh), None) => {
                 // Setup new receive buffer
-                let mut fb = FragBuffer::init_rx(src, hdr, d);
+                let mut fb = match FragBuffer::init_rx(src, hdr, d, rssi) {
+                    Ok(fb) => fb,
+                    Err(e) => {
+                        warn!("Fragment RX error: {:?}", e);
+                        self.stats.rx_invalid = self.stats.rx_invalid.saturating_add(1);
+                        return Err(SixLoError::Frag(e));
+                    }
+                };
                 fb.timeout = now_ms + self.config.frag_rx_timeout_ms;
 
                 debug!("Fragment {} RX start", fb.tag);
 
-                self.push(fb)?;
+                // Single fragment datagrams may already be complete
+                if fb.state == FragState::Done {
+                    let (tag, peer) = (fb.tag, fb.addr.clone());
+                    self.complete(fb)?;
+                    self.push_event(tag, peer, FragOutcome::RxComplete);
+                } else {
+                    self.push(fb)?;
+                }
             }
             // Update an existing buffer if found
             (Some(_fh), Some(i)) => {
-                let s = &mut self.buffs[i];
-                let done = s.update_rx(hdr, d);
+                let s = &mut self.rx_buffs[i];
+                let done = match s.update_rx(hdr, d, rssi) {
+                    Ok(done) => done,
+                    Err(e) => {
+                        warn!("Fragment {} RX error: {:?}", s.tag, e);
+                        self.stats.rx_invalid = self.stats.rx_invalid.saturating_add(1);
+
+                        // Overlapping fragments invalidate the datagram (RFC4944 Section 5.3)
+                        if e == FragError::Overlap {
+                            s.state = FragState::None;
+                        }
+
+                        return Err(SixLoError::Frag(e));
+                    }
+                };
 
                 if done {
                     debug!("Fragment {} RX complete", s.tag);
-                    // TODO: track completed fragment stats
-                    s.state = FragState::Done;
+
+                    // Move the datagram to the receive queue, freeing the slot
+                    let fb = core::mem::take(s);
+                    let (tag, peer) = (fb.tag, fb.addr.clone());
+                    self.complete(fb)?;
+                    self.push_event(tag, peer, FragOutcome::RxComplete);
                 }
             }
             // Skip fragmentation if not required
             (None, _) => {
-                let fb = FragBuffer::init_done(src, hdr, d);
+                let fb = FragBuffer::init_done(src, hdr, d, rssi);
 
-                self.push(fb)?;
+                self.complete(fb)?;
             }
         }
 
         Ok(())
     }
 
-    /// Poll for outgoing messages
+    /// Poll for outgoing messages, with fragments limited to the configured
+    /// fragment size (see [`Frag::poll_budget`] to limit these to the MAC payload)
     pub fn poll<'a>(
         &'a mut self,
         now_ms: Ts,
-        opts: PollOptions,
-    ) -> Option<(MacAddress, Header, &'a [u8])> {
-        // Handle timeouts
-        for i in 0..self.buffs.len() {
-            if self.buffs[i].state == FragState::None {
+        opts: PollOptions<A>,
+    ) -> Option<(A, Header, &'a [u8])> {
+        self.poll_budget(now_ms, opts, |_, _| usize::MAX)
+    }
+
+    /// Poll for outgoing messages, with each fragment limited to the configured
+    /// fragment size and the data budget returned by `budget` for the fragment
+    /// destination and headers, typically the MAC payload size less the encoded
+    /// header length. Non-final fragments are aligned down to 8 bytes, where the
+    /// budget does not allow this the datagram is aborted
+    pub fn poll_budget<'a, F>(
+        &'a mut self,
+        now_ms: Ts,
+        opts: PollOptions<A>,
+        budget: F,
+    ) -> Option<(A, Header, &'a [u8])>
+    where
+        F: Fn(&A, &Header) -> usize,
+    {
+        // Handle timeouts, reporting the outcome of dropped datagrams
+        for i in 0..TX_SLOTS + RX_SLOTS {
+            let b = match i.checked_sub(TX_SLOTS) {
+                None => &mut self.tx_buffs[i],
+                Some(i) => &mut self.rx_buffs[i],
+            };
+            if b.state == FragState::None || b.timeout == 0 || now_ms <= b.timeout {
                 continue;
             }
 
-            if self.buffs[i].timeout != 0 && now_ms > self.buffs[i].timeout {
-                warn!(
-                    "Timeout for datagram {} via {:?}",
-                    self.buffs[i].tag, self.buffs[i].addr
-                );
+            warn!("Timeout for datagram {} via {:?}", b.tag, b.addr);
+
+            let outcome = match b.state {
+                FragState::Rx => {
+                    self.stats.rx_timeouts = self.stats.rx_timeouts.saturating_add(1);
+                    FragOutcome::RxTimeout
+                }
+                _ => {
+                    self.stats.tx_timeouts = self.stats.tx_timeouts.saturating_add(1);
+                    FragOutcome::TxTimeout
+                }
+            };
+            b.state = FragState::None;
 
-                // TODO: signal / count datagram failures
+            let (tag, peer) = (b.tag, b.addr.clone());
+            self.push_event(tag, peer, outcome);
+        }
 
-                self.buffs[i].state = FragState::None;
+        // Expire forwarding entries for incomplete datagrams
+        let mut i = 0;
+        while i < self.fwd.len() {
+            if now_ms > self.fwd[i].timeout {
+                warn!(
+                    "Timeout forwarding datagram {} from {:?}",
+                    self.fwd[i].tag, self.fwd[i].origin
+                );
+                self.fwd.swap_remove(i);
+            } else {
+                i += 1;
             }
         }
 
-        // Update TX buffers
-        for i in 0..self.buffs.len() {
-            if self.buffs[i].state != FragState::Tx {
+        // Update TX buffers, starting from that following the last serviced
+        for n in 0..TX_SLOTS {
+            let i = (self.tx_next + n) % TX_SLOTS;
+            if self.tx_buffs[i].state != FragState::Tx {
                 continue;
             }
 
@@ -225,18 +881,45 @@ impl<const MAX_FRAG_SIZE: usize> Frag<MAX_FRAG_SIZE> {
             if !opts.can_tx {
                 continue;
             }
-            if opts.tx_addr != MacAddress::None && opts.tx_addr != self.buffs[i].addr {
+            if matches!(&opts.tx_addr, Some(a) if a != &self.tx_buffs[i].addr) {
                 continue;
             }
 
-            // Return fragment for TX
-            if let Some((h, o, l)) = self.buffs[i].next() {
-                debug!("TX fragment {} offset {}", self.buffs[i].tag, o);
+            // Fragments are held until the MAC reports outcomes, with up to
+            // `tx_window` awaiting these
+            if !self.tx_buffs[i].sendable(self.config.tx_window) {
+                continue;
+            }
 
-                return Some((self.buffs[i].addr, h, self.buffs[i].frag_data(o, l)));
-            } else {
-                debug!("TX fragment {} complete", self.buffs[i].tag);
+            // Pace fragments of each datagram
+            if now_ms < self.tx_buffs[i].next_tx {
+                continue;
+            }
+
+            // Size the current fragment to the budget following its headers
+            let b = &mut self.tx_buffs[i];
+            let (h, o, l) = b.frag(budget(&b.addr, &b.frag_header()));
+            if l == 0 {
+                warn!(
+                    "No payload for fragment {} offset {} via {:?}, aborting",
+                    b.tag, b.offset, b.addr
+                );
+
+                b.state = FragState::None;
+                let (tag, peer) = (b.tag, b.addr.clone());
+                self.stats.tx_failed = self.stats.tx_failed.saturating_add(1);
+                self.push_event(tag, peer, FragOutcome::TxFailed);
+                continue;
             }
+            b.frag_len = l;
+            b.polled = now_ms;
+
+            // Return the current fragment for TX, this is returned again until
+            // marked as sent via `Frag::sent`
+            let b = &self.tx_buffs[i];
+            debug!("TX fragment {} offset {} ({} bytes)", b.tag, o, l);
+
+            return Some((b.addr.clone(), h, b.frag_data(o, l)));
         }
 
         None
@@ -245,18 +928,18 @@ impl<const MAX_FRAG_SIZE: usize> Frag<MAX_FRAG_SIZE> {
 
 /// Options for fragment polling
 #[derive(Clone, PartialEq, Debug)]
-pub struct PollOptions {
+pub struct PollOptions<A = MacAddress> {
     /// Signals that fragments can be transmitted
     pub can_tx: bool,
     /// Filter outgoing fragments by destination address
-    pub tx_addr: MacAddress,
+    pub tx_addr: Option<A>,
 }
 
-impl Default for PollOptions {
+impl<A> Default for PollOptions<A> {
     fn default() -> Self {
         Self {
             can_tx: true,
-            tx_addr: MacAddress::None,
+            tx_addr: None,
         }
     }
 }
@@ -298,52 +981,91 @@ impl FragData for alloc::vec::Vec<u8> {
 
 /// Fragment buffer, contains a datagram for fragmentation and defragmentation
 #[derive(Clone, PartialEq, Debug)]
-pub struct FragBuffer<B: FragData, const MAX_FRAG: usize> {
+pub struct FragBuffer<B: FragData, A = MacAddress> {
     pub state: FragState,
     pub header: Header,
-    pub addr: MacAddress,
+    pub addr: A,
     pub tag: u16,
+    /// Uncompressed datagram size, with data stored at uncompressed offsets
     pub len: usize,
-    pub mask: u32,
+    /// Length of the uncompressed IPv6 (and UDP) headers preceding the data,
+    /// carried compressed in the first fragment
+    pub hdr_len: usize,
+    /// Maximum fragment size for transmission
+    pub frag_size: usize,
+    /// Received 8-byte blocks, used to detect duplicate / overlapping fragments
+    pub mask: [u32; MAX_DATAGRAM_SIZE / FRAG_BLOCK / 32],
     pub timeout: Ts,
     pub offset: usize,
+    /// Length of the fragment at `offset` last returned for transmission,
+    /// cleared once this is handed to the MAC
+    pub frag_len: usize,
+    /// Fragments handed to the MAC and awaiting transmit outcomes
+    pub in_flight: u8,
+    /// Length of the datagram confirmed as transmitted
+    pub acked: usize,
+    /// Time the fragment at `offset` was last returned for transmission
+    pub polled: Ts,
+    /// Earliest time the next fragment may be returned for transmission
+    pub next_tx: Ts,
+    /// Retries of the fragment at `offset`
+    pub retries: u8,
+    /// Received fragments excluding duplicates, with the minimum and summed RSSI of these
+    pub rx_frags: u16,
+    pub rssi_min: i16,
+    pub rssi_sum: i32,
     pub buff: B,
 }
 
-/// Default helper for constructing new fragmentation buffer instances
-impl<B: FragData, const MAX_FRAG: usize> Default for FragBuffer<B, MAX_FRAG> {
+/// Default helper for constructing new fragmentation buffer instances,
+/// unused buffers hold the broadcast address
+impl<B: FragData, A: AddressOps> Default for FragBuffer<B, A> {
     fn default() -> Self {
         Self {
             state: FragState::None,
-            addr: MacAddress::None,
+            addr: A::broadcast(),
             header: Header::default(),
             tag: 0,
             len: 0,
-            mask: 0,
+            hdr_len: 0,
+            frag_size: DEFAULT_FRAG_SIZE,
+            mask: [0; MAX_DATAGRAM_SIZE / FRAG_BLOCK / 32],
             timeout: 0,
             offset: 0,
+            frag_len: 0,
+            in_flight: 0,
+            acked: 0,
+            polled: 0,
+            next_tx: 0,
+            retries: 0,
+            rx_frags: 0,
+            rssi_min: i16::MAX,
+            rssi_sum: 0,
             buff: B::empty(0),
         }
     }
 }
 
-impl<B: FragData, const MAX_FRAG: usize> FragBuffer<B, MAX_FRAG> {
+impl<B: FragData, A: AddressOps> FragBuffer<B, A> {
     /// Initialise a fragmentation buffer in receive mode
-    pub fn init_rx(source: MacAddress, header: &Header, data: &[u8]) -> Self {
-        let fh = match &header.frag {
-            Some(fh) => fh.clone(),
-            None => unimplemented!(),
-        };
+    pub fn init_rx(source: A, header: &Header, data: &[u8], rssi: i16) -> Result<Self, FragError> {
+        let fh = header.frag.as_ref().ok_or(FragError::Mismatch)?;
 
         let mut s = Self {
             state: FragState::Rx,
             header: header.clone(),
-            addr: source,
+            addr: source.clone(),
             tag: fh.datagram_tag,
             len: fh.datagram_size as usize,
+            buff: B::empty(fh.datagram_size as usize),
             ..Default::default()
         };
 
+        // Check the datagram fits in the buffer
+        if s.len > s.buff.as_ref().len() {
+            return Err(FragError::Length);
+        }
+
         debug!(
             "New RX fragment from: {:?} tag: {} ({} bytes, {} fragments)",
             source,
@@ -352,21 +1074,31 @@ impl<B: FragData, const MAX_FRAG: usize> FragBuffer<B, MAX_FRAG> {
             s.num_frags()
         );
 
-        s.update_rx(header, data);
+        s.update_rx(header, data, rssi)?;
 
-        s
+        Ok(s)
     }
 
-    /// Initialise a fragmentation buffer in transmit mode
-    pub fn init_tx(dest: MacAddress, header: Header, tag: u16, data: &[u8]) -> Self {
-        let buff = B::from_bytes(data);
+    /// Initialise a fragmentation buffer in transmit mode.
+    ///
+    /// The datagram size and offsets are those of the uncompressed IPv6 datagram,
+    /// per [RFC4944 Section 5.3](https://tools.ietf.org/html/rfc4944#section-5.3),
+    /// with the IPHC (and UDP) headers standing in for the uncompressed headers
+    /// in the first fragment
+    pub fn init_tx(dest: A, header: Header, tag: u16, frag_size: usize, data: &[u8]) -> Self {
+        let hdr_len = header.uncompressed_len();
+
+        let mut buff = B::empty(hdr_len + data.len());
+        buff.as_mut()[hdr_len..hdr_len + data.len()].copy_from_slice(data);
 
         let mut s = Self {
             state: FragState::Tx,
             header: header,
-            addr: dest,
-            len: data.len(),
+            addr: dest.clone(),
+            len: hdr_len + data.len(),
+            hdr_len,
             tag,
+            frag_size,
             buff,
             ..Default::default()
         };
@@ -385,13 +1117,13 @@ impl<B: FragData, const MAX_FRAG: usize> FragBuffer<B, MAX_FRAG> {
     }
 
     /// Initialise fragmentation buffer with received data
-    pub fn init_done(source: MacAddress, header: &Header, data: &[u8]) -> Self {
+    pub fn init_done(source: A, header: &Header, data: &[u8], rssi: i16) -> Self {
         let buff = B::from_bytes(data);
 
-        let s = Self {
+        let mut s = Self {
             state: FragState::Done,
             header: header.clone(),
-            addr: source,
+            addr: source.clone(),
             tag: 0,
             len: data.len(),
             buff,
@@ -403,128 +1135,225 @@ impl<B: FragData, const MAX_FRAG: usize> FragBuffer<B, MAX_FRAG> {
             source, s.tag, s.len
         );
 
+        s.record_rssi(rssi);
+
         s
     }
 
-    /// Compute the number of fragments for a configured buffer
+    /// Compute the number of fragments for a configured buffer at the maximum
+    /// fragment size, fragments may be smaller where limited by headers
     pub fn num_frags(&self) -> usize {
-        let num_frags = self.len / MAX_FRAG;
-        if self.len % MAX_FRAG != 0 {
+        let num_frags = self.len / self.frag_size;
+        if self.len % self.frag_size != 0 {
             num_frags + 1
         } else {
             num_frags
         }
     }
 
-    /// Handle fragment receipt
-    pub fn update_rx(&mut self, header: &Header, data: &[u8]) -> bool {
+    /// Handle fragment receipt, returning true once the datagram is complete.
+    ///
+    /// Received data is tracked in 8-byte blocks so reassembly does not depend
+    /// on the fragment size used by the sender, exact duplicates are ignored.
+    pub fn update_rx(
+        &mut self,
+        header: &Header,
+        data: &[u8],
+        rssi: i16,
+    ) -> Result<bool, FragError> {
         // Fetch fragment header
-        let fh = match &header.frag {
-            Some(fh) => fh,
-            None => unimplemented!(),
-        };
+        let fh = header.frag.as_ref().ok_or(FragError::Mismatch)?;
 
         // Check headers match
         // TODO: dest / src addrs as well
-        if fh.datagram_tag != self.tag {
-            unimplemented!()
+        if fh.datagram_tag != self.tag || fh.datagram_size as usize != self.len {
+            return Err(FragError::Mismatch);
+        }
+
+        // The first fragment data follows the (compressed) uncompressed headers
+        let offset = fh.datagram_offset.unwrap_or(0) as usize * FRAG_BLOCK;
+        let start = match fh.datagram_offset {
+            Some(_) => offset,
+            None => header.uncompressed_len(),
+        };
+
+        // Check fragment bounds, all but the final fragment must be a multiple of 8 bytes
+        let end = start + data.len();
+        if end > self.len || (end < self.len && (end - offset) % FRAG_BLOCK != 0) {
+            return Err(FragError::Length);
         }
 
+        // Check for duplicate or overlapping fragments
+        let blocks = offset / FRAG_BLOCK..(end + FRAG_BLOCK - 1) / FRAG_BLOCK;
+        let received = blocks.clone().filter(|i| self.received(*i)).count();
+        if received == blocks.len() {
+            debug!("Fragment {} RX duplicate offset {}", self.tag, offset);
+            return Ok(false);
+        } else if received != 0 {
+            return Err(FragError::Overlap);
+        }
+
+        // Duplicates are excluded so retransmissions do not skew the RSSI aggregate
+        self.record_rssi(rssi);
+
         // Merge headers (in case we receive fragments out of order)
         self.header.merge(header);
         self.header.frag = None;
 
         // Apply fragment
-        let offset = fh.datagram_offset.unwrap_or(0) as usize * 8;
-        let len = data.len();
-        self.buff.as_mut()[offset..offset + len].copy_from_slice(data);
+        self.buff.as_mut()[start..end].copy_from_slice(data);
+        if fh.datagram_offset.is_none() {
+            self.hdr_len = start;
+        }
 
         // Update mask
         self.offset = offset;
-        let index = (offset / MAX_FRAG) as u32;
-        self.mask |= 1 << index;
+        for i in blocks {
+            self.mask[i / 32] |= 1 << (i % 32);
+        }
 
         // Check mask for completion
-        let num_frags = self.num_frags();
-        let check_mask = (1 << num_frags) - 1;
-
-        #[cfg(feature = "defmt")]
-        defmt::debug!(
-            "Fragment {} RX index {} mask 0b{:b} (check 0b{:b})",
-            self.tag,
-            index,
-            self.mask,
-            check_mask
-        );
+        let num_blocks = (self.len + FRAG_BLOCK - 1) / FRAG_BLOCK;
+        let complete = (0..num_blocks).all(|i| self.received(i));
 
-        #[cfg(not(feature = "defmt"))]
-        log::debug!(
-            "Fragment {} RX index {} mask 0b{:08b} (check 0b{:08b})",
+        debug!(
+            "Fragment {} RX offset {} ({} bytes of {})",
             self.tag,
-            index,
-            self.mask,
-            check_mask
+            offset,
+            data.len(),
+            self.len
         );
 
-        if self.mask == check_mask {
+        if complete {
             debug!("Fragment {} RX complete", self.tag);
             self.state = FragState::Done;
-            true
-        } else {
-            false
+        }
+
+        Ok(complete)
+    }
+
+    /// Check whether an 8-byte block has been received
+    fn received(&self, block: usize) -> bool {
+        self.mask[block / 32] & (1 << (block % 32)) != 0
+    }
+
+    /// Record the RSSI of a received fragment
+    fn record_rssi(&mut self, rssi: i16) {
+        self.rx_frags = self.rx_frags.saturating_add(1);
+        self.rssi_min = self.rssi_min.min(rssi);
+        self.rssi_sum += rssi as i32;
+    }
+
+    /// Fetch receive information aggregated over received fragments
+    pub fn rx_info(&self) -> DatagramInfo<A> {
+        let (rssi_min, rssi_avg) = match self.rx_frags {
+            0 => (0, 0),
+            n => (self.rssi_min, (self.rssi_sum / n as i32) as i16),
+        };
+
+        DatagramInfo {
+            source: self.addr.clone(),
+            rssi_min,
+            rssi_avg,
+            fragments: self.rx_frags,
         }
     }
 
-    /// Fetch a fragment header, offset, and data length for transmission
-    pub fn frag(&self, index: usize) -> (Header, usize, usize) {
-        // Setup header and offset
-        let (header, offset) = match index {
+    /// Fetch the header for the fragment at the current offset
+    pub fn frag_header(&self) -> Header {
+        match self.offset {
             0 => {
                 // First fragment contains complete header
-                let h = Header {
+                Header {
                     frag: Some(FragHeader {
                         datagram_size: self.len as u16,
                         datagram_offset: None,
                         datagram_tag: self.tag,
                     }),
                     ..self.header.clone()
-                };
-                (h, 0)
+                }
             }
-            _ => {
-                // Later fragments only fragment header
-                let o = index * MAX_FRAG;
-                let h = Header {
+            o => {
+                // Later fragments only carry mesh, broadcast, and fragment headers
+                Header {
+                    mesh: self.header.mesh.clone(),
+                    bcast: self.header.bcast.clone(),
                     frag: Some(FragHeader {
                         datagram_size: self.len as u16,
-                        datagram_offset: Some((o / 8) as u8),
+                        datagram_offset: Some((o / FRAG_BLOCK) as u8),
                         datagram_tag: self.tag,
                     }),
                     ..Default::default()
-                };
-                (h, o)
+                }
             }
+        }
+    }
+
+    /// Fetch the header, offset, and length of the fragment at the current
+    /// offset for transmission, with the fragment data limited to the maximum
+    /// fragment size and the provided data budget. Non-final fragments end on
+    /// 8-byte boundaries, and the first fragment also covers the uncompressed
+    /// headers, see [`Self::frag_data`] for the transmitted data
+    pub fn frag(&self, budget: usize) -> (Header, usize, usize) {
+        let max = self.frag_size.min(budget);
+
+        // Compute remainder and fragment end
+        let start = self.offset.max(self.hdr_len);
+        let end = if self.len - start <= max {
+            self.len
+        } else {
+            (start + max) & !(FRAG_BLOCK - 1)
         };
 
-        // Compute remainder and fragment length
-        let remainder = self.len - offset;
-        let len = MAX_FRAG.min(remainder);
+        // Fragments without data (where the budget is too small) have no length
+        let len = if end > start { end - self.offset } else { 0 };
+
+        (self.frag_header(), self.offset, len)
+    }
+
+    /// Check whether a fragment may be handed to the MAC, with fewer than `window`
+    /// fragments awaiting transmit outcomes
+    fn sendable(&self, window: u8) -> bool {
+        self.state == FragState::Tx && self.in_flight < window.max(1) && self.offset < self.len
+    }
+
+    /// Confirm transmission of the fragment at `offset`, returning true (and
+    /// releasing the buffer) once all fragments are confirmed. Fragments are sent
+    /// in order, so this also confirms those preceding the fragment
+    fn confirm(&mut self, offset: usize, len: usize) -> bool {
+        self.acked = self.acked.max(offset + len);
+        self.retries = 0;
+
+        if self.acked >= self.len && self.in_flight == 0 {
+            self.state = FragState::None;
+            return true;
+        }
+
+        false
+    }
 
-        (header, offset, len)
+    /// Resend from the fragment at `offset` following a failed transmission
+    fn rewind(&mut self, offset: usize) {
+        self.offset = offset;
+        self.acked = self.acked.min(offset);
+        self.frag_len = 0;
     }
 
-    /// Fetch fragment data given the offset and length from [`Self::frag`]
+    /// Fetch fragment data given the offset and length from [`Self::frag`],
+    /// excluding the uncompressed headers
     pub fn frag_data<'a>(&'a self, offset: usize, len: usize) -> &'a [u8] {
-        &self.buff.as_ref()[offset..offset + len]
+        &self.buff.as_ref()[offset.max(self.hdr_len)..offset + len]
     }
 
-    /// Fetch datagram payload
+    /// Fetch datagram payload, following the uncompressed headers
     pub fn data<'a>(&'a self) -> &'a [u8] {
-        &self.buff.as_ref()[..self.len]
+        &self.buff.as_ref()[self.hdr_len..self.len]
     }
 }
 
-impl<B: FragData, const MAX_FRAG: usize> Iterator for FragBuffer<B, MAX_FRAG> {
+/// Fragment iteration, advancing without confirmation for use where transmit
+/// outcomes are not available (see [`Frag::poll`] for confirmed transmission)
+impl<B: FragData, A: AddressOps> Iterator for FragBuffer<B, A> {
     type Item = (Header, usize, usize);
 
     fn next(&mut self) -> Option<Self::Item> {
@@ -534,11 +1363,11 @@ impl<B: FragData, const MAX_FRAG: usize> Iterator for FragBuffer<B, MAX_FRAG> {
         }
 
         // Retrieve fragment and update offset
-        let r = self.frag(self.offset / MAX_FRAG);
-        self.offset += MAX_FRAG;
+        let r = self.frag(self.frag_size);
+        self.offset += r.2;
 
         // Check for fragment completion
-        if self.offset > self.len {
+        if self.offset >= self.len {
             // TODO: not sure this is the right place to set _none_
             // probably should have TxDone and RxDone options
             self.state = FragState::None;
@@ -555,10 +1384,29 @@ mod test {
 
     use super::*;
 
-    use crate::sixlo::{headers::FragHeader, DEFAULT_FRAG_SIZE};
+    use crate::sixlo::headers::FragHeader;
+    use crate::TxFailure;
     use std::println;
 
-    const MAX_FRAG_SIZE: usize = 64;
+    /// Receive info for a fragment from the provided source
+    fn rx(source: MacAddress) -> RxInfo {
+        RxInfo {
+            source,
+            destination: MacAddress::Short(PanId(1), ShortAddress::broadcast()),
+            rssi: 0,
+        }
+    }
+
+    /// Poll the next fragment for transmission, confirming this as sent
+    fn poll_sent(frag_mgr: &mut Frag, now_ms: Ts) -> Option<(Header, std::vec::Vec<u8>)> {
+        let (a, h, d) = frag_mgr.poll(now_ms, PollOptions::default())?;
+        let d = d.to_vec();
+
+        frag_mgr.sent(0, a, h.frag.as_ref().unwrap().datagram_tag);
+        frag_mgr.tx_status(0, TxStatus::Sent);
+
+        Some((h, d))
+    }
 
     #[test]
     fn fragment() {
@@ -569,10 +1417,11 @@ mod test {
         }
 
         // Setup fragmentation buffer
-        let mut frag_buff = FragBuffer::<[u8; IPV6_MTU], DEFAULT_FRAG_SIZE>::init_tx(
+        let mut frag_buff = FragBuffer::<[u8; IPV6_MTU]>::init_tx(
             MacAddress::None,
             Header::default(),
             0,
+            DEFAULT_FRAG_SIZE,
             &tx,
         );
 
@@ -593,7 +1442,7 @@ mod test {
                     })
                 );
                 assert_eq!(offset, 0);
-                assert_eq!(len, MAX_FRAG_SIZE);
+                assert_eq!(len, DEFAULT_FRAG_SIZE);
             } else {
                 // Later fragments, same size + offsets
                 assert_eq!(
@@ -621,25 +1470,30 @@ mod test {
         }
 
         // Setup fragmentation buffer
-        let mut frag_buff = FragBuffer::<[u8; IPV6_MTU], DEFAULT_FRAG_SIZE>::init_tx(
+        let mut frag_buff = FragBuffer::<[u8; IPV6_MTU]>::init_tx(
             MacAddress::None,
             Header::default(),
             12,
+            DEFAULT_FRAG_SIZE,
             &tx,
         );
 
         let (h1, o, l) = frag_buff.next().unwrap();
 
         // Setup defragmentation buffer
-        let mut defrag_buff = FragBuffer::<[u8; IPV6_MTU], DEFAULT_FRAG_SIZE>::init_rx(
+        let mut defrag_buff = FragBuffer::<[u8; IPV6_MTU]>::init_rx(
             MacAddress::None,
             &h1,
             frag_buff.frag_data(o, l),
-        );
+            0,
+        )
+        .unwrap();
 
         // Transfer fragments
         while let Some((h, o, l)) = frag_buff.next() {
-            defrag_buff.update_rx(&h, frag_buff.frag_data(o, l));
+            defrag_buff
+                .update_rx(&h, frag_buff.frag_data(o, l), 0)
+                .unwrap();
         }
 
         // Check defrag state
@@ -647,6 +1501,188 @@ mod test {
         assert_eq!(frag_buff.data(), defrag_buff.data());
     }
 
+    /// Collect fragments for a datagram using the provided fragment size
+    fn fragments(tx: &[u8], frag_size: usize) -> std::vec::Vec<(Header, std::vec::Vec<u8>)> {
+        let mut frag_buff = FragBuffer::<[u8; IPV6_MTU]>::init_tx(
+            MacAddress::None,
+            Header::default(),
+            7,
+            frag_size,
+            tx,
+        );
+
+        let mut frags = std::vec::Vec::new();
+        while let Some((h, o, l)) = frag_buff.next() {
+            frags.push((h, frag_buff.frag_data(o, l).to_vec()));
+        }
+        frags
+    }
+
+    #[test]
+    fn defragment_out_of_order() {
+        let tx: std::vec::Vec<u8> = (0..200).map(|i| i as u8).collect();
+        let frags = fragments(&tx, DEFAULT_FRAG_SIZE);
+
+        // Deliver fragments in reverse order, with duplicates
+        let (h, d) = &frags[frags.len() - 1];
+        let mut defrag_buff =
+            FragBuffer::<[u8; IPV6_MTU]>::init_rx(MacAddress::None, h, d, 0).unwrap();
+
+        for (i, (h, d)) in frags.iter().enumerate().rev() {
+            assert_eq!(defrag_buff.update_rx(h, d, 0), Ok(i == 0));
+
+            // Exact duplicates are ignored
+            assert_eq!(defrag_buff.update_rx(h, d, 0), Ok(false));
+        }
+
+        assert_eq!(defrag_buff.state, FragState::Done);
+        assert_eq!(defrag_buff.data(), &tx[..]);
+    }
+
+    #[test]
+    fn defragment_sender_frag_size() {
+        let tx: std::vec::Vec<u8> = (0..200).map(|i| i as u8).collect();
+
+        // Sender uses a smaller fragment size than the receiver
+        let frags = fragments(&tx, 48);
+        assert_eq!(frags.len(), 5);
+
+        let (h, d) = &frags[0];
+        let mut defrag_buff =
+            FragBuffer::<[u8; IPV6_MTU]>::init_rx(MacAddress::None, h, d, 0).unwrap();
+
+        // Datagram is only complete once all fragments are received
+        for (i, (h, d)) in frags.iter().enumerate().skip(1) {
+            let done = defrag_buff.update_rx(h, d, 0).unwrap();
+            assert_eq!(done, i == frags.len() - 1);
+        }
+
+        assert_eq!(defrag_buff.state, FragState::Done);
+        assert_eq!(defrag_buff.data(), &tx[..]);
+    }
+
+    #[test]
+    fn defragment_invalid() {
+        let frag = |offset: Option<u8>| Header {
+            frag: Some(FragHeader {
+                datagram_size: 100,
+                datagram_tag: 3,
+                datagram_offset: offset,
+            }),
+            ..Default::default()
+        };
+
+        let mut defrag_buff =
+            FragBuffer::<[u8; IPV6_MTU]>::init_rx(MacAddress::None, &frag(None), &[0xaa; 64], 0)
+                .unwrap();
+
+        // Fragments exceeding the datagram size are rejected
+        assert_eq!(
+            defrag_buff.update_rx(&frag(Some(12)), &[0xbb; 16], 0),
+            Err(FragError::Length)
+        );
+
+        // Non-final fragments must be a multiple of 8 bytes
+        assert_eq!(
+            defrag_buff.update_rx(&frag(Some(8)), &[0xbb; 12], 0),
+            Err(FragError::Length)
+        );
+
+        // Fragments partially overlapping received data are rejected
+        assert_eq!(
+            defrag_buff.update_rx(&frag(Some(4)), &[0xbb; 64], 0),
+            Err(FragError::Overlap)
+        );
+        assert_eq!(defrag_buff.state, FragState::Rx);
+
+        // Datagrams exceeding the buffer capacity are rejected
+        assert_eq!(
+            FragBuffer::<[u8; 64]>::init_rx(MacAddress::None, &frag(None), &[0xaa; 64], 0),
+            Err(FragError::Length)
+        );
+    }
+
+    /// Corrupted fragment headers are rejected without disrupting reassembly
+    #[test]
+    fn frag_rx_corrupt() {
+        let src = MacAddress::Short(PanId(1), ShortAddress(1));
+        let dst = MacAddress::Short(PanId(1), ShortAddress(2));
+
+        let mut frag_mgr = Frag::<MacAddress>::new(FragConfig::default());
+
+        // Deterministic pseudo-random FRAG1 / FRAGN frames
+        let mut seed = 0x1234_5678u32;
+        let mut next = || {
+            seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+            (seed >> 16) as u8
+        };
+
+        let mut invalid = 0;
+        for _ in 0..1000 {
+            let mut buff = [0u8; 127];
+            for b in buff.iter_mut() {
+                *b = next();
+            }
+            buff[0] = (buff[0] & 0b0000_0111) | if buff[1] & 1 == 0 { 0xc0 } else { 0xe0 };
+            let len = 5 + next() as usize % (buff.len() - 5);
+
+            let (h, n) = match Header::decode(&buff[..len], &src, &dst) {
+                Ok(v) => v,
+                Err(_) => continue,
+            };
+
+            let r = frag_mgr.receive::<()>(0, rx(src), &h, &buff[n..len]);
+            if let Err(SixLoError::Frag(_)) = r {
+                invalid += 1;
+            }
+            while frag_mgr.pop().is_some() {}
+        }
+
+        assert!(invalid > 0);
+        assert_eq!(frag_mgr.stats().rx_invalid, invalid);
+
+        // Valid datagrams are still reassembled
+        let tx: std::vec::Vec<u8> = (0..200).map(|i| i as u8).collect();
+        for (h, d) in fragments(&tx, DEFAULT_FRAG_SIZE).iter() {
+            frag_mgr.receive::<()>(1, rx(dst), h, d).unwrap();
+        }
+
+        let (_info, _h, d) = frag_mgr.pop().unwrap();
+        assert_eq!(d, &tx[..]);
+    }
+
+    /// Test overlapping fragments abort reassembly of the datagram
+    #[test]
+    fn frag_rx_overlap() {
+        let addr_a = MacAddress::Short(PanId(1), ShortAddress(1));
+        let tx = [0xaa; 200];
+        let frags = fragments(&tx, DEFAULT_FRAG_SIZE);
+
+        let mut frag_mgr: Frag = Frag::new(FragConfig::default());
+        frag_mgr
+            .receive::<()>(0, rx(addr_a), &frags[0].0, &frags[0].1)
+            .unwrap();
+
+        // Fragment overlapping the first fragment
+        let h = Header {
+            frag: Some(FragHeader {
+                datagram_offset: Some(4),
+                ..frags[1].0.frag.clone().unwrap()
+            }),
+            ..Default::default()
+        };
+        assert_eq!(
+            frag_mgr.receive::<()>(1, rx(addr_a), &h, &frags[1].1),
+            Err(SixLoError::Frag(FragError::Overlap))
+        );
+
+        // Remaining fragments start a new (incomplete) datagram
+        for (h, d) in &frags[1..] {
+            frag_mgr.receive::<()>(2, rx(addr_a), h, d).unwrap();
+        }
+        assert!(frag_mgr.pop().is_none());
+    }
+
     #[test]
     fn frag_buffer() {
         let _ =
@@ -662,8 +1698,8 @@ mod test {
         let addr_a = MacAddress::Short(PanId(1), ShortAddress(1));
         let addr_b = MacAddress::Short(PanId(1), ShortAddress(2));
 
-        let mut frag_mgr_a = Frag::<64>::new(FragConfig::default());
-        let mut frag_mgr_b = Frag::<64>::new(FragConfig::default());
+        let mut frag_mgr_a: Frag = Frag::new(FragConfig::default());
+        let mut frag_mgr_b: Frag = Frag::new(FragConfig::default());
 
         let mut now_ms = 0;
 
@@ -677,9 +1713,11 @@ mod test {
 
         // Poll for fragments to TX
         let mut frag_rx = false;
-        while let Some((_a, h1, d1)) = frag_mgr_a.poll(now_ms, PollOptions::default()) {
+        while let Some((h1, d1)) = poll_sent(&mut frag_mgr_a, now_ms) {
             // Receive fragments
-            frag_mgr_b.receive::<()>(now_ms, addr_a, &h1, d1).unwrap();
+            frag_mgr_b
+                .receive::<()>(now_ms, rx(addr_a), &h1, &d1)
+                .unwrap();
 
             // Poll for complete message
             if let Some((_a, h2, d2)) = frag_mgr_b.pop() {
@@ -695,6 +1733,105 @@ mod test {
         assert!(frag_rx);
     }
 
+    /// Test the same datagram is transferred using various fragment sizes
+    #[test]
+    fn frag_buffer_sizes() {
+        let tx: std::vec::Vec<u8> = (0..200).map(|i| i as u8).collect();
+
+        let addr_a = MacAddress::Short(PanId(1), ShortAddress(1));
+        let addr_b = MacAddress::Short(PanId(1), ShortAddress(2));
+
+        for frag_size in [32, 64, 96].iter() {
+            let cfg = FragConfig {
+                frag_size: Some(*frag_size),
+                ..Default::default()
+            };
+            let mut frag_mgr_a: Frag = Frag::new(cfg.clone());
+            let mut frag_mgr_b: Frag = Frag::new(cfg);
+
+            frag_mgr_a
+                .transmit::<()>(0, addr_b, Header::default(), &tx)
+                .unwrap();
+
+            let mut num_frags = 0;
+            let mut rx_data = None;
+            while let Some((h, d)) = poll_sent(&mut frag_mgr_a, 0) {
+                assert!(d.len() <= *frag_size);
+                num_frags += 1;
+
+                frag_mgr_b.receive::<()>(0, rx(addr_a), &h, &d).unwrap();
+                if let Some((_a, _h, d)) = frag_mgr_b.pop() {
+                    rx_data = Some(d.to_vec());
+                }
+            }
+
+            assert_eq!(num_frags, (tx.len() + frag_size - 1) / frag_size);
+            assert_eq!(rx_data, Some(tx.clone()));
+        }
+    }
+
+    /// Test fragments are limited to the budget following the headers of each fragment
+    #[test]
+    fn frag_buffer_budget() {
+        let tx: std::vec::Vec<u8> = (0..200).map(|i| i as u8).collect();
+
+        let addr_a = MacAddress::Short(PanId(1), ShortAddress(1));
+        let addr_b = MacAddress::Short(PanId(1), ShortAddress(2));
+
+        let cfg = FragConfig {
+            frag_size: Some(128),
+            ..Default::default()
+        };
+        let mut frag_mgr_a: Frag = Frag::new(cfg);
+        let mut frag_mgr_b: Frag = Frag::new(FragConfig::default());
+
+        frag_mgr_a
+            .transmit::<()>(0, addr_b, Header::default(), &tx)
+            .unwrap();
+
+        // Budget for a 54 byte MAC payload, less FRAG1 (4 byte) or FRAGN (5 byte) headers
+        let budget = |_: &MacAddress, h: &Header| match h.frag.as_ref().unwrap().datagram_offset {
+            None => 50,
+            Some(_) => 49,
+        };
+
+        let mut sizes = std::vec::Vec::new();
+        let mut rx_data = None;
+        while let Some((a, h, d)) = frag_mgr_a.poll_budget(0, PollOptions::default(), budget) {
+            let (h, d) = (h, d.to_vec());
+
+            // Offsets are in units of 8 bytes, as are all but the final fragment
+            let offset = h.frag.as_ref().unwrap().datagram_offset.unwrap_or(0) as usize * 8;
+            assert_eq!(offset, sizes.iter().sum::<usize>());
+            sizes.push(d.len());
+
+            frag_mgr_a.sent(0, a, h.frag.as_ref().unwrap().datagram_tag);
+            frag_mgr_a.tx_status(0, TxStatus::Sent);
+
+            frag_mgr_b.receive::<()>(0, rx(addr_a), &h, &d).unwrap();
+            if let Some((_a, _h, d)) = frag_mgr_b.pop() {
+                rx_data = Some(d.to_vec());
+            }
+        }
+
+        assert_eq!(sizes, &[48, 48, 48, 48, 8]);
+        assert_eq!(rx_data, Some(tx.clone()));
+
+        // Datagrams are aborted where the budget does not allow a fragment
+        frag_mgr_a
+            .transmit::<()>(1, addr_b, Header::default(), &tx)
+            .unwrap();
+        assert!(frag_mgr_a
+            .poll_budget(1, PollOptions::default(), |_, _| 7)
+            .is_none());
+
+        assert_eq!(frag_mgr_a.stats().tx_failed, 1);
+        let outcomes: std::vec::Vec<_> = core::iter::from_fn(|| frag_mgr_a.take_event())
+            .map(|e| e.outcome)
+            .collect();
+        assert_eq!(outcomes, &[FragOutcome::TxComplete, FragOutcome::TxFailed]);
+    }
+
     /// Test passthrough of packets that do not need to be fragmented
     #[test]
     fn frag_buffer_passthrough() {
@@ -711,8 +1848,8 @@ mod test {
         let addr_a = MacAddress::Short(PanId(1), ShortAddress(1));
         let addr_b = MacAddress::Short(PanId(1), ShortAddress(2));
 
-        let mut frag_mgr_a = Frag::<64>::new(FragConfig::default());
-        let mut frag_mgr_b = Frag::<64>::new(FragConfig::default());
+        let mut frag_mgr_a: Frag = Frag::new(FragConfig::default());
+        let mut frag_mgr_b: Frag = Frag::new(FragConfig::default());
 
         let mut now_ms = 0;
 
@@ -725,10 +1862,12 @@ mod test {
             .unwrap();
 
         // Poll for fragments to TX
-        let (_a, h1, d1) = frag_mgr_a.poll(now_ms, PollOptions::default()).unwrap();
+        let (h1, d1) = poll_sent(&mut frag_mgr_a, now_ms).unwrap();
 
         // Receive fragments
-        frag_mgr_b.receive::<()>(now_ms, addr_a, &h1, d1).unwrap();
+        frag_mgr_b
+            .receive::<()>(now_ms, rx(addr_a), &h1, &d1)
+            .unwrap();
 
         // Poll for complete message
         let (_a, h2, d2) = frag_mgr_b.pop().unwrap();
@@ -737,4 +1876,665 @@ mod test {
         assert_eq!(&h, h2);
         assert_eq!(&tx, d2);
     }
+
+    /// Test a burst of unfragmented datagrams is queued up to the RX queue depth
+    #[test]
+    fn frag_rx_burst() {
+        let addr_a = MacAddress::Short(PanId(1), ShortAddress(1));
+
+        let mut frag_mgr: Frag = Frag::new(FragConfig::default());
+
+        // Start reassembly of a fragmented datagram, occupying a slot
+        let frags = fragments(&[0xbb; 200], DEFAULT_FRAG_SIZE);
+        frag_mgr
+            .receive::<()>(0, rx(addr_a), &frags[0].0, &frags[0].1)
+            .unwrap();
+
+        // Receive a burst of small datagrams
+        for i in 0..8u8 {
+            let r = frag_mgr.receive::<()>(0, rx(addr_a), &Header::default(), &[i; 10]);
+            if (i as usize) < RX_QUEUE_DEPTH {
+                assert_eq!(r, Ok(()));
+            } else {
+                assert_eq!(r, Err(SixLoError::NoRxSlots));
+            }
+        }
+        assert_eq!(frag_mgr.stats().rx_dropped, 8 - RX_QUEUE_DEPTH as u32);
+
+        // Queued datagrams are returned in order
+        for i in 0..RX_QUEUE_DEPTH as u8 {
+            let (a, _h, d) = frag_mgr.pop().unwrap();
+            assert_eq!(a.source, addr_a);
+            assert_eq!(d, &[i; 10]);
+        }
+        assert!(frag_mgr.pop().is_none());
+
+        // Reassembly is unaffected
+        for (h, d) in &frags[1..] {
+            frag_mgr.receive::<()>(1, rx(addr_a), h, d).unwrap();
+        }
+        let (_a, _h, d) = frag_mgr.pop().unwrap();
+        assert_eq!(d, &[0xbb; 200][..]);
+    }
+
+    /// Test interleaved datagrams from multiple sources are reassembled, with the
+    /// oldest incomplete datagram evicted where reassembly slots are exhausted
+    #[test]
+    fn frag_rx_evict() {
+        let sources: std::vec::Vec<_> = (1..=4)
+            .map(|i| MacAddress::Short(PanId(1), ShortAddress(i)))
+            .collect();
+        let frags: std::vec::Vec<_> = (1..=4u8)
+            .map(|i| fragments(&[i; 200], DEFAULT_FRAG_SIZE))
+            .collect();
+
+        let mut frag_mgr = Frag::<MacAddress, 1, 3>::new(FragConfig::default());
+
+        // Interleave the first fragments from three sources, filling all slots
+        for f in 0..2 {
+            for s in 0..3 {
+                let (h, d) = &frags[s][f];
+                frag_mgr
+                    .receive::<()>(s as u64, rx(sources[s]), h, d)
+                    .unwrap();
+            }
+        }
+
+        // A fourth source evicts the oldest incomplete datagram
+        let (h, d) = &frags[3][0];
+        frag_mgr.receive::<()>(10, rx(sources[3]), h, d).unwrap();
+        assert_eq!(frag_mgr.stats().rx_evicted, 1);
+
+        // Remaining datagrams complete
+        for f in 1..4 {
+            for s in 1..4 {
+                if s != 3 && f == 1 {
+                    continue;
+                }
+                let (h, d) = &frags[s][f];
+                frag_mgr.receive::<()>(20, rx(sources[s]), h, d).unwrap();
+            }
+        }
+
+        let mut received = std::vec::Vec::new();
+        while let Some((info, _h, d)) = frag_mgr.pop() {
+            assert_eq!(d, &[d[0]; 200][..]);
+            received.push((info.source, d[0]));
+        }
+        assert_eq!(
+            received,
+            &[(sources[1], 2), (sources[2], 3), (sources[3], 4)]
+        );
+
+        let stats = frag_mgr.stats();
+        assert_eq!(stats.rx_datagrams, 3);
+        assert_eq!(stats.rx_evicted, 1);
+        assert_eq!(stats.rx_fragments, 14);
+        assert_eq!(stats.rx_slots_max, 3);
+
+        // Incomplete datagrams are dropped on timeout
+        let (h, d) = &frags[0][0];
+        frag_mgr.receive::<()>(30, rx(sources[0]), h, d).unwrap();
+        let timeout = 30 + FragConfig::default().frag_rx_timeout_ms;
+        assert!(frag_mgr.poll(timeout + 1, PollOptions::default()).is_none());
+        assert_eq!(frag_mgr.stats().rx_timeouts, 1);
+
+        frag_mgr.reset_stats();
+        assert_eq!(frag_mgr.stats(), FragStats::new());
+    }
+
+    /// Test eviction selects the datagram with the oldest timeout, and is only
+    /// applied to reassembly slots
+    #[test]
+    fn frag_rx_evict_policy() {
+        let sources: std::vec::Vec<_> = (1..=3)
+            .map(|i| MacAddress::Short(PanId(1), ShortAddress(i)))
+            .collect();
+        let frags = fragments(&[0xaa; 200], DEFAULT_FRAG_SIZE);
+        let (h, d) = &frags[0];
+
+        for evict_oldest in [true, false].iter() {
+            let cfg = FragConfig {
+                evict_oldest: *evict_oldest,
+                ..Default::default()
+            };
+            let mut frag_mgr = Frag::<MacAddress, 1, 2>::new(cfg);
+
+            // Occupy the TX slot and both reassembly slots, the second slot oldest
+            frag_mgr
+                .transmit::<()>(0, sources[2], Header::default(), &[0xbb; 200])
+                .unwrap();
+            frag_mgr.receive::<()>(5, rx(sources[0]), h, d).unwrap();
+            frag_mgr.receive::<()>(1, rx(sources[1]), h, d).unwrap();
+
+            let r = frag_mgr.receive::<()>(10, rx(sources[2]), h, d);
+
+            let expected = match evict_oldest {
+                true => {
+                    assert_eq!(r, Ok(()));
+                    assert_eq!(frag_mgr.stats().rx_evicted, 1);
+                    [sources[0], sources[2]]
+                }
+                false => {
+                    assert_eq!(r, Err(SixLoError::NoRxSlots));
+                    assert_eq!(frag_mgr.stats().rx_evicted, 0);
+                    assert_eq!(frag_mgr.stats().rx_dropped, 1);
+                    [sources[0], sources[1]]
+                }
+            };
+            let active: std::vec::Vec<_> = frag_mgr.rx_buffs.iter().map(|b| b.addr).collect();
+            assert_eq!(active, &expected);
+
+            // With the TX datagram retained
+            assert_eq!(frag_mgr.tx_buffs[0].state, FragState::Tx);
+            let (a, _h, _d) = frag_mgr.poll(10, PollOptions::default()).unwrap();
+            assert_eq!(a, sources[2]);
+        }
+    }
+
+    /// Test receive RSSI is aggregated over the fragments of each datagram
+    #[test]
+    fn frag_rx_info() {
+        let addr_a = MacAddress::Short(PanId(1), ShortAddress(1));
+        let addr_b = MacAddress::Short(PanId(1), ShortAddress(2));
+
+        let mut frag_mgr: Frag = Frag::new(FragConfig::default());
+
+        let frags = fragments(&[0xbb; 200], DEFAULT_FRAG_SIZE);
+        assert_eq!(frags.len(), 4);
+
+        // Interleave fragments of datagrams from two sources
+        let rssi_a = [-40, -50, -60, -70];
+        for (i, (h, d)) in frags.iter().enumerate() {
+            let a = RxInfo {
+                rssi: rssi_a[i],
+                ..rx(addr_a)
+            };
+            frag_mgr.receive::<()>(0, a, h, d).unwrap();
+
+            // Duplicates do not contribute to the aggregate
+            if i == 1 {
+                let dup = RxInfo {
+                    rssi: -120,
+                    ..rx(addr_a)
+                };
+                frag_mgr.receive::<()>(0, dup, h, d).unwrap();
+            }
+
+            let b = RxInfo {
+                rssi: -80,
+                ..rx(addr_b)
+            };
+            frag_mgr.receive::<()>(0, b, h, d).unwrap();
+        }
+
+        let (info, _h, d) = frag_mgr.pop().unwrap();
+        assert_eq!(d, &[0xbb; 200][..]);
+        assert_eq!(
+            info,
+            DatagramInfo {
+                source: addr_a,
+                rssi_min: -70,
+                rssi_avg: -55,
+                fragments: 4,
+            }
+        );
+
+        let (info, _h, _d) = frag_mgr.pop().unwrap();
+        assert_eq!(
+            info,
+            DatagramInfo {
+                source: addr_b,
+                rssi_min: -80,
+                rssi_avg: -80,
+                fragments: 4,
+            }
+        );
+
+        // Unfragmented datagrams report the RSSI of the single frame
+        let c = RxInfo {
+            rssi: -33,
+            ..rx(addr_b)
+        };
+        frag_mgr
+            .receive::<()>(0, c, &Header::default(), &[0xcc; 10])
+            .unwrap();
+
+        let (info, _h, _d) = frag_mgr.pop().unwrap();
+        assert_eq!(
+            info,
+            DatagramInfo {
+                source: addr_b,
+                rssi_min: -33,
+                rssi_avg: -33,
+                fragments: 1,
+            }
+        );
+    }
+
+    /// Test failed fragment transmissions are retried, aborting the datagram
+    /// once retries are exhausted
+    #[test]
+    fn frag_tx_abort() {
+        let _ =
+            simplelog::SimpleLogger::init(log::LevelFilter::Debug, simplelog::Config::default());
+
+        let tx = [0xaa; 200];
+        let addr_b = MacAddress::Short(PanId(1), ShortAddress(2));
+
+        let cfg = FragConfig {
+            frag_tx_retries: 1,
+            ..Default::default()
+        };
+        let mut frag_mgr: Frag = Frag::new(cfg);
+        frag_mgr
+            .transmit::<()>(0, addr_b, Header::default(), &tx)
+            .unwrap();
+
+        // Hand the first fragment to the MAC
+        let (a, h1, _d) = frag_mgr.poll(0, PollOptions::default()).unwrap();
+        let tag = h1.frag.as_ref().unwrap().datagram_tag;
+        frag_mgr.sent(10, a, tag);
+
+        // Outcomes for unrelated handles are ignored, with the datagram held
+        // until the fragment outcome is reported
+        frag_mgr.tx_status(11, TxStatus::Failed(TxFailure::ChannelBusy));
+        assert!(frag_mgr.poll(1, PollOptions::default()).is_none());
+        assert_eq!(frag_mgr.next_deadline(1, true), Some(10_001));
+
+        // Failed fragments are retried
+        frag_mgr.tx_status(10, TxStatus::Failed(TxFailure::ChannelBusy));
+        assert_eq!(frag_mgr.next_deadline(2, true), Some(2));
+
+        let (a, h, _d) = frag_mgr.poll(2, PollOptions::default()).unwrap();
+        assert_eq!(h, h1);
+        frag_mgr.sent(12, a, tag);
+        assert_eq!(frag_mgr.stats().tx_retries, 1);
+
+        // Until retries are exhausted, aborting the datagram
+        frag_mgr.tx_status(12, TxStatus::Failed(TxFailure::ChannelBusy));
+        assert!(frag_mgr.poll(3, PollOptions::default()).is_none());
+        assert_eq!(frag_mgr.stats().tx_failed, 1);
+        assert_eq!(
+            frag_mgr.take_event(),
+            Some(FragEvent {
+                tag,
+                peer: addr_b,
+                outcome: FragOutcome::TxFailed
+            })
+        );
+    }
+
+    /// Test fragments are only advanced once the MAC confirms transmission
+    #[test]
+    fn frag_tx_confirm() {
+        let tx = [0xaa; 100];
+        let addr_b = MacAddress::Short(PanId(1), ShortAddress(2));
+
+        let mut frag_mgr: Frag = Frag::new(FragConfig::default());
+        frag_mgr
+            .transmit::<()>(0, addr_b, Header::default(), &tx)
+            .unwrap();
+
+        // Fragments not handed to the MAC are returned by the next poll
+        let (_a, h1, _d) = frag_mgr.poll(0, PollOptions::default()).unwrap();
+        let (a, h, _d) = frag_mgr.poll(1, PollOptions::default()).unwrap();
+        assert_eq!(h, h1);
+
+        let tag = h1.frag.as_ref().unwrap().datagram_tag;
+        frag_mgr.sent(0, a, tag);
+        frag_mgr.tx_status(0, TxStatus::Acked);
+
+        // The next fragment follows once the first is confirmed
+        let (a, h2, d) = frag_mgr.poll(2, PollOptions::default()).unwrap();
+        assert_ne!(h2, h1);
+        assert_eq!(d.len(), 100 - 64);
+
+        // With the datagram complete once the final fragment is confirmed
+        frag_mgr.sent(1, a, tag);
+        frag_mgr.tx_status(1, TxStatus::Sent);
+        assert!(frag_mgr.poll(3, PollOptions::default()).is_none());
+        assert_eq!(frag_mgr.stats().tx_datagrams, 1);
+        assert_eq!(frag_mgr.stats().tx_fragments, 2);
+        assert_eq!(frag_mgr.next_deadline(3, true), None);
+
+        // Reporting the datagram outcome
+        assert_eq!(
+            frag_mgr.take_event(),
+            Some(FragEvent {
+                tag,
+                peer: addr_b,
+                outcome: FragOutcome::TxComplete
+            })
+        );
+        assert_eq!(frag_mgr.take_event(), None);
+    }
+
+    /// Test fragments are handed to the MAC within the transmit window,
+    /// resending from failed fragments
+    #[test]
+    fn frag_tx_window() {
+        let tx = [0xaa; 200];
+        let addr_b = MacAddress::Short(PanId(1), ShortAddress(2));
+
+        let cfg = FragConfig {
+            tx_window: 2,
+            ..Default::default()
+        };
+        let mut frag_mgr: Frag = Frag::new(cfg);
+        frag_mgr
+            .transmit::<()>(0, addr_b, Header::default(), &tx)
+            .unwrap();
+
+        // Two fragments may be awaiting outcomes
+        let (a, h1, _d) = frag_mgr.poll(0, PollOptions::default()).unwrap();
+        let tag = h1.frag.as_ref().unwrap().datagram_tag;
+        frag_mgr.sent(0, a, tag);
+
+        let (a, h2, _d) = frag_mgr.poll(0, PollOptions::default()).unwrap();
+        assert_ne!(h2, h1);
+        frag_mgr.sent(1, a, tag);
+        assert!(frag_mgr.poll(0, PollOptions::default()).is_none());
+
+        // Failures resend from the failed fragment, ignoring later outcomes
+        frag_mgr.tx_status(0, TxStatus::Failed(TxFailure::ChannelBusy));
+        frag_mgr.tx_status(1, TxStatus::Sent);
+        assert_eq!(frag_mgr.stats().tx_fragments, 0);
+
+        let (a, h, _d) = frag_mgr.poll(1, PollOptions::default()).unwrap();
+        assert_eq!(h, h1);
+        frag_mgr.sent(2, a, tag);
+        frag_mgr.tx_status(2, TxStatus::Acked);
+
+        // With the remaining fragments pipelined until all are confirmed
+        let mut handle = 3;
+        while let Some((a, _h, _d)) = frag_mgr.poll(2, PollOptions::default()) {
+            frag_mgr.sent(handle, a, tag);
+            handle += 1;
+        }
+        assert_eq!(frag_mgr.stats().tx_datagrams, 0);
+
+        for h in 3..handle {
+            frag_mgr.tx_status(h, TxStatus::Sent);
+        }
+        while let Some((a, _h, _d)) = frag_mgr.poll(3, PollOptions::default()) {
+            frag_mgr.sent(handle, a, tag);
+            frag_mgr.tx_status(handle, TxStatus::Sent);
+            handle += 1;
+        }
+
+        assert_eq!(frag_mgr.stats().tx_datagrams, 1);
+        assert_eq!(frag_mgr.stats().tx_fragments, 4);
+        assert_eq!(frag_mgr.stats().tx_retries, 1);
+        assert_eq!(
+            frag_mgr.take_event(),
+            Some(FragEvent {
+                tag,
+                peer: addr_b,
+                outcome: FragOutcome::TxComplete
+            })
+        );
+    }
+
+    /// Test fragments of concurrent datagrams are interleaved across destinations,
+    /// with address filters still applied
+    #[test]
+    fn frag_tx_interleave() {
+        let addr_b = MacAddress::Short(PanId(1), ShortAddress(2));
+        let addr_c = MacAddress::Short(PanId(1), ShortAddress(3));
+
+        let mut frag_mgr: Frag = Frag::new(FragConfig::default());
+        frag_mgr
+            .transmit::<()>(0, addr_b, Header::default(), &[0xbb; 200])
+            .unwrap();
+        frag_mgr
+            .transmit::<()>(0, addr_c, Header::default(), &[0xcc; 128])
+            .unwrap();
+
+        let mut dests = std::vec::Vec::new();
+        while let Some((a, h, _d)) = frag_mgr.poll(0, PollOptions::default()) {
+            frag_mgr.sent(0, a, h.frag.as_ref().unwrap().datagram_tag);
+            frag_mgr.tx_status(0, TxStatus::Sent);
+            dests.push(a);
+        }
+
+        // Until the shorter datagram completes
+        assert_eq!(dests, &[addr_b, addr_c, addr_b, addr_c, addr_b, addr_b]);
+        assert_eq!(frag_mgr.stats().tx_datagrams, 2);
+
+        // Filtered polls only return fragments for the provided destination
+        frag_mgr
+            .transmit::<()>(0, addr_b, Header::default(), &[0xbb; 200])
+            .unwrap();
+        frag_mgr
+            .transmit::<()>(0, addr_c, Header::default(), &[0xcc; 128])
+            .unwrap();
+
+        let opts = PollOptions {
+            tx_addr: Some(addr_c),
+            ..Default::default()
+        };
+        for _ in 0..2 {
+            let (a, h, _d) = frag_mgr.poll(0, opts.clone()).unwrap();
+            assert_eq!(a, addr_c);
+            frag_mgr.sent(0, a, h.frag.as_ref().unwrap().datagram_tag);
+            frag_mgr.tx_status(0, TxStatus::Sent);
+        }
+        assert!(frag_mgr.poll(0, opts).is_none());
+    }
+
+    /// Test fragments of each datagram are paced by the configured interval
+    #[test]
+    fn frag_tx_pacing() {
+        let addr_b = MacAddress::Short(PanId(1), ShortAddress(2));
+        let addr_c = MacAddress::Short(PanId(1), ShortAddress(3));
+
+        let cfg = FragConfig {
+            frag_interval_ms: 10,
+            ..Default::default()
+        };
+        let mut frag_mgr: Frag = Frag::new(cfg);
+        frag_mgr
+            .transmit::<()>(0, addr_b, Header::default(), &[0xbb; 200])
+            .unwrap();
+
+        assert!(poll_sent(&mut frag_mgr, 0).is_some());
+
+        // The next fragment is held until the interval has elapsed
+        assert!(poll_sent(&mut frag_mgr, 5).is_none());
+        assert_eq!(frag_mgr.next_deadline(5, true), Some(10));
+
+        // Other datagrams are paced independently
+        frag_mgr
+            .transmit::<()>(5, addr_c, Header::default(), &[0xcc; 200])
+            .unwrap();
+        let (a, h, _d) = frag_mgr.poll(5, PollOptions::default()).unwrap();
+        assert_eq!(a, addr_c);
+        frag_mgr.sent(1, a, h.frag.as_ref().unwrap().datagram_tag);
+
+        assert_eq!(
+            frag_mgr
+                .poll(10, PollOptions::default())
+                .map(|(a, _h, _d)| a),
+            Some(addr_b)
+        );
+        assert_eq!(frag_mgr.next_deadline(10, true), Some(10));
+    }
+
+    /// Test datagram outcomes are reported, including those dropped on timeout
+    #[test]
+    fn frag_events() {
+        let tx = [0xaa; 200];
+        let addr_a = MacAddress::Short(PanId(1), ShortAddress(1));
+        let addr_b = MacAddress::Short(PanId(1), ShortAddress(2));
+
+        let mut sender: Frag = Frag::new(FragConfig::default());
+        let mut receiver: Frag = Frag::new(FragConfig::default());
+
+        // Reassembled datagrams are reported on completion
+        sender
+            .transmit::<()>(0, addr_b, Header::default(), &tx)
+            .unwrap();
+        while let Some((h, d)) = poll_sent(&mut sender, 0) {
+            assert_eq!(receiver.take_event(), None);
+            receiver.receive::<()>(0, rx(addr_a), &h, &d).unwrap();
+        }
+        assert_eq!(
+            receiver.take_event(),
+            Some(FragEvent {
+                tag: 0,
+                peer: addr_a,
+                outcome: FragOutcome::RxComplete
+            })
+        );
+        assert_eq!(
+            sender.take_event().map(|e| e.outcome),
+            Some(FragOutcome::TxComplete)
+        );
+
+        // Datagrams that are not completed are reported on timeout
+        sender
+            .transmit::<()>(0, addr_b, Header::default(), &tx)
+            .unwrap();
+        let (h, d) = poll_sent(&mut sender, 0).unwrap();
+        receiver.receive::<()>(0, rx(addr_a), &h, &d).unwrap();
+
+        let t = FragConfig::default().frag_tx_timeout_ms + 1;
+        assert!(sender
+            .poll(
+                t,
+                PollOptions {
+                    can_tx: false,
+                    ..Default::default()
+                }
+            )
+            .is_none());
+        assert!(receiver.poll(t, PollOptions::default()).is_none());
+
+        assert_eq!(
+            sender.take_event(),
+            Some(FragEvent {
+                tag: 1,
+                peer: addr_b,
+                outcome: FragOutcome::TxTimeout
+            })
+        );
+        assert_eq!(
+            receiver.take_event(),
+            Some(FragEvent {
+                tag: 1,
+                peer: addr_a,
+                outcome: FragOutcome::RxTimeout
+            })
+        );
+        assert_eq!(sender.stats().tx_timeouts, 1);
+        assert_eq!(receiver.stats().rx_timeouts, 1);
+
+        // Unfragmented datagrams are not reported
+        receiver
+            .receive::<()>(t, rx(addr_a), &Header::default(), &[0xbb; 16])
+            .unwrap();
+        assert_eq!(receiver.take_event(), None);
+    }
+
+    /// Test fragments of a datagram too large to reassemble are switched to the next hop
+    #[test]
+    fn frag_forward() {
+        let origin = MacAddress::Short(PanId(1), ShortAddress(1));
+        let next_hop = MacAddress::Short(PanId(1), ShortAddress(3));
+        let other = MacAddress::Short(PanId(1), ShortAddress(4));
+
+        // Three fragments of a datagram exceeding the reassembly buffer
+        let size = IPV6_MTU + 120;
+        let frag = |offset: Option<u8>| FragHeader {
+            datagram_size: size as u16,
+            datagram_tag: 5,
+            datagram_offset: offset,
+        };
+        let frags = [
+            (frag(None), 480),
+            (frag(Some(60)), 480),
+            (frag(Some(120)), 440),
+        ];
+        assert_eq!(frags.iter().map(|(_h, l)| l).sum::<usize>(), size);
+
+        let mut frag_mgr: Frag = Frag::new(FragConfig::default());
+
+        // Following fragments are dropped without an entry
+        assert_eq!(
+            frag_mgr.forward(0, &origin, &frags[1].0, 480, next_hop),
+            None
+        );
+
+        // Each fragment is forwarded as received, without buffering
+        for (h, l) in &frags {
+            assert_eq!(
+                frag_mgr.forward(1, &origin, h, *l, other),
+                Some((other, h.clone()))
+            );
+            assert!(frag_mgr.pop().is_none());
+        }
+
+        // The entry is released with the final fragment
+        assert_eq!(frag_mgr.forward(2, &origin, &frags[2].0, 440, other), None);
+
+        let stats = frag_mgr.stats();
+        assert_eq!(stats.fwd_datagrams, 1);
+        assert_eq!(stats.fwd_dropped, 2);
+        assert_eq!(stats.rx_datagrams, 0);
+
+        // The datagram could not have been reassembled
+        let h = Header {
+            frag: Some(frags[0].0.clone()),
+            ..Default::default()
+        };
+        assert_eq!(
+            frag_mgr.receive::<()>(3, rx(origin), &h, &[0xaa; 480]),
+            Err(SixLoError::Frag(FragError::Length))
+        );
+    }
+
+    /// Test tags are rewritten where in use toward the next hop, and entries expire
+    #[test]
+    fn frag_forward_tags() {
+        let origin_a = MacAddress::Short(PanId(1), ShortAddress(1));
+        let origin_b = MacAddress::Short(PanId(1), ShortAddress(2));
+        let next_hop = MacAddress::Short(PanId(1), ShortAddress(3));
+
+        let frag = |offset: Option<u8>| FragHeader {
+            datagram_size: 200,
+            datagram_tag: 5,
+            datagram_offset: offset,
+        };
+
+        let mut frag_mgr: Frag = Frag::new(FragConfig::default());
+
+        // The same tag from different originators
+        let (_a, h_a) = frag_mgr
+            .forward(0, &origin_a, &frag(None), 64, next_hop)
+            .unwrap();
+        let (_a, h_b) = frag_mgr
+            .forward(0, &origin_b, &frag(None), 64, next_hop)
+            .unwrap();
+        assert_eq!(h_a.datagram_tag, 5);
+        assert_ne!(h_b.datagram_tag, 5);
+
+        // Following fragments use the rewritten tag
+        let (_a, h) = frag_mgr
+            .forward(1, &origin_b, &frag(Some(8)), 64, next_hop)
+            .unwrap();
+        assert_eq!(h.datagram_tag, h_b.datagram_tag);
+        assert_eq!(h.datagram_offset, Some(8));
+
+        // Incomplete datagrams expire
+        let timeout = FragConfig::default().frag_rx_timeout_ms;
+        assert_eq!(frag_mgr.next_deadline(1, true), Some(timeout + 1));
+
+        frag_mgr.poll(timeout + 1, PollOptions::default());
+        assert_eq!(
+            frag_mgr.forward(timeout + 1, &origin_a, &frag(Some(8)), 64, next_hop),
+            None
+        );
+        assert_eq!(frag_mgr.next_deadline(timeout + 1, true), None);
+    }
 }
@@ -3,9 +3,14 @@
 // https://github.com/rust-iot/rust-lpwan
 // Copyright 2021 Ryan Kurte
 
-use byteorder::{ByteOrder, LittleEndian};
+use byteorder::{BigEndian, ByteOrder};
 
-use ieee802154::mac::{Address, DecodeError, ExtendedAddress, PanId, ShortAddress};
+use heapless::Vec;
+use ieee802154::mac::{DecodeError, ExtendedAddress, PanId, ShortAddress};
+
+use super::AddressOps;
+use crate::error::BufferError;
+use crate::Ts;
 
 // https://tools.ietf.org/html/rfc4944#page-3
 
@@ -13,18 +18,26 @@ use ieee802154::mac::{Address, DecodeError, ExtendedAddress, PanId, ShortAddress
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub struct Header {
     pub hc1: Option<Hc1Header>,
+    pub iphc: Option<IphcHeader>,
     pub mesh: Option<MeshHeader>,
     pub bcast: Option<BroadcastHeader>,
     pub frag: Option<FragHeader>,
+    /// UDP header, compressed via LOWPAN_NHC following an IPHC header
+    pub udp: Option<UdpNhcHeader>,
+    /// Uncompressed IPv6 dispatch, with the IPv6 packet following the headers
+    pub ipv6: bool,
 }
 
 impl Default for Header {
     fn default() -> Self {
         Self {
             hc1: None,
+            iphc: None,
             mesh: None,
             bcast: None,
             frag: None,
+            udp: None,
+            ipv6: false,
         }
     }
 }
@@ -50,33 +63,99 @@ impl Header {
             (true, Some(h)) => self.hc1 = Some(h.clone()),
             _ => (),
         }
+
+        match (self.iphc.is_none(), &h.iphc) {
+            (true, Some(h)) => self.iphc = Some(h.clone()),
+            _ => (),
+        }
+
+        match (self.udp.is_none(), &h.udp) {
+            (true, Some(h)) => self.udp = Some(h.clone()),
+            _ => (),
+        }
+
+        self.ipv6 |= h.ipv6;
     }
 
-    pub fn decode(buff: &[u8]) -> Result<(Self, usize), DecodeError> {
+    /// Decode 6LoWPAN headers, using the link-layer source and destination
+    /// addresses to restore compressed IPv6 addresses
+    pub fn decode<A>(buff: &[u8], src: &A, dst: &A) -> Result<(Self, usize), DecodeError>
+    where
+        A: Clone + Into<Eui64>,
+    {
+        Self::decode_ctx(buff, src, dst, &ContextTable::new())
+    }
+
+    /// Decode 6LoWPAN headers, using the link-layer source and destination
+    /// addresses and compression contexts to restore compressed IPv6 addresses
+    pub fn decode_ctx<A>(
+        buff: &[u8],
+        src: &A,
+        dst: &A,
+        ctx: &ContextTable,
+    ) -> Result<(Self, usize), DecodeError>
+    where
+        A: Clone + Into<Eui64>,
+    {
         let mut offset = 0;
+        let mut mesh = None;
+        let mut bcast = None;
+        let mut frag = None;
 
         // Skip non-lowpan packets
-        if buff[0] & HEADER_TYPE_MASK == HeaderType::Nalp as u8 {
-            return Ok((Header::default(), 0));
+        match buff.first() {
+            Some(d) if d & HEADER_TYPE_MASK != HeaderType::Nalp as u8 => (),
+            _ => return Ok((Header::default(), 0)),
         }
 
         // Parse out mesh headers
-        let mesh = if buff[offset] & HEADER_TYPE_MASK == HeaderType::Mesh as u8 {
+        if buff[offset] & HEADER_TYPE_MASK == HeaderType::Mesh as u8 {
             let (m, n) = MeshHeader::decode(&buff[offset..])?;
             offset += n;
-            Some(m)
-        } else {
-            None
-        };
+            mesh = Some(m);
+        }
 
-        // TODO: deocde BC0 broadcast header
-        let bcast = None;
+        // Parse BC0 broadcast header, following any mesh header
+        if offset < buff.len() && buff[offset] == DispatchBits::Bc0 as u8 {
+            let (b, n) = BroadcastHeader::decode(&buff[offset..])?;
+            offset += n;
+            bcast = Some(b);
+        }
 
         // Parse fragmentation header
-        let frag = if buff[offset] & HEADER_TYPE_MASK == HeaderType::Frag as u8 {
+        if offset < buff.len() && buff[offset] & HEADER_TYPE_MASK == HeaderType::Frag as u8 {
             let (m, n) = FragHeader::decode(&buff[offset..])?;
             offset += n;
-            Some(m)
+            frag = Some(m);
+        }
+
+        // Parse IPHC header, present in the first (or no) fragment only
+        let first = frag
+            .as_ref()
+            .map(|f: &FragHeader| f.datagram_offset.is_none())
+            .unwrap_or(true);
+
+        let mut udp = None;
+        let iphc = if first && offset < buff.len() && IphcHeader::is_iphc(buff[offset]) {
+            // Mesh addresses replace link-layer addresses where present
+            let (src, dst) = match &mesh {
+                Some(m) => (m.origin_addr, m.final_addr),
+                None => (src.clone().into(), dst.clone().into()),
+            };
+
+            let nhc = buff[offset] & IphcFlags0::NEXT_HDR_COMPRESS.bits() != 0;
+
+            let (h, n) = IphcHeader::decode_ctx(&buff[offset..], &src, &dst, ctx)?;
+            offset += n;
+
+            // Parse the compressed next header
+            if nhc {
+                let (u, n) = UdpNhcHeader::decode(&buff[offset..])?;
+                offset += n;
+                udp = Some(u);
+            }
+
+            Some(h)
         } else {
             None
         };
@@ -94,60 +173,163 @@ impl Header {
 
         let hc1 = None;
 
-        // TODO: parse out IPv6 uncompressed header
+        // Strip uncompressed IPv6 dispatches, so fragments are sized and offset
+        // by the IPv6 packet (RFC4944 Section 5.3), see `Header::restore_ipv6`
+        let ipv6 = iphc.is_none()
+            && first
+            && offset < buff.len()
+            && buff[offset] == DispatchBits::Ipv6 as u8;
+        if ipv6 {
+            offset += 1;
+        }
 
         Ok((
             Self {
                 hc1,
+                iphc,
                 mesh,
                 bcast,
                 frag,
+                udp,
+                ipv6,
             },
             offset,
         ))
     }
 
-    pub fn encode(&self, buff: &mut [u8]) -> usize {
+    /// Encode 6LoWPAN headers, using the link-layer source and destination
+    /// addresses for IPv6 address compression.
+    /// UDP headers are only encoded following an IPHC header
+    pub fn encode<A>(&self, src: &A, dst: &A, buff: &mut [u8]) -> Result<usize, BufferError>
+    where
+        A: Clone + Into<Eui64>,
+    {
+        self.encode_ctx(src, dst, &ContextTable::new(), buff)
+    }
+
+    /// Encode 6LoWPAN headers, using the link-layer source and destination
+    /// addresses and compression contexts for IPv6 address compression
+    pub fn encode_ctx<A>(
+        &self,
+        src: &A,
+        dst: &A,
+        ctx: &ContextTable,
+        buff: &mut [u8],
+    ) -> Result<usize, BufferError>
+    where
+        A: Clone + Into<Eui64>,
+    {
         let mut offset = 0;
 
         if let Some(mesh) = &self.mesh {
-            offset += mesh.encode(&mut buff[offset..]);
+            offset += mesh.encode(&mut buff[offset..])?;
         }
 
-        if let Some(_bcast) = &self.bcast {
-            // TODO: encode BC0 broadcast header
+        if let Some(bcast) = &self.bcast {
+            offset += bcast.encode(&mut buff[offset..])?;
         }
 
         if let Some(frag) = &self.frag {
-            offset += frag.encode(&mut buff[offset..]);
+            offset += frag.encode(&mut buff[offset..])?;
         }
 
         if let Some(hc1) = &self.hc1 {
-            offset += hc1.encode(&mut buff[offset..]);
+            offset += hc1.encode(&mut buff[offset..])?;
         }
 
-        offset
+        if let Some(iphc) = &self.iphc {
+            // Mesh addresses replace link-layer addresses where present
+            let (src, dst) = match &self.mesh {
+                Some(m) => (m.origin_addr, m.final_addr),
+                None => (src.clone().into(), dst.clone().into()),
+            };
+
+            // UDP headers are compressed via LOWPAN_NHC, eliding the IPHC next header
+            let nhc = self.udp.is_some();
+            offset += iphc.encode_inner(&src, &dst, ctx, nhc, &mut buff[offset..])?;
+
+            if let Some(udp) = &self.udp {
+                offset += udp.encode(&mut buff[offset..])?;
+            }
+        } else if self.ipv6 {
+            put(buff, &mut offset, 1)?[0] = DispatchBits::Ipv6 as u8;
+        }
+
+        Ok(offset)
+    }
+
+    /// Length of the IPv6 (and any UDP) headers compressed into this header, which
+    /// count toward fragment sizes and offsets per
+    /// [RFC6282 Section 2](https://tools.ietf.org/html/rfc6282#section-2)
+    pub fn uncompressed_len(&self) -> usize {
+        match (&self.iphc, &self.udp) {
+            (Some(_), Some(_)) => IPV6_HEADER_LEN + UDP_HEADER_LEN,
+            (Some(_), None) => IPV6_HEADER_LEN,
+            _ => 0,
+        }
+    }
+
+    /// Restore the uncompressed IPv6 packet for a received datagram, from the IPHC
+    /// (and any compressed UDP) header, or the payload following an uncompressed
+    /// IPv6 dispatch. Elided UDP checksums are recomputed from the payload.
+    ///
+    /// Returns the packet length, or `None` for non-IPv6 datagrams
+    pub fn restore_ipv6(&self, data: &[u8], buff: &mut [u8]) -> Result<Option<usize>, BufferError> {
+        let ip = match &self.iphc {
+            Some(ip) => ip,
+            None if self.ipv6 => {
+                if data.len() > buff.len() {
+                    return Err(BufferError::TooSmall);
+                }
+                buff[..data.len()].copy_from_slice(data);
+                return Ok(Some(data.len()));
+            }
+            None => return Ok(None),
+        };
+
+        let udp_len = self.udp.as_ref().map(|_| UDP_HEADER_LEN).unwrap_or(0);
+        if self.uncompressed_len() + data.len() > buff.len() {
+            return Err(BufferError::TooSmall);
+        }
+
+        let mut n = ip.encode_ipv6(udp_len + data.len(), buff);
+        if let Some(udp) = &self.udp {
+            let checksum = match udp.checksum {
+                Some(c) => c,
+                None => udp.compute_checksum(&ip.src, &ip.dst, data),
+            };
+
+            let udp = UdpNhcHeader {
+                checksum: Some(checksum),
+                ..udp.clone()
+            };
+            n += udp.encode_udp(data.len(), &mut buff[n..]);
+        }
+        buff[n..n + data.len()].copy_from_slice(data);
+
+        Ok(Some(n + data.len()))
     }
 }
 
+/// Header types, from the first two (most significant) bits of the dispatch byte
+/// per [RFC4449 Section 5.1](https://tools.ietf.org/html/rfc4944#section-5.1)
 #[derive(Copy, Clone, PartialEq, Debug)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub enum HeaderType {
     /// Not a LoWPAN Frame (discard packet)
     Nalp = 0b0000_0000,
     /// LoWPAN Headers
-    Lowpan = 0b0000_0001,
+    Lowpan = 0b0100_0000,
     /// Mesh Headers
-    Mesh = 0b0000_0010,
+    Mesh = 0b1000_0000,
     /// Fragmentation headers
-    Frag = 0b0000_0011,
+    Frag = 0b1100_0000,
 }
 
-pub const HEADER_TYPE_MASK: u8 = 0b0000_0011;
-pub const HEADER_DISPATCH_MASK: u8 = 0b1111_1100;
+pub const HEADER_TYPE_MASK: u8 = 0b1100_0000;
+pub const HEADER_DISPATCH_MASK: u8 = 0b0011_1111;
 
 /// Dispatch types per [RFC4449 Section 5.1](https://tools.ietf.org/html/rfc4944#section-5.1)
-// TODO: shit are these all backwards?!
 #[derive(Copy, Clone, PartialEq, Debug)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub enum DispatchBits {
@@ -169,18 +351,32 @@ pub enum DispatchBits {
     FragN = 0b1110_0000,
 }
 
-/// IPHC Header
-/// https://tools.ietf.org/html/draft-ietf-6lowpan-hc-15
+/// IPHC compressed IPv6 header per [RFC6282](https://tools.ietf.org/html/rfc6282).
+///
+/// This holds the (uncompressed) IPv6 header fields, with compression applied on encode
+/// and elided fields restored on decode using the link-layer (or mesh) addresses.
+/// Unicast addresses other than link-local addresses are compressed statefully where
+/// a matching [`Context`] is provided, stateful multicast compression is not supported.
 #[derive(Clone, PartialEq, Debug)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub struct IphcHeader {
-    pub flags_0: IphcFlags0,
-    pub flags_1: IphcFlags1,
+    /// Traffic class (DSCP + ECN)
+    pub traffic_class: u8,
+    /// Flow label (20 bits)
+    pub flow_label: u32,
+    /// Next header
+    pub next_header: u8,
+    /// Hop limit
+    pub hop_limit: u8,
+    /// Source address
+    pub src: V6Addr,
+    /// Destination address
+    pub dst: V6Addr,
 }
 
 bitflags::bitflags! {
     /// IPHC flags byte 1
-    /// https://tools.ietf.org/html/draft-ietf-6lowpan-hc-15#section-3.1.1
+    /// https://tools.ietf.org/html/rfc6282#section-3.1.1
     #[cfg_attr(feature = "defmt", derive(defmt::Format))]
     pub struct IphcFlags0: u8 {
         /// Traffic Control / Flow Label - ECN + DSCP + 4-bit Pad + Flow Label (4 bytes)
@@ -194,49 +390,49 @@ bitflags::bitflags! {
 
         /// Next header compressed and encoded via LOWPAN_NHC.
         /// otherwise full 8 header bits are inline
-        const NEXT_HDR_COMPRESS = 0b0010_0000;
+        const NEXT_HDR_COMPRESS = 0b0000_0100;
 
         /// Hop limit compressed with limit of 1
-        const HOP_LIMIT1        = 0b0100_0000;
+        const HOP_LIMIT1        = 0b0000_0001;
         /// Hop limit compressed with limit of 64
-        const HOP_LIMIT64       = 0b1000_0000;
+        const HOP_LIMIT64       = 0b0000_0010;
         /// Hop limit compressed with limit of 255
-        const HOP_LIMIT255      = 0b1100_0000;
+        const HOP_LIMIT255      = 0b0000_0011;
 
         /// Base bits (from dispatch)
-        const BASE = 0b0000_0110;
+        const BASE = 0b0110_0000;
     }
 }
 
 bitflags::bitflags! {
     /// IPHC flags byte 2
-    /// https://tools.ietf.org/html/draft-ietf-6lowpan-hc-15#section-3.1.1
+    /// https://tools.ietf.org/html/rfc6282#section-3.1.1
     #[cfg_attr(feature = "defmt", derive(defmt::Format))]
     pub struct IphcFlags1: u8 {
         /// Additional 8-bit Context Identifier Extension field immediately follows the DAM field.
-        const CID_EXT     = 0b0000_0001;
+        const CID_EXT     = 0b1000_0000;
 
         /// Source Address Compression (SAC) uses stateful, context-based compression.
-        const SAC_STATEFULL = 0b0000_0010;
+        const SAC_STATEFULL = 0b0100_0000;
 
         /// if SAC=0, 128 bit source address, carred inline
         /// if SAC=1, UNSPECIFIED address `::`
         const SAM_128BIT_UNSPEC = 0b0000_0000;
         /// if SAC=0, 64 bit source address, first 64-bits of the address are elided.
         /// if SAC=1, 64-bit source address, derived from context and 64 inline bits
-        const SAM_64BIT = 0b0000_0100;
+        const SAM_64BIT = 0b0001_0000;
         /// if SAC=0, 16 bit source address, first 112-bits of the address are elided.
         /// if SAC=1, 16-bit source address, derived from context and 16-bits inline
-        const SAM_16BIT = 0b0000_1000;
+        const SAM_16BIT = 0b0010_0000;
         /// if SAC=0, 0 bit source address, computed from encapsulating header
         /// if SAC=0, 0 bit source address, derived from context and encapsulating header
-        const SAM_0BIT  = 0b0000_1100;
+        const SAM_0BIT  = 0b0011_0000;
 
         /// Destination address is multicast address (M)
-        const MCAST_COMPRESS = 0b0001_0000;
+        const MCAST_COMPRESS = 0b0000_1000;
 
         /// Destination Address Compression (DAC) uses stateful, context-based compression.
-        const DAC_STATEFULL = 0b0010_0000;
+        const DAC_STATEFULL = 0b0000_0100;
 
         /// if M=0 DAC=0, 128 bit destination address, carred inline
         /// if M=0 DAC=1, reserved
@@ -247,367 +443,1402 @@ bitflags::bitflags! {
         /// if DAC=1, 64-bit destination address, derived from context and 64 inline bits
         /// if M=1 DAC=0, 48 bit destination address in the form FFXX::00XX:XXXX:XXXX
         /// if M=1 DAC=1, reserved
-        const DAM_64BIT = 0b0100_0000;
+        const DAM_64BIT = 0b0000_0001;
         /// if M=0 DAC=0, 16 bit destination address, first 112-bits of the address are elided.
         /// if DAC=1, 16-bit source address, derived from context and 16-bits inline
         /// if M=1 DAC=0, 32 bit destination address in the form FFXX::00XX:XXXX
         /// if M=1 DAC=1, reserved
-        const DAM_16BIT = 0b1000_0000;
+        const DAM_16BIT = 0b0000_0010;
         /// if M=0 DAC=0, 0 bit source address, computed from encapsulating header
         /// if DAC=0, 0 bit source address, derived from context and encapsulating header
         /// if M=1 DAC=0, 8 bit destination address in the form FF02::00XX
         /// if M=1 DAC=1, reserved
-        const DAM_0BIT  = 0b1100_0000;
+        const DAM_0BIT  = 0b0000_0011;
     }
 }
 
-// TODO: complete IPHC encode/decode
-impl IphcHeader {
-    pub fn decode(buff: &[u8]) -> Result<(Self, usize), DecodeError> {
-        unimplemented!()
-    }
+/// IPHC dispatch mask (`011x_xxxx`)
+pub const IPHC_DISPATCH_MASK: u8 = 0b1110_0000;
 
-    pub fn encode(&self, buff: &mut [u8]) -> usize {
-        unimplemented!()
-    }
-}
+const IPHC_TF_MASK: u8 = 0b0001_1000;
+const IPHC_HLIM_MASK: u8 = 0b0000_0011;
+const IPHC_SAM_SHIFT: u8 = 4;
+const IPHC_DAM_MASK: u8 = 0b0000_0011;
 
-/// IPv6 HC1 Header (wireshark doesn't seem to like this?)
-/// Per https://tools.ietf.org/html/rfc4944#section-10.1
-#[derive(Clone, PartialEq, Debug)]
-#[cfg_attr(feature = "defmt", derive(defmt::Format))]
-pub struct Hc1Header {
-    pub flags: Hc1Flags,
-    pub hop_limit: u8,
-}
+/// Link-local prefix (fe80::/64)
+const LINK_LOCAL_PREFIX: [u8; 8] = [0xfe, 0x80, 0, 0, 0, 0, 0, 0];
+/// Interface identifier prefix for 16-bit addresses (0000:00ff:fe00:XXXX)
+const SHORT_IID_PREFIX: [u8; 6] = [0, 0, 0, 0xff, 0xfe, 0];
 
-bitflags::bitflags! {
-    #[cfg_attr(feature = "defmt", derive(defmt::Format))]
-    pub struct Hc1Flags: u8 {
-        const SRC_IF_COMPRESS  = 0b0000_0001;
-        const SRC_PFX_COMPRESS = 0b0000_0010;
-        const DST_IF_COMPRESS  = 0b0000_0100;
-        const DST_PFX_COMPRESS = 0b0000_1000;
-        const TC_COMPRESS      = 0b0001_0000;
-        const NEXT_HDR_UDP     = 0b0010_0000;
-        const NEXT_HDR_ICMP    = 0b0100_0000;
-        const NEXT_HDR_TCP     = 0b0110_0000;
-        const HC2_EN           = 0b1000_0000;
+/// Uncompressed IPv6 header length
+pub const IPV6_HEADER_LEN: usize = 40;
 
-        const COMPRESS_ALL = Self::SRC_IF_COMPRESS.bits | Self::SRC_PFX_COMPRESS.bits
-            | Self::DST_IF_COMPRESS.bits | Self::DST_PFX_COMPRESS.bits | Self::TC_COMPRESS.bits;
+impl IphcHeader {
+    /// Create a new IPHC header with no traffic class or flow label
+    pub fn new(src: V6Addr, dst: V6Addr, next_header: u8, hop_limit: u8) -> Self {
+        Self {
+            traffic_class: 0,
+            flow_label: 0,
+            next_header,
+            hop_limit,
+            src,
+            dst,
+        }
     }
-}
 
-impl Hc1Header {
-    pub fn decode(buff: &[u8]) -> Result<(Self, usize), DecodeError> {
-        let mut offset = 0;
+    /// Check whether a dispatch byte indicates an IPHC header
+    pub fn is_iphc(dispatch: u8) -> bool {
+        dispatch & IPHC_DISPATCH_MASK == IphcFlags0::BASE.bits()
+    }
 
-        let flags = Hc1Flags::from_bits_truncate(buff[1]);
-        let hop_limit = buff[2];
+    /// Parse header fields from an uncompressed IPv6 header,
+    /// returning the header and payload length
+    pub fn from_ipv6(buff: &[u8]) -> Result<(Self, usize), DecodeError> {
+        if buff.len() < IPV6_HEADER_LEN {
+            return Err(DecodeError::NotEnoughBytes);
+        }
 
-        Ok((Self { flags, hop_limit }, 3))
-    }
+        // Check IP version
+        if buff[0] >> 4 != 6 {
+            return Err(DecodeError::InvalidValue);
+        }
 
-    pub fn encode(&self, buff: &mut [u8]) -> usize {
-        // Set header and dispatch for mesh HC1
-        buff[0] = HeaderType::Mesh as u8;
-        buff[0] |= DispatchBits::Hc1 as u8;
+        let v = BigEndian::read_u32(&buff[0..]);
+        let payload_len = BigEndian::read_u16(&buff[4..]) as usize;
+
+        let mut src = [0u8; 16];
+        src.copy_from_slice(&buff[8..24]);
+        let mut dst = [0u8; 16];
+        dst.copy_from_slice(&buff[24..40]);
+
+        let h = Self {
+            traffic_class: (v >> 20) as u8,
+            flow_label: v & 0x000f_ffff,
+            next_header: buff[6],
+            hop_limit: buff[7],
+            src: V6Addr(src),
+            dst: V6Addr(dst),
+        };
 
-        // TODO: Set HC1 flags
-        buff[1] = 0;
-        buff[1] |= Hc1Flags::COMPRESS_ALL.bits;
+        Ok((h, payload_len))
+    }
 
-        // Hop limit always written
-        buff[2] = self.hop_limit;
+    /// Write an uncompressed IPv6 header for the provided payload length
+    pub fn encode_ipv6(&self, payload_len: usize, buff: &mut [u8]) -> usize {
+        let v = 6 << 28 | (self.traffic_class as u32) << 20 | (self.flow_label & 0x000f_ffff);
 
-        // TODO: encode other header components
+        BigEndian::write_u32(&mut buff[0..], v);
+        BigEndian::write_u16(&mut buff[4..], payload_len as u16);
+        buff[6] = self.next_header;
+        buff[7] = self.hop_limit;
+        buff[8..24].copy_from_slice(&self.src.0);
+        buff[24..40].copy_from_slice(&self.dst.0);
 
-        return 3;
+        IPV6_HEADER_LEN
     }
-}
 
-const HEADER_MESH_SHORT_V: u8 = 0b0000_0010;
-const HEADER_MESH_SHORT_F: u8 = 0b0000_0100;
+    /// Decode an IPHC header, using the provided link-layer addresses to restore elided addresses
+    pub fn decode<L>(buff: &[u8], src_ll: &L, dst_ll: &L) -> Result<(Self, usize), DecodeError>
+    where
+        L: Clone + Into<Eui64>,
+    {
+        Self::decode_ctx(buff, src_ll, dst_ll, &ContextTable::new())
+    }
 
-/// Mesh header per [RFC4449 Section 5.2](https://tools.ietf.org/html/rfc4944#section-5.2)
-#[derive(Clone, PartialEq, Debug)]
-#[cfg_attr(feature = "defmt", derive(defmt::Format))]
-pub struct MeshHeader {
-    pub hops_left: u8,
-    pub origin_addr: Address,
-    pub final_addr: Address,
-}
+    /// Decode an IPHC header, using the provided link-layer addresses and compression
+    /// contexts to restore elided addresses
+    pub fn decode_ctx<L>(
+        buff: &[u8],
+        src_ll: &L,
+        dst_ll: &L,
+        ctx: &ContextTable,
+    ) -> Result<(Self, usize), DecodeError>
+    where
+        L: Clone + Into<Eui64>,
+    {
+        if buff.len() < 2 || !Self::is_iphc(buff[0]) {
+            return Err(DecodeError::NotEnoughBytes);
+        }
 
-impl MeshHeader {
-    pub fn decode(buff: &[u8]) -> Result<(Self, usize), DecodeError> {
-        let mut offset = 0;
-        let d = buff[0];
+        let f0 = buff[0];
+        let f1 = IphcFlags1::from_bits_truncate(buff[1]);
+        let mut offset = 2;
 
-        // Check header type is correct
-        if (d & HEADER_TYPE_MASK) != HeaderType::Mesh as u8 {
-            // TODO: Error
+        let sam = (f1.bits() >> IPHC_SAM_SHIFT) & 0b11;
+        let dam = f1.bits() & IPHC_DAM_MASK;
+
+        // Stateful multicast compression is not supported, and DAC=1 DAM=00 is reserved
+        if f1.contains(IphcFlags1::DAC_STATEFULL)
+            && (f1.contains(IphcFlags1::MCAST_COMPRESS) || dam == 0b00)
+        {
+            return Err(DecodeError::InvalidValue);
         }
 
-        // Read hops left
-        let hops_left = (d >> 4) & 0x0F;
+        // Context identifiers follow the flags where present, otherwise context 0 applies
+        let (sci, dci) = match f1.contains(IphcFlags1::CID_EXT) {
+            true => {
+                let b = take(buff, &mut offset, 1)?[0];
+                (b >> 4, b & 0x0f)
+            }
+            false => (0, 0),
+        };
+        let context = |cid| ctx.lookup_by_cid(cid).ok_or(DecodeError::InvalidValue);
+
+        // Traffic class and flow label
+        let (traffic_class, flow_label) = match f0 & IPHC_TF_MASK {
+            t if t == IphcFlags0::TCFL_ELIDE.bits() => (0, 0),
+            t if t == IphcFlags0::TCFL_NO_FL.bits() => {
+                let b = take(buff, &mut offset, 1)?;
+                (tc_from_inline(b[0]), 0)
+            }
+            t if t == IphcFlags0::TCFL_NO_DSCP.bits() => {
+                let b = take(buff, &mut offset, 3)?;
+                let fl = ((b[0] & 0x0f) as u32) << 16 | (b[1] as u32) << 8 | b[2] as u32;
+                (b[0] >> 6, fl)
+            }
+            _ => {
+                let b = take(buff, &mut offset, 4)?;
+                let fl = ((b[1] & 0x0f) as u32) << 16 | (b[2] as u32) << 8 | b[3] as u32;
+                (tc_from_inline(b[0]), fl)
+            }
+        };
 
-        offset += 1;
+        // Next header, LOWPAN_NHC compressed headers follow the IPHC header
+        let nhc = f0 & IphcFlags0::NEXT_HDR_COMPRESS.bits() != 0;
+        let next_header = match nhc {
+            true => IP_PROTO_UDP,
+            false => take(buff, &mut offset, 1)?[0],
+        };
 
-        // Read addresses
-        let origin_addr = if (d & HEADER_MESH_SHORT_V) != 0 {
-            let (s, n) = ShortAddress::decode(&buff[offset..])?;
-            offset += n;
-            Address::Short(PanId(0), s)
-        } else {
-            let (l, n) = ExtendedAddress::decode(&buff[offset..])?;
-            offset += n;
-            Address::Extended(PanId(0), l)
+        // Hop limit
+        let hop_limit = match f0 & IPHC_HLIM_MASK {
+            h if h == IphcFlags0::HOP_LIMIT1.bits() => 1,
+            h if h == IphcFlags0::HOP_LIMIT64.bits() => 64,
+            h if h == IphcFlags0::HOP_LIMIT255.bits() => 255,
+            _ => take(buff, &mut offset, 1)?[0],
         };
 
-        let final_addr = if (d & HEADER_MESH_SHORT_F) != 0 {
-            let (s, n) = ShortAddress::decode(&buff[offset..])?;
-            offset += n;
-            Address::Short(PanId(0), s)
+        // Source address, SAC=1 SAM=00 indicating the unspecified address
+        let src = match (f1.contains(IphcFlags1::SAC_STATEFULL), sam) {
+            (true, 0b00) => V6Addr::UNSPECIFIED,
+            (true, _) => decompress_stateful(buff, &mut offset, sam, context(sci)?, src_ll)?,
+            (false, _) => decompress_unicast(buff, &mut offset, sam, src_ll)?,
+        };
+
+        // Destination address
+        let dst = if f1.contains(IphcFlags1::MCAST_COMPRESS) {
+            decompress_multicast(buff, &mut offset, dam)?
+        } else if f1.contains(IphcFlags1::DAC_STATEFULL) {
+            decompress_stateful(buff, &mut offset, dam, context(dci)?, dst_ll)?
         } else {
-            let (l, n) = ExtendedAddress::decode(&buff[offset..])?;
-            offset += n;
-            Address::Extended(PanId(0), l)
+            decompress_unicast(buff, &mut offset, dam, dst_ll)?
         };
 
-        let h = MeshHeader {
-            hops_left,
-            origin_addr,
-            final_addr,
+        // Only UDP next header compression is currently supported
+        let udp = buff.get(offset).map(|d| UdpNhcHeader::is_udp_nhc(*d));
+        if nhc && udp != Some(true) {
+            return Err(DecodeError::InvalidValue);
+        }
+
+        let h = Self {
+            traffic_class,
+            flow_label,
+            next_header,
+            hop_limit,
+            src,
+            dst,
         };
 
         Ok((h, offset))
     }
 
-    pub fn encode(&self, buff: &mut [u8]) -> usize {
-        let mut offset = 0;
+    /// Encode an IPHC header, eliding fields where possible using the provided
+    /// link-layer addresses
+    pub fn encode<L>(&self, src_ll: &L, dst_ll: &L, buff: &mut [u8]) -> Result<usize, BufferError>
+    where
+        L: Clone + Into<Eui64>,
+    {
+        self.encode_inner(src_ll, dst_ll, &ContextTable::new(), false, buff)
+    }
 
-        // Write header type
-        buff[0] = HeaderType::Mesh as u8;
+    /// Encode an IPHC header, eliding fields where possible using the provided
+    /// link-layer addresses and compression contexts
+    pub fn encode_ctx<L>(
+        &self,
+        src_ll: &L,
+        dst_ll: &L,
+        ctx: &ContextTable,
+        buff: &mut [u8],
+    ) -> Result<usize, BufferError>
+    where
+        L: Clone + Into<Eui64>,
+    {
+        self.encode_inner(src_ll, dst_ll, ctx, false, buff)
+    }
 
-        // Write hops left
-        buff[0] |= (self.hops_left & 0x0F) << 4;
+    /// Encode an IPHC header, eliding the next header where this is to be
+    /// compressed via LOWPAN_NHC
+    fn encode_inner<L>(
+        &self,
+        src_ll: &L,
+        dst_ll: &L,
+        ctx: &ContextTable,
+        nhc: bool,
+        buff: &mut [u8],
+    ) -> Result<usize, BufferError>
+    where
+        L: Clone + Into<Eui64>,
+    {
+        let mut f0 = IphcFlags0::BASE;
+        let mut f1 = IphcFlags1::empty();
+        let mut offset = 0;
 
-        offset += 1;
+        // Reserve flags, written once the inline fields are known
+        put(buff, &mut offset, 2)?;
+
+        // Select contexts for stateful compression, link-local addresses are
+        // compressed statelessly and the unspecified address is always elided
+        let src_ctx = Some(&self.src)
+            .filter(|a| **a != V6Addr::UNSPECIFIED && !a.is_link_local())
+            .and_then(|a| compress_stateful(a, ctx.lookup_by_prefix(a)?, src_ll));
+        let dst_ctx = Some(&self.dst)
+            .filter(|a| !a.is_multicast() && !a.is_link_local())
+            .and_then(|a| compress_stateful(a, ctx.lookup_by_prefix(a)?, dst_ll));
+
+        // Context identifiers other than the default (0) are carried inline
+        let sci = src_ctx.map(|(cid, _, _)| cid).unwrap_or(0);
+        let dci = dst_ctx.map(|(cid, _, _)| cid).unwrap_or(0);
+        if sci != 0 || dci != 0 {
+            f1 |= IphcFlags1::CID_EXT;
+            put(buff, &mut offset, 1)?[0] = sci << 4 | dci;
+        }
 
-        // Write origin address
-        offset += match self.origin_addr {
-            Address::Short(_p, s) => {
-                buff[0] |= HEADER_MESH_SHORT_V;
-                s.encode(&mut buff[offset..])
-            }
-            Address::Extended(_p, e) => e.encode(&mut buff[offset..]),
-            Address::None => unreachable!(),
-        };
+        // Traffic class and flow label, inline traffic class is reordered to ECN + DSCP
+        let ecn = self.traffic_class & 0b11;
+        let dscp = self.traffic_class >> 2;
+        let fl = self.flow_label & 0x000f_ffff;
 
-        // Write destination address
-        offset += match self.origin_addr {
-            Address::Short(_p, s) => {
-                buff[0] |= HEADER_MESH_SHORT_V;
-                s.encode(&mut buff[offset..])
+        match (self.traffic_class, fl) {
+            (0, 0) => f0 |= IphcFlags0::TCFL_ELIDE,
+            (_, 0) => {
+                f0 |= IphcFlags0::TCFL_NO_FL;
+                put(buff, &mut offset, 1)?[0] = ecn << 6 | dscp;
             }
-            Address::Extended(_p, e) => e.encode(&mut buff[offset..]),
-            Address::None => unreachable!(),
-        };
+            _ if dscp == 0 => {
+                f0 |= IphcFlags0::TCFL_NO_DSCP;
+                put(buff, &mut offset, 3)?.copy_from_slice(&[
+                    ecn << 6 | (fl >> 16) as u8,
+                    (fl >> 8) as u8,
+                    fl as u8,
+                ]);
+            }
+            _ => {
+                put(buff, &mut offset, 4)?.copy_from_slice(&[
+                    ecn << 6 | dscp,
+                    (fl >> 16) as u8,
+                    (fl >> 8) as u8,
+                    fl as u8,
+                ]);
+            }
+        }
 
-        // Return new offset
-        offset
+        // Next header
+        match nhc {
+            true => f0 |= IphcFlags0::NEXT_HDR_COMPRESS,
+            false => put(buff, &mut offset, 1)?[0] = self.next_header,
+        }
+
+        // Hop limit
+        match self.hop_limit {
+            1 => f0 |= IphcFlags0::HOP_LIMIT1,
+            64 => f0 |= IphcFlags0::HOP_LIMIT64,
+            255 => f0 |= IphcFlags0::HOP_LIMIT255,
+            h => put(buff, &mut offset, 1)?[0] = h,
+        }
+
+        // Source address
+        if self.src == V6Addr::UNSPECIFIED {
+            f1 |= IphcFlags1::SAC_STATEFULL;
+        } else {
+            let (sam, start) = match src_ctx {
+                Some((_, sam, start)) => {
+                    f1 |= IphcFlags1::SAC_STATEFULL;
+                    (sam, start)
+                }
+                None => compress_unicast(&self.src, src_ll),
+            };
+            f1 |= IphcFlags1::from_bits_truncate(sam << IPHC_SAM_SHIFT);
+
+            let inline = &self.src.0[start..];
+            put(buff, &mut offset, inline.len())?.copy_from_slice(inline);
+        }
+
+        // Destination address
+        if self.dst.is_multicast() {
+            f1 |= IphcFlags1::MCAST_COMPRESS;
+
+            let (dam, n) = compress_multicast(&self.dst, &mut buff[offset..])?;
+            f1 |= IphcFlags1::from_bits_truncate(dam);
+            offset += n;
+        } else {
+            let (dam, start) = match dst_ctx {
+                Some((_, dam, start)) => {
+                    f1 |= IphcFlags1::DAC_STATEFULL;
+                    (dam, start)
+                }
+                None => compress_unicast(&self.dst, dst_ll),
+            };
+            f1 |= IphcFlags1::from_bits_truncate(dam);
+
+            let inline = &self.dst.0[start..];
+            put(buff, &mut offset, inline.len())?.copy_from_slice(inline);
+        }
+
+        buff[0] = f0.bits();
+        buff[1] = f1.bits();
+
+        Ok(offset)
     }
 }
 
-#[derive(Clone, PartialEq, Debug)]
-#[cfg_attr(feature = "defmt", derive(defmt::Format))]
-pub struct BroadcastHeader {}
+/// Maximum number of compression contexts, as identified by 4-bit context identifiers
+pub const MAX_CONTEXTS: usize = 16;
 
-/// Fragmentation header per [rfc4944 Section 5.3](https://tools.ietf.org/html/rfc4944#section-5.3)
+/// Compression context per [RFC6282 Section 3.1.2](https://tools.ietf.org/html/rfc6282#section-3.1.2),
+/// providing the prefix for stateful (context-based) IPHC address compression
 #[derive(Clone, PartialEq, Debug)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
-pub struct FragHeader {
-    /// IP packet size prior to link-layer fragmentation
-    pub datagram_size: u16,
-    /// Tag to correlated datagram fragments
-    pub datagram_tag: u16,
-    /// Offset for fragment, only present in N>0 fragments
-    pub datagram_offset: Option<u8>,
+pub struct Context {
+    /// Context identifier (0-15)
+    pub cid: u8,
+    /// Context prefix, and prefix length in bits
+    pub prefix: V6Addr,
+    pub prefix_len: u8,
+    /// Context may be used for compression, otherwise this is used only for decompression
+    pub compression: bool,
+    /// Context expiry time, `None` where the context does not expire
+    pub expires: Option<Ts>,
 }
 
-#[derive(Copy, Clone, PartialEq, Debug)]
-#[cfg_attr(feature = "defmt", derive(defmt::Format))]
-pub enum FragHeaderKind {
-    /// First fragment (no offset)
-    Frag1 = 0b0000,
-    /// Following fragments (including offset)
-    FragN = 0b0100,
-}
+impl Context {
+    /// Check whether an address matches the context prefix
+    pub fn matches(&self, addr: &V6Addr) -> bool {
+        let mut a = addr.clone();
+        self.apply(&mut a);
+        &a == addr
+    }
 
-impl FragHeader {
-    pub fn decode(buff: &[u8]) -> Result<(Self, usize), DecodeError> {
-        let mut offset = 0;
-        let d = buff[0];
+    /// Apply the context prefix to an address, replacing the leading prefix bits
+    fn apply(&self, addr: &mut V6Addr) {
+        let bits = (self.prefix_len as usize).min(128);
+        let (n, rem) = (bits / 8, bits % 8);
 
-        // Check header type is correct
-        if (d & HEADER_TYPE_MASK) != HeaderType::Frag as u8 {
-            // TODO: error
+        addr.0[..n].copy_from_slice(&self.prefix.0[..n]);
+        if rem != 0 {
+            let mask = 0xff << (8 - rem);
+            addr.0[n] = (self.prefix.0[n] & mask) | (addr.0[n] & !mask);
         }
+    }
+}
 
-        // Read datagram size
-        let datagram_size = (buff[0] & 0b1110_0000) as u16 >> 5 | (buff[1] as u16) << 3;
-        offset += 2;
+/// Compression context table, shared by IPHC compression and neighbour discovery
+#[derive(Debug, Clone, PartialEq)]
+pub struct ContextTable {
+    contexts: Vec<Context, MAX_CONTEXTS>,
+}
 
-        // Read datagram tag
-        let datagram_tag = (buff[2] as u16) | (buff[3] as u16) >> 8;
-        offset += 2;
+impl Default for ContextTable {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 
-        // For FragN, read datagram offset
-        let datagram_offset = if (d & FragHeaderKind::FragN as u8) != 0 {
-            offset += 1;
-            Some(buff[4])
-        } else {
-            None
-        };
+impl ContextTable {
+    /// Create a new (empty) context table
+    pub fn new() -> Self {
+        Self {
+            contexts: Vec::new(),
+        }
+    }
 
-        let h = FragHeader {
-            datagram_size,
-            datagram_tag,
-            datagram_offset,
-        };
+    /// Add or replace the context for a context identifier,
+    /// returning the context if the identifier or prefix length is invalid
+    pub fn add_context(&mut self, c: Context) -> Result<(), Context> {
+        if c.cid as usize >= MAX_CONTEXTS || c.prefix_len > 128 {
+            return Err(c);
+        }
 
-        Ok((h, offset))
+        match self.contexts.iter_mut().find(|e| e.cid == c.cid) {
+            Some(e) => {
+                *e = c;
+                Ok(())
+            }
+            None => self.contexts.push(c),
+        }
     }
 
-    pub fn encode(&self, buff: &mut [u8]) -> usize {
-        let mut offset = 0;
+    /// Remove the context for a context identifier
+    pub fn remove_context(&mut self, cid: u8) -> Option<Context> {
+        let i = self.contexts.iter().position(|c| c.cid == cid)?;
+        Some(self.contexts.swap_remove(i))
+    }
 
-        // Write header type
-        buff[0] = HeaderType::Frag as u8;
-        // Write datagram size
-        buff[0] |= ((self.datagram_size & 0b0000_0111) << 5) as u8;
-        buff[1] |= (self.datagram_size >> 3) as u8;
+    /// Lookup the context with the longest prefix matching an address,
+    /// of those that may be used for compression
+    pub fn lookup_by_prefix(&self, addr: &V6Addr) -> Option<&Context> {
+        self.contexts
+            .iter()
+            .filter(|c| c.compression && c.matches(addr))
+            .max_by_key(|c| c.prefix_len)
+    }
 
-        offset += 2;
+    /// Lookup a context by context identifier
+    pub fn lookup_by_cid(&self, cid: u8) -> Option<&Context> {
+        self.contexts.iter().find(|c| c.cid == cid)
+    }
 
-        // Write datagram tag
-        LittleEndian::write_u16(&mut buff[offset..], self.datagram_tag);
-        offset += 2;
+    /// Remove a context expiring prior to the provided time, if any
+    pub fn expire(&mut self, now: Ts) -> Option<Context> {
+        let i = self
+            .contexts
+            .iter()
+            .position(|c| matches!(c.expires, Some(t) if t < now))?;
+        Some(self.contexts.swap_remove(i))
+    }
 
-        // Write datagram offset for FragN
-        if let Some(datagram_offset) = self.datagram_offset {
-            buff[0] |= FragHeaderKind::FragN as u8;
-            buff[offset] = datagram_offset;
-            offset += 1;
-        } else {
-            buff[0] |= FragHeaderKind::Frag1 as u8;
-        }
+    /// Fetch the earliest context expiry
+    pub fn next_expiry(&self) -> Option<Ts> {
+        self.contexts.iter().filter_map(|c| c.expires).min()
+    }
 
-        // Return new offset
-        offset
+    /// Iterate over contexts
+    pub fn iter(&self) -> impl Iterator<Item = &Context> {
+        self.contexts.iter()
+    }
+
+    /// Fetch the number of contexts
+    pub fn len(&self) -> usize {
+        self.contexts.len()
     }
 }
 
+/// UDP next header value
+pub const IP_PROTO_UDP: u8 = 17;
+
+/// Uncompressed UDP header length
+pub const UDP_HEADER_LEN: usize = 8;
+
+/// LOWPAN_NHC UDP header per [RFC6282 Section 4.3](https://tools.ietf.org/html/rfc6282#section-4.3).
+///
+/// Ports are compressed where these fall within the `0xF0XX` or `0xF0BX` ranges, and
+/// the length is always elided, being restored from the datagram length on receipt.
+/// Checksums are elided where `None`, and must then be recomputed on decompression,
+/// see [`UdpNhcHeader::compute_checksum`].
 #[derive(Clone, PartialEq, Debug)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
-pub struct V6Addr(pub [u8; 16]);
+pub struct UdpNhcHeader {
+    /// Source port
+    pub src_port: u16,
+    /// Destination port
+    pub dst_port: u16,
+    /// Checksum, elided where `None`
+    pub checksum: Option<u16>,
+}
 
-impl From<Eui64> for V6Addr {
-    /// Compute IPv6 Link-Local Address from EUI-64
-    /// per [RFC4449 Section 7](https://tools.ietf.org/html/rfc4944#section-7)
-    fn from(eui: Eui64) -> V6Addr {
-        let mut buff = [0u8; 16];
+/// UDP port compression modes, from the `P` bits of the NHC octet
+#[derive(Copy, Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum UdpPorts {
+    /// Source and destination ports carried inline (16/16 bits)
+    Inline = 0b00,
+    /// Source port inline, destination port `0xF0XX` (16/8 bits)
+    CompressDst = 0b01,
+    /// Source port `0xF0XX`, destination port inline (8/16 bits)
+    CompressSrc = 0b10,
+    /// Source and destination ports `0xF0BX` (4/4 bits)
+    CompressBoth = 0b11,
+}
 
-        let header = 0b1111111010;
-        LittleEndian::write_u64(&mut buff, header);
-        LittleEndian::write_u64(&mut buff[4..], eui.0);
+/// UDP NHC dispatch (`1111_0xxx`)
+const UDP_NHC_DISPATCH: u8 = 0b1111_0000;
+const UDP_NHC_DISPATCH_MASK: u8 = 0b1111_1000;
+/// Checksum elided (C) flag
+const UDP_NHC_CHECKSUM: u8 = 0b0000_0100;
+const UDP_NHC_PORTS_MASK: u8 = 0b0000_0011;
+
+/// Port prefix for 8-bit compressed ports (`0xF0XX`)
+const UDP_PORT_PREFIX_8: u16 = 0xf000;
+/// Port prefix for 4-bit compressed ports (`0xF0BX`)
+const UDP_PORT_PREFIX_4: u16 = 0xf0b0;
+
+impl UdpNhcHeader {
+    /// Create a new UDP header, with the checksum elided
+    pub fn new(src_port: u16, dst_port: u16) -> Self {
+        Self {
+            src_port,
+            dst_port,
+            checksum: None,
+        }
+    }
 
-        V6Addr(buff)
+    /// Check whether a dispatch byte indicates a UDP NHC header
+    pub fn is_udp_nhc(dispatch: u8) -> bool {
+        dispatch & UDP_NHC_DISPATCH_MASK == UDP_NHC_DISPATCH
     }
-}
 
-#[cfg(any(feature = "alloc", feature = "std"))]
-impl core::fmt::Display for V6Addr {
-    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
-        let mut compress = false;
+    /// Select the port compression mode for this header
+    pub fn ports(&self) -> UdpPorts {
+        let short = |p: u16| p & 0xff00 == UDP_PORT_PREFIX_8;
+        let nibble = |p: u16| p & 0xfff0 == UDP_PORT_PREFIX_4;
 
-        for i in 0..8 {
-            let o = u16::from_be_bytes([self.0[i], self.0[i + 1]]);
+        match (self.src_port, self.dst_port) {
+            (s, d) if nibble(s) && nibble(d) => UdpPorts::CompressBoth,
+            (_, d) if short(d) => UdpPorts::CompressDst,
+            (s, _) if short(s) => UdpPorts::CompressSrc,
+            _ => UdpPorts::Inline,
+        }
+    }
 
-            match (o, compress) {
-                (0, false) if i < 7 => {
-                    compress = true;
-                    write!(f, ":")?;
-                }
-                (0, true) => (),
-                (_, true) => {
-                    compress = false;
-                    write!(f, ":{:04x}", o)?;
-                }
-                (_, false) if i == 0 => {
-                    write!(f, "{:04x}", o)?;
-                }
-                (_, false) => {
-                    write!(f, ":{:04x}", o)?;
-                }
-            }
+    /// Decode a UDP NHC header
+    pub fn decode(buff: &[u8]) -> Result<(Self, usize), DecodeError> {
+        if buff.is_empty() || !Self::is_udp_nhc(buff[0]) {
+            return Err(DecodeError::InvalidValue);
         }
 
-        Ok(())
-    }
-}
+        let mut offset = 1;
 
-/// interface identifier
-#[derive(Clone, PartialEq, Debug)]
+        let (src_port, dst_port) = match buff[0] & UDP_NHC_PORTS_MASK {
+            0b11 => {
+                let b = take(buff, &mut offset, 1)?[0];
+                (
+                    UDP_PORT_PREFIX_4 | (b >> 4) as u16,
+                    UDP_PORT_PREFIX_4 | (b & 0x0f) as u16,
+                )
+            }
+            0b10 => {
+                let b = take(buff, &mut offset, 3)?;
+                (
+                    UDP_PORT_PREFIX_8 | b[0] as u16,
+                    BigEndian::read_u16(&b[1..]),
+                )
+            }
+            0b01 => {
+                let b = take(buff, &mut offset, 3)?;
+                (BigEndian::read_u16(b), UDP_PORT_PREFIX_8 | b[2] as u16)
+            }
+            _ => {
+                let b = take(buff, &mut offset, 4)?;
+                (BigEndian::read_u16(b), BigEndian::read_u16(&b[2..]))
+            }
+        };
+
+        let checksum = match buff[0] & UDP_NHC_CHECKSUM {
+            0 => Some(BigEndian::read_u16(take(buff, &mut offset, 2)?)),
+            _ => None,
+        };
+
+        let h = Self {
+            src_port,
+            dst_port,
+            checksum,
+        };
+
+        Ok((h, offset))
+    }
+
+    /// Encode a UDP NHC header, compressing ports where possible
+    pub fn encode(&self, buff: &mut [u8]) -> Result<usize, BufferError> {
+        let ports = self.ports();
+        let mut offset = 0;
+
+        let mut nhc = UDP_NHC_DISPATCH | ports as u8;
+        if self.checksum.is_none() {
+            nhc |= UDP_NHC_CHECKSUM;
+        }
+        put(buff, &mut offset, 1)?[0] = nhc;
+
+        match ports {
+            UdpPorts::CompressBoth => {
+                put(buff, &mut offset, 1)?[0] =
+                    (self.src_port as u8 & 0x0f) << 4 | (self.dst_port as u8 & 0x0f);
+            }
+            UdpPorts::CompressSrc => {
+                let b = put(buff, &mut offset, 3)?;
+                b[0] = self.src_port as u8;
+                BigEndian::write_u16(&mut b[1..], self.dst_port);
+            }
+            UdpPorts::CompressDst => {
+                let b = put(buff, &mut offset, 3)?;
+                BigEndian::write_u16(b, self.src_port);
+                b[2] = self.dst_port as u8;
+            }
+            UdpPorts::Inline => {
+                let b = put(buff, &mut offset, 4)?;
+                BigEndian::write_u16(b, self.src_port);
+                BigEndian::write_u16(&mut b[2..], self.dst_port);
+            }
+        }
+
+        if let Some(c) = self.checksum {
+            BigEndian::write_u16(put(buff, &mut offset, 2)?, c);
+        }
+
+        Ok(offset)
+    }
+
+    /// Parse an uncompressed UDP header, returning the header and payload length
+    pub fn from_udp(buff: &[u8]) -> Result<(Self, usize), DecodeError> {
+        if buff.len() < UDP_HEADER_LEN {
+            return Err(DecodeError::NotEnoughBytes);
+        }
+
+        let len = BigEndian::read_u16(&buff[4..]) as usize;
+        if len < UDP_HEADER_LEN || len > buff.len() {
+            return Err(DecodeError::InvalidValue);
+        }
+
+        let h = Self {
+            src_port: BigEndian::read_u16(&buff[0..]),
+            dst_port: BigEndian::read_u16(&buff[2..]),
+            checksum: Some(BigEndian::read_u16(&buff[6..])),
+        };
+
+        Ok((h, len - UDP_HEADER_LEN))
+    }
+
+    /// Write an uncompressed UDP header for the provided payload length,
+    /// elided checksums must be restored prior to this
+    pub fn encode_udp(&self, payload_len: usize, buff: &mut [u8]) -> usize {
+        BigEndian::write_u16(&mut buff[0..], self.src_port);
+        BigEndian::write_u16(&mut buff[2..], self.dst_port);
+        BigEndian::write_u16(&mut buff[4..], (UDP_HEADER_LEN + payload_len) as u16);
+        BigEndian::write_u16(&mut buff[6..], self.checksum.unwrap_or(0));
+
+        UDP_HEADER_LEN
+    }
+
+    /// Compute the UDP checksum over the IPv6 pseudo-header, UDP header, and payload
+    /// per [RFC8200 Section 8.1](https://tools.ietf.org/html/rfc8200#section-8.1)
+    pub fn compute_checksum(&self, src: &V6Addr, dst: &V6Addr, payload: &[u8]) -> u16 {
+        let len = UDP_HEADER_LEN + payload.len();
+
+        let mut pseudo = [0u8; 40];
+        pseudo[..16].copy_from_slice(&src.0);
+        pseudo[16..32].copy_from_slice(&dst.0);
+        BigEndian::write_u32(&mut pseudo[32..], len as u32);
+        pseudo[39] = IP_PROTO_UDP;
+
+        let mut udp = [0u8; UDP_HEADER_LEN];
+        BigEndian::write_u16(&mut udp[0..], self.src_port);
+        BigEndian::write_u16(&mut udp[2..], self.dst_port);
+        BigEndian::write_u16(&mut udp[4..], len as u16);
+
+        let mut sum = ones_sum(0, &pseudo);
+        sum = ones_sum(sum, &udp);
+        sum = ones_sum(sum, payload);
+
+        while sum > 0xffff {
+            sum = (sum & 0xffff) + (sum >> 16);
+        }
+
+        // Computed zero checksums are transmitted as all ones
+        match !(sum as u16) {
+            0 => 0xffff,
+            c => c,
+        }
+    }
+}
+
+/// Accumulate the (unfolded) ones-complement sum of big-endian 16-bit words,
+/// padding odd length data with a trailing zero
+fn ones_sum(mut sum: u32, data: &[u8]) -> u32 {
+    for w in data.chunks(2) {
+        let v = match w {
+            [a, b] => (*a as u16) << 8 | *b as u16,
+            [a] => (*a as u16) << 8,
+            _ => 0,
+        };
+        sum += v as u32;
+    }
+    sum
+}
+
+/// Fetch a number of bytes from the buffer, updating the offset
+fn take<'a>(buff: &'a [u8], offset: &mut usize, n: usize) -> Result<&'a [u8], DecodeError> {
+    let b = buff
+        .get(*offset..*offset + n)
+        .ok_or(DecodeError::NotEnoughBytes)?;
+    *offset += n;
+    Ok(b)
+}
+
+/// Fetch a number of bytes from the buffer for writing, updating the offset
+fn put<'a>(buff: &'a mut [u8], offset: &mut usize, n: usize) -> Result<&'a mut [u8], BufferError> {
+    let b = buff
+        .get_mut(*offset..*offset + n)
+        .ok_or(BufferError::TooSmall)?;
+    *offset += n;
+    Ok(b)
+}
+
+/// Convert an inline ECN + DSCP byte to an IPv6 traffic class
+fn tc_from_inline(b: u8) -> u8 {
+    (b & 0b0011_1111) << 2 | b >> 6
+}
+
+/// Compute the IPv6 interface identifier for a link-layer address
+fn ll_iid<L: Clone + Into<Eui64>>(addr: &L) -> [u8; 8] {
+    let iid: Eui64 = addr.clone().into();
+    iid.0.to_be_bytes()
+}
+
+/// Select a stateless unicast address mode, returning the mode and start of inline data
+fn compress_unicast<L: Clone + Into<Eui64>>(addr: &V6Addr, ll: &L) -> (u8, usize) {
+    let a = &addr.0;
+
+    // Only link-local addresses can be elided without a context
+    if a[..8] != LINK_LOCAL_PREFIX {
+        return (0b00, 0);
+    }
+
+    match ll_iid(ll) {
+        iid if iid[..] == a[8..] => (0b11, 16),
+        _ if a[8..14] == SHORT_IID_PREFIX => (0b10, 14),
+        _ => (0b01, 8),
+    }
+}
+
+/// Restore a stateless unicast address from the provided address mode
+fn decompress_unicast<L: Clone + Into<Eui64>>(
+    buff: &[u8],
+    offset: &mut usize,
+    mode: u8,
+    ll: &L,
+) -> Result<V6Addr, DecodeError> {
+    let mut a = [0u8; 16];
+
+    if mode == 0b00 {
+        a.copy_from_slice(take(buff, offset, 16)?);
+        return Ok(V6Addr(a));
+    }
+
+    a[..8].copy_from_slice(&LINK_LOCAL_PREFIX);
+
+    match mode {
+        0b01 => a[8..].copy_from_slice(take(buff, offset, 8)?),
+        0b10 => {
+            a[8..14].copy_from_slice(&SHORT_IID_PREFIX);
+            a[14..].copy_from_slice(take(buff, offset, 2)?);
+        }
+        _ => a[8..].copy_from_slice(&ll_iid(ll)),
+    }
+
+    Ok(V6Addr(a))
+}
+
+/// Select a stateful unicast address mode using the provided context, returning the
+/// context identifier, mode, and start of inline data where the address can be restored
+fn compress_stateful<L: Clone + Into<Eui64>>(
+    addr: &V6Addr,
+    ctx: &Context,
+    ll: &L,
+) -> Option<(u8, u8, usize)> {
+    // Prefer the mode with the least inline data that restores the address
+    [(0b11, 16), (0b10, 14), (0b01, 8)]
+        .iter()
+        .find(|(mode, start)| restore_stateful(&addr.0[*start..], *mode, ctx, ll) == *addr)
+        .map(|(mode, start)| (ctx.cid, *mode, *start))
+}
+
+/// Restore a stateful unicast address from the provided (non-zero) address mode
+fn decompress_stateful<L: Clone + Into<Eui64>>(
+    buff: &[u8],
+    offset: &mut usize,
+    mode: u8,
+    ctx: &Context,
+    ll: &L,
+) -> Result<V6Addr, DecodeError> {
+    let inline: &[u8] = match mode {
+        0b01 => take(buff, offset, 8)?,
+        0b10 => take(buff, offset, 2)?,
+        _ => &[],
+    };
+
+    Ok(restore_stateful(inline, mode, ctx, ll))
+}
+
+/// Form a stateful unicast address from the context prefix and interface identifier,
+/// with context prefix bits taking precedence and remaining bits zero
+fn restore_stateful<L: Clone + Into<Eui64>>(
+    inline: &[u8],
+    mode: u8,
+    ctx: &Context,
+    ll: &L,
+) -> V6Addr {
+    let mut a = V6Addr([0u8; 16]);
+
+    match mode {
+        0b01 => a.0[8..].copy_from_slice(inline),
+        0b10 => {
+            a.0[8..14].copy_from_slice(&SHORT_IID_PREFIX);
+            a.0[14..].copy_from_slice(inline);
+        }
+        _ => a.0[8..].copy_from_slice(&ll_iid(ll)),
+    }
+    ctx.apply(&mut a);
+
+    a
+}
+
+/// Compress a multicast address, returning the address mode and inline length written
+fn compress_multicast(addr: &V6Addr, buff: &mut [u8]) -> Result<(u8, usize), BufferError> {
+    let a = &addr.0;
+    let zero = |r: core::ops::Range<usize>| a[r].iter().all(|v| *v == 0);
+    let mut offset = 0;
+
+    let dam = if a[1] == 0x02 && zero(2..15) {
+        // ff02::00XX
+        put(buff, &mut offset, 1)?[0] = a[15];
+        0b11
+    } else if zero(2..13) {
+        // ffXX::00XX:XXXX
+        let b = put(buff, &mut offset, 4)?;
+        b[0] = a[1];
+        b[1..].copy_from_slice(&a[13..]);
+        0b10
+    } else if zero(2..11) {
+        // ffXX::00XX:XXXX:XXXX
+        let b = put(buff, &mut offset, 6)?;
+        b[0] = a[1];
+        b[1..].copy_from_slice(&a[11..]);
+        0b01
+    } else {
+        put(buff, &mut offset, 16)?.copy_from_slice(a);
+        0b00
+    };
+
+    Ok((dam, offset))
+}
+
+/// Restore a multicast address from the provided address mode
+fn decompress_multicast(buff: &[u8], offset: &mut usize, mode: u8) -> Result<V6Addr, DecodeError> {
+    let mut a = [0u8; 16];
+    a[0] = 0xff;
+
+    match mode {
+        0b11 => {
+            a[1] = 0x02;
+            a[15] = take(buff, offset, 1)?[0];
+        }
+        0b10 => {
+            let b = take(buff, offset, 4)?;
+            a[1] = b[0];
+            a[13..].copy_from_slice(&b[1..]);
+        }
+        0b01 => {
+            let b = take(buff, offset, 6)?;
+            a[1] = b[0];
+            a[11..].copy_from_slice(&b[1..]);
+        }
+        _ => a.copy_from_slice(take(buff, offset, 16)?),
+    }
+
+    Ok(V6Addr(a))
+}
+
+/// IPv6 HC1 Header (wireshark doesn't seem to like this?)
+/// Per https://tools.ietf.org/html/rfc4944#section-10.1
+#[derive(Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Hc1Header {
+    pub flags: Hc1Flags,
+    pub hop_limit: u8,
+}
+
+bitflags::bitflags! {
+    #[cfg_attr(feature = "defmt", derive(defmt::Format))]
+    pub struct Hc1Flags: u8 {
+        const SRC_IF_COMPRESS  = 0b0000_0001;
+        const SRC_PFX_COMPRESS = 0b0000_0010;
+        const DST_IF_COMPRESS  = 0b0000_0100;
+        const DST_PFX_COMPRESS = 0b0000_1000;
+        const TC_COMPRESS      = 0b0001_0000;
+        const NEXT_HDR_UDP     = 0b0010_0000;
+        const NEXT_HDR_ICMP    = 0b0100_0000;
+        const NEXT_HDR_TCP     = 0b0110_0000;
+        const HC2_EN           = 0b1000_0000;
+
+        const COMPRESS_ALL = Self::SRC_IF_COMPRESS.bits | Self::SRC_PFX_COMPRESS.bits
+            | Self::DST_IF_COMPRESS.bits | Self::DST_PFX_COMPRESS.bits | Self::TC_COMPRESS.bits;
+    }
+}
+
+impl Hc1Header {
+    pub fn decode(buff: &[u8]) -> Result<(Self, usize), DecodeError> {
+        let b = buff.get(..3).ok_or(DecodeError::NotEnoughBytes)?;
+
+        // Check dispatch is correct
+        if b[0] != DispatchBits::Hc1 as u8 {
+            return Err(DecodeError::InvalidValue);
+        }
+
+        let flags = Hc1Flags::from_bits_truncate(b[1]);
+        let hop_limit = b[2];
+
+        Ok((Self { flags, hop_limit }, 3))
+    }
+
+    pub fn encode(&self, buff: &mut [u8]) -> Result<usize, BufferError> {
+        if buff.len() < 3 {
+            return Err(BufferError::TooSmall);
+        }
+
+        // Set dispatch for HC1
+        buff[0] = DispatchBits::Hc1 as u8;
+
+        // TODO: Set HC1 flags
+        buff[1] = 0;
+        buff[1] |= Hc1Flags::COMPRESS_ALL.bits;
+
+        // Hop limit always written
+        buff[2] = self.hop_limit;
+
+        // TODO: encode other header components
+
+        return Ok(3);
+    }
+}
+
+const HEADER_MESH_SHORT_V: u8 = 0b0010_0000;
+const HEADER_MESH_SHORT_F: u8 = 0b0001_0000;
+const HEADER_MESH_HOPS_MASK: u8 = 0b0000_1111;
+
+/// Mesh header per [RFC4449 Section 5.2](https://tools.ietf.org/html/rfc4944#section-5.2).
+///
+/// Originator and final addresses are held as interface identifiers, carried in network
+/// (big-endian) byte order as 16-bit short addresses where these are of the short
+/// form (see [`Eui64::short`]), otherwise as 64-bit extended addresses.
+#[derive(Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct MeshHeader {
+    pub hops_left: u8,
+    pub origin_addr: Eui64,
+    pub final_addr: Eui64,
+}
+
+impl MeshHeader {
+    pub fn decode(buff: &[u8]) -> Result<(Self, usize), DecodeError> {
+        let mut offset = 0;
+        let d = *buff.first().ok_or(DecodeError::NotEnoughBytes)?;
+
+        // Check header type is correct
+        if (d & HEADER_TYPE_MASK) != HeaderType::Mesh as u8 {
+            return Err(DecodeError::InvalidValue);
+        }
+
+        // Read hops left
+        let hops_left = d & HEADER_MESH_HOPS_MASK;
+
+        offset += 1;
+
+        // Read addresses
+        let origin_addr = Self::decode_addr(buff, &mut offset, d & HEADER_MESH_SHORT_V != 0)?;
+        let final_addr = Self::decode_addr(buff, &mut offset, d & HEADER_MESH_SHORT_F != 0)?;
+
+        let h = MeshHeader {
+            hops_left,
+            origin_addr,
+            final_addr,
+        };
+
+        Ok((h, offset))
+    }
+
+    pub fn encode(&self, buff: &mut [u8]) -> Result<usize, BufferError> {
+        let mut offset = 0;
+
+        // Write header type and hops left
+        put(buff, &mut offset, 1)?[0] =
+            HeaderType::Mesh as u8 | self.hops_left & HEADER_MESH_HOPS_MASK;
+
+        // Write origin address
+        if Self::encode_addr(&self.origin_addr, buff, &mut offset)? {
+            buff[0] |= HEADER_MESH_SHORT_V;
+        }
+
+        // Write final address
+        if Self::encode_addr(&self.final_addr, buff, &mut offset)? {
+            buff[0] |= HEADER_MESH_SHORT_F;
+        }
+
+        // Return new offset
+        Ok(offset)
+    }
+
+    fn decode_addr(buff: &[u8], offset: &mut usize, short: bool) -> Result<Eui64, DecodeError> {
+        let len = if short { 2 } else { 8 };
+        let b = buff
+            .get(*offset..*offset + len)
+            .ok_or(DecodeError::NotEnoughBytes)?;
+        *offset += len;
+
+        let a = match short {
+            true => Eui64::short(BigEndian::read_u16(b)),
+            false => Eui64::extended(BigEndian::read_u64(b)),
+        };
+
+        Ok(a)
+    }
+
+    /// Write an address, returning true for short addresses
+    fn encode_addr(addr: &Eui64, buff: &mut [u8], offset: &mut usize) -> Result<bool, BufferError> {
+        match addr.to_short() {
+            Some(s) => {
+                BigEndian::write_u16(put(buff, offset, 2)?, s);
+                Ok(true)
+            }
+            None => {
+                BigEndian::write_u64(put(buff, offset, 8)?, addr.to_extended());
+                Ok(false)
+            }
+        }
+    }
+}
+
+/// LOWPAN_BC0 broadcast header per [RFC4944 Section 11.1](https://tools.ietf.org/html/rfc4944#section-11.1),
+/// carrying a sequence number to detect duplicate broadcasts
+#[derive(Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct BroadcastHeader {
+    pub seq: u8,
+}
+
+impl BroadcastHeader {
+    pub fn decode(buff: &[u8]) -> Result<(Self, usize), DecodeError> {
+        let d = *buff.first().ok_or(DecodeError::NotEnoughBytes)?;
+
+        // Check dispatch is correct
+        if d != DispatchBits::Bc0 as u8 {
+            return Err(DecodeError::InvalidValue);
+        }
+
+        // Read sequence number
+        let seq = *buff.get(1).ok_or(DecodeError::NotEnoughBytes)?;
+
+        Ok((BroadcastHeader { seq }, 2))
+    }
+
+    pub fn encode(&self, buff: &mut [u8]) -> Result<usize, BufferError> {
+        let mut offset = 0;
+        put(buff, &mut offset, 2)?.copy_from_slice(&[DispatchBits::Bc0 as u8, self.seq]);
+        Ok(offset)
+    }
+}
+
+/// Fragmentation header per [rfc4944 Section 5.3](https://tools.ietf.org/html/rfc4944#section-5.3)
+#[derive(Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct FragHeader {
+    /// IP packet size prior to link-layer fragmentation
+    pub datagram_size: u16,
+    /// Tag to correlated datagram fragments
+    pub datagram_tag: u16,
+    /// Offset for fragment in 8-byte units, only present in N>0 fragments
+    pub datagram_offset: Option<u8>,
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum FragHeaderKind {
+    /// First fragment (no offset, `11000xxx`)
+    Frag1 = 0b1100_0000,
+    /// Following fragments (including offset, `11100xxx`)
+    FragN = 0b1110_0000,
+}
+
+/// Fragment dispatch mask, the remaining bits hold the top of the datagram size
+const FRAG_DISPATCH_MASK: u8 = 0b1111_1000;
+/// Datagram size mask (11 bits)
+const FRAG_SIZE_MASK: u16 = 0x07ff;
+
+impl FragHeader {
+    pub fn decode(buff: &[u8]) -> Result<(Self, usize), DecodeError> {
+        let d = *buff.first().ok_or(DecodeError::NotEnoughBytes)?;
+
+        // Check header type is correct
+        let n = match d & FRAG_DISPATCH_MASK {
+            v if v == FragHeaderKind::Frag1 as u8 => 4,
+            v if v == FragHeaderKind::FragN as u8 => 5,
+            _ => return Err(DecodeError::InvalidValue),
+        };
+        if buff.len() < n {
+            return Err(DecodeError::NotEnoughBytes);
+        }
+
+        // Read datagram size
+        let datagram_size = BigEndian::read_u16(&buff[0..]) & FRAG_SIZE_MASK;
+
+        // Read datagram tag
+        let datagram_tag = BigEndian::read_u16(&buff[2..]);
+
+        // For FragN, read datagram offset (in 8-byte units)
+        let datagram_offset = match n {
+            5 => Some(buff[4]),
+            _ => None,
+        };
+
+        let h = FragHeader {
+            datagram_size,
+            datagram_tag,
+            datagram_offset,
+        };
+
+        Ok((h, n))
+    }
+
+    pub fn encode(&self, buff: &mut [u8]) -> Result<usize, BufferError> {
+        let mut offset = 0;
+
+        // Write datagram size
+        BigEndian::write_u16(
+            put(buff, &mut offset, 2)?,
+            self.datagram_size & FRAG_SIZE_MASK,
+        );
+
+        // Write datagram tag
+        BigEndian::write_u16(put(buff, &mut offset, 2)?, self.datagram_tag);
+
+        // Write dispatch, and datagram offset for FragN
+        if let Some(datagram_offset) = self.datagram_offset {
+            put(buff, &mut offset, 1)?[0] = datagram_offset;
+            buff[0] |= FragHeaderKind::FragN as u8;
+        } else {
+            buff[0] |= FragHeaderKind::Frag1 as u8;
+        }
+
+        // Return new offset
+        Ok(offset)
+    }
+}
+
+/// IPv6 address, formatted per [RFC5952](https://tools.ietf.org/html/rfc5952)
+#[derive(Clone, PartialEq, Debug)]
+pub struct V6Addr(pub [u8; 16]);
+
+impl V6Addr {
+    /// Unspecified address (`::`)
+    pub const UNSPECIFIED: V6Addr = V6Addr([0u8; 16]);
+
+    /// Compute the link-local address (`fe80::/64`) for a link-layer address
+    pub fn link_local<L: Clone + Into<Eui64>>(addr: &L) -> V6Addr {
+        let mut a = [0u8; 16];
+        a[..8].copy_from_slice(&LINK_LOCAL_PREFIX);
+        a[8..].copy_from_slice(&ll_iid(addr));
+
+        V6Addr(a)
+    }
+
+    /// Fetch the interface identifier (lower 64 bits) of this address
+    pub fn iid(&self) -> Eui64 {
+        Eui64(BigEndian::read_u64(&self.0[8..]))
+    }
+
+    /// Check whether this is a multicast address (`ff00::/8`)
+    pub fn is_multicast(&self) -> bool {
+        self.0[0] == 0xff
+    }
+
+    /// Check whether this is a link-local unicast address (`fe80::/64`)
+    pub fn is_link_local(&self) -> bool {
+        self.0[..8] == LINK_LOCAL_PREFIX
+    }
+
+    /// Compute the link-layer address from the interface identifier,
+    /// the inverse of [`V6Addr::link_local`], with multicast addresses
+    /// mapped to the broadcast address. See [`AddressOps::from_iid`]
+    /// for use of the local address
+    pub fn link_layer<A: AddressOps>(&self, local: &A) -> A {
+        if self.is_multicast() {
+            return A::broadcast();
+        }
+
+        A::from_iid(self.iid(), local)
+    }
+
+    /// Fetch the 16-bit groups of this address
+    fn groups(&self) -> [u16; 8] {
+        let mut g = [0u16; 8];
+        for (i, v) in g.iter_mut().enumerate() {
+            *v = BigEndian::read_u16(&self.0[i * 2..]);
+        }
+        g
+    }
+
+    /// Locate the longest run of (two or more) zero groups for compression
+    /// as `(start, end)`, with the first run selected where these are equal
+    fn zero_run(groups: &[u16; 8]) -> Option<(usize, usize)> {
+        let mut best: Option<(usize, usize)> = None;
+        let mut i = 0;
+
+        while i < groups.len() {
+            if groups[i] != 0 {
+                i += 1;
+                continue;
+            }
+
+            let start = i;
+            while i < groups.len() && groups[i] == 0 {
+                i += 1;
+            }
+
+            let longer = best.map(|(s, e)| i - start > e - s).unwrap_or(true);
+            if i - start > 1 && longer {
+                best = Some((start, i));
+            }
+        }
+
+        best
+    }
+}
+
+impl From<Eui64> for V6Addr {
+    /// Compute the IPv6 link-local address (`fe80::/64`) for an interface identifier
+    /// per [RFC4944 Section 7](https://tools.ietf.org/html/rfc4944#section-7)
+    fn from(eui: Eui64) -> V6Addr {
+        let mut buff = [0u8; 16];
+        buff[..8].copy_from_slice(&LINK_LOCAL_PREFIX);
+        buff[8..].copy_from_slice(&eui.0.to_be_bytes());
+
+        V6Addr(buff)
+    }
+}
+
+/// Format per [RFC5952 Section 4](https://tools.ietf.org/html/rfc5952#section-4),
+/// lowercase with leading zeros suppressed and the longest run of zero groups compressed
+impl core::fmt::Display for V6Addr {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let groups = self.groups();
+        let run = Self::zero_run(&groups);
+
+        let mut i = 0;
+        while i < groups.len() {
+            match run {
+                Some((start, end)) if i == start => {
+                    write!(f, "::")?;
+                    i = end;
+                    continue;
+                }
+                _ if i > 0 && run.map(|(_, end)| end != i).unwrap_or(true) => write!(f, ":")?,
+                _ => (),
+            }
+
+            write!(f, "{:x}", groups[i])?;
+            i += 1;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(feature = "defmt")]
+impl defmt::Format for V6Addr {
+    fn format(&self, f: defmt::Formatter) {
+        let groups = self.groups();
+        let run = Self::zero_run(&groups);
+
+        let mut i = 0;
+        while i < groups.len() {
+            match run {
+                Some((start, end)) if i == start => {
+                    defmt::write!(f, "::");
+                    i = end;
+                    continue;
+                }
+                _ if i > 0 && run.map(|(_, end)| end != i).unwrap_or(true) => {
+                    defmt::write!(f, ":")
+                }
+                _ => (),
+            }
+
+            defmt::write!(f, "{=u16:x}", groups[i]);
+            i += 1;
+        }
+    }
+}
+
+/// interface identifier
+#[derive(Copy, Clone, PartialEq, Debug)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub struct Eui64(pub u64);
 
+/// Universal/local bit, complemented between extended addresses and interface identifiers
+const IID_UL_BIT: u64 = 0b10 << 56;
+
+impl Eui64 {
+    /// Interface identifier for a 16-bit short address (`0000:00ff:fe00:XXXX`),
+    /// per [RFC6282 Section 3.2.2](https://tools.ietf.org/html/rfc6282#section-3.2.2)
+    pub fn short(addr: u16) -> Self {
+        let mut iid = [0u8; 8];
+        iid[..6].copy_from_slice(&SHORT_IID_PREFIX);
+        iid[6..].copy_from_slice(&addr.to_be_bytes());
+        Eui64(u64::from_be_bytes(iid))
+    }
+
+    /// Interface identifier for a 64-bit extended address, complementing the universal/local bit
+    pub fn extended(addr: u64) -> Self {
+        Eui64(addr ^ IID_UL_BIT)
+    }
+
+    /// Fetch the short address where this is of the short form
+    pub fn to_short(&self) -> Option<u16> {
+        let iid = self.0.to_be_bytes();
+        match iid[..6] == SHORT_IID_PREFIX {
+            true => Some(BigEndian::read_u16(&iid[6..])),
+            false => None,
+        }
+    }
+
+    /// Fetch the extended address for this interface identifier
+    pub fn to_extended(&self) -> u64 {
+        self.0 ^ IID_UL_BIT
+    }
+}
+
 impl From<(PanId, ShortAddress)> for Eui64 {
     /// Create a new EUI-64 Interface Identifier from an 802.15.4 pan_id and short address
-    /// Per [RFC4449 Section 7](https://tools.ietf.org/html/rfc4944#section-6)
+    /// (`PAN:00ff:fe00:XXXX`) with the universal/local bit cleared,
+    /// per [RFC4944 Section 6](https://tools.ietf.org/html/rfc4944#section-6)
     fn from(a: (PanId, ShortAddress)) -> Self {
-        let pan_id = a.0;
-        let short_addr = a.1;
-
-        Eui64(u64::from_le_bytes([
-            0,
-            0,
-            pan_id.0 as u8,
-            (pan_id.0 >> 8) as u8,
-            0,
-            0,
-            short_addr.0 as u8,
-            (short_addr.0 >> 8) as u8,
+        let pan_id = a.0 .0.to_be_bytes();
+        let short_addr = a.1 .0.to_be_bytes();
+
+        Eui64(u64::from_be_bytes([
+            pan_id[0] & !0b10,
+            pan_id[1],
+            0x00,
+            0xFF,
+            0xFE,
+            0x00,
+            short_addr[0],
+            short_addr[1],
         ]))
     }
 }
 
 impl From<ExtendedAddress> for Eui64 {
-    /// Create a new EUI-64 Interface Identifier from an 802.15.4 Extended address
-    /// Per [RFC4449 Section 7](https://tools.ietf.org/html/rfc4944#section-6), [RFC2464 Section 4](https://tools.ietf.org/html/rfc2464)
+    /// Create a new EUI-64 Interface Identifier from an 802.15.4 Extended address,
+    /// complementing the universal/local bit, per
+    /// [RFC4944 Section 6](https://tools.ietf.org/html/rfc4944#section-6), [RFC2464 Section 4](https://tools.ietf.org/html/rfc2464)
     fn from(extended: ExtendedAddress) -> Self {
-        Eui64(
-            // TODO: dropping the top extended address bits, is this correct?
-            u64::from_le_bytes([
-                extended.0 as u8,
-                (extended.0 >> 8) as u8,
-                (extended.0 >> 16) as u8,
-                0xFF,
-                0xFE,
-                (extended.0 >> 24) as u8,
-                (extended.0 >> 32) as u8,
-                (extended.0 >> 48) as u8,
-            ]),
-        )
+        Eui64::extended(extended.0)
     }
 }
 
 impl From<[u8; 6]> for Eui64 {
     /// Create a new EUI-64 Interface Identifier from a MAC address
-    /// Per [RFC2464 Section 4](https://tools.ietf.org/html/rfc2464
+    /// Per [RFC2464 Section 4](https://tools.ietf.org/html/rfc2464)
     fn from(mac: [u8; 6]) -> Self {
-        Eui64(u64::from_le_bytes([
+        Eui64(u64::from_be_bytes([
             mac[0] ^ 0b10, // Complement universal/local bit
             mac[1],
             mac[2],
@@ -632,6 +1863,7 @@ impl From<[u8; 6]> for Eui64 {
 mod test {
     use super::*;
 
+    use ieee802154::mac::Address;
     use std::string::ToString;
 
     #[test]
@@ -645,7 +1877,7 @@ mod test {
         };
 
         // Encode and decode header
-        let n = fh.encode(&mut buff);
+        let n = fh.encode(&mut buff).unwrap();
         let (fh2, n2) = FragHeader::decode(&buff[..n]).unwrap();
 
         std::println!("Encoded: {:02x?}", &buff[..n]);
@@ -655,9 +1887,679 @@ mod test {
         assert_eq!(n, n2);
     }
 
+    #[test]
+    fn frag_header_roundtrip() {
+        let mut buff = [0u8; 16];
+
+        // All representable sizes, with tags and offsets spanning the full ranges
+        let tags = [0x0000, 0x0001, 0x00ff, 0x0100, 0x1234, 0xff00, 0xffff];
+        let offsets = [None, Some(0x00), Some(0x01), Some(0x80), Some(0xff)];
+
+        for datagram_size in 0..=FRAG_SIZE_MASK {
+            for datagram_tag in tags.iter() {
+                for datagram_offset in offsets.iter() {
+                    let fh = FragHeader {
+                        datagram_size,
+                        datagram_tag: *datagram_tag,
+                        datagram_offset: *datagram_offset,
+                    };
+
+                    let n = fh.encode(&mut buff).unwrap();
+                    assert_eq!(FragHeader::decode(&buff[..n]), Ok((fh, n)));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn frag_header_vectors() {
+        let mut buff = [0u8; 16];
+
+        // 1280 byte datagram, tag 0x1234
+        let frag1 = FragHeader {
+            datagram_size: 1280,
+            datagram_tag: 0x1234,
+            datagram_offset: None,
+        };
+        let frag1_enc = [0xc5, 0x00, 0x12, 0x34];
+
+        let n = frag1.encode(&mut buff).unwrap();
+        assert_eq!(&buff[..n], &frag1_enc);
+        assert_eq!(FragHeader::decode(&frag1_enc), Ok((frag1, 4)));
+
+        // Subsequent fragment at byte offset 64
+        let mut buff = [0u8; 16];
+        let fragn = FragHeader {
+            datagram_size: 1280,
+            datagram_tag: 0x1234,
+            datagram_offset: Some(64 / 8),
+        };
+        let fragn_enc = [0xe5, 0x00, 0x12, 0x34, 0x08];
+
+        let n = fragn.encode(&mut buff).unwrap();
+        assert_eq!(&buff[..n], &fragn_enc);
+        assert_eq!(FragHeader::decode(&fragn_enc), Ok((fragn, 5)));
+
+        // Invalid dispatch and truncated headers are rejected
+        assert!(FragHeader::decode(&[0xd5, 0x00, 0x12, 0x34]).is_err());
+        assert!(FragHeader::decode(&fragn_enc[..4]).is_err());
+    }
+
+    #[test]
+    fn mesh_header_vectors() {
+        let mut buff = [0u8; 32];
+
+        let mh = MeshHeader {
+            hops_left: 5,
+            origin_addr: Eui64::extended(0x0011_2233_4455_6677),
+            final_addr: Eui64::short(0x1234),
+        };
+        let mh_enc = [
+            0x95, 0x00, 0x11, 0x22, 0x33, 0x44, 0x55, 0x66, 0x77, 0x12, 0x34,
+        ];
+
+        let n = mh.encode(&mut buff).unwrap();
+        assert_eq!(&buff[..n], &mh_enc);
+        assert_eq!(MeshHeader::decode(&mh_enc), Ok((mh, mh_enc.len())));
+
+        let mut buff = [0u8; 32];
+        let mh = MeshHeader {
+            hops_left: 7,
+            origin_addr: Eui64::short(0x0001),
+            final_addr: Eui64::short(0x0002),
+        };
+        let mh_enc = [0xb7, 0x00, 0x01, 0x00, 0x02];
+
+        let n = mh.encode(&mut buff).unwrap();
+        assert_eq!(&buff[..n], &mh_enc);
+        assert_eq!(MeshHeader::decode(&mh_enc), Ok((mh, mh_enc.len())));
+    }
+
+    #[test]
+    fn header_mesh_bc0_frag() {
+        let src_ll = Address::Short(PanId(1), ShortAddress(0x0002));
+        let dst_ll = Address::Short(PanId(1), ShortAddress::BROADCAST);
+
+        // Mesh (origin 0x0001, final broadcast), BC0 (seq 0x2a), FRAG1 (200 bytes, tag 0x1234)
+        let enc = [
+            0xb5, 0x00, 0x01, 0xff, 0xff, 0x50, 0x2a, 0xc0, 0xc8, 0x12, 0x34,
+        ];
+
+        let (h, n) = Header::decode(&enc, &src_ll, &dst_ll).unwrap();
+        assert_eq!(n, enc.len());
+        assert_eq!(
+            h.mesh,
+            Some(MeshHeader {
+                hops_left: 5,
+                origin_addr: Eui64::short(0x0001),
+                final_addr: Eui64::short(ShortAddress::BROADCAST.0),
+            })
+        );
+        assert_eq!(h.bcast, Some(BroadcastHeader { seq: 0x2a }));
+        assert_eq!(
+            h.frag,
+            Some(FragHeader {
+                datagram_size: 200,
+                datagram_tag: 0x1234,
+                datagram_offset: None,
+            })
+        );
+
+        // Headers re-encode to the same bytes
+        let mut buff = [0u8; 32];
+        let n = h.encode(&src_ll, &dst_ll, &mut buff).unwrap();
+        assert_eq!(&buff[..n], &enc);
+
+        // Truncated BC0 headers are rejected
+        assert!(BroadcastHeader::decode(&[0x50]).is_err());
+    }
+
+    #[test]
+    fn header_frag_iphc() {
+        let src_ll = Address::Extended(PanId(1), ExtendedAddress(0x0011_2233_4455_6677));
+        let dst_ll = Address::Extended(PanId(1), ExtendedAddress(0x8899_aabb_ccdd_eeff));
+
+        // FRAG1 followed by IPHC
+        let enc = [0xc5, 0x00, 0x12, 0x34, 0x7a, 0x33, 0x11];
+
+        let (h, n) = Header::decode(&enc, &src_ll, &dst_ll).unwrap();
+        assert_eq!(n, enc.len());
+        assert_eq!(
+            h.frag,
+            Some(FragHeader {
+                datagram_size: 1280,
+                datagram_tag: 0x1234,
+                datagram_offset: None,
+            })
+        );
+        assert_eq!(h.iphc.map(|i| i.next_header), Some(17));
+    }
+
+    #[test]
+    fn header_frag_ipv6() {
+        let src_ll = Address::Short(PanId(1), ShortAddress(0x0001));
+        let dst_ll = Address::Short(PanId(1), ShortAddress(0x0002));
+
+        // FRAG1 (1280 bytes, tag 0x1234) followed by an uncompressed IPv6 dispatch
+        let enc = [0xc5, 0x00, 0x12, 0x34, 0x41];
+
+        // The dispatch is consumed with the headers
+        let (h, n) = Header::decode(&enc, &src_ll, &dst_ll).unwrap();
+        assert_eq!(n, enc.len());
+        assert!(h.ipv6);
+        assert_eq!(h.uncompressed_len(), 0);
+
+        let mut buff = [0u8; 8];
+        let n = h.encode(&src_ll, &dst_ll, &mut buff).unwrap();
+        assert_eq!(&buff[..n], &enc);
+
+        // Following fragments carry no dispatch
+        let (h, n) =
+            Header::decode(&[0xe5, 0x00, 0x12, 0x34, 0x08, 0x41], &src_ll, &dst_ll).unwrap();
+        assert_eq!(n, 5);
+        assert!(!h.ipv6);
+    }
+
+    #[test]
+    fn iphc_link_local_elided() {
+        let src_ll = Address::Extended(PanId(1), ExtendedAddress(0x0011_2233_4455_6677));
+        let dst_ll = Address::Extended(PanId(1), ExtendedAddress(0x8899_aabb_ccdd_eeff));
+
+        let h = IphcHeader::new(
+            V6Addr::link_local(&src_ll),
+            V6Addr::link_local(&dst_ll),
+            17,
+            64,
+        );
+        assert_eq!(
+            h.src.0,
+            [0xfe, 0x80, 0, 0, 0, 0, 0, 0, 0x02, 0x11, 0x22, 0x33, 0x44, 0x55, 0x66, 0x77]
+        );
+
+        // TF=11, NH inline, HLIM=64, SAM=11, DAM=11
+        let expected = [0x7a, 0x33, 0x11];
+
+        let mut buff = [0u8; 64];
+        let n = h.encode(&src_ll, &dst_ll, &mut buff).unwrap();
+        assert_eq!(&buff[..n], &expected);
+
+        let (h2, n2) = IphcHeader::decode(&expected, &src_ll, &dst_ll).unwrap();
+        assert_eq!(h2, h);
+        assert_eq!(n2, expected.len());
+    }
+
+    fn context(cid: u8, prefix: &[u8], prefix_len: u8) -> Context {
+        let mut p = [0u8; 16];
+        p[..prefix.len()].copy_from_slice(prefix);
+
+        Context {
+            cid,
+            prefix: V6Addr(p),
+            prefix_len,
+            compression: true,
+            expires: None,
+        }
+    }
+
+    #[test]
+    fn iphc_stateful() {
+        let src_ll = Address::Short(PanId(1), ShortAddress(0x0001));
+        let dst_ll = Address::Short(PanId(1), ShortAddress(0x0002));
+
+        let mut ctx = ContextTable::new();
+        ctx.add_context(context(0, &[0x20, 0x01, 0x0d, 0xb8], 64))
+            .unwrap();
+        ctx.add_context(context(2, &[0x20, 0x01, 0x0d, 0xb8, 0, 0, 0, 1], 64))
+            .unwrap();
+
+        // 2001:db8::ff:fe00:1 -> 2001:db8:0:1::1234
+        let mut src = [0u8; 16];
+        src[..4].copy_from_slice(&[0x20, 0x01, 0x0d, 0xb8]);
+        src[11..].copy_from_slice(&[0xff, 0xfe, 0, 0, 0x01]);
+        let mut dst = [0u8; 16];
+        dst[..8].copy_from_slice(&[0x20, 0x01, 0x0d, 0xb8, 0, 0, 0, 1]);
+        dst[14..].copy_from_slice(&[0x12, 0x34]);
+        let h = IphcHeader::new(V6Addr(src), V6Addr(dst), 17, 64);
+
+        // TF=11, NH inline, HLIM=64, CID=1, SAC=1, SAM=11, DAC=1, DAM=01, SCI=0, DCI=2
+        let expected = [0x7a, 0xf5, 0x02, 17, 0, 0, 0, 0, 0, 0, 0x12, 0x34];
+
+        let mut buff = [0u8; 64];
+        let n = h.encode_ctx(&src_ll, &dst_ll, &ctx, &mut buff).unwrap();
+        assert_eq!(&buff[..n], &expected);
+
+        let (h2, n2) = IphcHeader::decode_ctx(&expected, &src_ll, &dst_ll, &ctx).unwrap();
+        assert_eq!(h2, h);
+        assert_eq!(n2, expected.len());
+
+        // Unknown contexts are rejected
+        assert_eq!(
+            IphcHeader::decode(&expected, &src_ll, &dst_ll),
+            Err(DecodeError::InvalidValue)
+        );
+
+        // Addresses without a context are carried inline
+        let n = h.encode(&src_ll, &dst_ll, &mut buff).unwrap();
+        assert_eq!(&buff[..2], &[0x7a, 0x00]);
+        assert_eq!(n, 35);
+    }
+
+    #[test]
+    fn context_table() {
+        let mut ctx = ContextTable::new();
+
+        ctx.add_context(context(0, &[0x20, 0x01, 0x0d, 0xb8], 32))
+            .unwrap();
+        ctx.add_context(context(1, &[0x20, 0x01, 0x0d, 0xb8], 64))
+            .unwrap();
+
+        // Invalid context identifiers and lengths are rejected
+        assert!(ctx.add_context(context(16, &[0x20], 8)).is_err());
+        assert!(ctx.add_context(context(3, &[0x20], 129)).is_err());
+
+        // Longest matching prefix is selected
+        let mut a = [0u8; 16];
+        a[..4].copy_from_slice(&[0x20, 0x01, 0x0d, 0xb8]);
+        assert_eq!(ctx.lookup_by_prefix(&V6Addr(a)).map(|c| c.cid), Some(1));
+        a[7] = 1;
+        assert_eq!(ctx.lookup_by_prefix(&V6Addr(a)).map(|c| c.cid), Some(0));
+        a[0] = 0xfd;
+        assert_eq!(ctx.lookup_by_prefix(&V6Addr(a)), None);
+
+        // Contexts are replaced by identifier, with decompression-only
+        // contexts not used for compression
+        let mut c = context(1, &[0x20, 0x01, 0x0d, 0xb8], 64);
+        c.compression = false;
+        c.expires = Some(100);
+        ctx.add_context(c.clone()).unwrap();
+        assert_eq!(ctx.len(), 2);
+        assert_eq!(ctx.lookup_by_cid(1), Some(&c));
+        a[0] = 0x20;
+        a[7] = 0;
+        assert_eq!(ctx.lookup_by_prefix(&V6Addr(a)).map(|c| c.cid), Some(0));
+
+        // Contexts are removed on expiry
+        assert_eq!(ctx.next_expiry(), Some(100));
+        ctx.expire(100);
+        assert_eq!(ctx.len(), 2);
+        ctx.expire(101);
+        assert_eq!(ctx.lookup_by_cid(1), None);
+
+        ctx.remove_context(0);
+        assert_eq!(ctx.len(), 0);
+    }
+
+    #[test]
+    fn iphc_multicast_short() {
+        let src_ll = Address::Short(PanId(1), ShortAddress(0x0001));
+        let dst_ll = Address::Short(PanId(1), ShortAddress::BROADCAST);
+
+        // fe80::ff:fe00:1 -> ff02::1, ICMPv6 with hop limit 255
+        let mut dst = [0u8; 16];
+        dst[0] = 0xff;
+        dst[1] = 0x02;
+        dst[15] = 0x01;
+        let h = IphcHeader::new(V6Addr::link_local(&src_ll), V6Addr(dst), 58, 255);
+
+        // TF=11, NH inline, HLIM=255, SAM=11, M=1, DAM=11
+        let expected = [0x7b, 0x3b, 0x3a, 0x01];
+
+        let mut buff = [0u8; 64];
+        let n = h.encode(&src_ll, &dst_ll, &mut buff).unwrap();
+        assert_eq!(&buff[..n], &expected);
+
+        let (h2, n2) = IphcHeader::decode(&expected, &src_ll, &dst_ll).unwrap();
+        assert_eq!(h2, h);
+        assert_eq!(n2, expected.len());
+    }
+
+    #[test]
+    fn iphc_inline() {
+        let src_ll = Address::Short(PanId(1), ShortAddress(0x0001));
+        let dst_ll = Address::Short(PanId(1), ShortAddress(0x0002));
+
+        // Global source, link-local destination not matching the link-layer address
+        let mut src = [0u8; 16];
+        src[0] = 0x20;
+        src[1] = 0x01;
+        src[2] = 0x0d;
+        src[3] = 0xb8;
+        src[15] = 0x01;
+        let mut dst = [0u8; 16];
+        dst[..2].copy_from_slice(&[0xfe, 0x80]);
+        dst[11..].copy_from_slice(&[0xff, 0xfe, 0x00, 0x12, 0x34]);
+
+        let h = IphcHeader {
+            traffic_class: 0b1010_1001,
+            flow_label: 0x0_1234,
+            next_header: 6,
+            hop_limit: 10,
+            src: V6Addr(src),
+            dst: V6Addr(dst),
+        };
+
+        let mut expected = std::vec![
+            0x60,        // TF=00, NH inline, HLIM inline
+            0x02,        // SAM=00, DAM=10
+            0b01_101010, // ECN + DSCP
+            0x00,
+            0x12,
+            0x34, // Flow label
+            6,    // Next header
+            10,   // Hop limit
+        ];
+        expected.extend_from_slice(&src);
+        expected.extend_from_slice(&[0x12, 0x34]);
+
+        let mut buff = [0u8; 64];
+        let n = h.encode(&src_ll, &dst_ll, &mut buff).unwrap();
+        assert_eq!(&buff[..n], &expected[..]);
+
+        let (h2, n2) = IphcHeader::decode(&expected, &src_ll, &dst_ll).unwrap();
+        assert_eq!(h2, h);
+        assert_eq!(n2, expected.len());
+
+        // Truncated headers are rejected
+        assert!(IphcHeader::decode(&expected[..10], &src_ll, &dst_ll).is_err());
+    }
+
+    #[test]
+    fn iphc_ipv6() {
+        let src_ll = Address::Extended(PanId(1), ExtendedAddress(0x0011_2233_4455_6677));
+        let dst_ll = Address::Short(PanId(1), ShortAddress(0x0002));
+
+        let h = IphcHeader {
+            traffic_class: 0xb8,
+            flow_label: 0x0_beef,
+            ..IphcHeader::new(
+                V6Addr::link_local(&src_ll),
+                V6Addr::link_local(&dst_ll),
+                58,
+                64,
+            )
+        };
+
+        let mut buff = [0u8; IPV6_HEADER_LEN];
+        assert_eq!(h.encode_ipv6(12, &mut buff), IPV6_HEADER_LEN);
+        assert_eq!(&buff[..8], &[0x6b, 0x80, 0xbe, 0xef, 0x00, 12, 58, 64]);
+        assert_eq!(IphcHeader::from_ipv6(&buff), Ok((h.clone(), 12)));
+
+        // Link-layer addresses are recovered from interface identifiers
+        assert_eq!(h.src.link_layer(&dst_ll), src_ll);
+        assert_eq!(h.dst.link_layer(&src_ll), dst_ll);
+    }
+
+    #[test]
+    fn header_iphc() {
+        let src_ll = Address::Short(PanId(1), ShortAddress(0x0001));
+        let dst_ll = Address::Short(PanId(1), ShortAddress(0x0002));
+
+        let h = Header {
+            iphc: Some(IphcHeader::new(
+                V6Addr::link_local(&src_ll),
+                V6Addr::link_local(&dst_ll),
+                17,
+                64,
+            )),
+            ..Default::default()
+        };
+
+        let mut buff = [0u8; 64];
+        let n = h.encode(&src_ll, &dst_ll, &mut buff).unwrap();
+        let (h2, n2) = Header::decode(&buff[..n], &src_ll, &dst_ll).unwrap();
+
+        assert_eq!(h, h2);
+        assert_eq!(n, n2);
+    }
+
+    #[test]
+    fn udp_nhc_ports() {
+        let tests = [
+            (
+                "16/16 bits",
+                UdpNhcHeader {
+                    checksum: Some(0xabcd),
+                    ..UdpNhcHeader::new(0x1234, 0x5678)
+                },
+                UdpPorts::Inline,
+                &[0xf0, 0x12, 0x34, 0x56, 0x78, 0xab, 0xcd][..],
+            ),
+            (
+                "16/8 bits",
+                UdpNhcHeader::new(0x1234, 0xf012),
+                UdpPorts::CompressDst,
+                &[0xf5, 0x12, 0x34, 0x12][..],
+            ),
+            (
+                "8/16 bits",
+                UdpNhcHeader::new(0xf034, 0x5678),
+                UdpPorts::CompressSrc,
+                &[0xf6, 0x34, 0x56, 0x78][..],
+            ),
+            (
+                "4/4 bits",
+                UdpNhcHeader {
+                    checksum: Some(0x0102),
+                    ..UdpNhcHeader::new(0xf0b1, 0xf0b2)
+                },
+                UdpPorts::CompressBoth,
+                &[0xf3, 0x12, 0x01, 0x02][..],
+            ),
+        ];
+
+        for (name, h, ports, expected) in &tests {
+            assert_eq!(h.ports(), *ports, "{}", name);
+
+            let mut buff = [0u8; 16];
+            let n = h.encode(&mut buff).unwrap();
+            assert_eq!(&buff[..n], *expected, "{}", name);
+
+            let (h2, n2) = UdpNhcHeader::decode(expected).unwrap();
+            assert_eq!(&h2, h, "{}", name);
+            assert_eq!(n2, expected.len(), "{}", name);
+
+            // Truncated headers are rejected
+            assert!(
+                UdpNhcHeader::decode(&expected[..n - 1]).is_err(),
+                "{}",
+                name
+            );
+        }
+    }
+
+    #[test]
+    fn udp_checksum() {
+        let src = V6Addr::link_local(&Address::Short(PanId(1), ShortAddress(0x0001)));
+        let dst = V6Addr::link_local(&Address::Short(PanId(1), ShortAddress(0x0002)));
+        let payload = b"hello";
+
+        let h = UdpNhcHeader::new(0xf0b1, 0xf0b2);
+        let checksum = h.compute_checksum(&src, &dst, payload);
+        assert_eq!(checksum, 0xdf98);
+
+        // Uncompressed headers round-trip with restored checksums
+        let h = UdpNhcHeader {
+            checksum: Some(checksum),
+            ..h
+        };
+
+        let mut buff = [0u8; UDP_HEADER_LEN + 5];
+        assert_eq!(h.encode_udp(payload.len(), &mut buff), UDP_HEADER_LEN);
+        assert_eq!(
+            &buff[..UDP_HEADER_LEN],
+            &[0xf0, 0xb1, 0xf0, 0xb2, 0x00, 13, 0xdf, 0x98]
+        );
+
+        buff[UDP_HEADER_LEN..].copy_from_slice(payload);
+        assert_eq!(UdpNhcHeader::from_udp(&buff), Ok((h, payload.len())));
+        assert!(UdpNhcHeader::from_udp(&buff[..UDP_HEADER_LEN + 2]).is_err());
+    }
+
+    #[test]
+    fn header_iphc_udp() {
+        let src_ll = Address::Short(PanId(1), ShortAddress(0x0001));
+        let dst_ll = Address::Short(PanId(1), ShortAddress(0x0002));
+
+        let h = Header {
+            iphc: Some(IphcHeader::new(
+                V6Addr::link_local(&src_ll),
+                V6Addr::link_local(&dst_ll),
+                IP_PROTO_UDP,
+                64,
+            )),
+            udp: Some(UdpNhcHeader::new(0xf0b1, 0xf0b2)),
+            ..Default::default()
+        };
+
+        // Next header is elided from IPHC, followed by a 4/4 bit NHC UDP header
+        let mut buff = [0u8; 64];
+        let n = h.encode(&src_ll, &dst_ll, &mut buff).unwrap();
+        assert_eq!(&buff[..n], &[0x7e, 0x33, 0xf7, 0x12]);
+
+        let (h2, n2) = Header::decode(&buff[..n], &src_ll, &dst_ll).unwrap();
+        assert_eq!(h, h2);
+        assert_eq!(n, n2);
+
+        // Unsupported next header compression is rejected
+        buff[2] = 0xe0;
+        assert!(Header::decode(&buff[..n], &src_ll, &dst_ll).is_err());
+    }
+
+    #[test]
+    fn header_buffer_length() {
+        let src_ll = Address::Short(PanId(1), ShortAddress(0x0002));
+        let dst_ll = Address::Short(PanId(1), ShortAddress::BROADCAST);
+
+        let h = Header {
+            mesh: Some(MeshHeader {
+                hops_left: 5,
+                origin_addr: Eui64::extended(0x0011_2233_4455_6677),
+                final_addr: Eui64::short(ShortAddress::BROADCAST.0),
+            }),
+            bcast: Some(BroadcastHeader { seq: 0x2a }),
+            frag: Some(FragHeader {
+                datagram_size: 200,
+                datagram_tag: 0x1234,
+                datagram_offset: None,
+            }),
+            iphc: Some(IphcHeader::new(
+                V6Addr::link_local(&src_ll),
+                V6Addr::link_local(&dst_ll),
+                17,
+                64,
+            )),
+            ..Default::default()
+        };
+
+        let mut buff = [0u8; 64];
+        let n = h.encode(&src_ll, &dst_ll, &mut buff).unwrap();
+
+        // Encoding into any shorter buffer is rejected
+        for i in 0..n {
+            assert_eq!(
+                h.encode(&src_ll, &dst_ll, &mut buff[..i]),
+                Err(BufferError::TooSmall)
+            );
+        }
+    }
+
+    #[test]
+    fn header_malformed() {
+        let src_ll = Address::Short(PanId(1), ShortAddress(0x0001));
+        let dst_ll = Address::Short(PanId(1), ShortAddress(0x0002));
+
+        // Mesh, BC0, FRAG1, IPHC and UDP NHC (4/4 bit ports, inline checksum)
+        let enc = [
+            0xb5, 0x00, 0x01, 0x00, 0x02, 0x50, 0x2a, 0xc0, 0xc8, 0x12, 0x34, 0x7e, 0x33, 0xf3,
+            0x12, 0xab, 0xcd,
+        ];
+        assert_eq!(
+            Header::decode(&enc, &src_ll, &dst_ll).map(|(_, n)| n),
+            Ok(enc.len())
+        );
+
+        // Truncation at header boundaries is accepted (non-lowpan, mesh, BC0, FRAG1),
+        // with truncation within any header rejected
+        for n in 0..enc.len() {
+            let r = Header::decode(&enc[..n], &src_ll, &dst_ll);
+            match n {
+                0 | 5 | 7 | 11 => assert_eq!(r.map(|(_, n)| n), Ok(n), "length {}", n),
+                _ => assert!(r.is_err(), "length {}", n),
+            }
+        }
+
+        // Headers with the wrong dispatch are rejected
+        let invalid: &[(&str, Result<usize, DecodeError>)] = &[
+            (
+                "mesh",
+                MeshHeader::decode(&[0x41, 0x00, 0x01, 0x00, 0x02]).map(|(_, n)| n),
+            ),
+            (
+                "bc0",
+                BroadcastHeader::decode(&[0x51, 0x2a]).map(|(_, n)| n),
+            ),
+            (
+                "frag",
+                FragHeader::decode(&[0xd0, 0xc8, 0x12, 0x34]).map(|(_, n)| n),
+            ),
+            (
+                "hc1",
+                Hc1Header::decode(&[0x41, 0x00, 0x40]).map(|(_, n)| n),
+            ),
+            ("udp", UdpNhcHeader::decode(&[0xe3, 0x12]).map(|(_, n)| n)),
+        ];
+        for (name, r) in invalid {
+            assert_eq!(*r, Err(DecodeError::InvalidValue), "{}", name);
+        }
+
+        // As are empty headers
+        assert!(MeshHeader::decode(&[]).is_err());
+        assert!(BroadcastHeader::decode(&[]).is_err());
+        assert!(FragHeader::decode(&[]).is_err());
+        assert!(Hc1Header::decode(&[]).is_err());
+        assert!(IphcHeader::decode(&[], &src_ll, &dst_ll).is_err());
+        assert!(UdpNhcHeader::decode(&[]).is_err());
+    }
+
     #[test]
     fn fmt_addr_v6() {
         let addr = V6Addr::from(Eui64::from((PanId(16), ShortAddress(24))));
-        assert_eq!(addr.to_string(), "fa03:0300::0010:1000:0000");
+        assert_eq!(addr.to_string(), "fe80::10:ff:fe00:18");
+
+        let fmt = |s: &str| {
+            let a: std::net::Ipv6Addr = s.parse().unwrap();
+            V6Addr(a.octets()).to_string()
+        };
+
+        // Longest zero run is compressed, with the first of equal runs selected
+        assert_eq!(fmt("2001:db8:0:0:1:0:0:1"), "2001:db8::1:0:0:1");
+        assert_eq!(fmt("2001:0:0:1:0:0:0:1"), "2001:0:0:1::1");
+        // Single zero groups are not compressed
+        assert_eq!(fmt("2001:db8:0:1:1:1:1:1"), "2001:db8:0:1:1:1:1:1");
+        // Leading and trailing runs
+        assert_eq!(fmt("::"), "::");
+        assert_eq!(fmt("::1"), "::1");
+        assert_eq!(fmt("fe80::"), "fe80::");
+        assert_eq!(fmt("FF02::1:FF00:1234"), "ff02::1:ff00:1234");
+    }
+
+    #[test]
+    fn eui64_derivation() {
+        // RFC4944 Section 6, PAN ID and short address with the U/L bit cleared
+        let iid = Eui64::from((PanId(0x1234), ShortAddress(0xabcd)));
+        assert_eq!(iid, Eui64(0x1034_00ff_fe00_abcd));
+        assert_eq!(
+            Eui64::from((PanId(0), ShortAddress(0x0001))),
+            Eui64::short(0x0001)
+        );
+
+        // Extended addresses retain all bits with the U/L bit complemented
+        let iid = Eui64::from(ExtendedAddress(0x0123_4567_89ab_cdef));
+        assert_eq!(iid, Eui64(0x0323_4567_89ab_cdef));
+        assert_eq!(iid.to_extended(), 0x0123_4567_89ab_cdef);
+
+        // RFC2464 Section 4, 48-bit MAC with FFFE inserted
+        let iid = Eui64::from([0x34, 0x56, 0x78, 0x9a, 0xbc, 0xde]);
+        assert_eq!(iid, Eui64(0x3656_78ff_fe9a_bcde));
     }
 }
@@ -0,0 +1,271 @@
+//! 6LoWPAN MAC Adaptor
+//!
+//! Exposes the 6LoWPAN stack via the [`Mac`] trait so further layers (or test
+//! harnesses written against [`Mac`]) may be stacked above this. [`SixLoMac`] owns
+//! a [`Timer`] in place of the timestamps passed to [`SixLo`] methods, with the
+//! inherent [`SixLo`] methods remaining available for header access via
+//! [`SixLoMac::sixlo_mut`].
+//
+// https://github.com/rust-iot/rust-lpwan
+// Copyright 2021 Ryan Kurte
+
+use heapless::spsc::Queue;
+
+use crate::log::{debug, FmtError};
+use crate::timer::Timer;
+use crate::{Mac, MacEvent, MacState, RxInfo, StackStats, Ts, TxHandle, TxStatus};
+
+use super::frag::{DEFAULT_RX_SLOTS, DEFAULT_TX_SLOTS};
+use super::{AddressOps, SixLo, SixLoError};
+
+/// Number of transmit outcomes buffered for [`Mac::tx_status`]
+pub const TX_STATUS_DEPTH: usize = 8;
+
+/// 6LoWPAN stack implementing [`Mac`], using the provided timer for operation
+pub struct SixLoMac<
+    M,
+    A,
+    T,
+    const MAX_PAYLOAD: usize,
+    const TX_SLOTS: usize = DEFAULT_TX_SLOTS,
+    const RX_SLOTS: usize = DEFAULT_RX_SLOTS,
+> {
+    sixlo: SixLo<M, A, MAX_PAYLOAD, TX_SLOTS, RX_SLOTS>,
+    timer: T,
+    handle: TxHandle,
+    tx_status: Queue<(TxHandle, TxStatus), { TX_STATUS_DEPTH + 1 }>,
+}
+
+impl<M, A, T, const MAX_PAYLOAD: usize, const TX_SLOTS: usize, const RX_SLOTS: usize>
+    SixLoMac<M, A, T, MAX_PAYLOAD, TX_SLOTS, RX_SLOTS>
+where
+    M: Mac<A>,
+    A: AddressOps,
+    <M as Mac<A>>::Error: FmtError,
+    T: Timer,
+{
+    /// Create a new 6LoWPAN MAC adaptor, using the provided timer for operation
+    pub fn new(sixlo: SixLo<M, A, MAX_PAYLOAD, TX_SLOTS, RX_SLOTS>, timer: T) -> Self {
+        Self {
+            sixlo,
+            timer,
+            handle: 0,
+            tx_status: Queue::new(),
+        }
+    }
+
+    /// Fetch the underlying stack
+    pub fn sixlo(&self) -> &SixLo<M, A, MAX_PAYLOAD, TX_SLOTS, RX_SLOTS> {
+        &self.sixlo
+    }
+
+    /// Fetch the underlying stack mutably
+    pub fn sixlo_mut(&mut self) -> &mut SixLo<M, A, MAX_PAYLOAD, TX_SLOTS, RX_SLOTS> {
+        &mut self.sixlo
+    }
+
+    /// Return the underlying stack
+    pub fn into_inner(self) -> SixLo<M, A, MAX_PAYLOAD, TX_SLOTS, RX_SLOTS> {
+        self.sixlo
+    }
+}
+
+impl<M, A, T, const MAX_PAYLOAD: usize, const TX_SLOTS: usize, const RX_SLOTS: usize> Mac<A>
+    for SixLoMac<M, A, T, MAX_PAYLOAD, TX_SLOTS, RX_SLOTS>
+where
+    M: Mac<A>,
+    A: AddressOps,
+    <M as Mac<A>>::Error: FmtError,
+    T: Timer,
+{
+    type Error = SixLoError<<M as Mac<A>>::Error>;
+
+    /// Fetch the state of the underlying MAC
+    fn state(&self) -> Result<MacState<A>, Self::Error> {
+        self.sixlo.mac.state().map_err(SixLoError::Mac)
+    }
+
+    fn tick(&mut self) -> Result<(), Self::Error> {
+        self.sixlo.tick(self.timer.ticks_ms())
+    }
+
+    fn busy(&mut self) -> Result<bool, Self::Error> {
+        self.sixlo.mac.busy().map_err(SixLoError::Mac)
+    }
+
+    fn can_transmit(&self) -> Result<bool, Self::Error> {
+        self.sixlo.can_transmit()
+    }
+
+    /// Setup a datagram for transmission, fragmenting this as required.
+    /// Acknowledgements are requested by the 6LoWPAN layer for unicast frames,
+    /// and the reported outcome is the acceptance of the datagram, outcomes of
+    /// fragmented datagrams are available via [`SixLo::poll_event`]
+    fn transmit(&mut self, dest: A, data: &[u8], _ack: bool) -> Result<TxHandle, Self::Error> {
+        self.sixlo.transmit(self.timer.ticks_ms(), dest, data)?;
+
+        let handle = self.handle;
+        self.handle = self.handle.wrapping_add(1);
+
+        // Drop the oldest outcome if these are not being collected
+        if self.tx_status.is_full() {
+            let _ = self.tx_status.dequeue();
+        }
+        let _ = self.tx_status.enqueue((handle, TxStatus::Sent));
+
+        Ok(handle)
+    }
+
+    fn tx_status(&mut self) -> Result<Option<(TxHandle, TxStatus)>, Self::Error> {
+        Ok(self.tx_status.dequeue())
+    }
+
+    /// MAC events are handled by the 6LoWPAN layer and not reported
+    fn event(&mut self) -> Result<Option<MacEvent<A>>, Self::Error> {
+        Ok(None)
+    }
+
+    /// Receive a datagram, with the source being the mesh originator where present
+    /// and the RSSI averaged over received fragments
+    fn receive(&mut self, data: &mut [u8]) -> Result<Option<(usize, RxInfo<A>)>, Self::Error> {
+        let (n, info, h) = match self.sixlo.receive(self.timer.ticks_ms(), data)? {
+            Some(v) => v,
+            None => return Ok(None),
+        };
+
+        // Broadcasts (including via mesh) carry broadcast headers
+        let destination = match h.bcast {
+            Some(_) => A::broadcast(),
+            None => self.sixlo.mac_addr.clone(),
+        };
+
+        debug!("Received {} byte datagram from {:?}", n, info.source);
+
+        let info = RxInfo {
+            source: info.source,
+            destination,
+            rssi: info.rssi_avg,
+        };
+        Ok(Some((n, info)))
+    }
+
+    fn discard(&mut self) -> Result<bool, Self::Error> {
+        Ok(self.sixlo.discard())
+    }
+
+    fn next_deadline(&self) -> Option<Ts> {
+        self.sixlo.next_deadline(self.timer.ticks_ms())
+    }
+
+    fn radio_event(&mut self) {
+        self.sixlo.radio_event()
+    }
+
+    /// Fetch datagram statistics, with queue depths and timing statistics
+    /// from the underlying MAC
+    fn stats(&self) -> StackStats {
+        let s = self.sixlo.stats();
+
+        StackStats {
+            tx_ok: s.tx_datagrams,
+            tx_fail: s.tx_failed.saturating_add(s.tx_timeouts),
+            rx_ok: s.rx_datagrams,
+            rx_dropped: s.rx_dropped,
+            ..s.mac
+        }
+    }
+
+    fn reset_stats(&mut self) {
+        self.sixlo.reset_stats()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::vec::Vec;
+
+    use ieee802154::mac::{Address as MacAddress, PanId, ShortAddress};
+
+    use super::*;
+    use crate::sixlo::{mock::MockMac, nd::NdConfig, SixLoConfig};
+    use crate::timer::mock::MockTimer;
+
+    /// Exchange a datagram between layers via the [`Mac`] trait
+    fn exchange<N: Mac<MacAddress>>(
+        timer: &mut MockTimer,
+        a: &mut N,
+        b: &mut N,
+        dest: MacAddress,
+        data: &[u8],
+    ) -> Option<(Vec<u8>, RxInfo<MacAddress>)> {
+        let handle = a.transmit(dest, data, true).unwrap();
+        assert_eq!(a.tx_status().unwrap(), Some((handle, TxStatus::Sent)));
+
+        let mut buff = [0u8; 512];
+        for _ in 0..20 {
+            timer.inc();
+            a.tick().unwrap();
+            b.tick().unwrap();
+
+            if let Some((n, info)) = b.receive(&mut buff).unwrap() {
+                return Some((buff[..n].to_vec(), info));
+            }
+        }
+
+        None
+    }
+
+    #[test]
+    fn sixlo_mac() {
+        let addr_a = MacAddress::Short(PanId(1), ShortAddress(1));
+        let addr_b = MacAddress::Short(PanId(1), ShortAddress(2));
+
+        let mac_a = MockMac::new(addr_a);
+        let mac_b = mac_a.link(addr_b);
+
+        // Routers, so no solicitations are scheduled
+        let cfg = SixLoConfig {
+            nd: NdConfig {
+                router: true,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let mut timer = MockTimer::new();
+        let mut a = SixLoMac::new(
+            SixLo::<_, _, 127>::new(mac_a, addr_a, cfg.clone()),
+            timer.clone(),
+        );
+        let mut b = SixLoMac::new(SixLo::<_, _, 127>::new(mac_b, addr_b, cfg), timer.clone());
+
+        assert_eq!(a.state(), Ok(MacState::Associated(addr_a)));
+        assert_eq!(a.can_transmit(), Ok(true));
+
+        // Short and fragmented datagrams are exchanged, with the short datagram
+        // starting with a NALP dispatch as this is sent without 6LoWPAN headers
+        let short = [0x00; 16];
+        let rx = exchange(&mut timer, &mut a, &mut b, addr_b, &short);
+        let info = RxInfo {
+            source: addr_a,
+            destination: addr_b,
+            rssi: 0,
+        };
+        assert_eq!(rx, Some((short.to_vec(), info.clone())));
+
+        let long: Vec<u8> = (0..300).map(|v| v as u8).collect();
+        let rx = exchange(&mut timer, &mut a, &mut b, addr_b, &long);
+        assert_eq!(rx, Some((long.clone(), info)));
+
+        // Broadcasts are reported to the broadcast address
+        let bcast = <MacAddress as AddressOps>::broadcast();
+        let (_d, info) = exchange(&mut timer, &mut a, &mut b, bcast, &short).unwrap();
+        assert_eq!(info.destination, bcast);
+
+        let stats = a.stats();
+        assert_eq!(stats.tx_ok, 3);
+        assert_eq!(b.stats().rx_ok, 3);
+
+        // Inherent methods remain available
+        assert_eq!(a.sixlo().stats().tx_datagrams, 3);
+    }
+}
@@ -0,0 +1,296 @@
+//! 6LoWPAN Mesh-Under Forwarding
+//!
+//! Frames with a mesh header addressed to another node are forwarded toward the
+//! final address per [RFC4944 Section 11](https://tools.ietf.org/html/rfc4944#section-11),
+//! with remaining headers preserved so fragments are forwarded without reassembly.
+//! Fragments are switched per-datagram via the fragmentation layer (see
+//! [`Frag::forward`](super::frag::Frag::forward)), following the next hop of the
+//! first fragment. Next hops are resolved via a static route table then the MAC
+//! (such as coordinator routes to devices associated via routers), with destinations
+//! otherwise assumed to be direct neighbours.
+//
+// https://github.com/rust-iot/rust-lpwan
+// Copyright 2021 Ryan Kurte
+
+use heapless::Vec;
+use ieee802154::mac::Address as MacAddress;
+
+use crate::log::{debug, warn, FmtError};
+use crate::{Mac, Ts};
+
+use super::headers::{BroadcastHeader, FragHeader, Header, MeshHeader};
+use super::{requires_ack, AddressOps, SixLo, SixLoError};
+
+/// Default route table size
+pub const DEFAULT_MAX_ROUTES: usize = 8;
+
+/// Mesh configuration
+#[derive(Clone, PartialEq, Debug)]
+pub struct MeshConfig {
+    /// Hops left for originated mesh frames
+    pub hops_left: u8,
+}
+
+impl Default for MeshConfig {
+    fn default() -> Self {
+        Self { hops_left: 7 }
+    }
+}
+
+/// Mesh route, mapping a final address to the next hop
+#[derive(Debug, Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Route<A = MacAddress> {
+    pub final_addr: A,
+    pub next_hop: A,
+}
+
+/// Mesh route table
+#[derive(Debug, Clone, PartialEq)]
+pub struct RouteTable<A = MacAddress, const N: usize = DEFAULT_MAX_ROUTES> {
+    routes: Vec<Route<A>, N>,
+}
+
+impl<A: PartialEq, const N: usize> Default for RouteTable<A, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<A: PartialEq, const N: usize> RouteTable<A, N> {
+    /// Create a new (empty) route table
+    pub fn new() -> Self {
+        Self { routes: Vec::new() }
+    }
+
+    /// Add or replace the route to a final address,
+    /// returning the route if the table is full
+    pub fn add(&mut self, final_addr: A, next_hop: A) -> Result<(), Route<A>> {
+        let r = Route {
+            final_addr,
+            next_hop,
+        };
+
+        match self
+            .routes
+            .iter_mut()
+            .find(|e| e.final_addr == r.final_addr)
+        {
+            Some(e) => {
+                *e = r;
+                Ok(())
+            }
+            None => self.routes.push(r),
+        }
+    }
+
+    /// Remove the route to a final address
+    pub fn remove(&mut self, final_addr: &A) -> Option<Route<A>> {
+        let i = self
+            .routes
+            .iter()
+            .position(|r| &r.final_addr == final_addr)?;
+        Some(self.routes.swap_remove(i))
+    }
+
+    /// Lookup the route to a final address
+    pub fn lookup(&self, final_addr: &A) -> Option<&Route<A>> {
+        self.routes.iter().find(|r| &r.final_addr == final_addr)
+    }
+
+    /// Iterate over routes
+    pub fn iter(&self) -> impl Iterator<Item = &Route<A>> {
+        self.routes.iter()
+    }
+}
+
+impl<M, A, const MAX_PAYLOAD: usize, const TX_SLOTS: usize, const RX_SLOTS: usize>
+    SixLo<M, A, MAX_PAYLOAD, TX_SLOTS, RX_SLOTS>
+where
+    M: Mac<A>,
+    A: AddressOps,
+    <M as Mac<A>>::Error: FmtError,
+{
+    /// Fetch the mesh route table
+    pub fn routes(&self) -> &RouteTable<A> {
+        &self.routes
+    }
+
+    /// Fetch the mesh route table for modification
+    pub fn routes_mut(&mut self) -> &mut RouteTable<A> {
+        &mut self.routes
+    }
+
+    /// Resolve the next hop toward a final address via the route table or
+    /// otherwise the MAC (see [`Mac::next_hop`]), assuming this is a direct
+    /// neighbour where no route exists
+    pub(crate) fn next_hop(&self, final_addr: &A) -> A {
+        if let Some(r) = self.routes.lookup(final_addr) {
+            return r.next_hop.clone();
+        }
+
+        self.mac
+            .next_hop(final_addr)
+            .unwrap_or_else(|| final_addr.clone())
+    }
+
+    /// Forward a mesh frame toward the final address, decrementing hops left
+    pub(crate) fn forward(
+        &mut self,
+        now_ms: Ts,
+        data: &[u8],
+    ) -> Result<(), SixLoError<<M as Mac<A>>::Error>> {
+        let (mut mesh, n) = match MeshHeader::decode(data) {
+            Ok(v) => v,
+            Err(e) => {
+                warn!("Dropped invalid mesh frame: {:?}", e);
+                return Ok(());
+            }
+        };
+
+        mesh.hops_left = mesh.hops_left.saturating_sub(1);
+        if mesh.hops_left == 0 {
+            debug!(
+                "Dropped mesh frame from {:?}: no hops left",
+                mesh.origin_addr
+            );
+            return Ok(());
+        }
+
+        let mut next_hop = self.next_hop(&A::from_iid(mesh.final_addr, &self.mac_addr));
+
+        // Parse broadcast and fragment headers following the mesh header
+        let mut rest = &data[n..];
+        let bcast = BroadcastHeader::decode(rest).ok().map(|(b, k)| {
+            rest = &rest[k..];
+            b
+        });
+        let mut frag = FragHeader::decode(rest).ok().map(|(f, k)| {
+            rest = &rest[k..];
+            f
+        });
+
+        // Switch fragments to the next hop of the datagram, rewriting tags as required
+        if let Some(fh) = frag.as_mut() {
+            let origin = A::from_iid(mesh.origin_addr, &self.mac_addr);
+
+            match self.frag.forward(now_ms, &origin, fh, rest.len(), next_hop) {
+                Some((a, h)) => {
+                    next_hop = a;
+                    *fh = h;
+                }
+                None => return Ok(()),
+            }
+        }
+
+        // Re-encode headers, preserving the remaining headers and payload
+        let header = Header {
+            mesh: Some(mesh.clone()),
+            bcast,
+            frag,
+            ..Default::default()
+        };
+
+        let mut buff = [0u8; MAX_PAYLOAD];
+        let mut len = match header.encode(&self.mac_addr, &next_hop, &mut buff) {
+            Ok(n) => n,
+            Err(e) => {
+                warn!("Dropped mesh frame: {:?}", e);
+                return Ok(());
+            }
+        };
+
+        if len + rest.len() > buff.len() {
+            warn!("Dropped mesh frame: {} bytes exceeds MTU", len + rest.len());
+            return Ok(());
+        }
+
+        buff[len..len + rest.len()].copy_from_slice(rest);
+        len += rest.len();
+
+        debug!(
+            "Forwarding {} byte mesh frame to {:?} via {:?}",
+            len, mesh.final_addr, next_hop
+        );
+
+        let ack = requires_ack(&next_hop);
+        self.mac
+            .transmit(next_hop, &buff[..len], ack)
+            .map_err(SixLoError::Mac)?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use ieee802154::mac::{PanId, ShortAddress};
+
+    use super::*;
+    use crate::sixlo::{mock::MockMac, SixLoConfig};
+
+    #[test]
+    fn route_table() {
+        let a = MacAddress::Short(PanId(1), ShortAddress(1));
+        let b = MacAddress::Short(PanId(1), ShortAddress(2));
+        let c = MacAddress::Short(PanId(1), ShortAddress(3));
+
+        let mut t = RouteTable::<_, 1>::new();
+        t.add(c, b).unwrap();
+        t.add(c, a).unwrap();
+        assert_eq!(t.lookup(&c).map(|r| r.next_hop), Some(a));
+        assert!(t.add(b, a).is_err());
+
+        assert!(t.remove(&c).is_some());
+        assert_eq!(t.lookup(&c), None);
+    }
+
+    /// Fragmented datagram from A is forwarded via B to C
+    #[test]
+    fn mesh_forward() {
+        let addr_a = MacAddress::Short(PanId(1), ShortAddress(1));
+        let addr_b = MacAddress::Short(PanId(1), ShortAddress(2));
+        let addr_c = MacAddress::Short(PanId(1), ShortAddress(3));
+
+        let mac_a = MockMac::new(addr_a);
+        let mac_b = mac_a.link(addr_b);
+        let mac_c = mac_a.link(addr_c);
+
+        let mut sixlo_a = SixLo::<_, _, 127>::new(mac_a, addr_a, SixLoConfig::default());
+        let mut sixlo_b = SixLo::<_, _, 127>::new(mac_b, addr_b, SixLoConfig::default());
+        let mut sixlo_c = SixLo::<_, _, 127>::new(mac_c, addr_c, SixLoConfig::default());
+
+        sixlo_a.routes_mut().add(addr_c, addr_b).unwrap();
+
+        let data: std::vec::Vec<u8> = (0..200).map(|v| v as u8).collect();
+        sixlo_a.transmit(0, addr_c, &data).unwrap();
+
+        let mut buff = [0u8; 256];
+        let mut received = None;
+        for t in 0..40 {
+            sixlo_a.tick(t).unwrap();
+            sixlo_b.tick(t).unwrap();
+            sixlo_c.tick(t).unwrap();
+
+            // Intermediate nodes do not reassemble
+            assert_eq!(sixlo_b.receive(t, &mut buff).unwrap(), None);
+
+            if let Some(r) = sixlo_c.receive(t, &mut buff).unwrap() {
+                received = Some(r);
+                break;
+            }
+        }
+
+        let (n, info, header) = received.unwrap();
+        assert_eq!(&buff[..n], &data[..]);
+        assert_eq!(info.source, addr_a);
+
+        // Datagram is switched via the intermediate node, then received at the final node
+        assert_eq!(sixlo_b.frag_stats().fwd_datagrams, 1);
+        assert_eq!(sixlo_b.frag_stats().rx_datagrams, 0);
+        assert_eq!(sixlo_c.frag_stats().rx_datagrams, 1);
+        // Mesh headers are retained, fragment headers are consumed by reassembly
+        assert_eq!(header.mesh.as_ref().map(|m| m.hops_left), Some(6));
+        assert_eq!(header.frag, None);
+    }
+}
@@ -5,19 +5,42 @@
 
 use core::marker::PhantomData;
 
-use crate::log::{debug, error, info, trace, FmtError};
-use crate::{Mac, Ts};
+use heapless::spsc::Queue;
 
-use ieee802154::mac::{Address as MacAddress, ExtendedAddress, ShortAddress};
+use crate::error::BufferError;
+use crate::log::{debug, error, info, trace, warn, FmtError};
+use crate::{Mac, MacError, MacEvent, RxInfo, StackStats, Ts};
 
 #[cfg(feature = "smoltcp")]
 pub mod smoltcp;
 
+#[cfg(feature = "tun")]
+pub mod tun;
+
+pub mod mac;
+pub use mac::SixLoMac;
+
+pub mod addr;
+pub use addr::AddressOps;
+
 pub mod headers;
-use headers::{Eui64, Header, V6Addr};
+use headers::{
+    DispatchBits, Header, IphcHeader, UdpNhcHeader, V6Addr, IPV6_HEADER_LEN, IP_PROTO_UDP,
+    UDP_HEADER_LEN,
+};
 
 pub mod frag;
 use frag::*;
+pub use frag::{DatagramInfo, FragEvent, FragOutcome};
+
+pub mod nd;
+use nd::{NdConfig, NdState};
+
+pub mod mesh;
+use mesh::{MeshConfig, RouteTable};
+
+pub mod bcast;
+use bcast::{BroadcastConfig, BroadcastState};
 
 use self::headers::MeshHeader;
 
@@ -25,29 +48,72 @@ pub const IPV6_MTU: usize = 1280;
 
 pub const DEFAULT_FRAG_SIZE: usize = 64;
 
+/// Hop limit for UDP datagrams sent via [`SixLo::transmit_udp`]
+pub const DEFAULT_HOP_LIMIT: u8 = 64;
+
+/// Check whether a frame to the provided address should request an acknowledgement,
+/// i.e. the destination is not a broadcast address
+fn requires_ack<A: AddressOps>(addr: &A) -> bool {
+    !addr.is_broadcast()
+}
+
 /// 6LoWPAN Implementation, provides IP compatible interface to higher-layers.
 /// This includes IPv6 addressing, header compression, fragmentation,
-/// and neighbour discovery and management
-pub struct SixLo<M, const MAX_PAYLOAD: usize> {
+/// and neighbour discovery and management.
+///
+/// This is generic over the link-layer address type `A` of the underlying MAC,
+/// see [`AddressOps`], with the number of datagrams that may be transmitted and
+/// reassembled concurrently set by `TX_SLOTS` and `RX_SLOTS` (see [`Frag`])
+pub struct SixLo<
+    M,
+    A,
+    const MAX_PAYLOAD: usize,
+    const TX_SLOTS: usize = DEFAULT_TX_SLOTS,
+    const RX_SLOTS: usize = DEFAULT_RX_SLOTS,
+> {
     cfg: SixLoConfig,
 
     mac: M,
-    mac_addr: MacAddress,
+    mac_addr: A,
 
     //eui64: Eui64,
     //v6_addr: V6Addr,
-    frag: Frag<DEFAULT_FRAG_SIZE>,
+    frag: Frag<A, TX_SLOTS, RX_SLOTS>,
+    nd: NdState<A>,
+    routes: RouteTable<A>,
+    bcast: BroadcastState<A>,
+    stats: SixLoStats,
+    events: Queue<FragEvent<A>, { EVENT_QUEUE_DEPTH + 1 }>,
+
+    #[cfg(feature = "smoltcp")]
+    staging: smoltcp::Staging,
 }
 
 #[derive(Clone, PartialEq, Debug)]
 pub struct SixLoConfig {
     pub frag: FragConfig,
+    pub nd: NdConfig,
+    pub mesh: MeshConfig,
+    pub bcast: BroadcastConfig,
+    /// Elide UDP checksums from compressed headers, this should only be enabled
+    /// where frames are integrity protected by the MAC, per
+    /// [RFC6282 Section 4.3.2](https://tools.ietf.org/html/rfc6282#section-4.3.2)
+    pub elide_udp_checksum: bool,
+    /// Maximum number of fragments handed to the MAC per [`SixLo::tick`], while the
+    /// MAC reports capacity via [`Mac::can_transmit`]. Multiple fragments of a datagram
+    /// additionally require [`FragConfig::tx_window`]
+    pub tx_budget: u8,
 }
 
 impl Default for SixLoConfig {
     fn default() -> Self {
         Self {
             frag: Default::default(),
+            nd: Default::default(),
+            mesh: Default::default(),
+            bcast: Default::default(),
+            elide_udp_checksum: false,
+            tx_budget: 4,
         }
     }
 }
@@ -56,30 +122,112 @@ impl Default for SixLoConfig {
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub enum SixLoError<M> {
     Mac(M),
-    NoTxFragSlots,
+    /// No fragmentation buffers available for transmission
+    NoTxSlots,
+    /// No reassembly buffers or receive queue space available
+    NoRxSlots,
+    /// Invalid fragment received
+    Frag(FragError),
+    /// Encoding or receive buffer too small
+    Buffer(BufferError),
+    /// Invalid IPv6 packet provided for transmission
+    InvalidPacket,
 }
 
-impl<M, const MAX_PAYLOAD: usize> SixLo<M, MAX_PAYLOAD>
+impl<M: MacError> MacError for SixLoError<M> {
+    fn queue_full(&self) -> bool {
+        match self {
+            SixLoError::Mac(e) => e.queue_full(),
+            SixLoError::NoTxSlots => true,
+            _ => false,
+        }
+    }
+
+    fn buffer_length(&self) -> bool {
+        match self {
+            SixLoError::Mac(e) => e.buffer_length(),
+            SixLoError::Buffer(_) => true,
+            _ => false,
+        }
+    }
+
+    fn fatal(&self) -> bool {
+        match self {
+            SixLoError::Mac(e) => e.fatal(),
+            _ => false,
+        }
+    }
+}
+
+/// 6LoWPAN statistics, reported via [`SixLo::stats`] with those of the underlying MAC
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct SixLoStats {
+    /// Datagrams transmitted, fragmented datagrams once all fragments are sent
+    pub tx_datagrams: u32,
+    /// Datagrams received for this node
+    pub rx_datagrams: u32,
+    /// Fragments transmitted
+    pub tx_fragments: u32,
+    /// Fragments received for reassembly
+    pub rx_fragments: u32,
+    /// Fragmented datagrams aborted once fragment retries were exhausted
+    pub tx_failed: u32,
+    /// Fragmented datagrams dropped on transmit timeout
+    pub tx_timeouts: u32,
+    /// Received frames or datagrams dropped on decode errors, invalid fragments,
+    /// or without buffer space
+    pub rx_dropped: u32,
+    /// Incomplete datagrams dropped on reassembly timeout
+    pub rx_timeouts: u32,
+    /// Maximum number of reassembly slots in use
+    pub rx_slots_max: u32,
+    /// MAC layer statistics
+    pub mac: StackStats,
+}
+
+impl<M, A, const MAX_PAYLOAD: usize, const TX_SLOTS: usize, const RX_SLOTS: usize>
+    SixLo<M, A, MAX_PAYLOAD, TX_SLOTS, RX_SLOTS>
 where
-    M: Mac,
-    <M as Mac>::Error: FmtError,
+    M: Mac<A>,
+    A: AddressOps,
+    <M as Mac<A>>::Error: FmtError,
 {
     /// Create a new 6LowPAN stack instance
-    pub fn new(mac: M, addr: MacAddress, cfg: SixLoConfig) -> Self {
+    pub fn new(mac: M, addr: A, mut cfg: SixLoConfig) -> Self {
+        // Limit fragments to the MAC payload where not specified, these are
+        // further limited by the headers of each fragment on transmission
+        if cfg.frag.frag_size.is_none() {
+            cfg.frag.frag_size = Some(MAX_PAYLOAD & !0b111);
+        }
+
         let frag = Frag::new(cfg.frag.clone());
+        let nd = NdState::new(&cfg.nd);
 
         let s = Self {
             cfg,
 
             mac,
-            mac_addr: addr.clone(),
+            mac_addr: addr,
 
             // TODO: v6 + EUI addrs? PAN IDs?
             //v6_addr: V6Addr::from(addr.into()),
             frag,
+            nd,
+            routes: RouteTable::new(),
+            bcast: BroadcastState::new(),
+            stats: SixLoStats::default(),
+            events: Queue::new(),
+
+            #[cfg(feature = "smoltcp")]
+            staging: Default::default(),
         };
 
-        info!("Setup sixlo with address: {:?}", s.mac_addr);
+        info!(
+            "Setup sixlo with address: {:?} (fragment size: {})",
+            s.mac_addr,
+            s.frag.frag_size()
+        );
 
         s
     }
@@ -88,11 +236,28 @@ where
     fn handle_rx(
         &mut self,
         now_ms: Ts,
-        source: MacAddress,
+        info: RxInfo<A>,
         data: &[u8],
-    ) -> Result<(), SixLoError<<M as Mac>::Error>> {
-        // Decode headers
-        let (hdr, offset) = Header::decode(&data).unwrap();
+    ) -> Result<(), SixLoError<<M as Mac<A>>::Error>> {
+        let RxInfo {
+            source,
+            destination,
+            rssi,
+        } = info;
+
+        // Decode headers, restoring addresses compressed using known contexts
+        let ctx = &self.nd.contexts;
+        let (hdr, offset) = match Header::decode_ctx(&data, &source, &self.mac_addr, ctx) {
+            Ok(v) => v,
+            Err(e) => {
+                warn!(
+                    "Dropped frame from {:?}, header decode failed: {:?}",
+                    source, e
+                );
+                self.stats.rx_dropped = self.stats.rx_dropped.saturating_add(1);
+                return Ok(());
+            }
+        };
 
         debug!(
             "Received {:?} from {:?}, {} bytes",
@@ -101,9 +266,51 @@ where
             data.len() - offset
         );
 
+        // Forward mesh frames addressed to other nodes, without reassembly
+        if let Some(m) = &hdr.mesh {
+            let final_addr = A::from_iid(m.final_addr, &self.mac_addr);
+            if final_addr != self.mac_addr && requires_ack(&final_addr) {
+                return self.forward(now_ms, data);
+            }
+        }
+
+        // The mesh originator replaces the link-layer source where present
+        let source = match &hdr.mesh {
+            Some(m) => A::from_iid(m.origin_addr, &self.mac_addr),
+            None => source,
+        };
+
+        // Drop re-broadcast or forwarded copies of broadcasts already received
+        if self.bcast_duplicate(now_ms, &source, &hdr) {
+            return Ok(());
+        }
+
+        // Neighbour discovery messages are handled internally
+        if let Some(ip) = hdr.iphc.as_ref().filter(|_| hdr.frag.is_none()) {
+            if nd::is_nd(ip, &data[offset..]) {
+                return self.handle_nd(now_ms, source, ip, &data[offset..]);
+            }
+        }
+
         // Handle fragmentation
         // TODO: other layers before / after here?
-        self.frag.receive(now_ms, source, &hdr, &data[offset..])?;
+        let info = RxInfo {
+            source: source.clone(),
+            destination,
+            rssi,
+        };
+        match self.frag.receive(now_ms, info, &hdr, &data[offset..]) {
+            // Invalid fragments are dropped rather than failing the stack
+            Err(SixLoError::Frag(e)) => {
+                warn!("Dropped fragment from {:?}: {:?}", source, e);
+                self.stats.rx_dropped = self.stats.rx_dropped.saturating_add(1);
+            }
+            // Drops due to full buffers are counted in fragmentation stats
+            Err(SixLoError::NoRxSlots) => {
+                warn!("Dropped fragment from {:?}: no RX slots", source);
+            }
+            r => r?,
+        }
 
         Ok(())
     }
@@ -111,15 +318,69 @@ where
     pub fn mac(&self) -> &M {
         &self.mac
     }
+
+    /// Fetch the underlying MAC mutably
+    pub fn mac_mut(&mut self) -> &mut M {
+        &mut self.mac
+    }
+
+    /// Check whether a datagram can be accepted for transmission, requiring
+    /// a free fragmentation slot and capacity in the underlying MAC
+    pub fn can_transmit(&self) -> Result<bool, SixLoError<<M as Mac<A>>::Error>> {
+        let can_tx = self.mac.can_transmit().map_err(SixLoError::Mac)?;
+        Ok(can_tx && self.frag.can_transmit())
+    }
+
+    /// Signal a radio event to the underlying MAC, see [`Mac::radio_event`]
+    pub fn radio_event(&mut self) {
+        self.mac.radio_event()
+    }
+
+    /// Fetch fragmentation statistics
+    pub fn frag_stats(&self) -> FragStats {
+        self.frag.stats()
+    }
+
+    /// Fetch the next fragmented datagram outcome, collected by [`SixLo::tick`]
+    pub fn poll_event(&mut self) -> Option<FragEvent<A>> {
+        self.events.dequeue()
+    }
+
+    /// Fetch 6LoWPAN and MAC statistics
+    pub fn stats(&self) -> SixLoStats {
+        let f = self.frag.stats();
+
+        SixLoStats {
+            tx_datagrams: self.stats.tx_datagrams.saturating_add(f.tx_datagrams),
+            rx_datagrams: f.rx_datagrams,
+            tx_fragments: f.tx_fragments,
+            rx_fragments: f.rx_fragments,
+            tx_failed: f.tx_failed,
+            tx_timeouts: f.tx_timeouts,
+            rx_dropped: self.stats.rx_dropped.saturating_add(f.rx_dropped),
+            rx_timeouts: f.rx_timeouts,
+            rx_slots_max: f.rx_slots_max,
+            mac: self.mac.stats(),
+        }
+    }
+
+    /// Reset 6LoWPAN, fragmentation, and MAC statistics
+    pub fn reset_stats(&mut self) {
+        self.stats = SixLoStats::default();
+        self.frag.reset_stats();
+        self.mac.reset_stats();
+    }
 }
 
-impl<M, const MAX_PAYLOAD: usize> SixLo<M, MAX_PAYLOAD>
+impl<M, A, const MAX_PAYLOAD: usize, const TX_SLOTS: usize, const RX_SLOTS: usize>
+    SixLo<M, A, MAX_PAYLOAD, TX_SLOTS, RX_SLOTS>
 where
-    M: Mac,
-    <M as Mac>::Error: FmtError,
+    M: Mac<A>,
+    A: AddressOps,
+    <M as Mac<A>>::Error: FmtError,
 {
     /// Tick to update the stack
-    pub fn tick(&mut self, now_ms: u64) -> Result<(), SixLoError<<M as Mac>::Error>> {
+    pub fn tick(&mut self, now_ms: u64) -> Result<(), SixLoError<<M as Mac<A>>::Error>> {
         let mut buff = [0u8; MAX_PAYLOAD];
 
         trace!("MAC tick at {} ms", now_ms);
@@ -129,71 +390,299 @@ where
 
         let _mac_busy = self.mac.busy().map_err(SixLoError::Mac)?;
 
-        // Check for (and handle) received packets from the MAC
-        if let Some((n, info)) = self.mac.receive(&mut buff).map_err(SixLoError::Mac)? {
-            self.handle_rx(now_ms, info.source, &buff[..n])?;
+        // Apply MAC transmit outcomes to in-flight fragments
+        while let Some((handle, status)) = self.mac.tx_status().map_err(SixLoError::Mac)? {
+            self.frag.tx_status(handle, status);
         }
 
-        // Poll fragmentation buffer for pending fragments
-        let opts = PollOptions {
-            can_tx: self.mac.can_transmit().map_err(SixLoError::Mac)?,
-            ..Default::default()
-        };
-        if let Some((a, h, d)) = self.frag.poll(now_ms, opts) {
-            let ack = match a {
-                MacAddress::Short(_, s) if s != ShortAddress::BROADCAST => true,
-                MacAddress::Extended(_, s) if s != ExtendedAddress::BROADCAST => true,
-                _ => false,
+        // In-progress datagrams do not survive a MAC restart, which may also
+        // change the link-layer address
+        while let Some(event) = self.mac.event().map_err(SixLoError::Mac)? {
+            match event {
+                MacEvent::Restarted(addr) => {
+                    info!("MAC restarted with address {:?}, flushing fragments", addr);
+                    self.frag.flush();
+                    self.mac_addr = addr;
+                }
+                // Link-layer addresses include the PAN ID, so in-progress datagrams
+                // are also dropped on realignment
+                MacEvent::Realigned(addr) => {
+                    info!("MAC realigned with address {:?}, flushing fragments", addr);
+                    self.frag.flush();
+                    self.mac_addr = addr;
+                }
+                MacEvent::PanConflict | MacEvent::Disassociated(_) => (),
+            }
+        }
+
+        // Send datagrams staged by the smoltcp device
+        #[cfg(feature = "smoltcp")]
+        self.transmit_staged(now_ms)?;
+
+        // Update neighbour discovery
+        self.tick_nd(now_ms)?;
+
+        // Age out received broadcasts
+        self.tick_bcast(now_ms);
+
+        // Check for (and handle) received packets from the MAC,
+        // frames exceeding the MAC payload size are dropped
+        match self.mac.receive(&mut buff) {
+            Ok(Some((n, info))) => self.handle_rx(now_ms, info, &buff[..n])?,
+            Ok(None) => (),
+            Err(e) if e.buffer_length() => {
+                warn!("Dropped received frame exceeding {} bytes", MAX_PAYLOAD);
+                self.mac.discard().map_err(SixLoError::Mac)?;
+            }
+            Err(e) => return Err(SixLoError::Mac(e)),
+        }
+
+        // Hand pending fragments to the MAC while this has capacity,
+        // up to the configured budget per tick
+        for _ in 0..self.cfg.tx_budget.max(1) {
+            let opts = PollOptions {
+                can_tx: self.mac.can_transmit().map_err(SixLoError::Mac)?,
+                ..Default::default()
+            };
+            let (mac_addr, contexts) = (&self.mac_addr, &self.nd.contexts);
+            let budget = |a: &A, h: &Header| {
+                let mut b = [0u8; MAX_PAYLOAD];
+                match h.encode_ctx(mac_addr, a, contexts, &mut b) {
+                    Ok(n) => MAX_PAYLOAD - n,
+                    Err(_) => 0,
+                }
             };
 
+            // Fragments are polled regardless of MAC capacity to apply timeouts
+            let (a, h, d) = match self.frag.poll_budget(now_ms, opts, budget) {
+                Some(v) => v,
+                None => break,
+            };
+            let ack = requires_ack(&a);
+
             // Encode header + data
-            let mut n = h.encode(&mut buff);
+            let mut n = h
+                .encode_ctx(&self.mac_addr, &a, &self.nd.contexts, &mut buff)
+                .map_err(SixLoError::Buffer)?;
+            if n + d.len() > buff.len() {
+                error!(
+                    "Fragment of {} bytes exceeds MAC payload size {}",
+                    n + d.len(),
+                    MAX_PAYLOAD
+                );
+                return Err(SixLoError::Buffer(BufferError::TooSmall));
+            }
+
             buff[n..n + d.len()].copy_from_slice(d);
             n += d.len();
 
             debug!("Transferring {} byte fragment to MAC", n);
 
-            // Transmit fragment
-            self.mac
-                .transmit(a, &buff[..n], ack)
-                .map_err(SixLoError::Mac)?;
+            // Transmit fragment, this is returned by the next poll if the MAC queue is full
+            let sent = match self.mac.transmit(a.clone(), &buff[..n], ack) {
+                Ok(handle) => h.frag.map(|fh| (handle, a, fh.datagram_tag)),
+                Err(e) if e.queue_full() => {
+                    debug!("MAC queue full, deferring fragment");
+                    break;
+                }
+                Err(e) => return Err(SixLoError::Mac(e)),
+            };
+
+            // Track the fragment outcome, advancing or retrying once this is reported
+            match sent {
+                Some((handle, a, tag)) => self.frag.sent(handle, a, tag),
+                None => break,
+            }
+        }
+
+        // Collect datagram outcomes, dropping the oldest if these are not being polled
+        while let Some(e) = self.frag.take_event() {
+            if self.events.is_full() {
+                let _ = self.events.dequeue();
+            }
+            let _ = self.events.enqueue(e);
         }
 
         Ok(())
     }
 
-    /// Transmit a datagram, fragmenting this as required
+    /// Fetch the time of the next scheduled operation, including those of the
+    /// underlying MAC, by which [`SixLo::tick`] should be called.
+    /// Deadlines at or before the current time are due immediately
+    pub fn next_deadline(&self, now_ms: Ts) -> Option<Ts> {
+        // Datagrams staged by the smoltcp device are sent on the next tick
+        #[cfg(feature = "smoltcp")]
+        if self.staged() {
+            return Some(now_ms);
+        }
+
+        let can_tx = self.mac.can_transmit().unwrap_or(false);
+
+        [
+            self.mac.next_deadline(),
+            self.frag.next_deadline(now_ms, can_tx),
+            self.nd_deadline(),
+            self.bcast_deadline(),
+        ]
+        .iter()
+        .flatten()
+        .min()
+        .copied()
+    }
+
+    /// Transmit a datagram, fragmenting this as required.
+    /// Datagrams starting with an uncompressed IPv6 dispatch are fragmented by
+    /// the following IPv6 packet, with the dispatch in the first fragment
     pub fn transmit(
         &mut self,
         now_ms: Ts,
-        dest: MacAddress,
+        dest: A,
         data: &[u8],
-    ) -> Result<(), SixLoError<<M as Mac>::Error>> {
-        let mut buff = [0u8; MAX_PAYLOAD];
+    ) -> Result<(), SixLoError<<M as Mac<A>>::Error>> {
+        match data.split_first() {
+            Some((d, packet)) if *d == DispatchBits::Ipv6 as u8 => {
+                let header = Header {
+                    ipv6: true,
+                    ..Default::default()
+                };
+                self.transmit_header(now_ms, dest, header, packet)
+            }
+            _ => self.transmit_header(now_ms, dest, Header::default(), data),
+        }
+    }
+
+    /// Transmit an IPv6 payload with a compressed (IPHC) header,
+    /// fragmenting this as required. The link-layer destination is
+    /// resolved from the IPv6 destination via [`SixLo::resolve`]
+    pub fn transmit_ipv6(
+        &mut self,
+        now_ms: Ts,
+        ip: IphcHeader,
+        data: &[u8],
+    ) -> Result<(), SixLoError<<M as Mac<A>>::Error>> {
+        self.transmit_ip(now_ms, ip, None, data)
+    }
+
+    /// Transmit an uncompressed IPv6 packet, compressing the IPv6 (and any UDP)
+    /// header and fragmenting this as required. Packets exceeding the [`IPV6_MTU`]
+    /// are rejected with [`SixLoError::Buffer`], and malformed packets with
+    /// [`SixLoError::InvalidPacket`]
+    pub fn transmit_ipv6_packet(
+        &mut self,
+        now_ms: Ts,
+        packet: &[u8],
+    ) -> Result<(), SixLoError<<M as Mac<A>>::Error>> {
+        if packet.len() > IPV6_MTU {
+            warn!(
+                "Rejecting {} byte IPv6 packet, exceeds {} byte MTU",
+                packet.len(),
+                IPV6_MTU
+            );
+            return Err(SixLoError::Buffer(BufferError::TooSmall));
+        }
+
+        let (ip, payload_len) = match IphcHeader::from_ipv6(packet) {
+            Ok(v) if IPV6_HEADER_LEN + v.1 <= packet.len() => v,
+            _ => {
+                warn!("Dropping invalid IPv6 packet ({} bytes)", packet.len());
+                return Err(SixLoError::InvalidPacket);
+            }
+        };
+
+        debug!("Transmit {} byte IPv6 packet to {:?}", packet.len(), ip.dst);
+
+        let payload = &packet[IPV6_HEADER_LEN..IPV6_HEADER_LEN + payload_len];
+
+        // Compress UDP headers where present
+        if ip.next_header == IP_PROTO_UDP {
+            if let Ok((mut udp, n)) = UdpNhcHeader::from_udp(payload) {
+                if self.cfg.elide_udp_checksum {
+                    udp.checksum = None;
+                }
 
-        // Write IPv6 headers
-        // TODO: actually set these headers
-        let mut header = Header::default();
+                let data = &payload[UDP_HEADER_LEN..UDP_HEADER_LEN + n];
+                return self.transmit_ip(now_ms, ip, Some(udp), data);
+            }
+        }
+
+        self.transmit_ipv6(now_ms, ip, payload)
+    }
 
-        #[cfg(nope)]
-        {
-            // Disabled while sorting out which headers are right / useful / required
+    /// Transmit a UDP datagram with compressed (IPHC and NHC) headers,
+    /// fragmenting this as required. The source address is the global address
+    /// for non link-local destinations where available, otherwise the link-local address
+    pub fn transmit_udp(
+        &mut self,
+        now_ms: Ts,
+        dst: V6Addr,
+        src_port: u16,
+        dst_port: u16,
+        data: &[u8],
+    ) -> Result<(), SixLoError<<M as Mac<A>>::Error>> {
+        let src = match self.global_addr() {
+            Some(a) if !dst.is_link_local() && !dst.is_multicast() => a,
+            _ => self.link_local_addr(),
+        };
+
+        let ip = IphcHeader::new(src, dst, IP_PROTO_UDP, DEFAULT_HOP_LIMIT);
+
+        let mut udp = UdpNhcHeader::new(src_port, dst_port);
+        if !self.cfg.elide_udp_checksum {
+            udp.checksum = Some(udp.compute_checksum(&ip.src, &ip.dst, data));
+        }
+
+        self.transmit_ip(now_ms, ip, Some(udp), data)
+    }
+
+    fn transmit_ip(
+        &mut self,
+        now_ms: Ts,
+        ip: IphcHeader,
+        udp: Option<UdpNhcHeader>,
+        data: &[u8],
+    ) -> Result<(), SixLoError<<M as Mac<A>>::Error>> {
+        let dest = self.resolve(&ip.dst);
+
+        let header = Header {
+            iphc: Some(ip),
+            udp,
+            ..Default::default()
+        };
+
+        self.transmit_header(now_ms, dest, header, data)
+    }
+
+    fn transmit_header(
+        &mut self,
+        now_ms: Ts,
+        dest: A,
+        mut header: Header,
+        data: &[u8],
+    ) -> Result<(), SixLoError<<M as Mac<A>>::Error>> {
+        let mut buff = [0u8; MAX_PAYLOAD];
+
+        // Add a mesh header where the destination is reached via another node
+        let next_hop = self.next_hop(&dest);
+        if next_hop != dest {
             header.mesh = Some(MeshHeader {
-                final_addr: dest,
-                origin_addr: self.mac_addr,
-                hops_left: 7,
+                final_addr: dest.clone().into(),
+                origin_addr: self.mac_addr.clone().into(),
+                hops_left: self.cfg.mesh.hops_left,
             });
         }
 
-        let mut n = header.encode(&mut buff);
+        // Add a broadcast header with the next sequence number for broadcasts
+        if dest.is_broadcast() && header.bcast.is_none() {
+            header.bcast = Some(self.bcast_header());
+        }
+
+        let mut n = header
+            .encode_ctx(&self.mac_addr, &next_hop, &self.nd.contexts, &mut buff)
+            .map_err(SixLoError::Buffer)?;
 
         debug!("TX header: {:?} ({} bytes)", header, n);
 
-        let ack = match dest {
-            MacAddress::Short(_, s) if s != ShortAddress::BROADCAST => true,
-            MacAddress::Extended(_, s) if s != ExtendedAddress::BROADCAST => true,
-            _ => false,
-        };
+        let ack = requires_ack(&next_hop);
 
         // If we don't need to fragment, send directly
         if n + data.len() < buff.len() {
@@ -205,14 +694,15 @@ where
 
             // Transmit directly
             self.mac
-                .transmit(dest, &buff[..n], ack)
+                .transmit(next_hop, &buff[..n], ack)
                 .map_err(SixLoError::Mac)?;
+            self.stats.tx_datagrams = self.stats.tx_datagrams.saturating_add(1);
 
         // Otherwise, add the datagram to the fragmentation buffer
         } else {
             debug!("Fragmented TX {} byte datagram", data.len());
 
-            if let Err(e) = self.frag.transmit(now_ms, dest, header, data) {
+            if let Err(e) = self.frag.transmit(now_ms, next_hop, header, data) {
                 error!("Failed to add datagram to fragmentation buffer: {:?}", e);
                 return Err(e);
             }
@@ -221,26 +711,938 @@ where
         Ok(())
     }
 
-    /// Receive a datagram, reassembled internally
+    /// Receive a datagram, reassembled internally.
+    /// Datagrams exceeding the provided buffer are retained, returning
+    /// [`SixLoError::Buffer`] with the required length, and may be received with
+    /// a larger buffer or dropped via [`SixLo::discard`].
+    ///
+    /// The datagram source is returned with RSSI aggregated over received fragments
+    /// in [`DatagramInfo`], and UDP ports in [`Header::udp`] for compressed UDP datagrams,
+    /// with elided checksums recomputed from the received datagram
     pub fn receive(
         &mut self,
         now_ms: Ts,
         buff: &mut [u8],
-    ) -> Result<Option<(usize, MacAddress, Header)>, SixLoError<<M as Mac>::Error>> {
-        if let Some((a, h, d)) = self.frag.pop() {
+    ) -> Result<Option<(usize, DatagramInfo<A>, Header)>, SixLoError<<M as Mac<A>>::Error>> {
+        // Retain datagrams exceeding the provided buffer for retry
+        if let Some((_info, _h, d)) = self.frag.peek() {
+            if d.len() > buff.len() {
+                debug!(
+                    "Received {} byte datagram exceeds {} byte buffer",
+                    d.len(),
+                    buff.len()
+                );
+                let needed = d.len();
+                return Err(SixLoError::Buffer(BufferError::Insufficient { needed }));
+            }
+        }
+
+        if let Some((info, h, d)) = self.frag.pop() {
             buff[..d.len()].copy_from_slice(d);
 
-            Ok(Some((d.len(), a.clone(), h.clone())))
+            let mut h = h.clone();
+            if let (Some(ip), Some(udp)) = (&h.iphc, &mut h.udp) {
+                if udp.checksum.is_none() {
+                    udp.checksum = Some(udp.compute_checksum(&ip.src, &ip.dst, d));
+                }
+            }
+
+            Ok(Some((d.len(), info, h)))
         } else {
             Ok(None)
         }
     }
+
+    /// Drop the next received datagram, as where this exceeds the buffers available
+    /// for [`SixLo::receive`], returning whether a datagram was dropped
+    pub fn discard(&mut self) -> bool {
+        match self.frag.pop() {
+            Some((info, _h, d)) => {
+                warn!("Dropped {} byte datagram from {:?}", d.len(), info.source);
+                self.stats.rx_dropped = self.stats.rx_dropped.saturating_add(1);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Receive a datagram as an uncompressed IPv6 packet, reassembled internally.
+    /// Non-IPv6 datagrams are dropped, as are packets exceeding the provided buffer,
+    /// returning [`SixLoError::Buffer`]
+    pub fn receive_ipv6_packet(
+        &mut self,
+        _now_ms: Ts,
+        buff: &mut [u8],
+    ) -> Result<Option<(usize, DatagramInfo<A>)>, SixLoError<<M as Mac<A>>::Error>> {
+        while let Some((info, h, d)) = self.frag.pop() {
+            match h.restore_ipv6(d, buff) {
+                Ok(Some(n)) => return Ok(Some((n, info))),
+                Ok(None) => {
+                    debug!(
+                        "Dropped non-IPv6 datagram from {:?} ({} bytes)",
+                        info.source,
+                        d.len()
+                    );
+                    self.stats.rx_dropped = self.stats.rx_dropped.saturating_add(1);
+                }
+                Err(e) => {
+                    warn!(
+                        "Dropped {} byte datagram from {:?}, exceeds {} byte buffer",
+                        d.len(),
+                        info.source,
+                        buff.len()
+                    );
+                    self.stats.rx_dropped = self.stats.rx_dropped.saturating_add(1);
+                    return Err(SixLoError::Buffer(e));
+                }
+            }
+        }
+
+        Ok(None)
+    }
+}
+
+#[cfg(any(test, feature = "mocks"))]
+pub mod mock {
+    use std::collections::VecDeque;
+    use std::sync::{Arc, Mutex};
+    use std::vec::Vec;
+
+    use ieee802154::mac::Address;
+
+    use super::AddressOps;
+    use crate::error::BufferError;
+    use crate::{Mac, MacError, MacEvent, MacState, RxInfo, Ts, TxFailure, TxHandle, TxStatus};
+
+    /// Mock MAC error
+    #[derive(Clone, Debug, PartialEq)]
+    #[cfg_attr(feature = "defmt", derive(defmt::Format))]
+    pub enum MockError {
+        /// Transmit queue full
+        QueueFull,
+        /// Receive buffer too small
+        Buffer(BufferError),
+        /// Tick failure, see [`MockMac::fail_tick`]
+        Tick,
+        /// Fatal tick failure, see [`MockMac::fail_tick`]
+        Wedged,
+    }
+
+    impl MacError for MockError {
+        fn queue_full(&self) -> bool {
+            *self == MockError::QueueFull
+        }
+
+        fn buffer_length(&self) -> bool {
+            matches!(self, MockError::Buffer(_))
+        }
+
+        fn fatal(&self) -> bool {
+            *self == MockError::Wedged
+        }
+    }
+
+    /// Frames in flight, as (source, destination, data)
+    type Medium<A> = Arc<Mutex<VecDeque<(A, A, Vec<u8>)>>>;
+
+    /// Mock MAC implementation to assist with testing,
+    /// delivers frames between linked instances via a shared medium
+    #[derive(Clone, Debug)]
+    pub struct MockMac<A = Address> {
+        addr: A,
+        medium: Medium<A>,
+        /// Maximum frames in flight from this instance
+        capacity: usize,
+        /// Fail every nth transmission, see [`MockMac::with_failures`]
+        fail_every: usize,
+        tx_count: usize,
+        next_handle: TxHandle,
+        tx_status: VecDeque<(TxHandle, TxStatus)>,
+        events: VecDeque<MacEvent<A>>,
+        tick_errors: VecDeque<MockError>,
+    }
+
+    impl<A: AddressOps> MockMac<A> {
+        pub fn new(addr: A) -> Self {
+            Self {
+                addr,
+                medium: Arc::new(Mutex::new(VecDeque::new())),
+                capacity: usize::MAX,
+                fail_every: 0,
+                tx_count: 0,
+                next_handle: 0,
+                tx_status: VecDeque::new(),
+                events: VecDeque::new(),
+                tick_errors: VecDeque::new(),
+            }
+        }
+
+        /// Simulate a MAC restart with the provided address, reported via [`Mac::event`]
+        pub fn restart(&mut self, addr: A) {
+            self.addr = addr.clone();
+            self.events.push_back(MacEvent::Restarted(addr));
+        }
+
+        /// Create a new MAC sharing the medium with this instance
+        pub fn link(&self, addr: A) -> Self {
+            Self {
+                medium: self.medium.clone(),
+                ..Self::new(addr)
+            }
+        }
+
+        /// Limit the number of frames in flight from this instance,
+        /// with further transmissions rejected as [`MockError::QueueFull`]
+        pub fn with_capacity(mut self, capacity: usize) -> Self {
+            self.capacity = capacity;
+            self
+        }
+
+        /// Fail every nth transmission, with the frame dropped and the failure
+        /// reported via [`Mac::tx_status`]
+        pub fn with_failures(mut self, every: usize) -> Self {
+            self.fail_every = every;
+            self
+        }
+
+        /// Fail the next (not yet failed) tick with the provided error
+        pub fn fail_tick(&mut self, err: MockError) {
+            self.tick_errors.push_back(err);
+        }
+
+        /// Fetch the number of frames in flight
+        pub fn pending(&self) -> usize {
+            self.medium.lock().unwrap().len()
+        }
+
+        /// Fetch the number of frames in flight from this instance
+        fn queued(&self) -> usize {
+            let m = self.medium.lock().unwrap();
+            m.iter().filter(|(s, _, _)| s == &self.addr).count()
+        }
+    }
+
+    impl<A: AddressOps> Mac<A> for MockMac<A> {
+        type Error = MockError;
+
+        fn state(&self) -> Result<MacState<A>, Self::Error> {
+            Ok(MacState::Associated(self.addr.clone()))
+        }
+
+        fn tick(&mut self) -> Result<(), Self::Error> {
+            match self.tick_errors.pop_front() {
+                Some(e) => Err(e),
+                None => Ok(()),
+            }
+        }
+
+        fn busy(&mut self) -> Result<bool, Self::Error> {
+            Ok(false)
+        }
+
+        fn can_transmit(&self) -> Result<bool, Self::Error> {
+            Ok(self.queued() < self.capacity)
+        }
+
+        fn transmit(&mut self, dest: A, data: &[u8], _ack: bool) -> Result<TxHandle, Self::Error> {
+            if self.queued() >= self.capacity {
+                return Err(MockError::QueueFull);
+            }
+
+            let handle = self.next_handle;
+            self.next_handle = self.next_handle.wrapping_add(1);
+
+            self.tx_count += 1;
+            if self.fail_every != 0 && self.tx_count % self.fail_every == 0 {
+                let status = TxStatus::Failed(TxFailure::RetriesExceeded);
+                self.tx_status.push_back((handle, status));
+                return Ok(handle);
+            }
+
+            let mut m = self.medium.lock().unwrap();
+            m.push_back((self.addr.clone(), dest, data.to_vec()));
+
+            self.tx_status.push_back((handle, TxStatus::Sent));
+
+            Ok(handle)
+        }
+
+        fn tx_status(&mut self) -> Result<Option<(TxHandle, TxStatus)>, Self::Error> {
+            Ok(self.tx_status.pop_front())
+        }
+
+        fn event(&mut self) -> Result<Option<MacEvent<A>>, Self::Error> {
+            Ok(self.events.pop_front())
+        }
+
+        /// Transmit outcomes are due for collection immediately
+        fn next_deadline(&self) -> Option<Ts> {
+            match self.tx_status.is_empty() {
+                true => None,
+                false => Some(0),
+            }
+        }
+
+        fn radio_event(&mut self) {}
+
+        fn receive(&mut self, data: &mut [u8]) -> Result<Option<(usize, RxInfo<A>)>, Self::Error> {
+            let mut m = self.medium.lock().unwrap();
+
+            // Find the next frame addressed to this instance
+            let addr = &self.addr;
+            let i = match m
+                .iter()
+                .position(|(s, d, _)| s != addr && (d == addr || d.is_broadcast()))
+            {
+                Some(i) => i,
+                None => return Ok(None),
+            };
+
+            let needed = m[i].2.len();
+            if needed > data.len() {
+                return Err(MockError::Buffer(BufferError::Insufficient { needed }));
+            }
+
+            let (source, destination, d) = m.remove(i).unwrap();
+
+            data[..d.len()].copy_from_slice(&d);
+
+            let info = RxInfo {
+                source,
+                destination,
+                rssi: 0,
+            };
+            Ok(Some((d.len(), info)))
+        }
+
+        fn discard(&mut self) -> Result<bool, Self::Error> {
+            let mut m = self.medium.lock().unwrap();
+
+            let addr = &self.addr;
+            match m
+                .iter()
+                .position(|(s, d, _)| s != addr && (d == addr || d.is_broadcast()))
+            {
+                Some(i) => Ok(m.remove(i).is_some()),
+                None => Ok(false),
+            }
+        }
+    }
 }
 
 #[cfg(test)]
 mod test {
+    use ieee802154::mac::{Address as MacAddress, PanId, ShortAddress};
+
+    use super::headers::Eui64;
+    use super::mock::{MockError, MockMac};
     use super::*;
+    use crate::sixlo::nd::{NdConfig, ALL_NODES};
 
     #[test]
     fn test_frag_defrag() {}
+
+    /// Datagrams exceeding the MAC queue capacity are held until this drains
+    #[test]
+    fn frag_backpressure() {
+        let addr_a = MacAddress::Short(PanId(1), ShortAddress(1));
+        let addr_b = MacAddress::Short(PanId(1), ShortAddress(2));
+
+        let mac_a = MockMac::new(addr_a).with_capacity(2);
+        let mac_b = mac_a.link(addr_b);
+
+        let mut sixlo_a = SixLo::<_, _, 127>::new(mac_a, addr_a, SixLoConfig::default());
+        let mut sixlo_b = SixLo::<_, _, 127>::new(mac_b, addr_b, SixLoConfig::default());
+
+        let mut tx = [0u8; 300];
+        for (i, b) in tx.iter_mut().enumerate() {
+            *b = i as u8;
+        }
+        sixlo_a.transmit(0, addr_b, &tx).unwrap();
+
+        // Fragments are only passed to the MAC while there is space
+        for t in 0..4 {
+            sixlo_a.tick(t).unwrap();
+        }
+        assert_eq!(sixlo_a.mac().pending(), 2);
+
+        // Immediate transmissions report the full queue
+        assert_eq!(
+            sixlo_a.transmit(4, addr_b, &[0xaa, 0xbb]),
+            Err(SixLoError::Mac(MockError::QueueFull))
+        );
+
+        // Remaining fragments follow as the queue drains
+        let mut buff = [0u8; 512];
+        let mut rx = None;
+        for t in 5..20 {
+            sixlo_a.tick(t).unwrap();
+            sixlo_b.tick(t).unwrap();
+
+            if let Some((n, info, _h)) = sixlo_b.receive(t, &mut buff).unwrap() {
+                rx = Some((n, info.source));
+            }
+        }
+
+        assert_eq!(rx, Some((tx.len(), addr_a)));
+        assert_eq!(&buff[..tx.len()], &tx[..]);
+    }
+
+    /// Fragments failed by the MAC are retried, with the datagram delivered intact
+    #[test]
+    fn frag_retry() {
+        let addr_a = MacAddress::Short(PanId(1), ShortAddress(1));
+        let addr_b = MacAddress::Short(PanId(1), ShortAddress(2));
+
+        // Every second transmission fails
+        let mac_a = MockMac::new(addr_a).with_failures(2);
+        let mac_b = mac_a.link(addr_b);
+
+        // Routers, so no solicitations are scheduled
+        let cfg = SixLoConfig {
+            nd: NdConfig {
+                router: true,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let mut sixlo_a = SixLo::<_, _, 127>::new(mac_a, addr_a, cfg.clone());
+        let mut sixlo_b = SixLo::<_, _, 127>::new(mac_b, addr_b, cfg.clone());
+
+        let mut tx = [0u8; 300];
+        for (i, b) in tx.iter_mut().enumerate() {
+            *b = i as u8;
+        }
+        sixlo_a.transmit(0, addr_b, &tx).unwrap();
+
+        let mut buff = [0u8; 512];
+        let mut rx = None;
+        for t in 1..40 {
+            sixlo_a.tick(t).unwrap();
+            sixlo_b.tick(t).unwrap();
+
+            if let Some((n, info, _h)) = sixlo_b.receive(t, &mut buff).unwrap() {
+                rx = Some((n, info.source));
+            }
+        }
+
+        assert_eq!(rx, Some((tx.len(), addr_a)));
+        assert_eq!(&buff[..tx.len()], &tx[..]);
+
+        // Each fragment following the first fails once prior to being sent
+        let stats = sixlo_a.frag_stats();
+        assert_eq!(stats.tx_datagrams, 1);
+        assert_eq!(stats.tx_retries, stats.tx_fragments - 1);
+        assert_eq!(stats.tx_failed, 0);
+    }
+
+    /// Statistics count datagrams sent and received, and frames dropped on receipt
+    #[test]
+    fn stats() {
+        let addr_a = MacAddress::Short(PanId(1), ShortAddress(1));
+        let addr_b = MacAddress::Short(PanId(1), ShortAddress(2));
+
+        let mac_a = MockMac::new(addr_a);
+        let mac_b = mac_a.link(addr_b);
+
+        // Routers, so no solicitations are scheduled
+        let cfg = SixLoConfig {
+            nd: NdConfig {
+                router: true,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let mut sixlo_a = SixLo::<_, _, 127>::new(mac_a, addr_a, cfg.clone());
+        let mut sixlo_b = SixLo::<_, _, 127>::new(mac_b, addr_b, cfg);
+
+        // Short datagrams are sent immediately, larger datagrams as fragments
+        sixlo_a.transmit(0, addr_b, &[0x00, 0x11, 0x22]).unwrap();
+        sixlo_a.transmit(0, addr_b, &[0x00; 200]).unwrap();
+
+        let mut buff = [0u8; 256];
+        for t in 1..10 {
+            sixlo_a.tick(t).unwrap();
+            sixlo_b.tick(t).unwrap();
+            while sixlo_b.receive(t, &mut buff).unwrap().is_some() {}
+        }
+
+        // Fragments carry the MAC payload remaining following headers
+        let stats = sixlo_a.stats();
+        assert_eq!(stats.tx_datagrams, 2);
+        assert_eq!(stats.tx_fragments, 2);
+
+        let stats = sixlo_b.stats();
+        assert_eq!(stats.rx_datagrams, 2);
+        assert_eq!(stats.rx_fragments, 2);
+        assert_eq!(stats.rx_slots_max, 1);
+
+        // Frames failing header decoding are dropped
+        sixlo_a.transmit(10, addr_b, &[0x81]).unwrap();
+        sixlo_a.tick(10).unwrap();
+        sixlo_b.tick(10).unwrap();
+
+        assert!(sixlo_b.receive(10, &mut buff).unwrap().is_none());
+        assert_eq!(sixlo_b.stats().rx_dropped, 1);
+
+        sixlo_b.reset_stats();
+        assert_eq!(sixlo_b.stats(), SixLoStats::default());
+    }
+
+    /// Datagrams exceeding the receive buffer are retained for retry
+    #[test]
+    fn receive_retry() {
+        let addr_a = MacAddress::Short(PanId(1), ShortAddress(1));
+        let addr_b = MacAddress::Short(PanId(1), ShortAddress(2));
+
+        let mac_a = MockMac::new(addr_a);
+        let mac_b = mac_a.link(addr_b);
+
+        // Routers, so no solicitations are scheduled
+        let cfg = SixLoConfig {
+            nd: NdConfig {
+                router: true,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let mut sixlo_a = SixLo::<_, _, 127>::new(mac_a, addr_a, cfg.clone());
+        let mut sixlo_b = SixLo::<_, _, 127>::new(mac_b, addr_b, cfg);
+
+        let mut tx = [0u8; 300];
+        for (i, b) in tx.iter_mut().enumerate() {
+            *b = i as u8;
+        }
+        sixlo_a.transmit(0, addr_b, &tx).unwrap();
+
+        // Reporting the length required where the buffer is too small
+        let mut small = [0u8; 128];
+        let mut rx = None;
+        for t in 1..20 {
+            sixlo_a.tick(t).unwrap();
+            sixlo_b.tick(t).unwrap();
+
+            if let Err(e) = sixlo_b.receive(t, &mut small) {
+                rx = Some(e);
+                break;
+            }
+        }
+        assert_eq!(
+            rx,
+            Some(SixLoError::Buffer(BufferError::Insufficient {
+                needed: 300
+            }))
+        );
+
+        // With the datagram then received on retry with a larger buffer
+        let mut buff = [0u8; 512];
+        let (n, info, _h) = sixlo_b.receive(20, &mut buff).unwrap().unwrap();
+        assert_eq!(&buff[..n], &tx[..]);
+        assert_eq!(info.source, addr_a);
+
+        // Or discarded where no larger buffer is available
+        sixlo_a.transmit(20, addr_b, &[0x00; 200]).unwrap();
+        for t in 20..40 {
+            sixlo_a.tick(t).unwrap();
+            sixlo_b.tick(t).unwrap();
+        }
+        assert!(sixlo_b.receive(40, &mut small).is_err());
+        assert!(sixlo_b.discard());
+        assert!(sixlo_b.receive(40, &mut small).unwrap().is_none());
+        assert_eq!(sixlo_b.stats().rx_dropped, 1);
+    }
+
+    #[test]
+    fn deadlines() {
+        let addr_a = MacAddress::Short(PanId(1), ShortAddress(1));
+        let addr_b = MacAddress::Short(PanId(1), ShortAddress(2));
+
+        let mac_a = MockMac::new(addr_a);
+        let mac_b = mac_a.link(addr_b);
+
+        // Routers, so no solicitations are scheduled
+        let cfg = SixLoConfig {
+            nd: NdConfig {
+                router: true,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let mut sixlo_a = SixLo::<_, _, 127>::new(mac_a, addr_a, cfg.clone());
+        let mut sixlo_b = SixLo::<_, _, 127>::new(mac_b, addr_b, cfg.clone());
+
+        assert_eq!(sixlo_a.next_deadline(0), None);
+
+        // Pending fragments are due immediately
+        sixlo_a.transmit(0, addr_b, &[0xaa; 200]).unwrap();
+        assert_eq!(sixlo_a.next_deadline(0), Some(0));
+
+        // Following transmission fragments are held until the MAC reports the outcome
+        sixlo_a.tick(1).unwrap();
+        assert_eq!(sixlo_a.next_deadline(1), Some(0));
+
+        // Partially received datagrams are due at the reassembly timeout
+        sixlo_b.tick(2).unwrap();
+        assert_eq!(
+            sixlo_b.next_deadline(2),
+            Some(2 + cfg.frag.frag_rx_timeout_ms + 1)
+        );
+    }
+
+    #[test]
+    fn mac_restart_flush() {
+        let addr_a = MacAddress::Short(PanId(1), ShortAddress(1));
+        let addr_b = MacAddress::Short(PanId(1), ShortAddress(2));
+
+        let mac_a = MockMac::new(addr_a);
+        let mac_b = mac_a.link(addr_b);
+
+        // Routers, so no solicitations are scheduled
+        let cfg = SixLoConfig {
+            nd: NdConfig {
+                router: true,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let mut sixlo_a = SixLo::<_, _, 127>::new(mac_a, addr_a, cfg.clone());
+        let mut sixlo_b = SixLo::<_, _, 127>::new(mac_b, addr_b, cfg.clone());
+
+        // Partially receive a datagram
+        sixlo_a.transmit(0, addr_b, &[0xaa; 200]).unwrap();
+        sixlo_a.tick(1).unwrap();
+        sixlo_b.tick(2).unwrap();
+        assert!(sixlo_b.next_deadline(2).is_some());
+
+        // Restarting the MAC flushes the reassembly buffer and applies the new address
+        let addr_b2 = MacAddress::Short(PanId(2), ShortAddress(2));
+        sixlo_b.mac_mut().restart(addr_b2);
+        sixlo_b.tick(3).unwrap();
+
+        assert_eq!(sixlo_b.next_deadline(3), None);
+        assert_eq!(sixlo_b.mac_addr, addr_b2);
+    }
+
+    /// Fragmented datagram outcomes are reported to both peers
+    #[test]
+    fn frag_events() {
+        let addr_a = MacAddress::Short(PanId(1), ShortAddress(1));
+        let addr_b = MacAddress::Short(PanId(1), ShortAddress(2));
+
+        let mac_a = MockMac::new(addr_a);
+        let mac_b = mac_a.link(addr_b);
+
+        // Routers, so no solicitations are scheduled
+        let cfg = SixLoConfig {
+            nd: NdConfig {
+                router: true,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let mut sixlo_a = SixLo::<_, _, 127>::new(mac_a, addr_a, cfg.clone());
+        let mut sixlo_b = SixLo::<_, _, 127>::new(mac_b, addr_b, cfg.clone());
+
+        sixlo_a.transmit(0, addr_b, &[0xaa; 200]).unwrap();
+        for t in 1..10 {
+            sixlo_a.tick(t).unwrap();
+            sixlo_b.tick(t).unwrap();
+        }
+
+        let event = |tag, peer, outcome| Some(FragEvent { tag, peer, outcome });
+        assert_eq!(
+            sixlo_a.poll_event(),
+            event(0, addr_b, FragOutcome::TxComplete)
+        );
+        assert_eq!(
+            sixlo_b.poll_event(),
+            event(0, addr_a, FragOutcome::RxComplete)
+        );
+        assert_eq!(sixlo_b.poll_event(), None);
+
+        // Partially received datagrams are reported on reassembly timeout
+        sixlo_a.transmit(10, addr_b, &[0xaa; 200]).unwrap();
+        sixlo_a.tick(10).unwrap();
+        sixlo_b.tick(10).unwrap();
+        assert_eq!(sixlo_b.poll_event(), None);
+
+        let t = 11 + cfg.frag.frag_rx_timeout_ms;
+        sixlo_b.tick(t).unwrap();
+        assert_eq!(
+            sixlo_b.poll_event(),
+            event(1, addr_a, FragOutcome::RxTimeout)
+        );
+        assert_eq!(sixlo_b.stats().rx_timeouts, 1);
+    }
+
+    /// UDP datagrams are exchanged with compressed headers, restoring elided checksums
+    #[test]
+    fn udp_transfer() {
+        let addr_a = MacAddress::Short(PanId(1), ShortAddress(1));
+        let addr_b = MacAddress::Short(PanId(1), ShortAddress(2));
+
+        let mac_a = MockMac::new(addr_a);
+        let mac_b = mac_a.link(addr_b);
+
+        // Routers, so no solicitations are scheduled
+        let cfg = SixLoConfig {
+            nd: NdConfig {
+                router: true,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let cfg_elide = SixLoConfig {
+            elide_udp_checksum: true,
+            ..cfg.clone()
+        };
+        let mut sixlo_a = SixLo::<_, _, 127>::new(mac_a, addr_a, cfg_elide);
+        let mut sixlo_b = SixLo::<_, _, 127>::new(mac_b, addr_b, cfg);
+
+        let (ip_a, ip_b) = (sixlo_a.link_local_addr(), sixlo_b.link_local_addr());
+        let mut buff = [0u8; 256];
+
+        // Short datagrams are sent unfragmented, larger datagrams carry
+        // the UDP header in the first fragment
+        let short = [0xab; 16];
+        let long: std::vec::Vec<u8> = (0..200).map(|v| v as u8).collect();
+
+        for (t, data) in [(0, &short[..]), (20, &long[..])].iter() {
+            let udp = UdpNhcHeader::new(0xf0b1, 5683);
+            let expected = UdpNhcHeader {
+                checksum: Some(udp.compute_checksum(&ip_a, &ip_b, data)),
+                ..udp
+            };
+
+            // A elides checksums, these are recomputed at B
+            sixlo_a
+                .transmit_udp(*t, ip_b.clone(), 0xf0b1, 5683, data)
+                .unwrap();
+
+            let mut rx = None;
+            for t in *t..*t + 20 {
+                sixlo_a.tick(t).unwrap();
+                sixlo_b.tick(t).unwrap();
+
+                if let Some(r) = sixlo_b.receive(t, &mut buff).unwrap() {
+                    rx = Some(r);
+                    break;
+                }
+            }
+
+            let (n, info, h) = rx.unwrap();
+            assert_eq!(&buff[..n], *data);
+            assert_eq!(info.source, addr_a);
+            assert_eq!(
+                h.iphc.map(|ip| (ip.src, ip.dst)),
+                Some((ip_a.clone(), ip_b.clone()))
+            );
+            assert_eq!(h.udp, Some(expected));
+
+            // B carries checksums inline
+            sixlo_b
+                .transmit_udp(*t, ip_a.clone(), 5683, 0xf0b1, data)
+                .unwrap();
+
+            let mut rx = None;
+            for t in *t..*t + 20 {
+                sixlo_b.tick(t).unwrap();
+                sixlo_a.tick(t).unwrap();
+
+                if let Some((n, _info, h)) = sixlo_a.receive(t, &mut buff).unwrap() {
+                    rx = Some((n, h.udp));
+                    break;
+                }
+            }
+
+            let (n, udp) = rx.unwrap();
+            assert_eq!(&buff[..n], *data);
+            let udp = udp.unwrap();
+            assert_eq!((udp.src_port, udp.dst_port), (5683, 0xf0b1));
+            assert!(udp.checksum.is_some());
+        }
+    }
+
+    /// Toy link-layer address, standing in for that of a non-802.15.4 MAC
+    #[derive(Copy, Clone, PartialEq, Debug)]
+    struct NodeId(u16);
+
+    impl From<NodeId> for Eui64 {
+        fn from(a: NodeId) -> Self {
+            Eui64::short(a.0)
+        }
+    }
+
+    impl AddressOps for NodeId {
+        fn broadcast() -> Self {
+            NodeId(0xffff)
+        }
+
+        fn is_broadcast(&self) -> bool {
+            self.0 == 0xffff
+        }
+
+        fn from_iid(iid: Eui64, _local: &Self) -> Self {
+            NodeId(iid.to_short().unwrap_or(iid.0 as u16))
+        }
+    }
+
+    /// Fragmented datagrams are exchanged over a MAC with non-802.15.4 addresses
+    #[test]
+    fn generic_address() {
+        let addr_a = NodeId(1);
+        let addr_b = NodeId(2);
+
+        let mac_a = MockMac::new(addr_a);
+        let mac_b = mac_a.link(addr_b);
+
+        let mut sixlo_a = SixLo::<_, _, 127>::new(mac_a, addr_a, SixLoConfig::default());
+        let mut sixlo_b = SixLo::<_, _, 127>::new(mac_b, addr_b, SixLoConfig::default());
+
+        let tx: std::vec::Vec<u8> = (0..200).map(|v| v as u8).collect();
+        sixlo_a.transmit(0, addr_b, &tx).unwrap();
+
+        let mut buff = [0u8; 256];
+        let mut rx = None;
+        for t in 0..20 {
+            sixlo_a.tick(t).unwrap();
+            sixlo_b.tick(t).unwrap();
+
+            if let Some((n, info, _h)) = sixlo_b.receive(t, &mut buff).unwrap() {
+                rx = Some((n, info.source));
+                break;
+            }
+        }
+
+        assert_eq!(rx, Some((tx.len(), addr_a)));
+        assert_eq!(&buff[..tx.len()], &tx[..]);
+
+        // IPv6 destinations resolve to link-layer addresses via the interface identifier
+        assert_eq!(sixlo_a.resolve(&sixlo_b.link_local_addr()), addr_b);
+        assert_eq!(sixlo_a.resolve(&ALL_NODES), NodeId::broadcast());
+    }
+
+    #[test]
+    fn frag_size() {
+        let addr_a = MacAddress::Short(PanId(1), ShortAddress(1));
+        let addr_b = MacAddress::Short(PanId(1), ShortAddress(2));
+
+        // Frames are collected directly via a linked MAC
+        let mac_a = MockMac::new(addr_a);
+        let mut mac_b = mac_a.link(addr_b);
+
+        let cfg = SixLoConfig {
+            nd: NdConfig {
+                router: true,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let mut sixlo_a = SixLo::<_, _, 127>::new(mac_a, addr_a, cfg);
+
+        let ip_b = V6Addr::link_local(&addr_b);
+        let tx: std::vec::Vec<u8> = (0..400).map(|i| i as u8).collect();
+        sixlo_a.transmit_udp(0, ip_b, 0xf0b1, 5683, &tx).unwrap();
+
+        // Sizes and offsets are those of the uncompressed IPv6 and UDP datagram
+        let size = IPV6_HEADER_LEN + UDP_HEADER_LEN + tx.len();
+
+        let mut buff = [0u8; 256];
+        let (mut sizes, mut data) = (std::vec::Vec::new(), std::vec::Vec::new());
+        for t in 0..20 {
+            sixlo_a.tick(t).unwrap();
+
+            while let Some((n, _info)) = mac_b.receive(&mut buff).unwrap() {
+                let (h, hdr_len) = Header::decode(&buff[..n], &addr_a, &addr_b).unwrap();
+                let fh = h.frag.as_ref().unwrap();
+                assert_eq!(fh.datagram_size as usize, size);
+
+                // Fragments including headers fit the MAC payload
+                assert!(n <= 127);
+
+                // The first fragment covers the headers it carries compressed
+                let len = h.uncompressed_len() + n - hdr_len;
+
+                // Offsets and non-final fragments are in units of 8 bytes
+                let offset = fh.datagram_offset.unwrap_or(0) as usize * 8;
+                if offset + len < size {
+                    assert_eq!(len % 8, 0);
+                }
+                assert_eq!(offset, sizes.iter().sum::<usize>());
+
+                sizes.push(len);
+                data.push(n - hdr_len);
+            }
+        }
+
+        // The first fragment carries larger (IPHC and UDP) headers than later fragments
+        assert_eq!(sizes.iter().sum::<usize>(), size);
+        assert_eq!(data.iter().sum::<usize>(), tx.len());
+        assert!(data[0] < data[1]);
+        assert_eq!(sixlo_a.frag_stats().tx_datagrams, 1);
+    }
+
+    #[test]
+    fn frag_ipv6_uncompressed() {
+        let addr_a = MacAddress::Short(PanId(1), ShortAddress(1));
+        let addr_b = MacAddress::Short(PanId(1), ShortAddress(2));
+
+        // Frames are collected directly via a linked MAC
+        let mac_a = MockMac::new(addr_a);
+        let mut mac_b = mac_a.link(addr_b);
+        let mut sixlo_a = SixLo::<_, _, 127>::new(mac_a.clone(), addr_a, SixLoConfig::default());
+
+        // MTU sized packets following the dispatch fit the reassembly buffer
+        let packet: std::vec::Vec<u8> = (0..IPV6_MTU).map(|i| i as u8).collect();
+        let mut tx = std::vec![DispatchBits::Ipv6 as u8];
+        tx.extend_from_slice(&packet);
+        sixlo_a.transmit(0, addr_b, &tx).unwrap();
+
+        let mut buff = [0u8; 256];
+        let mut frames = std::vec::Vec::new();
+        for t in 0..50 {
+            sixlo_a.tick(t).unwrap();
+
+            while let Some((n, _info)) = mac_b.receive(&mut buff).unwrap() {
+                let (h, hdr_len) = Header::decode(&buff[..n], &addr_a, &addr_b).unwrap();
+
+                // Skip neighbour discovery messages
+                let fh = match h.frag.as_ref() {
+                    Some(fh) => fh,
+                    None => continue,
+                };
+
+                // Fragments are sized and offset by the IPv6 packet, with the
+                // dispatch carried in the first fragment
+                assert_eq!(fh.datagram_size as usize, IPV6_MTU);
+                assert_eq!(h.ipv6, fh.datagram_offset.is_none());
+                if fh.datagram_offset.is_none() {
+                    assert_eq!((n - hdr_len) % 8, 0);
+                }
+
+                frames.push(buff[..n].to_vec());
+            }
+        }
+        assert!(frames.len() > 1);
+
+        // Fragments are reassembled to the original packet
+        let mut sixlo_b = SixLo::<_, _, 127>::new(mac_b, addr_b, SixLoConfig::default());
+        let mut inject = mac_a.link(addr_a);
+        for f in &frames {
+            inject.transmit(addr_b, f, false).unwrap();
+        }
+
+        let mut rx = [0u8; IPV6_MTU];
+        let mut received = None;
+        for t in 50..100 {
+            sixlo_b.tick(t).unwrap();
+            if let Some((n, _info)) = sixlo_b.receive_ipv6_packet(t, &mut rx).unwrap() {
+                received = Some(n);
+                break;
+            }
+        }
+
+        assert_eq!(received, Some(IPV6_MTU));
+        assert_eq!(&rx[..], &packet[..]);
+    }
 }
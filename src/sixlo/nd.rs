@@ -0,0 +1,1389 @@
+//! 6LoWPAN Neighbour Discovery
+//!
+//! Router discovery and address registration per [RFC6775](https://tools.ietf.org/html/rfc6775).
+//! Hosts solicit routers on startup, learning the prefix and router address from
+//! router advertisements, then register their address with the router using the
+//! Address Registration Option (ARO). Routers answer solicitations and registrations,
+//! maintaining a neighbour cache of registered hosts.
+//
+// https://github.com/rust-iot/rust-lpwan
+// Copyright 2021 Ryan Kurte
+
+use byteorder::{BigEndian, ByteOrder};
+use heapless::Vec;
+use ieee802154::mac::{Address as MacAddress, DecodeError, ExtendedAddress};
+
+use crate::log::{debug, info, warn, FmtError};
+use crate::{Mac, Ts};
+
+use super::headers::{Context, ContextTable, Eui64, Header, IphcHeader, V6Addr};
+use super::{AddressOps, SixLo, SixLoError};
+
+/// ICMPv6 next header value
+pub const ICMPV6: u8 = 58;
+
+/// Hop limit for neighbour discovery messages, which must not be forwarded
+pub const ND_HOP_LIMIT: u8 = 255;
+
+/// All-nodes link-local multicast address (`ff02::1`)
+pub const ALL_NODES: V6Addr = V6Addr([0xff, 0x02, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0x01]);
+
+/// All-routers link-local multicast address (`ff02::2`)
+pub const ALL_ROUTERS: V6Addr = V6Addr([0xff, 0x02, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0x02]);
+
+/// Maximum number of options decoded per message, further options are ignored
+pub const MAX_ND_OPTIONS: usize = 4;
+
+/// Maximum encoded neighbour discovery message length
+pub const MAX_ND_LEN: usize = 128;
+
+/// Default neighbour cache size
+pub const DEFAULT_MAX_NEIGHBOURS: usize = 8;
+
+const ICMP_RS: u8 = 133;
+const ICMP_RA: u8 = 134;
+const ICMP_NS: u8 = 135;
+const ICMP_NA: u8 = 136;
+
+const OPT_SLLAO: u8 = 1;
+const OPT_TLLAO: u8 = 2;
+const OPT_PIO: u8 = 3;
+const OPT_ARO: u8 = 33;
+const OPT_6CO: u8 = 34;
+
+/// Prefix information on-link flag
+pub const PIO_FLAG_ON_LINK: u8 = 0x80;
+/// Prefix information autonomous address configuration flag
+pub const PIO_FLAG_AUTONOMOUS: u8 = 0x40;
+
+/// Neighbour advertisement router flag
+pub const NA_FLAG_ROUTER: u8 = 0x80;
+/// Neighbour advertisement solicited flag
+pub const NA_FLAG_SOLICITED: u8 = 0x40;
+/// Neighbour advertisement override flag
+pub const NA_FLAG_OVERRIDE: u8 = 0x20;
+
+/// 6LoWPAN context option compression flag
+const CONTEXT_FLAG_COMPRESSION: u8 = 0x10;
+
+/// Address registration status
+#[derive(Debug, Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum AroStatus {
+    Success = 0,
+    Duplicate = 1,
+    CacheFull = 2,
+}
+
+/// Neighbour discovery configuration
+#[derive(Clone, PartialEq, Debug)]
+pub struct NdConfig {
+    /// Act as a (border) router, answering solicitations and registrations
+    pub router: bool,
+    /// Prefix (and prefix length) advertised by routers
+    pub prefix: Option<(V6Addr, u8)>,
+    /// Requested address registration lifetime in minutes
+    pub registration_lifetime: u16,
+    /// Advertised router lifetime in seconds
+    pub router_lifetime: u16,
+    /// Interval between router solicitations
+    pub rs_interval_ms: Ts,
+    /// Interval between unanswered registration attempts
+    pub ns_retry_ms: Ts,
+}
+
+impl Default for NdConfig {
+    fn default() -> Self {
+        Self {
+            router: false,
+            prefix: None,
+            registration_lifetime: 60,
+            router_lifetime: 1800,
+            rs_interval_ms: 10_000,
+            ns_retry_ms: 10_000,
+        }
+    }
+}
+
+/// Neighbour discovery (ICMPv6) message
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum NdMessage {
+    RouterSolicitation,
+    RouterAdvertisement {
+        hop_limit: u8,
+        flags: u8,
+        /// Router lifetime in seconds
+        router_lifetime: u16,
+        reachable_time: u32,
+        retrans_timer: u32,
+    },
+    NeighbourSolicitation {
+        target: V6Addr,
+    },
+    NeighbourAdvertisement {
+        flags: u8,
+        target: V6Addr,
+    },
+}
+
+/// Neighbour discovery options.
+///
+/// Link-layer addresses are held as interface identifiers, carried as 16-bit short
+/// or 64-bit extended addresses as for mesh headers (see [`Eui64::short`])
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum NdOption {
+    /// Source link-layer address (SLLAO)
+    SourceLinkLayer(Eui64),
+    /// Target link-layer address (TLLAO)
+    TargetLinkLayer(Eui64),
+    /// Prefix information (PIO), lifetimes in seconds
+    PrefixInformation {
+        prefix_len: u8,
+        flags: u8,
+        valid_lifetime: u32,
+        preferred_lifetime: u32,
+        prefix: V6Addr,
+    },
+    /// Address registration (ARO), lifetime in minutes
+    AddressRegistration {
+        status: u8,
+        lifetime: u16,
+        eui64: ExtendedAddress,
+    },
+    /// 6LoWPAN context (6CO), lifetime in minutes
+    Context {
+        context_len: u8,
+        compression: bool,
+        cid: u8,
+        lifetime: u16,
+        prefix: V6Addr,
+    },
+}
+
+impl NdOption {
+    /// Decode an option, returning `None` for unsupported option types.
+    /// The buffer must contain exactly one option (as sized by the length field)
+    pub fn decode(o: &[u8]) -> Result<Option<Self>, DecodeError> {
+        let opt = match o[0] {
+            OPT_SLLAO => NdOption::SourceLinkLayer(decode_ll(o)?),
+            OPT_TLLAO => NdOption::TargetLinkLayer(decode_ll(o)?),
+            OPT_PIO if o.len() == 32 => NdOption::PrefixInformation {
+                prefix_len: o[2],
+                flags: o[3],
+                valid_lifetime: BigEndian::read_u32(&o[4..]),
+                preferred_lifetime: BigEndian::read_u32(&o[8..]),
+                prefix: v6(&o[16..32]),
+            },
+            OPT_ARO if o.len() == 16 => NdOption::AddressRegistration {
+                status: o[2],
+                lifetime: BigEndian::read_u16(&o[6..]),
+                eui64: ExtendedAddress(BigEndian::read_u64(&o[8..])),
+            },
+            OPT_6CO if o.len() == 16 || o.len() == 24 => {
+                let mut prefix = [0u8; 16];
+                prefix[..o.len() - 8].copy_from_slice(&o[8..]);
+
+                NdOption::Context {
+                    context_len: o[2],
+                    compression: o[3] & CONTEXT_FLAG_COMPRESSION != 0,
+                    cid: o[3] & 0x0f,
+                    lifetime: BigEndian::read_u16(&o[6..]),
+                    prefix: V6Addr(prefix),
+                }
+            }
+            OPT_PIO | OPT_ARO | OPT_6CO => return Err(DecodeError::InvalidValue),
+            _ => return Ok(None),
+        };
+
+        Ok(Some(opt))
+    }
+
+    /// Encode an option, returning the encoded length
+    pub fn encode(&self, buff: &mut [u8]) -> usize {
+        let len = self.encoded_len();
+        if len == 0 {
+            return 0;
+        }
+
+        for b in buff[..len].iter_mut() {
+            *b = 0;
+        }
+        buff[1] = (len / 8) as u8;
+
+        match self {
+            NdOption::SourceLinkLayer(a) => {
+                buff[0] = OPT_SLLAO;
+                encode_ll(a, &mut buff[2..]);
+            }
+            NdOption::TargetLinkLayer(a) => {
+                buff[0] = OPT_TLLAO;
+                encode_ll(a, &mut buff[2..]);
+            }
+            NdOption::PrefixInformation {
+                prefix_len,
+                flags,
+                valid_lifetime,
+                preferred_lifetime,
+                prefix,
+            } => {
+                buff[0] = OPT_PIO;
+                buff[2] = *prefix_len;
+                buff[3] = *flags;
+                BigEndian::write_u32(&mut buff[4..], *valid_lifetime);
+                BigEndian::write_u32(&mut buff[8..], *preferred_lifetime);
+                buff[16..32].copy_from_slice(&prefix.0);
+            }
+            NdOption::AddressRegistration {
+                status,
+                lifetime,
+                eui64,
+            } => {
+                buff[0] = OPT_ARO;
+                buff[2] = *status;
+                BigEndian::write_u16(&mut buff[6..], *lifetime);
+                BigEndian::write_u64(&mut buff[8..], eui64.0);
+            }
+            NdOption::Context {
+                context_len,
+                compression,
+                cid,
+                lifetime,
+                prefix,
+            } => {
+                buff[0] = OPT_6CO;
+                buff[2] = *context_len;
+                buff[3] = cid & 0x0f;
+                if *compression {
+                    buff[3] |= CONTEXT_FLAG_COMPRESSION;
+                }
+                BigEndian::write_u16(&mut buff[6..], *lifetime);
+                buff[8..len].copy_from_slice(&prefix.0[..len - 8]);
+            }
+        }
+
+        len
+    }
+
+    /// Compute encoded option length (a multiple of 8 bytes)
+    fn encoded_len(&self) -> usize {
+        match self {
+            NdOption::SourceLinkLayer(a) | NdOption::TargetLinkLayer(a) => match a.to_short() {
+                Some(_) => 8,
+                None => 16,
+            },
+            NdOption::PrefixInformation { .. } => 32,
+            NdOption::AddressRegistration { .. } => 16,
+            // Contexts of up to 64 bits carry only the first half of the prefix
+            NdOption::Context { context_len, .. } if *context_len <= 64 => 16,
+            NdOption::Context { .. } => 24,
+        }
+    }
+}
+
+/// Neighbour discovery packet, comprising a message and options
+#[derive(Debug, Clone, PartialEq)]
+pub struct NdPacket {
+    pub message: NdMessage,
+    pub options: Vec<NdOption, MAX_ND_OPTIONS>,
+}
+
+impl NdPacket {
+    /// Create a new packet with no options
+    pub fn new(message: NdMessage) -> Self {
+        Self {
+            message,
+            options: Vec::new(),
+        }
+    }
+
+    /// Decode a packet from an ICMPv6 payload, checking the checksum
+    /// against the provided IPv6 source and destination addresses
+    pub fn decode(buff: &[u8], src: &V6Addr, dst: &V6Addr) -> Result<Self, DecodeError> {
+        if buff.len() < 8 {
+            return Err(DecodeError::NotEnoughBytes);
+        }
+
+        // Checksum over a valid message (including the checksum field) is zero
+        if checksum(src, dst, buff) != 0 || buff[1] != 0 {
+            return Err(DecodeError::InvalidValue);
+        }
+
+        let (message, mut offset) = match buff[0] {
+            ICMP_RS => (NdMessage::RouterSolicitation, 8),
+            ICMP_RA if buff.len() >= 16 => (
+                NdMessage::RouterAdvertisement {
+                    hop_limit: buff[4],
+                    flags: buff[5],
+                    router_lifetime: BigEndian::read_u16(&buff[6..]),
+                    reachable_time: BigEndian::read_u32(&buff[8..]),
+                    retrans_timer: BigEndian::read_u32(&buff[12..]),
+                },
+                16,
+            ),
+            ICMP_NS if buff.len() >= 24 => (
+                NdMessage::NeighbourSolicitation {
+                    target: v6(&buff[8..24]),
+                },
+                24,
+            ),
+            ICMP_NA if buff.len() >= 24 => (
+                NdMessage::NeighbourAdvertisement {
+                    flags: buff[4],
+                    target: v6(&buff[8..24]),
+                },
+                24,
+            ),
+            ICMP_RA | ICMP_NS | ICMP_NA => return Err(DecodeError::NotEnoughBytes),
+            _ => return Err(DecodeError::InvalidValue),
+        };
+
+        let mut options = Vec::new();
+        while offset < buff.len() {
+            let len = match buff.get(offset + 1) {
+                Some(0) => return Err(DecodeError::InvalidValue),
+                Some(l) => *l as usize * 8,
+                None => return Err(DecodeError::NotEnoughBytes),
+            };
+
+            let o = buff
+                .get(offset..offset + len)
+                .ok_or(DecodeError::NotEnoughBytes)?;
+
+            if let Some(opt) = NdOption::decode(o)? {
+                let _ = options.push(opt);
+            }
+
+            offset += len;
+        }
+
+        Ok(Self { message, options })
+    }
+
+    /// Encode a packet to an ICMPv6 payload, computing the checksum
+    /// for the provided IPv6 source and destination addresses
+    pub fn encode(&self, src: &V6Addr, dst: &V6Addr, buff: &mut [u8]) -> usize {
+        for b in buff[..8].iter_mut() {
+            *b = 0;
+        }
+
+        let mut n = match &self.message {
+            NdMessage::RouterSolicitation => {
+                buff[0] = ICMP_RS;
+                8
+            }
+            NdMessage::RouterAdvertisement {
+                hop_limit,
+                flags,
+                router_lifetime,
+                reachable_time,
+                retrans_timer,
+            } => {
+                buff[0] = ICMP_RA;
+                buff[4] = *hop_limit;
+                buff[5] = *flags;
+                BigEndian::write_u16(&mut buff[6..], *router_lifetime);
+                BigEndian::write_u32(&mut buff[8..], *reachable_time);
+                BigEndian::write_u32(&mut buff[12..], *retrans_timer);
+                16
+            }
+            NdMessage::NeighbourSolicitation { target } => {
+                buff[0] = ICMP_NS;
+                buff[8..24].copy_from_slice(&target.0);
+                24
+            }
+            NdMessage::NeighbourAdvertisement { flags, target } => {
+                buff[0] = ICMP_NA;
+                buff[4] = *flags;
+                buff[8..24].copy_from_slice(&target.0);
+                24
+            }
+        };
+
+        for o in self.options.iter() {
+            n += o.encode(&mut buff[n..]);
+        }
+
+        let c = checksum(src, dst, &buff[..n]);
+        BigEndian::write_u16(&mut buff[2..], c);
+
+        n
+    }
+
+    /// Fetch the source link-layer address option, if present
+    pub fn source_link_layer(&self) -> Option<Eui64> {
+        self.options.iter().find_map(|o| match o {
+            NdOption::SourceLinkLayer(a) => Some(*a),
+            _ => None,
+        })
+    }
+
+    /// Fetch the address registration option as (status, lifetime, EUI-64), if present
+    pub fn registration(&self) -> Option<(u8, u16, ExtendedAddress)> {
+        self.options.iter().find_map(|o| match o {
+            NdOption::AddressRegistration {
+                status,
+                lifetime,
+                eui64,
+            } => Some((*status, *lifetime, *eui64)),
+            _ => None,
+        })
+    }
+}
+
+/// Check whether a datagram contains a neighbour discovery message
+pub fn is_nd(ip: &IphcHeader, data: &[u8]) -> bool {
+    ip.next_header == ICMPV6 && matches!(data.first(), Some(t) if (ICMP_RS..=ICMP_NA).contains(t))
+}
+
+/// Compute the ICMPv6 checksum over the IPv6 pseudo-header and message
+pub fn checksum(src: &V6Addr, dst: &V6Addr, data: &[u8]) -> u16 {
+    let mut sum: u32 = 0;
+    let mut add = |b: &[u8]| {
+        for c in b.chunks(2) {
+            sum += (c[0] as u32) << 8 | c.get(1).copied().unwrap_or(0) as u32;
+        }
+    };
+
+    add(&src.0);
+    add(&dst.0);
+    add(&(data.len() as u32).to_be_bytes());
+    add(&[0, 0, 0, ICMPV6]);
+    add(data);
+
+    while sum >> 16 != 0 {
+        sum = (sum & 0xffff) + (sum >> 16);
+    }
+
+    !(sum as u16)
+}
+
+fn v6(b: &[u8]) -> V6Addr {
+    let mut a = [0u8; 16];
+    a.copy_from_slice(b);
+    V6Addr(a)
+}
+
+/// Decode a link-layer address option per [RFC4944 Section 8](https://tools.ietf.org/html/rfc4944#section-8)
+fn decode_ll(o: &[u8]) -> Result<Eui64, DecodeError> {
+    match o.len() {
+        8 => Ok(Eui64::short(BigEndian::read_u16(&o[2..]))),
+        16 => Ok(Eui64::extended(BigEndian::read_u64(&o[2..]))),
+        _ => Err(DecodeError::InvalidValue),
+    }
+}
+
+fn encode_ll(a: &Eui64, buff: &mut [u8]) {
+    match a.to_short() {
+        Some(s) => BigEndian::write_u16(buff, s),
+        None => BigEndian::write_u64(buff, a.to_extended()),
+    }
+}
+
+/// Neighbour cache entry
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Neighbour<A = MacAddress> {
+    /// Neighbour IPv6 address
+    pub ip: V6Addr,
+    /// Neighbour link-layer address
+    pub mac: A,
+    /// EUI-64 for neighbours registered via ARO
+    pub eui64: Option<ExtendedAddress>,
+    /// Whether this neighbour is a router
+    pub router: bool,
+    /// Entry expiry time
+    pub expires: Ts,
+}
+
+/// Neighbour cache, mapping IPv6 addresses to link-layer addresses
+#[derive(Debug, Clone, PartialEq)]
+pub struct NeighbourTable<A = MacAddress, const N: usize = DEFAULT_MAX_NEIGHBOURS> {
+    entries: Vec<Neighbour<A>, N>,
+}
+
+impl<A, const N: usize> Default for NeighbourTable<A, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<A, const N: usize> NeighbourTable<A, N> {
+    /// Create a new (empty) neighbour cache
+    pub fn new() -> Self {
+        Self {
+            entries: Vec::new(),
+        }
+    }
+
+    /// Add or update a neighbour, returning the entry if the cache is full
+    pub fn update(&mut self, n: Neighbour<A>) -> Result<(), Neighbour<A>> {
+        match self.entries.iter_mut().find(|e| e.ip == n.ip) {
+            Some(e) => {
+                *e = n;
+                Ok(())
+            }
+            None => self.entries.push(n),
+        }
+    }
+
+    /// Lookup a neighbour by IPv6 address
+    pub fn lookup(&self, ip: &V6Addr) -> Option<&Neighbour<A>> {
+        self.entries.iter().find(|e| &e.ip == ip)
+    }
+
+    /// Fetch the default router, if known
+    pub fn router(&self) -> Option<&Neighbour<A>> {
+        self.entries.iter().find(|e| e.router)
+    }
+
+    /// Remove a neighbour by IPv6 address
+    pub fn remove(&mut self, ip: &V6Addr) -> Option<Neighbour<A>> {
+        let i = self.entries.iter().position(|e| &e.ip == ip)?;
+        Some(self.entries.swap_remove(i))
+    }
+
+    /// Remove a neighbour with an expiry prior to the provided time, if any
+    pub fn expire(&mut self, now: Ts) -> Option<Neighbour<A>> {
+        let i = self.entries.iter().position(|e| e.expires < now)?;
+        Some(self.entries.swap_remove(i))
+    }
+
+    /// Fetch the earliest neighbour expiry
+    pub fn next_expiry(&self) -> Option<Ts> {
+        self.entries.iter().map(|e| e.expires).min()
+    }
+
+    /// Iterate over neighbours
+    pub fn iter(&self) -> impl Iterator<Item = &Neighbour<A>> {
+        self.entries.iter()
+    }
+
+    /// Fetch the number of neighbours
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+}
+
+/// Neighbour discovery state
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct NdState<A> {
+    neighbours: NeighbourTable<A>,
+    /// Compression contexts, learned from router advertisements (or installed by the
+    /// application) and used for IPHC address compression
+    pub(crate) contexts: ContextTable,
+    /// Prefix learned from router advertisements (or configured for routers)
+    prefix: Option<(V6Addr, u8)>,
+    /// Address registration expiry (hosts only)
+    registered: Option<Ts>,
+    /// Time of the next router solicitation or registration (hosts only)
+    next_tx: Ts,
+}
+
+impl<A> NdState<A> {
+    pub(crate) fn new(cfg: &NdConfig) -> Self {
+        Self {
+            neighbours: NeighbourTable::new(),
+            contexts: ContextTable::new(),
+            prefix: cfg.prefix.clone().filter(|_| cfg.router),
+            registered: None,
+            next_tx: 0,
+        }
+    }
+}
+
+impl<M, A, const MAX_PAYLOAD: usize, const TX_SLOTS: usize, const RX_SLOTS: usize>
+    SixLo<M, A, MAX_PAYLOAD, TX_SLOTS, RX_SLOTS>
+where
+    M: Mac<A>,
+    A: AddressOps,
+    <M as Mac<A>>::Error: FmtError,
+{
+    /// Fetch the neighbour cache
+    pub fn neighbours(&self) -> &NeighbourTable<A> {
+        &self.nd.neighbours
+    }
+
+    /// Fetch the prefix learned from router advertisements (or configured for routers)
+    pub fn prefix(&self) -> Option<&(V6Addr, u8)> {
+        self.nd.prefix.as_ref()
+    }
+
+    /// Fetch the compression context table
+    pub fn contexts(&self) -> &ContextTable {
+        &self.nd.contexts
+    }
+
+    /// Fetch the compression context table for modification, contexts installed
+    /// by routers are advertised to hosts via the 6LoWPAN context option (6CO)
+    pub fn contexts_mut(&mut self) -> &mut ContextTable {
+        &mut self.nd.contexts
+    }
+
+    /// Fetch the default router learned from router advertisements (hosts only)
+    pub fn default_router(&self) -> Option<&Neighbour<A>> {
+        self.nd.neighbours.router()
+    }
+
+    /// Act as a router advertising the provided prefix (and prefix length),
+    /// answering solicitations and registrations in place of soliciting routers
+    pub fn set_router(&mut self, prefix: (V6Addr, u8)) {
+        info!("Acting as router for prefix {:?}/{}", prefix.0, prefix.1);
+
+        self.cfg.nd.router = true;
+        self.cfg.nd.prefix = Some(prefix.clone());
+        self.nd.prefix = Some(prefix);
+        self.nd.registered = None;
+
+        // Routers do not register with (or route via) other routers
+        while let Some(ip) = self.nd.neighbours.router().map(|r| r.ip.clone()) {
+            self.nd.neighbours.remove(&ip);
+        }
+    }
+
+    /// Check whether this node holds an address registration with a router
+    pub fn registered(&self) -> bool {
+        self.nd.registered.is_some()
+    }
+
+    /// Fetch the link-local address for this node
+    pub fn link_local_addr(&self) -> V6Addr {
+        V6Addr::link_local(&self.mac_addr)
+    }
+
+    /// Fetch the global address for this node, formed from the prefix
+    /// and link-local interface identifier
+    pub fn global_addr(&self) -> Option<V6Addr> {
+        let (prefix, _) = self.nd.prefix.as_ref()?;
+
+        let mut a = self.link_local_addr();
+        a.0[..8].copy_from_slice(&prefix.0[..8]);
+
+        Some(a)
+    }
+
+    /// Resolve the link-layer (next hop) address for an IPv6 destination.
+    ///
+    /// Multicast destinations map to the broadcast address, known neighbours to their
+    /// cached address, and other non link-local destinations to the default router,
+    /// falling back to the address derived from the interface identifier
+    pub fn resolve(&self, dst: &V6Addr) -> A {
+        if let Some(n) = self.nd.neighbours.lookup(dst) {
+            return n.mac.clone();
+        }
+
+        if !dst.is_multicast() && !dst.is_link_local() {
+            if let Some(r) = self.nd.neighbours.router() {
+                return r.mac.clone();
+            }
+        }
+
+        dst.link_layer(&self.mac_addr)
+    }
+
+    /// Update neighbour discovery, expiring neighbours and soliciting routers
+    /// or registering addresses as required
+    pub(crate) fn tick_nd(&mut self, now_ms: Ts) -> Result<(), SixLoError<<M as Mac<A>>::Error>> {
+        while let Some(n) = self.nd.neighbours.expire(now_ms) {
+            debug!("Neighbour {:?} ({:?}) expired", n.ip, n.mac);
+
+            // Registrations are lost with the router
+            if n.router {
+                self.nd.registered = None;
+                self.nd.next_tx = now_ms;
+            }
+        }
+
+        while let Some(c) = self.nd.contexts.expire(now_ms) {
+            debug!(
+                "Context {} ({:?}/{}) expired",
+                c.cid, c.prefix, c.prefix_len
+            );
+        }
+
+        if matches!(self.nd.registered, Some(t) if t < now_ms) {
+            warn!("Address registration expired");
+            self.nd.registered = None;
+        }
+
+        // Routers only respond to solicitations
+        if self.cfg.nd.router || now_ms < self.nd.next_tx {
+            return Ok(());
+        }
+
+        let (router_ip, router_mac) = match self.nd.neighbours.router() {
+            Some(r) => (r.ip.clone(), r.mac.clone()),
+            None => {
+                debug!("Sending router solicitation");
+                self.nd.next_tx = now_ms + self.cfg.nd.rs_interval_ms;
+
+                let mut p = NdPacket::new(NdMessage::RouterSolicitation);
+                let _ = p.options.push(NdOption::SourceLinkLayer(self.iid()));
+
+                let dest = ALL_ROUTERS.link_layer(&self.mac_addr);
+                return self.transmit_nd(now_ms, self.link_local_addr(), ALL_ROUTERS, dest, &p);
+            }
+        };
+
+        // Register (or refresh the registration of) our address with the router
+        let addr = self.global_addr().unwrap_or_else(|| self.link_local_addr());
+
+        debug!("Registering address {:?} with router {:?}", addr, router_ip);
+        self.nd.next_tx = now_ms + self.cfg.nd.ns_retry_ms;
+
+        let mut p = NdPacket::new(NdMessage::NeighbourSolicitation {
+            target: addr.clone(),
+        });
+        let _ = p.options.push(NdOption::AddressRegistration {
+            status: AroStatus::Success as u8,
+            lifetime: self.cfg.nd.registration_lifetime,
+            eui64: self.eui64(),
+        });
+        let _ = p.options.push(NdOption::SourceLinkLayer(self.iid()));
+
+        self.transmit_nd(now_ms, addr, router_ip, router_mac, &p)
+    }
+
+    /// Fetch the time of the next neighbour discovery operation
+    pub(crate) fn nd_deadline(&self) -> Option<Ts> {
+        let expiry = self.nd.neighbours.next_expiry().map(|t| t + 1);
+        let contexts = self.nd.contexts.next_expiry().map(|t| t + 1);
+        let registered = self.nd.registered.map(|t| t + 1);
+
+        // Routers only respond to solicitations
+        let next_tx = Some(self.nd.next_tx).filter(|_| !self.cfg.nd.router);
+
+        [expiry, contexts, registered, next_tx]
+            .iter()
+            .flatten()
+            .min()
+            .copied()
+    }
+
+    /// Handle a received neighbour discovery message
+    pub(crate) fn handle_nd(
+        &mut self,
+        now_ms: Ts,
+        source: A,
+        ip: &IphcHeader,
+        data: &[u8],
+    ) -> Result<(), SixLoError<<M as Mac<A>>::Error>> {
+        if ip.hop_limit != ND_HOP_LIMIT {
+            warn!("Dropped ND message from {:?}: invalid hop limit", source);
+            return Ok(());
+        }
+
+        let p = match NdPacket::decode(data, &ip.src, &ip.dst) {
+            Ok(p) => p,
+            Err(e) => {
+                warn!("Dropped ND message from {:?}: {:?}", source, e);
+                return Ok(());
+            }
+        };
+
+        debug!("Received ND {:?} from {:?}", p.message, ip.src);
+
+        // Prefer the advertised link-layer address over the frame source
+        let mac = p
+            .source_link_layer()
+            .map(|a| A::from_iid(a, &self.mac_addr))
+            .unwrap_or(source);
+
+        match (&p.message, self.cfg.nd.router) {
+            (NdMessage::RouterSolicitation, true) => self.advertise(now_ms, &ip.src, mac),
+            (
+                NdMessage::RouterAdvertisement {
+                    router_lifetime, ..
+                },
+                false,
+            ) => {
+                self.handle_ra(now_ms, ip.src.clone(), mac, *router_lifetime, &p);
+                Ok(())
+            }
+            (NdMessage::NeighbourSolicitation { target }, router) => match p.registration() {
+                Some((_, lifetime, eui64)) if router => {
+                    self.register(now_ms, ip.src.clone(), mac, lifetime, eui64)
+                }
+                // Registrations are only handled by routers
+                Some(_) => Ok(()),
+                None => self.handle_ns(now_ms, &ip.src, mac, target),
+            },
+            (NdMessage::NeighbourAdvertisement { target, .. }, false) => {
+                match p.registration() {
+                    Some((status, lifetime, _)) if status == AroStatus::Success as u8 => {
+                        info!("Registered address {:?} ({} min)", target, lifetime);
+
+                        let lifetime_ms = lifetime as Ts * 60_000;
+                        self.nd.registered = Some(now_ms + lifetime_ms);
+
+                        // Refresh prior to expiry
+                        self.nd.next_tx = now_ms + lifetime_ms * 3 / 4;
+                    }
+                    Some((status, _, _)) => {
+                        warn!("Address registration failed (status: {})", status);
+                    }
+                    None => (),
+                }
+                Ok(())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Learn the router, prefix, and compression contexts from a router advertisement
+    fn handle_ra(&mut self, now_ms: Ts, src: V6Addr, mac: A, router_lifetime: u16, p: &NdPacket) {
+        for o in p.options.iter() {
+            match o {
+                NdOption::PrefixInformation {
+                    prefix_len,
+                    flags,
+                    prefix,
+                    ..
+                } if flags & PIO_FLAG_AUTONOMOUS != 0 => {
+                    self.nd.prefix = Some((prefix.clone(), *prefix_len));
+                }
+                // Zero lifetime indicates the context is no longer valid
+                NdOption::Context {
+                    cid, lifetime: 0, ..
+                } => {
+                    self.nd.contexts.remove_context(*cid);
+                }
+                NdOption::Context {
+                    context_len,
+                    compression,
+                    cid,
+                    lifetime,
+                    prefix,
+                } => {
+                    let c = Context {
+                        cid: *cid,
+                        prefix: prefix.clone(),
+                        prefix_len: *context_len,
+                        compression: *compression,
+                        expires: Some(now_ms + *lifetime as Ts * 60_000),
+                    };
+                    if self.nd.contexts.add_context(c).is_err() {
+                        warn!("Ignoring invalid context {} from {:?}", cid, mac);
+                    }
+                }
+                _ => (),
+            }
+        }
+
+        // Zero lifetime indicates the router is no longer a default router
+        if router_lifetime == 0 {
+            self.nd.neighbours.remove(&src);
+            return;
+        }
+
+        let n = Neighbour {
+            ip: src,
+            mac: mac.clone(),
+            eui64: None,
+            router: true,
+            expires: now_ms + router_lifetime as Ts * 1000,
+        };
+        if self.nd.neighbours.update(n).is_err() {
+            warn!("Neighbour cache full, ignoring router {:?}", mac);
+            return;
+        }
+
+        // Register with new routers immediately
+        if self.nd.registered.is_none() {
+            self.nd.next_tx = now_ms;
+        }
+    }
+
+    /// Answer a router solicitation (routers only)
+    fn advertise(
+        &mut self,
+        now_ms: Ts,
+        dst: &V6Addr,
+        dest: A,
+    ) -> Result<(), SixLoError<<M as Mac<A>>::Error>> {
+        let cfg = &self.cfg.nd;
+
+        let mut p = NdPacket::new(NdMessage::RouterAdvertisement {
+            hop_limit: 0,
+            flags: 0,
+            router_lifetime: cfg.router_lifetime,
+            reachable_time: 0,
+            retrans_timer: 0,
+        });
+        let _ = p.options.push(NdOption::SourceLinkLayer(self.iid()));
+
+        if let Some((prefix, prefix_len)) = &cfg.prefix {
+            // Prefixes are not on-link in route-over networks (RFC6775 Section 5.4)
+            let _ = p.options.push(NdOption::PrefixInformation {
+                prefix_len: *prefix_len,
+                flags: PIO_FLAG_AUTONOMOUS,
+                valid_lifetime: u32::MAX,
+                preferred_lifetime: u32::MAX,
+                prefix: prefix.clone(),
+            });
+        }
+
+        // Advertise compression contexts while option space remains,
+        // with contexts that do not expire advertised for the router lifetime
+        for c in self.nd.contexts.iter() {
+            let lifetime = match c.expires {
+                Some(t) => (t.saturating_sub(now_ms) / 60_000).min(u16::MAX as Ts) as u16,
+                None => cfg.router_lifetime / 60,
+            };
+            let o = NdOption::Context {
+                context_len: c.prefix_len,
+                compression: c.compression,
+                cid: c.cid,
+                lifetime,
+                prefix: c.prefix.clone(),
+            };
+            if p.options.push(o).is_err() {
+                warn!("Unable to advertise context {}, options full", c.cid);
+                break;
+            }
+        }
+
+        // Solicitations from the unspecified address are answered via multicast
+        let dst = if dst == &V6Addr::UNSPECIFIED {
+            ALL_NODES
+        } else {
+            dst.clone()
+        };
+
+        self.transmit_nd(now_ms, self.link_local_addr(), dst, dest, &p)
+    }
+
+    /// Handle an address registration (routers only)
+    fn register(
+        &mut self,
+        now_ms: Ts,
+        ip: V6Addr,
+        mac: A,
+        lifetime: u16,
+        eui64: ExtendedAddress,
+    ) -> Result<(), SixLoError<<M as Mac<A>>::Error>> {
+        let status = match (lifetime, self.nd.neighbours.lookup(&ip)) {
+            // Zero lifetime removes existing registrations
+            (0, _) => {
+                self.nd.neighbours.remove(&ip);
+                AroStatus::Success
+            }
+            (_, Some(n)) if n.eui64.is_some() && n.eui64 != Some(eui64) => AroStatus::Duplicate,
+            _ => {
+                let n = Neighbour {
+                    ip: ip.clone(),
+                    mac: mac.clone(),
+                    eui64: Some(eui64),
+                    router: false,
+                    expires: now_ms + lifetime as Ts * 60_000,
+                };
+                match self.nd.neighbours.update(n) {
+                    Ok(_) => AroStatus::Success,
+                    Err(_) => AroStatus::CacheFull,
+                }
+            }
+        };
+
+        debug!("Registration for {:?} from {:?}: {:?}", ip, mac, status);
+
+        let mut p = NdPacket::new(NdMessage::NeighbourAdvertisement {
+            flags: NA_FLAG_ROUTER | NA_FLAG_SOLICITED,
+            target: ip.clone(),
+        });
+        let _ = p.options.push(NdOption::AddressRegistration {
+            status: status as u8,
+            lifetime,
+            eui64,
+        });
+
+        self.transmit_nd(now_ms, self.link_local_addr(), ip, mac, &p)
+    }
+
+    /// Answer a neighbour solicitation for one of our addresses
+    fn handle_ns(
+        &mut self,
+        now_ms: Ts,
+        src: &V6Addr,
+        mac: A,
+        target: &V6Addr,
+    ) -> Result<(), SixLoError<<M as Mac<A>>::Error>> {
+        if target != &self.link_local_addr() && self.global_addr().as_ref() != Some(target) {
+            return Ok(());
+        }
+
+        let mut flags = NA_FLAG_SOLICITED | NA_FLAG_OVERRIDE;
+        if self.cfg.nd.router {
+            flags |= NA_FLAG_ROUTER;
+        }
+
+        let mut p = NdPacket::new(NdMessage::NeighbourAdvertisement {
+            flags,
+            target: target.clone(),
+        });
+        let _ = p.options.push(NdOption::TargetLinkLayer(self.iid()));
+
+        let dst = if src == &V6Addr::UNSPECIFIED {
+            ALL_NODES
+        } else {
+            src.clone()
+        };
+
+        self.transmit_nd(now_ms, target.clone(), dst, mac, &p)
+    }
+
+    /// Encode and transmit a neighbour discovery message
+    fn transmit_nd(
+        &mut self,
+        now_ms: Ts,
+        src: V6Addr,
+        dst: V6Addr,
+        dest: A,
+        p: &NdPacket,
+    ) -> Result<(), SixLoError<<M as Mac<A>>::Error>> {
+        let mut buff = [0u8; MAX_ND_LEN];
+        let n = p.encode(&src, &dst, &mut buff);
+
+        let header = Header {
+            iphc: Some(IphcHeader::new(src, dst, ICMPV6, ND_HOP_LIMIT)),
+            ..Default::default()
+        };
+
+        self.transmit_header(now_ms, dest, header, &buff[..n])
+    }
+
+    /// Interface identifier for this node
+    fn iid(&self) -> Eui64 {
+        self.mac_addr.clone().into()
+    }
+
+    /// EUI-64 used for address registration, using the interface identifier
+    /// where only a short address is available
+    fn eui64(&self) -> ExtendedAddress {
+        let iid = self.iid();
+        match iid.to_short() {
+            Some(_) => ExtendedAddress(iid.0),
+            None => ExtendedAddress(iid.to_extended()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use ieee802154::mac::{AddressMode, PanId, ShortAddress};
+
+    use super::*;
+    use crate::sixlo::{mock::MockMac, SixLoConfig};
+
+    const PREFIX: V6Addr = V6Addr([0x20, 0x01, 0x0d, 0xb8, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+
+    fn ll(short: u16) -> V6Addr {
+        V6Addr::link_local(&MacAddress::Short(PanId(1), ShortAddress(short)))
+    }
+
+    #[test]
+    fn rs_sllao() {
+        let src = ll(2);
+
+        let mut p = NdPacket::new(NdMessage::RouterSolicitation);
+        p.options
+            .push(NdOption::SourceLinkLayer(Eui64::short(0x1234)))
+            .unwrap();
+
+        let mut buff = [0u8; MAX_ND_LEN];
+        let n = p.encode(&src, &ALL_ROUTERS, &mut buff);
+
+        assert_eq!(n, 16);
+        assert_eq!(&buff[..2], &[133, 0]);
+        assert_eq!(&buff[4..n], &[0, 0, 0, 0, 1, 1, 0x12, 0x34, 0, 0, 0, 0]);
+
+        let d = NdPacket::decode(&buff[..n], &src, &ALL_ROUTERS).unwrap();
+        assert_eq!(d, p);
+
+        // Checksums include the pseudo-header
+        assert_eq!(
+            NdPacket::decode(&buff[..n], &src, &ALL_NODES),
+            Err(DecodeError::InvalidValue)
+        );
+        buff[6] ^= 0x01;
+        assert_eq!(
+            NdPacket::decode(&buff[..n], &src, &ALL_ROUTERS),
+            Err(DecodeError::InvalidValue)
+        );
+    }
+
+    #[test]
+    fn ns_aro() {
+        let mut target = PREFIX.clone();
+        target.0[15] = 0x02;
+        let dst = ll(1);
+
+        let mut p = NdPacket::new(NdMessage::NeighbourSolicitation {
+            target: target.clone(),
+        });
+        p.options
+            .push(NdOption::AddressRegistration {
+                status: 0,
+                lifetime: 60,
+                eui64: ExtendedAddress(0x0011_2233_4455_6677),
+            })
+            .unwrap();
+        p.options
+            .push(NdOption::SourceLinkLayer(Eui64::extended(
+                0x0011_2233_4455_6677,
+            )))
+            .unwrap();
+
+        let mut buff = [0u8; MAX_ND_LEN];
+        let n = p.encode(&target, &dst, &mut buff);
+
+        assert_eq!(n, 24 + 16 + 16);
+        assert_eq!(buff[0], 135);
+        assert_eq!(&buff[8..24], &target.0);
+        assert_eq!(
+            &buff[24..40],
+            &[33, 2, 0, 0, 0, 0, 0, 60, 0x00, 0x11, 0x22, 0x33, 0x44, 0x55, 0x66, 0x77]
+        );
+        assert_eq!(
+            &buff[40..56],
+            &[1, 2, 0x00, 0x11, 0x22, 0x33, 0x44, 0x55, 0x66, 0x77, 0, 0, 0, 0, 0, 0]
+        );
+
+        let d = NdPacket::decode(&buff[..n], &target, &dst).unwrap();
+        assert_eq!(d, p);
+        assert_eq!(
+            d.registration(),
+            Some((0, 60, ExtendedAddress(0x0011_2233_4455_6677)))
+        );
+    }
+
+    #[test]
+    fn ra_pio_6co() {
+        let src = ll(1);
+        let dst = ll(2);
+
+        let mut p = NdPacket::new(NdMessage::RouterAdvertisement {
+            hop_limit: 0,
+            flags: 0,
+            router_lifetime: 1800,
+            reachable_time: 0,
+            retrans_timer: 0,
+        });
+        p.options
+            .push(NdOption::PrefixInformation {
+                prefix_len: 64,
+                flags: PIO_FLAG_AUTONOMOUS,
+                valid_lifetime: u32::MAX,
+                preferred_lifetime: u32::MAX,
+                prefix: PREFIX,
+            })
+            .unwrap();
+        p.options
+            .push(NdOption::Context {
+                context_len: 64,
+                compression: true,
+                cid: 1,
+                lifetime: 30,
+                prefix: PREFIX,
+            })
+            .unwrap();
+
+        let mut buff = [0u8; MAX_ND_LEN];
+        let n = p.encode(&src, &dst, &mut buff);
+
+        assert_eq!(n, 16 + 32 + 16);
+        assert_eq!(&buff[4..8], &[0, 0, 0x07, 0x08]);
+        assert_eq!(&buff[16..20], &[3, 4, 64, 0x40]);
+        assert_eq!(
+            &buff[48..64],
+            &[34, 2, 64, 0x11, 0, 0, 0, 30, 0x20, 0x01, 0x0d, 0xb8, 0, 0, 0, 0]
+        );
+
+        let d = NdPacket::decode(&buff[..n], &src, &dst).unwrap();
+        assert_eq!(d, p);
+    }
+
+    #[test]
+    fn neighbour_expiry() {
+        let mut t = NeighbourTable::<_, 2>::new();
+
+        let n = |short, router, expires| Neighbour {
+            ip: ll(short),
+            mac: MacAddress::Short(PanId(1), ShortAddress(short)),
+            eui64: None,
+            router,
+            expires,
+        };
+
+        t.update(n(1, true, 100)).unwrap();
+        t.update(n(2, false, 200)).unwrap();
+        assert_eq!(t.update(n(3, false, 200)), Err(n(3, false, 200)));
+
+        // Updates replace existing entries
+        t.update(n(2, false, 300)).unwrap();
+        assert_eq!(t.len(), 2);
+
+        assert_eq!(t.router().map(|r| r.ip.clone()), Some(ll(1)));
+        assert_eq!(t.expire(100), None);
+        assert_eq!(t.expire(101), Some(n(1, true, 100)));
+        assert_eq!(t.router(), None);
+        assert_eq!(t.lookup(&ll(2)), Some(&n(2, false, 300)));
+    }
+
+    /// Host discovers and registers with a border router
+    #[test]
+    fn host_registration() {
+        let addr_r = MacAddress::Short(PanId(1), ShortAddress(1));
+        let addr_h = MacAddress::Short(PanId(1), ShortAddress(2));
+
+        let mac_r = MockMac::new(addr_r);
+        let mac_h = mac_r.link(addr_h);
+
+        let cfg_r = SixLoConfig {
+            nd: NdConfig {
+                router: true,
+                prefix: Some((PREFIX, 64)),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        let mut sixlo_r = SixLo::<_, _, 127>::new(mac_r, addr_r, cfg_r);
+        let mut sixlo_h = SixLo::<_, _, 127>::new(mac_h, addr_h, SixLoConfig::default());
+
+        for t in 0..10 {
+            sixlo_h.tick(t * 10).unwrap();
+            sixlo_r.tick(t * 10).unwrap();
+        }
+
+        // Host learns router and prefix
+        assert_eq!(sixlo_h.prefix(), Some(&(PREFIX, 64)));
+        assert!(sixlo_h.registered());
+        assert_eq!(sixlo_h.neighbours().router().map(|r| r.mac), Some(addr_r));
+
+        // Router caches the registered address
+        let global = sixlo_h.global_addr().unwrap();
+        let n = sixlo_r.neighbours().lookup(&global).unwrap();
+        assert_eq!(n.mac, addr_h);
+        assert_eq!(n.expires, 20 + 60 * 60_000);
+
+        // Destinations resolve via the neighbour cache
+        let mut remote = PREFIX.clone();
+        remote.0[1] = 0x02;
+        assert_eq!(sixlo_h.resolve(&remote), addr_r);
+        assert_eq!(sixlo_r.resolve(&global), addr_h);
+        assert_eq!(
+            sixlo_h.resolve(&ALL_NODES),
+            MacAddress::broadcast(&AddressMode::Short)
+        );
+
+        // Registrations expire without refresh
+        sixlo_r.tick(21 + 60 * 60_000).unwrap();
+        assert_eq!(sixlo_r.neighbours().lookup(&global), None);
+    }
+
+    /// Nodes may be switched to act as a router at runtime
+    #[test]
+    fn set_router() {
+        let addr_r = MacAddress::Short(PanId(1), ShortAddress(1));
+        let addr_h = MacAddress::Short(PanId(1), ShortAddress(2));
+
+        let mac_r = MockMac::new(addr_r);
+        let mac_h = mac_r.link(addr_h);
+
+        let mut sixlo_r = SixLo::<_, _, 127>::new(mac_r, addr_r, SixLoConfig::default());
+        let mut sixlo_h = SixLo::<_, _, 127>::new(mac_h, addr_h, SixLoConfig::default());
+
+        sixlo_r.set_router((PREFIX, 64));
+        assert_eq!(sixlo_r.prefix(), Some(&(PREFIX, 64)));
+
+        for t in 0..10 {
+            sixlo_h.tick(t * 10).unwrap();
+            sixlo_r.tick(t * 10).unwrap();
+        }
+
+        // Host solicits, learns the router and prefix, and registers
+        assert_eq!(sixlo_h.default_router().map(|r| r.mac), Some(addr_r));
+        assert_eq!(sixlo_h.prefix(), Some(&(PREFIX, 64)));
+        assert!(sixlo_h.registered());
+
+        let global = sixlo_h.global_addr().unwrap();
+        assert_eq!(
+            sixlo_r.neighbours().lookup(&global).map(|n| n.mac),
+            Some(addr_h)
+        );
+
+        // Routers do not use a default router
+        assert_eq!(sixlo_r.default_router(), None);
+        assert!(!sixlo_r.registered());
+    }
+
+    /// Contexts installed at the router are advertised to hosts and used for
+    /// stateful compression of global addresses
+    #[test]
+    fn context_compression() {
+        let addr_r = MacAddress::Short(PanId(1), ShortAddress(1));
+        let addr_h = MacAddress::Short(PanId(1), ShortAddress(2));
+
+        let mac_r = MockMac::new(addr_r);
+        let mac_h = mac_r.link(addr_h);
+
+        let mut sixlo_r = SixLo::<_, _, 127>::new(mac_r, addr_r, SixLoConfig::default());
+        let mut sixlo_h = SixLo::<_, _, 127>::new(mac_h, addr_h, SixLoConfig::default());
+
+        let context = Context {
+            cid: 1,
+            prefix: PREFIX,
+            prefix_len: 64,
+            compression: true,
+            expires: None,
+        };
+        sixlo_r.set_router((PREFIX, 64));
+        sixlo_r.contexts_mut().add_context(context.clone()).unwrap();
+
+        for t in 0..10 {
+            sixlo_h.tick(t * 10).unwrap();
+            sixlo_r.tick(t * 10).unwrap();
+        }
+        assert!(sixlo_h.registered());
+
+        // Host learns the context, expiring with the advertised lifetime
+        let learned = sixlo_h.contexts().lookup_by_cid(1).cloned().unwrap();
+        let expires = learned.expires.unwrap();
+        assert!(expires >= 30 * 60_000 && expires <= 100 + 30 * 60_000);
+        assert_eq!(
+            learned,
+            Context {
+                expires: Some(expires),
+                ..context
+            }
+        );
+
+        // Global addresses are compressed using the context
+        let global_h = sixlo_h.global_addr().unwrap();
+        let global_r = sixlo_r.global_addr().unwrap();
+        let ip = IphcHeader::new(global_h.clone(), global_r.clone(), 17, 64);
+        let mut buff = [0u8; 64];
+        let n = ip
+            .encode_ctx(&addr_h, &addr_r, sixlo_h.contexts(), &mut buff)
+            .unwrap();
+        assert_eq!(&buff[..n], &[0x7a, 0xf7, 0x11, 17]);
+
+        // And restored by the router
+        sixlo_h
+            .transmit_udp(100, global_r.clone(), 0xf0b1, 5683, &[0xaa; 16])
+            .unwrap();
+        sixlo_h.tick(100).unwrap();
+        sixlo_r.tick(100).unwrap();
+
+        let mut buff = [0u8; 64];
+        let (n, _info, h) = sixlo_r.receive(100, &mut buff).unwrap().unwrap();
+        assert_eq!(&buff[..n], &[0xaa; 16]);
+        let ip = h.iphc.unwrap();
+        assert_eq!((ip.src, ip.dst), (global_h, global_r));
+
+        // Learned contexts expire
+        sixlo_h.tick(expires + 1).unwrap();
+        assert_eq!(sixlo_h.contexts().len(), 0);
+    }
+}
@@ -1,45 +1,136 @@
 //! 6LoWPAN/IPv6 smoltcp support
+//!
+//! Received datagrams are decompressed into IPv6 frames for smoltcp via an RX staging buffer,
+//! and frames written by smoltcp are staged for compression and transmission on the next
+//! [`SixLo::tick`].
 //
 // https://github.com/rust-iot/rust-lpwan
 // Copyright 2021 Ryan Kurte
 
 use smoltcp::{phy, time::Instant};
 
-use crate::log::info;
+use crate::log::{debug, FmtError};
 
-use super::SixLo;
-use crate::Mac;
+use super::{AddressOps, SixLo, SixLoError, IPV6_MTU};
+use crate::{Mac, Ts};
 
-// TODO: how to implement smolctp device on top of 6lo + 802.15.4?
-impl<'a, M, const MAX_PAYLOAD: usize> phy::Device<'a> for SixLo<M, MAX_PAYLOAD>
+/// Staging buffers for smoltcp device tokens
+pub struct Staging {
+    rx: [u8; IPV6_MTU],
+    tx: [u8; IPV6_MTU],
+    tx_len: Option<usize>,
+}
+
+impl Default for Staging {
+    fn default() -> Self {
+        Self {
+            rx: [0u8; IPV6_MTU],
+            tx: [0u8; IPV6_MTU],
+            tx_len: None,
+        }
+    }
+}
+
+impl<'a, M, A, const MAX_PAYLOAD: usize, const TX_SLOTS: usize, const RX_SLOTS: usize>
+    phy::Device<'a> for SixLo<M, A, MAX_PAYLOAD, TX_SLOTS, RX_SLOTS>
 where
-    M: Mac,
-    <M as Mac>::Error: core::fmt::Debug,
+    M: Mac<A>,
+    A: AddressOps,
+    <M as Mac<A>>::Error: core::fmt::Debug,
 {
     type RxToken = RxToken<'a>;
     type TxToken = TxToken<'a>;
 
     fn receive(&'a mut self) -> Option<(Self::RxToken, Self::TxToken)> {
-        //Some((RxToken(&mut self.rx_buffer[..]), TxToken(&mut self.tx_buffer[..])))
-        None
+        // Hold received datagrams until any staged frame has been sent,
+        // as responses require a TX token
+        if self.staging.tx_len.is_some() {
+            return None;
+        }
+
+        // Fetch the next reassembled datagram
+        let (_addr, header, data) = self.frag.pop()?;
+
+        // Restore the IPv6 (and any compressed UDP) header
+        let n = match header.restore_ipv6(data, &mut self.staging.rx) {
+            Ok(Some(n)) => n,
+            _ => {
+                debug!("Dropping non-IPv6 datagram ({} bytes)", data.len());
+                return None;
+            }
+        };
+
+        let Staging { rx, tx, tx_len } = &mut self.staging;
+
+        Some((
+            RxToken(&mut rx[..n]),
+            TxToken {
+                buff: tx,
+                len: tx_len,
+            },
+        ))
     }
 
     fn transmit(&'a mut self) -> Option<Self::TxToken> {
-        //Some(TxToken(&mut self.tx_buffer[..]))
-        None
+        // Only one frame may be staged at a time
+        if self.staging.tx_len.is_some() {
+            return None;
+        }
+
+        let Staging { tx, tx_len, .. } = &mut self.staging;
+
+        Some(TxToken {
+            buff: tx,
+            len: tx_len,
+        })
     }
 
     fn capabilities(&self) -> phy::DeviceCapabilities {
         let mut caps = phy::DeviceCapabilities::default();
-        // TODO: fix this
-        caps.max_transmission_unit = 1536;
+        // IPv6 packets are exchanged uncompressed, with 6LoWPAN compression
+        // performed by SixLo below the device
+        caps.medium = phy::Medium::Ip;
+        caps.max_transmission_unit = IPV6_MTU;
         caps.max_burst_size = Some(1);
         caps
     }
 }
 
-// TODO: how to interact via tokens? the MAC needs to continue ticking etc. so,
-// maybe this could be buffered?
+impl<M, A, const MAX_PAYLOAD: usize, const TX_SLOTS: usize, const RX_SLOTS: usize>
+    SixLo<M, A, MAX_PAYLOAD, TX_SLOTS, RX_SLOTS>
+where
+    M: Mac<A>,
+    A: AddressOps,
+    <M as Mac<A>>::Error: FmtError,
+{
+    /// Check whether a frame is staged for transmission
+    pub(crate) fn staged(&self) -> bool {
+        self.staging.tx_len.is_some()
+    }
+
+    /// Compress and transmit a frame staged by the smoltcp TX token, if any
+    pub(crate) fn transmit_staged(
+        &mut self,
+        now_ms: Ts,
+    ) -> Result<(), SixLoError<<M as Mac<A>>::Error>> {
+        let n = match self.staging.tx_len.take() {
+            Some(n) => n,
+            None => return Ok(()),
+        };
+
+        // Copy out the frame so the staging buffer can be re-used
+        let mut frame = [0u8; IPV6_MTU];
+        frame[..n].copy_from_slice(&self.staging.tx[..n]);
+
+        // Invalid frames are dropped (with a warning) rather than reported
+        match self.transmit_ipv6_packet(now_ms, &frame[..n]) {
+            Err(SixLoError::InvalidPacket) => Ok(()),
+            r => r,
+        }
+    }
+}
+
+/// Receive token, provides a received IPv6 frame to smoltcp
 pub struct RxToken<'a>(&'a mut [u8]);
 
 impl<'a> phy::RxToken for RxToken<'a> {
@@ -47,23 +138,145 @@ impl<'a> phy::RxToken for RxToken<'a> {
     where
         F: FnOnce(&mut [u8]) -> smoltcp::Result<R>,
     {
-        // TODO: receive packet into buffer
-        let result = f(&mut self.0);
-        info!("rx called");
-        result
+        f(&mut self.0)
     }
 }
 
-pub struct TxToken<'a>(&'a mut [u8]);
+/// Transmit token, captures an IPv6 frame from smoltcp for transmission
+pub struct TxToken<'a> {
+    buff: &'a mut [u8; IPV6_MTU],
+    len: &'a mut Option<usize>,
+}
 
 impl<'a> phy::TxToken for TxToken<'a> {
     fn consume<R, F>(self, _timestamp: Instant, len: usize, f: F) -> smoltcp::Result<R>
     where
         F: FnOnce(&mut [u8]) -> smoltcp::Result<R>,
     {
-        let result = f(&mut self.0[..len]);
-        info!("tx called {}", len);
-        // TODO: send packet out
+        if len > self.buff.len() {
+            return Err(smoltcp::Error::Truncated);
+        }
+
+        let result = f(&mut self.buff[..len]);
+
+        // Stage frame for transmission
+        if result.is_ok() {
+            *self.len = Some(len);
+        }
+
         result
     }
 }
+
+#[cfg(test)]
+mod test {
+    use ieee802154::mac::{Address as MacAddress, PanId, ShortAddress};
+    use smoltcp::iface::{InterfaceBuilder, SocketStorage};
+    use smoltcp::phy::ChecksumCapabilities;
+    use smoltcp::socket::{IcmpEndpoint, IcmpPacketMetadata, IcmpSocket, IcmpSocketBuffer};
+    use smoltcp::wire::{Icmpv6Packet, Icmpv6Repr, IpAddress, IpCidr, Ipv6Address};
+
+    use super::*;
+    use crate::sixlo::{headers::V6Addr, mock::MockMac, SixLoConfig};
+
+    /// Exchange an ICMPv6 echo between two smoltcp interfaces via SixLo
+    #[test]
+    fn icmpv6_echo() {
+        let addr_a = MacAddress::Short(PanId(1), ShortAddress(1));
+        let addr_b = MacAddress::Short(PanId(1), ShortAddress(2));
+
+        let mac_a = MockMac::new(addr_a);
+        let mac_b = mac_a.link(addr_b);
+
+        let sixlo_a = SixLo::<_, _, 127>::new(mac_a, addr_a, SixLoConfig::default());
+        let sixlo_b = SixLo::<_, _, 127>::new(mac_b, addr_b, SixLoConfig::default());
+
+        // Use link-local addresses derived from MAC addresses
+        let ip_a = Ipv6Address::from_bytes(&V6Addr::link_local(&addr_a).0);
+        let ip_b = Ipv6Address::from_bytes(&V6Addr::link_local(&addr_b).0);
+
+        let mut ip_addrs_a = [IpCidr::new(IpAddress::Ipv6(ip_a), 64)];
+        let mut socket_storage_a = [SocketStorage::EMPTY];
+        let mut iface_a = InterfaceBuilder::new(sixlo_a, &mut socket_storage_a[..])
+            .ip_addrs(&mut ip_addrs_a[..])
+            .finalize();
+
+        let mut ip_addrs_b = [IpCidr::new(IpAddress::Ipv6(ip_b), 64)];
+        let mut socket_storage_b = [SocketStorage::EMPTY];
+        let mut iface_b = InterfaceBuilder::new(sixlo_b, &mut socket_storage_b[..])
+            .ip_addrs(&mut ip_addrs_b[..])
+            .finalize();
+
+        // Setup ICMP socket on A
+        let mut rx_meta = [IcmpPacketMetadata::EMPTY];
+        let mut rx_data = [0u8; 256];
+        let mut tx_meta = [IcmpPacketMetadata::EMPTY];
+        let mut tx_data = [0u8; 256];
+        let mut socket = IcmpSocket::new(
+            IcmpSocketBuffer::new(&mut rx_meta[..], &mut rx_data[..]),
+            IcmpSocketBuffer::new(&mut tx_meta[..], &mut tx_data[..]),
+        );
+        socket.bind(IcmpEndpoint::Ident(0x22b)).unwrap();
+        let handle = iface_a.add_socket(socket);
+
+        // Queue echo request from A to B
+        let echo = Icmpv6Repr::EchoRequest {
+            ident: 0x22b,
+            seq_no: 1,
+            data: b"ping",
+        };
+        {
+            let socket = iface_a.get_socket::<IcmpSocket>(handle);
+            let payload = socket
+                .send(echo.buffer_len(), IpAddress::Ipv6(ip_b))
+                .unwrap();
+            let mut packet = Icmpv6Packet::new_unchecked(payload);
+            echo.emit(
+                &IpAddress::Ipv6(ip_a),
+                &IpAddress::Ipv6(ip_b),
+                &mut packet,
+                &ChecksumCapabilities::default(),
+            );
+        }
+
+        // Run both stacks until the echo reply is received
+        let mut replied = false;
+        for t in 0..20u64 {
+            let now = Instant::from_millis(t as i64);
+
+            let _ = iface_a.poll(now);
+            iface_a.device_mut().tick(t).unwrap();
+            iface_b.device_mut().tick(t).unwrap();
+            let _ = iface_b.poll(now);
+
+            let socket = iface_a.get_socket::<IcmpSocket>(handle);
+            if socket.can_recv() {
+                let (data, addr) = socket.recv().unwrap();
+                assert_eq!(addr, IpAddress::Ipv6(ip_b));
+
+                let packet = Icmpv6Packet::new_checked(data).unwrap();
+                let repr = Icmpv6Repr::parse(
+                    &IpAddress::Ipv6(ip_b),
+                    &IpAddress::Ipv6(ip_a),
+                    &packet,
+                    &ChecksumCapabilities::default(),
+                )
+                .unwrap();
+
+                assert_eq!(
+                    repr,
+                    Icmpv6Repr::EchoReply {
+                        ident: 0x22b,
+                        seq_no: 1,
+                        data: b"ping",
+                    }
+                );
+
+                replied = true;
+                break;
+            }
+        }
+
+        assert!(replied);
+    }
+}
@@ -0,0 +1,286 @@
+//! 6LoWPAN/IPv6 TUN bridge
+//!
+//! Forwards datagrams between a 6LoWPAN network and the host IPv6 stack via a TUN
+//! interface, for use as a border router. Received datagrams are restored to IPv6
+//! packets (from IPHC compressed or uncompressed IPv6 dispatches) for the host, and
+//! packets from the host are compressed and fragmented for transmission as required.
+//!
+//! TUN I/O is abstracted via the [`Tun`] trait, implemented for [`tun_tap::Iface`],
+//! see [`open`] to create an interface.
+//
+// https://github.com/rust-iot/rust-lpwan
+// Copyright 2021 Ryan Kurte
+
+use std::io;
+
+use crate::log::{debug, warn, FmtError};
+use crate::{Mac, Ts};
+
+use super::{AddressOps, SixLo, SixLoError};
+
+/// TUN packet buffer length, exceeding typical host MTUs so packets larger than the
+/// [`IPV6_MTU`](super::IPV6_MTU) are rejected rather than truncated on read
+pub const TUN_BUFF_LEN: usize = 2048;
+
+/// Interface for exchanging raw IPv6 packets with the host network stack
+pub trait Tun {
+    type Error;
+
+    /// Read a packet from the host without blocking,
+    /// returning the packet length or `None` where no packet is available
+    fn recv(&mut self, buff: &mut [u8]) -> Result<Option<usize>, Self::Error>;
+
+    /// Write a packet to the host
+    fn send(&mut self, packet: &[u8]) -> Result<(), Self::Error>;
+}
+
+impl Tun for tun_tap::Iface {
+    type Error = io::Error;
+
+    fn recv(&mut self, buff: &mut [u8]) -> Result<Option<usize>, Self::Error> {
+        match tun_tap::Iface::recv(self, buff) {
+            Ok(n) => Ok(Some(n)),
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    fn send(&mut self, packet: &[u8]) -> Result<(), Self::Error> {
+        tun_tap::Iface::send(self, packet).map(|_| ())
+    }
+}
+
+/// Open a non-blocking TUN interface (without packet information headers)
+/// for use with [`TunBridge`]
+pub fn open(name: &str) -> Result<tun_tap::Iface, io::Error> {
+    let iface = tun_tap::Iface::without_packet_info(name, tun_tap::Mode::Tun)?;
+    iface.set_non_blocking()?;
+    Ok(iface)
+}
+
+/// TUN bridge errors
+#[derive(Debug, PartialEq)]
+pub enum TunError<T, M> {
+    /// TUN interface error
+    Tun(T),
+    /// 6LoWPAN stack error
+    SixLo(SixLoError<M>),
+}
+
+/// TUN bridge statistics
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct TunStats {
+    /// Packets forwarded to the host
+    pub to_host: u32,
+    /// Packets forwarded from the host to the network
+    pub from_host: u32,
+    /// Packets dropped as oversized, malformed, or without buffer space
+    pub dropped: u32,
+}
+
+/// Bridge between a 6LoWPAN stack and a TUN interface
+pub struct TunBridge<T> {
+    tun: T,
+    buff: [u8; TUN_BUFF_LEN],
+    stats: TunStats,
+}
+
+impl<T: Tun> TunBridge<T> {
+    /// Create a new bridge using the provided TUN interface
+    pub fn new(tun: T) -> Self {
+        Self {
+            tun,
+            buff: [0u8; TUN_BUFF_LEN],
+            stats: TunStats::default(),
+        }
+    }
+
+    /// Fetch the TUN interface
+    pub fn tun(&self) -> &T {
+        &self.tun
+    }
+
+    /// Fetch the TUN interface mutably
+    pub fn tun_mut(&mut self) -> &mut T {
+        &mut self.tun
+    }
+
+    /// Fetch bridge statistics
+    pub fn stats(&self) -> &TunStats {
+        &self.stats
+    }
+
+    /// Forward datagrams received by the stack to the host, and packets from the host
+    /// to the network. Packets that cannot be forwarded are dropped with a warning,
+    /// this should be called following each [`SixLo::tick`]
+    pub fn poll<M, A, const MAX_PAYLOAD: usize, const TX_SLOTS: usize, const RX_SLOTS: usize>(
+        &mut self,
+        now_ms: Ts,
+        sixlo: &mut SixLo<M, A, MAX_PAYLOAD, TX_SLOTS, RX_SLOTS>,
+    ) -> Result<(), TunError<T::Error, <M as Mac<A>>::Error>>
+    where
+        M: Mac<A>,
+        A: AddressOps,
+        <M as Mac<A>>::Error: FmtError,
+    {
+        // Forward received datagrams to the host
+        loop {
+            match sixlo.receive_ipv6_packet(now_ms, &mut self.buff) {
+                Ok(Some((n, info))) => {
+                    debug!("Forwarding {} byte packet from {:?}", n, info.source);
+                    self.tun.send(&self.buff[..n]).map_err(TunError::Tun)?;
+                    self.stats.to_host = self.stats.to_host.saturating_add(1);
+                }
+                Ok(None) => break,
+                Err(SixLoError::Buffer(_)) => {
+                    self.stats.dropped = self.stats.dropped.saturating_add(1);
+                }
+                Err(e) => return Err(TunError::SixLo(e)),
+            }
+        }
+
+        // Forward packets from the host to the network
+        while let Some(n) = self.tun.recv(&mut self.buff).map_err(TunError::Tun)? {
+            match sixlo.transmit_ipv6_packet(now_ms, &self.buff[..n]) {
+                Ok(()) => {
+                    self.stats.from_host = self.stats.from_host.saturating_add(1);
+                }
+                // Oversized and malformed packets are logged by the stack
+                Err(SixLoError::Buffer(_)) | Err(SixLoError::InvalidPacket) => {
+                    self.stats.dropped = self.stats.dropped.saturating_add(1);
+                }
+                Err(SixLoError::NoTxSlots) => {
+                    warn!("No fragmentation buffers, dropped {} byte packet", n);
+                    self.stats.dropped = self.stats.dropped.saturating_add(1);
+                }
+                Err(e) => return Err(TunError::SixLo(e)),
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::VecDeque;
+    use std::vec::Vec;
+
+    use ieee802154::mac::{Address as MacAddress, PanId, ShortAddress};
+
+    use super::*;
+    use crate::sixlo::headers::{IphcHeader, UdpNhcHeader, V6Addr, IP_PROTO_UDP};
+    use crate::sixlo::{mock::MockMac, nd::NdConfig, SixLoConfig, IPV6_MTU};
+
+    /// Mock TUN interface, queueing packets from and to the host
+    #[derive(Debug, Default)]
+    struct MockTun {
+        from_host: VecDeque<Vec<u8>>,
+        to_host: Vec<Vec<u8>>,
+    }
+
+    impl Tun for MockTun {
+        type Error = ();
+
+        fn recv(&mut self, buff: &mut [u8]) -> Result<Option<usize>, Self::Error> {
+            let p = match self.from_host.pop_front() {
+                Some(p) => p,
+                None => return Ok(None),
+            };
+
+            let n = p.len().min(buff.len());
+            buff[..n].copy_from_slice(&p[..n]);
+            Ok(Some(n))
+        }
+
+        fn send(&mut self, packet: &[u8]) -> Result<(), Self::Error> {
+            self.to_host.push(packet.to_vec());
+            Ok(())
+        }
+    }
+
+    /// Build an uncompressed IPv6 UDP packet
+    fn udp_packet(src: &V6Addr, dst: &V6Addr, data: &[u8]) -> Vec<u8> {
+        let ip = IphcHeader::new(src.clone(), dst.clone(), IP_PROTO_UDP, 64);
+        let mut udp = UdpNhcHeader::new(0xf0b1, 5683);
+        udp.checksum = Some(udp.compute_checksum(src, dst, data));
+
+        let mut buff = [0u8; TUN_BUFF_LEN];
+        let mut n = ip.encode_ipv6(8 + data.len(), &mut buff);
+        n += udp.encode_udp(data.len(), &mut buff[n..]);
+        buff[n..n + data.len()].copy_from_slice(data);
+
+        buff[..n + data.len()].to_vec()
+    }
+
+    /// Packets are bridged between host interfaces via two in-process stacks
+    #[test]
+    fn tun_bridge() {
+        let addr_a = MacAddress::Short(PanId(1), ShortAddress(1));
+        let addr_b = MacAddress::Short(PanId(1), ShortAddress(2));
+
+        let mac_a = MockMac::new(addr_a);
+        let mac_b = mac_a.link(addr_b);
+
+        // Routers, so no solicitations are scheduled
+        let cfg = SixLoConfig {
+            nd: NdConfig {
+                router: true,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let mut sixlo_a = SixLo::<_, _, 127>::new(mac_a, addr_a, cfg.clone());
+        let mut sixlo_b = SixLo::<_, _, 127>::new(mac_b, addr_b, cfg);
+
+        let mut bridge_a = TunBridge::new(MockTun::default());
+        let mut bridge_b = TunBridge::new(MockTun::default());
+
+        let (ip_a, ip_b) = (sixlo_a.link_local_addr(), sixlo_b.link_local_addr());
+
+        // Short packets are sent unfragmented, larger packets are fragmented
+        let short = udp_packet(&ip_a, &ip_b, &[0xab; 16]);
+        let long_data: Vec<u8> = (0..200).map(|v| v as u8).collect();
+        let long = udp_packet(&ip_a, &ip_b, &long_data);
+
+        for packet in [&short, &long].iter() {
+            bridge_a.tun_mut().from_host.push_back(packet.to_vec());
+
+            for t in 0..20 {
+                bridge_a.poll(t, &mut sixlo_a).unwrap();
+                sixlo_a.tick(t).unwrap();
+                sixlo_b.tick(t).unwrap();
+                bridge_b.poll(t, &mut sixlo_b).unwrap();
+
+                if !bridge_b.tun().to_host.is_empty() {
+                    break;
+                }
+            }
+
+            // Packets are restored unchanged for the host
+            assert_eq!(bridge_b.tun_mut().to_host.pop().as_ref(), Some(*packet));
+        }
+        assert_eq!(bridge_a.stats().from_host, 2);
+        assert_eq!(bridge_b.stats().to_host, 2);
+
+        // Uncompressed IPv6 dispatches are also forwarded to the host
+        let mut uncompressed = std::vec![0x41];
+        uncompressed.extend_from_slice(&short);
+        sixlo_a.transmit(20, addr_b, &uncompressed).unwrap();
+
+        sixlo_a.tick(20).unwrap();
+        sixlo_b.tick(20).unwrap();
+        bridge_b.poll(20, &mut sixlo_b).unwrap();
+
+        assert_eq!(bridge_b.tun().to_host, &[short.clone()]);
+
+        // Packets exceeding the MTU or malformed are dropped
+        let oversized = udp_packet(&ip_a, &ip_b, &[0xcd; IPV6_MTU]);
+        bridge_a.tun_mut().from_host.push_back(oversized);
+        bridge_a.tun_mut().from_host.push_back(short[..20].to_vec());
+        bridge_a.poll(30, &mut sixlo_a).unwrap();
+
+        assert_eq!(bridge_a.stats().dropped, 2);
+        assert_eq!(bridge_a.stats().from_host, 2);
+    }
+}